@@ -0,0 +1,95 @@
+package main
+
+import (
+	"bufio"
+	"encoding/json"
+	"fmt"
+	"log"
+	"os"
+	"syscall"
+
+	"image-gen-service/internal/config"
+	"image-gen-service/internal/desktop"
+	"image-gen-service/internal/provider"
+)
+
+// 桌面端经标准输入下发的控制命令，每行一个 JSON：{"id":"1","command":"ping","args":{}}
+// 结果以一行 {"nb_reply":"1","ok":true,"result":{...}} 写回标准输出；不是 JSON 的行直接忽略
+type controlRequest struct {
+	ID      string          `json:"id"`
+	Command string          `json:"command"`
+	Args    json.RawMessage `json:"args"`
+}
+
+func writeReply(id string, result interface{}, err error) {
+	reply := map[string]interface{}{
+		"nb_reply": id,
+		"ok":       err == nil,
+	}
+	if err != nil {
+		reply["error"] = err.Error()
+	} else if result != nil {
+		reply["result"] = result
+	}
+	desktop.WriteLine(reply)
+}
+
+func handleControl(req controlRequest) (interface{}, error) {
+	switch req.Command {
+	case "ping":
+		return map[string]interface{}{
+			"version": Version,
+			"pid":     os.Getpid(),
+		}, nil
+	case "reload_config":
+		if err := config.ReloadConfig(); err != nil {
+			return nil, err
+		}
+		log.Println("配置已按桌面端请求重新加载")
+		return nil, nil
+	case "reload_providers":
+		// 桌面端切换了 API Key（写入 provider_configs）后重建 Provider
+		if err := provider.InitProviders(); err != nil {
+			return nil, err
+		}
+		log.Println("Provider 已按桌面端请求重新加载")
+		return nil, nil
+	case "set_log_level":
+		var args struct {
+			Level string `json:"level"`
+		}
+		if len(req.Args) > 0 {
+			if err := json.Unmarshal(req.Args, &args); err != nil {
+				return nil, fmt.Errorf("invalid args: %v", err)
+			}
+		}
+		level, err := setLogLevel(args.Level)
+		if err != nil {
+			return nil, err
+		}
+		return map[string]interface{}{"level": level}, nil
+	default:
+		return nil, fmt.Errorf("unknown command: %s", req.Command)
+	}
+}
+
+// watchStdin 逐行读取控制命令；标准输入关闭或异常时（桌面端退出）通知退出流程
+func watchStdin(quit chan<- os.Signal) {
+	reader := bufio.NewReader(os.Stdin)
+	for {
+		line, err := reader.ReadBytes('\n')
+		if len(line) > 0 {
+			var req controlRequest
+			if jsonErr := json.Unmarshal(line, &req); jsonErr == nil && req.Command != "" {
+				result, cmdErr := handleControl(req)
+				writeReply(req.ID, result, cmdErr)
+			}
+		}
+		if err != nil {
+			log.Printf("检测到标准输入关闭或异常 (%v)，正在安全退出...", err)
+			// Windows 不支持向自身发送 SIGTERM，直接通知退出流程
+			quit <- syscall.SIGTERM
+			return
+		}
+	}
+}
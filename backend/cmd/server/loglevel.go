@@ -0,0 +1,86 @@
+package main
+
+import (
+	"fmt"
+	"log"
+	"os"
+	"strings"
+	"sync/atomic"
+
+	"github.com/gin-gonic/gin"
+)
+
+// 运行时日志级别：启动时取 NB_LOG_LEVEL，之后可经控制命令 set_log_level 修改，不必重启。
+// 标准库 log 不分级别，这里只决定逐请求的访问日志（info）与调试日志（debug）是否输出
+const (
+	levelError int32 = iota
+	levelWarn
+	levelInfo
+	levelDebug
+)
+
+var logLevelNames = []string{"error", "warn", "info", "debug"}
+
+var logLevel atomic.Int32
+
+func parseLogLevel(value string) (int32, error) {
+	value = strings.ToLower(strings.TrimSpace(value))
+	switch value {
+	case "warning":
+		value = "warn"
+	case "trace":
+		value = "debug"
+	}
+	for i, name := range logLevelNames {
+		if name == value {
+			return int32(i), nil
+		}
+	}
+	return 0, fmt.Errorf("unknown log level: %s", value)
+}
+
+func initLogLevel() {
+	logLevel.Store(levelInfo)
+	value := os.Getenv("NB_LOG_LEVEL")
+	if value == "" {
+		return
+	}
+	level, err := parseLogLevel(value)
+	if err != nil {
+		log.Printf("忽略非法的 NB_LOG_LEVEL: %s", value)
+		return
+	}
+	logLevel.Store(level)
+}
+
+func setLogLevel(value string) (string, error) {
+	level, err := parseLogLevel(value)
+	if err != nil {
+		return "", err
+	}
+	logLevel.Store(level)
+	log.Printf("日志级别已调整为 %s", logLevelNames[level])
+	return logLevelNames[level], nil
+}
+
+func logEnabled(level int32) bool {
+	return logLevel.Load() >= level
+}
+
+func debugf(format string, args ...interface{}) {
+	if logEnabled(levelDebug) {
+		log.Printf("[DEBUG] "+format, args...)
+	}
+}
+
+// accessLogger 只在 info 及以上级别记录 gin 的访问日志
+func accessLogger() gin.HandlerFunc {
+	logger := gin.Logger()
+	return func(c *gin.Context) {
+		if logEnabled(levelInfo) {
+			logger(c)
+			return
+		}
+		c.Next()
+	}
+}
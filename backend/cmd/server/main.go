@@ -2,6 +2,7 @@ package main
 
 import (
 	"context"
+	"encoding/json"
 	"fmt"
 	"log"
 	"net"
@@ -23,7 +24,80 @@ import (
 	"github.com/gin-gonic/gin"
 )
 
+// Version 构建时通过 -ldflags "-X main.Version=x.y.z" 注入
+var Version = "dev"
+
+// handshakeProtocol 握手格式的版本号，字段有不兼容变化时递增
+const handshakeProtocol = 1
+
+// printHandshake 向桌面端宣布监听地址：单独一行 JSON，桌面端整行解析并校验，
+// 不会因为与其他输出交织而误读；随后仍输出旧的 SERVER_PORT/SERVER_SOCKET 行，兼容旧版桌面端
+func printHandshake(port int, socket string, workDir string) {
+	dataDir, err := os.Getwd()
+	if err != nil {
+		dataDir = workDir
+	}
+	line, err := json.Marshal(map[string]interface{}{
+		"nb_handshake": handshakeProtocol,
+		"port":         port,
+		"socket":       socket,
+		"version":      Version,
+		"pid":          os.Getpid(),
+		"data_dir":     dataDir,
+	})
+	if err != nil {
+		return
+	}
+	fmt.Printf("%s\n", line)
+}
+
+// portFromArgs 解析 --port N / --port=N，未指定或非法时返回 0
+func portFromArgs(args []string) int {
+	for i, arg := range args {
+		value := ""
+		if strings.HasPrefix(arg, "--port=") {
+			value = strings.TrimPrefix(arg, "--port=")
+		} else if arg == "--port" && i+1 < len(args) {
+			value = args[i+1]
+		} else {
+			continue
+		}
+		port, err := strconv.Atoi(strings.TrimSpace(value))
+		if err != nil || port <= 0 || port > 65535 {
+			log.Printf("忽略非法的 --port 参数: %s", value)
+			return 0
+		}
+		return port
+	}
+	return 0
+}
+
+// dataDirFromArgs 解析 --data-dir DIR / --data-dir=DIR，未指定时返回空串
+func dataDirFromArgs(args []string) string {
+	for i, arg := range args {
+		if strings.HasPrefix(arg, "--data-dir=") {
+			return strings.TrimSpace(strings.TrimPrefix(arg, "--data-dir="))
+		}
+		if arg == "--data-dir" && i+1 < len(args) {
+			return strings.TrimSpace(args[i+1])
+		}
+	}
+	return ""
+}
+
 func getWorkDir() string {
+	// 桌面端每次启动都会以 --data-dir（同时设置 NB_DATA_DIR）传入与自身一致的数据目录，优先使用，
+	// 不再自行推断；下面的推断只用于单独运行或旧版桌面端
+	for _, dir := range []string{dataDirFromArgs(os.Args[1:]), strings.TrimSpace(os.Getenv("NB_DATA_DIR"))} {
+		if dir == "" {
+			continue
+		}
+		err := os.MkdirAll(dir, 0755)
+		if err == nil {
+			return dir
+		}
+		log.Printf("无法使用数据目录 %s: %v", dir, err)
+	}
 	// 如果是作为 Tauri 边车运行，使用用户目录下的应用支持目录
 	if os.Getenv("TAURI_PLATFORM") != "" || os.Getenv("TAURI_FAMILY") != "" {
 		configDir, err := os.UserConfigDir()
@@ -92,6 +166,7 @@ func getDefaultHost(configuredHost string) string {
 }
 
 func main() {
+	initLogLevel()
 	workDir := getWorkDir()
 	log.Printf("Working directory: %s", workDir)
 	_ = os.Chdir(workDir)
@@ -123,12 +198,13 @@ func main() {
 	provider.InitProviders()
 
 	// 5. 设置路由
-	r := gin.Default()
+	r := gin.New()
+	r.Use(accessLogger(), gin.Recovery())
 
 	// 允许跨域请求
 	r.Use(func(c *gin.Context) {
 		origin := c.Request.Header.Get("Origin")
-		log.Printf("[CORS] Request from Origin: %s, Method: %s, Path: %s", origin, c.Request.Method, c.Request.URL.Path)
+		debugf("[CORS] Request from Origin: %s, Method: %s, Path: %s", origin, c.Request.Method, c.Request.URL.Path)
 
 		if origin != "" {
 			c.Writer.Header().Set("Access-Control-Allow-Origin", origin)
@@ -165,6 +241,10 @@ func main() {
 		v1.POST("/images/export", api.ExportImagesHandler)
 		v1.DELETE("/images/:id", api.DeleteImageHandler)
 		v1.GET("/images/:id/download", api.DownloadImageHandler)
+		v1.POST("/uploads", api.CreateUploadHandler)
+		v1.GET("/uploads/:upload_id", api.GetUploadHandler)
+		v1.PUT("/uploads/:upload_id", api.UploadChunkHandler)
+		v1.DELETE("/uploads/:upload_id", api.DeleteUploadHandler)
 	}
 
 	// 静态资源访问 (将 storage 目录整体暴露，以匹配数据库中的 storage/local/xxx.jpg 路径)
@@ -179,51 +259,73 @@ func main() {
 	if port <= 0 {
 		port = 8080
 	}
+	// 桌面端配置了固定端口时通过 --port 传入：只尝试这一个端口，被占用直接退出，不再向后顺延
+	fixedPort := portFromArgs(os.Args[1:])
+	if fixedPort > 0 {
+		port = fixedPort
+	}
 	// 自动检测运行环境并选择合适的监听地址
 	host := getDefaultHost(config.GlobalConfig.Server.Host)
 	var ln net.Listener
 	var err error
 
-	log.Printf("Starting port discovery from %s:%d...", host, port)
+	if socketPath := strings.TrimSpace(os.Getenv("NB_LISTEN_SOCKET")); socketPath != "" {
+		// 桌面端选择 Unix 域套接字通信：不占 TCP 端口，也不会触发系统防火墙提示
+		// 清理上次异常退出残留的套接字文件
+		_ = os.Remove(socketPath)
+		ln, err = net.Listen("unix", socketPath)
+		if err != nil {
+			log.Fatalf("Fatal: Could not listen on socket %s: %v", socketPath, err)
+		}
+		_ = os.Chmod(socketPath, 0600)
+		log.Printf("Successfully bound to unix socket %s", socketPath)
+		printHandshake(0, socketPath, workDir)
+		fmt.Printf("SERVER_SOCKET=%s\n", socketPath)
+		os.Stdout.Sync()
+	} else {
+		log.Printf("Starting port discovery from %s:%d...", host, port)
 
-	// 尝试从 8080 开始寻找可用端口
-	// 默认绑定到 127.0.0.1 避免 macOS 沙盒拦截 0.0.0.0
-	for i := 0; i < 100; i++ {
-		addr := net.JoinHostPort(host, strconv.Itoa(port+i))
-		ln, err = net.Listen("tcp", addr)
-		if err == nil {
-			port = port + i
-			break
+		// 尝试从 8080 开始寻找可用端口
+		// 默认绑定到 127.0.0.1 避免 macOS 沙盒拦截 0.0.0.0
+		attempts := 100
+		if fixedPort > 0 {
+			attempts = 1
+		}
+		for i := 0; i < attempts; i++ {
+			addr := net.JoinHostPort(host, strconv.Itoa(port+i))
+			ln, err = net.Listen("tcp", addr)
+			if err == nil {
+				port = port + i
+				break
+			}
+			log.Printf("Port %d is busy, trying next...", port+i)
 		}
-		log.Printf("Port %d is busy, trying next...", port+i)
-	}
 
-	if err != nil {
-		log.Fatalf("Fatal: Could not find any available port: %v", err)
-	}
+		if err != nil && fixedPort > 0 {
+			log.Fatalf("Fatal: Fixed port %d is not available: %v", fixedPort, err)
+		}
+		if err != nil {
+			log.Fatalf("Fatal: Could not find any available port: %v", err)
+		}
 
-	log.Printf("Successfully bound to %s:%d", host, port)
+		log.Printf("Successfully bound to %s:%d", host, port)
 
-	// 如果是在 Tauri 边车模式下，将实际监听的端口打印到标准输出，方便前端发现
-	fmt.Printf("SERVER_PORT=%d\n", port)
-	os.Stdout.Sync()
+		// 如果是在 Tauri 边车模式下，将实际监听的端口打印到标准输出，方便前端发现
+		printHandshake(port, "", workDir)
+		fmt.Printf("SERVER_PORT=%d\n", port)
+		os.Stdout.Sync()
+	}
+
+	// 等待中断信号
+	quit := make(chan os.Signal, 1)
+	signal.Notify(quit, syscall.SIGINT, syscall.SIGTERM)
 
 	// 监听标准输入，用于检测父进程是否退出（仅 Tauri 边车模式）
+	// 桌面端退出时会主动关闭标准输入，这里走与信号相同的优雅退出流程
 	// Docker 环境中通过 DISABLE_STDIN_MONITOR 环境变量禁用
+	// 同一通道上还承载桌面端下发的控制命令，见 control.go
 	if os.Getenv("DISABLE_STDIN_MONITOR") == "" {
-		go func() {
-			buf := make([]byte, 1)
-			for {
-				_, err := os.Stdin.Read(buf)
-				if err != nil {
-					log.Printf("检测到标准输入关闭或异常 (%v)，正在安全退出...", err)
-					// 发送退出信号
-					p, _ := os.FindProcess(os.Getpid())
-					p.Signal(syscall.SIGTERM)
-					return
-				}
-			}
-		}()
+		go watchStdin(quit)
 	} else {
 		log.Println("标准输入监听已禁用（Docker/生产模式）")
 	}
@@ -239,9 +341,6 @@ func main() {
 		}
 	}()
 
-	// 等待中断信号
-	quit := make(chan os.Signal, 1)
-	signal.Notify(quit, syscall.SIGINT, syscall.SIGTERM)
 	<-quit
 	log.Println("正在关闭服务...")
 
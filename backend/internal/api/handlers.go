@@ -2,8 +2,10 @@ package api
 
 import (
 	"context"
+	"crypto/sha256"
 	"crypto/tls"
 	"encoding/base64"
+	"encoding/hex"
 	"encoding/json"
 	"errors"
 	"fmt"
@@ -550,6 +552,11 @@ func DeleteImageHandler(c *gin.Context) {
 		if err := storage.GlobalStorage.Delete(fileName); err != nil {
 			fmt.Printf("警告: 删除物理文件失败 %s: %v\n", fileName, err)
 		}
+		// 桌面端可按命名方案把文件放进子目录，Delete 只认文件名，这里按记录中的实际路径再补删一次
+		_ = os.Remove(task.LocalPath)
+		if task.ThumbnailPath != "" {
+			_ = os.Remove(task.ThumbnailPath)
+		}
 	} else {
 		// 兼容旧数据：尝试各种格式
 		for _, ext := range []string{".png", ".jpg", ".gif", ".webp"} {
@@ -596,9 +603,26 @@ func DownloadImageHandler(c *gin.Context) {
 	c.Header("Content-Transfer-Encoding", "binary")
 	c.Header("Content-Disposition", fmt.Sprintf("attachment; filename=%s", fileName))
 	c.Header("Content-Type", "application/octet-stream")
+	// 桌面端边下载边落盘，据此校验写入的内容是否完整
+	if digest, err := fileSHA256(task.LocalPath); err == nil {
+		c.Header("X-Content-SHA256", digest)
+	}
 	c.File(task.LocalPath)
 }
 
+func fileSHA256(path string) (string, error) {
+	f, err := os.Open(path)
+	if err != nil {
+		return "", err
+	}
+	defer f.Close()
+	h := sha256.New()
+	if _, err := io.Copy(h, f); err != nil {
+		return "", err
+	}
+	return hex.EncodeToString(h.Sum(nil)), nil
+}
+
 func getOptimizeSystemPrompt(forceJSON bool) string {
 	if forceJSON {
 		prompt := strings.TrimSpace(config.GlobalConfig.Prompts.OptimizeSystemJSON)
@@ -623,6 +647,7 @@ func callGeminiOptimize(ctx context.Context, cfg *model.ProviderConfig, modelNam
 	httpClient := &http.Client{
 		Timeout: timeout,
 		Transport: &http.Transport{
+			Proxy:               http.ProxyFromEnvironment,
 			DisableKeepAlives:   true,
 			ForceAttemptHTTP2:   false,
 			MaxIdleConns:        0,
@@ -952,6 +977,7 @@ func callGeminiImageToPrompt(ctx context.Context, cfg *model.ProviderConfig, mod
 	httpClient := &http.Client{
 		Timeout: timeout,
 		Transport: &http.Transport{
+			Proxy:               http.ProxyFromEnvironment,
 			DisableKeepAlives:   true,
 			ForceAttemptHTTP2:   false,
 			MaxIdleConns:        0,
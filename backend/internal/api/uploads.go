@@ -0,0 +1,224 @@
+package api
+
+import (
+	"io"
+	"net/http"
+	"os"
+	"path/filepath"
+	"strconv"
+	"strings"
+	"sync"
+	"time"
+
+	"image-gen-service/internal/config"
+
+	"github.com/gin-gonic/gin"
+	"github.com/google/uuid"
+)
+
+// 分片上传：桌面端把大体积参考图分片写入，网络中断后按已接收字节数续传；
+// 完成后得到本地文件路径，图生图请求通过 refPaths 引用，不必整包放进 multipart
+const (
+	uploadChunkSize = 4 * 1024 * 1024
+	maxUploadSize   = 1024 * 1024 * 1024
+	// 超过该时间没有新分片的会话连同文件一起清理
+	uploadTTL = 24 * time.Hour
+)
+
+type uploadSession struct {
+	mu       sync.Mutex
+	ID       string
+	Filename string
+	Size     int64
+	Received int64
+	Path     string
+	Updated  time.Time
+}
+
+var (
+	uploadsMu sync.Mutex
+	uploads   = map[string]*uploadSession{}
+)
+
+type createUploadRequest struct {
+	Filename string `json:"filename"`
+	Size     int64  `json:"size"`
+}
+
+func uploadDir() string {
+	return filepath.Join(config.GlobalConfig.Storage.LocalDir, "uploads")
+}
+
+// 只保留简单的扩展名，文件名本身不参与落盘路径
+func uploadExt(filename string) string {
+	ext := strings.ToLower(filepath.Ext(filepath.Base(filename)))
+	if len(ext) < 2 || len(ext) > 8 {
+		return ""
+	}
+	for _, r := range ext[1:] {
+		if (r < 'a' || r > 'z') && (r < '0' || r > '9') {
+			return ""
+		}
+	}
+	return ext
+}
+
+// 调用方需持有 s.mu
+func uploadStatus(s *uploadSession) gin.H {
+	return gin.H{
+		"upload_id":  s.ID,
+		"filename":   s.Filename,
+		"size":       s.Size,
+		"received":   s.Received,
+		"complete":   s.Received == s.Size,
+		"path":       s.Path,
+		"chunk_size": uploadChunkSize,
+	}
+}
+
+func getUpload(id string) *uploadSession {
+	uploadsMu.Lock()
+	defer uploadsMu.Unlock()
+	return uploads[id]
+}
+
+func cleanupUploads() {
+	uploadsMu.Lock()
+	defer uploadsMu.Unlock()
+	for id, s := range uploads {
+		s.mu.Lock()
+		expired := time.Since(s.Updated) > uploadTTL
+		path := s.Path
+		s.mu.Unlock()
+		if expired {
+			_ = os.Remove(path)
+			delete(uploads, id)
+		}
+	}
+}
+
+// CreateUploadHandler 创建分片上传会话
+func CreateUploadHandler(c *gin.Context) {
+	var req createUploadRequest
+	if err := c.ShouldBindJSON(&req); err != nil {
+		Error(c, http.StatusBadRequest, 400, "参数解析失败")
+		return
+	}
+	if req.Size <= 0 || req.Size > maxUploadSize {
+		Error(c, http.StatusBadRequest, 400, "文件大小无效")
+		return
+	}
+	cleanupUploads()
+
+	dir := uploadDir()
+	if err := os.MkdirAll(dir, 0o755); err != nil {
+		Error(c, http.StatusInternalServerError, 500, "创建上传目录失败")
+		return
+	}
+	id := uuid.New().String()
+	path, err := filepath.Abs(filepath.Join(dir, id+uploadExt(req.Filename)))
+	if err != nil {
+		Error(c, http.StatusInternalServerError, 500, "创建上传文件失败")
+		return
+	}
+	f, err := os.Create(path)
+	if err != nil {
+		Error(c, http.StatusInternalServerError, 500, "创建上传文件失败")
+		return
+	}
+	_ = f.Close()
+
+	s := &uploadSession{
+		ID:       id,
+		Filename: filepath.Base(req.Filename),
+		Size:     req.Size,
+		Path:     path,
+		Updated:  time.Now(),
+	}
+	uploadsMu.Lock()
+	uploads[id] = s
+	uploadsMu.Unlock()
+
+	s.mu.Lock()
+	defer s.mu.Unlock()
+	Success(c, uploadStatus(s))
+}
+
+// GetUploadHandler 查询已接收的字节数，客户端据此断点续传
+func GetUploadHandler(c *gin.Context) {
+	s := getUpload(c.Param("upload_id"))
+	if s == nil {
+		Error(c, http.StatusNotFound, 404, "上传会话不存在")
+		return
+	}
+	s.mu.Lock()
+	defer s.mu.Unlock()
+	Success(c, uploadStatus(s))
+}
+
+// UploadChunkHandler 写入一个分片：offset 必须等于已接收字节数，否则返回 409 与当前进度；
+// 分片中途断开时已写入的部分保留，客户端从 received 继续即可
+func UploadChunkHandler(c *gin.Context) {
+	s := getUpload(c.Param("upload_id"))
+	if s == nil {
+		Error(c, http.StatusNotFound, 404, "上传会话不存在")
+		return
+	}
+	offset, err := strconv.ParseInt(c.Query("offset"), 10, 64)
+	if err != nil || offset < 0 {
+		Error(c, http.StatusBadRequest, 400, "offset 无效")
+		return
+	}
+
+	s.mu.Lock()
+	defer s.mu.Unlock()
+	if offset != s.Received {
+		c.JSON(http.StatusConflict, Response{
+			Code:    409,
+			Message: "offset 与已接收字节数不一致",
+			Data:    uploadStatus(s),
+		})
+		return
+	}
+	remaining := s.Size - s.Received
+	if remaining == 0 {
+		Success(c, uploadStatus(s))
+		return
+	}
+
+	f, err := os.OpenFile(s.Path, os.O_WRONLY, 0o644)
+	if err != nil {
+		Error(c, http.StatusInternalServerError, 500, "打开上传文件失败")
+		return
+	}
+	defer f.Close()
+	if _, err := f.Seek(offset, io.SeekStart); err != nil {
+		Error(c, http.StatusInternalServerError, 500, "写入上传文件失败")
+		return
+	}
+	n, copyErr := io.Copy(f, io.LimitReader(c.Request.Body, min(remaining, uploadChunkSize)))
+	s.Received += n
+	s.Updated = time.Now()
+	if copyErr != nil {
+		Error(c, http.StatusBadRequest, 400, "接收分片中断: "+copyErr.Error())
+		return
+	}
+	Success(c, uploadStatus(s))
+}
+
+// DeleteUploadHandler 放弃上传并删除已写入的文件
+func DeleteUploadHandler(c *gin.Context) {
+	id := c.Param("upload_id")
+	uploadsMu.Lock()
+	s := uploads[id]
+	delete(uploads, id)
+	uploadsMu.Unlock()
+	if s == nil {
+		Error(c, http.StatusNotFound, 404, "上传会话不存在")
+		return
+	}
+	s.mu.Lock()
+	_ = os.Remove(s.Path)
+	s.mu.Unlock()
+	Success(c, gin.H{"upload_id": id})
+}
@@ -1,6 +1,7 @@
 package config
 
 import (
+	"fmt"
 	"log"
 	"strings"
 
@@ -232,3 +233,16 @@ func InitConfig() {
 		log.Fatalf("解析配置失败: %v", err)
 	}
 }
+
+// ReloadConfig 重新读取配置文件，供桌面端的 reload_config 控制命令使用
+func ReloadConfig() error {
+	if err := viper.ReadInConfig(); err != nil {
+		return fmt.Errorf("读取配置失败: %w", err)
+	}
+	var next Config
+	if err := viper.Unmarshal(&next); err != nil {
+		return fmt.Errorf("解析配置失败: %w", err)
+	}
+	GlobalConfig = next
+	return nil
+}
@@ -0,0 +1,41 @@
+package desktop
+
+import (
+	"encoding/json"
+	"fmt"
+	"os"
+	"sync"
+)
+
+// 与桌面端约定的标准输出协议：控制命令回复（nb_reply）与事件（nb_event）各占一行 JSON，
+// 共用一把锁，保证整行写出，不与其他行交织
+var stdoutMu sync.Mutex
+
+// Enabled 仅在作为桌面端 sidecar 运行时为 true
+func Enabled() bool {
+	return os.Getenv("TAURI_PLATFORM") != ""
+}
+
+// WriteLine 把 v 序列化为一行 JSON 写到标准输出
+func WriteLine(v interface{}) {
+	line, err := json.Marshal(v)
+	if err != nil {
+		return
+	}
+	stdoutMu.Lock()
+	defer stdoutMu.Unlock()
+	fmt.Printf("%s\n", line)
+	os.Stdout.Sync()
+}
+
+// Emit 向桌面端发送事件：{"nb_event":"<name>", ...fields}；非桌面端运行时不输出
+func Emit(name string, fields map[string]interface{}) {
+	if !Enabled() {
+		return
+	}
+	event := map[string]interface{}{"nb_event": name}
+	for k, v := range fields {
+		event[k] = v
+	}
+	WriteLine(event)
+}
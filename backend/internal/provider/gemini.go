@@ -35,6 +35,8 @@ func NewGeminiProvider(config *model.ProviderConfig) (*GeminiProvider, error) {
 	httpClient := &http.Client{
 		Timeout: timeout,
 		Transport: &http.Transport{
+			// 遵循 HTTP(S)_PROXY 环境变量（桌面端按 NB_PROXY 注入）
+			Proxy: http.ProxyFromEnvironment,
 			// 禁用连接复用和 HTTP/2
 			DisableKeepAlives:   true,
 			ForceAttemptHTTP2:   false,
@@ -6,9 +6,11 @@ import (
 	"errors"
 	"fmt"
 	"log"
+	"strings"
 	"sync"
 	"time"
 
+	"image-gen-service/internal/desktop"
 	"image-gen-service/internal/model"
 	"image-gen-service/internal/provider"
 	"image-gen-service/internal/storage"
@@ -139,6 +141,14 @@ func (wp *WorkerPool) processTask(task *Task) {
 			}
 			log.Printf("任务 %s 调用 Provider 成功: provider=%s model=%s elapsed=%s images=%d", task.TaskModel.TaskID, task.TaskModel.ProviderName, task.TaskModel.ModelID, elapsed, imageCount)
 		}
+		// 服务退出导致的取消不算这把 Key 的调用
+		if !errors.Is(err, context.Canceled) {
+			desktop.Emit("provider_call", map[string]interface{}{
+				"provider": task.TaskModel.ProviderName,
+				"ok":       err == nil,
+				"quota":    isQuotaError(err),
+			})
+		}
 		done <- generateResult{result: result, err: err}
 	}()
 
@@ -207,6 +217,20 @@ func (wp *WorkerPool) processTask(task *Task) {
 	}
 }
 
+// isQuotaError 判断是否为配额用尽或限流错误，桌面端据此切换到下一把 API Key
+func isQuotaError(err error) bool {
+	if err == nil {
+		return false
+	}
+	msg := strings.ToLower(err.Error())
+	for _, marker := range []string{"429", "resource_exhausted", "quota", "rate limit", "rate_limit"} {
+		if strings.Contains(msg, marker) {
+			return true
+		}
+	}
+	return false
+}
+
 func (wp *WorkerPool) failTask(taskModel *model.Task, err error) {
 	log.Printf("任务 %s 失败: %v", taskModel.TaskID, err)
 	model.DB.Model(taskModel).Updates(map[string]interface{}{
@@ -1,3 +1,74 @@
+use std::fs::File;
+use std::path::PathBuf;
+
+use sha2::{Digest, Sha256};
+
+// 包内 sidecar 的 SHA-256 写进应用（NB_SIDECAR_SHA256），启动时比对，见 sidecar::verify_integrity。
+// macOS 打包时会重新签名 sidecar、改变文件内容，只能由 CI 在签名后通过同名环境变量传入；
+// 其他平台未传入时直接对 bin/server-<target> 计算，算不出来（sidecar 缺失或为空）时 release 构建直接失败，
+// 确实要出不带校验的包（本地试打包等）时设置 NB_SKIP_SIDECAR_HASH=1。
+// macOS 签名发生在编译之后，build.rs 无从得知签名后的摘要，未传入时只给出构建警告、不阻断发布；
+// 摘要为空时运行时跳过校验并记 WARN
+fn sidecar_sha256() -> Option<String> {
+    if let Some(digest) = std::env::var("NB_SIDECAR_SHA256")
+        .ok()
+        .map(|d| d.trim().to_ascii_lowercase())
+        .filter(|d| !d.is_empty())
+    {
+        return Some(digest);
+    }
+    let target = std::env::var("TARGET").ok()?;
+    if target.contains("apple") {
+        return None;
+    }
+    let ext = if target.contains("windows") {
+        ".exe"
+    } else {
+        ""
+    };
+    let path = PathBuf::from("bin").join(format!("server-{}{}", target, ext));
+    println!("cargo:rerun-if-changed={}", path.display());
+    let mut file = File::open(&path).ok()?;
+    let mut hasher = Sha256::new();
+    let size = std::io::copy(&mut file, &mut hasher).ok()?;
+    if size == 0 {
+        return None;
+    }
+    Some(
+        hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect(),
+    )
+}
+
 fn main() {
+    println!("cargo:rerun-if-env-changed=NB_SIDECAR_SHA256");
+    println!("cargo:rerun-if-env-changed=NB_SKIP_SIDECAR_HASH");
+    // 反馈地址与密钥经 option_env! 写入，见 feedback.rs
+    println!("cargo:rerun-if-env-changed=NB_FEEDBACK_URL");
+    println!("cargo:rerun-if-env-changed=NB_FEEDBACK_KEY");
+    let digest = sidecar_sha256();
+    // build.rs 自身的 cfg 不代表目标的 profile，按 Cargo 传入的目标 cfg 判断
+    let release = std::env::var_os("CARGO_CFG_DEBUG_ASSERTIONS").is_none();
+    let skip = std::env::var("NB_SKIP_SIDECAR_HASH").is_ok_and(|v| v.trim() == "1");
+    let apple = std::env::var("TARGET").is_ok_and(|t| t.contains("apple"));
+    if digest.is_none() && release && !skip {
+        if apple {
+            println!(
+                "cargo:warning=NB_SIDECAR_SHA256 not set for macOS: the sidecar integrity check is disabled in this build"
+            );
+        } else {
+            panic!(
+                "sidecar SHA-256 unavailable: bin/server-<target> is missing or empty; \
+                 set NB_SIDECAR_SHA256 or NB_SKIP_SIDECAR_HASH=1 to build without the integrity check"
+            );
+        }
+    }
+    println!(
+        "cargo:rustc-env=NB_SIDECAR_SHA256={}",
+        digest.unwrap_or_default()
+    );
     tauri_build::build()
 }
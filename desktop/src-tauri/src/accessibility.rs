@@ -0,0 +1,296 @@
+use std::thread;
+use std::time::Duration;
+
+use tauri::Emitter;
+
+// 原生无障碍播报：打包后的 WebView 里 aria-live 区域经常不被 VoiceOver/NVDA 读出，
+// 生成完成、出错等关键状态改由系统无障碍接口直接播报
+const MAX_ANNOUNCE_CHARS: usize = 500;
+pub const PREFS_CHANGED_EVENT: &str = "accessibility-prefs-changed";
+const PREFS_POLL_INTERVAL: Duration = Duration::from_secs(3);
+
+// 系统无障碍偏好：WKWebView 不支持 prefers-contrast 等媒体查询，由原生层读取后推给前端。
+// 读不到的项为 None（平台不支持或读取失败）
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize)]
+pub struct AccessibilityPrefs {
+    pub reduce_motion: Option<bool>,
+    pub increase_contrast: Option<bool>,
+    pub reduce_transparency: Option<bool>,
+    pub invert_colors: Option<bool>,
+    pub differentiate_without_color: Option<bool>,
+    // 系统色彩滤镜（Windows 颜色筛选器 / 色盲模式）
+    pub color_filter: Option<bool>,
+}
+
+pub fn accessibility_prefs() -> AccessibilityPrefs {
+    platform::prefs()
+}
+
+// 系统没有跨平台的变更通知可用，后台低频轮询，变化时发事件，前端只需监听
+pub fn spawn_prefs_watcher(app: tauri::AppHandle) {
+    thread::spawn(move || {
+        let mut last = platform::prefs();
+        loop {
+            thread::sleep(PREFS_POLL_INTERVAL);
+            let current = platform::prefs();
+            if current != last {
+                let _ = app.emit(PREFS_CHANGED_EVENT, &current);
+                last = current;
+            }
+        }
+    });
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Priority {
+    Low,
+    Medium,
+    High,
+}
+
+impl Priority {
+    // 同时接受 aria-live 的 polite/assertive 写法
+    pub fn parse(value: Option<&str>) -> Result<Self, String> {
+        match value.map(|v| v.trim().to_lowercase()).as_deref() {
+            None | Some("") | Some("medium") | Some("polite") => Ok(Priority::Medium),
+            Some("low") => Ok(Priority::Low),
+            Some("high") | Some("assertive") => Ok(Priority::High),
+            Some(other) => Err(format!("unknown priority: {}", other)),
+        }
+    }
+}
+
+pub fn normalize_message(message: &str) -> Result<String, String> {
+    let message = message.trim();
+    if message.is_empty() {
+        return Err("announcement is empty".to_string());
+    }
+    Ok(message.chars().take(MAX_ANNOUNCE_CHARS).collect())
+}
+
+// 返回 true 表示已交给系统无障碍服务；不支持的平台返回 false，前端继续走 live region
+pub fn announce(
+    window: &tauri::WebviewWindow,
+    message: &str,
+    priority: Priority,
+) -> Result<bool, String> {
+    let message = normalize_message(message)?;
+    platform::announce(window, message, priority)
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::Priority;
+    use objc2::runtime::AnyObject;
+    use objc2::MainThreadMarker;
+    use objc2_app_kit::{
+        NSAccessibilityAnnouncementKey, NSAccessibilityAnnouncementRequestedNotification,
+        NSAccessibilityPostNotificationWithUserInfo, NSAccessibilityPriorityKey,
+        NSAccessibilityPriorityLevel, NSApplication, NSWorkspace,
+    };
+    use objc2_foundation::{NSDictionary, NSNumber, NSString};
+
+    pub fn prefs() -> super::AccessibilityPrefs {
+        let workspace = NSWorkspace::sharedWorkspace();
+        super::AccessibilityPrefs {
+            reduce_motion: Some(workspace.accessibilityDisplayShouldReduceMotion()),
+            increase_contrast: Some(workspace.accessibilityDisplayShouldIncreaseContrast()),
+            reduce_transparency: Some(workspace.accessibilityDisplayShouldReduceTransparency()),
+            invert_colors: Some(workspace.accessibilityDisplayShouldInvertColors()),
+            differentiate_without_color: Some(
+                workspace.accessibilityDisplayShouldDifferentiateWithoutColor(),
+            ),
+            // macOS 的颜色滤镜没有公开 API
+            color_filter: None,
+        }
+    }
+
+    pub fn announce(
+        window: &tauri::WebviewWindow,
+        message: String,
+        priority: Priority,
+    ) -> Result<bool, String> {
+        let level = match priority {
+            Priority::Low => NSAccessibilityPriorityLevel::Low,
+            Priority::Medium => NSAccessibilityPriorityLevel::Medium,
+            Priority::High => NSAccessibilityPriorityLevel::High,
+        };
+        // AppKit 只能在主线程调用
+        window
+            .run_on_main_thread(move || {
+                let Some(mtm) = MainThreadMarker::new() else {
+                    return;
+                };
+                let app = NSApplication::sharedApplication(mtm);
+                let text = NSString::from_str(&message);
+                let level = NSNumber::new_isize(level.0);
+                let values: [&AnyObject; 2] = [&text, &level];
+                unsafe {
+                    let info = NSDictionary::from_slices(
+                        &[NSAccessibilityAnnouncementKey, NSAccessibilityPriorityKey],
+                        &values,
+                    );
+                    NSAccessibilityPostNotificationWithUserInfo(
+                        &app,
+                        NSAccessibilityAnnouncementRequestedNotification,
+                        Some(&info),
+                    );
+                }
+            })
+            .map_err(|e| format!("announce failed: {}", e))?;
+        Ok(true)
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use super::Priority;
+    use windows::core::{BOOL, BSTR, PCWSTR};
+    use windows::Win32::System::Registry::{RegGetValueW, HKEY_CURRENT_USER, RRF_RT_REG_DWORD};
+    use windows::Win32::UI::Accessibility::{
+        NotificationKind_Other, NotificationProcessing_ImportantAll,
+        NotificationProcessing_ImportantMostRecent, NotificationProcessing_MostRecent,
+        UiaClientsAreListening, UiaHostProviderFromHwnd, UiaRaiseNotificationEvent,
+        HCF_HIGHCONTRASTON, HIGHCONTRASTW,
+    };
+    use windows::Win32::UI::WindowsAndMessaging::{
+        SystemParametersInfoW, SPI_GETCLIENTAREAANIMATION, SPI_GETHIGHCONTRAST,
+        SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS,
+    };
+
+    fn wide(value: &str) -> Vec<u16> {
+        value.encode_utf16().chain(std::iter::once(0)).collect()
+    }
+
+    fn read_dword(subkey: &str, value: &str) -> Option<u32> {
+        let subkey = wide(subkey);
+        let value = wide(value);
+        let mut data: u32 = 0;
+        let mut size = std::mem::size_of::<u32>() as u32;
+        let status = unsafe {
+            RegGetValueW(
+                HKEY_CURRENT_USER,
+                PCWSTR(subkey.as_ptr()),
+                PCWSTR(value.as_ptr()),
+                RRF_RT_REG_DWORD,
+                None,
+                Some(&mut data as *mut u32 as *mut _),
+                Some(&mut size),
+            )
+        };
+        status.is_ok().then_some(data)
+    }
+
+    pub fn prefs() -> super::AccessibilityPrefs {
+        let mut animation = BOOL(1);
+        let reduce_motion = unsafe {
+            SystemParametersInfoW(
+                SPI_GETCLIENTAREAANIMATION,
+                0,
+                Some(&mut animation as *mut BOOL as *mut _),
+                SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS(0),
+            )
+        }
+        .ok()
+        .map(|_| !animation.as_bool());
+
+        let mut contrast = HIGHCONTRASTW {
+            cbSize: std::mem::size_of::<HIGHCONTRASTW>() as u32,
+            ..Default::default()
+        };
+        let increase_contrast = unsafe {
+            SystemParametersInfoW(
+                SPI_GETHIGHCONTRAST,
+                contrast.cbSize,
+                Some(&mut contrast as *mut HIGHCONTRASTW as *mut _),
+                SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS(0),
+            )
+        }
+        .ok()
+        .map(|_| contrast.dwFlags.contains(HCF_HIGHCONTRASTON));
+
+        super::AccessibilityPrefs {
+            reduce_motion,
+            increase_contrast,
+            reduce_transparency: read_dword(
+                "Software\\Microsoft\\Windows\\CurrentVersion\\Themes\\Personalize",
+                "EnableTransparency",
+            )
+            .map(|v| v == 0),
+            invert_colors: None,
+            differentiate_without_color: None,
+            color_filter: read_dword("Software\\Microsoft\\ColorFiltering", "Active")
+                .map(|v| v != 0),
+        }
+    }
+
+    pub fn announce(
+        window: &tauri::WebviewWindow,
+        message: String,
+        priority: Priority,
+    ) -> Result<bool, String> {
+        // 没有读屏软件在监听时不必构造 provider
+        if !unsafe { UiaClientsAreListening() }.as_bool() {
+            return Ok(false);
+        }
+        let processing = match priority {
+            Priority::Low => NotificationProcessing_MostRecent,
+            Priority::Medium => NotificationProcessing_ImportantMostRecent,
+            Priority::High => NotificationProcessing_ImportantAll,
+        };
+        let hwnd = window
+            .hwnd()
+            .map_err(|e| format!("announce failed: {}", e))?;
+        unsafe {
+            let provider =
+                UiaHostProviderFromHwnd(hwnd).map_err(|e| format!("announce failed: {}", e))?;
+            UiaRaiseNotificationEvent(
+                &provider,
+                NotificationKind_Other,
+                processing,
+                &BSTR::from(message),
+                &BSTR::from("nano-banana-announce"),
+            )
+            .map_err(|e| format!("announce failed: {}", e))?;
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+mod platform {
+    use super::Priority;
+
+    // GNOME 下通过 gsettings 读取；没有 gsettings 的桌面环境全部返回 None
+    fn gsettings_bool(schema: &str, key: &str) -> Option<bool> {
+        let out = std::process::Command::new("gsettings")
+            .args(["get", schema, key])
+            .output()
+            .ok()?;
+        if !out.status.success() {
+            return None;
+        }
+        match String::from_utf8_lossy(&out.stdout).trim() {
+            "true" => Some(true),
+            "false" => Some(false),
+            _ => None,
+        }
+    }
+
+    pub fn prefs() -> super::AccessibilityPrefs {
+        super::AccessibilityPrefs {
+            reduce_motion: gsettings_bool("org.gnome.desktop.interface", "enable-animations")
+                .map(|enabled| !enabled),
+            increase_contrast: gsettings_bool("org.gnome.desktop.a11y.interface", "high-contrast"),
+            ..Default::default()
+        }
+    }
+
+    pub fn announce(
+        _window: &tauri::WebviewWindow,
+        _message: String,
+        _priority: Priority,
+    ) -> Result<bool, String> {
+        Ok(false)
+    }
+}
@@ -0,0 +1,83 @@
+use std::sync::Mutex;
+
+use tauri::Manager;
+
+// App Nap：窗口被遮挡时 macOS 会让应用打盹，定时器与事件循环被大幅推迟，批量生成跑到一半
+// sidecar 的输出没人处理、任务跟着卡住。有生成任务或原生长任务在进行时用 NSProcessInfo 的
+// activity 声明"用户发起的工作"阻止打盹（仍允许系统空闲睡眠），全部结束后结束 activity 交还系统。
+// 其他平台没有对应机制，什么都不做
+const REASON: &str = "Generating images";
+
+#[derive(Default)]
+pub struct AppNap {
+    activity: Mutex<Option<platform::Activity>>,
+}
+
+pub struct AppNapState(pub AppNap);
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use objc2::rc::Retained;
+    use objc2::runtime::{NSObjectProtocol, ProtocolObject};
+    use objc2_foundation::{NSActivityOptions, NSProcessInfo, NSString};
+
+    pub struct Activity(Retained<ProtocolObject<dyn NSObjectProtocol>>);
+
+    // activity 只是一个不透明的令牌，NSProcessInfo 可在任意线程上结束它
+    unsafe impl Send for Activity {}
+
+    pub fn begin(reason: &str) -> Option<Activity> {
+        let token = NSProcessInfo::processInfo().beginActivityWithOptions_reason(
+            NSActivityOptions::UserInitiatedAllowingIdleSystemSleep,
+            &NSString::from_str(reason),
+        );
+        Some(Activity(token))
+    }
+
+    pub fn end(activity: Activity) {
+        unsafe { NSProcessInfo::processInfo().endActivity(&activity.0) };
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+mod platform {
+    pub type Activity = ();
+
+    pub fn begin(_reason: &str) -> Option<Activity> {
+        None
+    }
+
+    pub fn end(_activity: Activity) {}
+}
+
+fn busy(app: &tauri::AppHandle) -> bool {
+    let generating = app
+        .state::<crate::GenerationState>()
+        .0
+        .lock()
+        .map(|s| *s)
+        .unwrap_or(false);
+    generating || app.state::<crate::progress::JobsState>().0.active() > 0
+}
+
+// 任务开始或结束后调用，按当前是否有任务在跑开始或结束 activity
+pub fn refresh(app: &tauri::AppHandle) {
+    let busy = busy(app);
+    let state = app.state::<AppNapState>();
+    let Ok(mut activity) = state.0.activity.lock() else {
+        return;
+    };
+    if busy == activity.is_some() {
+        return;
+    }
+    let log = app.state::<crate::LogState>();
+    if busy {
+        *activity = platform::begin(REASON);
+        if activity.is_some() {
+            log.log_app("INFO", "App Nap disabled while jobs are running");
+        }
+    } else if let Some(token) = activity.take() {
+        platform::end(token);
+        log.log_app("INFO", "App Nap allowed, no jobs running");
+    }
+}
@@ -0,0 +1,25 @@
+use std::io::{Read, Seek};
+
+// 读取 zip 中的单个条目，超过 limit 字节即失败。先看目录里声明的大小，
+// 再按 limit + 1 截断实际读取，防止声明大小被篡改的压缩炸弹
+pub fn read_entry<R: Read + Seek>(
+    archive: &mut zip::ZipArchive<R>,
+    name: &str,
+    limit: u64,
+) -> Result<Vec<u8>, String> {
+    let entry = archive
+        .by_name(name)
+        .map_err(|e| format!("missing entry {}: {}", name, e))?;
+    if entry.size() > limit {
+        return Err(format!("entry {} too large ({} bytes)", name, entry.size()));
+    }
+    let mut bytes = Vec::with_capacity(entry.size() as usize);
+    entry
+        .take(limit + 1)
+        .read_to_end(&mut bytes)
+        .map_err(|e| format!("read entry {} failed: {}", name, e))?;
+    if bytes.len() as u64 > limit {
+        return Err(format!("entry {} too large", name));
+    }
+    Ok(bytes)
+}
@@ -0,0 +1,403 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tauri::http::{Request, Response, StatusCode};
+use tauri::{AppHandle, Manager, Runtime, UriSchemeContext, UriSchemeResponder};
+
+// socket 传输模式：后端监听 Unix 域套接字而不是 TCP 端口（没有端口冲突，也不会弹防火墙提示），
+// WebView 访问 nbapi://localhost/api/v1/...，由这里转发到套接字上。
+// 自定义协议只能整包返回响应，SSE 接口直接拒绝，前端会自动回退到轮询
+pub const SCHEME: &str = "nbapi";
+const MAX_RESPONSE_BYTES: u64 = 512 * 1024 * 1024;
+const IO_TIMEOUT: Duration = Duration::from_secs(300);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+// sockaddr_un 的路径长度上限约 104（macOS）/ 108 字节
+const MAX_SOCKET_PATH: usize = 100;
+
+#[derive(Default)]
+pub struct BackendSocket(pub Arc<Mutex<Option<PathBuf>>>);
+
+pub fn socket_path(base: &Path) -> PathBuf {
+    let preferred = base.join("backend.sock");
+    if preferred.as_os_str().len() <= MAX_SOCKET_PATH {
+        return preferred;
+    }
+    std::env::temp_dir().join(format!("nb-backend-{}.sock", std::process::id()))
+}
+
+// Windows 上 WebView2 的自定义协议需要写成 http://<scheme>.localhost
+pub fn base_url() -> String {
+    if cfg!(windows) {
+        format!("http://{}.localhost", SCHEME)
+    } else {
+        format!("{}://localhost", SCHEME)
+    }
+}
+
+pub fn handle<R: Runtime>(
+    ctx: UriSchemeContext<'_, R>,
+    request: Request<Vec<u8>>,
+    responder: UriSchemeResponder,
+) {
+    let app = ctx.app_handle().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        responder.respond(forward(&app, &request));
+    });
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response<Vec<u8>> {
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "text/plain; charset=utf-8")
+        .header("Access-Control-Allow-Origin", "*")
+        .body(message.as_bytes().to_vec())
+        .unwrap_or_default()
+}
+
+fn forward<R: Runtime>(app: &AppHandle<R>, request: &Request<Vec<u8>>) -> Response<Vec<u8>> {
+    let socket = app
+        .try_state::<BackendSocket>()
+        .and_then(|state| state.0.lock().ok().and_then(|path| path.clone()));
+    let Some(socket) = socket else {
+        return error_response(StatusCode::SERVICE_UNAVAILABLE, "backend not ready");
+    };
+    let wants_stream = request
+        .headers()
+        .get("accept")
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains("text/event-stream"))
+        || request.uri().path().ends_with("/stream");
+    if wants_stream {
+        return error_response(
+            StatusCode::NOT_IMPLEMENTED,
+            "streaming is not available over the socket transport",
+        );
+    }
+    match exchange(&socket, request) {
+        Ok(response) => response,
+        Err(e) => error_response(StatusCode::BAD_GATEWAY, &e),
+    }
+}
+
+// socket 模式下的健康检查，返回 /api/v1/health 的 HTTP 状态码
+pub fn health_status(socket: &Path) -> Result<u16, String> {
+    let request = Request::builder()
+        .method("GET")
+        .uri("/api/v1/health")
+        .body(Vec::new())
+        .map_err(|e| format!("build request failed: {}", e))?;
+    Ok(exchange(socket, &request)?.status().as_u16())
+}
+
+#[cfg(unix)]
+fn connect(path: &Path) -> Result<std::os::unix::net::UnixStream, String> {
+    let stream = std::os::unix::net::UnixStream::connect(path)
+        .map_err(|e| format!("connect backend socket failed: {}", e))?;
+    stream
+        .set_read_timeout(Some(IO_TIMEOUT))
+        .and_then(|_| stream.set_write_timeout(Some(IO_TIMEOUT)))
+        .map_err(|e| format!("connect backend socket failed: {}", e))?;
+    Ok(stream)
+}
+
+#[cfg(windows)]
+fn connect(path: &Path) -> Result<uds_windows::UnixStream, String> {
+    let stream = uds_windows::UnixStream::connect(path)
+        .map_err(|e| format!("connect backend socket failed: {}", e))?;
+    stream
+        .set_read_timeout(Some(IO_TIMEOUT))
+        .and_then(|_| stream.set_write_timeout(Some(IO_TIMEOUT)))
+        .map_err(|e| format!("connect backend socket failed: {}", e))?;
+    Ok(stream)
+}
+
+trait Transport: Read + Write {}
+
+impl<T: Read + Write> Transport for T {}
+
+// 原生层直接访问后端：socket 模式走套接字，否则走 127.0.0.1 上的端口
+fn open<R: Runtime>(app: &AppHandle<R>) -> Result<Box<dyn Transport>, String> {
+    let socket = app
+        .try_state::<BackendSocket>()
+        .and_then(|state| state.0.lock().ok().and_then(|path| path.clone()));
+    if let Some(socket) = socket {
+        return Ok(Box::new(connect(&socket)?));
+    }
+    let port = app
+        .try_state::<crate::BackendPort>()
+        .and_then(|state| state.0.lock().ok().map(|port| *port))
+        .unwrap_or(0);
+    if port == 0 {
+        return Err("backend not ready".to_string());
+    }
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+    let stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)
+        .map_err(|e| format!("connect backend failed: {}", e))?;
+    stream
+        .set_read_timeout(Some(IO_TIMEOUT))
+        .and_then(|_| stream.set_write_timeout(Some(IO_TIMEOUT)))
+        .map_err(|e| format!("connect backend failed: {}", e))?;
+    Ok(Box::new(stream))
+}
+
+// 原生层调用后端接口，整包返回响应
+pub fn call<R: Runtime>(
+    app: &AppHandle<R>,
+    request: &Request<Vec<u8>>,
+) -> Result<Response<Vec<u8>>, String> {
+    send(open(app)?, request)
+}
+
+fn exchange(socket: &Path, request: &Request<Vec<u8>>) -> Result<Response<Vec<u8>>, String> {
+    send(connect(socket)?, request)
+}
+
+fn write_request<S: Write>(stream: &mut S, request: &Request<Vec<u8>>) -> Result<(), String> {
+    let target = request
+        .uri()
+        .path_and_query()
+        .map(|p| p.as_str())
+        .unwrap_or("/");
+    let body = request.body();
+    let mut head = format!(
+        "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: {}\r\n",
+        request.method(),
+        target,
+        body.len()
+    )
+    .into_bytes();
+    for (name, value) in request.headers() {
+        if matches!(
+            name.as_str(),
+            "host" | "connection" | "content-length" | "transfer-encoding" | "keep-alive"
+        ) {
+            continue;
+        }
+        head.extend_from_slice(name.as_str().as_bytes());
+        head.extend_from_slice(b": ");
+        head.extend_from_slice(value.as_bytes());
+        head.extend_from_slice(b"\r\n");
+    }
+    head.extend_from_slice(b"\r\n");
+    stream
+        .write_all(&head)
+        .and_then(|_| stream.write_all(body))
+        .map_err(|e| format!("send to backend failed: {}", e))
+}
+
+// 每个请求一条连接（Connection: close），读到 EOF 即完整响应
+fn send<S: Read + Write>(
+    mut stream: S,
+    request: &Request<Vec<u8>>,
+) -> Result<Response<Vec<u8>>, String> {
+    write_request(&mut stream, request)?;
+
+    let mut raw = Vec::new();
+    stream
+        .take(MAX_RESPONSE_BYTES + 1)
+        .read_to_end(&mut raw)
+        .map_err(|e| format!("read from backend failed: {}", e))?;
+    if raw.len() as u64 > MAX_RESPONSE_BYTES {
+        return Err("backend response too large".to_string());
+    }
+    parse_response(&raw)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn parse_response(raw: &[u8]) -> Result<Response<Vec<u8>>, String> {
+    let split = find(raw, b"\r\n\r\n").ok_or("malformed backend response")?;
+    let head = std::str::from_utf8(&raw[..split]).map_err(|_| "malformed backend response")?;
+    let mut lines = head.split("\r\n");
+    let status: u16 = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse().ok())
+        .ok_or("malformed backend status line")?;
+    let mut builder = Response::builder().status(status);
+    let mut chunked = false;
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let (name, value) = (name.trim(), value.trim());
+        match name.to_ascii_lowercase().as_str() {
+            "transfer-encoding" => chunked = value.to_ascii_lowercase().contains("chunked"),
+            // 长度由 WebView 按实际内容计算
+            "connection" | "keep-alive" | "content-length" => {}
+            _ => builder = builder.header(name, value),
+        }
+    }
+    let body = &raw[split + 4..];
+    let body = if chunked {
+        decode_chunked(body)?
+    } else {
+        body.to_vec()
+    };
+    builder
+        .body(body)
+        .map_err(|e| format!("build response failed: {}", e))
+}
+
+fn decode_chunked(mut data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    loop {
+        let line_end = find(data, b"\r\n").ok_or("truncated chunked body")?;
+        let size_line = std::str::from_utf8(&data[..line_end]).map_err(|_| "bad chunk size")?;
+        let size_hex = size_line.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size_hex, 16).map_err(|_| "bad chunk size")?;
+        data = &data[line_end + 2..];
+        if size == 0 {
+            return Ok(out);
+        }
+        if data.len() < size + 2 {
+            return Err("truncated chunked body".to_string());
+        }
+        out.extend_from_slice(&data[..size]);
+        data = &data[size + 2..];
+    }
+}
+
+const MAX_HEAD_BYTES: usize = 64 * 1024;
+
+pub struct StreamedHead {
+    pub status: u16,
+    // 名称已转为小写
+    pub headers: Vec<(String, String)>,
+}
+
+impl StreamedHead {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+fn read_head<Rd: BufRead>(reader: &mut Rd) -> Result<StreamedHead, String> {
+    let mut lines = Vec::new();
+    let mut total = 0;
+    loop {
+        let mut line = Vec::new();
+        let n = reader
+            .read_until(b'\n', &mut line)
+            .map_err(|e| format!("read from backend failed: {}", e))?;
+        if n == 0 {
+            return Err("malformed backend response".to_string());
+        }
+        total += n;
+        if total > MAX_HEAD_BYTES {
+            return Err("backend response head too large".to_string());
+        }
+        let line = String::from_utf8_lossy(&line).trim_end().to_string();
+        if line.is_empty() {
+            break;
+        }
+        lines.push(line);
+    }
+    let mut lines = lines.into_iter();
+    let status: u16 = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1).and_then(|c| c.parse().ok()))
+        .ok_or("malformed backend status line")?;
+    let headers = lines
+        .filter_map(|line| {
+            line.split_once(':')
+                .map(|(k, v)| (k.trim().to_ascii_lowercase(), v.trim().to_string()))
+        })
+        .collect();
+    Ok(StreamedHead { status, headers })
+}
+
+fn copy_limited<Rd: Read, W: Write>(
+    reader: &mut Rd,
+    sink: &mut W,
+    limit: u64,
+) -> Result<u64, String> {
+    std::io::copy(&mut reader.by_ref().take(limit), sink)
+        .map_err(|e| format!("stream backend response failed: {}", e))
+}
+
+fn copy_chunked<Rd: BufRead, W: Write>(reader: &mut Rd, sink: &mut W) -> Result<u64, String> {
+    let mut total = 0u64;
+    loop {
+        let mut size_line = String::new();
+        reader
+            .read_line(&mut size_line)
+            .map_err(|e| format!("read from backend failed: {}", e))?;
+        let size_hex = size_line.split(';').next().unwrap_or_default().trim();
+        let size = u64::from_str_radix(size_hex, 16).map_err(|_| "bad chunk size")?;
+        if size == 0 {
+            return Ok(total);
+        }
+        total += size;
+        if total > MAX_RESPONSE_BYTES {
+            return Err("backend response too large".to_string());
+        }
+        if copy_limited(reader, sink, size)? < size {
+            return Err("truncated chunked body".to_string());
+        }
+        let mut crlf = [0u8; 2];
+        reader
+            .read_exact(&mut crlf)
+            .map_err(|_| "truncated chunked body")?;
+    }
+}
+
+// 把响应体直接写进 sink，不在内存里整包缓冲（大图片直接落盘）；非 2xx 时读出少量正文作为错误信息
+pub fn stream_to<R: Runtime, W: Write>(
+    app: &AppHandle<R>,
+    request: &Request<Vec<u8>>,
+    sink: &mut W,
+) -> Result<(StreamedHead, u64), String> {
+    let mut stream = open(app)?;
+    write_request(&mut stream, request)?;
+    let mut reader = BufReader::new(stream);
+    let head = read_head(&mut reader)?;
+    if !(200..300).contains(&head.status) {
+        let mut body = Vec::new();
+        let _ = reader.by_ref().take(64 * 1024).read_to_end(&mut body);
+        return Err(format!(
+            "backend returned {}: {}",
+            head.status,
+            String::from_utf8_lossy(&body).trim()
+        ));
+    }
+    let chunked = head
+        .header("transfer-encoding")
+        .is_some_and(|v| v.to_ascii_lowercase().contains("chunked"));
+    let length = head
+        .header("content-length")
+        .and_then(|v| v.parse::<u64>().ok());
+    let written = match (chunked, length) {
+        (true, _) => copy_chunked(&mut reader, sink)?,
+        (false, Some(len)) => {
+            if len > MAX_RESPONSE_BYTES {
+                return Err("backend response too large".to_string());
+            }
+            let n = copy_limited(&mut reader, sink, len)?;
+            if n < len {
+                return Err(format!("backend response truncated at {}/{} bytes", n, len));
+            }
+            n
+        }
+        (false, None) => {
+            let n = copy_limited(&mut reader, sink, MAX_RESPONSE_BYTES + 1)?;
+            if n > MAX_RESPONSE_BYTES {
+                return Err("backend response too large".to_string());
+            }
+            n
+        }
+    };
+    sink.flush()
+        .map_err(|e| format!("stream backend response failed: {}", e))?;
+    Ok((head, written))
+}
@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tauri::Manager;
+
+// 经 sidecar 标准输入下发的控制命令：每行一个 JSON {"id","command","args"}，后端处理后在标准输出写回
+// 一行 {"nb_reply": id, "ok", "result" | "error"}，由 supervisor 的输出循环交给这里按 id 配对。
+// 标准输入同时是退出信号（关闭即请后端退出），所以写入只经由 SidecarState 里的 CommandChild
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_TIMEOUT_MS: u64 = 60_000;
+const MAX_COMMAND_LEN: usize = 64;
+
+#[derive(Debug, serde::Deserialize)]
+struct Reply {
+    #[serde(rename = "nb_reply")]
+    id: String,
+    ok: bool,
+    #[serde(default)]
+    result: serde_json::Value,
+    #[serde(default)]
+    error: String,
+}
+
+#[derive(Default)]
+pub struct BackendControl {
+    next_id: AtomicU64,
+    // 等待回复的请求
+    pending: Mutex<HashMap<String, mpsc::Sender<Result<serde_json::Value, String>>>>,
+}
+
+pub struct BackendControlState(pub BackendControl);
+
+impl BackendControl {
+    fn forget(&self, id: &str) {
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.remove(id);
+        }
+    }
+}
+
+pub fn timeout_from_ms(timeout_ms: Option<u64>) -> Duration {
+    timeout_ms
+        .map(|ms| Duration::from_millis(ms.clamp(1, MAX_TIMEOUT_MS)))
+        .unwrap_or(DEFAULT_TIMEOUT)
+}
+
+fn validate_command(command: &str) -> Result<(), String> {
+    if command.is_empty()
+        || command.len() > MAX_COMMAND_LEN
+        || !command
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+    {
+        return Err(format!("invalid backend command: {:?}", command));
+    }
+    Ok(())
+}
+
+// 写入一条命令并等待回复；后端报错时返回其错误信息
+pub fn send(
+    app: &tauri::AppHandle,
+    command: &str,
+    args: serde_json::Value,
+    timeout: Duration,
+) -> Result<serde_json::Value, String> {
+    validate_command(command)?;
+    let state = app.state::<BackendControlState>();
+    let control = &state.0;
+    let id = (control.next_id.fetch_add(1, Ordering::SeqCst) + 1).to_string();
+    let mut line = serde_json::to_vec(&serde_json::json!({
+        "id": id,
+        "command": command,
+        "args": args,
+    }))
+    .map_err(|e| format!("serialize backend command failed: {}", e))?;
+    line.push(b'\n');
+
+    let (tx, rx) = mpsc::channel();
+    if let Ok(mut pending) = control.pending.lock() {
+        pending.insert(id.clone(), tx);
+    }
+    let written = app
+        .state::<crate::SidecarState>()
+        .0
+        .lock()
+        .map_err(|_| "sidecar lock poisoned".to_string())
+        .and_then(|mut child| match child.as_mut() {
+            Some(child) => child
+                .write(&line)
+                .map_err(|e| format!("write backend command failed: {}", e)),
+            None => Err("sidecar is not running".to_string()),
+        });
+    if let Err(e) = written {
+        control.forget(&id);
+        return Err(e);
+    }
+    let reply = rx.recv_timeout(timeout);
+    control.forget(&id);
+    match reply {
+        Ok(result) => result,
+        // 旧版后端只把标准输入当退出信号，不会回复
+        Err(_) => Err(format!(
+            "backend did not reply to {} within {} ms",
+            command,
+            timeout.as_millis()
+        )),
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct LogLevelChange {
+    pub level: String,
+    // 后端是否已按新级别输出；未运行或旧版后端不支持时为 false，server.log 的过滤仍然生效
+    pub backend_applied: bool,
+    pub backend_error: Option<String>,
+}
+
+pub fn set_log_level(app: &tauri::AppHandle, level: &str) -> Result<LogLevelChange, String> {
+    let level = level.trim().to_lowercase();
+    if !crate::config::LOG_LEVELS.contains(&level.as_str()) {
+        return Err(format!(
+            "invalid log level: expected one of {}",
+            crate::config::LOG_LEVELS.join("/")
+        ));
+    }
+    let log_state = app.state::<crate::LogState>();
+    let previous = log_state.server_level();
+    log_state.set_server_level(&level);
+    let result = send(
+        app,
+        "set_log_level",
+        serde_json::json!({ "level": level }),
+        DEFAULT_TIMEOUT,
+    );
+    log_state.log_app(
+        "INFO",
+        &format!("backend log level {} -> {}", previous, level),
+    );
+    let backend_error = result.err();
+    if let Some(e) = &backend_error {
+        log_state.log_app("WARN", &format!("backend log level not applied: {}", e));
+    }
+    Ok(LogLevelChange {
+        level,
+        backend_applied: backend_error.is_none(),
+        backend_error,
+    })
+}
+
+// supervisor 收到的每行标准输出先经过这里；是控制命令的回复时返回 true，不再按其他格式解析
+pub fn handle_output(app: &tauri::AppHandle, line: &str) -> bool {
+    let line = line.trim();
+    if !line.starts_with('{') || !line.contains("\"nb_reply\"") {
+        return false;
+    }
+    let Ok(reply) = serde_json::from_str::<Reply>(line) else {
+        return false;
+    };
+    let Some(control) = app.try_state::<BackendControlState>() else {
+        return true;
+    };
+    let sender = control
+        .0
+        .pending
+        .lock()
+        .ok()
+        .and_then(|mut pending| pending.remove(&reply.id));
+    if let Some(sender) = sender {
+        let result = if reply.ok {
+            Ok(reply.result)
+        } else if reply.error.is_empty() {
+            Err("backend command failed".to_string())
+        } else {
+            Err(reply.error)
+        };
+        let _ = sender.send(result);
+    }
+    true
+}
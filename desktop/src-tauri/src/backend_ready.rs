@@ -0,0 +1,186 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tauri::{Emitter, Manager};
+
+// 等待后端可用：端口（或 socket）已知且健康检查返回 2xx 才算就绪。
+// 取代前端轮询 get_backend_port 直到非 0 的做法，失败时返回带 code 的结构化错误
+// 端口宣布时 HTTP 服务未必已经开始接受请求；宣布后在后台探测，第一次健康检查通过时发出 backend-ready
+// （载荷同 BackendReady），前端据此开始首次加载。错过事件的页面仍可调用 wait_for_backend_ready
+pub const EVENT: &str = "backend-ready";
+pub const DEFAULT_TIMEOUT_MS: u64 = 30_000;
+const MIN_TIMEOUT_MS: u64 = 100;
+const MAX_TIMEOUT_MS: u64 = 5 * 60 * 1000;
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct BackendReady {
+    // socket 模式下为 0
+    pub port: u16,
+    pub epoch: u64,
+    pub base_url: String,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct BackendWaitError {
+    // timeout / terminated / unhealthy / internal
+    pub code: String,
+    pub message: String,
+    // 超时前最后一次健康检查的错误，便于排查
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+impl BackendWaitError {
+    fn new(code: &str, message: String, last_error: Option<String>) -> Self {
+        Self {
+            code: code.to_string(),
+            message,
+            last_error,
+        }
+    }
+}
+
+enum Endpoint {
+    Port(u16),
+    Socket(std::path::PathBuf),
+}
+
+fn endpoint(app: &tauri::AppHandle) -> Option<Endpoint> {
+    let socket = app
+        .state::<crate::backend_bridge::BackendSocket>()
+        .0
+        .lock()
+        .ok()
+        .and_then(|s| s.clone());
+    if let Some(path) = socket {
+        return Some(Endpoint::Socket(path));
+    }
+    let port = app
+        .state::<crate::BackendPort>()
+        .0
+        .lock()
+        .map(|p| *p)
+        .unwrap_or(0);
+    (port > 0).then_some(Endpoint::Port(port))
+}
+
+fn sidecar_alive(app: &tauri::AppHandle) -> bool {
+    app.state::<crate::SidecarState>()
+        .0
+        .lock()
+        .map(|child| child.is_some())
+        .unwrap_or(false)
+}
+
+// 对当前端点做一次健康检查，返回 HTTP 状态码；端口/socket 尚未宣布时返回 None
+pub fn check_once(app: &tauri::AppHandle) -> Option<Result<u16, String>> {
+    Some(match endpoint(app)? {
+        Endpoint::Port(port) => crate::firewall::health_status(port),
+        Endpoint::Socket(path) => crate::backend_bridge::health_status(&path),
+    })
+}
+
+pub fn wait(
+    app: &tauri::AppHandle,
+    timeout_ms: Option<u64>,
+) -> Result<BackendReady, BackendWaitError> {
+    let timeout = Duration::from_millis(
+        timeout_ms
+            .unwrap_or(DEFAULT_TIMEOUT_MS)
+            .clamp(MIN_TIMEOUT_MS, MAX_TIMEOUT_MS),
+    );
+    let deadline = Instant::now() + timeout;
+    let mut last_error = None;
+    // 健康检查返回非 2xx 说明后端已经起来但状态异常，超时后按 unhealthy 报告
+    let mut unhealthy_status = None;
+    loop {
+        if !sidecar_alive(app) {
+            return Err(BackendWaitError::new(
+                "terminated",
+                "backend process is not running".to_string(),
+                last_error,
+            ));
+        }
+        if let Some(endpoint) = endpoint(app) {
+            let status = match &endpoint {
+                Endpoint::Port(port) => crate::firewall::health_status(*port),
+                Endpoint::Socket(path) => crate::backend_bridge::health_status(path),
+            };
+            match status {
+                Ok(code) if (200..300).contains(&code) => {
+                    let (port, base_url) = match endpoint {
+                        Endpoint::Port(port) => (port, format!("http://127.0.0.1:{}/api/v1", port)),
+                        Endpoint::Socket(_) => {
+                            (0, format!("{}/api/v1", crate::backend_bridge::base_url()))
+                        }
+                    };
+                    let epoch = app
+                        .state::<crate::port_events::PortAnnouncer>()
+                        .current()
+                        .epoch;
+                    return Ok(BackendReady {
+                        port,
+                        epoch,
+                        base_url,
+                    });
+                }
+                Ok(code) => {
+                    last_error = Some(format!("health check returned {}", code));
+                    unhealthy_status = Some(code);
+                }
+                Err(e) => {
+                    last_error = Some(e);
+                    unhealthy_status = None;
+                }
+            }
+        }
+        if Instant::now() + POLL_INTERVAL > deadline {
+            break;
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+    Err(match unhealthy_status {
+        Some(code) => BackendWaitError::new(
+            "unhealthy",
+            format!("backend health check returned {}", code),
+            last_error,
+        ),
+        None => BackendWaitError::new(
+            "timeout",
+            format!("backend not ready after {} ms", timeout.as_millis()),
+            last_error,
+        ),
+    })
+}
+
+// 由 supervisor 在采用新宣布的端点后调用；期间进程被替换时不再发出
+pub fn announce_when_ready(app: tauri::AppHandle, generation: u64) {
+    thread::spawn(move || {
+        let result = wait(&app, Some(MAX_TIMEOUT_MS));
+        let current = app
+            .state::<crate::supervisor::SupervisorState>()
+            .0
+            .generation();
+        if current != generation {
+            return;
+        }
+        let log_state = app.state::<crate::LogState>();
+        match result {
+            Ok(ready) => {
+                log_state.log_app(
+                    "INFO",
+                    &format!(
+                        "backend ready at {} (epoch {})",
+                        ready.base_url, ready.epoch
+                    ),
+                );
+                let _ = app.emit(EVENT, ready);
+            }
+            Err(e) => log_state.log_app(
+                "WARN",
+                &format!("backend-ready not emitted ({}): {}", e.code, e.message),
+            ),
+        }
+    });
+}
@@ -0,0 +1,378 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::Cursor;
+use std::path::Path;
+
+use sha2::{Digest, Sha256};
+
+use crate::archive::read_entry;
+use crate::library::{db_timestamp, ImageMeta, Library, NewTask, TaskRecord};
+use crate::naming::{new_file_id, TemplateContext};
+use crate::organize;
+use crate::settings::Settings;
+use crate::storage::{normalize_id, Storage};
+
+pub const BUNDLE_FORMAT: &str = "nano-banana-bundle";
+pub const BUNDLE_VERSION: u32 = 1;
+const MANIFEST_NAME: &str = "manifest.json";
+
+// 防御：manifest 与单张图片的大小上限，避免恶意包撑爆内存
+const MAX_MANIFEST_BYTES: u64 = 4 * 1024 * 1024;
+const MAX_IMAGE_BYTES: u64 = 100 * 1024 * 1024;
+
+// 项目包 manifest：images + prompts，由导出方写入 zip 根目录的 manifest.json
+#[derive(serde::Deserialize)]
+pub struct BundleManifest {
+    pub format: String,
+    pub version: u32,
+    #[serde(default)]
+    pub name: String,
+    pub items: Vec<BundleItem>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct BundleItem {
+    #[serde(default)]
+    pub id: String,
+    pub file: String,
+    #[serde(default)]
+    pub prompt: String,
+    #[serde(default)]
+    pub provider_name: String,
+    #[serde(default)]
+    pub model_id: String,
+    #[serde(default)]
+    pub config_snapshot: String,
+    #[serde(default)]
+    pub sha256: Option<String>,
+    #[serde(default)]
+    pub project: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+#[derive(Default, serde::Serialize)]
+pub struct ImportReport {
+    pub name: String,
+    pub imported: Vec<ImportedItem>,
+    pub duplicates: Vec<DuplicateItem>,
+    pub conflicts: Vec<ConflictItem>,
+    pub failed: Vec<FailedItem>,
+    // 用户中途取消时为 true，已导入的部分照常入库
+    pub cancelled: bool,
+}
+
+#[derive(serde::Serialize)]
+pub struct ImportedItem {
+    pub source_id: String,
+    pub task_id: String,
+}
+
+#[derive(serde::Serialize)]
+pub struct DuplicateItem {
+    pub source_id: String,
+    pub existing_task_id: String,
+}
+
+#[derive(serde::Serialize)]
+pub struct ConflictItem {
+    pub source_id: String,
+    pub task_id: String,
+    pub reason: String,
+}
+
+#[derive(serde::Serialize)]
+pub struct FailedItem {
+    pub source_id: String,
+    pub file: String,
+    pub error: String,
+}
+
+fn validate_manifest(manifest: &BundleManifest) -> Result<(), String> {
+    if manifest.format != BUNDLE_FORMAT {
+        return Err(format!("unsupported bundle format: {}", manifest.format));
+    }
+    if manifest.version == 0 || manifest.version > BUNDLE_VERSION {
+        return Err(format!("unsupported bundle version: {}", manifest.version));
+    }
+    if manifest.items.is_empty() {
+        return Err("bundle has no items".to_string());
+    }
+    for item in &manifest.items {
+        normalize_id(&item.file).map_err(|e| format!("invalid item file {}: {}", item.file, e))?;
+    }
+    Ok(())
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+// 按内容去重：先按文件大小筛选候选，只有大小相同的已有图片才去算哈希，避免整库重新读盘
+struct ContentIndex<'a> {
+    storage: &'a dyn Storage,
+    by_size: HashMap<u64, Vec<TaskRecord>>,
+    hashes: HashMap<String, String>,
+}
+
+impl<'a> ContentIndex<'a> {
+    fn build(storage: &'a dyn Storage, tasks: Vec<TaskRecord>) -> Self {
+        let mut by_size: HashMap<u64, Vec<TaskRecord>> = HashMap::new();
+        for task in tasks {
+            let Ok(id) = normalize_id(&task.local_path) else {
+                continue;
+            };
+            if let Some(meta) = storage.stat(&id) {
+                by_size.entry(meta.len).or_default().push(task);
+            }
+        }
+        Self {
+            storage,
+            by_size,
+            hashes: HashMap::new(),
+        }
+    }
+
+    fn find(&mut self, len: u64, hash: &str) -> Option<String> {
+        let candidates = self.by_size.get(&len)?;
+        for task in candidates {
+            let existing = match self.hashes.get(&task.task_id) {
+                Some(h) => h.clone(),
+                None => {
+                    let Ok(id) = normalize_id(&task.local_path) else {
+                        continue;
+                    };
+                    let Ok(bytes) = self.storage.get(&id) else {
+                        continue;
+                    };
+                    let h = sha256_hex(&bytes);
+                    self.hashes.insert(task.task_id.clone(), h.clone());
+                    h
+                }
+            };
+            if existing == hash {
+                return Some(task.task_id.clone());
+            }
+        }
+        None
+    }
+}
+
+// 导入其他用户导出的项目包：校验 manifest，按内容哈希去重，写入存储并合并到历史库，
+// 返回逐项报告（导入/重复/ID 冲突/失败）。每处理一项调用一次 progress(已处理数, 总数)，返回 false 即停止
+pub fn import_bundle(
+    path: &Path,
+    storage: &dyn Storage,
+    library: &Library,
+    settings: &Settings,
+    id_seed: u128,
+    progress: &mut dyn FnMut(usize, usize) -> bool,
+) -> Result<ImportReport, String> {
+    let file = File::open(crate::long_path::extend(path))
+        .map_err(|e| format!("open bundle failed: {} ({})", e, path.display()))?;
+    let mut archive =
+        zip::ZipArchive::new(file).map_err(|e| format!("read bundle failed: {}", e))?;
+
+    let manifest_bytes = read_entry(&mut archive, MANIFEST_NAME, MAX_MANIFEST_BYTES)?;
+    let manifest: BundleManifest = serde_json::from_slice(&manifest_bytes)
+        .map_err(|e| format!("parse manifest failed: {}", e))?;
+    validate_manifest(&manifest)?;
+
+    let mut conn = library.open()?;
+    let mut index = ContentIndex::build(storage, library.completed_tasks(&conn)?);
+    let mut report = ImportReport {
+        name: manifest.name.clone(),
+        ..Default::default()
+    };
+
+    let mut seen_hashes: HashMap<String, String> = HashMap::new();
+    let mut reserved_ids: HashSet<String> = HashSet::new();
+    let mut written: Vec<String> = Vec::new();
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("begin transaction failed: {}", e))?;
+
+    let total = manifest.items.len();
+    for (i, item) in manifest.items.iter().enumerate() {
+        if !progress(i, total) {
+            report.cancelled = true;
+            break;
+        }
+        let source_id = if item.id.trim().is_empty() {
+            format!("item-{}", i + 1)
+        } else {
+            item.id.trim().to_string()
+        };
+        let fail = |error: String| FailedItem {
+            source_id: source_id.clone(),
+            file: item.file.clone(),
+            error,
+        };
+
+        let entry_name = normalize_id(&item.file).unwrap_or_else(|_| item.file.clone());
+        let bytes = match read_entry(&mut archive, &entry_name, MAX_IMAGE_BYTES) {
+            Ok(b) => b,
+            Err(e) => {
+                report.failed.push(fail(e));
+                continue;
+            }
+        };
+
+        let hash = sha256_hex(&bytes);
+        if let Some(expected) = &item.sha256 {
+            if !expected.eq_ignore_ascii_case(&hash) {
+                report.failed.push(fail("sha256 mismatch".to_string()));
+                continue;
+            }
+        }
+
+        if let Some(existing) = seen_hashes
+            .get(&hash)
+            .cloned()
+            .or_else(|| index.find(bytes.len() as u64, &hash))
+        {
+            report.duplicates.push(DuplicateItem {
+                source_id,
+                existing_task_id: existing,
+            });
+            continue;
+        }
+
+        let reader = match image::ImageReader::new(Cursor::new(&bytes)).with_guessed_format() {
+            Ok(r) => r,
+            Err(e) => {
+                report
+                    .failed
+                    .push(fail(format!("read image failed: {}", e)));
+                continue;
+            }
+        };
+        let ext = match reader.format() {
+            Some(image::ImageFormat::Png) => "png",
+            Some(image::ImageFormat::Jpeg) => "jpg",
+            Some(image::ImageFormat::WebP) => "webp",
+            _ => {
+                report
+                    .failed
+                    .push(fail("unsupported image format".to_string()));
+                continue;
+            }
+        };
+        let (width, height) = match reader.into_dimensions() {
+            Ok(d) => d,
+            Err(e) => {
+                report
+                    .failed
+                    .push(fail(format!("decode image failed: {}", e)));
+                continue;
+            }
+        };
+
+        // 与本地已有任务 ID 冲突时换一个新 ID，保留两条记录并在报告中说明
+        let mut task_id = source_id.clone();
+        let taken = match library.task_exists(&tx, &task_id) {
+            Ok(exists) => exists || reserved_ids.contains(&task_id),
+            Err(e) => {
+                report.failed.push(fail(e));
+                continue;
+            }
+        };
+        if taken {
+            task_id = format!("{}-import-{}-{}", source_id, id_seed, i + 1);
+            report.conflicts.push(ConflictItem {
+                source_id: source_id.clone(),
+                task_id: task_id.clone(),
+                reason: "task id already exists".to_string(),
+            });
+        }
+
+        let created_at = db_timestamp();
+        let meta = ImageMeta {
+            project: item.project.trim().to_string(),
+            tags: item
+                .tags
+                .iter()
+                .map(|t| t.trim().to_string())
+                .filter(|t| !t.is_empty())
+                .collect(),
+            ..Default::default()
+        };
+        let record = TaskRecord {
+            task_id: task_id.clone(),
+            prompt: item.prompt.clone(),
+            provider_name: item.provider_name.clone(),
+            model_id: item.model_id.clone(),
+            status: "completed".to_string(),
+            image_url: String::new(),
+            local_path: String::new(),
+            thumbnail_path: String::new(),
+            width: width as i64,
+            height: height as i64,
+            config_snapshot: item.config_snapshot.clone(),
+            created_at: created_at.clone(),
+        };
+        let dir = organize::target_dir(&settings.organize.rules, &record, &meta);
+        let name_ctx = TemplateContext {
+            id: &task_id,
+            prompt: &item.prompt,
+            model: &item.model_id,
+            provider: &item.provider_name,
+            created_at: &created_at,
+            width: width as i64,
+            height: height as i64,
+            seq: i + 1,
+        };
+        let local_path = match new_file_id(storage, &settings.naming, &dir, &name_ctx, ext) {
+            Ok(id) => id,
+            Err(e) => {
+                report.failed.push(fail(e));
+                continue;
+            }
+        };
+        if let Err(e) = storage.put(&local_path, &bytes) {
+            report.failed.push(fail(e));
+            continue;
+        }
+
+        let inserted = library.insert_task(
+            &tx,
+            &NewTask {
+                task_id: &task_id,
+                prompt: &item.prompt,
+                provider_name: &item.provider_name,
+                model_id: &item.model_id,
+                local_path: &local_path,
+                width: width as i64,
+                height: height as i64,
+                config_snapshot: &item.config_snapshot,
+            },
+        );
+        let inserted = inserted.and_then(|_| {
+            if meta.project.is_empty() && meta.tags.is_empty() {
+                Ok(())
+            } else {
+                library.put_meta(&tx, &task_id, &meta)
+            }
+        });
+        if let Err(e) = inserted {
+            let _ = storage.delete(&local_path);
+            report.failed.push(fail(e));
+            continue;
+        }
+        written.push(local_path);
+
+        seen_hashes.insert(hash, task_id.clone());
+        reserved_ids.insert(task_id.clone());
+        report.imported.push(ImportedItem { source_id, task_id });
+    }
+
+    // 入库失败时清理已写入的文件，避免留下没有记录的孤儿图片
+    if let Err(e) = tx.commit() {
+        for id in &written {
+            let _ = storage.delete(id);
+        }
+        return Err(format!("commit import failed: {}", e));
+    }
+
+    Ok(report)
+}
@@ -0,0 +1,130 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use tauri::Emitter;
+
+use crate::storage::{Storage, StorageMeta};
+
+// 原生层数据变更的事件流：前端按 id 增量刷新视图，不必每次重新拉整个图库。
+// 文件层的变更由 NotifyingStorage 包装存储后端统一发出，数据库记录的变更由各命令在提交后发出。
+// seq 单调递增，前端发现跳号时说明错过了事件，应退回全量刷新。后端进程写入的记录不经过这里
+pub const EVENT: &str = "storage-changed";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Created,
+    Updated,
+    Deleted,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct StorageChange {
+    pub seq: u64,
+    pub kind: ChangeKind,
+    // file（存储逻辑 ID）/ task（历史记录）/ collection（智能相册）/ provider（Provider 配置）
+    pub entity: &'static str,
+    pub ids: Vec<String>,
+}
+
+pub struct Changefeed {
+    app: tauri::AppHandle,
+    seq: AtomicU64,
+}
+
+pub struct ChangefeedState(pub Arc<Changefeed>);
+
+impl Changefeed {
+    pub fn new(app: tauri::AppHandle) -> Self {
+        Self {
+            app,
+            seq: AtomicU64::new(0),
+        }
+    }
+
+    pub fn emit(&self, kind: ChangeKind, entity: &'static str, ids: Vec<String>) {
+        if ids.is_empty() {
+            return;
+        }
+        let change = StorageChange {
+            seq: self.seq.fetch_add(1, Ordering::SeqCst) + 1,
+            kind,
+            entity,
+            ids,
+        };
+        let _ = self.app.emit(EVENT, change);
+    }
+}
+
+// 包装任意存储后端，写入/删除/重命名成功后发出 file 事件；重命名记为旧 ID 删除 + 新 ID 创建
+pub struct NotifyingStorage {
+    inner: Arc<dyn Storage>,
+    feed: Arc<Changefeed>,
+}
+
+impl NotifyingStorage {
+    pub fn new(inner: Arc<dyn Storage>, feed: Arc<Changefeed>) -> Self {
+        Self { inner, feed }
+    }
+}
+
+impl Storage for NotifyingStorage {
+    fn put(&self, id: &str, bytes: &[u8]) -> Result<(), String> {
+        let existed = self.inner.exists(id);
+        self.inner.put(id, bytes)?;
+        let kind = if existed {
+            ChangeKind::Updated
+        } else {
+            ChangeKind::Created
+        };
+        self.feed.emit(kind, "file", vec![id.to_string()]);
+        Ok(())
+    }
+
+    fn replace(&self, id: &str, bytes: &[u8]) -> Result<(), String> {
+        self.inner.replace(id, bytes)?;
+        self.feed
+            .emit(ChangeKind::Updated, "file", vec![id.to_string()]);
+        Ok(())
+    }
+
+    fn get(&self, id: &str) -> Result<Vec<u8>, String> {
+        self.inner.get(id)
+    }
+
+    fn get_range(&self, id: &str, start: u64, len: u64) -> Result<Vec<u8>, String> {
+        self.inner.get_range(id, start, len)
+    }
+
+    fn exists(&self, id: &str) -> bool {
+        self.inner.exists(id)
+    }
+
+    fn delete(&self, id: &str) -> Result<(), String> {
+        let existed = self.inner.exists(id);
+        self.inner.delete(id)?;
+        if existed {
+            self.feed
+                .emit(ChangeKind::Deleted, "file", vec![id.to_string()]);
+        }
+        Ok(())
+    }
+
+    fn rename(&self, from: &str, to: &str) -> Result<(), String> {
+        self.inner.rename(from, to)?;
+        self.feed
+            .emit(ChangeKind::Deleted, "file", vec![from.to_string()]);
+        self.feed
+            .emit(ChangeKind::Created, "file", vec![to.to_string()]);
+        Ok(())
+    }
+
+    fn stat(&self, id: &str) -> Option<StorageMeta> {
+        self.inner.stat(id)
+    }
+
+    fn local_path(&self, id: &str) -> Option<PathBuf> {
+        self.inner.local_path(id)
+    }
+}
@@ -0,0 +1,233 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use tauri::{Emitter, Manager};
+
+use crate::settings::ClipboardSettings;
+
+// 剪贴板文本历史：开启 clipboard.text_history 后由后台线程轮询剪贴板，把看起来像提示词的文本
+// 记到 AppData/clipboard-history.json（固定在本机目录，不随自定义数据目录同步走），方便从多处拼提示词。
+// 单个词（网址、路径、API Key 之类）与命中排除关键词的文本不记；重复复制的移到最前并累计次数
+pub const EVENT: &str = "clipboard-history-updated";
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+const READ_TIMEOUT: Duration = Duration::from_secs(2);
+const DEFAULT_MAX_ENTRIES: usize = 50;
+const MAX_ENTRIES: usize = 500;
+const DEFAULT_MIN_CHARS: usize = 8;
+const DEFAULT_MAX_CHARS: usize = 4000;
+const MAX_CHARS: usize = 20000;
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct ClipboardEntry {
+    pub text: String,
+    // 最近一次复制的时间
+    pub copied_at: String,
+    pub count: u32,
+}
+
+pub struct ClipboardHistory {
+    path: PathBuf,
+    lock: Mutex<()>,
+    // 上一次读到的剪贴板内容，没变化时不重复处理
+    last_seen: Mutex<Option<String>>,
+}
+
+pub struct ClipboardHistoryState(pub ClipboardHistory);
+
+pub fn validate(settings: &ClipboardSettings) -> Result<(), String> {
+    if let Some(max) = settings.max_entries {
+        if max == 0 || max > MAX_ENTRIES {
+            return Err(format!(
+                "clipboard.max_entries must be between 1 and {}",
+                MAX_ENTRIES
+            ));
+        }
+    }
+    if let Some(max) = settings.max_chars {
+        if max == 0 || max > MAX_CHARS {
+            return Err(format!(
+                "clipboard.max_chars must be between 1 and {}",
+                MAX_CHARS
+            ));
+        }
+    }
+    if settings.min_chars.unwrap_or(DEFAULT_MIN_CHARS)
+        > settings.max_chars.unwrap_or(DEFAULT_MAX_CHARS)
+    {
+        return Err("clipboard.min_chars must not exceed clipboard.max_chars".to_string());
+    }
+    Ok(())
+}
+
+fn keywords(list: &[String]) -> Vec<String> {
+    list.iter()
+        .map(|k| k.trim().to_lowercase())
+        .filter(|k| !k.is_empty())
+        .collect()
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32, 0x3040..=0x30FF | 0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xAC00..=0xD7AF)
+}
+
+// 像提示词：长度在范围内，至少两个词（中日韩文字不靠空格分词，含有即可），且符合关键词过滤
+fn looks_like_prompt(text: &str, settings: &ClipboardSettings) -> bool {
+    let chars = text.chars().count();
+    if chars < settings.min_chars.unwrap_or(DEFAULT_MIN_CHARS)
+        || chars > settings.max_chars.unwrap_or(DEFAULT_MAX_CHARS)
+    {
+        return false;
+    }
+    if text.split_whitespace().nth(1).is_none() && !text.chars().any(is_cjk) {
+        return false;
+    }
+    let lower = text.to_lowercase();
+    if keywords(&settings.exclude)
+        .iter()
+        .any(|k| lower.contains(k.as_str()))
+    {
+        return false;
+    }
+    let include = keywords(&settings.include);
+    include.is_empty() || include.iter().any(|k| lower.contains(k.as_str()))
+}
+
+impl ClipboardHistory {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            lock: Mutex::new(()),
+            last_seen: Mutex::new(None),
+        }
+    }
+
+    fn read(&self) -> Vec<ClipboardEntry> {
+        fs::read(&self.path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default()
+    }
+
+    fn write(&self, entries: &[ClipboardEntry]) -> Result<(), String> {
+        let bytes = serde_json::to_vec(entries)
+            .map_err(|e| format!("serialize clipboard history failed: {}", e))?;
+        crate::storage::write_atomic(&self.path, &bytes)
+    }
+
+    // 最近的在前
+    pub fn list(&self) -> Vec<ClipboardEntry> {
+        let _guard = self.lock.lock();
+        self.read()
+    }
+
+    pub fn clear(&self) -> Result<(), String> {
+        let _guard = self
+            .lock
+            .lock()
+            .map_err(|_| "clipboard history lock poisoned".to_string())?;
+        match fs::remove_file(&self.path) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(format!("clear clipboard history failed: {}", e)),
+        }
+    }
+
+    // 返回是否记入了历史
+    fn record(&self, text: &str, settings: &ClipboardSettings) -> Result<bool, String> {
+        let text = text.trim();
+        if !looks_like_prompt(text, settings) {
+            return Ok(false);
+        }
+        let _guard = self
+            .lock
+            .lock()
+            .map_err(|_| "clipboard history lock poisoned".to_string())?;
+        let mut entries = self.read();
+        let count = match entries.iter().position(|e| e.text == text) {
+            Some(index) => entries.remove(index).count + 1,
+            None => 1,
+        };
+        entries.insert(
+            0,
+            ClipboardEntry {
+                text: text.to_string(),
+                copied_at: chrono::Local::now().to_rfc3339(),
+                count,
+            },
+        );
+        entries.truncate(settings.max_entries.unwrap_or(DEFAULT_MAX_ENTRIES));
+        self.write(&entries)?;
+        Ok(true)
+    }
+
+    // 内容变化时返回新内容
+    fn changed(&self, text: String) -> Option<String> {
+        let mut last = self.last_seen.lock().ok()?;
+        if last.as_deref() == Some(text.as_str()) {
+            return None;
+        }
+        *last = Some(text.clone());
+        Some(text)
+    }
+
+    fn forget_last(&self) {
+        if let Ok(mut last) = self.last_seen.lock() {
+            *last = None;
+        }
+    }
+}
+
+// macOS 上部分剪贴板实现要求在主线程调用，与 read_image_from_clipboard 一样切到主线程读；
+// 剪贴板里不是文本时 get_text 会报错，属于正常情况，按 None 处理
+fn read_text(app: &tauri::AppHandle) -> Result<Option<String>, String> {
+    let (tx, rx) = std::sync::mpsc::channel();
+    app.run_on_main_thread(move || {
+        let result = arboard::Clipboard::new()
+            .map(|mut clipboard| clipboard.get_text().ok())
+            .map_err(|e| format!("clipboard init failed: {}", e));
+        let _ = tx.send(result);
+    })
+    .map_err(|e| format!("run_on_main_thread failed: {}", e))?;
+    rx.recv_timeout(READ_TIMEOUT)
+        .map_err(|_| "clipboard read timed out".to_string())?
+}
+
+pub fn start(app: tauri::AppHandle) {
+    thread::spawn(move || {
+        // 同样的错误（如 Linux 下没有显示服务）只记一次
+        let mut last_error: Option<String> = None;
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            let settings = app.state::<crate::SettingsState>().0.get().clipboard;
+            let state = app.state::<ClipboardHistoryState>();
+            let history = &state.0;
+            if !settings.text_history {
+                history.forget_last();
+                continue;
+            }
+            let result =
+                read_text(&app).and_then(|text| match text.and_then(|t| history.changed(t)) {
+                    Some(text) => history.record(&text, &settings),
+                    None => Ok(false),
+                });
+            match result {
+                Ok(recorded) => {
+                    last_error = None;
+                    if recorded {
+                        let _ = app.emit(EVENT, ());
+                    }
+                }
+                Err(e) => {
+                    if last_error.as_deref() != Some(e.as_str()) {
+                        app.state::<crate::LogState>()
+                            .log_app("WARN", &format!("clipboard history: {}", e));
+                    }
+                    last_error = Some(e);
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,363 @@
+use std::collections::HashSet;
+use std::thread;
+use std::time::Duration;
+
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use tauri::{Emitter, Manager};
+
+use crate::library::{task_from_row, Library, TaskRecord, TASK_COLUMNS};
+
+pub const COLLECTION_UPDATED_EVENT: &str = "smart-collection-updated";
+const WATCH_INTERVAL: Duration = Duration::from_secs(3);
+const MAX_PAGE: u32 = 500;
+
+// 智能相册（保存的搜索）：条件之间为 AND，未设置的条件不参与过滤
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct CollectionQuery {
+    // 提示词包含（不区分大小写）
+    pub text: Option<String>,
+    pub model: Option<String>,
+    pub provider: Option<String>,
+    pub project: Option<String>,
+    // 必须同时带有的标签
+    pub tags: Vec<String>,
+    pub favorite: Option<bool>,
+    // 如 "16:9"，允许 1% 误差（生成尺寸常被取整到 8/64 的倍数）
+    pub aspect_ratio: Option<String>,
+    // created_at 的日期范围（YYYY-MM-DD，含端点）
+    pub since: Option<String>,
+    pub until: Option<String>,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct SmartCollection {
+    pub id: String,
+    pub name: String,
+    pub query: CollectionQuery,
+    pub created_at: String,
+}
+
+#[derive(Clone, serde::Serialize)]
+struct CollectionUpdate {
+    id: String,
+    name: String,
+    task_ids: Vec<String>,
+}
+
+fn trimmed(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty())
+}
+
+fn parse_ratio(value: &str) -> Result<(f64, f64), String> {
+    let (w, h) = value
+        .split_once(':')
+        .ok_or_else(|| format!("invalid aspect_ratio: {}", value))?;
+    let w = w.trim().parse::<f64>().ok().filter(|v| *v > 0.0);
+    let h = h.trim().parse::<f64>().ok().filter(|v| *v > 0.0);
+    match (w, h) {
+        (Some(w), Some(h)) => Ok((w, h)),
+        _ => Err(format!("invalid aspect_ratio: {}", value)),
+    }
+}
+
+fn check_date(name: &str, value: &str) -> Result<(), String> {
+    chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map(|_| ())
+        .map_err(|_| format!("invalid {}: {} (expected YYYY-MM-DD)", name, value))
+}
+
+impl CollectionQuery {
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(ratio) = trimmed(&self.aspect_ratio) {
+            parse_ratio(ratio)?;
+        }
+        if let Some(since) = trimmed(&self.since) {
+            check_date("since", since)?;
+        }
+        if let Some(until) = trimmed(&self.until) {
+            check_date("until", until)?;
+        }
+        Ok(())
+    }
+
+    // 拼出 WHERE 子句与参数；image_meta 里的条件用子查询，避免与 tasks 的同名列冲突
+    pub fn to_sql(&self) -> Result<(String, Vec<rusqlite::types::Value>), String> {
+        use rusqlite::types::Value;
+
+        let mut clauses = vec![
+            "deleted_at IS NULL".to_string(),
+            "status = 'completed'".to_string(),
+        ];
+        let mut args: Vec<Value> = Vec::new();
+
+        if let Some(text) = trimmed(&self.text) {
+            args.push(Value::Text(format!("%{}%", text.to_lowercase())));
+            clauses.push(format!("LOWER(prompt) LIKE ?{}", args.len()));
+        }
+        if let Some(model) = trimmed(&self.model) {
+            args.push(Value::Text(model.to_string()));
+            clauses.push(format!("model_id = ?{}", args.len()));
+        }
+        if let Some(provider) = trimmed(&self.provider) {
+            args.push(Value::Text(provider.to_string()));
+            clauses.push(format!("provider_name = ?{}", args.len()));
+        }
+        if let Some(project) = trimmed(&self.project) {
+            args.push(Value::Text(project.to_lowercase()));
+            clauses.push(format!(
+                "task_id IN (SELECT task_id FROM image_meta WHERE LOWER(project) = ?{})",
+                args.len()
+            ));
+        }
+        for tag in self.tags.iter().map(|t| t.trim()).filter(|t| !t.is_empty()) {
+            args.push(Value::Text(tag.to_lowercase()));
+            clauses.push(format!(
+                "task_id IN (SELECT m.task_id FROM image_meta m, json_each(m.tags) t \
+                 WHERE LOWER(t.value) = ?{})",
+                args.len()
+            ));
+        }
+        match self.favorite {
+            Some(true) => clauses
+                .push("task_id IN (SELECT task_id FROM image_meta WHERE favorite = 1)".into()),
+            Some(false) => clauses
+                .push("task_id NOT IN (SELECT task_id FROM image_meta WHERE favorite = 1)".into()),
+            None => {}
+        }
+        if let Some(ratio) = trimmed(&self.aspect_ratio) {
+            let (rw, rh) = parse_ratio(ratio)?;
+            args.push(Value::Real(rw));
+            let rw_idx = args.len();
+            args.push(Value::Real(rh));
+            let rh_idx = args.len();
+            clauses.push(format!(
+                "width > 0 AND height > 0 AND \
+                 ABS(width * ?{rh} - height * ?{rw}) <= 0.01 * width * ?{rh}",
+                rw = rw_idx,
+                rh = rh_idx
+            ));
+        }
+        if let Some(since) = trimmed(&self.since) {
+            args.push(Value::Text(since.to_string()));
+            clauses.push(format!("SUBSTR(created_at, 1, 10) >= ?{}", args.len()));
+        }
+        if let Some(until) = trimmed(&self.until) {
+            args.push(Value::Text(until.to_string()));
+            clauses.push(format!("SUBSTR(created_at, 1, 10) <= ?{}", args.len()));
+        }
+
+        Ok((clauses.join(" AND "), args))
+    }
+}
+
+fn ensure_schema(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS smart_collections (\
+             id TEXT PRIMARY KEY NOT NULL, \
+             name TEXT NOT NULL, \
+             query TEXT NOT NULL, \
+             created_at TEXT NOT NULL)",
+    )
+    .map_err(|e| format!("init smart_collections failed: {}", e))
+}
+
+fn collection_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<SmartCollection> {
+    let query: String = row.get(2)?;
+    Ok(SmartCollection {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        query: serde_json::from_str(&query).unwrap_or_default(),
+        created_at: row.get(3)?,
+    })
+}
+
+pub fn create(
+    library: &Library,
+    name: &str,
+    query: CollectionQuery,
+) -> Result<SmartCollection, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("name is empty".to_string());
+    }
+    query.validate()?;
+
+    let conn = library.open()?;
+    ensure_schema(&conn)?;
+    let collection = SmartCollection {
+        id: format!("sc-{}", crate::now_ms()),
+        name: name.to_string(),
+        query,
+        created_at: crate::library::db_timestamp(),
+    };
+    let json = serde_json::to_string(&collection.query)
+        .map_err(|e| format!("serialize query failed: {}", e))?;
+    conn.execute(
+        "INSERT INTO smart_collections (id, name, query, created_at) VALUES (?1, ?2, ?3, ?4)",
+        params![collection.id, collection.name, json, collection.created_at],
+    )
+    .map_err(|e| format!("create smart collection failed: {}", e))?;
+    Ok(collection)
+}
+
+pub fn list(library: &Library) -> Result<Vec<SmartCollection>, String> {
+    let conn = library.open()?;
+    list_with(&conn)
+}
+
+fn list_with(conn: &Connection) -> Result<Vec<SmartCollection>, String> {
+    ensure_schema(conn)?;
+    let mut stmt = conn
+        .prepare("SELECT id, name, query, created_at FROM smart_collections ORDER BY created_at")
+        .map_err(|e| format!("query smart collections failed: {}", e))?;
+    let rows = stmt
+        .query_map([], collection_from_row)
+        .map_err(|e| format!("query smart collections failed: {}", e))?;
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("query smart collections failed: {}", e))
+}
+
+pub fn delete(library: &Library, id: &str) -> Result<(), String> {
+    let conn = library.open()?;
+    ensure_schema(&conn)?;
+    conn.execute("DELETE FROM smart_collections WHERE id = ?1", params![id])
+        .map(|_| ())
+        .map_err(|e| format!("delete smart collection failed: {}", e))
+}
+
+// 撤销删除：按原 id 写回；同 id 已存在时保持现状
+pub fn restore(library: &Library, collection: &SmartCollection) -> Result<(), String> {
+    let conn = library.open()?;
+    ensure_schema(&conn)?;
+    let json = serde_json::to_string(&collection.query)
+        .map_err(|e| format!("serialize query failed: {}", e))?;
+    conn.execute(
+        "INSERT OR IGNORE INTO smart_collections (id, name, query, created_at) VALUES (?1, ?2, ?3, ?4)",
+        params![collection.id, collection.name, json, collection.created_at],
+    )
+    .map(|_| ())
+    .map_err(|e| format!("restore smart collection failed: {}", e))
+}
+
+// 按创建时间倒序分页返回命中的记录
+pub fn evaluate(
+    library: &Library,
+    id: &str,
+    limit: u32,
+    offset: u32,
+) -> Result<Vec<TaskRecord>, String> {
+    let conn = library.open()?;
+    ensure_schema(&conn)?;
+    let collection = conn
+        .query_row(
+            "SELECT id, name, query, created_at FROM smart_collections WHERE id = ?1",
+            params![id],
+            collection_from_row,
+        )
+        .optional()
+        .map_err(|e| format!("query smart collection failed: {}", e))?
+        .ok_or_else(|| format!("smart collection not found: {}", id))?;
+
+    let (clause, mut args) = collection.query.to_sql()?;
+    args.push(rusqlite::types::Value::Integer(
+        limit.clamp(1, MAX_PAGE) as i64
+    ));
+    args.push(rusqlite::types::Value::Integer(offset as i64));
+    let sql = format!(
+        "SELECT {} FROM tasks WHERE {} ORDER BY created_at DESC LIMIT ?{} OFFSET ?{}",
+        TASK_COLUMNS,
+        clause,
+        args.len() - 1,
+        args.len()
+    );
+    let mut stmt = conn
+        .prepare(&sql)
+        .map_err(|e| format!("evaluate smart collection failed: {}", e))?;
+    let rows = stmt
+        .query_map(params_from_iter(args), task_from_row)
+        .map_err(|e| format!("evaluate smart collection failed: {}", e))?;
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("evaluate smart collection failed: {}", e))
+}
+
+fn latest_completed(conn: &Connection) -> Option<String> {
+    conn.query_row(
+        "SELECT MAX(completed_at) FROM tasks WHERE status = 'completed'",
+        [],
+        |row| row.get::<_, Option<String>>(0),
+    )
+    .ok()
+    .flatten()
+}
+
+// 检查上次之后新完成的记录命中了哪些相册
+fn poll(conn: &Connection, since: &str) -> Result<Vec<CollectionUpdate>, String> {
+    let collections = list_with(conn)?;
+    let mut updates = Vec::new();
+    for collection in collections {
+        let (clause, mut args) = collection.query.to_sql()?;
+        args.push(rusqlite::types::Value::Text(since.to_string()));
+        let sql = format!(
+            "SELECT task_id FROM tasks WHERE {} AND completed_at > ?{}",
+            clause,
+            args.len()
+        );
+        let mut stmt = conn
+            .prepare(&sql)
+            .map_err(|e| format!("evaluate smart collection failed: {}", e))?;
+        let task_ids = stmt
+            .query_map(params_from_iter(args), |row| row.get::<_, String>(0))
+            .map_err(|e| format!("evaluate smart collection failed: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("evaluate smart collection failed: {}", e))?;
+        if !task_ids.is_empty() {
+            updates.push(CollectionUpdate {
+                id: collection.id,
+                name: collection.name,
+                task_ids,
+            });
+        }
+    }
+    Ok(updates)
+}
+
+// 后台轮询历史库：有新图完成且命中某个智能相册时发送事件，前端据此刷新动态相册
+pub fn spawn_watcher(app: tauri::AppHandle, library: Library) {
+    thread::spawn(move || {
+        let mut last_seen: Option<String> = None;
+        let mut errors: HashSet<String> = HashSet::new();
+        loop {
+            thread::sleep(WATCH_INTERVAL);
+            // 后端尚未建库时 open 会失败，等下一轮即可
+            let Ok(conn) = library.open() else {
+                continue;
+            };
+            let latest = latest_completed(&conn);
+            let Some(since) = last_seen.clone() else {
+                // 首轮只记录基线，不把历史记录当作“新图”
+                last_seen = Some(latest.unwrap_or_default());
+                continue;
+            };
+            if latest.as_deref().unwrap_or_default() <= since.as_str() {
+                continue;
+            }
+            match poll(&conn, &since) {
+                Ok(updates) => {
+                    for update in updates {
+                        let _ = app.emit(COLLECTION_UPDATED_EVENT, update);
+                    }
+                    errors.clear();
+                }
+                Err(e) => {
+                    // 同一个错误只打印一次，避免每 3 秒刷屏
+                    if errors.insert(e.clone()) {
+                        app.state::<crate::LogState>()
+                            .log_app("WARN", &format!("smart collection watch failed: {}", e));
+                    }
+                }
+            }
+            last_seen = latest;
+        }
+    });
+}
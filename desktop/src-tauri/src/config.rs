@@ -0,0 +1,717 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use tauri::Manager;
+
+use crate::settings::RuntimeSettings;
+use crate::signed_manifest::Remote;
+
+// 启动期分层配置：默认值 < settings.json(runtime) < NB_* 环境变量 < 命令行参数。
+// 面向托管/企业部署：管理员可以不碰用户的设置文件，直接用环境变量或快捷方式参数固定代理、数据目录等；
+// 管理员下发的策略文件（policy.json）位于所有层之上，其中的值被锁定，用户无法修改
+pub const LOG_LEVELS: [&str; 4] = ["error", "warn", "info", "debug"];
+const DEFAULT_LOG_LEVEL: &str = "info";
+// 与后端的通信方式：tcp 为本地端口；socket 为 Unix 域套接字（Windows 10 1803+ 同样支持 AF_UNIX）
+pub const TRANSPORTS: [&str; 2] = ["tcp", "socket"];
+const DEFAULT_TRANSPORT: &str = "tcp";
+// 未配置 open_domains 时 WebView 可打开的域名
+const DEFAULT_OPEN_DOMAINS: [&str; 2] = ["github.com", "yunwu.ai"];
+const MAX_POLICY_BYTES: u64 = 64 * 1024;
+// 远程紧急开关：构建时配置了 NB_KILL_SWITCH_URL 时，启动后拉取一份用 updater 密钥签名的规则，
+// 对命中的版本关掉自动更新、或去掉 sidecar 的 GODEBUG 调试输出。规则连同签名缓存在 AppData/kill-switch.json，
+// 离线时沿用上次的（拉取、验签与缓存见 signed_manifest.rs）。开关只会关掉功能，安全模式下同样生效
+pub const KILL_SWITCH_EVENT: &str = "kill-switch-updated";
+const KILL_SWITCH_URL: Option<&str> = option_env!("NB_KILL_SWITCH_URL");
+const MAX_KILL_SWITCH_BYTES: usize = 64 * 1024;
+const MAX_KILL_SWITCH_RULES: usize = 100;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigSource {
+    Default,
+    File,
+    Env,
+    Cli,
+    Policy,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct ConfigValue<T> {
+    pub value: T,
+    pub source: ConfigSource,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct RuntimeConfig {
+    pub proxy: ConfigValue<Option<String>>,
+    pub data_dir: ConfigValue<Option<String>>,
+    pub log_level: ConfigValue<String>,
+    pub backend_url: ConfigValue<Option<String>>,
+    pub transport: ConfigValue<String>,
+    pub backend_port: ConfigValue<Option<String>>,
+    pub external_backend: ConfigValue<Option<String>>,
+    // 已规范化为小写、逗号分隔
+    pub open_domains: ConfigValue<Option<String>>,
+    // 被忽略的非法覆盖值，启动时写入日志
+    pub warnings: Vec<String>,
+}
+
+// 管理员策略：只读加载，不会被应用写回
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ManagedPolicy {
+    pub runtime: RuntimeSettings,
+    // false 表示禁用；不写表示不限制
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub telemetry: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updater: Option<bool>,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize)]
+pub struct PolicyFile {
+    pub path: Option<String>,
+    pub policy: ManagedPolicy,
+    // 文件存在但读取/解析失败（此时按无策略处理）
+    pub error: Option<String>,
+}
+
+// get_effective_config 的返回值：生效配置 + 策略锁定情况
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct EffectiveConfig {
+    pub runtime: RuntimeConfig,
+    pub policy_path: Option<String>,
+    pub policy_error: Option<String>,
+    // 被策略锁定的设置项，如 runtime.proxy、updater
+    pub locked: Vec<String>,
+    pub telemetry_enabled: bool,
+    pub updater_enabled: bool,
+    // 远程紧急开关的当前状态，由 get_effective_config 填入
+    pub kill_switch: Option<KillSwitch>,
+}
+
+pub struct ConfigState(pub EffectiveConfig);
+
+// 一条规则：versions 为 semver 范围（如 ">=1.4.0, <1.4.2"），命中当前版本时生效
+#[derive(Clone, Debug, serde::Deserialize)]
+struct KillSwitchRule {
+    versions: String,
+    #[serde(default)]
+    disable_updater: bool,
+    #[serde(default)]
+    disable_godebug: bool,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+struct KillSwitchManifest {
+    version: u64,
+    #[serde(default)]
+    rules: Vec<KillSwitchRule>,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize)]
+pub struct KillSwitch {
+    pub updater_disabled: bool,
+    pub godebug_disabled: bool,
+    // 命中规则给出的原因
+    pub reasons: Vec<String>,
+    // none / cache / remote
+    pub source: String,
+    pub version: Option<u64>,
+    pub fetched_at: Option<String>,
+}
+
+pub struct KillSwitchStore {
+    remote: Remote<KillSwitchManifest>,
+    current: Mutex<KillSwitch>,
+}
+
+pub struct KillSwitchState(pub KillSwitchStore);
+
+// 每个字段的环境变量名与命令行参数名
+const KEYS: [(&str, &str, &str); 8] = [
+    ("proxy", "NB_PROXY", "--proxy"),
+    ("data_dir", "NB_DATA_DIR", "--data-dir"),
+    ("log_level", "NB_LOG_LEVEL", "--log-level"),
+    ("backend_url", "NB_BACKEND_URL", "--backend-url"),
+    ("transport", "NB_TRANSPORT", "--transport"),
+    ("backend_port", "NB_BACKEND_PORT", "--backend-port"),
+    (
+        "external_backend",
+        "NB_EXTERNAL_BACKEND",
+        "--external-backend",
+    ),
+    ("open_domains", "NB_OPEN_DOMAINS", "--open-domains"),
+];
+
+fn field_mut<'a>(layer: &'a mut RuntimeSettings, key: &str) -> Option<&'a mut Option<String>> {
+    match key {
+        "proxy" => Some(&mut layer.proxy),
+        "data_dir" => Some(&mut layer.data_dir),
+        "log_level" => Some(&mut layer.log_level),
+        "backend_url" => Some(&mut layer.backend_url),
+        "transport" => Some(&mut layer.transport),
+        "backend_port" => Some(&mut layer.backend_port),
+        "external_backend" => Some(&mut layer.external_backend),
+        "open_domains" => Some(&mut layer.open_domains),
+        _ => None,
+    }
+}
+
+fn field<'a>(layer: &'a RuntimeSettings, key: &str) -> Option<&'a String> {
+    match key {
+        "proxy" => layer.proxy.as_ref(),
+        "data_dir" => layer.data_dir.as_ref(),
+        "log_level" => layer.log_level.as_ref(),
+        "backend_url" => layer.backend_url.as_ref(),
+        "transport" => layer.transport.as_ref(),
+        "backend_port" => layer.backend_port.as_ref(),
+        "external_backend" => layer.external_backend.as_ref(),
+        "open_domains" => layer.open_domains.as_ref(),
+        _ => None,
+    }
+    .filter(|v| !v.trim().is_empty())
+}
+
+fn check_url(value: &str, schemes: &[&str]) -> Result<String, String> {
+    let url = tauri::Url::parse(value.trim()).map_err(|e| format!("invalid url: {}", e))?;
+    if !schemes.contains(&url.scheme()) {
+        return Err(format!("unsupported scheme: {}", url.scheme()));
+    }
+    if url.host_str().unwrap_or_default().is_empty() {
+        return Err("missing host".to_string());
+    }
+    Ok(value.trim().trim_end_matches('/').to_string())
+}
+
+// 外部后端只能在本机：原生层按 127.0.0.1:<端口> 直连，规范化为 http://127.0.0.1:<端口>
+fn check_external(value: &str) -> Result<String, String> {
+    let port = match value.parse::<u16>() {
+        Ok(port) => port,
+        Err(_) => {
+            let url =
+                tauri::Url::parse(value).map_err(|e| format!("invalid url or port: {}", e))?;
+            if url.scheme() != "http" {
+                return Err(format!("unsupported scheme: {}", url.scheme()));
+            }
+            match url.host_str().unwrap_or_default() {
+                "127.0.0.1" | "localhost" => {}
+                host => return Err(format!("host must be loopback, got {}", host)),
+            }
+            if !matches!(url.path(), "" | "/" | "/api/v1" | "/api/v1/") {
+                return Err(format!("unexpected path: {}", url.path()));
+            }
+            url.port_or_known_default().unwrap_or(0)
+        }
+    };
+    if port == 0 {
+        return Err("port must not be 0".to_string());
+    }
+    Ok(format!("http://127.0.0.1:{}", port))
+}
+
+// 域名列表：逗号分隔，只允许字母数字、- 与 .，至少含一个点（不接受 com 这种整个顶级域）
+fn check_domains(value: &str) -> Result<String, String> {
+    let mut domains = Vec::new();
+    for domain in value.split(',').map(|d| d.trim().trim_end_matches('.')) {
+        if domain.is_empty() {
+            continue;
+        }
+        let domain = domain.to_ascii_lowercase();
+        let valid = domain.contains('.')
+            && !domain.starts_with('.')
+            && domain
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.');
+        if !valid {
+            return Err(format!("invalid domain: {}", domain));
+        }
+        domains.push(domain);
+    }
+    if domains.is_empty() {
+        return Err("expected at least one domain".to_string());
+    }
+    Ok(domains.join(","))
+}
+
+// 校验并规范化单个值
+fn check(key: &str, value: &str) -> Result<String, String> {
+    let value = value.trim();
+    match key {
+        "proxy" => check_url(value, &["http", "https", "socks5", "socks5h"]),
+        "backend_url" => check_url(value, &["http", "https"]),
+        "data_dir" => {
+            if Path::new(value).is_absolute() {
+                Ok(value.to_string())
+            } else {
+                Err("must be an absolute path".to_string())
+            }
+        }
+        "log_level" => {
+            let level = value.to_lowercase();
+            if LOG_LEVELS.contains(&level.as_str()) {
+                Ok(level)
+            } else {
+                Err(format!("expected one of {}", LOG_LEVELS.join("/")))
+            }
+        }
+        "transport" => {
+            let transport = value.to_lowercase();
+            if TRANSPORTS.contains(&transport.as_str()) {
+                Ok(transport)
+            } else {
+                Err(format!("expected one of {}", TRANSPORTS.join("/")))
+            }
+        }
+        // 低于 1024 的端口在 macOS/Linux 上需要特权
+        "backend_port" => match value.parse::<u16>() {
+            Ok(port) if port >= 1024 => Ok(port.to_string()),
+            _ => Err("expected a port between 1024 and 65535".to_string()),
+        },
+        "external_backend" => check_external(value),
+        "open_domains" => check_domains(value),
+        _ => Err("unknown key".to_string()),
+    }
+}
+
+// settings.json 里 runtime 段的校验（update_settings / import_settings 时调用）
+pub fn validate_layer(layer: &RuntimeSettings) -> Result<(), String> {
+    for (key, _, _) in KEYS {
+        if let Some(value) = field(layer, key) {
+            check(key, value).map_err(|e| format!("runtime.{} {}", key, e))?;
+        }
+    }
+    Ok(())
+}
+
+pub fn env_layer() -> RuntimeSettings {
+    let mut layer = RuntimeSettings::default();
+    for (key, env, _) in KEYS {
+        if let Some(slot) = field_mut(&mut layer, key) {
+            *slot = std::env::var(env).ok().filter(|v| !v.trim().is_empty());
+        }
+    }
+    layer
+}
+
+// 支持 --proxy=http://... 与 --proxy http://... 两种写法；未知参数（如 .nbp 路径、-psn_xxx）直接忽略
+pub fn cli_layer<I: IntoIterator<Item = String>>(args: I) -> RuntimeSettings {
+    let mut layer = RuntimeSettings::default();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        for (key, _, flag) in KEYS {
+            let value = if let Some(rest) = arg.strip_prefix(&format!("{}=", flag)) {
+                Some(rest.to_string())
+            } else if arg == flag {
+                args.next()
+            } else {
+                continue;
+            };
+            if let Some(slot) = field_mut(&mut layer, key) {
+                *slot = value;
+            }
+            break;
+        }
+    }
+    layer
+}
+
+impl RuntimeConfig {
+    // 从高到低取第一个合法值；非法值记一条警告并继续看下一层，避免一个拼写错误导致应用起不来
+    pub fn resolve(
+        policy: &RuntimeSettings,
+        file: &RuntimeSettings,
+        env: &RuntimeSettings,
+        cli: &RuntimeSettings,
+    ) -> Self {
+        let layers = [
+            (ConfigSource::Policy, policy),
+            (ConfigSource::Cli, cli),
+            (ConfigSource::Env, env),
+            (ConfigSource::File, file),
+        ];
+        let mut warnings = Vec::new();
+        let mut pick = |key: &str| -> Option<ConfigValue<String>> {
+            for (source, layer) in &layers {
+                let Some(raw) = field(layer, key) else {
+                    continue;
+                };
+                match check(key, raw) {
+                    Ok(value) => {
+                        return Some(ConfigValue {
+                            value,
+                            source: *source,
+                        })
+                    }
+                    Err(e) => warnings.push(format!(
+                        "ignored {} from {:?}: {} ({})",
+                        key, source, raw, e
+                    )),
+                }
+            }
+            None
+        };
+        let optional = |v: Option<ConfigValue<String>>| match v {
+            Some(v) => ConfigValue {
+                value: Some(v.value),
+                source: v.source,
+            },
+            None => ConfigValue {
+                value: None,
+                source: ConfigSource::Default,
+            },
+        };
+
+        let proxy = optional(pick("proxy"));
+        let data_dir = optional(pick("data_dir"));
+        let log_level = pick("log_level").unwrap_or(ConfigValue {
+            value: DEFAULT_LOG_LEVEL.to_string(),
+            source: ConfigSource::Default,
+        });
+        let backend_url = optional(pick("backend_url"));
+        let transport = pick("transport").unwrap_or(ConfigValue {
+            value: DEFAULT_TRANSPORT.to_string(),
+            source: ConfigSource::Default,
+        });
+        let backend_port = optional(pick("backend_port"));
+        let external_backend = optional(pick("external_backend"));
+        let open_domains = optional(pick("open_domains"));
+        Self {
+            proxy,
+            data_dir,
+            log_level,
+            backend_url,
+            transport,
+            backend_port,
+            external_backend,
+            open_domains,
+            warnings,
+        }
+    }
+
+    pub fn load(policy: &RuntimeSettings, file: &RuntimeSettings) -> Self {
+        Self::resolve(
+            policy,
+            file,
+            &env_layer(),
+            &cli_layer(std::env::args().skip(1)),
+        )
+    }
+
+    // 安全模式：不读 settings.json 与环境变量，只保留策略与本次启动显式传入的参数
+    pub fn load_safe(policy: &RuntimeSettings) -> Self {
+        Self::resolve(
+            policy,
+            &RuntimeSettings::default(),
+            &RuntimeSettings::default(),
+            &cli_layer(std::env::args().skip(1)),
+        )
+    }
+
+    pub fn open_domains(&self) -> Vec<String> {
+        match &self.open_domains.value {
+            Some(list) => list.split(',').map(str::to_string).collect(),
+            None => DEFAULT_OPEN_DOMAINS.iter().map(|d| d.to_string()).collect(),
+        }
+    }
+
+    // 数据根目录：未覆盖时沿用 AppData
+    pub fn data_base(&self, default: PathBuf) -> PathBuf {
+        self.data_dir
+            .value
+            .as_ref()
+            .map(PathBuf::from)
+            .unwrap_or(default)
+    }
+
+    // 已通过校验，解析不会失败
+    pub fn fixed_port(&self) -> Option<u16> {
+        self.backend_port
+            .value
+            .as_ref()
+            .and_then(|p| p.parse().ok())
+    }
+
+    // 外部后端的端口；已规范化为 http://127.0.0.1:<端口>
+    pub fn external_port(&self) -> Option<u16> {
+        self.external_backend
+            .value
+            .as_ref()
+            .and_then(|url| url.rsplit_once(':'))
+            .and_then(|(_, port)| port.parse().ok())
+    }
+
+    // 传给后端 sidecar 的环境变量：代理（Go 侧 http.ProxyFromEnvironment 读取）；数据目录由 supervisor 单独传入
+    pub fn sidecar_env(&self) -> Vec<(&'static str, String)> {
+        let mut env = Vec::new();
+        if let Some(proxy) = &self.proxy.value {
+            env.push(("HTTP_PROXY", proxy.clone()));
+            env.push(("HTTPS_PROXY", proxy.clone()));
+        }
+        env
+    }
+}
+
+// 级别越小越重要；未知级别按 info 处理
+pub fn level_rank(level: &str) -> usize {
+    let level = level.trim().to_lowercase();
+    let level = match level.as_str() {
+        "warning" => "warn",
+        "trace" => "debug",
+        other => other,
+    };
+    LOG_LEVELS.iter().position(|l| *l == level).unwrap_or(2)
+}
+
+// 系统级策略文件位置：普通用户不可写，由 MDM/组策略/配置管理工具下发
+fn policy_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    #[cfg(target_os = "macos")]
+    {
+        paths.push(PathBuf::from(
+            "/Library/Managed Preferences/com.dztool.banana/policy.json",
+        ));
+        paths.push(PathBuf::from(
+            "/Library/Application Support/com.dztool.banana/policy.json",
+        ));
+    }
+    #[cfg(target_os = "windows")]
+    {
+        let program_data =
+            std::env::var("ProgramData").unwrap_or_else(|_| "C:\\ProgramData".to_string());
+        paths.push(
+            PathBuf::from(program_data)
+                .join("com.dztool.banana")
+                .join("policy.json"),
+        );
+    }
+    #[cfg(all(unix, not(target_os = "macos")))]
+    {
+        paths.push(PathBuf::from("/etc/com.dztool.banana/policy.json"));
+    }
+    paths
+}
+
+fn read_policy(path: &Path) -> Result<ManagedPolicy, String> {
+    let meta = fs::metadata(path).map_err(|e| format!("read policy failed: {}", e))?;
+    if meta.len() > MAX_POLICY_BYTES {
+        return Err("policy file too large".to_string());
+    }
+    let bytes = fs::read(path).map_err(|e| format!("read policy failed: {}", e))?;
+    serde_json::from_slice(&bytes).map_err(|e| format!("parse policy failed: {}", e))
+}
+
+// 取第一个存在的策略文件；读取失败记录错误并按无策略处理，不阻塞启动
+pub fn load_policy() -> PolicyFile {
+    for path in policy_paths() {
+        if !path.is_file() {
+            continue;
+        }
+        let display = path.to_string_lossy().to_string();
+        return match read_policy(&path) {
+            Ok(policy) => PolicyFile {
+                path: Some(display),
+                policy,
+                error: None,
+            },
+            Err(e) => PolicyFile {
+                path: Some(display),
+                policy: ManagedPolicy::default(),
+                error: Some(e),
+            },
+        };
+    }
+    PolicyFile::default()
+}
+
+impl EffectiveConfig {
+    pub fn new(policy: &PolicyFile, runtime: RuntimeConfig) -> Self {
+        let mut locked: Vec<String> = KEYS
+            .iter()
+            .filter(|(key, _, _)| field(&policy.policy.runtime, key).is_some())
+            .map(|(key, _, _)| format!("runtime.{}", key))
+            .collect();
+        if policy.policy.telemetry.is_some() {
+            locked.push("telemetry".to_string());
+        }
+        if policy.policy.updater.is_some() {
+            locked.push("updater".to_string());
+        }
+        Self {
+            runtime,
+            policy_path: policy.path.clone(),
+            policy_error: policy.error.clone(),
+            locked,
+            telemetry_enabled: policy.policy.telemetry.unwrap_or(true),
+            updater_enabled: policy.policy.updater.unwrap_or(true),
+            kill_switch: None,
+        }
+    }
+
+    // 拒绝修改被策略锁定的 runtime 项（patch 为 update_settings 的局部更新）
+    pub fn check_patch(&self, patch: &serde_json::Value) -> Result<(), String> {
+        let Some(runtime) = patch.get("runtime").and_then(|v| v.as_object()) else {
+            return Ok(());
+        };
+        for key in runtime.keys() {
+            let name = format!("runtime.{}", key);
+            if self.locked.contains(&name) {
+                return Err(format!("{} is locked by policy", name));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn no_kill_switch() -> KillSwitch {
+    KillSwitch {
+        source: "none".to_string(),
+        ..Default::default()
+    }
+}
+
+impl crate::signed_manifest::Manifest for KillSwitchManifest {
+    fn version(&self) -> u64 {
+        self.version
+    }
+
+    fn check(&self) -> Result<(), String> {
+        if self.rules.len() > MAX_KILL_SWITCH_RULES {
+            return Err("kill switch has too many rules".to_string());
+        }
+        Ok(())
+    }
+}
+
+// 合并所有命中当前版本的规则；范围写错的规则跳过，不影响其他规则
+fn resolve_kill_switch(
+    manifest: &KillSwitchManifest,
+    app_version: &semver::Version,
+    source: &str,
+    fetched_at: Option<String>,
+) -> KillSwitch {
+    let mut kill = KillSwitch {
+        source: source.to_string(),
+        version: Some(manifest.version),
+        fetched_at,
+        ..Default::default()
+    };
+    for rule in &manifest.rules {
+        let hit = semver::VersionReq::parse(rule.versions.trim())
+            .is_ok_and(|req| req.matches(app_version));
+        if !hit {
+            continue;
+        }
+        kill.updater_disabled |= rule.disable_updater;
+        kill.godebug_disabled |= rule.disable_godebug;
+        if let Some(reason) = rule
+            .reason
+            .as_deref()
+            .map(str::trim)
+            .filter(|r| !r.is_empty())
+        {
+            kill.reasons.push(reason.to_string());
+        }
+    }
+    kill
+}
+
+impl KillSwitchStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            remote: Remote::new("kill switch", KILL_SWITCH_URL, path, MAX_KILL_SWITCH_BYTES),
+            current: Mutex::new(no_kill_switch()),
+        }
+    }
+
+    pub fn get(&self) -> KillSwitch {
+        self.current
+            .lock()
+            .map(|kill| kill.clone())
+            .unwrap_or_else(|_| no_kill_switch())
+    }
+
+    fn set(&self, kill: KillSwitch) {
+        if let Ok(mut current) = self.current.lock() {
+            *current = kill;
+        }
+    }
+}
+
+// 更新相关命令的统一检查：管理员策略优先，其次是远程紧急开关
+pub fn check_updater(app: &tauri::AppHandle) -> Result<(), String> {
+    if !app.state::<ConfigState>().0.updater_enabled {
+        return Err("updates are disabled by policy".to_string());
+    }
+    if app.state::<KillSwitchState>().0.get().updater_disabled {
+        return Err("updates are disabled remotely for this version".to_string());
+    }
+    Ok(())
+}
+
+// get_effective_config 的返回值：把紧急开关的状态合并进去，前端据此置灰更新设置
+pub fn effective(app: &tauri::AppHandle) -> EffectiveConfig {
+    let mut config = app.state::<ConfigState>().0.clone();
+    let kill = app.state::<KillSwitchState>().0.get();
+    config.updater_enabled &= !kill.updater_disabled;
+    config.kill_switch = Some(kill);
+    config
+}
+
+// supervisor 组装好 sidecar 环境变量后调用：命中规则时去掉 GODEBUG（不论来自默认值还是 sidecar.env）
+pub fn apply_kill_switch_env(app: &tauri::AppHandle, env: &mut Vec<(String, String)>) {
+    if !app.state::<KillSwitchState>().0.get().godebug_disabled {
+        return;
+    }
+    let before = env.len();
+    env.retain(|(key, _)| !key.eq_ignore_ascii_case("GODEBUG"));
+    if env.len() != before {
+        app.state::<crate::LogState>()
+            .log_app("INFO", "GODEBUG removed from sidecar env by kill switch");
+    }
+}
+
+// setup 阶段、启动 sidecar 之前调用：先同步应用缓存（验签通过的话），再在后台拉取最新规则；
+// 新规则对更新命令立即生效，GODEBUG 在下次启动后端时生效
+pub fn start_kill_switch(app: tauri::AppHandle) {
+    let state = app.state::<KillSwitchState>();
+    let store = &state.0;
+    let Ok(pubkey) = crate::updater_pubkey(&app) else {
+        return;
+    };
+    let app_version = semver::Version::parse(&app.package_info().version.to_string())
+        .unwrap_or_else(|_| semver::Version::new(0, 0, 0));
+    let cached_version = store
+        .remote
+        .cached(&app, &pubkey)
+        .map(|(manifest, fetched_at)| {
+            store.set(resolve_kill_switch(
+                &manifest,
+                &app_version,
+                "cache",
+                fetched_at,
+            ));
+            manifest.version
+        });
+    store.remote.refresh(
+        &app,
+        pubkey,
+        cached_version,
+        KILL_SWITCH_EVENT,
+        move |app, manifest, fetched_at| {
+            let kill = resolve_kill_switch(manifest, &app_version, "remote", Some(fetched_at));
+            app.state::<crate::LogState>().log_app(
+                "INFO",
+                &format!(
+                    "kill switch v{} applied (updater disabled: {}, GODEBUG disabled: {})",
+                    manifest.version, kill.updater_disabled, kill.godebug_disabled
+                ),
+            );
+            app.state::<KillSwitchState>().0.set(kill.clone());
+            kill
+        },
+    );
+}
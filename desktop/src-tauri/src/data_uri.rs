@@ -0,0 +1,208 @@
+use std::io::Cursor;
+
+use base64::Engine;
+
+use crate::library::{ImageMeta, Library, NewTask, TaskRecord};
+use crate::naming::{new_file_id, TemplateContext};
+use crate::organize;
+use crate::settings::Settings;
+use crate::storage::{normalize_id, Storage};
+
+// 后端以 data URI 返回图片时，由原生层一次完成解码、校验、落盘、缩略图与入库，
+// 前端不再依次调用写文件、生成缩略图、插入记录、写元数据四个接口
+const MAX_IMAGE_BYTES: usize = 100 * 1024 * 1024;
+const THUMB_SIZE: u32 = 256;
+const THUMB_QUALITY: u8 = 80;
+
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default)]
+pub struct DataUriMeta {
+    // 不填时按时间生成
+    pub task_id: String,
+    pub prompt: String,
+    pub provider_name: String,
+    pub model_id: String,
+    pub config_snapshot: String,
+    pub title: String,
+    pub project: String,
+    pub tags: Vec<String>,
+}
+
+fn decode(data: &str) -> Result<(String, Vec<u8>), String> {
+    let rest = data
+        .trim()
+        .strip_prefix("data:")
+        .ok_or_else(|| "not a data uri".to_string())?;
+    let (header, payload) = rest
+        .split_once(',')
+        .ok_or_else(|| "data uri has no payload".to_string())?;
+    let mut parts = header.split(';');
+    let mime = parts.next().unwrap_or_default().trim().to_ascii_lowercase();
+    if !parts.any(|p| p.trim().eq_ignore_ascii_case("base64")) {
+        return Err("data uri is not base64 encoded".to_string());
+    }
+    // 估算解码后的大小，超限时不分配内存
+    if payload.len() / 4 * 3 > MAX_IMAGE_BYTES {
+        return Err("data uri image is too large".to_string());
+    }
+    let cleaned: String = payload.chars().filter(|c| !c.is_whitespace()).collect();
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(cleaned.as_bytes())
+        .map_err(|e| format!("decode data uri failed: {}", e))?;
+    Ok((mime, bytes))
+}
+
+fn image_ext(format: Option<image::ImageFormat>) -> Result<&'static str, String> {
+    match format {
+        Some(image::ImageFormat::Png) => Ok("png"),
+        Some(image::ImageFormat::Jpeg) => Ok("jpg"),
+        Some(image::ImageFormat::WebP) => Ok("webp"),
+        _ => Err("unsupported image format".to_string()),
+    }
+}
+
+fn thumbnail(img: &image::DynamicImage) -> Result<Vec<u8>, String> {
+    let thumb = img.thumbnail(THUMB_SIZE, THUMB_SIZE).to_rgb8();
+    let mut out = Vec::new();
+    image::codecs::jpeg::JpegEncoder::new_with_quality(&mut out, THUMB_QUALITY)
+        .encode_image(&thumb)
+        .map_err(|e| format!("encode thumbnail failed: {}", e))?;
+    Ok(out)
+}
+
+pub fn persist(
+    storage: &dyn Storage,
+    library: &Library,
+    settings: &Settings,
+    data: &str,
+    meta: &DataUriMeta,
+    id_seed: u128,
+) -> Result<TaskRecord, String> {
+    let (mime, bytes) = decode(data)?;
+    let reader = image::ImageReader::new(Cursor::new(&bytes))
+        .with_guessed_format()
+        .map_err(|e| format!("read image failed: {}", e))?;
+    let format = reader.format();
+    let ext = image_ext(format)?;
+    // 声明的类型与实际内容不符时以内容为准，但明显不是图片的声明直接拒绝
+    if !mime.is_empty() && !mime.starts_with("image/") {
+        return Err(format!("data uri is not an image: {}", mime));
+    }
+    let img = reader
+        .decode()
+        .map_err(|e| format!("decode image failed: {}", e))?;
+    let (width, height) = (img.width() as i64, img.height() as i64);
+
+    let task_id = match meta.task_id.trim() {
+        "" => format!("local-{}", id_seed),
+        id => id.to_string(),
+    };
+    if task_id.len() > 64
+        || !task_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(format!("invalid task id: {}", task_id));
+    }
+
+    let mut conn = library.open()?;
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("begin transaction failed: {}", e))?;
+    if library.task_exists(&tx, &task_id)? {
+        return Err(format!("task already exists: {}", task_id));
+    }
+
+    let image_meta = ImageMeta {
+        title: meta.title.trim().to_string(),
+        project: meta.project.trim().to_string(),
+        tags: meta
+            .tags
+            .iter()
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+            .collect(),
+        ..Default::default()
+    };
+    let created_at = crate::library::db_timestamp();
+    let mut record = TaskRecord {
+        task_id: task_id.clone(),
+        prompt: meta.prompt.clone(),
+        provider_name: meta.provider_name.clone(),
+        model_id: meta.model_id.clone(),
+        status: "completed".to_string(),
+        image_url: String::new(),
+        local_path: String::new(),
+        thumbnail_path: String::new(),
+        width,
+        height,
+        config_snapshot: meta.config_snapshot.clone(),
+        created_at: created_at.clone(),
+    };
+    let dir = organize::target_dir(&settings.organize.rules, &record, &image_meta);
+    let name_ctx = TemplateContext {
+        id: &task_id,
+        prompt: &meta.prompt,
+        model: &meta.model_id,
+        provider: &meta.provider_name,
+        created_at: &created_at,
+        width,
+        height,
+        seq: 1,
+    };
+    let local_path = new_file_id(storage, &settings.naming, &dir, &name_ctx, ext)?;
+    // 缩略图沿用后端的命名约定：与主图同目录的 thumb_<主图名>.jpg
+    let (parent, stem) = match local_path.rsplit_once('/') {
+        Some((parent, name)) => (format!("{}/", parent), name),
+        None => (String::new(), local_path.as_str()),
+    };
+    let stem = stem.rsplit_once('.').map(|(s, _)| s).unwrap_or(stem);
+    let thumb_path = normalize_id(&format!("{}thumb_{}.jpg", parent, stem))?;
+    let thumb = thumbnail(&img)?;
+
+    storage.put(&local_path, &bytes)?;
+    if let Err(e) = storage.put(&thumb_path, &thumb) {
+        let _ = storage.delete(&local_path);
+        return Err(e);
+    }
+
+    let inserted = library
+        .insert_task(
+            &tx,
+            &NewTask {
+                task_id: &task_id,
+                prompt: &meta.prompt,
+                provider_name: &meta.provider_name,
+                model_id: &meta.model_id,
+                local_path: &local_path,
+                width,
+                height,
+                config_snapshot: &meta.config_snapshot,
+            },
+        )
+        .and_then(|_| library.update_paths(&tx, &task_id, &local_path, &thumb_path))
+        .and_then(|_| {
+            if image_meta.title.is_empty()
+                && image_meta.project.is_empty()
+                && image_meta.tags.is_empty()
+            {
+                Ok(())
+            } else {
+                library.put_meta(&tx, &task_id, &image_meta)
+            }
+        })
+        .and_then(|_| {
+            tx.commit()
+                .map_err(|e| format!("commit data uri image failed: {}", e))
+        });
+    // 入库失败时清理已写入的文件，避免留下没有记录的孤儿图片
+    if let Err(e) = inserted {
+        let _ = storage.delete(&local_path);
+        let _ = storage.delete(&thumb_path);
+        return Err(e);
+    }
+
+    record.local_path = local_path;
+    record.thumbnail_path = thumb_path;
+    Ok(record)
+}
@@ -0,0 +1,134 @@
+use std::fs;
+use std::io::Read;
+use std::path::PathBuf;
+
+use sha2::{Digest, Sha256};
+
+// 增量更新：发布时对上一版本的文件（应用更新包 / 后端二进制）做 bsdiff 再 gzip，
+// 客户端用本地旧文件打补丁还原出完整新文件。还原结果照常走 SHA-256 / 签名校验，
+// 任何一步失败都回退到完整下载
+pub const APP_PROGRESS_EVENT: &str = "app-update-progress";
+const MAX_PATCH_BYTES: u64 = 256 * 1024 * 1024;
+const MAX_OUTPUT_BYTES: u64 = 1024 * 1024 * 1024;
+
+// 清单里按“旧版本号”索引：{ "patches": { "1.2.0": { ... } } }
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct PatchEntry {
+    pub url: String,
+    // 补丁文件本身的 SHA-256
+    pub sha256: String,
+    // 补丁基准（旧文件）的 SHA-256，本地文件对不上就不能用这个补丁
+    pub base_sha256: String,
+    #[serde(default)]
+    pub size: Option<u64>,
+    // 还原后完整文件的大小
+    #[serde(default)]
+    pub target_size: Option<u64>,
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+// 补丁格式：gzip(bsdiff)
+pub fn apply(base: &[u8], patch: &[u8], target_size: Option<u64>) -> Result<Vec<u8>, String> {
+    if target_size.is_some_and(|size| size > MAX_OUTPUT_BYTES) {
+        return Err("patched file too large".to_string());
+    }
+    let mut decoder = flate2::read::GzDecoder::new(patch).take(MAX_OUTPUT_BYTES * 2);
+    let mut out = Vec::with_capacity(target_size.unwrap_or(0) as usize);
+    bsdiff::patch(base, &mut decoder, &mut out)
+        .map_err(|e| format!("apply patch failed: {}", e))?;
+    if out.len() as u64 > MAX_OUTPUT_BYTES {
+        return Err("patched file too large".to_string());
+    }
+    if let Some(size) = target_size {
+        if out.len() as u64 != size {
+            return Err(format!(
+                "patched size mismatch: expected {}, got {}",
+                size,
+                out.len()
+            ));
+        }
+    }
+    Ok(out)
+}
+
+// 下载补丁并应用到 base；返回还原后的完整文件，调用方负责校验最终结果
+pub async fn download_and_apply(
+    app: &tauri::AppHandle,
+    base: &[u8],
+    patch: &PatchEntry,
+    event: &str,
+) -> Result<Vec<u8>, String> {
+    let base_digest = sha256_hex(base);
+    if !base_digest.eq_ignore_ascii_case(patch.base_sha256.trim()) {
+        return Err("local file does not match patch base".to_string());
+    }
+    let bytes =
+        crate::sidecar_update::download(app, &patch.url, patch.size, MAX_PATCH_BYTES, event)
+            .await?;
+    let digest = sha256_hex(&bytes);
+    if !digest.eq_ignore_ascii_case(patch.sha256.trim()) {
+        return Err(format!(
+            "patch checksum mismatch: expected {}, got {}",
+            patch.sha256.trim(),
+            digest
+        ));
+    }
+    apply(base, &bytes, patch.target_size)
+}
+
+// 应用更新包没法从已安装的应用反推出来，每次安装时把更新包留一份，作为下次打补丁的基准。
+// 首次安装（从 dmg/安装包装的）没有基准，只能完整下载一次
+pub struct AppUpdateCache {
+    dir: PathBuf,
+}
+
+pub struct AppUpdateCacheState(pub AppUpdateCache);
+
+impl AppUpdateCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn path(&self, version: &str) -> PathBuf {
+        self.dir
+            .join(format!("{}.bundle", crate::naming::sanitize_stem(version)))
+    }
+
+    pub fn base(&self, version: &str) -> Option<Vec<u8>> {
+        fs::read(self.path(version)).ok()
+    }
+
+    // 只保留最新一份
+    pub fn store(&self, version: &str, bytes: &[u8]) -> Result<(), String> {
+        fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("create update cache dir failed: {}", e))?;
+        let target = self.path(version);
+        crate::storage::write_atomic(&target, bytes)?;
+        if let Ok(entries) = fs::read_dir(&self.dir) {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path != target && path.extension().is_some_and(|ext| ext == "bundle") {
+                    let _ = fs::remove_file(path);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// latest.json 里找到本次更新包所在的平台条目，读出针对当前版本的补丁
+pub fn app_patch(
+    raw_json: &serde_json::Value,
+    download_url: &str,
+    from: &str,
+) -> Option<PatchEntry> {
+    let platforms = raw_json.get("platforms")?.as_object()?;
+    let entry = platforms
+        .values()
+        .find(|entry| entry.get("url").and_then(|u| u.as_str()) == Some(download_url))?;
+    let patch = entry.get("patches")?.get(from)?;
+    serde_json::from_value(patch.clone()).ok()
+}
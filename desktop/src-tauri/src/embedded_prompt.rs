@@ -0,0 +1,390 @@
+use std::collections::BTreeMap;
+use std::io::Read;
+
+use encoding_rs::{Encoding, BIG5, GB18030, SHIFT_JIS, UTF_16BE, UTF_16LE, WINDOWS_1252};
+
+// 导入图片里嵌入的提示词：PNG 的 tEXt / zTXt / iTXt 文本块（SD WebUI 的 parameters、NovelAI 的
+// Description + Comment 等）与 JPEG / WebP 的 EXIF UserComment。PNG 规范要求 tEXt 为 Latin-1，
+// 但国内不少工具直接写 UTF-8 或 GBK，按 Latin-1 读出来就是乱码；这里按内容判断实际编码，
+// 并修复"UTF-8 被当成 Latin-1 再存一遍"的二次编码，统一成换行为 \n 的 UTF-8 文本
+const MAX_TEXT_BYTES: usize = 256 * 1024;
+// 这些键名的文本块可能是提示词，按优先级排列
+const PROMPT_KEYS: [&str; 4] = ["parameters", "Description", "prompt", "Comment"];
+const NEGATIVE_LABELS: [&str; 5] = [
+    "negative prompt",
+    "负面提示词",
+    "反向提示词",
+    "负向提示词",
+    "ネガティブプロンプト",
+];
+// UTF-8 以外的候选编码，按国内工具常见程度排列
+const LEGACY_ENCODINGS: [&Encoding; 3] = [GB18030, BIG5, SHIFT_JIS];
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct EmbeddedPrompt {
+    pub prompt: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub negative_prompt: Option<String>,
+    // Steps、Sampler、Seed 等参数，原样保留为文本
+    pub parameters: BTreeMap<String, String>,
+    // 来源，如 png:parameters、exif:UserComment
+    pub source: String,
+    // 判断出的原始编码；修复过二次编码时带 (repaired)
+    pub encoding: String,
+    pub raw: String,
+}
+
+// (正向提示词, 负面提示词, 参数)
+type EmbeddedParts = (String, Option<String>, BTreeMap<String, String>);
+
+struct TextChunk {
+    key: String,
+    bytes: Vec<u8>,
+    // iTXt 声明为 UTF-8
+    utf8: bool,
+}
+
+fn be32(b: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(b.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn inflate(data: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    flate2::read::ZlibDecoder::new(data)
+        .take(MAX_TEXT_BYTES as u64 + 1)
+        .read_to_end(&mut out)
+        .ok()?;
+    (out.len() <= MAX_TEXT_BYTES).then_some(out)
+}
+
+fn split_nul(data: &[u8]) -> Option<(&[u8], &[u8])> {
+    let index = data.iter().position(|b| *b == 0)?;
+    Some((&data[..index], &data[index + 1..]))
+}
+
+fn parse_chunk(kind: &[u8], data: &[u8]) -> Option<TextChunk> {
+    let (key, rest) = split_nul(data)?;
+    let key = String::from_utf8_lossy(key).to_string();
+    let (bytes, utf8) = match kind {
+        b"tEXt" => (rest.to_vec(), false),
+        // 压缩方式只有 0（zlib）
+        b"zTXt" => (inflate(rest.get(1..)?)?, false),
+        b"iTXt" => {
+            let (compressed, rest) = (*rest.first()?, rest.get(2..)?);
+            let (_language, rest) = split_nul(rest)?;
+            let (_translated, text) = split_nul(rest)?;
+            let bytes = if compressed == 1 {
+                inflate(text)?
+            } else {
+                text.to_vec()
+            };
+            (bytes, true)
+        }
+        _ => return None,
+    };
+    (bytes.len() <= MAX_TEXT_BYTES).then_some(TextChunk { key, bytes, utf8 })
+}
+
+// IDAT 之后也可能有文本块，一直读到 IEND
+fn png_chunks(bytes: &[u8]) -> Vec<TextChunk> {
+    let mut chunks = Vec::new();
+    if !bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return chunks;
+    }
+    let mut pos = 8;
+    while pos + 12 <= bytes.len() {
+        let Some(len) = be32(bytes, pos).map(|l| l as usize) else {
+            break;
+        };
+        let kind = &bytes[pos + 4..pos + 8];
+        if kind == b"IEND" {
+            break;
+        }
+        let Some(data) = bytes.get(pos + 8..pos + 8 + len) else {
+            break;
+        };
+        if let Some(chunk) = parse_chunk(kind, data) {
+            chunks.push(chunk);
+        }
+        pos += 12 + len;
+    }
+    chunks
+}
+
+// 文本看起来像正常文字的程度：ASCII、中日韩文字与标点加分，其余扣分
+fn plausibility(text: &str) -> i64 {
+    text.chars()
+        .map(|c| match c as u32 {
+            0x09 | 0x0A | 0x0D | 0x20..=0x7E => 1,
+            0x3000..=0x30FF | 0x4E00..=0x9FFF | 0xAC00..=0xD7AF | 0xFF00..=0xFFEF => 2,
+            0x00..=0x1F | 0x7F..=0x9F | 0xFFFD => -10,
+            _ => -1,
+        })
+        .sum()
+}
+
+fn decode_strict(encoding: &'static Encoding, bytes: &[u8]) -> Option<String> {
+    encoding
+        .decode_without_bom_handling_and_without_replacement(bytes)
+        .map(|text| text.into_owned())
+}
+
+// UTF-8 或 GBK 先被当成 Latin-1 / CP1252 读出、再按 UTF-8 存下的文本（"ä½ å¥½"、"ÄãºÃ"）还原成原文
+fn repair_double_encoding(text: &str) -> Option<(String, &'static str)> {
+    if text.is_ascii() || text.chars().any(|c| c as u32 > 0x2122) {
+        return None;
+    }
+    let bytes: Vec<u8> = if text.chars().all(|c| (c as u32) < 0x100) {
+        text.chars().map(|c| c as u32 as u8).collect()
+    } else {
+        let (bytes, _, unmappable) = WINDOWS_1252.encode(text);
+        if unmappable {
+            return None;
+        }
+        bytes.into_owned()
+    };
+    let (repaired, encoding) = match String::from_utf8(bytes) {
+        Ok(repaired) => (repaired, "utf-8"),
+        Err(e) => (decode_strict(GB18030, e.as_bytes())?, "gb18030"),
+    };
+    (!repaired.is_ascii() && plausibility(&repaired) > plausibility(text))
+        .then_some((repaired, encoding))
+}
+
+// 返回文本与判断出的编码
+fn decode_text(bytes: &[u8]) -> (String, String) {
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return match repair_double_encoding(text) {
+            Some((repaired, encoding)) => (repaired, format!("{} (repaired)", encoding)),
+            None => (text.to_string(), "utf-8".to_string()),
+        };
+    }
+    let best = LEGACY_ENCODINGS
+        .iter()
+        .filter_map(|encoding| Some((*encoding, decode_strict(encoding, bytes)?)))
+        .max_by_key(|(_, text)| plausibility(text));
+    if let Some((encoding, text)) = best.filter(|(_, text)| plausibility(text) > 0) {
+        return (text, encoding.name().to_ascii_lowercase());
+    }
+    // 最后按规范的 Latin-1 读
+    (
+        bytes.iter().map(|b| *b as char).collect(),
+        "latin-1".to_string(),
+    )
+}
+
+fn decode_utf16(data: &[u8], little_first: bool) -> (String, String) {
+    if let Some((encoding, _)) = Encoding::for_bom(data) {
+        let (text, _) = encoding.decode_with_bom_removal(data);
+        return (text.into_owned(), encoding.name().to_ascii_lowercase());
+    }
+    let order = if little_first {
+        [UTF_16LE, UTF_16BE]
+    } else {
+        [UTF_16BE, UTF_16LE]
+    };
+    // 多数写入方固定用大端，与 TIFF 本身的字节序无关，两种都试
+    let candidates = order.map(|encoding| {
+        let (text, _) = encoding.decode_without_bom_handling(data);
+        (encoding, text.into_owned())
+    });
+    let [first, second] = candidates;
+    let (encoding, text) = if plausibility(&second.1) > plausibility(&first.1) {
+        second
+    } else {
+        first
+    };
+    (text, encoding.name().to_ascii_lowercase())
+}
+
+// UserComment 前 8 字节为字符集：ASCII / UNICODE / JIS / 全 0（未定义）
+fn decode_user_comment(comment: &[u8], little: bool) -> Option<(String, String)> {
+    let (header, data) = (comment.get(..8)?, comment.get(8..)?);
+    if data.is_empty() || data.len() > MAX_TEXT_BYTES {
+        return None;
+    }
+    Some(match header {
+        b"UNICODE\0" => decode_utf16(data, little),
+        b"JIS\0\0\0\0\0" => {
+            let (text, _) = encoding_rs::ISO_2022_JP.decode_without_bom_handling(data);
+            (text.into_owned(), "iso-2022-jp".to_string())
+        }
+        _ => decode_text(data),
+    })
+}
+
+fn normalize(text: &str) -> String {
+    text.replace("\r\n", "\n")
+        .replace('\r', "\n")
+        .replace('\0', "")
+        .trim()
+        .to_string()
+}
+
+// "Negative prompt:" 及常见的中文、日文写法，冒号可为全角
+fn negative_label(line: &str) -> Option<&str> {
+    let lower = line.to_lowercase();
+    NEGATIVE_LABELS.iter().find_map(|label| {
+        if !lower.starts_with(label) {
+            return None;
+        }
+        let rest = line.get(label.len()..)?.trim_start();
+        rest.strip_prefix(':')
+            .or_else(|| rest.strip_prefix('：'))
+            .map(str::trim_start)
+    })
+}
+
+// "Steps: 20, Sampler: Euler a, Lora hashes: "a: 1, b: 2"" 这样的参数行；值可带引号，分隔符可为全角
+fn parse_parameters(line: &str) -> Option<BTreeMap<String, String>> {
+    let line = line.replace('：', ":").replace('，', ",");
+    let mut params = BTreeMap::new();
+    let mut rest = line.trim();
+    while !rest.is_empty() {
+        let (key, value_part) = rest.split_once(':')?;
+        let key = key.trim();
+        if key.is_empty() || key.len() > 64 {
+            return None;
+        }
+        let value_part = value_part.trim_start();
+        let (value, next) = if let Some(quoted) = value_part.strip_prefix('"') {
+            let end = quoted.find('"')?;
+            let next = quoted[end + 1..].trim_start();
+            (&quoted[..end], next.strip_prefix(',').unwrap_or(next))
+        } else {
+            match value_part.split_once(',') {
+                Some((value, next)) => (value, next),
+                None => (value_part, ""),
+            }
+        };
+        params.insert(key.to_string(), value.trim().to_string());
+        rest = next.trim_start();
+    }
+    (!params.is_empty()).then_some(params)
+}
+
+// SD WebUI 格式：正向提示词若干行、可选的 Negative prompt 行、最后一行参数
+fn parse_webui(text: &str) -> EmbeddedParts {
+    let mut lines: Vec<&str> = text.lines().collect();
+    let parameters = match lines.last() {
+        Some(last) if last.contains("Steps") || last.contains("步数") => {
+            let parsed = parse_parameters(last);
+            if parsed.is_some() {
+                lines.pop();
+            }
+            parsed.unwrap_or_default()
+        }
+        _ => BTreeMap::new(),
+    };
+    let mut prompt = Vec::new();
+    let mut negative: Option<Vec<&str>> = None;
+    for line in lines {
+        match (&mut negative, negative_label(line)) {
+            (None, Some(first)) => negative = Some(vec![first]),
+            (Some(negative), _) => negative.push(line),
+            (None, None) => prompt.push(line),
+        }
+    }
+    (
+        prompt.join("\n").trim().to_string(),
+        negative
+            .map(|lines| lines.join("\n").trim().to_string())
+            .filter(|n| !n.is_empty()),
+        parameters,
+    )
+}
+
+fn scalar_text(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::String(s) => Some(s.clone()),
+        serde_json::Value::Number(n) => Some(n.to_string()),
+        serde_json::Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+// NovelAI：Description 为正向提示词，Comment 为 JSON，uc 为负面提示词
+fn parse_novelai(description: &str, comment: Option<&str>) -> EmbeddedParts {
+    let mut parameters = BTreeMap::new();
+    let mut negative = None;
+    let json = comment.and_then(|c| serde_json::from_str::<serde_json::Value>(c).ok());
+    if let Some(serde_json::Value::Object(map)) = json {
+        for (key, value) in map {
+            if key == "uc" {
+                negative = scalar_text(&value).filter(|n| !n.trim().is_empty());
+            } else if key != "prompt" {
+                if let Some(text) = scalar_text(&value) {
+                    parameters.insert(key, text);
+                }
+            }
+        }
+    }
+    (description.to_string(), negative, parameters)
+}
+
+fn find_chunk<'a>(
+    chunks: &'a [(String, String, String)],
+    key: &str,
+) -> Option<&'a (String, String, String)> {
+    chunks
+        .iter()
+        .find(|(k, _, text)| k.eq_ignore_ascii_case(key) && !text.is_empty())
+}
+
+fn from_png(bytes: &[u8]) -> Option<EmbeddedPrompt> {
+    // (键名, 编码, 文本)
+    let chunks: Vec<(String, String, String)> = png_chunks(bytes)
+        .into_iter()
+        .map(|chunk| {
+            let (text, encoding) = match (chunk.utf8, String::from_utf8(chunk.bytes.clone())) {
+                (true, Ok(text)) => (text, "utf-8".to_string()),
+                _ => decode_text(&chunk.bytes),
+            };
+            (chunk.key, encoding, normalize(&text))
+        })
+        .collect();
+    let (key, encoding, text) = PROMPT_KEYS.iter().find_map(|key| {
+        let chunk = find_chunk(&chunks, key)?;
+        // ComfyUI 的 prompt 是节点图 JSON，不是提示词；没有 Description 时的 JSON Comment 同理
+        (!chunk.2.starts_with('{')).then_some(chunk)
+    })?;
+    let (prompt, negative_prompt, parameters) = if key.eq_ignore_ascii_case("Description") {
+        parse_novelai(text, find_chunk(&chunks, "Comment").map(|c| c.2.as_str()))
+    } else {
+        parse_webui(text)
+    };
+    Some(EmbeddedPrompt {
+        prompt,
+        negative_prompt,
+        parameters,
+        source: format!("png:{}", key),
+        encoding: encoding.clone(),
+        raw: text.clone(),
+    })
+}
+
+fn from_exif(bytes: &[u8]) -> Option<EmbeddedPrompt> {
+    let (comment, little) = crate::geotag::user_comment(bytes)?;
+    let (text, encoding) = decode_user_comment(&comment, little)?;
+    let text = normalize(&text);
+    if text.is_empty() {
+        return None;
+    }
+    let (prompt, negative_prompt, parameters) = parse_webui(&text);
+    Some(EmbeddedPrompt {
+        prompt,
+        negative_prompt,
+        parameters,
+        source: "exif:UserComment".to_string(),
+        encoding,
+        raw: text,
+    })
+}
+
+// 没有可识别的提示词时返回 None
+pub fn read(bytes: &[u8]) -> Option<EmbeddedPrompt> {
+    from_png(bytes)
+        .or_else(|| from_exif(bytes))
+        .filter(|p| !p.prompt.is_empty() || p.negative_prompt.is_some())
+}
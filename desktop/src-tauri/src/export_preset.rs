@@ -0,0 +1,293 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use image::{DynamicImage, RgbaImage};
+
+use crate::fonts::FontState;
+use crate::library::{Library, TaskRecord};
+use crate::resize::{OutputFormat, ResizeParams};
+use crate::settings::{ExportPreset, ExportSettings, ExportWatermark, MetadataPolicy};
+use crate::storage::{normalize_id, write_atomic, Storage};
+use crate::text::{Anchor, TextOptions, TextPos, TextRenderer, TextState};
+
+// 导出预设：按名字取出设置里的交付规格，一次调用完成转码、缩放、加水印、处理元数据并写到目标目录。
+// 同名文件追加 -2、-3 ...，不覆盖目录里已有的文件；单张失败不影响其余
+const MAX_DIMENSION: u32 = 8192;
+const MAX_IDS: usize = 1000;
+const DEFAULT_WATERMARK_SIZE: f32 = 3.0;
+const DEFAULT_WATERMARK_MARGIN: f32 = 2.0;
+const DEFAULT_WATERMARK_COLOR: &str = "#ffffffb3";
+const MIN_WATERMARK_PX: f32 = 8.0;
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct ExportedItem {
+    pub task_id: String,
+    pub path: String,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct ExportFailure {
+    pub task_id: String,
+    pub error: String,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize)]
+pub struct ExportReport {
+    pub preset: String,
+    pub destination: String,
+    pub exported: Vec<ExportedItem>,
+    pub failed: Vec<ExportFailure>,
+    // 用户中途取消时为 true，剩余的不再导出
+    pub cancelled: bool,
+}
+
+fn anchor(position: Option<&str>) -> Result<Anchor, String> {
+    let position = position.unwrap_or("bottom-right").trim();
+    serde_json::from_value(serde_json::Value::String(position.to_string()))
+        .map_err(|_| format!("invalid watermark position: {}", position))
+}
+
+fn validate_watermark(mark: &ExportWatermark) -> Result<(), String> {
+    if mark.text.trim().is_empty() {
+        return Err("watermark text is empty".to_string());
+    }
+    if let Some(size) = mark.size_percent {
+        if !(size.is_finite() && size > 0.0 && size <= 50.0) {
+            return Err("watermark size_percent must be between 0 and 50".to_string());
+        }
+    }
+    if let Some(margin) = mark.margin_percent {
+        if !(margin.is_finite() && (0.0..=25.0).contains(&margin)) {
+            return Err("watermark margin_percent must be between 0 and 25".to_string());
+        }
+    }
+    if let Some(color) = &mark.color {
+        crate::text::parse_color(color)?;
+    }
+    anchor(mark.position.as_deref()).map(drop)
+}
+
+fn validate_preset(preset: &ExportPreset) -> Result<(), String> {
+    let destination = preset.destination.trim();
+    if destination.is_empty() {
+        return Err("destination is empty".to_string());
+    }
+    if !Path::new(destination).is_absolute() {
+        return Err("destination must be an absolute path".to_string());
+    }
+    if let Some(format) = &preset.format {
+        OutputFormat::parse(format)
+            .ok_or_else(|| format!("format is not supported: {}", format))?;
+    }
+    if let Some(max) = preset.max_dimension {
+        if max == 0 || max > MAX_DIMENSION {
+            return Err(format!(
+                "max_dimension must be between 1 and {}",
+                MAX_DIMENSION
+            ));
+        }
+    }
+    if let Some(quality) = preset.quality {
+        if !(1..=100).contains(&quality) {
+            return Err("quality must be between 1 and 100".to_string());
+        }
+    }
+    match &preset.watermark {
+        Some(mark) => validate_watermark(mark),
+        None => Ok(()),
+    }
+}
+
+pub fn validate(settings: &ExportSettings) -> Result<(), String> {
+    let mut names = HashSet::new();
+    for preset in &settings.presets {
+        let name = preset.name.trim();
+        if name.is_empty() {
+            return Err("export preset name is empty".to_string());
+        }
+        if !names.insert(name.to_lowercase()) {
+            return Err(format!("duplicate export preset: {}", name));
+        }
+        validate_preset(preset).map_err(|e| format!("export preset {}: {}", name, e))?;
+    }
+    Ok(())
+}
+
+// 名字不区分大小写
+pub fn find<'a>(settings: &'a ExportSettings, name: &str) -> Result<&'a ExportPreset, String> {
+    let name = name.trim();
+    settings
+        .presets
+        .iter()
+        .find(|p| p.name.trim().eq_ignore_ascii_case(name))
+        .ok_or_else(|| format!("export preset not found: {}", name))
+}
+
+fn draw_watermark(
+    renderer: &mut TextRenderer,
+    img: &mut RgbaImage,
+    mark: &ExportWatermark,
+) -> Result<(), String> {
+    let (width, height) = (img.width() as f32, img.height() as f32);
+    let short = width.min(height);
+    let size = (short * mark.size_percent.unwrap_or(DEFAULT_WATERMARK_SIZE) / 100.0)
+        .clamp(MIN_WATERMARK_PX, 2000.0);
+    let margin = short * mark.margin_percent.unwrap_or(DEFAULT_WATERMARK_MARGIN) / 100.0;
+    let anchor = anchor(mark.position.as_deref())?;
+    let (fx, fy) = anchor.factors();
+    let pos = TextPos {
+        x: margin + (width - margin * 2.0) * fx,
+        y: margin + (height - margin * 2.0) * fy,
+        anchor,
+    };
+    let color = crate::text::parse_color(mark.color.as_deref().unwrap_or(DEFAULT_WATERMARK_COLOR))?;
+    let options = TextOptions {
+        max_width: Some((width - margin * 2.0).max(size)),
+        ..Default::default()
+    };
+    crate::text::draw_text(
+        renderer,
+        img,
+        mark.text.trim(),
+        mark.font.as_deref(),
+        size,
+        pos,
+        color,
+        &options,
+    )
+}
+
+struct Exporter<'a> {
+    storage: &'a dyn Storage,
+    text_state: &'a TextState,
+    fonts: &'a FontState,
+    preset: &'a ExportPreset,
+    folder: PathBuf,
+}
+
+impl Exporter<'_> {
+    fn render(&self, source: &[u8], format: OutputFormat) -> Result<Vec<u8>, String> {
+        let preset = self.preset;
+        let img =
+            image::load_from_memory(source).map_err(|e| format!("decode image failed: {}", e))?;
+        let params = ResizeParams {
+            width: preset.max_dimension,
+            height: preset.max_dimension,
+            format: Some(format),
+            quality: preset.quality,
+        };
+        let mut img = crate::resize::shrink(img, &params);
+        if let Some(mark) = &preset.watermark {
+            let mut rgba = img.to_rgba8();
+            let mut guard = self
+                .text_state
+                .0
+                .lock()
+                .map_err(|_| "text renderer lock poisoned".to_string())?;
+            let renderer = guard.get_or_insert_with(|| TextRenderer::new(self.fonts.database()));
+            draw_watermark(renderer, &mut rgba, mark)?;
+            img = DynamicImage::ImageRgba8(rgba);
+        }
+        crate::resize::encode(&img, format, preset.quality)
+    }
+
+    fn export(&self, task: &TaskRecord) -> Result<PathBuf, String> {
+        let preset = self.preset;
+        let id = normalize_id(&task.local_path)
+            .map_err(|_| format!("task {} has no local image", task.task_id))?;
+        let source = self.storage.get(&id)?;
+        let original = OutputFormat::from_id(&id);
+        let format = preset
+            .format
+            .as_deref()
+            .and_then(OutputFormat::parse)
+            .unwrap_or(original);
+        let reencode = format != original
+            || preset.max_dimension.is_some()
+            || preset.watermark.is_some()
+            || preset.metadata == MetadataPolicy::StripAll;
+        // 重新编码的图片本来就不带原图的元数据；原样复制时按策略处理
+        let (bytes, ext) = if reencode {
+            (self.render(&source, format)?, format.ext().to_string())
+        } else {
+            let ext = Path::new(&id)
+                .extension()
+                .and_then(|e| e.to_str())
+                .unwrap_or(original.ext())
+                .to_ascii_lowercase();
+            let bytes = match preset.metadata {
+                MetadataPolicy::StripLocation => {
+                    crate::geotag::strip_location(&source).unwrap_or(source)
+                }
+                _ => source,
+            };
+            (bytes, ext)
+        };
+        let stem = Path::new(&id)
+            .file_stem()
+            .map(|s| crate::naming::sanitize_stem(&s.to_string_lossy()))
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(|| crate::naming::sanitize_stem(&task.task_id));
+        let path = crate::publish::target_path(&self.folder, &stem, &ext)?;
+        write_atomic(&path, &bytes)?;
+        Ok(path)
+    }
+}
+
+pub fn export(
+    storage: &dyn Storage,
+    library: &Library,
+    text_state: &TextState,
+    fonts: &FontState,
+    preset: &ExportPreset,
+    ids: &[String],
+    progress: &mut dyn FnMut(usize, usize) -> bool,
+) -> Result<ExportReport, String> {
+    if ids.is_empty() {
+        return Err("ids is empty".to_string());
+    }
+    if ids.len() > MAX_IDS {
+        return Err(format!("too many ids (max {})", MAX_IDS));
+    }
+    // 设置文件可能被手动改过，执行前再校验一次
+    validate_preset(preset).map_err(|e| format!("export preset {}: {}", preset.name, e))?;
+    let folder = PathBuf::from(preset.destination.trim());
+    std::fs::create_dir_all(&folder)
+        .map_err(|e| format!("create export folder failed: {} ({})", e, folder.display()))?;
+    let exporter = Exporter {
+        storage,
+        text_state,
+        fonts,
+        preset,
+        folder,
+    };
+
+    let conn = library.open()?;
+    let mut report = ExportReport {
+        preset: preset.name.trim().to_string(),
+        destination: exporter.folder.to_string_lossy().to_string(),
+        ..Default::default()
+    };
+    for (i, id) in ids.iter().enumerate() {
+        if !progress(i, ids.len()) {
+            report.cancelled = true;
+            break;
+        }
+        let id = id.trim();
+        let result = library
+            .get_task(&conn, id)?
+            .ok_or_else(|| format!("task not found: {}", id))
+            .and_then(|task| exporter.export(&task));
+        match result {
+            Ok(path) => report.exported.push(ExportedItem {
+                task_id: id.to_string(),
+                path: path.to_string_lossy().to_string(),
+            }),
+            Err(error) => report.failed.push(ExportFailure {
+                task_id: id.to_string(),
+                error,
+            }),
+        }
+    }
+    Ok(report)
+}
@@ -0,0 +1,217 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use tauri::Manager;
+
+use crate::signed_manifest::Remote;
+
+// 功能开关：默认值编译在这里；构建时配置了 NB_FLAGS_URL 时，启动后拉取一份用 updater 密钥签名的清单覆盖默认值，
+// 有风险的功能可按比例灰度、出问题时在服务端关掉，不必发新版。清单的拉取、验签与缓存（AppData/feature-flags.json）
+// 见 signed_manifest.rs。安全模式下只用默认值
+pub const EVENT: &str = "feature-flags-updated";
+const REMOTE_URL: Option<&str> = option_env!("NB_FLAGS_URL");
+const MAX_MANIFEST_BYTES: usize = 64 * 1024;
+const MAX_FLAGS: usize = 200;
+const DEFAULTS: [(&str, bool); 4] = [
+    // 配额用尽时自动换下一把 API Key，见 key_slots.rs
+    ("key_slot_failover", true),
+    // 启动 sidecar.extra 里声明的其他 sidecar，见 sidecar_manager.rs
+    ("extra_sidecars", true),
+    ("prompt_translation", true),
+    ("embedded_prompt_import", true),
+];
+// 本机灰度分桶在缓存文件里的字段名，首次生成后固定
+const BUCKET_KEY: &str = "bucket";
+
+// 单个开关：直接写布尔值，或 {enabled, rollout}（按 0-99 的本机分桶灰度）
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(untagged)]
+enum RemoteFlag {
+    Plain(bool),
+    Rollout {
+        enabled: bool,
+        #[serde(default)]
+        rollout: Option<u32>,
+        #[serde(default)]
+        min_version: Option<String>,
+    },
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+struct Manifest {
+    version: u64,
+    #[serde(default)]
+    flags: BTreeMap<String, RemoteFlag>,
+}
+
+impl crate::signed_manifest::Manifest for Manifest {
+    fn version(&self) -> u64 {
+        self.version
+    }
+
+    fn check(&self) -> Result<(), String> {
+        if self.flags.len() > MAX_FLAGS {
+            return Err("flag manifest has too many flags".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct FeatureFlags {
+    pub flags: BTreeMap<String, bool>,
+    // defaults / cache / remote
+    pub source: String,
+    pub version: Option<u64>,
+    pub fetched_at: Option<String>,
+}
+
+pub struct FeatureFlagStore {
+    remote: Remote<Manifest>,
+    // 安全模式下只用默认值
+    defaults_only: bool,
+    current: Mutex<FeatureFlags>,
+}
+
+pub struct FeatureFlagState(pub FeatureFlagStore);
+
+fn defaults() -> FeatureFlags {
+    FeatureFlags {
+        flags: DEFAULTS
+            .iter()
+            .map(|(name, on)| (name.to_string(), *on))
+            .collect(),
+        source: "defaults".to_string(),
+        version: None,
+        fetched_at: None,
+    }
+}
+
+fn flag_on(flag: &RemoteFlag, bucket: u32, app_version: &semver::Version) -> bool {
+    match flag {
+        RemoteFlag::Plain(on) => *on,
+        RemoteFlag::Rollout {
+            enabled,
+            rollout,
+            min_version,
+        } => {
+            let version_ok = min_version
+                .as_deref()
+                .and_then(|v| semver::Version::parse(v.trim_start_matches('v')).ok())
+                .is_none_or(|min| *app_version >= min);
+            *enabled && version_ok && bucket < rollout.unwrap_or(100)
+        }
+    }
+}
+
+fn resolve(
+    manifest: &Manifest,
+    bucket: u32,
+    app_version: &semver::Version,
+    source: &str,
+    fetched_at: Option<String>,
+) -> FeatureFlags {
+    let mut flags = defaults();
+    for (name, flag) in &manifest.flags {
+        flags
+            .flags
+            .insert(name.clone(), flag_on(flag, bucket, app_version));
+    }
+    flags.source = source.to_string();
+    flags.version = Some(manifest.version);
+    flags.fetched_at = fetched_at;
+    flags
+}
+
+fn new_bucket() -> u32 {
+    let mut bytes = [0u8; 4];
+    let _ = getrandom::fill(&mut bytes);
+    u32::from_le_bytes(bytes) % 100
+}
+
+impl FeatureFlagStore {
+    pub fn new(path: PathBuf, defaults_only: bool) -> Self {
+        Self {
+            remote: Remote::new("feature flags", REMOTE_URL, path, MAX_MANIFEST_BYTES),
+            defaults_only,
+            current: Mutex::new(defaults()),
+        }
+    }
+
+    // 分桶缺失时补上并写回
+    fn bucket(&self) -> u32 {
+        let mut cache = self.remote.read();
+        if let Some(bucket) = cache.extra.get(BUCKET_KEY).and_then(|b| b.as_u64()) {
+            return bucket as u32;
+        }
+        let bucket = new_bucket();
+        cache.extra.insert(BUCKET_KEY.to_string(), bucket.into());
+        let _ = self.remote.write(&cache);
+        bucket
+    }
+
+    pub fn get(&self) -> FeatureFlags {
+        self.current
+            .lock()
+            .map(|flags| flags.clone())
+            .unwrap_or_else(|_| defaults())
+    }
+
+    pub fn enabled(&self, name: &str) -> bool {
+        self.get().flags.get(name).copied().unwrap_or(false)
+    }
+
+    fn set(&self, flags: FeatureFlags) {
+        if let Ok(mut current) = self.current.lock() {
+            *current = flags;
+        }
+    }
+}
+
+pub fn enabled(app: &tauri::AppHandle, name: &str) -> bool {
+    app.state::<FeatureFlagState>().0.enabled(name)
+}
+
+// setup 阶段调用：先用缓存（验签通过的话）顶上，再在后台拉取最新清单
+pub fn start(app: tauri::AppHandle) {
+    let state = app.state::<FeatureFlagState>();
+    let store = &state.0;
+    if store.defaults_only {
+        return;
+    }
+    let Ok(pubkey) = crate::updater_pubkey(&app) else {
+        return;
+    };
+    let app_version = semver::Version::parse(&app.package_info().version.to_string())
+        .unwrap_or_else(|_| semver::Version::new(0, 0, 0));
+    let bucket = store.bucket();
+    let cached_version = store
+        .remote
+        .cached(&app, &pubkey)
+        .map(|(manifest, fetched_at)| {
+            store.set(resolve(
+                &manifest,
+                bucket,
+                &app_version,
+                "cache",
+                fetched_at,
+            ));
+            manifest.version
+        });
+    store.remote.refresh(
+        &app,
+        pubkey,
+        cached_version,
+        EVENT,
+        move |app, manifest, fetched_at| {
+            let flags = resolve(manifest, bucket, &app_version, "remote", Some(fetched_at));
+            app.state::<crate::LogState>().log_app(
+                "INFO",
+                &format!("feature flags v{} applied", manifest.version),
+            );
+            app.state::<FeatureFlagState>().0.set(flags.clone());
+            flags
+        },
+    );
+}
@@ -0,0 +1,383 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use base64::Engine;
+use tauri::Manager;
+
+// 应用内反馈：由 Rust 组装并发送，反馈地址与密钥在构建时写入（NB_FEEDBACK_URL / NB_FEEDBACK_KEY），
+// 不经过前端也不在 settings.json 里。提交时就把日志（已脱敏）与主窗口截图一起存进 AppData/feedback/
+// 的队列，离线或服务端暂时不可用时留在队列里，由后台线程定时重试，发送成功后删除
+const ENDPOINT: Option<&str> = option_env!("NB_FEEDBACK_URL");
+const API_KEY: Option<&str> = option_env!("NB_FEEDBACK_KEY");
+const RETRY_INTERVAL: Duration = Duration::from_secs(120);
+const UPLOAD_TIMEOUT: Duration = Duration::from_secs(60);
+const MAX_TEXT_CHARS: usize = 10_000;
+const MAX_QUEUED: usize = 50;
+const MAX_ATTEMPTS: u32 = 30;
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+struct QueuedFeedback {
+    id: String,
+    text: String,
+    created_at: String,
+    app_version: String,
+    os: String,
+    arch: String,
+    // 附件与记录同目录：<id>-logs.zip / <id>-screenshot.png
+    has_logs: bool,
+    has_screenshot: bool,
+    #[serde(default)]
+    attempts: u32,
+    #[serde(default)]
+    last_error: Option<String>,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct FeedbackStatus {
+    pub id: String,
+    // 已送达；false 表示已排队，稍后自动重试
+    pub sent: bool,
+    pub pending: usize,
+    pub error: Option<String>,
+    // 截图失败等不影响提交的问题
+    pub warnings: Vec<String>,
+}
+
+pub struct FeedbackQueue {
+    dir: PathBuf,
+    // 同一时间只有一轮发送
+    flushing: AtomicBool,
+}
+
+// 一轮发送结束（含提前返回）时清掉标记
+struct Flushing<'a>(&'a AtomicBool);
+
+impl Drop for Flushing<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+pub struct FeedbackState(pub FeedbackQueue);
+
+// 发送失败的原因是否值得重试（网络、5xx、429）
+struct SendError {
+    retry: bool,
+    message: String,
+}
+
+fn endpoint() -> Result<(&'static str, &'static str), String> {
+    match (ENDPOINT.map(str::trim), API_KEY.map(str::trim)) {
+        (Some(url), Some(key)) if !url.is_empty() && !key.is_empty() => Ok((url, key)),
+        _ => Err("feedback is not available in this build".to_string()),
+    }
+}
+
+pub fn available() -> bool {
+    endpoint().is_ok()
+}
+
+async fn upload(body: Vec<u8>) -> Result<(), SendError> {
+    let (url, key) = endpoint().map_err(|message| SendError {
+        retry: false,
+        message,
+    })?;
+    let client = reqwest::Client::builder()
+        .timeout(UPLOAD_TIMEOUT)
+        .https_only(true)
+        .build()
+        .map_err(|e| SendError {
+            retry: true,
+            message: format!("create http client failed: {}", e),
+        })?;
+    let response = client
+        .post(url)
+        .header("Content-Type", "application/json")
+        .header("Authorization", format!("Bearer {}", key))
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| SendError {
+            retry: true,
+            message: format!("send feedback failed: {}", e),
+        })?;
+    let status = response.status();
+    if status.is_success() {
+        return Ok(());
+    }
+    Err(SendError {
+        retry: status.is_server_error() || status.as_u16() == 429,
+        message: format!("send feedback failed: HTTP {}", status.as_u16()),
+    })
+}
+
+impl FeedbackQueue {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            flushing: AtomicBool::new(false),
+        }
+    }
+
+    fn record_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+
+    fn logs_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}-logs.zip", id))
+    }
+
+    fn screenshot_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}-screenshot.png", id))
+    }
+
+    // 旧的在前
+    fn pending(&self) -> Vec<QueuedFeedback> {
+        let mut items: Vec<QueuedFeedback> = fs::read_dir(&self.dir)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .map(|e| e.path())
+                    .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+                    .filter_map(|p| fs::read(p).ok())
+                    .filter_map(|bytes| serde_json::from_slice(&bytes).ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+        items.sort_by(|a: &QueuedFeedback, b| a.created_at.cmp(&b.created_at));
+        items
+    }
+
+    pub fn pending_count(&self) -> usize {
+        self.pending().len()
+    }
+
+    fn save(&self, item: &QueuedFeedback) -> Result<(), String> {
+        let bytes =
+            serde_json::to_vec(item).map_err(|e| format!("serialize feedback failed: {}", e))?;
+        crate::storage::write_atomic(&self.record_path(&item.id), &bytes)
+    }
+
+    fn remove(&self, id: &str) {
+        for path in [
+            self.record_path(id),
+            self.logs_path(id),
+            self.screenshot_path(id),
+        ] {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    fn enqueue(
+        &self,
+        item: QueuedFeedback,
+        logs: Option<Vec<u8>>,
+        screenshot: Option<Vec<u8>>,
+    ) -> Result<(), String> {
+        if self.pending_count() >= MAX_QUEUED {
+            return Err(format!(
+                "too many unsent feedback items (max {}), try again when online",
+                MAX_QUEUED
+            ));
+        }
+        fs::create_dir_all(&self.dir).map_err(|e| format!("create feedback dir failed: {}", e))?;
+        let attachments = [
+            (self.logs_path(&item.id), logs),
+            (self.screenshot_path(&item.id), screenshot),
+        ];
+        for (path, bytes) in &attachments {
+            if let Some(bytes) = bytes {
+                crate::storage::write_atomic(path, bytes)?;
+            }
+        }
+        // 记录最后写，队列里看到记录时附件一定已经在了
+        self.save(&item).inspect_err(|_| self.remove(&item.id))
+    }
+
+    fn attachment(path: &Path) -> Option<String> {
+        fs::read(path)
+            .ok()
+            .map(|bytes| base64::engine::general_purpose::STANDARD.encode(bytes))
+    }
+
+    fn body(&self, item: &QueuedFeedback) -> Result<Vec<u8>, String> {
+        let logs = item
+            .has_logs
+            .then(|| Self::attachment(&self.logs_path(&item.id)))
+            .flatten();
+        let screenshot = item
+            .has_screenshot
+            .then(|| Self::attachment(&self.screenshot_path(&item.id)))
+            .flatten();
+        serde_json::to_vec(&serde_json::json!({
+            "id": item.id,
+            "text": item.text,
+            "created_at": item.created_at,
+            "app_version": item.app_version,
+            "os": item.os,
+            "arch": item.arch,
+            "logs_zip_base64": logs,
+            "screenshot_png_base64": screenshot,
+        }))
+        .map_err(|e| format!("serialize feedback failed: {}", e))
+    }
+
+    // 按顺序发送队列；遇到可重试的失败（多半是离线）就停下，等下一轮。返回 only 指定的那条是否已送达
+    async fn flush(&self, log: &crate::LogState, only: Option<&str>) -> Result<bool, String> {
+        if self.flushing.swap(true, Ordering::SeqCst) {
+            return Ok(false);
+        }
+        let _guard = Flushing(&self.flushing);
+        let mut delivered = false;
+        for mut item in self.pending() {
+            let body = self.body(&item)?;
+            match upload(body).await {
+                Ok(()) => {
+                    self.remove(&item.id);
+                    log.log_app("INFO", &format!("feedback {} sent", item.id));
+                    delivered |= only == Some(item.id.as_str());
+                }
+                Err(e) => {
+                    item.attempts += 1;
+                    item.last_error = Some(e.message.clone());
+                    if !e.retry || item.attempts >= MAX_ATTEMPTS {
+                        log.log_app(
+                            "WARN",
+                            &format!("feedback {} dropped: {}", item.id, e.message),
+                        );
+                        self.remove(&item.id);
+                        if only == Some(item.id.as_str()) {
+                            return Err(e.message);
+                        }
+                        continue;
+                    }
+                    let _ = self.save(&item);
+                    break;
+                }
+            }
+        }
+        Ok(delivered)
+    }
+}
+
+pub fn validate_text(text: &str) -> Result<String, String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err("feedback text is empty".to_string());
+    }
+    if text.chars().count() > MAX_TEXT_CHARS {
+        return Err(format!(
+            "feedback text is too long (max {} characters)",
+            MAX_TEXT_CHARS
+        ));
+    }
+    Ok(text.to_string())
+}
+
+// 截取主窗口；窗口隐藏或平台不支持时返回错误，由调用方记为提示
+fn screenshot(app: &tauri::AppHandle) -> Result<Vec<u8>, String> {
+    let window = app
+        .get_webview_window("main")
+        .ok_or_else(|| "main window not found".to_string())?;
+    if !window.is_visible().unwrap_or(false) {
+        return Err("main window is hidden".to_string());
+    }
+    let title = window
+        .title()
+        .map_err(|e| format!("read window title failed: {}", e))?;
+    crate::window_capture::capture_png(&title)
+}
+
+// 组装并排队，随即尝试发送一次
+pub async fn send(
+    app: &tauri::AppHandle,
+    text: String,
+    include_logs: bool,
+    include_screenshot: bool,
+) -> Result<FeedbackStatus, String> {
+    endpoint()?;
+    let text = validate_text(&text)?;
+    let log = app.state::<crate::LogState>();
+    let mut warnings = Vec::new();
+    let logs = if include_logs {
+        let info = serde_json::json!({
+            "app": app.package_info().name,
+            "version": app.package_info().version.to_string(),
+            "os": std::env::consts::OS,
+            "arch": std::env::consts::ARCH,
+        });
+        match crate::diagnostics::build_bundle(&log.dir, &info) {
+            Ok(bundle) => Some(bundle.bytes),
+            Err(e) => {
+                warnings.push(format!("logs not attached: {}", e));
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let screenshot = if include_screenshot {
+        match screenshot(app) {
+            Ok(png) => Some(png),
+            Err(e) => {
+                warnings.push(format!("screenshot not attached: {}", e));
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let item = QueuedFeedback {
+        id: format!("fb-{}", crate::now_ms()),
+        text,
+        created_at: chrono::Local::now().to_rfc3339(),
+        app_version: app.package_info().version.to_string(),
+        os: std::env::consts::OS.to_string(),
+        arch: std::env::consts::ARCH.to_string(),
+        has_logs: logs.is_some(),
+        has_screenshot: screenshot.is_some(),
+        attempts: 0,
+        last_error: None,
+    };
+    let id = item.id.clone();
+    let state = app.state::<FeedbackState>();
+    let queue = &state.0;
+    queue.enqueue(item, logs, screenshot)?;
+    let sent = queue.flush(&log, Some(&id)).await?;
+    let error = if sent {
+        None
+    } else {
+        queue
+            .pending()
+            .into_iter()
+            .find(|i| i.id == id)
+            .and_then(|i| i.last_error)
+    };
+    Ok(FeedbackStatus {
+        id,
+        sent,
+        pending: queue.pending_count(),
+        error,
+        warnings,
+    })
+}
+
+pub fn start(app: tauri::AppHandle) {
+    if !available() {
+        return;
+    }
+    thread::spawn(move || loop {
+        thread::sleep(RETRY_INTERVAL);
+        let state = app.state::<FeedbackState>();
+        if state.0.pending_count() == 0 {
+            continue;
+        }
+        let log = app.state::<crate::LogState>();
+        if let Err(e) = tauri::async_runtime::block_on(state.0.flush(&log, None)) {
+            log.log_app("WARN", &format!("feedback retry failed: {}", e));
+        }
+    });
+}
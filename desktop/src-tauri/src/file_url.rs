@@ -0,0 +1,71 @@
+use std::path::PathBuf;
+
+use percent_encoding::percent_decode_str;
+
+// 命令收到的路径可能是普通路径，也可能是前端（拖放、<input type=file>、convertFileSrc 的逆向）给的 file:// URL。
+// 统一在这里转换：去掉 file: 前缀与 localhost，做百分号解码（空格、中文文件名），
+// 识别 Windows 的 file:///C:/x 与旧式 file:///C|/x，以及 file://server/share 形式的 UNC 路径。
+// 不是 file: URL 的输入原样返回，不做解码，文件名里本来就可能有 %
+fn is_file_url(input: &str) -> bool {
+    input
+        .get(..5)
+        .is_some_and(|scheme| scheme.eq_ignore_ascii_case("file:"))
+}
+
+// /C:/x 或 /C|/x 去掉开头的 /，并把 | 换回 :
+fn drive_path(path: &str) -> Option<String> {
+    let bytes = path.as_bytes();
+    let drive = bytes.len() >= 3
+        && bytes[0] == b'/'
+        && bytes[1].is_ascii_alphabetic()
+        && matches!(bytes[2], b':' | b'|')
+        && (bytes.len() == 3 || bytes[3] == b'/');
+    drive.then(|| format!("{}:{}", &path[1..2], &path[3..]))
+}
+
+#[cfg(unix)]
+fn decode(path: &str) -> PathBuf {
+    use std::os::unix::ffi::OsStringExt;
+    // Unix 文件名是任意字节，解码结果不一定是 UTF-8
+    PathBuf::from(std::ffi::OsString::from_vec(
+        percent_decode_str(path).collect(),
+    ))
+}
+
+#[cfg(not(unix))]
+fn decode(path: &str) -> PathBuf {
+    PathBuf::from(
+        percent_decode_str(path)
+            .decode_utf8_lossy()
+            .replace('/', "\\"),
+    )
+}
+
+pub fn to_path(input: &str) -> PathBuf {
+    let input = input.trim();
+    if !is_file_url(input) {
+        return PathBuf::from(input);
+    }
+    let rest = &input[5..];
+    let path = match rest.strip_prefix("//") {
+        // file:////server/share 是另一种 UNC 写法
+        Some(unc) if unc.starts_with("//") => unc.to_string(),
+        Some(after) => {
+            let (host, path) = match after.find('/') {
+                Some(index) => after.split_at(index),
+                None => (after, ""),
+            };
+            if host.is_empty() || host.eq_ignore_ascii_case("localhost") {
+                path.to_string()
+            } else {
+                format!("//{}{}", host, path)
+            }
+        }
+        // file:/x 与 file:x
+        None => rest.to_string(),
+    };
+    match drive_path(&path) {
+        Some(drive) => decode(&drive),
+        None => decode(&path),
+    }
+}
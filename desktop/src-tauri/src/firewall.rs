@@ -0,0 +1,168 @@
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+use tauri::{Emitter, Manager};
+
+// 后端报出端口后立即探测一次本地连通性：被防火墙/安全软件拦截时前端通常只能看到超时，
+// 这里区分出来并发送 firewall-blocked 事件，附带当前平台的处理建议
+pub const BLOCKED_EVENT: &str = "firewall-blocked";
+const PROBE_ATTEMPTS: u32 = 5;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct FirewallBlocked {
+    pub port: u16,
+    pub reason: String,
+    // 系统防火墙是否开启；无法判断时为 None
+    pub firewall_enabled: Option<bool>,
+    pub remediation: Vec<String>,
+}
+
+// 返回 /api/v1/health 的 HTTP 状态码
+pub fn health_status(port: u16) -> Result<u16, String> {
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+    let mut stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)
+        .map_err(|e| format!("connect 127.0.0.1:{} failed: {}", port, e))?;
+    stream
+        .set_read_timeout(Some(CONNECT_TIMEOUT))
+        .and_then(|_| stream.set_write_timeout(Some(CONNECT_TIMEOUT)))
+        .and_then(|_| {
+            stream.write_all(
+                b"GET /api/v1/health HTTP/1.1\r\nHost: 127.0.0.1\r\nConnection: close\r\n\r\n",
+            )
+        })
+        .map_err(|e| format!("send probe failed: {}", e))?;
+    // 连接建立但读不到 HTTP 响应，多见于拦截型安全软件；状态行形如 "HTTP/1.1 200"
+    let mut head = [0u8; 12];
+    stream
+        .read_exact(&mut head)
+        .map_err(|e| format!("no response from backend: {}", e))?;
+    if !head.starts_with(b"HTTP/") {
+        return Err("unexpected response from backend".to_string());
+    }
+    std::str::from_utf8(&head[9..12])
+        .ok()
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| "unexpected response from backend".to_string())
+}
+
+// 后端刚启动时可能还没开始 accept，失败几次才算被拦截
+pub fn probe(port: u16) -> Result<(), String> {
+    let mut last_error = String::new();
+    for attempt in 0..PROBE_ATTEMPTS {
+        if attempt > 0 {
+            thread::sleep(RETRY_DELAY);
+        }
+        // 能拿到任何 HTTP 响应就说明没被拦截
+        match health_status(port) {
+            Ok(_) => return Ok(()),
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
+
+pub fn spawn_probe(app: tauri::AppHandle, port: u16) {
+    thread::spawn(move || {
+        let Err(reason) = probe(port) else {
+            return;
+        };
+        // 后端已经退出时是崩溃而不是被拦截，交给退出事件处理
+        let alive = app
+            .state::<crate::SidecarState>()
+            .0
+            .lock()
+            .map(|child| child.is_some())
+            .unwrap_or(false);
+        if !alive {
+            return;
+        }
+        let payload = FirewallBlocked {
+            port,
+            reason,
+            firewall_enabled: platform::firewall_enabled(),
+            remediation: platform::remediation(),
+        };
+        app.state::<crate::LogState>().log_app(
+            "ERROR",
+            &format!(
+                "backend unreachable on 127.0.0.1:{}, possibly blocked by a firewall: {} (firewall enabled: {:?})",
+                port, payload.reason, payload.firewall_enabled
+            ),
+        );
+        let _ = app.emit(BLOCKED_EVENT, payload);
+    });
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    const SOCKETFILTERFW: &str = "/usr/libexec/ApplicationFirewall/socketfilterfw";
+
+    pub fn firewall_enabled() -> Option<bool> {
+        let out = std::process::Command::new(SOCKETFILTERFW)
+            .arg("--getglobalstate")
+            .output()
+            .ok()?;
+        let text = String::from_utf8_lossy(&out.stdout).to_lowercase();
+        Some(text.contains("enabled"))
+    }
+
+    pub fn remediation() -> Vec<String> {
+        vec![
+            "打开「系统设置 → 网络 → 防火墙 → 选项」，确认没有开启「阻止所有传入连接」，并允许 Nano Banana 及其 server 组件接收传入连接".to_string(),
+            "如果安装了 Little Snitch、LuLu 等网络过滤工具，请放行 127.0.0.1 上的本地连接".to_string(),
+            "也可以在设置中把后端通信方式切换为 socket（不使用本地端口），重启后生效".to_string(),
+        ]
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use std::os::windows::process::CommandExt;
+
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    pub fn firewall_enabled() -> Option<bool> {
+        let out = std::process::Command::new("netsh")
+            .args(["advfirewall", "show", "currentprofile", "state"])
+            .creation_flags(CREATE_NO_WINDOW)
+            .output()
+            .ok()?;
+        if !out.status.success() {
+            return None;
+        }
+        let text = String::from_utf8_lossy(&out.stdout).to_uppercase();
+        if text.contains(" ON") {
+            Some(true)
+        } else if text.contains(" OFF") {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    pub fn remediation() -> Vec<String> {
+        vec![
+            "打开「Windows 安全中心 → 防火墙和网络保护 → 允许应用通过防火墙」，勾选 Nano Banana 的 server.exe".to_string(),
+            "如果安装了第三方杀毒/安全软件，请将应用安装目录加入信任列表，或放行 127.0.0.1 上的本地连接".to_string(),
+            "也可以在设置中把后端通信方式切换为 socket（不使用本地端口），重启后生效".to_string(),
+        ]
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+mod platform {
+    pub fn firewall_enabled() -> Option<bool> {
+        None
+    }
+
+    pub fn remediation() -> Vec<String> {
+        vec![
+            "请检查防火墙规则（iptables/nftables/ufw）是否放行了回环接口 lo 上的连接".to_string(),
+            "也可以在设置中把后端通信方式切换为 socket（不使用本地端口），重启后生效".to_string(),
+        ]
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+// 系统字体目录：首次使用时扫描一次（几百毫秒级），之后水印、联系表、文字渲染共用
+#[derive(Default)]
+pub struct FontState(pub OnceLock<fontdb::Database>);
+
+impl FontState {
+    pub fn database(&self) -> &fontdb::Database {
+        self.0.get_or_init(|| {
+            let mut db = fontdb::Database::new();
+            db.load_system_fonts();
+            db
+        })
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct SystemFont {
+    // 英文族名（与 CSS font-family 一致）
+    pub family: String,
+    // 其他语言的族名，如“苹方-简”
+    pub aliases: Vec<String>,
+    pub postscript_name: String,
+    pub style: String,
+    pub weight: u16,
+    pub monospaced: bool,
+    pub path: Option<String>,
+    // 字体集合（.ttc）中的下标
+    pub index: u32,
+}
+
+fn style_name(style: fontdb::Style) -> &'static str {
+    match style {
+        fontdb::Style::Normal => "normal",
+        fontdb::Style::Italic => "italic",
+        fontdb::Style::Oblique => "oblique",
+    }
+}
+
+pub fn list_system_fonts(db: &fontdb::Database) -> Vec<SystemFont> {
+    let mut seen = HashSet::new();
+    let mut fonts: Vec<SystemFont> = db
+        .faces()
+        .filter_map(|face| {
+            let (family, _) = face.families.first()?;
+            let path = match &face.source {
+                fontdb::Source::File(path) | fontdb::Source::SharedFile(path, _) => {
+                    Some(path.to_string_lossy().to_string())
+                }
+                fontdb::Source::Binary(_) => None,
+            };
+            // 同一字体在多个目录各装了一份时只保留一条
+            if !seen.insert((face.post_script_name.clone(), face.style, face.weight.0)) {
+                return None;
+            }
+            let mut aliases: Vec<String> = face
+                .families
+                .iter()
+                .skip(1)
+                .map(|(name, _)| name.clone())
+                .filter(|name| name != family)
+                .collect();
+            aliases.dedup();
+            Some(SystemFont {
+                family: family.clone(),
+                aliases,
+                postscript_name: face.post_script_name.clone(),
+                style: style_name(face.style).to_string(),
+                weight: face.weight.0,
+                monospaced: face.monospaced,
+                path,
+                index: face.index,
+            })
+        })
+        .collect();
+    fonts.sort_by(|a, b| {
+        a.family
+            .to_lowercase()
+            .cmp(&b.family.to_lowercase())
+            .then(a.weight.cmp(&b.weight))
+            .then(a.style.cmp(&b.style))
+    });
+    fonts
+}
@@ -0,0 +1,348 @@
+use rusqlite::params_from_iter;
+use rusqlite::types::Value;
+
+use crate::collections::CollectionQuery;
+use crate::library::Library;
+
+const DEFAULT_LIMIT: u32 = 100;
+const MAX_LIMIT: u32 = 500;
+
+// 画廊列表用的轻量记录：只带渲染网格需要的字段，提示词等详情按需再取
+#[derive(serde::Serialize)]
+pub struct ImageSummary {
+    pub id: String,
+    pub thumbnail_path: String,
+    pub local_path: String,
+    pub width: i64,
+    pub height: i64,
+    pub created_at: String,
+    pub completed_at: String,
+}
+
+#[derive(serde::Serialize)]
+pub struct ImagePage {
+    pub items: Vec<ImageSummary>,
+    // 为 None 表示已到末尾
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SortOrder {
+    NewestFirst,
+    OldestFirst,
+}
+
+impl SortOrder {
+    fn parse(value: Option<&str>) -> Result<Self, String> {
+        match value.map(str::trim).unwrap_or("") {
+            "" | "created_desc" | "newest" => Ok(Self::NewestFirst),
+            "created_asc" | "oldest" => Ok(Self::OldestFirst),
+            other => Err(format!("unsupported sort: {}", other)),
+        }
+    }
+}
+
+// 游标 = 上一页最后一条的 (created_at, id)，hex 编码后对前端不透明
+fn encode_cursor(created_at: &str, row_id: i64) -> String {
+    hex::encode(format!("{}\n{}", created_at, row_id))
+}
+
+fn decode_cursor(cursor: &str) -> Result<(String, i64), String> {
+    let invalid = || "invalid cursor".to_string();
+    let bytes = hex::decode(cursor.trim()).map_err(|_| invalid())?;
+    let text = String::from_utf8(bytes).map_err(|_| invalid())?;
+    let (created_at, row_id) = text.split_once('\n').ok_or_else(invalid)?;
+    let row_id = row_id.parse::<i64>().map_err(|_| invalid())?;
+    Ok((created_at.to_string(), row_id))
+}
+
+// keyset 分页：按 (created_at, id) 排序并从游标之后继续取，翻到很深的页也不会变慢，
+// 期间有新图插入也不会出现重复/跳过
+pub fn list_images(
+    library: &Library,
+    cursor: Option<&str>,
+    limit: Option<u32>,
+    sort: Option<&str>,
+    filters: &CollectionQuery,
+) -> Result<ImagePage, String> {
+    let order = SortOrder::parse(sort)?;
+    filters.validate()?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+    let (mut clause, mut args) = filters.to_sql()?;
+    if let Some(cursor) = cursor.map(str::trim).filter(|c| !c.is_empty()) {
+        let (created_at, row_id) = decode_cursor(cursor)?;
+        args.push(Value::Text(created_at));
+        let c = args.len();
+        args.push(Value::Integer(row_id));
+        let i = args.len();
+        let op = if order == SortOrder::NewestFirst {
+            "<"
+        } else {
+            ">"
+        };
+        clause.push_str(&format!(
+            " AND (created_at {op} ?{c} OR (created_at = ?{c} AND id {op} ?{i}))",
+            op = op,
+            c = c,
+            i = i
+        ));
+    }
+    let dir = if order == SortOrder::NewestFirst {
+        "DESC"
+    } else {
+        "ASC"
+    };
+    // 多取一条用来判断是否还有下一页
+    args.push(Value::Integer(limit as i64 + 1));
+    let sql = format!(
+        "SELECT id, task_id, COALESCE(thumbnail_path, ''), COALESCE(local_path, ''), \
+         COALESCE(width, 0), COALESCE(height, 0), COALESCE(created_at, ''), \
+         COALESCE(completed_at, '') FROM tasks WHERE {} \
+         ORDER BY created_at {dir}, id {dir} LIMIT ?{}",
+        clause,
+        args.len(),
+        dir = dir
+    );
+
+    let conn = library.open()?;
+    let mut stmt = conn
+        .prepare(&sql)
+        .map_err(|e| format!("list images failed: {}", e))?;
+    let mut rows: Vec<(i64, ImageSummary)> = stmt
+        .query_map(params_from_iter(args), |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                ImageSummary {
+                    id: row.get(1)?,
+                    thumbnail_path: row.get(2)?,
+                    local_path: row.get(3)?,
+                    width: row.get(4)?,
+                    height: row.get(5)?,
+                    created_at: row.get(6)?,
+                    completed_at: row.get(7)?,
+                },
+            ))
+        })
+        .map_err(|e| format!("list images failed: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("list images failed: {}", e))?;
+
+    let next_cursor = if rows.len() > limit as usize {
+        rows.truncate(limit as usize);
+        rows.last()
+            .map(|(row_id, item)| encode_cursor(&item.created_at, *row_id))
+    } else {
+        None
+    };
+    Ok(ImagePage {
+        items: rows.into_iter().map(|(_, item)| item).collect(),
+        next_cursor,
+    })
+}
+
+const STATS_DAYS: u32 = 90;
+const TOP_TAGS: u32 = 20;
+
+#[derive(serde::Serialize)]
+pub struct ModelCount {
+    pub provider_name: String,
+    pub model_id: String,
+    pub count: i64,
+}
+
+#[derive(serde::Serialize)]
+pub struct DayCount {
+    pub day: String,
+    pub count: i64,
+    // 截至当天的累计数量（窗口函数计算，反映图库增长）
+    pub cumulative: i64,
+}
+
+#[derive(serde::Serialize)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: i64,
+}
+
+#[derive(serde::Serialize)]
+pub struct MonthStorage {
+    pub month: String,
+    pub bytes: u64,
+    pub cumulative_bytes: u64,
+}
+
+#[derive(serde::Serialize)]
+pub struct LibraryStats {
+    pub total: i64,
+    pub failed: i64,
+    pub favorites: i64,
+    pub avg_width: f64,
+    pub avg_height: f64,
+    pub avg_megapixels: f64,
+    pub by_model: Vec<ModelCount>,
+    // 最近 90 天
+    pub by_day: Vec<DayCount>,
+    pub top_tags: Vec<TagCount>,
+    pub storage_by_month: Vec<MonthStorage>,
+    pub total_bytes: u64,
+}
+
+fn query_list<T>(
+    conn: &rusqlite::Connection,
+    sql: &str,
+    map: impl FnMut(&rusqlite::Row<'_>) -> rusqlite::Result<T>,
+) -> Result<Vec<T>, String> {
+    let mut stmt = conn
+        .prepare(sql)
+        .map_err(|e| format!("query stats failed: {}", e))?;
+    let rows = stmt
+        .query_map([], map)
+        .map_err(|e| format!("query stats failed: {}", e))?;
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("query stats failed: {}", e))
+}
+
+// 统计全部在 SQL 里聚合，只把结果交给前端；占用空间需要读文件大小，按月汇总
+pub fn library_stats(
+    library: &Library,
+    storage: &dyn crate::storage::Storage,
+) -> Result<LibraryStats, String> {
+    let conn = library.open()?;
+    let live = "deleted_at IS NULL AND status = 'completed'";
+
+    let (total, avg_width, avg_height, avg_megapixels) = conn
+        .query_row(
+            &format!(
+                "SELECT COUNT(*), COALESCE(AVG(NULLIF(width, 0)), 0), \
+                 COALESCE(AVG(NULLIF(height, 0)), 0), \
+                 COALESCE(AVG(NULLIF(width * height, 0)) / 1000000.0, 0) \
+                 FROM tasks WHERE {}",
+                live
+            ),
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .map_err(|e| format!("query stats failed: {}", e))?;
+    let failed: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM tasks WHERE deleted_at IS NULL AND status = 'failed'",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("query stats failed: {}", e))?;
+    let favorites: i64 = conn
+        .query_row(
+            &format!(
+                "SELECT COUNT(*) FROM tasks WHERE {} AND task_id IN \
+                 (SELECT task_id FROM image_meta WHERE favorite = 1)",
+                live
+            ),
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("query stats failed: {}", e))?;
+
+    let by_model = query_list(
+        &conn,
+        &format!(
+            "SELECT COALESCE(provider_name, ''), COALESCE(model_id, ''), COUNT(*) AS n \
+             FROM tasks WHERE {} GROUP BY 1, 2 ORDER BY n DESC",
+            live
+        ),
+        |row| {
+            Ok(ModelCount {
+                provider_name: row.get(0)?,
+                model_id: row.get(1)?,
+                count: row.get(2)?,
+            })
+        },
+    )?;
+
+    let by_day = query_list(
+        &conn,
+        &format!(
+            "SELECT day, n, cumulative FROM (\
+                 SELECT SUBSTR(created_at, 1, 10) AS day, COUNT(*) AS n, \
+                 SUM(COUNT(*)) OVER (ORDER BY SUBSTR(created_at, 1, 10)) AS cumulative \
+                 FROM tasks WHERE {} GROUP BY day) \
+             WHERE day >= DATE('now', 'localtime', '-{} days') ORDER BY day",
+            live, STATS_DAYS
+        ),
+        |row| {
+            Ok(DayCount {
+                day: row.get(0)?,
+                count: row.get(1)?,
+                cumulative: row.get(2)?,
+            })
+        },
+    )?;
+
+    let top_tags = query_list(
+        &conn,
+        &format!(
+            "SELECT t.value, COUNT(*) AS n FROM image_meta m, json_each(m.tags) t \
+             WHERE m.task_id IN (SELECT task_id FROM tasks WHERE {}) \
+             GROUP BY LOWER(t.value) ORDER BY n DESC LIMIT {}",
+            live, TOP_TAGS
+        ),
+        |row| {
+            Ok(TagCount {
+                tag: row.get(0)?,
+                count: row.get(1)?,
+            })
+        },
+    )?;
+
+    let files = query_list(
+        &conn,
+        &format!(
+            "SELECT SUBSTR(created_at, 1, 7), COALESCE(local_path, ''), \
+             COALESCE(thumbnail_path, '') FROM tasks WHERE {} ORDER BY created_at",
+            live
+        ),
+        |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        },
+    )?;
+    let mut storage_by_month: Vec<MonthStorage> = Vec::new();
+    let mut total_bytes = 0u64;
+    for (month, local, thumb) in files {
+        let bytes: u64 = [local, thumb]
+            .iter()
+            .filter_map(|p| crate::storage::normalize_id(p).ok())
+            .filter_map(|id| storage.stat(&id))
+            .map(|m| m.len)
+            .sum();
+        total_bytes += bytes;
+        match storage_by_month.last_mut() {
+            Some(last) if last.month == month => {
+                last.bytes += bytes;
+                last.cumulative_bytes = total_bytes;
+            }
+            _ => storage_by_month.push(MonthStorage {
+                month,
+                bytes,
+                cumulative_bytes: total_bytes,
+            }),
+        }
+    }
+
+    Ok(LibraryStats {
+        total,
+        failed,
+        favorites,
+        avg_width,
+        avg_height,
+        avg_megapixels,
+        by_model,
+        by_day,
+        top_tags,
+        storage_by_month,
+        total_bytes,
+    })
+}
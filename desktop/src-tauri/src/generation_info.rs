@@ -0,0 +1,158 @@
+use crate::library::{ImageMeta, TaskRecord};
+
+// 一次生成的可读信息（提示词 + 参数），用于复制到剪贴板、导出 PDF 等场景
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct GenerationInfo {
+    pub id: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub title: String,
+    pub prompt: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub negative_prompt: Option<String>,
+    pub provider: String,
+    pub model: String,
+    pub width: i64,
+    pub height: i64,
+    // config_snapshot 中除 provider/model 以外的参数（宽高比、分辨率档位等）
+    pub parameters: serde_json::Map<String, serde_json::Value>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub project: String,
+    pub created_at: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InfoFormat {
+    Json,
+    Markdown,
+}
+
+impl InfoFormat {
+    pub fn parse(value: Option<&str>) -> Result<Self, String> {
+        match value.map(|v| v.trim().to_lowercase()).as_deref() {
+            None | Some("") | Some("json") => Ok(InfoFormat::Json),
+            Some("markdown") | Some("md") => Ok(InfoFormat::Markdown),
+            Some(other) => Err(format!("unsupported format: {}", other)),
+        }
+    }
+}
+
+fn value_text(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+impl GenerationInfo {
+    pub fn new(task: &TaskRecord, meta: &ImageMeta) -> Self {
+        let mut parameters = serde_json::from_str::<serde_json::Value>(&task.config_snapshot)
+            .ok()
+            .and_then(|v| match v {
+                serde_json::Value::Object(map) => Some(map),
+                _ => None,
+            })
+            .unwrap_or_default();
+        parameters.remove("provider");
+        parameters.remove("model_id");
+        let negative_prompt = ["negative_prompt", "negativePrompt"]
+            .iter()
+            .find_map(|key| parameters.remove(*key))
+            .map(|v| value_text(&v))
+            .filter(|v| !v.trim().is_empty());
+        Self {
+            id: task.task_id.clone(),
+            title: meta.title.clone(),
+            prompt: task.prompt.clone(),
+            negative_prompt,
+            provider: task.provider_name.clone(),
+            model: task.model_id.clone(),
+            width: task.width,
+            height: task.height,
+            parameters,
+            tags: meta.tags.clone(),
+            project: meta.project.clone(),
+            created_at: task.created_at.clone(),
+        }
+    }
+
+    // 标签与值，按展示顺序
+    pub fn fields(&self) -> Vec<(String, String)> {
+        let mut fields = vec![
+            ("服务商".to_string(), self.provider.clone()),
+            ("模型".to_string(), self.model.clone()),
+        ];
+        if self.width > 0 && self.height > 0 {
+            fields.push((
+                "尺寸".to_string(),
+                format!("{}×{}", self.width, self.height),
+            ));
+        }
+        for (key, value) in &self.parameters {
+            fields.push((key.clone(), value_text(value)));
+        }
+        if !self.project.is_empty() {
+            fields.push(("项目".to_string(), self.project.clone()));
+        }
+        if !self.tags.is_empty() {
+            fields.push(("标签".to_string(), self.tags.join(", ")));
+        }
+        fields.push(("生成时间".to_string(), self.created_at.clone()));
+        fields.push(("ID".to_string(), self.id.clone()));
+        fields
+            .into_iter()
+            .filter(|(_, value)| !value.trim().is_empty())
+            .collect()
+    }
+
+    pub fn render(&self, format: InfoFormat) -> Result<String, String> {
+        match format {
+            InfoFormat::Json => serde_json::to_string_pretty(self)
+                .map_err(|e| format!("serialize metadata failed: {}", e)),
+            InfoFormat::Markdown => Ok(self.to_markdown()),
+        }
+    }
+
+    fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        let title = if self.title.trim().is_empty() {
+            "生成信息"
+        } else {
+            self.title.trim()
+        };
+        out.push_str(&format!("## {}\n\n", title));
+        out.push_str("**提示词**\n\n");
+        out.push_str(&fenced(&self.prompt));
+        if let Some(negative) = &self.negative_prompt {
+            out.push_str("\n**反向提示词**\n\n");
+            out.push_str(&fenced(negative));
+        }
+        out.push_str("\n| 参数 | 值 |\n| --- | --- |\n");
+        for (key, value) in self.fields() {
+            out.push_str(&format!(
+                "| {} | {} |\n",
+                table_cell(&key),
+                table_cell(&value)
+            ));
+        }
+        out
+    }
+}
+
+// 围栏比内容里最长的连续反引号多一个，提示词里带 ``` 也不会被截断
+fn fenced(text: &str) -> String {
+    let longest = text
+        .split(|c| c != '`')
+        .map(|run| run.len())
+        .max()
+        .unwrap_or(0);
+    let fence = "`".repeat(longest.max(2) + 1);
+    format!("{}text\n{}\n{}\n", fence, text.trim_end(), fence)
+}
+
+fn table_cell(text: &str) -> String {
+    text.replace('|', "\\|")
+        .replace("\r\n", " ")
+        .replace('\n', " ")
+}
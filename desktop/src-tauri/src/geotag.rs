@@ -0,0 +1,316 @@
+// 导入图片中的 EXIF GPS 定位：参考图会被原样上传到云端模型接口，带定位的手机照片等于把拍摄地点一起发出去。
+// 这里只解析 JPEG APP1 / PNG eXIf / WebP EXIF 里的 TIFF 结构，清除时原地把 GPS IFD 清空
+// （条目数置 0、数据置零），文件长度和其他 EXIF（方向、拍摄时间等）都不变
+pub const PRIVACY_WARNING_EVENT: &str = "privacy-warning";
+
+const TAG_GPS_IFD: u16 = 0x8825;
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_USER_COMMENT: u16 = 0x9286;
+const TAG_LAT_REF: u16 = 1;
+const TAG_LAT: u16 = 2;
+const TAG_LON_REF: u16 = 3;
+const TAG_LON: u16 = 4;
+const MAX_IFD_ENTRIES: usize = 512;
+
+#[derive(Clone, Copy, Debug, serde::Serialize)]
+pub struct GpsLocation {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct PrivacyWarning {
+    // 导入后的存储 ID，直接传给 strip_location
+    pub path: String,
+    pub source: String,
+    pub kind: String,
+    pub location: GpsLocation,
+}
+
+struct ExifBlock {
+    start: usize,
+    len: usize,
+    // PNG 的 eXIf 块改动后要重算 CRC，这里记块类型字段的偏移
+    png_chunk: Option<usize>,
+}
+
+fn be16(b: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(b.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn be32(b: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(b.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn le32(b: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(b.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn find_exif(bytes: &[u8]) -> Option<ExifBlock> {
+    if bytes.starts_with(&[0xFF, 0xD8]) {
+        let mut pos = 2;
+        while pos + 4 <= bytes.len() && bytes[pos] == 0xFF {
+            let marker = bytes[pos + 1];
+            // SOS 之后是压缩数据
+            if marker == 0xDA || marker == 0xD9 {
+                return None;
+            }
+            let len = be16(bytes, pos + 2)? as usize;
+            if len < 2 {
+                return None;
+            }
+            let data = pos + 4;
+            if marker == 0xE1 && bytes.get(data..data + 6) == Some(b"Exif\0\0") {
+                return Some(ExifBlock {
+                    start: data + 6,
+                    len: (len - 2).checked_sub(6)?,
+                    png_chunk: None,
+                });
+            }
+            pos += 2 + len;
+        }
+        return None;
+    }
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        let mut pos = 8;
+        while pos + 12 <= bytes.len() {
+            let len = be32(bytes, pos)? as usize;
+            let kind = &bytes[pos + 4..pos + 8];
+            if kind == b"eXIf" {
+                return Some(ExifBlock {
+                    start: pos + 8,
+                    len,
+                    png_chunk: Some(pos + 4),
+                });
+            }
+            if kind == b"IDAT" || kind == b"IEND" {
+                return None;
+            }
+            pos += 12 + len;
+        }
+        return None;
+    }
+    if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        let mut pos = 12;
+        while pos + 8 <= bytes.len() {
+            let len = le32(bytes, pos + 4)? as usize;
+            if &bytes[pos..pos + 4] == b"EXIF" {
+                let mut start = pos + 8;
+                let mut len = len;
+                // 部分写入方会带上 JPEG 风格的 "Exif\0\0" 前缀
+                if bytes.get(start..start + 6) == Some(b"Exif\0\0") {
+                    start += 6;
+                    len = len.checked_sub(6)?;
+                }
+                return Some(ExifBlock {
+                    start,
+                    len,
+                    png_chunk: None,
+                });
+            }
+            pos += 8 + len + (len & 1);
+        }
+    }
+    None
+}
+
+struct Tiff<'a> {
+    data: &'a [u8],
+    little: bool,
+}
+
+struct Entry {
+    // 条目在 TIFF 数据中的偏移
+    at: usize,
+    tag: u16,
+    kind: u16,
+    count: u32,
+}
+
+impl<'a> Tiff<'a> {
+    fn new(data: &'a [u8]) -> Option<Self> {
+        let little = match data.get(..2)? {
+            b"II" => true,
+            b"MM" => false,
+            _ => return None,
+        };
+        let tiff = Self { data, little };
+        (tiff.u16(2)? == 42).then_some(tiff)
+    }
+
+    fn u16(&self, at: usize) -> Option<u16> {
+        let b: [u8; 2] = self.data.get(at..at + 2)?.try_into().ok()?;
+        Some(if self.little {
+            u16::from_le_bytes(b)
+        } else {
+            u16::from_be_bytes(b)
+        })
+    }
+
+    fn u32(&self, at: usize) -> Option<u32> {
+        let b: [u8; 4] = self.data.get(at..at + 4)?.try_into().ok()?;
+        Some(if self.little {
+            u32::from_le_bytes(b)
+        } else {
+            u32::from_be_bytes(b)
+        })
+    }
+
+    fn entries(&self, ifd: usize) -> Option<Vec<Entry>> {
+        let count = self.u16(ifd)? as usize;
+        if count > MAX_IFD_ENTRIES {
+            return None;
+        }
+        (0..count)
+            .map(|i| {
+                let at = ifd + 2 + i * 12;
+                Some(Entry {
+                    at,
+                    tag: self.u16(at)?,
+                    kind: self.u16(at + 2)?,
+                    count: self.u32(at + 4)?,
+                })
+            })
+            .collect()
+    }
+
+    // IFD0 里指向子 IFD（GPS、Exif）的偏移
+    fn sub_ifd(&self, tag: u16) -> Option<usize> {
+        let ifd0 = self.u32(4)? as usize;
+        let entry = self.entries(ifd0)?.into_iter().find(|e| e.tag == tag)?;
+        let offset = self.u32(entry.at + 8)? as usize;
+        (offset > 0 && offset < self.data.len()).then_some(offset)
+    }
+
+    fn gps_ifd(&self) -> Option<usize> {
+        self.sub_ifd(TAG_GPS_IFD)
+    }
+
+    // 值不超过 4 字节时直接存在条目里，否则条目里是偏移
+    fn value_range(&self, entry: &Entry) -> Option<(usize, usize)> {
+        let unit = match entry.kind {
+            1 | 2 | 6 | 7 => 1,
+            3 | 8 => 2,
+            4 | 9 | 11 => 4,
+            5 | 10 | 12 => 8,
+            _ => return None,
+        };
+        let size = unit * entry.count as usize;
+        if size <= 4 {
+            return Some((entry.at + 8, size));
+        }
+        let offset = self.u32(entry.at + 8)? as usize;
+        (offset.checked_add(size)? <= self.data.len()).then_some((offset, size))
+    }
+
+    fn degrees(&self, entry: &Entry) -> Option<f64> {
+        if entry.kind != 5 || entry.count < 3 {
+            return None;
+        }
+        let (offset, _) = self.value_range(entry)?;
+        let mut parts = [0f64; 3];
+        for (i, part) in parts.iter_mut().enumerate() {
+            let num = self.u32(offset + i * 8)? as f64;
+            let den = self.u32(offset + i * 8 + 4)? as f64;
+            *part = if den == 0.0 { 0.0 } else { num / den };
+        }
+        Some(parts[0] + parts[1] / 60.0 + parts[2] / 3600.0)
+    }
+
+    fn reference(&self, entry: &Entry) -> Option<u8> {
+        let (offset, size) = self.value_range(entry)?;
+        (size > 0).then(|| self.data[offset].to_ascii_uppercase())
+    }
+}
+
+pub fn gps_location(bytes: &[u8]) -> Option<GpsLocation> {
+    let block = find_exif(bytes)?;
+    let tiff = Tiff::new(bytes.get(block.start..block.start + block.len)?)?;
+    let entries = tiff.entries(tiff.gps_ifd()?)?;
+    let find = |tag: u16| entries.iter().find(|e| e.tag == tag);
+    let mut latitude = tiff.degrees(find(TAG_LAT)?)?;
+    let mut longitude = tiff.degrees(find(TAG_LON)?)?;
+    if find(TAG_LAT_REF).and_then(|e| tiff.reference(e)) == Some(b'S') {
+        latitude = -latitude;
+    }
+    if find(TAG_LON_REF).and_then(|e| tiff.reference(e)) == Some(b'W') {
+        longitude = -longitude;
+    }
+    // 0,0 多半是设备没拿到定位时写入的占位值
+    if !latitude.is_finite() || !longitude.is_finite() || (latitude == 0.0 && longitude == 0.0) {
+        return None;
+    }
+    Some(GpsLocation {
+        latitude,
+        longitude,
+    })
+}
+
+// EXIF UserComment 的原始字节（含 8 字节字符集头）及 TIFF 是否为小端，供 embedded_prompt.rs 解码
+pub fn user_comment(bytes: &[u8]) -> Option<(Vec<u8>, bool)> {
+    let block = find_exif(bytes)?;
+    let tiff = Tiff::new(bytes.get(block.start..block.start + block.len)?)?;
+    let entry = tiff
+        .entries(tiff.sub_ifd(TAG_EXIF_IFD)?)?
+        .into_iter()
+        .find(|e| e.tag == TAG_USER_COMMENT)?;
+    let (offset, size) = tiff.value_range(&entry)?;
+    Some((tiff.data.get(offset..offset + size)?.to_vec(), tiff.little))
+}
+
+// 返回 None 表示没有需要清除的 GPS 数据
+pub fn strip_location(bytes: &[u8]) -> Option<Vec<u8>> {
+    let block = find_exif(bytes)?;
+    let end = block.start.checked_add(block.len)?;
+    let (ranges, count_at) = {
+        let tiff = Tiff::new(bytes.get(block.start..end)?)?;
+        let ifd = tiff.gps_ifd()?;
+        let entries = tiff.entries(ifd)?;
+        if entries.is_empty() {
+            return None;
+        }
+        let mut ranges: Vec<(usize, usize)> = Vec::with_capacity(entries.len() * 2);
+        for entry in &entries {
+            ranges.push((entry.at, 12));
+            if let Some((offset, size)) = tiff.value_range(entry) {
+                if offset >= entry.at + 12 || offset + size <= entry.at {
+                    ranges.push((offset, size));
+                }
+            }
+        }
+        (ranges, ifd)
+    };
+
+    let mut out = bytes.to_vec();
+    let tiff = &mut out[block.start..end];
+    for (offset, size) in ranges {
+        if let Some(slice) = tiff.get_mut(offset..offset + size) {
+            slice.fill(0);
+        }
+    }
+    // 条目数置 0 后 GPS IFD 仍是合法的空目录，IFD0 里的指针不用改
+    tiff[count_at] = 0;
+    tiff[count_at + 1] = 0;
+
+    if let Some(chunk) = block.png_chunk {
+        let crc = crc32(&out[chunk..end]);
+        out.get_mut(end..end + 4)?
+            .copy_from_slice(&crc.to_be_bytes());
+    }
+    Some(out)
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
@@ -0,0 +1,472 @@
+use std::collections::HashMap;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tauri::{Emitter, Manager};
+
+use crate::lan_share::{self, HttpRequest};
+
+// 多设备接力：在局域网暴露一个常驻端点并通过 mDNS（Bonjour）广播，配套手机 App 或另一台桌面端
+// 发现后可拉取本机最近的生成结果。每台新设备都要先配对：对方发起请求，本机弹出 6 位配对码，
+// 对方输入正确的配对码后才拿到访问 token。token 只保存在内存里，应用重启或关闭端点后需要重新配对
+pub const SERVICE_TYPE: &str = "_nanobanana._tcp.local.";
+pub const PAIR_REQUEST_EVENT: &str = "handoff-pair-request";
+pub const PAIRED_EVENT: &str = "handoff-paired";
+const PAIR_TTL: Duration = Duration::from_secs(120);
+const MAX_PAIR_ATTEMPTS: u32 = 5;
+const MAX_PENDING_PAIRS: usize = 5;
+const MAX_DEVICE_NAME_CHARS: usize = 64;
+const DEFAULT_RESULTS: usize = 20;
+const MAX_RESULTS: usize = 100;
+const ACCEPT_POLL: Duration = Duration::from_millis(200);
+
+struct PendingPair {
+    device: String,
+    code: String,
+    attempts: u32,
+    expires: Instant,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct PairedDevice {
+    pub id: String,
+    pub name: String,
+    pub paired_at: String,
+    #[serde(skip)]
+    token: String,
+}
+
+#[derive(Default)]
+struct Sessions {
+    pending: HashMap<String, PendingPair>,
+    devices: Vec<PairedDevice>,
+}
+
+struct Running {
+    info: HandoffInfo,
+    stop: Arc<AtomicBool>,
+    daemon: mdns_sd::ServiceDaemon,
+    fullname: String,
+}
+
+#[derive(Default)]
+pub struct HandoffState {
+    running: Mutex<Option<Running>>,
+    sessions: Arc<Mutex<Sessions>>,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct HandoffInfo {
+    pub instance: String,
+    pub service: String,
+    pub address: String,
+    pub port: u16,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+struct PairRequest {
+    request_id: String,
+    device: String,
+    code: String,
+    expires_in_secs: u64,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+struct ResultItem {
+    id: String,
+    title: String,
+    prompt: String,
+    width: i64,
+    height: i64,
+    created_at: String,
+    image_path: String,
+}
+
+fn pair_code() -> Result<String, String> {
+    let mut buf = [0u8; 4];
+    getrandom::fill(&mut buf).map_err(|e| format!("generate pairing code failed: {}", e))?;
+    Ok(format!("{:06}", u32::from_le_bytes(buf) % 1_000_000))
+}
+
+fn json_response(stream: &mut TcpStream, status: &str, value: serde_json::Value) {
+    let body = value.to_string();
+    lan_share::respond(
+        stream,
+        status,
+        &[("Content-Type", "application/json".to_string())],
+        body.len(),
+        body.as_bytes(),
+    );
+}
+
+fn error(stream: &mut TcpStream, status: &str, message: &str) {
+    json_response(stream, status, serde_json::json!({ "error": message }));
+}
+
+fn body_json(request: &HttpRequest) -> Option<serde_json::Value> {
+    serde_json::from_slice(&request.body).ok()
+}
+
+fn body_str(body: &serde_json::Value, key: &str) -> String {
+    body.get(key)
+        .and_then(|v| v.as_str())
+        .unwrap_or_default()
+        .trim()
+        .to_string()
+}
+
+fn authorized(sessions: &Mutex<Sessions>, request: &HttpRequest) -> bool {
+    let Some(token) = request
+        .header("authorization")
+        .and_then(|v| v.strip_prefix("Bearer "))
+    else {
+        return false;
+    };
+    sessions
+        .lock()
+        .map(|s| {
+            s.devices
+                .iter()
+                .any(|d| lan_share::token_matches(token.trim(), &d.token))
+        })
+        .unwrap_or(false)
+}
+
+fn start_pairing(
+    app: &tauri::AppHandle,
+    sessions: &Mutex<Sessions>,
+    request: &HttpRequest,
+) -> Result<String, (&'static str, String)> {
+    let bad = |m: &str| ("400 Bad Request", m.to_string());
+    let body = body_json(request).ok_or_else(|| bad("invalid json"))?;
+    let device: String = body_str(&body, "device")
+        .chars()
+        .filter(|c| !c.is_control())
+        .take(MAX_DEVICE_NAME_CHARS)
+        .collect();
+    if device.is_empty() {
+        return Err(bad("device is empty"));
+    }
+    let request_id = lan_share::new_token().map_err(|e| ("500 Internal Server Error", e))?;
+    let code = pair_code().map_err(|e| ("500 Internal Server Error", e))?;
+    {
+        let mut sessions = sessions
+            .lock()
+            .map_err(|_| ("500 Internal Server Error", "lock poisoned".to_string()))?;
+        let now = Instant::now();
+        sessions.pending.retain(|_, p| p.expires > now);
+        if sessions.pending.len() >= MAX_PENDING_PAIRS {
+            return Err((
+                "429 Too Many Requests",
+                "too many pending pairings".to_string(),
+            ));
+        }
+        sessions.pending.insert(
+            request_id.clone(),
+            PendingPair {
+                device: device.clone(),
+                code: code.clone(),
+                attempts: 0,
+                expires: now + PAIR_TTL,
+            },
+        );
+    }
+    let _ = app.emit(
+        PAIR_REQUEST_EVENT,
+        PairRequest {
+            request_id: request_id.clone(),
+            device,
+            code,
+            expires_in_secs: PAIR_TTL.as_secs(),
+        },
+    );
+    Ok(request_id)
+}
+
+// 配对码校验：错满 MAX_PAIR_ATTEMPTS 次或过期即作废，需要对方重新发起
+fn confirm_pairing(
+    app: &tauri::AppHandle,
+    sessions: &Mutex<Sessions>,
+    request: &HttpRequest,
+) -> Result<String, (&'static str, String)> {
+    let body = body_json(request).ok_or(("400 Bad Request", "invalid json".to_string()))?;
+    let request_id = body_str(&body, "request_id");
+    let code = body_str(&body, "code");
+    let mut sessions = sessions
+        .lock()
+        .map_err(|_| ("500 Internal Server Error", "lock poisoned".to_string()))?;
+    let not_found = || ("404 Not Found", "pairing request not found".to_string());
+    let pending = sessions
+        .pending
+        .get_mut(&request_id)
+        .ok_or_else(not_found)?;
+    if pending.expires <= Instant::now() {
+        sessions.pending.remove(&request_id);
+        return Err(not_found());
+    }
+    if !lan_share::token_matches(&code, &pending.code) {
+        pending.attempts += 1;
+        if pending.attempts >= MAX_PAIR_ATTEMPTS {
+            sessions.pending.remove(&request_id);
+        }
+        return Err(("403 Forbidden", "wrong pairing code".to_string()));
+    }
+    let Some(pending) = sessions.pending.remove(&request_id) else {
+        return Err(not_found());
+    };
+    let token = lan_share::new_token().map_err(|e| ("500 Internal Server Error", e))?;
+    let device = PairedDevice {
+        id: request_id,
+        name: pending.device,
+        paired_at: chrono::Local::now().to_rfc3339(),
+        token: token.clone(),
+    };
+    sessions.devices.push(device.clone());
+    drop(sessions);
+    app.state::<crate::LogState>()
+        .log_app("INFO", &format!("handoff device paired: {}", device.name));
+    let _ = app.emit(PAIRED_EVENT, device);
+    Ok(token)
+}
+
+fn recent_results(app: &tauri::AppHandle, query: &str) -> Result<Vec<ResultItem>, String> {
+    let limit = query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == "limit")
+        .and_then(|(_, value)| value.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_RESULTS)
+        .clamp(1, MAX_RESULTS);
+    let library = app.state::<crate::library::LibraryState>();
+    let conn = library.0.open()?;
+    let tasks = library.0.recent_tasks(&conn, limit)?;
+    let meta = library.0.all_meta(&conn)?;
+    Ok(tasks
+        .into_iter()
+        .map(|task| ResultItem {
+            title: meta
+                .get(&task.task_id)
+                .map(|m| m.title.clone())
+                .unwrap_or_default(),
+            image_path: format!("/results/{}/image", task.task_id),
+            id: task.task_id,
+            prompt: task.prompt,
+            width: task.width,
+            height: task.height,
+            created_at: task.created_at,
+        })
+        .collect())
+}
+
+fn result_image(app: &tauri::AppHandle, task_id: &str) -> Result<Option<Vec<u8>>, String> {
+    let library = app.state::<crate::library::LibraryState>();
+    let conn = library.0.open()?;
+    let Some(task) = library.0.get_task(&conn, task_id)? else {
+        return Ok(None);
+    };
+    let Ok(image_id) = crate::storage::normalize_id(&task.local_path) else {
+        return Ok(None);
+    };
+    app.state::<crate::storage::StorageState>()
+        .0
+        .get(&image_id)
+        .map(Some)
+}
+
+fn handle_client(app: tauri::AppHandle, sessions: Arc<Mutex<Sessions>>, mut stream: TcpStream) {
+    lan_share::prepare_client(&stream);
+    let Some(request) = lan_share::read_request(&mut stream) else {
+        return;
+    };
+    let segments: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+    match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["info"]) => {
+            let info = app
+                .state::<HandoffState>()
+                .info()
+                .map(|i| i.instance)
+                .unwrap_or_default();
+            json_response(
+                &mut stream,
+                "200 OK",
+                serde_json::json!({
+                    "instance": info,
+                    "version": app.package_info().version.to_string(),
+                }),
+            );
+        }
+        ("POST", ["pair"]) => match start_pairing(&app, &sessions, &request) {
+            Ok(request_id) => json_response(
+                &mut stream,
+                "200 OK",
+                serde_json::json!({ "request_id": request_id, "expires_in_secs": PAIR_TTL.as_secs() }),
+            ),
+            Err((status, message)) => error(&mut stream, status, &message),
+        },
+        ("POST", ["pair", "confirm"]) => match confirm_pairing(&app, &sessions, &request) {
+            Ok(token) => {
+                json_response(&mut stream, "200 OK", serde_json::json!({ "token": token }))
+            }
+            Err((status, message)) => error(&mut stream, status, &message),
+        },
+        (_, ["results", ..]) if !authorized(&sessions, &request) => {
+            error(&mut stream, "401 Unauthorized", "pairing required");
+        }
+        ("GET", ["results"]) => match recent_results(&app, &request.query) {
+            Ok(items) => {
+                json_response(&mut stream, "200 OK", serde_json::json!({ "items": items }))
+            }
+            Err(e) => error(&mut stream, "500 Internal Server Error", &e),
+        },
+        ("GET", ["results", task_id, "image"]) => match result_image(&app, task_id) {
+            Ok(Some(bytes)) => {
+                let (content_type, _) = lan_share::content_type(&bytes);
+                lan_share::respond(
+                    &mut stream,
+                    "200 OK",
+                    &[("Content-Type", content_type.to_string())],
+                    bytes.len(),
+                    &bytes,
+                );
+            }
+            Ok(None) => error(&mut stream, "404 Not Found", "result not found"),
+            Err(e) => error(&mut stream, "500 Internal Server Error", &e),
+        },
+        _ => error(&mut stream, "404 Not Found", "not found"),
+    }
+}
+
+impl HandoffState {
+    pub fn info(&self) -> Option<HandoffInfo> {
+        self.running
+            .lock()
+            .ok()
+            .and_then(|r| r.as_ref().map(|r| r.info.clone()))
+    }
+
+    // 已经在运行时直接返回当前信息
+    pub fn start(&self, app: &tauri::AppHandle) -> Result<HandoffInfo, String> {
+        let mut running = self
+            .running
+            .lock()
+            .map_err(|_| "handoff lock poisoned".to_string())?;
+        if let Some(current) = running.as_ref() {
+            return Ok(current.info.clone());
+        }
+        let ip = lan_share::lan_ip()?;
+        let listener = TcpListener::bind(SocketAddr::new(ip, 0))
+            .map_err(|e| format!("start handoff failed: {}", e))?;
+        listener
+            .set_nonblocking(true)
+            .map_err(|e| format!("start handoff failed: {}", e))?;
+        let port = listener
+            .local_addr()
+            .map_err(|e| format!("start handoff failed: {}", e))?
+            .port();
+
+        let host = tauri_plugin_os::hostname();
+        let host = host.trim_end_matches(".local");
+        let instance = format!("{} ({})", app.package_info().name, host);
+        let host_name = format!("{}.local.", host.replace(' ', "-"));
+        let version = app.package_info().version.to_string();
+        let properties = [("version", version.as_str()), ("pairing", "code")];
+        let service = mdns_sd::ServiceInfo::new(
+            SERVICE_TYPE,
+            &instance,
+            &host_name,
+            ip,
+            port,
+            &properties[..],
+        )
+        .map_err(|e| format!("register mdns service failed: {}", e))?;
+        let fullname = service.get_fullname().to_string();
+        let daemon =
+            mdns_sd::ServiceDaemon::new().map_err(|e| format!("start mdns failed: {}", e))?;
+        daemon
+            .register(service)
+            .map_err(|e| format!("register mdns service failed: {}", e))?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let thread_app = app.clone();
+        let sessions = self.sessions.clone();
+        thread::spawn(move || {
+            while !thread_stop.load(Ordering::SeqCst) {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        let app = thread_app.clone();
+                        let sessions = sessions.clone();
+                        thread::spawn(move || handle_client(app, sessions, stream));
+                    }
+                    Err(_) => thread::sleep(ACCEPT_POLL),
+                }
+            }
+        });
+
+        let info = HandoffInfo {
+            instance,
+            service: SERVICE_TYPE.to_string(),
+            address: ip.to_string(),
+            port,
+        };
+        app.state::<crate::LogState>().log_app(
+            "INFO",
+            &format!("handoff endpoint started on {}:{}", info.address, port),
+        );
+        *running = Some(Running {
+            info: info.clone(),
+            stop,
+            daemon,
+            fullname,
+        });
+        Ok(info)
+    }
+
+    // 关闭端点时一并作废所有配对，避免旧 token 在下次开启后继续可用
+    pub fn stop(&self) -> Result<bool, String> {
+        let current = self
+            .running
+            .lock()
+            .map_err(|_| "handoff lock poisoned".to_string())?
+            .take();
+        let Some(current) = current else {
+            return Ok(false);
+        };
+        current.stop.store(true, Ordering::SeqCst);
+        let _ = current.daemon.unregister(&current.fullname);
+        let _ = current.daemon.shutdown();
+        if let Ok(mut sessions) = self.sessions.lock() {
+            *sessions = Sessions::default();
+        }
+        Ok(true)
+    }
+
+    pub fn reject(&self, request_id: &str) -> Result<bool, String> {
+        let mut sessions = self
+            .sessions
+            .lock()
+            .map_err(|_| "handoff lock poisoned".to_string())?;
+        Ok(sessions.pending.remove(request_id).is_some())
+    }
+
+    pub fn devices(&self) -> Result<Vec<PairedDevice>, String> {
+        let sessions = self
+            .sessions
+            .lock()
+            .map_err(|_| "handoff lock poisoned".to_string())?;
+        Ok(sessions.devices.clone())
+    }
+
+    pub fn revoke(&self, device_id: &str) -> Result<bool, String> {
+        let mut sessions = self
+            .sessions
+            .lock()
+            .map_err(|_| "handoff lock poisoned".to_string())?;
+        let before = sessions.devices.len();
+        sessions.devices.retain(|d| d.id != device_id);
+        Ok(sessions.devices.len() != before)
+    }
+}
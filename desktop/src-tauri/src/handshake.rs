@@ -0,0 +1,96 @@
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+// sidecar 启动握手：后端监听成功后输出一行 JSON（端口或 socket、版本、pid、数据目录），
+// 整行解析并校验后才采用；旧版后端没有这一行，回退到按行匹配 SERVER_PORT= / SERVER_SOCKET=。
+// 收到有效握手后，同一进程后续的旧格式行只作日志，不再重复宣布
+pub const PROTOCOL: u32 = 1;
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct Handshake {
+    #[serde(rename = "nb_handshake")]
+    pub protocol: u32,
+    #[serde(default)]
+    pub port: u16,
+    #[serde(default)]
+    pub socket: String,
+    #[serde(default)]
+    pub version: String,
+    pub pid: u32,
+    #[serde(default)]
+    pub data_dir: String,
+}
+
+pub enum Endpoint {
+    Port(u16),
+    Socket(PathBuf),
+}
+
+impl Handshake {
+    pub fn endpoint(&self) -> Endpoint {
+        if self.socket.is_empty() {
+            Endpoint::Port(self.port)
+        } else {
+            Endpoint::Socket(PathBuf::from(&self.socket))
+        }
+    }
+}
+
+// 最近一次有效握手，供诊断与后端信息查询
+#[derive(Default)]
+pub struct HandshakeState(pub Mutex<Option<Handshake>>);
+
+// 不是握手行时返回 None；是握手行但内容不合法时返回错误
+pub fn parse(line: &str, pid: u32) -> Option<Result<Handshake, String>> {
+    let line = line.trim();
+    if !line.starts_with('{') || !line.contains("\"nb_handshake\"") {
+        return None;
+    }
+    Some(
+        serde_json::from_str::<Handshake>(line)
+            .map_err(|e| format!("malformed handshake: {}", e))
+            .and_then(|handshake| validate(handshake, pid)),
+    )
+}
+
+fn validate(handshake: Handshake, pid: u32) -> Result<Handshake, String> {
+    if handshake.protocol != PROTOCOL {
+        return Err(format!(
+            "unsupported handshake protocol {} (expected {})",
+            handshake.protocol, PROTOCOL
+        ));
+    }
+    // 沙箱包装层通过 exec 启动后端，pid 与 spawn 得到的一致
+    if handshake.pid != pid {
+        return Err(format!(
+            "handshake pid {} does not match sidecar pid {}",
+            handshake.pid, pid
+        ));
+    }
+    match (handshake.port, handshake.socket.is_empty()) {
+        (0, true) => return Err("handshake has neither port nor socket".to_string()),
+        (port, false) if port != 0 => return Err("handshake has both port and socket".to_string()),
+        _ => {}
+    }
+    if handshake.data_dir.trim().is_empty() {
+        return Err("handshake has no data_dir".to_string());
+    }
+    Ok(handshake)
+}
+
+// 旧版后端的输出格式；要求整行就是 KEY=VALUE，避免从混杂的日志里误取
+pub fn parse_legacy(line: &str) -> Option<Endpoint> {
+    let line = line.trim();
+    if let Some(port) = line.strip_prefix("SERVER_PORT=") {
+        return port
+            .trim()
+            .parse::<u16>()
+            .ok()
+            .filter(|p| *p > 0)
+            .map(Endpoint::Port);
+    }
+    line.strip_prefix("SERVER_SOCKET=")
+        .map(str::trim)
+        .filter(|path| !path.is_empty())
+        .map(|path| Endpoint::Socket(PathBuf::from(path)))
+}
@@ -0,0 +1,172 @@
+use std::path::Path;
+
+// 启动时探测本机可用的加速方式（macOS 的 Metal，Windows 的 CUDA / DirectML，Linux 的 CUDA），
+// 选出一种通过 NB_ACCELERATION / NB_GPU 传给 sidecar，并由 get_hardware_info 给前端展示。
+// 只做文件与注册表层面的快速检查，不加载驱动；安全模式下固定为 cpu
+pub const ACCELERATIONS: [&str; 4] = ["cuda", "directml", "metal", "cpu"];
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct HardwareInfo {
+    pub os: String,
+    pub arch: String,
+    pub cpu_count: usize,
+    pub gpus: Vec<String>,
+    // 探测到的加速方式，按优先级排序
+    pub available: Vec<String>,
+    // 实际传给 sidecar 的加速方式，没有可用的时为 cpu
+    pub active: String,
+    // 没有使用加速的原因
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+pub struct HardwareState(pub HardwareInfo);
+
+#[cfg(target_os = "macos")]
+fn probe() -> (Vec<String>, Vec<String>) {
+    let mut gpus = Vec::new();
+    // Apple 芯片的 GPU 集成在 SoC 里，芯片名即 GPU 名
+    if cfg!(target_arch = "aarch64") {
+        if let Ok(output) = std::process::Command::new("sysctl")
+            .args(["-n", "machdep.cpu.brand_string"])
+            .output()
+        {
+            let name = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            if !name.is_empty() {
+                gpus.push(name);
+            }
+        }
+    }
+    let mut available = Vec::new();
+    if Path::new("/System/Library/Frameworks/Metal.framework").exists() {
+        available.push("metal".to_string());
+    }
+    (gpus, available)
+}
+
+#[cfg(windows)]
+fn registry_string(subkey: &str, value: &str) -> Option<String> {
+    use windows::core::PCWSTR;
+    use windows::Win32::System::Registry::{RegGetValueW, HKEY_LOCAL_MACHINE, RRF_RT_REG_SZ};
+
+    let wide = |value: &str| -> Vec<u16> { value.encode_utf16().chain(Some(0)).collect() };
+    let subkey = wide(subkey);
+    let value = wide(value);
+    let mut buffer = [0u16; 256];
+    let mut size = std::mem::size_of_val(&buffer) as u32;
+    let status = unsafe {
+        RegGetValueW(
+            HKEY_LOCAL_MACHINE,
+            PCWSTR(subkey.as_ptr()),
+            PCWSTR(value.as_ptr()),
+            RRF_RT_REG_SZ,
+            None,
+            Some(buffer.as_mut_ptr() as *mut _),
+            Some(&mut size),
+        )
+    };
+    if status.is_err() {
+        return None;
+    }
+    let len = buffer.iter().position(|c| *c == 0).unwrap_or(buffer.len());
+    Some(String::from_utf16_lossy(&buffer[..len]))
+}
+
+#[cfg(windows)]
+fn probe() -> (Vec<String>, Vec<String>) {
+    // 显示适配器设备类下的 0000、0001……子键，DriverDesc 为适配器名称
+    const DISPLAY_CLASS: &str =
+        "SYSTEM\\CurrentControlSet\\Control\\Class\\{4d36e968-e325-11ce-bfc1-08002be10318}";
+    let gpus: Vec<String> = (0..8)
+        .filter_map(|i| registry_string(&format!("{}\\{:04}", DISPLAY_CLASS, i), "DriverDesc"))
+        .filter(|name| !name.contains("Basic Display") && !name.contains("Basic Render"))
+        .collect();
+    let system32 =
+        Path::new(&std::env::var("SystemRoot").unwrap_or_else(|_| "C:\\Windows".to_string()))
+            .join("System32");
+    let mut available = Vec::new();
+    // NVIDIA 驱动会把 CUDA 驱动库装进 System32
+    if system32.join("nvcuda.dll").is_file() {
+        available.push("cuda".to_string());
+    }
+    // DirectML 随 Windows 10 1903 起的系统分发，还需要一块真正的 GPU
+    if !gpus.is_empty()
+        && system32.join("d3d12.dll").is_file()
+        && system32.join("DirectML.dll").is_file()
+    {
+        available.push("directml".to_string());
+    }
+    (gpus, available)
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn probe() -> (Vec<String>, Vec<String>) {
+    // 装了 NVIDIA 专有驱动时每块卡在这里有一个目录，information 里有 Model 行
+    let gpus: Vec<String> = std::fs::read_dir("/proc/driver/nvidia/gpus")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| std::fs::read_to_string(entry.path().join("information")).ok())
+        .filter_map(|text| {
+            text.lines()
+                .find_map(|line| line.strip_prefix("Model:"))
+                .map(|model| model.trim().to_string())
+        })
+        .collect();
+    let mut available = Vec::new();
+    if !gpus.is_empty() && Path::new("/proc/driver/nvidia/version").exists() {
+        available.push("cuda".to_string());
+    }
+    (gpus, available)
+}
+
+pub fn detect(safe: bool) -> HardwareInfo {
+    let (gpus, mut available) = probe();
+    available.sort_by_key(|a| ACCELERATIONS.iter().position(|b| b == a));
+    let (active, note) = match available.first() {
+        _ if safe => ("cpu".to_string(), Some("safe mode".to_string())),
+        Some(best) => (best.clone(), None),
+        None => (
+            "cpu".to_string(),
+            Some("no supported GPU found".to_string()),
+        ),
+    };
+    HardwareInfo {
+        os: std::env::consts::OS.to_string(),
+        arch: std::env::consts::ARCH.to_string(),
+        cpu_count: std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1),
+        gpus,
+        available,
+        active,
+        note,
+    }
+}
+
+// 追加到 sidecar 启动环境；NB_GPU 只在识别出 GPU 名称时传
+pub fn sidecar_env(info: &HardwareInfo) -> Vec<(String, String)> {
+    let mut env = vec![("NB_ACCELERATION".to_string(), info.active.clone())];
+    if info.active != "cpu" {
+        if let Some(gpu) = info.gpus.first() {
+            env.push(("NB_GPU".to_string(), gpu.clone()));
+        }
+    }
+    env
+}
+
+pub fn describe(info: &HardwareInfo) -> String {
+    format!(
+        "hardware: {} {}, {} cpus, gpus [{}], acceleration {} (available: [{}]){}",
+        info.os,
+        info.arch,
+        info.cpu_count,
+        info.gpus.join(", "),
+        info.active,
+        info.available.join(", "),
+        info.note
+            .as_deref()
+            .map(|note| format!(", {}", note))
+            .unwrap_or_default()
+    )
+}
@@ -0,0 +1,180 @@
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tauri::{Emitter, Manager};
+
+// 后端存活监控：端口宣布只说明进程启动过一次，这里定期请求 /health，状态变化时发出 backend-status。
+// starting：进程已启动、尚未通过第一次健康检查；healthy：最近一次检查通过；
+// unresponsive：进程还在但连续多次检查失败（卡死、死锁）；down：没有后端进程（崩溃、重启间隙、启动失败）
+pub const EVENT: &str = "backend-status";
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+// 启动阶段更快地确认就绪
+const STARTING_POLL_INTERVAL: Duration = Duration::from_secs(1);
+// 连续失败 3 次（约 15 秒）才判为无响应，偶发的慢请求不打扰用户
+const UNRESPONSIVE_AFTER: u32 = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Starting,
+    Healthy,
+    Unresponsive,
+    Down,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct BackendStatus {
+    pub status: Status,
+    // 进入当前状态的时间
+    pub since: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_ok: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    pub consecutive_failures: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+}
+
+impl BackendStatus {
+    fn new(status: Status) -> Self {
+        Self {
+            status,
+            since: chrono::Local::now().to_rfc3339(),
+            last_ok: None,
+            last_error: None,
+            consecutive_failures: 0,
+            latency_ms: None,
+        }
+    }
+}
+
+pub struct HealthMonitor {
+    current: Mutex<BackendStatus>,
+}
+
+pub struct HealthState(pub HealthMonitor);
+
+impl Default for HealthMonitor {
+    fn default() -> Self {
+        Self {
+            current: Mutex::new(BackendStatus::new(Status::Starting)),
+        }
+    }
+}
+
+impl HealthMonitor {
+    pub fn current(&self) -> BackendStatus {
+        self.current
+            .lock()
+            .map(|s| s.clone())
+            .unwrap_or_else(|_| BackendStatus::new(Status::Down))
+    }
+
+    // 由 supervisor 在进程启动/退出时直接调用，不必等下一次轮询
+    pub fn set(&self, app: &tauri::AppHandle, status: Status, reason: Option<String>) {
+        self.update(app, |current| {
+            current.consecutive_failures = 0;
+            current.latency_ms = None;
+            if reason.is_some() {
+                current.last_error = reason;
+            }
+            status
+        });
+    }
+
+    fn update(&self, app: &tauri::AppHandle, apply: impl FnOnce(&mut BackendStatus) -> Status) {
+        let changed = {
+            let Ok(mut current) = self.current.lock() else {
+                return;
+            };
+            let next = apply(&mut current);
+            if next == current.status {
+                None
+            } else {
+                current.status = next;
+                current.since = chrono::Local::now().to_rfc3339();
+                Some(current.clone())
+            }
+        };
+        if let Some(status) = changed {
+            let level = match status.status {
+                Status::Unresponsive | Status::Down => "WARN",
+                _ => "INFO",
+            };
+            app.state::<crate::LogState>().log_app(
+                level,
+                &format!(
+                    "backend status: {:?}{}",
+                    status.status,
+                    status
+                        .last_error
+                        .as_ref()
+                        .filter(|_| level == "WARN")
+                        .map(|e| format!(" ({})", e))
+                        .unwrap_or_default()
+                ),
+            );
+            let _ = app.emit(EVENT, status);
+        }
+    }
+
+    fn poll(&self, app: &tauri::AppHandle) {
+        let alive = app
+            .state::<crate::SidecarState>()
+            .0
+            .lock()
+            .map(|child| child.is_some())
+            .unwrap_or(false);
+        if !alive {
+            self.update(app, |_| Status::Down);
+            return;
+        }
+        let started = Instant::now();
+        let Some(result) = crate::backend_ready::check_once(app) else {
+            // 端口还没宣布
+            self.update(app, |current| match current.status {
+                Status::Down => Status::Starting,
+                status => status,
+            });
+            return;
+        };
+        let latency = started.elapsed().as_millis() as u64;
+        self.update(app, |current| match result {
+            Ok(code) if (200..300).contains(&code) => {
+                current.consecutive_failures = 0;
+                current.last_ok = Some(chrono::Local::now().to_rfc3339());
+                current.latency_ms = Some(latency);
+                Status::Healthy
+            }
+            failed => {
+                current.consecutive_failures += 1;
+                current.latency_ms = None;
+                current.last_error = Some(match failed {
+                    Ok(code) => format!("health check returned {}", code),
+                    Err(e) => e,
+                });
+                match current.status {
+                    // 启动阶段连接被拒是常态，不算无响应
+                    Status::Starting | Status::Down => Status::Starting,
+                    _ if current.consecutive_failures >= UNRESPONSIVE_AFTER => Status::Unresponsive,
+                    status => status,
+                }
+            }
+        });
+    }
+}
+
+pub fn start(app: tauri::AppHandle) {
+    thread::spawn(move || loop {
+        let state = app.state::<HealthState>();
+        state.0.poll(&app);
+        let interval = if state.0.current().status == Status::Starting {
+            STARTING_POLL_INTERVAL
+        } else {
+            POLL_INTERVAL
+        };
+        thread::sleep(interval);
+    });
+}
@@ -0,0 +1,171 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+
+use sha2::{Digest, Sha256};
+use tauri::http::Request;
+
+use crate::storage::{normalize_id, Storage};
+
+// 生成结果落盘：由原生层请求后端的下载接口，边读边写进存储并计算 SHA-256，
+// 与后端给出的 X-Content-SHA256 比对通过后才换成正式文件，前端拿到的是逻辑 ID 与 nbimage:// 地址，
+// 大图不再以 base64 或整包 Blob 的形式经过 IPC 堆在 WebView 内存里
+const DIR: &str = "downloads";
+const EXTENSIONS: [&str; 4] = ["png", "jpg", "jpeg", "webp"];
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct SavedImage {
+    pub id: String,
+    pub url: String,
+    pub sha256: String,
+    pub bytes: u64,
+    // 后端给出了摘要并且一致
+    pub verified: bool,
+    // 之前已经下载过，直接复用
+    pub reused: bool,
+}
+
+struct HashingWriter<W: Write> {
+    inner: W,
+    hasher: Sha256,
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn valid_task_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 64
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+// 从 Content-Disposition 的文件名取扩展名，取不到时按 PNG
+fn extension(disposition: Option<&str>) -> &'static str {
+    let ext = disposition
+        .and_then(|d| d.rsplit_once('.'))
+        .map(|(_, ext)| ext.trim_matches(|c: char| c == '"' || c.is_whitespace()))
+        .map(|ext| ext.to_ascii_lowercase())
+        .unwrap_or_default();
+    EXTENSIONS
+        .iter()
+        .find(|candidate| **candidate == ext)
+        .copied()
+        .unwrap_or("png")
+}
+
+fn verify(expected: Option<&str>, actual: &str) -> Result<bool, String> {
+    match expected.map(|e| e.trim().to_ascii_lowercase()) {
+        Some(expected) if !expected.is_empty() => {
+            if expected == actual {
+                Ok(true)
+            } else {
+                Err(format!(
+                    "downloaded image is corrupted: sha256 {} != {}",
+                    actual, expected
+                ))
+            }
+        }
+        _ => Ok(false),
+    }
+}
+
+pub fn save_generated(
+    app: &tauri::AppHandle,
+    storage: &dyn Storage,
+    task_id: &str,
+) -> Result<SavedImage, String> {
+    if !valid_task_id(task_id) {
+        return Err(format!("invalid task id: {}", task_id));
+    }
+    // 同一任务的结果不会变，已下载过就不再重复拉取
+    for ext in EXTENSIONS {
+        let id = normalize_id(&format!("{}/{}.{}", DIR, task_id, ext))?;
+        if storage.exists(&id) {
+            let bytes = storage.get(&id)?;
+            return Ok(SavedImage {
+                url: crate::protocol::url_for(&id),
+                sha256: hex::encode(Sha256::digest(&bytes)),
+                bytes: bytes.len() as u64,
+                id,
+                verified: false,
+                reused: true,
+            });
+        }
+    }
+
+    let request = Request::builder()
+        .method("GET")
+        .uri(format!("/api/v1/images/{}/download", task_id))
+        .body(Vec::new())
+        .map_err(|e| format!("build request failed: {}", e))?;
+    let part_id = normalize_id(&format!("{}/{}.part", DIR, task_id))?;
+
+    let (head, bytes, sha256) = match storage.local_path(&part_id) {
+        Some(part_path) => {
+            if let Some(parent) = part_path.parent() {
+                fs::create_dir_all(parent)
+                    .map_err(|e| format!("create download dir failed: {}", e))?;
+            }
+            let file = File::create(&part_path)
+                .map_err(|e| format!("create download file failed: {}", e))?;
+            let mut sink = HashingWriter {
+                inner: BufWriter::new(file),
+                hasher: Sha256::new(),
+            };
+            let result = crate::backend_bridge::stream_to(app, &request, &mut sink);
+            let sha256 = hex::encode(sink.hasher.finalize());
+            drop(sink.inner);
+            match result {
+                Ok((head, bytes)) => (head, bytes, sha256),
+                Err(e) => {
+                    let _ = fs::remove_file(&part_path);
+                    return Err(e);
+                }
+            }
+        }
+        // 远端存储没有本地路径，只能先收进内存再整体写入
+        None => {
+            let mut sink = HashingWriter {
+                inner: Vec::new(),
+                hasher: Sha256::new(),
+            };
+            let (head, bytes) = crate::backend_bridge::stream_to(app, &request, &mut sink)?;
+            let sha256 = hex::encode(sink.hasher.finalize());
+            storage.put(&part_id, &sink.inner)?;
+            (head, bytes, sha256)
+        }
+    };
+
+    let verified = match verify(head.header("x-content-sha256"), &sha256) {
+        Ok(verified) => verified,
+        Err(e) => {
+            let _ = storage.delete(&part_id);
+            return Err(e);
+        }
+    };
+    let id = normalize_id(&format!(
+        "{}/{}.{}",
+        DIR,
+        task_id,
+        extension(head.header("content-disposition"))
+    ))?;
+    storage.rename(&part_id, &id)?;
+    Ok(SavedImage {
+        url: crate::protocol::url_for(&id),
+        id,
+        sha256,
+        bytes,
+        verified,
+        reused: false,
+    })
+}
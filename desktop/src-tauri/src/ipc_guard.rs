@@ -0,0 +1,111 @@
+use tauri::ipc::Invoke;
+use tauri::{Manager, Runtime, Webview};
+
+// 命令的调用方校验：只接受主窗口里加载应用自身页面的 WebView。capability 已限定窗口，这里再按实际加载的 origin 检查一遍，
+// 防止主窗口被导航到外部页面后继续调用原生能力。拒绝的调用写入应用日志。
+// wrap 在 invoke_handler 外层统一拦截：除 UNGUARDED 中的只读查询外，所有命令（写文件、剪贴板、对外共享、任务控制等）都要校验，
+// 新增命令默认受保护；个别命令内部的 require_trusted 保留，作为直接调用时的第二道检查
+const TRUSTED_WINDOW: &str = "main";
+// 不改任何状态、也不返回密钥或文件内容的查询
+const UNGUARDED: &[&str] = &[
+    "greet",
+    "get_backend_port",
+    "get_backend_port_info",
+    "wait_for_backend_ready",
+    "get_backend_status",
+    "get_sidecar_stats",
+    "get_backend_info",
+    "list_sidecars",
+    "get_feature_flags",
+    "get_hardware_info",
+    "get_license_status",
+    "has_license_feature",
+    "get_backend_base_url",
+    "get_app_data_dir",
+    "get_runtime_config",
+    "get_effective_config",
+    "get_accessibility_prefs",
+    "list_system_fonts",
+    "get_log_dir",
+    "list_settings_versions",
+    "list_smart_collections",
+    "list_undoable_actions",
+    "get_library_stats",
+    "get_overlay_status",
+    "get_handoff_status",
+    "get_session_stats",
+    "get_safe_mode",
+];
+
+// 打包后的前端：macOS/Linux 为 tauri://localhost，Windows 为 http(s)://tauri.localhost
+fn bundled_origin(url: &tauri::Url) -> bool {
+    match url.scheme() {
+        "tauri" => url.host_str() == Some("localhost"),
+        "http" | "https" => url.host_str() == Some("tauri.localhost"),
+        _ => false,
+    }
+}
+
+// 开发构建额外放行 devUrl（vite dev server）
+fn dev_origin(config: &tauri::Config, url: &tauri::Url) -> bool {
+    if !cfg!(debug_assertions) {
+        return false;
+    }
+    match &config.build.dev_url {
+        Some(dev) => dev.origin() == url.origin(),
+        None => false,
+    }
+}
+
+// 应用自身页面的 origin；nbimage 协议据此决定是否回 Access-Control-Allow-Origin
+pub fn is_app_origin(config: &tauri::Config, url: &tauri::Url) -> bool {
+    bundled_origin(url) || dev_origin(config, url)
+}
+
+fn check<R: Runtime>(webview: &Webview<R>) -> Result<(), String> {
+    let label = webview.label();
+    if label != TRUSTED_WINDOW {
+        return Err(format!("untrusted webview: {}", label));
+    }
+    let url = webview
+        .url()
+        .map_err(|e| format!("get webview url failed: {}", e))?;
+    if is_app_origin(webview.config(), &url) {
+        Ok(())
+    } else {
+        Err(format!(
+            "untrusted origin: {}",
+            url.origin().ascii_serialization()
+        ))
+    }
+}
+
+pub fn require_trusted<R: Runtime>(webview: &Webview<R>, command: &str) -> Result<(), String> {
+    let result = check(webview);
+    if let Err(reason) = &result {
+        if let Some(log) = webview.try_state::<crate::LogState>() {
+            log.log_app("WARN", &format!("blocked ipc call {}: {}", command, reason));
+        }
+    }
+    result.map_err(|reason| format!("{} rejected: {}", command, reason))
+}
+
+pub fn wrap<R, F>(handler: F) -> impl Fn(Invoke<R>) -> bool + Send + Sync + 'static
+where
+    R: Runtime,
+    F: Fn(Invoke<R>) -> bool + Send + Sync + 'static,
+{
+    move |invoke: Invoke<R>| {
+        let command = invoke.message.command().to_string();
+        if UNGUARDED.contains(&command.as_str()) {
+            return handler(invoke);
+        }
+        match require_trusted(invoke.message.webview_ref(), &command) {
+            Ok(()) => handler(invoke),
+            Err(message) => {
+                invoke.resolver.reject(message);
+                true
+            }
+        }
+    }
+}
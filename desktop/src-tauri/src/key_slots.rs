@@ -0,0 +1,526 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use sha2::{Digest, Sha256};
+use tauri::{Emitter, Manager};
+
+// 多把 API Key：每个 provider 可存多把带标签的密钥。密钥本身存系统钥匙串（macOS Keychain、
+// Windows 凭据管理器、Linux Secret Service），AppData/key-slots.json 里只有标签、末四位、指纹和用量，
+// WebView 能读到 AppData 也拿不到密钥。旧版明文存在 json 里的密钥启动时迁入钥匙串，迁移前照常可用。
+// 当前启用的那把写进后端 provider_configs 并让 sidecar 重建 Provider，生成请求照常由后端发出。
+// 后端每次调用 Provider 后输出一行 {"nb_event":"provider_call",...}，据此累计各把的用量；遇到
+// 配额用尽或限流时把这把标为本次会话内已用尽，自动切到下一把可用的。用尽标记只在内存里，重启即清空
+pub const SWITCHED_EVENT: &str = "api-key-switched";
+const MAX_SLOTS: usize = 20;
+const MAX_LABEL_CHARS: usize = 64;
+const MAX_KEY_LEN: usize = 512;
+// 切换后还在路上的请求仍用旧 Key，这段时间内的配额错误不再触发切换
+const SWITCH_GRACE: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct KeyUsage {
+    pub calls: u64,
+    pub failures: u64,
+    pub quota_errors: u64,
+    pub last_used_at: Option<String>,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+struct StoredSlot {
+    // 钥匙串条目的账户名为 <provider>/<id>
+    #[serde(default)]
+    id: String,
+    label: String,
+    // 旧版文件里的明文密钥：只读不写，迁移后为空
+    #[serde(default, skip_serializing)]
+    key: String,
+    #[serde(default)]
+    hint: String,
+    // 密钥的 SHA-256，用于查重和会话内的用尽标记，不必读钥匙串
+    #[serde(default)]
+    fingerprint: String,
+    added_at: String,
+    #[serde(default)]
+    usage: KeyUsage,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+struct ProviderSlots {
+    active: Option<usize>,
+    #[serde(default)]
+    slots: Vec<StoredSlot>,
+}
+
+// 返回给前端的信息不含完整密钥
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct KeySlotInfo {
+    pub provider: String,
+    pub slot: usize,
+    pub label: String,
+    // 末四位
+    pub key_hint: String,
+    pub added_at: String,
+    pub active: bool,
+    pub exhausted: bool,
+    pub usage: KeyUsage,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+struct SwitchedPayload {
+    provider: String,
+    from: Option<usize>,
+    // None 表示所有 Key 都已用尽，保持当前这把
+    to: Option<usize>,
+    label: Option<String>,
+    reason: String,
+}
+
+#[derive(Default)]
+struct Session {
+    // (provider, 密钥指纹)
+    exhausted: HashSet<(String, String)>,
+    switched_at: HashMap<String, Instant>,
+}
+
+pub struct KeySlots {
+    path: PathBuf,
+    // 钥匙串里的服务名
+    service: String,
+    lock: Mutex<()>,
+    session: Mutex<Session>,
+}
+
+pub struct KeySlotsState(pub KeySlots);
+
+#[derive(serde::Deserialize)]
+struct ProviderCall {
+    nb_event: String,
+    #[serde(default)]
+    provider: String,
+    #[serde(default)]
+    ok: bool,
+    #[serde(default)]
+    quota: bool,
+}
+
+fn key_hint(key: &str) -> String {
+    let chars: Vec<char> = key.chars().collect();
+    let tail: String = chars[chars.len().saturating_sub(4)..].iter().collect();
+    format!("…{}", tail)
+}
+
+fn fingerprint(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+impl StoredSlot {
+    fn fingerprint(&self) -> String {
+        if self.fingerprint.is_empty() {
+            fingerprint(&self.key)
+        } else {
+            self.fingerprint.clone()
+        }
+    }
+
+    fn hint(&self) -> String {
+        if self.hint.is_empty() {
+            key_hint(&self.key)
+        } else {
+            self.hint.clone()
+        }
+    }
+}
+
+fn new_id() -> Result<String, String> {
+    let mut buf = [0u8; 16];
+    getrandom::fill(&mut buf).map_err(|e| format!("generate key slot id failed: {}", e))?;
+    Ok(hex::encode(buf))
+}
+
+fn validate_provider(provider: &str) -> Result<String, String> {
+    let provider = provider.trim();
+    let valid = !provider.is_empty()
+        && provider.len() <= 64
+        && provider
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'));
+    if !valid {
+        return Err(format!("invalid provider name: {:?}", provider));
+    }
+    Ok(provider.to_string())
+}
+
+fn validate_key(key: &str) -> Result<String, String> {
+    let key = key.trim();
+    if key.is_empty() {
+        return Err("API key is empty".to_string());
+    }
+    if key.len() > MAX_KEY_LEN || key.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return Err("API key is invalid".to_string());
+    }
+    Ok(key.to_string())
+}
+
+impl KeySlots {
+    pub fn new(path: PathBuf, service: String) -> Self {
+        Self {
+            path,
+            service,
+            lock: Mutex::new(()),
+            session: Mutex::new(Session::default()),
+        }
+    }
+
+    fn read(&self) -> BTreeMap<String, ProviderSlots> {
+        fs::read(&self.path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default()
+    }
+
+    fn write(&self, providers: &BTreeMap<String, ProviderSlots>) -> Result<(), String> {
+        let bytes = serde_json::to_vec_pretty(providers)
+            .map_err(|e| format!("serialize key slots failed: {}", e))?;
+        crate::storage::write_atomic(&self.path, &bytes)
+    }
+
+    fn entry(&self, provider: &str, id: &str) -> Result<keyring::Entry, String> {
+        keyring::Entry::new(&self.service, &format!("{}/{}", provider, id))
+            .map_err(|e| format!("open keychain entry failed: {}", e))
+    }
+
+    fn load_secret(&self, provider: &str, slot: &StoredSlot) -> Result<String, String> {
+        if !slot.key.is_empty() {
+            return Ok(slot.key.clone());
+        }
+        self.entry(provider, &slot.id)?
+            .get_password()
+            .map_err(|e| format!("read API key from keychain failed: {}", e))
+    }
+
+    fn delete_secret(&self, provider: &str, slot: &StoredSlot) -> Result<(), String> {
+        if slot.id.is_empty() {
+            return Ok(());
+        }
+        match self.entry(provider, &slot.id)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(format!("delete API key from keychain failed: {}", e)),
+        }
+    }
+
+    // 把旧版 json 里的明文密钥迁入钥匙串，返回迁移的数量；任何一把失败时文件保持原样
+    pub fn migrate(&self) -> Result<usize, String> {
+        let _guard = self.guard()?;
+        let mut providers = self.read();
+        let mut migrated = 0;
+        for (provider, slots) in providers.iter_mut() {
+            for slot in slots.slots.iter_mut().filter(|s| !s.key.is_empty()) {
+                if slot.id.is_empty() {
+                    slot.id = new_id()?;
+                }
+                self.entry(provider, &slot.id)?
+                    .set_password(&slot.key)
+                    .map_err(|e| format!("store API key in keychain failed: {}", e))?;
+                slot.hint = key_hint(&slot.key);
+                slot.fingerprint = fingerprint(&slot.key);
+                slot.key.clear();
+                migrated += 1;
+            }
+        }
+        if migrated > 0 {
+            self.write(&providers)?;
+        }
+        Ok(migrated)
+    }
+
+    fn guard(&self) -> Result<std::sync::MutexGuard<'_, ()>, String> {
+        self.lock
+            .lock()
+            .map_err(|_| "key slots lock poisoned".to_string())
+    }
+
+    fn is_exhausted(&self, provider: &str, fingerprint: &str) -> bool {
+        self.session.lock().is_ok_and(|session| {
+            session
+                .exhausted
+                .contains(&(provider.to_string(), fingerprint.to_string()))
+        })
+    }
+
+    pub fn list(&self) -> Vec<KeySlotInfo> {
+        let _guard = self.lock.lock();
+        let mut out = Vec::new();
+        for (provider, slots) in self.read() {
+            for (index, slot) in slots.slots.iter().enumerate() {
+                out.push(KeySlotInfo {
+                    provider: provider.clone(),
+                    slot: index,
+                    label: slot.label.clone(),
+                    key_hint: slot.hint(),
+                    added_at: slot.added_at.clone(),
+                    active: slots.active == Some(index),
+                    exhausted: self.is_exhausted(&provider, &slot.fingerprint()),
+                    usage: slot.usage.clone(),
+                });
+            }
+        }
+        out
+    }
+
+    // 返回需要写入后端的 (provider, key)：第一把 Key 自动启用
+    pub fn add(
+        &self,
+        provider: &str,
+        label: &str,
+        key: &str,
+    ) -> Result<Option<(String, String)>, String> {
+        let provider = validate_provider(provider)?;
+        let key = validate_key(key)?;
+        let label = label.trim();
+        if label.chars().count() > MAX_LABEL_CHARS {
+            return Err(format!(
+                "label is too long (max {} characters)",
+                MAX_LABEL_CHARS
+            ));
+        }
+        let _guard = self.guard()?;
+        let mut providers = self.read();
+        let slots = providers.entry(provider.clone()).or_default();
+        if slots.slots.len() >= MAX_SLOTS {
+            return Err(format!(
+                "too many API keys for {} (max {})",
+                provider, MAX_SLOTS
+            ));
+        }
+        let digest = fingerprint(&key);
+        if slots.slots.iter().any(|s| s.fingerprint() == digest) {
+            return Err("this API key is already stored".to_string());
+        }
+        let label = if label.is_empty() {
+            format!("Key {}", slots.slots.len() + 1)
+        } else {
+            label.to_string()
+        };
+        let slot = StoredSlot {
+            id: new_id()?,
+            label,
+            key: String::new(),
+            hint: key_hint(&key),
+            fingerprint: digest,
+            added_at: chrono::Local::now().to_rfc3339(),
+            usage: KeyUsage::default(),
+        };
+        self.entry(&provider, &slot.id)?
+            .set_password(&key)
+            .map_err(|e| format!("store API key in keychain failed: {}", e))?;
+        slots.slots.push(slot.clone());
+        let activate = slots.active.is_none();
+        if activate {
+            slots.active = Some(slots.slots.len() - 1);
+        }
+        if let Err(e) = self.write(&providers) {
+            let _ = self.delete_secret(&provider, &slot);
+            return Err(e);
+        }
+        Ok(activate.then_some((provider, key)))
+    }
+
+    // 删掉启用中的 Key 时改用它后面的一把（没有了就往前找）；返回需要写入后端的 (provider, key)。
+    // 删光后后端里的密钥保持原样
+    pub fn remove(&self, provider: &str, slot: usize) -> Result<Option<(String, String)>, String> {
+        let provider = validate_provider(provider)?;
+        let _guard = self.guard()?;
+        let mut providers = self.read();
+        let slots = providers
+            .get_mut(&provider)
+            .filter(|s| slot < s.slots.len())
+            .ok_or_else(|| format!("API key slot {} not found for {}", slot, provider))?;
+        let removed = slots.slots.remove(slot);
+        let mut apply = None;
+        slots.active = match slots.active {
+            Some(active) if active == slot => {
+                let next = (!slots.slots.is_empty()).then(|| slot.min(slots.slots.len() - 1));
+                if let Some(index) = next {
+                    let key = self.load_secret(&provider, &slots.slots[index])?;
+                    apply = Some((provider.clone(), key));
+                }
+                next
+            }
+            Some(active) if active > slot => Some(active - 1),
+            other => other,
+        };
+        if slots.slots.is_empty() {
+            providers.remove(&provider);
+        }
+        self.write(&providers)?;
+        self.delete_secret(&provider, &removed)?;
+        if let Ok(mut session) = self.session.lock() {
+            session.exhausted.remove(&(provider, removed.fingerprint()));
+        }
+        Ok(apply)
+    }
+
+    // 手动启用时清掉这把的用尽标记
+    pub fn set_active(&self, provider: &str, slot: usize) -> Result<(String, String), String> {
+        let provider = validate_provider(provider)?;
+        let _guard = self.guard()?;
+        let mut providers = self.read();
+        let slots = providers
+            .get_mut(&provider)
+            .filter(|s| slot < s.slots.len())
+            .ok_or_else(|| format!("API key slot {} not found for {}", slot, provider))?;
+        let key = self.load_secret(&provider, &slots.slots[slot])?;
+        let digest = slots.slots[slot].fingerprint();
+        slots.active = Some(slot);
+        self.write(&providers)?;
+        if let Ok(mut session) = self.session.lock() {
+            session.exhausted.remove(&(provider.clone(), digest));
+            session.switched_at.insert(provider.clone(), Instant::now());
+        }
+        Ok((provider, key))
+    }
+
+    // 记一次调用到启用中的那把；配额错误且允许切换时返回切换事件与要换上的 Key
+    fn record(
+        &self,
+        call: &ProviderCall,
+        failover: bool,
+    ) -> Result<Option<(SwitchedPayload, Option<String>)>, String> {
+        let _guard = self.guard()?;
+        let mut providers = self.read();
+        let Some(slots) = providers.get_mut(&call.provider) else {
+            return Ok(None);
+        };
+        let Some(active) = slots.active.filter(|a| *a < slots.slots.len()) else {
+            return Ok(None);
+        };
+        let usage = &mut slots.slots[active].usage;
+        usage.calls += 1;
+        usage.last_used_at = Some(chrono::Local::now().to_rfc3339());
+        if !call.ok {
+            usage.failures += 1;
+        }
+        if call.quota {
+            usage.quota_errors += 1;
+        }
+        let mut switched = None;
+        if call.quota && failover {
+            let mut session = self
+                .session
+                .lock()
+                .map_err(|_| "key slots lock poisoned".to_string())?;
+            let in_grace = session
+                .switched_at
+                .get(&call.provider)
+                .is_some_and(|at| at.elapsed() < SWITCH_GRACE);
+            if !in_grace {
+                let current = slots.slots[active].fingerprint();
+                session.exhausted.insert((call.provider.clone(), current));
+                let count = slots.slots.len();
+                let next = (1..count)
+                    .map(|step| (active + step) % count)
+                    .find(|index| {
+                        !session
+                            .exhausted
+                            .contains(&(call.provider.clone(), slots.slots[*index].fingerprint()))
+                    });
+                let payload = SwitchedPayload {
+                    provider: call.provider.clone(),
+                    from: Some(active),
+                    to: next,
+                    label: next.map(|index| slots.slots[index].label.clone()),
+                    reason: "quota".to_string(),
+                };
+                let key = match next {
+                    Some(index) => Some(self.load_secret(&call.provider, &slots.slots[index])?),
+                    None => None,
+                };
+                if next.is_some() {
+                    slots.active = next;
+                    session
+                        .switched_at
+                        .insert(call.provider.clone(), Instant::now());
+                }
+                switched = Some((payload, key));
+            }
+        }
+        self.write(&providers)?;
+        Ok(switched)
+    }
+}
+
+// 写入 provider_configs 并让运行中的后端重建 Provider；后端未运行时下次启动自然读到新 Key。
+// 会阻塞等待后端回复，不能在 sidecar 输出线程里直接调用
+pub fn apply(app: &tauri::AppHandle, provider: &str, key: &str) -> Result<(), String> {
+    let state = app.state::<crate::LibraryState>();
+    let library = &state.0;
+    let conn = library.open()?;
+    library.set_provider_api_key(&conn, provider, key)?;
+    drop(conn);
+    let running = app
+        .state::<crate::SidecarState>()
+        .0
+        .lock()
+        .is_ok_and(|child| child.is_some());
+    if running {
+        crate::backend_control::send(
+            app,
+            "reload_providers",
+            serde_json::Value::Null,
+            crate::backend_control::DEFAULT_TIMEOUT,
+        )?;
+    }
+    Ok(())
+}
+
+// sidecar 标准输出里的 provider_call 事件；返回 true 表示这一行已处理
+pub fn handle_output(app: &tauri::AppHandle, line: &str) -> bool {
+    let line = line.trim();
+    if !line.starts_with('{') || !line.contains("\"nb_event\"") {
+        return false;
+    }
+    let Ok(call) = serde_json::from_str::<ProviderCall>(line) else {
+        return false;
+    };
+    if call.nb_event != "provider_call" || call.provider.is_empty() {
+        return true;
+    }
+    let log = app.state::<crate::LogState>();
+    let failover = crate::feature_flags::enabled(app, "key_slot_failover");
+    let switched = match app.state::<KeySlotsState>().0.record(&call, failover) {
+        Ok(Some(switched)) => switched,
+        Ok(None) => return true,
+        Err(e) => {
+            log.log_app("WARN", &format!("record API key usage failed: {}", e));
+            return true;
+        }
+    };
+    let (payload, key) = switched;
+    let _ = app.emit(SWITCHED_EVENT, payload.clone());
+    let (Some(to), Some(key)) = (payload.to, key) else {
+        log.log_app(
+            "WARN",
+            &format!("all API keys for {} are exhausted", payload.provider),
+        );
+        return true;
+    };
+    log.log_app(
+        "INFO",
+        &format!(
+            "API key for {} switched to slot {} after quota error",
+            payload.provider, to
+        ),
+    );
+    let app = app.clone();
+    thread::spawn(move || {
+        if let Err(e) = apply(&app, &payload.provider, &key) {
+            app.state::<crate::LogState>()
+                .log_app("WARN", &format!("apply API key failed: {}", e));
+        }
+    });
+    true
+}
@@ -0,0 +1,339 @@
+use std::io::{Cursor, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use base64::Engine;
+use tauri::{Emitter, Manager};
+
+// 局域网临时分享：在本机局域网地址的随机端口上提供单张图片，URL 带一次性 token，
+// 手机扫码下载一次或到期后自动关闭。同一时间只保留一个分享，新分享会顶掉旧的
+pub const FINISHED_EVENT: &str = "lan-share-finished";
+pub const DEFAULT_TTL_SECS: u64 = 10 * 60;
+const MIN_TTL_SECS: u64 = 30;
+const MAX_TTL_SECS: u64 = 60 * 60;
+const ACCEPT_POLL: Duration = Duration::from_millis(200);
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_REQUEST_HEAD: u64 = 8 * 1024;
+const MAX_REQUEST_BODY: usize = 16 * 1024;
+const QR_MODULE_PX: u32 = 8;
+
+struct ActiveShare {
+    token: String,
+    stop: Arc<AtomicBool>,
+}
+
+#[derive(Default)]
+pub struct LanShareState(Mutex<Option<ActiveShare>>);
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct LanShare {
+    pub url: String,
+    // data:image/png;base64,...，前端直接放进 <img>
+    pub qr_png: String,
+    pub expires_at: String,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+struct ShareFinished {
+    url: String,
+    // downloaded / expired / stopped
+    reason: String,
+}
+
+pub struct HttpRequest {
+    pub method: String,
+    // 不含查询串
+    pub path: String,
+    pub query: String,
+    // 头名统一小写
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl HttpRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+struct Payload {
+    bytes: Vec<u8>,
+    content_type: &'static str,
+    file_name: String,
+}
+
+// 不真的发包：对外 UDP connect 只是让系统选出默认路由对应的本机地址
+pub fn lan_ip() -> Result<IpAddr, String> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))
+        .map_err(|e| format!("detect lan address failed: {}", e))?;
+    socket
+        .connect((Ipv4Addr::new(192, 0, 2, 1), 80))
+        .map_err(|e| format!("detect lan address failed: {}", e))?;
+    let ip = socket
+        .local_addr()
+        .map_err(|e| format!("detect lan address failed: {}", e))?
+        .ip();
+    if ip.is_loopback() || ip.is_unspecified() {
+        return Err("no lan address found, please connect to a network".to_string());
+    }
+    Ok(ip)
+}
+
+pub fn new_token() -> Result<String, String> {
+    let mut buf = [0u8; 16];
+    getrandom::fill(&mut buf).map_err(|e| format!("generate token failed: {}", e))?;
+    Ok(hex::encode(buf))
+}
+
+pub fn content_type(bytes: &[u8]) -> (&'static str, &'static str) {
+    match image::guess_format(bytes) {
+        Ok(image::ImageFormat::Jpeg) => ("image/jpeg", "jpg"),
+        Ok(image::ImageFormat::WebP) => ("image/webp", "webp"),
+        _ => ("image/png", "png"),
+    }
+}
+
+fn qr_data_uri(url: &str) -> Result<String, String> {
+    let qr = crate::qr::render(url, QR_MODULE_PX)?;
+    let mut png = Vec::new();
+    qr.write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+        .map_err(|e| format!("encode qr failed: {}", e))?;
+    Ok(format!(
+        "data:image/png;base64,{}",
+        base64::engine::general_purpose::STANDARD.encode(png)
+    ))
+}
+
+// 逐字节比较全部长度，避免按前缀猜 token
+pub fn token_matches(candidate: &str, token: &str) -> bool {
+    candidate.len() == token.len()
+        && candidate
+            .bytes()
+            .zip(token.bytes())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+// 只处理本模块和 handoff 用到的简单请求：单次 Connection: close，body 按 Content-Length 读取
+pub fn read_request(stream: &mut TcpStream) -> Option<HttpRequest> {
+    let mut head = Vec::new();
+    let mut byte = [0u8; 1];
+    let mut limited = (&*stream).take(MAX_REQUEST_HEAD);
+    while !head.ends_with(b"\r\n\r\n") {
+        match limited.read(&mut byte) {
+            Ok(1) => head.push(byte[0]),
+            _ => return None,
+        }
+    }
+    let text = String::from_utf8_lossy(&head);
+    let mut lines = text.lines();
+    let mut parts = lines.next()?.split_whitespace();
+    let method = parts.next()?.to_string();
+    let target = parts.next()?;
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+    let length = headers
+        .iter()
+        .find(|(name, _)| name == "content-length")
+        .and_then(|(_, value)| value.parse::<usize>().ok())
+        .unwrap_or(0);
+    if length > MAX_REQUEST_BODY {
+        return None;
+    }
+    let mut body = vec![0u8; length];
+    stream.read_exact(&mut body).ok()?;
+    Some(HttpRequest {
+        method,
+        path: path.to_string(),
+        query: query.to_string(),
+        headers,
+        body,
+    })
+}
+
+// HEAD 请求时 body 为空但 Content-Length 仍按完整内容给出
+pub fn respond(
+    stream: &mut TcpStream,
+    status: &str,
+    headers: &[(&str, String)],
+    content_length: usize,
+    body: &[u8],
+) -> bool {
+    let mut head = format!(
+        "HTTP/1.1 {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n",
+        status, content_length
+    );
+    for (name, value) in headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str("\r\n");
+    stream
+        .write_all(head.as_bytes())
+        .and_then(|_| stream.write_all(body))
+        .and_then(|_| stream.flush())
+        .is_ok()
+}
+
+// accept 出来的连接继承了监听端的非阻塞模式，这里改回阻塞并加超时
+pub fn prepare_client(stream: &TcpStream) {
+    let _ = stream.set_nonblocking(false);
+    let _ = stream.set_read_timeout(Some(CLIENT_TIMEOUT));
+    let _ = stream.set_write_timeout(Some(CLIENT_TIMEOUT));
+}
+
+// 返回 true 表示图片已完整发出，分享随即结束
+fn serve_client(mut stream: TcpStream, token: &str, payload: &Payload) -> bool {
+    prepare_client(&stream);
+    let Some(request) = read_request(&mut stream) else {
+        return false;
+    };
+    let valid = request
+        .path
+        .strip_prefix("/s/")
+        .is_some_and(|candidate| token_matches(candidate, token));
+    if !valid {
+        respond(&mut stream, "404 Not Found", &[], 9, b"not found");
+        return false;
+    }
+    let headers = [
+        ("Content-Type", payload.content_type.to_string()),
+        (
+            "Content-Disposition",
+            format!("inline; filename=\"{}\"", payload.file_name),
+        ),
+    ];
+    let len = payload.bytes.len();
+    match request.method.as_str() {
+        "GET" => respond(&mut stream, "200 OK", &headers, len, &payload.bytes),
+        // 部分手机浏览器会先发 HEAD 探测，不算一次下载
+        "HEAD" => {
+            respond(&mut stream, "200 OK", &headers, len, b"");
+            false
+        }
+        _ => {
+            respond(&mut stream, "405 Method Not Allowed", &[], 0, b"");
+            false
+        }
+    }
+}
+
+pub fn start(
+    app: &tauri::AppHandle,
+    task_id: &str,
+    bytes: Vec<u8>,
+    ttl_secs: Option<u64>,
+) -> Result<LanShare, String> {
+    let ip = lan_ip()?;
+    let listener = TcpListener::bind(SocketAddr::new(ip, 0))
+        .map_err(|e| format!("start lan share failed: {}", e))?;
+    listener
+        .set_nonblocking(true)
+        .map_err(|e| format!("start lan share failed: {}", e))?;
+    let port = listener
+        .local_addr()
+        .map_err(|e| format!("start lan share failed: {}", e))?
+        .port();
+    let token = new_token()?;
+    let url = format!("http://{}/s/{}", SocketAddr::new(ip, port), token);
+    let qr_png = qr_data_uri(&url)?;
+
+    let ttl = Duration::from_secs(
+        ttl_secs
+            .unwrap_or(DEFAULT_TTL_SECS)
+            .clamp(MIN_TTL_SECS, MAX_TTL_SECS),
+    );
+    let expires_at = (chrono::Local::now()
+        + chrono::Duration::from_std(ttl).unwrap_or(chrono::Duration::zero()))
+    .to_rfc3339();
+    let (content_type, ext) = content_type(&bytes);
+    let payload = Payload {
+        bytes,
+        content_type,
+        file_name: format!("{}.{}", crate::naming::sanitize_stem(task_id), ext),
+    };
+
+    let stop = Arc::new(AtomicBool::new(false));
+    {
+        let state = app.state::<LanShareState>();
+        let mut active = state
+            .0
+            .lock()
+            .map_err(|_| "lan share lock poisoned".to_string())?;
+        if let Some(previous) = active.take() {
+            previous.stop.store(true, Ordering::SeqCst);
+        }
+        *active = Some(ActiveShare {
+            token: token.clone(),
+            stop: stop.clone(),
+        });
+    }
+
+    let app = app.clone();
+    let share_url = url.clone();
+    thread::spawn(move || {
+        let deadline = Instant::now() + ttl;
+        let reason = loop {
+            if stop.load(Ordering::SeqCst) {
+                break "stopped";
+            }
+            if Instant::now() >= deadline {
+                break "expired";
+            }
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    if serve_client(stream, &token, &payload) {
+                        break "downloaded";
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                    thread::sleep(ACCEPT_POLL);
+                }
+                Err(_) => thread::sleep(ACCEPT_POLL),
+            }
+        };
+        drop(listener);
+        // 被新分享顶掉时不要清掉新分享的记录
+        if let Ok(mut active) = app.state::<LanShareState>().0.lock() {
+            if active.as_ref().is_some_and(|a| a.token == token) {
+                *active = None;
+            }
+        }
+        let _ = app.emit(
+            FINISHED_EVENT,
+            ShareFinished {
+                url: share_url,
+                reason: reason.to_string(),
+            },
+        );
+    });
+
+    Ok(LanShare {
+        url,
+        qr_png,
+        expires_at,
+    })
+}
+
+pub fn stop(app: &tauri::AppHandle) -> Result<bool, String> {
+    let state = app.state::<LanShareState>();
+    let mut active = state
+        .0
+        .lock()
+        .map_err(|_| "lan share lock poisoned".to_string())?;
+    Ok(match active.take() {
+        Some(share) => {
+            share.stop.store(true, Ordering::SeqCst);
+            true
+        }
+        None => false,
+    })
+}
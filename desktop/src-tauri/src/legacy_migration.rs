@@ -0,0 +1,319 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rusqlite::{Connection, OpenFlags};
+
+use crate::library::Library;
+
+// 早期版本的后端以当前工作目录为数据目录（data.db、storage/、logs/ 都写在启动时所在的目录），
+// Linux 上还用过 ~/.config/com.dztool.banana。启动时扫描这些旧位置，只有其中的 data.db 确实是后端的历史库
+// （有 tasks 表）才算旧数据目录；先把要搬的内容写进日志，再一次性把文件搬进当前数据目录：
+// 能 rename 的直接移动；目标还没有 storage/ 而旧目录在另一个卷上时改为建符号链接，避免启动时复制大量图片；
+// 已存在的目标文件不覆盖。数据库里指向旧目录的绝对路径改写成相对路径。
+// 结果写入 AppData/legacy-migration.json，有错误时不写完成标记，下次启动重试（已搬过的会被跳过）
+const REPORT_FILE: &str = "legacy-migration.json";
+const DB_FILES: [&str; 3] = ["data.db", "data.db-wal", "data.db-shm"];
+
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct MigrationReport {
+    pub completed_at: Option<String>,
+    pub sources: Vec<String>,
+    // 搬移前确定的计划：来源 -> 目标
+    pub planned: Vec<String>,
+    pub moved: Vec<String>,
+    pub linked: Vec<String>,
+    // 目标已存在而保留原样的文件
+    pub skipped: Vec<String>,
+    pub errors: Vec<String>,
+    pub db_paths_updated: usize,
+}
+
+impl MigrationReport {
+    fn is_empty(&self) -> bool {
+        self.moved.is_empty()
+            && self.linked.is_empty()
+            && self.skipped.is_empty()
+            && self.errors.is_empty()
+    }
+}
+
+fn same_dir(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+// 旧版本可能写过数据的目录，去掉与当前数据目录相同的
+fn candidates(data_base: &Path, default_base: &Path) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Ok(dir) = std::env::current_dir() {
+        dirs.push(dir);
+    }
+    if let Some(dir) = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+    {
+        dirs.push(dir);
+    }
+    // 旧后端在 Go 的 os.UserConfigDir() 下建目录；macOS / Windows 上与 Tauri 的 AppData 相同，只有 Linux 不同
+    #[cfg(all(unix, not(target_os = "macos")))]
+    {
+        let config = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+        if let Some(config) = config {
+            dirs.push(config.join("com.dztool.banana"));
+        }
+    }
+    let mut unique: Vec<PathBuf> = Vec::new();
+    for dir in dirs {
+        let current = same_dir(&dir, data_base) || same_dir(&dir, default_base);
+        if !current && !unique.iter().any(|d| same_dir(d, &dir)) {
+            unique.push(dir);
+        }
+    }
+    unique
+}
+
+// 旧目录里要有后端的数据库（含 tasks 表）才算旧数据；只有 storage/、logs/ 的目录（如家目录下碰巧同名的文件夹）不动。
+// 只读打开，不在别人的文件上建表或留下 -wal
+fn has_legacy_data(dir: &Path) -> bool {
+    let db = dir.join("data.db");
+    if !db.is_file() {
+        return false;
+    }
+    let Ok(conn) = Connection::open_with_flags(&db, OpenFlags::SQLITE_OPEN_READ_ONLY) else {
+        return false;
+    };
+    conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'tasks'",
+        [],
+        |row| row.get::<_, i64>(0),
+    )
+    .is_ok_and(|count| count > 0)
+}
+
+// 与 migrate_dir 的处理顺序一致，列出这个旧目录里会被搬走的内容
+fn plan_dir(legacy: &Path, data_base: &Path, default_base: &Path) -> Vec<String> {
+    let mut plan = Vec::new();
+    let entry = |from: PathBuf, to: PathBuf| format!("{} -> {}", from.display(), to.display());
+    if !data_base.join("data.db").exists() {
+        for name in DB_FILES {
+            if legacy.join(name).is_file() {
+                plan.push(entry(legacy.join(name), data_base.join(name)));
+            }
+        }
+    }
+    let storage = legacy.join(crate::organize::STORAGE_ROOT);
+    if storage.is_dir() {
+        plan.push(entry(
+            storage,
+            data_base.join(crate::organize::STORAGE_ROOT),
+        ));
+    }
+    let logs = legacy.join("logs");
+    if logs.is_dir() {
+        plan.push(entry(logs, default_base.join("logs").join("legacy")));
+    }
+    plan
+}
+
+// 同卷 rename，跨卷时复制后删除
+fn move_file(from: &Path, to: &Path) -> Result<(), String> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("create dir failed: {} ({})", e, parent.display()))?;
+    }
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    fs::copy(from, to).map_err(|e| format!("copy failed: {} ({})", e, from.display()))?;
+    fs::remove_file(from).map_err(|e| format!("remove failed: {} ({})", e, from.display()))
+}
+
+fn link_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(from, to)
+    }
+    #[cfg(windows)]
+    {
+        std::os::windows::fs::symlink_dir(from, to)
+    }
+}
+
+fn move_tree(from: &Path, to: &Path, report: &mut MigrationReport) {
+    let entries = match fs::read_dir(from) {
+        Ok(entries) => entries,
+        Err(e) => {
+            report
+                .errors
+                .push(format!("read dir failed: {} ({})", e, from.display()));
+            return;
+        }
+    };
+    for entry in entries.flatten() {
+        let source = entry.path();
+        let target = to.join(entry.file_name());
+        let Ok(kind) = entry.file_type() else {
+            continue;
+        };
+        if kind.is_dir() {
+            move_tree(&source, &target, report);
+            // 搬空了就删掉，还有冲突文件留下的不删
+            let _ = fs::remove_dir(&source);
+        } else if kind.is_file() {
+            if target.exists() {
+                report.skipped.push(source.to_string_lossy().to_string());
+                continue;
+            }
+            match move_file(&source, &target) {
+                Ok(()) => report.moved.push(source.to_string_lossy().to_string()),
+                Err(e) => report.errors.push(e),
+            }
+        }
+    }
+}
+
+fn migrate_storage(from: &Path, to: &Path, report: &mut MigrationReport) {
+    if !to.exists() {
+        if fs::rename(from, to).is_ok() {
+            report.moved.push(from.to_string_lossy().to_string());
+            return;
+        }
+        // 跨卷：链接过去，图片留在原处
+        if link_dir(from, to).is_ok() {
+            report
+                .linked
+                .push(format!("{} -> {}", to.display(), from.display()));
+            return;
+        }
+    }
+    move_tree(from, to, report);
+    let _ = fs::remove_dir(from);
+}
+
+fn migrate_dir(legacy: &Path, data_base: &Path, default_base: &Path, report: &mut MigrationReport) {
+    report.sources.push(legacy.to_string_lossy().to_string());
+    // 目标已有数据库时保留现有的，旧库原样留下，不做合并
+    if data_base.join("data.db").exists() {
+        report
+            .skipped
+            .push(legacy.join("data.db").to_string_lossy().to_string());
+    } else {
+        for name in DB_FILES {
+            let source = legacy.join(name);
+            if !source.is_file() {
+                continue;
+            }
+            match move_file(&source, &data_base.join(name)) {
+                Ok(()) => report.moved.push(source.to_string_lossy().to_string()),
+                Err(e) => report.errors.push(e),
+            }
+        }
+    }
+    let storage = legacy.join(crate::organize::STORAGE_ROOT);
+    if storage.is_dir() {
+        migrate_storage(
+            &storage,
+            &data_base.join(crate::organize::STORAGE_ROOT),
+            report,
+        );
+    }
+    // 旧日志放进 logs/legacy，不与当前日志混在一起
+    let logs = legacy.join("logs");
+    if logs.is_dir() {
+        move_tree(&logs, &default_base.join("logs").join("legacy"), report);
+        let _ = fs::remove_dir(&logs);
+    }
+}
+
+// 数据库里指向旧目录的绝对路径改成相对数据目录的路径
+fn rebase_db(db_path: PathBuf, legacy: &[PathBuf], report: &mut MigrationReport) {
+    if legacy.is_empty() || !db_path.exists() {
+        return;
+    }
+    let library = Library::new(db_path);
+    let conn = match library.open() {
+        Ok(conn) => conn,
+        Err(e) => {
+            report.errors.push(e);
+            return;
+        }
+    };
+    for dir in legacy {
+        let mut prefixes = vec![format!("{}{}", dir.display(), std::path::MAIN_SEPARATOR)];
+        if cfg!(windows) {
+            prefixes.push(format!("{}/", dir.display()));
+        }
+        for prefix in prefixes {
+            match library.rebase_paths(&conn, &prefix, "") {
+                Ok(changed) => report.db_paths_updated += changed,
+                Err(e) => report.errors.push(e),
+            }
+        }
+    }
+}
+
+fn write_report(path: &Path, report: &MigrationReport) -> Result<(), String> {
+    let bytes = serde_json::to_vec_pretty(report)
+        .map_err(|e| format!("serialize migration report failed: {}", e))?;
+    crate::storage::write_atomic(path, &bytes)
+}
+
+fn completed(path: &Path) -> bool {
+    fs::read(path)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice::<MigrationReport>(&bytes).ok())
+        .is_some_and(|report| report.completed_at.is_some())
+}
+
+// setup 阶段、打开存储和启动后端之前调用；开发构建（工作目录在源码树里）与安全模式下不执行
+pub fn run(log: &crate::LogState, data_base: &Path, default_base: &Path) {
+    let report_path = default_base.join(REPORT_FILE);
+    if cfg!(debug_assertions) || completed(&report_path) {
+        return;
+    }
+    let legacy: Vec<PathBuf> = candidates(data_base, default_base)
+        .into_iter()
+        .filter(|dir| has_legacy_data(dir))
+        .collect();
+    let mut report = MigrationReport::default();
+    for dir in &legacy {
+        report
+            .planned
+            .extend(plan_dir(dir, data_base, default_base));
+    }
+    for entry in &report.planned {
+        log.log_app("INFO", &format!("legacy migration plan: {}", entry));
+    }
+    for dir in &legacy {
+        migrate_dir(dir, data_base, default_base, &mut report);
+    }
+    rebase_db(data_base.join("data.db"), &legacy, &mut report);
+    if report.errors.is_empty() {
+        report.completed_at = Some(chrono::Local::now().to_rfc3339());
+    }
+    if let Err(e) = write_report(&report_path, &report) {
+        log.log_app("WARN", &format!("write migration report failed: {}", e));
+    }
+    if report.is_empty() && report.db_paths_updated == 0 {
+        return;
+    }
+    log.log_app(
+        "INFO",
+        &format!(
+            "legacy data migrated from [{}]: {} moved, {} linked, {} skipped, {} db paths updated",
+            report.sources.join(", "),
+            report.moved.len(),
+            report.linked.len(),
+            report.skipped.len(),
+            report.db_paths_updated
+        ),
+    );
+    for error in &report.errors {
+        log.log_app("WARN", &format!("legacy migration: {}", error));
+    }
+}
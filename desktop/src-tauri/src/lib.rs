@@ -1,31 +1,113 @@
+use std::collections::HashMap;
 use std::fs::{self, OpenOptions};
 use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use tauri::{Emitter, Manager, State};
-#[cfg(target_os = "macos")]
 use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
-use tauri_plugin_opener::OpenerExt;
-use tauri_plugin_shell::process::{CommandChild, CommandEvent};
-use tauri_plugin_shell::ShellExt;
-
-#[derive(Clone, serde::Serialize)]
-struct PortPayload {
-    port: u16,
-}
+use tauri_plugin_fs::FsExt;
+use tauri_plugin_shell::process::CommandChild;
+
+mod accessibility;
+mod app_nap;
+mod archive;
+mod backend_bridge;
+mod backend_control;
+mod backend_ready;
+mod bundle;
+mod changefeed;
+mod clipboard_history;
+mod collections;
+mod config;
+mod credentials;
+mod data_uri;
+mod delta;
+mod diagnostics;
+mod embedded_prompt;
+mod export_preset;
+mod feature_flags;
+mod feedback;
+mod file_url;
+mod firewall;
+mod fonts;
+mod gallery;
+mod generation_info;
+mod geotag;
+mod handoff;
+mod handshake;
+mod hardware;
+mod health;
+mod image_fetch;
+mod ipc_guard;
+mod key_slots;
+mod lan_share;
+mod legacy_migration;
+mod library;
+mod license;
+mod long_path;
+mod lut;
+mod metadata;
+mod naming;
+mod nbp;
+mod opener_policy;
+mod organize;
+mod overlay;
+mod pdf;
+mod port_events;
+mod postprocess;
+mod priority;
+mod progress;
+mod protocol;
+mod publish;
+mod qr;
+mod rate_limit;
+mod reference;
+mod resize;
+mod resource_limits;
+mod safe_mode;
+mod sandbox;
+mod session_state;
+mod settings;
+mod sidecar;
+mod sidecar_env;
+mod sidecar_manager;
+mod sidecar_update;
+mod signed_manifest;
+mod stale_sidecar;
+mod stats;
+mod storage;
+mod stylus;
+mod supervisor;
+mod text;
+mod translate;
+mod undo;
+mod upload;
+mod watermark;
+mod window_capture;
+
+use config::ConfigState;
+use library::{Library, LibraryState};
+use settings::{SettingsState, SettingsStore};
+use stats::StatsState;
+use storage::{normalize_id, LocalStorage, Storage, StorageState};
 
 struct BackendPort(Arc<Mutex<u16>>);
 struct SidecarState(Arc<Mutex<Option<CommandChild>>>);
 struct GenerationState(Arc<Mutex<bool>>);
+// 启动参数或系统“打开方式”传入、前端尚未取走的 .nbp 文件
+struct PendingOpenState(Arc<Mutex<Vec<String>>>);
 
 #[derive(Default)]
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
 struct QuitGuard {
     confirmed_exit: bool,
     confirming: bool,
 }
 
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
 struct QuitGuardState(Arc<Mutex<QuitGuard>>);
 
 #[derive(Clone)]
@@ -85,23 +167,41 @@ struct LogState {
     dir: PathBuf,
     app: LogWriter,
     server: LogWriter,
+    // 最低记录级别（config::LOG_LEVELS 下标），sidecar 输出不受影响
+    level: usize,
+    // 本次会话开始的单调时钟，每行除墙上时间外再记一列 +毫秒，系统改时间或休眠后时间线仍然可比
+    started: Instant,
+    // server.log 的记录级别，经 set_backend_log_level 调整，同时以 NB_LOG_LEVEL 传给后端
+    server_level: Arc<AtomicUsize>,
+}
+
+// sidecar 输出行的级别：按行内的级别标记判断，没有标记的算 info
+fn server_line_rank(message: &str) -> usize {
+    let upper = message.to_ascii_uppercase();
+    let rank = if upper.contains("[DEBUG]") || upper.contains("[GIN-DEBUG]") {
+        "debug"
+    } else if upper.contains("[ERROR]") || upper.starts_with("PANIC:") || upper.contains("FATAL") {
+        "error"
+    } else if upper.contains("[WARN") || upper.contains("WARNING") {
+        "warn"
+    } else {
+        "info"
+    };
+    config::level_rank(rank)
 }
 
 impl LogState {
-    fn init(app: &tauri::AppHandle) -> Self {
-        let base = app
-            .path()
-            .app_data_dir()
-            .unwrap_or_else(|_| std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")));
-        let dir = base.join("logs");
+    fn init(app: &tauri::AppHandle, level: &str) -> Self {
+        let dir = app_data_base(app).join("logs");
         let app_log = LogWriter::new(dir.join("app.log"));
         let server_log = LogWriter::new(dir.join("server.log"));
 
         app_log.open();
         server_log.open();
 
+        let started = Instant::now();
         let header = format!(
-            "[{}] [INFO] session start name={} version={} os={} arch={}",
+            "[{}] [+0] [INFO] session start name={} version={} os={} arch={}",
             now_ms(),
             app.package_info().name,
             app.package_info().version,
@@ -114,16 +214,43 @@ impl LogState {
             dir,
             app: app_log,
             server: server_log,
+            level: config::level_rank(level),
+            started,
+            server_level: Arc::new(AtomicUsize::new(config::level_rank("info"))),
         }
     }
 
+    // 行首时间戳：[墙上时间 ms] [+会话内单调毫秒]
+    fn stamp(&self) -> String {
+        format!("[{}] [+{}]", now_ms(), self.started.elapsed().as_millis())
+    }
+
+    fn enabled(&self, level: &str) -> bool {
+        config::level_rank(level) <= self.level
+    }
+
     fn log_app(&self, level: &str, message: &str) {
-        let line = format!("[{}] [{}] {}", now_ms(), level, message);
+        if !self.enabled(level) {
+            return;
+        }
+        let line = format!("{} [{}] {}", self.stamp(), level, message);
         self.app.write_line(&line);
     }
 
+    fn server_level(&self) -> &'static str {
+        config::LOG_LEVELS[self.server_level.load(Ordering::SeqCst)]
+    }
+
+    fn set_server_level(&self, level: &str) {
+        self.server_level
+            .store(config::level_rank(level), Ordering::SeqCst);
+    }
+
     fn log_server(&self, stream: &str, message: &str) {
-        let line = format!("[{}] [{}] {}", now_ms(), stream, message);
+        if server_line_rank(message) > self.server_level.load(Ordering::SeqCst) {
+            return;
+        }
+        let line = format!("{} [{}] {}", self.stamp(), stream, message);
         self.server.write_line(&line);
     }
 }
@@ -135,6 +262,13 @@ struct FrontendLogEntry {
     context: Option<String>,
 }
 
+// AppData 根目录（获取失败时退回当前目录），日志与本地存储都以此为基准
+fn app_data_base(app: &tauri::AppHandle) -> PathBuf {
+    app.path()
+        .app_data_dir()
+        .unwrap_or_else(|_| std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")))
+}
+
 fn now_ms() -> u128 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -193,20 +327,329 @@ fn get_backend_port(state: State<'_, BackendPort>) -> u16 {
     *port
 }
 
+// 等到后端端口已知且健康检查通过；失败时返回 { code, message, last_error }
+#[tauri::command]
+async fn wait_for_backend_ready(
+    app: tauri::AppHandle,
+    timeout_ms: Option<u64>,
+) -> Result<backend_ready::BackendReady, backend_ready::BackendWaitError> {
+    tauri::async_runtime::spawn_blocking(move || backend_ready::wait(&app, timeout_ms))
+        .await
+        .map_err(|e| backend_ready::BackendWaitError {
+            code: "internal".to_string(),
+            message: format!("wait for backend failed: {}", e),
+            last_error: None,
+        })?
+}
+
+// 后端卡死时重启 sidecar，不必重启整个应用；新进程就绪后返回其端口并发出 backend-restarted
+#[tauri::command]
+async fn restart_backend(
+    app: tauri::AppHandle,
+    timeout_ms: Option<u64>,
+) -> Result<supervisor::RestartedNotice, String> {
+    let timeout =
+        std::time::Duration::from_millis(timeout_ms.unwrap_or(backend_ready::DEFAULT_TIMEOUT_MS));
+    tauri::async_runtime::spawn_blocking(move || supervisor::restart_now(&app, timeout))
+        .await
+        .map_err(|e| format!("restart backend failed: {}", e))?
+}
+
+// 后端当前的存活状态；之后的变化通过 backend-status 事件推送
+#[tauri::command]
+fn get_backend_status(health: State<'_, health::HealthState>) -> health::BackendStatus {
+    health.0.current()
+}
+
+// 经标准输入向后端发送控制命令（ping / reload_config 等），返回后端的处理结果
+#[tauri::command(async)]
+fn send_backend_command(
+    webview: tauri::Webview,
+    app: tauri::AppHandle,
+    command: String,
+    args: Option<serde_json::Value>,
+    timeout_ms: Option<u64>,
+) -> Result<serde_json::Value, String> {
+    ipc_guard::require_trusted(&webview, "send_backend_command")?;
+    let command = command.trim();
+    app.state::<LogState>()
+        .log_app("INFO", &format!("sending backend command: {}", command));
+    backend_control::send(
+        &app,
+        command,
+        args.unwrap_or(serde_json::Value::Null),
+        backend_control::timeout_from_ms(timeout_ms),
+    )
+}
+
+// sidecar 的 CPU/内存/打开文件数，供状态小部件排查失控的生成任务；后端未运行时报错
+#[tauri::command(async)]
+fn get_sidecar_stats(
+    sidecar: State<'_, SidecarState>,
+) -> Result<resource_limits::SidecarStats, String> {
+    let pid = sidecar
+        .0
+        .lock()
+        .ok()
+        .and_then(|child| child.as_ref().map(|child| child.pid()))
+        .ok_or_else(|| "read sidecar stats failed: sidecar is not running".to_string())?;
+    resource_limits::stats(pid)
+}
+
+// 后端进程的 PID、启动时间、已运行时长、重启次数与最近一次退出状态，供诊断面板显示
+#[tauri::command]
+fn get_backend_info(
+    sidecar: State<'_, SidecarState>,
+    supervisor: State<'_, supervisor::SupervisorState>,
+) -> supervisor::BackendInfo {
+    let pid = sidecar
+        .0
+        .lock()
+        .ok()
+        .and_then(|child| child.as_ref().map(|child| child.pid()));
+    supervisor.0.info(pid)
+}
+
+// 调整后端日志级别：server.log 立即按新级别过滤，并经标准输入通知后端（之后重启的后端也沿用）；
+// 复现问题时临时调到 debug，结束后调回 info
+#[tauri::command(async)]
+fn set_backend_log_level(
+    webview: tauri::Webview,
+    app: tauri::AppHandle,
+    level: String,
+) -> Result<backend_control::LogLevelChange, String> {
+    ipc_guard::require_trusted(&webview, "set_backend_log_level")?;
+    backend_control::set_log_level(&app, &level)
+}
+
+// 当前生效的功能开关（默认值与远程清单合并后的结果）
+#[tauri::command]
+fn get_feature_flags(
+    flags: State<'_, feature_flags::FeatureFlagState>,
+) -> feature_flags::FeatureFlags {
+    flags.0.get()
+}
+
+// 本机 GPU 与传给后端的加速方式（启动时探测一次）
+#[tauri::command]
+fn get_hardware_info(hardware: State<'_, hardware::HardwareState>) -> hardware::HardwareInfo {
+    hardware.0.clone()
+}
+
+// 团队授权：校验、在线激活都在 Rust 里完成，见 license.rs
+#[tauri::command]
+async fn activate_license(
+    webview: tauri::Webview,
+    app: tauri::AppHandle,
+    key: String,
+) -> Result<license::LicenseStatus, String> {
+    ipc_guard::require_trusted(&webview, "activate_license")?;
+    license::activate(&app, &key).await
+}
+
+#[tauri::command(async)]
+fn get_license_status(license: State<'_, license::LicenseState>) -> license::LicenseStatus {
+    license.0.status()
+}
+
+#[tauri::command(async)]
+fn has_license_feature(license: State<'_, license::LicenseState>, feature: String) -> bool {
+    license.0.has_feature(&feature)
+}
+
+#[tauri::command]
+fn deactivate_license(
+    webview: tauri::Webview,
+    license: State<'_, license::LicenseState>,
+) -> Result<(), String> {
+    ipc_guard::require_trusted(&webview, "deactivate_license")?;
+    license.0.deactivate()
+}
+
+// sidecar.extra 里声明的其他 sidecar 的运行状态；API 后端见 get_backend_info
 #[tauri::command]
-fn set_generation_active(state: State<'_, GenerationState>, active: bool) {
+fn list_sidecars(
+    manager: State<'_, sidecar_manager::SidecarManagerState>,
+) -> Vec<sidecar_manager::SidecarStatus> {
+    manager.0.list()
+}
+
+#[tauri::command]
+fn restart_sidecar(
+    webview: tauri::Webview,
+    app: tauri::AppHandle,
+    name: String,
+) -> Result<(), String> {
+    ipc_guard::require_trusted(&webview, "restart_sidecar")?;
+    sidecar_manager::restart(&app, &name)
+}
+
+// 多把 API Key（只返回末四位）
+#[tauri::command]
+fn list_key_slots(key_slots: State<'_, key_slots::KeySlotsState>) -> Vec<key_slots::KeySlotInfo> {
+    key_slots.0.list()
+}
+
+#[tauri::command(async)]
+fn add_key_slot(
+    webview: tauri::Webview,
+    app: tauri::AppHandle,
+    key_slots: State<'_, key_slots::KeySlotsState>,
+    provider: String,
+    label: String,
+    key: String,
+) -> Result<Vec<key_slots::KeySlotInfo>, String> {
+    ipc_guard::require_trusted(&webview, "add_key_slot")?;
+    if let Some((provider, key)) = key_slots.0.add(&provider, &label, &key)? {
+        key_slots::apply(&app, &provider, &key)?;
+    }
+    Ok(key_slots.0.list())
+}
+
+#[tauri::command(async)]
+fn remove_key_slot(
+    webview: tauri::Webview,
+    app: tauri::AppHandle,
+    key_slots: State<'_, key_slots::KeySlotsState>,
+    provider: String,
+    slot: usize,
+) -> Result<Vec<key_slots::KeySlotInfo>, String> {
+    ipc_guard::require_trusted(&webview, "remove_key_slot")?;
+    if let Some((provider, key)) = key_slots.0.remove(&provider, slot)? {
+        key_slots::apply(&app, &provider, &key)?;
+    }
+    Ok(key_slots.0.list())
+}
+
+// 手动切换启用的 Key，同时清掉它在本次会话里的用尽标记
+#[tauri::command(async)]
+fn set_active_key(
+    webview: tauri::Webview,
+    app: tauri::AppHandle,
+    key_slots: State<'_, key_slots::KeySlotsState>,
+    provider: String,
+    slot: usize,
+) -> Result<Vec<key_slots::KeySlotInfo>, String> {
+    ipc_guard::require_trusted(&webview, "set_active_key")?;
+    let (provider, key) = key_slots.0.set_active(&provider, slot)?;
+    key_slots::apply(&app, &provider, &key)?;
+    Ok(key_slots.0.list())
+}
+
+// 当前端口及其 epoch；前端启动时用它作为基准，之后只接受 epoch 更大的 backend-port 事件
+#[tauri::command]
+fn get_backend_port_info(
+    announcer: State<'_, port_events::PortAnnouncer>,
+) -> port_events::PortAnnouncement {
+    announcer.current()
+}
+
+// 前端应使用的 API 根地址：socket 模式下走 nbapi 协议，否则为本地端口；后端未就绪时为 null
+#[tauri::command]
+fn get_backend_base_url(
+    port: State<'_, BackendPort>,
+    socket: State<'_, backend_bridge::BackendSocket>,
+) -> Option<String> {
+    if socket.0.lock().map(|s| s.is_some()).unwrap_or(false) {
+        return Some(format!("{}/api/v1", backend_bridge::base_url()));
+    }
+    let port = port.0.lock().map(|p| *p).unwrap_or(0);
+    (port > 0).then(|| format!("http://127.0.0.1:{}/api/v1", port))
+}
+
+#[tauri::command]
+fn set_generation_active(
+    app: tauri::AppHandle,
+    state: State<'_, GenerationState>,
+    stats: State<'_, StatsState>,
+    active: bool,
+) {
     if let Ok(mut flag) = state.0.lock() {
+        if active && !*flag {
+            stats.0.generation_started();
+        }
         *flag = active;
     }
+    app_nap::refresh(&app);
+}
+
+#[tauri::command]
+fn get_safe_mode(state: State<'_, safe_mode::SafeModeState>) -> safe_mode::SafeModeInfo {
+    state.0.clone()
+}
+
+// 退出安全模式：以正常模式重启应用
+#[tauri::command]
+fn exit_safe_mode(app: tauri::AppHandle, state: State<'_, safe_mode::SafeModeState>) {
+    if !state.0.active {
+        return;
+    }
+    app.state::<LogState>()
+        .log_app("INFO", "leaving safe mode, restarting");
+    app.state::<StatsState>().0.mark_exit();
+    supervisor::shutdown(&app, supervisor::SHUTDOWN_TIMEOUT);
+    sidecar_manager::shutdown(&app, supervisor::SHUTDOWN_TIMEOUT);
+    safe_mode::restart_normal(&app);
+}
+
+// 本次会话的运行时长、生成次数、后端重启/崩溃次数（含历史累计），仅本地统计
+#[tauri::command]
+fn get_session_stats(stats: State<'_, StatsState>) -> stats::SessionStats {
+    stats.0.snapshot()
+}
+
+// 保存前端序列化的界面状态（未提交的提示词、画布布局等），返回保存时间
+#[tauri::command(async)]
+fn save_session_state(
+    app: tauri::AppHandle,
+    store: State<'_, session_state::SessionStateState>,
+    state: String,
+) -> Result<String, String> {
+    store
+        .0
+        .save(&state, &app.package_info().version.to_string())
+}
+
+// 取回上次保存的界面状态；安全模式下不恢复，避免坏状态再次把界面带崩
+#[tauri::command]
+fn restore_session_state(
+    store: State<'_, session_state::SessionStateState>,
+    stats: State<'_, StatsState>,
+    safe_mode: State<'_, safe_mode::SafeModeState>,
+) -> Option<session_state::RestoredSession> {
+    if safe_mode.0.active {
+        return None;
+    }
+    store.0.load(stats.0.snapshot().previous_session_crashed)
+}
+
+#[tauri::command]
+fn clear_session_state(store: State<'_, session_state::SessionStateState>) -> Result<(), String> {
+    store.0.clear()
 }
 
 // 获取应用数据目录的命令，用于前端拼接本地图片路径
 #[tauri::command]
-fn get_app_data_dir(app: tauri::AppHandle) -> String {
-    app.path()
-        .app_data_dir()
-        .map(|p| p.to_string_lossy().to_string())
-        .unwrap_or_default()
+fn get_app_data_dir(app: tauri::AppHandle, config: State<'_, ConfigState>) -> String {
+    // 配置了 data_dir 时返回覆盖后的目录，前端据此拼接本地图片路径；与传给 sidecar 的 --data-dir 同源
+    config
+        .0
+        .runtime
+        .data_base(app_data_base(&app))
+        .to_string_lossy()
+        .to_string()
+}
+
+// 当前生效的启动期配置及每项来源（default/file/env/cli），便于排查托管部署
+#[tauri::command]
+fn get_runtime_config(config: State<'_, ConfigState>) -> config::RuntimeConfig {
+    config.0.runtime.clone()
+}
+
+// 生效配置 + 管理员策略：哪些项被锁定、更新/遥测是否被禁用，前端据此置灰对应设置
+#[tauri::command]
+fn get_effective_config(app: tauri::AppHandle) -> config::EffectiveConfig {
+    config::effective(&app)
 }
 
 // 获取日志目录，便于用户导出/提交诊断日志
@@ -216,13 +659,99 @@ fn get_log_dir(state: State<'_, LogState>) -> String {
     state.dir.to_string_lossy().to_string()
 }
 
+// 把脱敏后的诊断包（日志 + 运行环境）上传到 support.endpoint，返回支持端的参考编号；
+// 上传前在原生对话框里确认，前端无法静默带出日志
+#[tauri::command]
+async fn submit_diagnostics(
+    webview: tauri::Webview,
+    app: tauri::AppHandle,
+    settings: State<'_, SettingsState>,
+    config: State<'_, ConfigState>,
+    log: State<'_, LogState>,
+    ticket_id: String,
+) -> Result<diagnostics::SubmitReceipt, String> {
+    ipc_guard::require_trusted(&webview, "submit_diagnostics")?;
+    if !config.0.telemetry_enabled {
+        return Err("diagnostics upload is disabled by policy".to_string());
+    }
+    let ticket_id = diagnostics::validate_ticket(&ticket_id)?;
+    let endpoint = diagnostics::endpoint_url(&settings.0.get().support)?
+        .ok_or_else(|| "support endpoint is not configured".to_string())?;
+    let info = serde_json::json!({
+        "ticket_id": ticket_id,
+        "app": app.package_info().name,
+        "version": app.package_info().version.to_string(),
+        "os": std::env::consts::OS,
+        "arch": std::env::consts::ARCH,
+        "created_at": chrono::Local::now().to_rfc3339(),
+        "config": config.0,
+    });
+    let log_dir = log.dir.clone();
+    let confirm_app = app.clone();
+    let confirm_ticket = ticket_id.clone();
+    let host = endpoint.host_str().unwrap_or_default().to_string();
+    let (bundle, confirmed) = tauri::async_runtime::spawn_blocking(move || {
+        let bundle = diagnostics::build_bundle(&log_dir, &info)?;
+        let confirmed = confirm_app
+            .dialog()
+            .message(format!(
+                "将把诊断包（约 {} KB，API Key、令牌、邮箱等已隐藏）上传到 {}，工单号 {}。\n\n包含：{}",
+                bundle.bytes.len().div_ceil(1024),
+                host,
+                confirm_ticket,
+                bundle.files.join("、")
+            ))
+            .title("上传诊断信息")
+            .kind(MessageDialogKind::Info)
+            .buttons(MessageDialogButtons::OkCancelCustom(
+                "上传".to_string(),
+                "取消".to_string(),
+            ))
+            .blocking_show();
+        Ok::<_, String>((bundle, confirmed))
+    })
+    .await
+    .map_err(|e| format!("build diagnostics failed: {}", e))??;
+    if !confirmed {
+        return Err("diagnostics upload not confirmed by user".to_string());
+    }
+    let receipt = diagnostics::upload(
+        endpoint,
+        &ticket_id,
+        &app.package_info().version.to_string(),
+        bundle.bytes,
+    )
+    .await?;
+    log.log_app(
+        "INFO",
+        &format!(
+            "diagnostics submitted ticket={} reference={} bytes={}",
+            receipt.ticket_id, receipt.reference, receipt.bytes
+        ),
+    );
+    Ok(receipt)
+}
+
+// 发送应用内反馈，可附带日志（已脱敏）与主窗口截图；离线时排队，后台自动重试。
+// 反馈地址与密钥只在 Rust 侧，前端拿不到
+#[tauri::command]
+async fn send_feedback(
+    webview: tauri::Webview,
+    app: tauri::AppHandle,
+    text: String,
+    include_logs: bool,
+    include_screenshot: bool,
+) -> Result<feedback::FeedbackStatus, String> {
+    ipc_guard::require_trusted(&webview, "send_feedback")?;
+    feedback::send(&app, text, include_logs, include_screenshot).await
+}
+
 // 打开日志目录
 #[tauri::command]
-fn open_log_dir(app: tauri::AppHandle, state: State<'_, LogState>) -> Result<(), String> {
+fn open_log_dir(state: State<'_, LogState>) -> Result<(), String> {
     let _ = fs::create_dir_all(&state.dir);
-    let open_result = app
-        .opener()
-        .open_path(state.dir.to_string_lossy().to_string(), None::<String>);
+    // 原生侧调用不经过 opener_policy 的白名单
+    let open_result = tauri_plugin_opener::open_path(&state.dir, None::<String>);
 
     if let Err(err) = open_result {
         open_dir_with_command(&state.dir)
@@ -269,6 +798,9 @@ fn write_frontend_logs(
     const MAX_LINE_CHARS: usize = 4000;
 
     for entry in entries.into_iter().take(MAX_ENTRIES) {
+        if !state.enabled(&entry.level) {
+            continue;
+        }
         let level = entry.level.trim().to_uppercase();
         let mut msg = entry.message.replace('\r', "").replace('\n', "\\n");
         if msg.len() > MAX_LINE_CHARS {
@@ -281,12 +813,10 @@ fn write_frontend_logs(
             .unwrap_or_default()
             .replace('\r', "")
             .replace('\n', "\\n");
-        let mut line = format!("[{}] [FE] [{}] {}", now_ms(), level, msg);
-        if !ctx.trim().is_empty() {
-            if line.len() + ctx.len() + 4 <= MAX_LINE_CHARS {
-                line.push_str(" | ");
-                line.push_str(ctx.trim());
-            }
+        let mut line = format!("{} [FE] [{}] {}", state.stamp(), level, msg);
+        if !ctx.trim().is_empty() && line.len() + ctx.len() + 4 <= MAX_LINE_CHARS {
+            line.push_str(" | ");
+            line.push_str(ctx.trim());
         }
 
         state.app.write_line(&line);
@@ -294,36 +824,23 @@ fn write_frontend_logs(
     Ok(())
 }
 
-// 将本地图片写入系统剪贴板（用于 macOS 打包环境下 Web Clipboard API 不可用/不稳定的兜底）
-#[tauri::command]
-fn copy_image_to_clipboard(app: tauri::AppHandle, path: String) -> Result<(), String> {
-    use std::borrow::Cow;
-    use std::path::PathBuf;
-    use std::sync::mpsc;
-
-    let trimmed = path.trim();
-    if trimmed.is_empty() {
-        return Err("path is empty".to_string());
-    }
-
-    // 兼容 file:// URL（可能包含 host=localhost）
-    let normalized = if let Some(p) = trimmed.strip_prefix("file://localhost") {
-        p.to_string()
-    } else if let Some(p) = trimmed.strip_prefix("file://") {
-        p.to_string()
-    } else {
-        trimmed.to_string()
-    };
-
-    let input_path = PathBuf::from(normalized);
+// 读取命令传入的本地文件：相对路径优先视为存储逻辑 ID，由存储后端读取；
+// 兼容：后端历史可能存的是相对路径（如 storage/xxx.jpg），打包/开发环境工作目录也可能不同
+fn read_input_file(
+    app: &tauri::AppHandle,
+    storage: &dyn Storage,
+    path: &str,
+) -> Result<Vec<u8>, String> {
+    let input_path = file_url::to_path(path);
 
-    // 兼容：后端历史可能存的是相对路径（如 storage/xxx.jpg），打包/开发环境工作目录也可能不同
     let mut candidates: Vec<PathBuf> = Vec::new();
     if input_path.is_absolute() {
         candidates.push(input_path);
     } else {
-        if let Ok(app_data) = app.path().app_data_dir() {
-            candidates.push(app_data.join(&input_path));
+        if let Ok(id) = normalize_id(&input_path.to_string_lossy()) {
+            if storage.exists(&id) {
+                return storage.get(&id);
+            }
         }
         if let Ok(current_dir) = std::env::current_dir() {
             candidates.push(current_dir.join(&input_path));
@@ -337,12 +854,30 @@ fn copy_image_to_clipboard(app: tauri::AppHandle, path: String) -> Result<(), St
 
     let file_path = candidates
         .iter()
+        .map(|p| long_path::extend(p))
         .find(|p| p.exists())
-        .cloned()
-        .unwrap_or_else(|| candidates.first().cloned().unwrap());
+        .unwrap_or_else(|| long_path::extend(&candidates[0]));
+
+    std::fs::read(&file_path)
+        .map_err(|e| format!("read file failed: {} ({})", e, file_path.display()))
+}
+
+// 将本地图片写入系统剪贴板（用于 macOS 打包环境下 Web Clipboard API 不可用/不稳定的兜底）
+#[tauri::command]
+fn copy_image_to_clipboard(
+    app: tauri::AppHandle,
+    storage: State<'_, StorageState>,
+    path: String,
+) -> Result<(), String> {
+    use std::borrow::Cow;
+    use std::sync::mpsc;
+
+    let trimmed = path.trim();
+    if trimmed.is_empty() {
+        return Err("path is empty".to_string());
+    }
 
-    let bytes = std::fs::read(&file_path)
-        .map_err(|e| format!("read file failed: {} ({})", e, file_path.display()))?;
+    let bytes = read_input_file(&app, storage.0.as_ref(), trimmed)?;
 
     let img = image::load_from_memory(&bytes).map_err(|e| format!("decode image failed: {}", e))?;
     let rgba = img.to_rgba8();
@@ -376,14 +911,40 @@ fn copy_image_to_clipboard(app: tauri::AppHandle, path: String) -> Result<(), St
 // 复制文本到系统剪贴板（用于日志路径等）
 #[tauri::command]
 fn copy_text_to_clipboard(app: tauri::AppHandle, text: String) -> Result<(), String> {
-    use std::sync::mpsc;
-
     let trimmed = text.trim();
     if trimmed.is_empty() {
         return Err("text is empty".to_string());
     }
+    set_clipboard_text(&app, trimmed.to_string())
+}
+
+// 以 JSON 或 Markdown 复制某次生成的提示词与参数；走原生剪贴板，多行内容在 macOS 上不会被 WebView 打乱
+#[tauri::command(async)]
+fn copy_metadata_to_clipboard(
+    app: tauri::AppHandle,
+    library: State<'_, LibraryState>,
+    id: String,
+    format: Option<String>,
+) -> Result<String, String> {
+    let task_id = id.trim();
+    if task_id.is_empty() {
+        return Err("id is empty".to_string());
+    }
+    let format = generation_info::InfoFormat::parse(format.as_deref())?;
+    let conn = library.0.open()?;
+    let task = library
+        .0
+        .get_task(&conn, task_id)?
+        .ok_or_else(|| format!("task not found: {}", task_id))?;
+    let meta = library.0.get_meta(&conn, task_id)?;
+    let text = generation_info::GenerationInfo::new(&task, &meta).render(format)?;
+    set_clipboard_text(&app, text.clone())?;
+    Ok(text)
+}
+
+fn set_clipboard_text(app: &tauri::AppHandle, content: String) -> Result<(), String> {
+    use std::sync::mpsc;
 
-    let content = trimmed.to_string();
     let (tx, rx) = mpsc::channel::<Result<(), String>>();
     app.run_on_main_thread(move || {
         let result = (|| {
@@ -401,9 +962,32 @@ fn copy_text_to_clipboard(app: tauri::AppHandle, text: String) -> Result<(), Str
     rx.recv()
         .map_err(|_| "clipboard task aborted".to_string())?
 }
+// 剪贴板文本历史（需在设置中开启 clipboard.text_history），最近的在前
+#[tauri::command]
+fn get_clipboard_history(
+    webview: tauri::Webview,
+    history: State<'_, clipboard_history::ClipboardHistoryState>,
+) -> Result<Vec<clipboard_history::ClipboardEntry>, String> {
+    ipc_guard::require_trusted(&webview, "get_clipboard_history")?;
+    Ok(history.0.list())
+}
+
+#[tauri::command]
+fn clear_clipboard_history(
+    history: State<'_, clipboard_history::ClipboardHistoryState>,
+) -> Result<(), String> {
+    history.0.clear()
+}
+
 // 从系统剪贴板读取图片并写入 AppData 临时文件（用于打包环境下 Web ClipboardData 不可用/不稳定的兜底）
 #[tauri::command]
-fn read_image_from_clipboard(app: tauri::AppHandle) -> Result<Option<String>, String> {
+fn read_image_from_clipboard(
+    webview: tauri::Webview,
+    app: tauri::AppHandle,
+    storage: State<'_, StorageState>,
+    settings: State<'_, SettingsState>,
+) -> Result<Option<String>, String> {
+    ipc_guard::require_trusted(&webview, "read_image_from_clipboard")?;
     use std::sync::mpsc;
 
     // macOS 上部分剪贴板实现要求在主线程调用：统一切主线程读剪贴板
@@ -431,32 +1015,309 @@ fn read_image_from_clipboard(app: tauri::AppHandle) -> Result<Option<String>, St
         return Ok(None);
     };
 
-    let base = app
-        .path()
-        .app_data_dir()
-        .unwrap_or_else(|_| std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")));
-    let dir = base.join("clipboard");
-    fs::create_dir_all(&dir).map_err(|e| format!("create clipboard dir failed: {}", e))?;
-
-    let out_path = dir.join(format!("clipboard-{}.png", now_ms()));
     let w = width as u32;
     let h = height as u32;
     let buffer = image::ImageBuffer::<image::Rgba<u8>, Vec<u8>>::from_raw(w, h, bytes)
         .ok_or_else(|| "invalid clipboard image data".to_string())?;
+    let mut encoded = std::io::Cursor::new(Vec::new());
     buffer
-        .save(&out_path)
+        .write_to(&mut encoded, image::ImageFormat::Png)
         .map_err(|e| format!("save clipboard image failed: {}", e))?;
 
-    Ok(Some(out_path.to_string_lossy().to_string()))
+    let created_at = library::db_timestamp();
+    let ctx = naming::TemplateContext {
+        id: &format!("clipboard-{}", now_ms()),
+        prompt: "",
+        model: "",
+        provider: "",
+        created_at: &created_at,
+        width: w as i64,
+        height: h as i64,
+        seq: 1,
+    };
+    let id = naming::new_file_id(
+        storage.0.as_ref(),
+        &settings.0.get().naming,
+        "clipboard",
+        &ctx,
+        "png",
+    )?;
+    storage.0.put(&id, encoded.get_ref())?;
+
+    // 前端拿到的是可直接读取的本地路径；远端存储没有本地路径时退回逻辑 ID
+    let out = storage
+        .0
+        .local_path(&id)
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or(id);
+    Ok(Some(out))
+}
+
+#[tauri::command]
+fn get_settings(settings: State<'_, SettingsState>) -> settings::Settings {
+    settings.0.get()
+}
+
+// 局部更新原生层设置（只需传要改的字段），校验通过后落盘并返回完整设置
+#[tauri::command]
+fn update_settings(
+    webview: tauri::Webview,
+    app: tauri::AppHandle,
+    settings: State<'_, SettingsState>,
+    config: State<'_, ConfigState>,
+    publisher: State<'_, publish::PublishState>,
+    log: State<'_, LogState>,
+    patch: serde_json::Value,
+) -> Result<settings::Settings, String> {
+    ipc_guard::require_trusted(&webview, "update_settings")?;
+    config.0.check_patch(&patch)?;
+    let updated = settings.0.update(&log, patch)?;
+    publisher.0.notify();
+    priority::apply(&app);
+    Ok(updated)
+}
+
+#[tauri::command]
+fn list_settings_versions(settings: State<'_, SettingsState>) -> Vec<settings::SettingsVersion> {
+    settings.0.versions()
+}
+
+// 回滚到某个历史版本（回滚本身也会把当前设置归档，可以再回滚回来）
+#[tauri::command]
+fn revert_settings(
+    webview: tauri::Webview,
+    settings: State<'_, SettingsState>,
+    config: State<'_, ConfigState>,
+    log: State<'_, LogState>,
+    version: u64,
+) -> Result<settings::Settings, String> {
+    ipc_guard::require_trusted(&webview, "revert_settings")?;
+    let target = settings.0.read_version(version)?;
+    // 被策略锁定的运行参数不能借回滚改掉
+    let (from, to) = (
+        serde_json::to_value(settings.0.get().runtime).unwrap_or_default(),
+        serde_json::to_value(&target.runtime).unwrap_or_default(),
+    );
+    let empty = serde_json::Map::new();
+    let (from, to) = (
+        from.as_object().unwrap_or(&empty),
+        to.as_object().unwrap_or(&empty),
+    );
+    let changed: serde_json::Map<String, serde_json::Value> = from
+        .keys()
+        .chain(to.keys())
+        .filter(|key| from.get(*key) != to.get(*key))
+        .map(|key| (key.clone(), serde_json::Value::Null))
+        .collect();
+    config
+        .0
+        .check_patch(&serde_json::json!({ "runtime": changed }))?;
+    settings.0.replace(&log, target)
+}
+
+// 包含 API Key 的导入/导出必须经过用户在原生对话框中再次确认，前端无法静默带出密钥
+fn confirm_secrets(app: &tauri::AppHandle, message: &str) -> Result<(), String> {
+    let confirmed = app
+        .dialog()
+        .message(message)
+        .title("包含 API Key")
+        .kind(MessageDialogKind::Warning)
+        .buttons(MessageDialogButtons::OkCancelCustom(
+            "继续".to_string(),
+            "取消".to_string(),
+        ))
+        .blocking_show();
+    if confirmed {
+        Ok(())
+    } else {
+        Err("secrets not confirmed by user".to_string())
+    }
+}
+
+// 导出设置为可移植文件（原生层设置 + Provider 配置），默认不含 API Key
+#[tauri::command(async)]
+fn export_settings(
+    webview: tauri::Webview,
+    app: tauri::AppHandle,
+    settings: State<'_, SettingsState>,
+    library: State<'_, LibraryState>,
+    dest: String,
+    include_secrets: Option<bool>,
+) -> Result<String, String> {
+    ipc_guard::require_trusted(&webview, "export_settings")?;
+    let trimmed = dest.trim();
+    if trimmed.is_empty() {
+        return Err("dest is empty".to_string());
+    }
+    let include_secrets = include_secrets.unwrap_or(false);
+    if include_secrets {
+        confirm_secrets(
+            &app,
+            "导出的设置文件将包含明文 API Key，任何拿到该文件的人都可以使用你的账号额度。确定继续吗？",
+        )?;
+    }
+    let dest_path = file_url::to_path(trimmed);
+    settings::export_portable(&settings.0, &library.0, &dest_path, include_secrets)?;
+    Ok(dest_path.to_string_lossy().to_string())
+}
+
+// 导入设置文件；文件中的 API Key 只有 include_secrets 且用户确认后才会写入
+#[tauri::command(async)]
+fn import_settings(
+    webview: tauri::Webview,
+    app: tauri::AppHandle,
+    settings: State<'_, SettingsState>,
+    library: State<'_, LibraryState>,
+    feed: State<'_, changefeed::ChangefeedState>,
+    src: String,
+    include_secrets: Option<bool>,
+) -> Result<settings::ImportSettingsReport, String> {
+    ipc_guard::require_trusted(&webview, "import_settings")?;
+    let trimmed = src.trim();
+    if trimmed.is_empty() {
+        return Err("src is empty".to_string());
+    }
+    let include_secrets = include_secrets.unwrap_or(false);
+    if include_secrets {
+        confirm_secrets(
+            &app,
+            "将用设置文件中的 API Key 覆盖本机已保存的密钥。确定继续吗？",
+        )?;
+    }
+    let report = settings::import_portable(
+        &settings.0,
+        &app.state::<LogState>(),
+        &library.0,
+        &file_url::to_path(trimmed),
+        include_secrets,
+    )?;
+    feed.0.emit(
+        changefeed::ChangeKind::Updated,
+        "provider",
+        report.providers_updated.clone(),
+    );
+    Ok(report)
+}
+
+// 上传前校验并规范化参考图（格式/体积/尺寸/宽高比），返回可直接上传的临时文件与修改说明
+#[tauri::command(async)]
+fn prepare_reference(
+    webview: tauri::Webview,
+    app: tauri::AppHandle,
+    storage: State<'_, StorageState>,
+    path: String,
+    constraints: Option<reference::UploadConstraints>,
+) -> Result<reference::PreparedReference, String> {
+    ipc_guard::require_trusted(&webview, "prepare_reference")?;
+    let trimmed = path.trim();
+    if trimmed.is_empty() {
+        return Err("path is empty".to_string());
+    }
+    let bytes = read_input_file(&app, storage.0.as_ref(), trimmed)?;
+    let stem = naming::sanitize_stem(
+        &file_url::to_path(trimmed)
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default(),
+    );
+    reference::prepare(&bytes, &stem, &constraints.unwrap_or_default())
+}
+
+// 截取其他应用的窗口作为参考图：target 为 bundle id（com.figma.Desktop）、进程/应用名或窗口标题的一部分
+#[tauri::command(async)]
+fn capture_window_of_app(
+    webview: tauri::Webview,
+    target: String,
+    constraints: Option<reference::UploadConstraints>,
+) -> Result<window_capture::CapturedWindow, String> {
+    ipc_guard::require_trusted(&webview, "capture_window_of_app")?;
+    window_capture::capture(&target, &constraints.unwrap_or_default())
+}
+
+// 分片上传大体积参考图到后端，进度通过 upload-progress 推送；upload_id 为上次中断的会话时续传。
+// 来源只能是存储逻辑 ID，或用户在文件对话框里选过的文件（对话框会把所选文件加入 fs scope）
+#[tauri::command(async)]
+fn upload_reference(
+    webview: tauri::Webview,
+    app: tauri::AppHandle,
+    storage: State<'_, StorageState>,
+    path: String,
+    upload_id: Option<String>,
+) -> Result<upload::UploadResult, String> {
+    ipc_guard::require_trusted(&webview, "upload_reference")?;
+    let trimmed = path.trim();
+    if trimmed.is_empty() {
+        return Err("path is empty".to_string());
+    }
+    let source = file_url::to_path(trimmed);
+    let source = if source.is_absolute() {
+        if !app.fs_scope().is_allowed(&source) {
+            return Err(format!("path not allowed: {}", source.display()));
+        }
+        source
+    } else {
+        let id = normalize_id(trimmed)?;
+        storage
+            .0
+            .local_path(&id)
+            .ok_or_else(|| format!("file not found: {}", trimmed))?
+    };
+    upload::upload(&app, &source, upload_id.as_deref().map(str::trim))
+}
+
+// 把生成结果从后端直接流式写入存储（校验 SHA-256），返回逻辑 ID 与 nbimage:// 地址
+#[tauri::command(async)]
+fn save_generated_image(
+    webview: tauri::Webview,
+    app: tauri::AppHandle,
+    storage: State<'_, StorageState>,
+    task_id: String,
+) -> Result<image_fetch::SavedImage, String> {
+    ipc_guard::require_trusted(&webview, "save_generated_image")?;
+    image_fetch::save_generated(&app, storage.0.as_ref(), task_id.trim())
+}
+
+// 后端返回 data URI 时一次完成解码、校验、落盘、缩略图与入库，返回新记录
+#[tauri::command(async)]
+#[allow(clippy::too_many_arguments)]
+fn persist_data_uri(
+    webview: tauri::Webview,
+    storage: State<'_, StorageState>,
+    library: State<'_, LibraryState>,
+    settings: State<'_, SettingsState>,
+    feed: State<'_, changefeed::ChangefeedState>,
+    publisher: State<'_, publish::PublishState>,
+    data: String,
+    meta: Option<data_uri::DataUriMeta>,
+) -> Result<library::TaskRecord, String> {
+    ipc_guard::require_trusted(&webview, "persist_data_uri")?;
+    let record = data_uri::persist(
+        storage.0.as_ref(),
+        &library.0,
+        &settings.0.get(),
+        &data,
+        &meta.unwrap_or_default(),
+        now_ms(),
+    )?;
+    feed.0.emit(
+        changefeed::ChangeKind::Created,
+        "task",
+        vec![record.task_id.clone()],
+    );
+    publisher.0.notify();
+    Ok(record)
 }
 
 // 将任意本地图片复制到 AppData/ref_images（用于持久化参考图）
 #[tauri::command]
 fn persist_ref_image(
+    webview: tauri::Webview,
     app: tauri::AppHandle,
+    storage: State<'_, StorageState>,
     path: String,
     dest_name: String,
 ) -> Result<String, String> {
+    ipc_guard::require_trusted(&webview, "persist_ref_image")?;
     let trimmed = path.trim();
     if trimmed.is_empty() {
         return Err("path is empty".to_string());
@@ -469,51 +1330,964 @@ fn persist_ref_image(
         return Err("dest_name invalid".to_string());
     }
 
-    let normalized = if let Some(p) = trimmed.strip_prefix("file://localhost") {
-        p.to_string()
-    } else if let Some(p) = trimmed.strip_prefix("file://") {
-        p.to_string()
-    } else {
-        trimmed.to_string()
-    };
-
-    let input_path = PathBuf::from(normalized);
-    let mut candidates: Vec<PathBuf> = Vec::new();
-    if input_path.is_absolute() {
-        candidates.push(input_path);
-    } else {
-        if let Ok(app_data) = app.path().app_data_dir() {
-            candidates.push(app_data.join(&input_path));
+    let id = normalize_id(&format!("ref_images/{}", dest))?;
+    if !storage.0.exists(&id) {
+        let bytes = read_input_file(&app, storage.0.as_ref(), trimmed)?;
+        storage.0.put(&id, &bytes)?;
+        // 参考图会随请求上传到云端，带 GPS 定位时提醒用户，前端可一键调用 strip_location
+        if let Some(location) = geotag::gps_location(&bytes) {
+            let _ = app.emit(
+                geotag::PRIVACY_WARNING_EVENT,
+                geotag::PrivacyWarning {
+                    path: id.clone(),
+                    source: trimmed.to_string(),
+                    kind: "gps".to_string(),
+                    location,
+                },
+            );
         }
-        if let Ok(current_dir) = std::env::current_dir() {
-            candidates.push(current_dir.join(&input_path));
+    }
+
+    Ok(id)
+}
+
+// 翻译提示词：请求在 Rust 中发出，使用 provider_configs 里的密钥（或 settings 中配置的本机翻译服务）
+#[tauri::command]
+async fn translate_text(
+    webview: tauri::Webview,
+    library: State<'_, LibraryState>,
+    settings: State<'_, SettingsState>,
+    text: String,
+    target_lang: String,
+) -> Result<translate::Translation, String> {
+    ipc_guard::require_trusted(&webview, "translate_text")?;
+    if !feature_flags::enabled(webview.app_handle(), "prompt_translation") {
+        return Err("prompt translation is disabled".to_string());
+    }
+    let translation = settings.0.get().translation;
+    translate::translate(&library.0, &translation, &text, &target_lang).await
+}
+
+// 读取图片里嵌入的提示词与参数（SD WebUI / NovelAI 等工具写入的 PNG 文本块或 EXIF UserComment），
+// 自动识别 UTF-8 / GBK / Big5 / Shift_JIS / UTF-16 并修复乱码；path 可为应用存储内 ID 或外部文件。没有时返回 null
+#[tauri::command(async)]
+fn read_embedded_prompt(
+    webview: tauri::Webview,
+    app: tauri::AppHandle,
+    storage: State<'_, StorageState>,
+    path: String,
+) -> Result<Option<embedded_prompt::EmbeddedPrompt>, String> {
+    ipc_guard::require_trusted(&webview, "read_embedded_prompt")?;
+    if !feature_flags::enabled(&app, "embedded_prompt_import") {
+        return Err("embedded prompt import is disabled".to_string());
+    }
+    let trimmed = path.trim();
+    if trimmed.is_empty() {
+        return Err("path is empty".to_string());
+    }
+    let stored = normalize_id(&file_url::to_path(trimmed).to_string_lossy())
+        .ok()
+        .filter(|id| storage.0.exists(id));
+    let bytes = match &stored {
+        Some(id) => storage.0.get(id)?,
+        None => read_input_file(&app, storage.0.as_ref(), trimmed)?,
+    };
+    Ok(embedded_prompt::read(&bytes))
+}
+
+// 清除图片中的 GPS 定位：应用存储内的文件原地改写（原件进撤销日志，可用 undo_last_native_action 恢复）；
+// 外部文件不动原件，清理后的副本存到 ref_images 并返回新 ID。没有定位信息时原样返回
+#[tauri::command(async)]
+fn strip_location(
+    webview: tauri::Webview,
+    app: tauri::AppHandle,
+    storage: State<'_, StorageState>,
+    undo: State<'_, undo::UndoState>,
+    path: String,
+) -> Result<String, String> {
+    ipc_guard::require_trusted(&webview, "strip_location")?;
+    let trimmed = path.trim();
+    if trimmed.is_empty() {
+        return Err("path is empty".to_string());
+    }
+    let stored = normalize_id(&file_url::to_path(trimmed).to_string_lossy())
+        .ok()
+        .filter(|id| storage.0.exists(id));
+    let bytes = match &stored {
+        Some(id) => storage.0.get(id)?,
+        None => read_input_file(&app, storage.0.as_ref(), trimmed)?,
+    };
+    let Some(cleaned) = geotag::strip_location(&bytes) else {
+        return Ok(stored.unwrap_or_else(|| trimmed.to_string()));
+    };
+    match stored {
+        // 存储内的文件：先把原件存进撤销日志（存不下就不动文件），再原子替换
+        Some(id) => {
+            let action = undo.0.backup_file(&id, &bytes, &cleaned)?;
+            storage.0.replace(&id, &cleaned)?;
+            let _ = undo.0.record("strip_location", action);
+            Ok(id)
         }
-        if let Ok(resource_dir) = app.path().resource_dir() {
-            candidates.push(resource_dir.join(&input_path));
+        // 外部文件：原件不动，清理后的副本存到 ref_images
+        None => {
+            let source = file_url::to_path(trimmed);
+            let stem = naming::sanitize_stem(
+                &source
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+            );
+            let ext = source
+                .extension()
+                .map(|e| e.to_string_lossy().to_lowercase())
+                .unwrap_or_else(|| "jpg".to_string());
+            let stem = if stem.is_empty() {
+                "image".to_string()
+            } else {
+                stem
+            };
+            let id = normalize_id(&format!("ref_images/{}-noloc-{}.{}", stem, now_ms(), ext))?;
+            storage.0.put(&id, &cleaned)?;
+            Ok(id)
         }
-        candidates.push(input_path);
     }
+}
 
-    let file_path = candidates
+fn load_input_image(
+    app: &tauri::AppHandle,
+    storage: &dyn Storage,
+    path: &str,
+) -> Result<image::DynamicImage, String> {
+    let trimmed = path.trim();
+    if trimmed.is_empty() {
+        return Err("path is empty".to_string());
+    }
+    let bytes = read_input_file(app, storage, trimmed)?;
+    image::load_from_memory(&bytes).map_err(|e| format!("decode image failed: {}", e))
+}
+
+// 处理后的图片一律另存为 PNG 副本（<dir>/ 下按命名方案取名，默认沿用原文件名），不覆盖原图
+fn save_derived_image(
+    storage: &dyn Storage,
+    naming: &settings::NamingSettings,
+    dir: &str,
+    source: &str,
+    img: &image::RgbaImage,
+) -> Result<String, String> {
+    let stem: String = file_url::to_path(source.trim())
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+        .take(64)
+        .collect();
+    let stem = if stem.is_empty() {
+        "image".to_string()
+    } else {
+        stem
+    };
+
+    let mut encoded = std::io::Cursor::new(Vec::new());
+    img.write_to(&mut encoded, image::ImageFormat::Png)
+        .map_err(|e| format!("encode png failed: {}", e))?;
+
+    let created_at = library::db_timestamp();
+    let ctx = naming::TemplateContext {
+        id: &stem,
+        prompt: "",
+        model: "",
+        provider: "",
+        created_at: &created_at,
+        width: img.width() as i64,
+        height: img.height() as i64,
+        seq: 1,
+    };
+    let id = naming::new_file_id(storage, naming, dir, &ctx, "png")?;
+    storage.put(&id, encoded.get_ref())?;
+    Ok(storage
+        .local_path(&id)
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or(id))
+}
+
+// 给交付图嵌入不可见水印（payload 最长 32 字节），另存为 PNG 副本并返回其路径
+#[tauri::command(async)]
+fn embed_invisible_watermark(
+    webview: tauri::Webview,
+    app: tauri::AppHandle,
+    storage: State<'_, StorageState>,
+    settings: State<'_, SettingsState>,
+    path: String,
+    payload: String,
+) -> Result<String, String> {
+    ipc_guard::require_trusted(&webview, "embed_invisible_watermark")?;
+    let mut img = load_input_image(&app, storage.0.as_ref(), &path)?.to_rgba8();
+    watermark::embed(&mut img, &payload)?;
+    save_derived_image(
+        storage.0.as_ref(),
+        &settings.0.get().naming,
+        "watermarked",
+        &path,
+        &img,
+    )
+}
+
+// 检测图片中是否带有本工具嵌入的不可见水印
+#[tauri::command(async)]
+fn detect_invisible_watermark(
+    app: tauri::AppHandle,
+    storage: State<'_, StorageState>,
+    path: String,
+) -> Result<watermark::WatermarkDetection, String> {
+    let img = load_input_image(&app, storage.0.as_ref(), &path)?.to_rgba8();
+    Ok(watermark::detect(&img))
+}
+
+// 对指定区域高斯模糊（遮挡人脸/隐私参考内容），另存为打码副本并返回其路径
+#[tauri::command(async)]
+fn blur_regions(
+    webview: tauri::Webview,
+    app: tauri::AppHandle,
+    storage: State<'_, StorageState>,
+    settings: State<'_, SettingsState>,
+    path: String,
+    rects: Vec<postprocess::Rect>,
+    sigma: Option<f32>,
+) -> Result<String, String> {
+    ipc_guard::require_trusted(&webview, "blur_regions")?;
+    let mut img = load_input_image(&app, storage.0.as_ref(), &path)?.to_rgba8();
+    postprocess::blur_regions(&mut img, &rects, sigma)?;
+    save_derived_image(
+        storage.0.as_ref(),
+        &settings.0.get().naming,
+        "redacted",
+        &path,
+        &img,
+    )
+}
+
+// 导出时套用 .cube 3D LUT，让生成图与项目调色一致；结果另存为 PNG 副本
+#[tauri::command(async)]
+fn apply_lut(
+    webview: tauri::Webview,
+    app: tauri::AppHandle,
+    storage: State<'_, StorageState>,
+    settings: State<'_, SettingsState>,
+    path: String,
+    cube_file: String,
+) -> Result<String, String> {
+    ipc_guard::require_trusted(&webview, "apply_lut")?;
+    let trimmed = cube_file.trim();
+    if trimmed.is_empty() {
+        return Err("cube_file is empty".to_string());
+    }
+    let cube = read_input_file(&app, storage.0.as_ref(), trimmed)?;
+    let text = String::from_utf8(cube).map_err(|_| "cube file is not utf-8".to_string())?;
+    let lut = lut::Lut3d::parse(&text).map_err(|e| format!("parse cube failed: {}", e))?;
+
+    let mut img = load_input_image(&app, storage.0.as_ref(), &path)?.to_rgba8();
+    lut.apply(&mut img);
+    save_derived_image(
+        storage.0.as_ref(),
+        &settings.0.get().naming,
+        "graded",
+        &path,
+        &img,
+    )
+}
+
+// 原生后期滤镜（unsharp / grain / vignette），params 为对应滤镜的参数对象，缺省字段取默认值
+#[tauri::command(async)]
+fn apply_filter(
+    webview: tauri::Webview,
+    app: tauri::AppHandle,
+    storage: State<'_, StorageState>,
+    settings: State<'_, SettingsState>,
+    path: String,
+    filter: String,
+    params: Option<serde_json::Value>,
+) -> Result<String, String> {
+    ipc_guard::require_trusted(&webview, "apply_filter")?;
+    let filter = postprocess::Filter::parse(&filter, params.unwrap_or_default())?;
+    let mut img = load_input_image(&app, storage.0.as_ref(), &path)?.to_rgba8();
+    filter.apply(&mut img);
+    save_derived_image(
+        storage.0.as_ref(),
+        &settings.0.get().naming,
+        "filtered",
+        &path,
+        &img,
+    )
+}
+
+// 在图片上原生绘制文字（字幕/表情包），按原图分辨率排版，支持 emoji 与多行；color 为 #rrggbb[aa]
+#[tauri::command(async)]
+#[allow(clippy::too_many_arguments)]
+fn draw_text(
+    webview: tauri::Webview,
+    app: tauri::AppHandle,
+    storage: State<'_, StorageState>,
+    settings: State<'_, SettingsState>,
+    fonts: State<'_, fonts::FontState>,
+    text_state: State<'_, text::TextState>,
+    path: String,
+    text: String,
+    font: Option<String>,
+    size: f32,
+    pos: text::TextPos,
+    color: String,
+    options: Option<text::TextOptions>,
+) -> Result<String, String> {
+    ipc_guard::require_trusted(&webview, "draw_text")?;
+    let color = text::parse_color(&color)?;
+    let mut img = load_input_image(&app, storage.0.as_ref(), &path)?.to_rgba8();
+    {
+        let mut guard = text_state
+            .0
+            .lock()
+            .map_err(|_| "text renderer lock poisoned".to_string())?;
+        let renderer = guard.get_or_insert_with(|| text::TextRenderer::new(fonts.database()));
+        text::draw_text(
+            renderer,
+            &mut img,
+            &text,
+            font.as_deref(),
+            size,
+            pos,
+            color,
+            &options.unwrap_or_default(),
+        )?;
+    }
+    save_derived_image(
+        storage.0.as_ref(),
+        &settings.0.get().naming,
+        "captioned",
+        &path,
+        &img,
+    )
+}
+
+// 按模板批量重命名图片文件（{date} {time} {prompt:30} {seq} 等），文件与数据库一起原子更新
+#[tauri::command(async)]
+fn rename_images(
+    webview: tauri::Webview,
+    storage: State<'_, StorageState>,
+    library: State<'_, LibraryState>,
+    undo: State<'_, undo::UndoState>,
+    feed: State<'_, changefeed::ChangefeedState>,
+    ids: Vec<String>,
+    template: String,
+) -> Result<Vec<naming::RenamedImage>, String> {
+    ipc_guard::require_trusted(&webview, "rename_images")?;
+    let ids: Vec<String> = ids.iter().map(|id| id.trim().to_string()).collect();
+    let conn = library.0.open()?;
+    let before = library.0.get_tasks(&conn, &ids)?;
+    let renamed = naming::rename_images(storage.0.as_ref(), &library.0, &ids, template.trim())?;
+    // 撤销日志写入失败不影响操作本身
+    if let Ok(after) = library.0.get_tasks(&conn, &ids) {
+        let changes = undo::path_changes(&before, &after);
+        let _ = undo
+            .0
+            .record("rename", undo::UndoAction::MovePaths { changes });
+    }
+    feed.0.emit(
+        changefeed::ChangeKind::Updated,
+        "task",
+        renamed.iter().map(|r| r.id.clone()).collect(),
+    );
+    Ok(renamed)
+}
+
+// 按当前子目录规则整理已有图片；dry_run 为 true 时只返回移动计划
+#[tauri::command(async)]
+fn reorganize_existing(
+    webview: tauri::Webview,
+    storage: State<'_, StorageState>,
+    library: State<'_, LibraryState>,
+    settings: State<'_, SettingsState>,
+    undo: State<'_, undo::UndoState>,
+    feed: State<'_, changefeed::ChangefeedState>,
+    dry_run: bool,
+) -> Result<organize::ReorganizeReport, String> {
+    ipc_guard::require_trusted(&webview, "reorganize_existing")?;
+    let rules = settings.0.get().organize.rules;
+    if dry_run {
+        return organize::reorganize_existing(storage.0.as_ref(), &library.0, &rules, true);
+    }
+    let conn = library.0.open()?;
+    let before = library.0.completed_tasks(&conn)?;
+    let report = organize::reorganize_existing(storage.0.as_ref(), &library.0, &rules, false)?;
+    if let Ok(after) = library.0.completed_tasks(&conn) {
+        let changes = undo::path_changes(&before, &after);
+        let _ = undo
+            .0
+            .record("reorganize", undo::UndoAction::MovePaths { changes });
+    }
+    feed.0.emit(
+        changefeed::ChangeKind::Updated,
+        "task",
+        report.moved.iter().map(|m| m.id.clone()).collect(),
+    );
+    Ok(report)
+}
+
+// 智能相册（保存的搜索）：条件保存在历史库中，新图命中时发送 smart-collection-updated 事件
+#[tauri::command(async)]
+fn create_smart_collection(
+    library: State<'_, LibraryState>,
+    feed: State<'_, changefeed::ChangefeedState>,
+    name: String,
+    query: collections::CollectionQuery,
+) -> Result<collections::SmartCollection, String> {
+    let collection = collections::create(&library.0, &name, query)?;
+    feed.0.emit(
+        changefeed::ChangeKind::Created,
+        "collection",
+        vec![collection.id.clone()],
+    );
+    Ok(collection)
+}
+
+#[tauri::command(async)]
+fn list_smart_collections(
+    library: State<'_, LibraryState>,
+) -> Result<Vec<collections::SmartCollection>, String> {
+    collections::list(&library.0)
+}
+
+#[tauri::command(async)]
+fn delete_smart_collection(
+    webview: tauri::Webview,
+    library: State<'_, LibraryState>,
+    undo: State<'_, undo::UndoState>,
+    feed: State<'_, changefeed::ChangefeedState>,
+    id: String,
+) -> Result<(), String> {
+    ipc_guard::require_trusted(&webview, "delete_smart_collection")?;
+    let id = id.trim();
+    let existing = collections::list(&library.0)?
+        .into_iter()
+        .find(|c| c.id == id);
+    collections::delete(&library.0, id)?;
+    feed.0.emit(
+        changefeed::ChangeKind::Deleted,
+        "collection",
+        vec![id.to_string()],
+    );
+    if let Some(collection) = existing {
+        let _ = undo.0.record(
+            "delete_collection",
+            undo::UndoAction::RestoreCollection {
+                collection: Box::new(collection),
+            },
+        );
+    }
+    Ok(())
+}
+
+#[tauri::command(async)]
+fn evaluate_smart_collection(
+    library: State<'_, LibraryState>,
+    id: String,
+    limit: Option<u32>,
+    offset: Option<u32>,
+) -> Result<Vec<library::TaskRecord>, String> {
+    collections::evaluate(
+        &library.0,
+        id.trim(),
+        limit.unwrap_or(100),
+        offset.unwrap_or(0),
+    )
+}
+
+// 读取扩展元数据（含 revision），前端编辑前取一次，提交时作为 expected_revisions 回传
+#[tauri::command(async)]
+fn get_image_meta(
+    library: State<'_, LibraryState>,
+    ids: Vec<String>,
+) -> Result<HashMap<String, library::ImageMeta>, String> {
+    let conn = library.0.open()?;
+    ids.iter()
+        .map(|id| id.trim())
+        .filter(|id| !id.is_empty())
+        .map(|id| {
+            library
+                .0
+                .get_meta(&conn, id)
+                .map(|meta| (id.to_string(), meta))
+        })
+        .collect()
+}
+
+// 批量修改标题/项目/标签/收藏，一次 IPC 在单个事务里完成；write_sidecars 时同时写 .xmp。
+// 传入 expected_revisions 时做乐观并发检查，被其他窗口改过则返回 { code: "conflict", conflicts }
+#[tauri::command(async)]
+#[allow(clippy::too_many_arguments)]
+fn update_metadata_bulk(
+    webview: tauri::Webview,
+    storage: State<'_, StorageState>,
+    library: State<'_, LibraryState>,
+    undo: State<'_, undo::UndoState>,
+    feed: State<'_, changefeed::ChangefeedState>,
+    publisher: State<'_, publish::PublishState>,
+    ids: Vec<String>,
+    patch: metadata::MetadataPatch,
+    expected_revisions: Option<HashMap<String, u64>>,
+    write_sidecars: Option<bool>,
+) -> Result<metadata::BulkUpdateReport, metadata::MetadataUpdateError> {
+    ipc_guard::require_trusted(&webview, "update_metadata_bulk")?;
+    let ids: Vec<String> = ids
         .iter()
-        .find(|p| p.exists())
-        .cloned()
-        .unwrap_or_else(|| candidates.first().cloned().unwrap());
+        .map(|id| id.trim().to_string())
+        .filter(|id| !id.is_empty())
+        .collect();
+    let write_sidecars = write_sidecars.unwrap_or(false);
+    let conn = library.0.open()?;
+    let mut unique = ids.clone();
+    unique.sort();
+    unique.dedup();
+    let previous = unique
+        .iter()
+        .map(|id| library.0.get_meta(&conn, id).map(|meta| (id.clone(), meta)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let report = metadata::update_metadata_bulk(
+        storage.0.as_ref(),
+        &library.0,
+        &ids,
+        &patch,
+        &expected_revisions.unwrap_or_default(),
+        write_sidecars,
+    )?;
+    let _ = undo.0.record(
+        "metadata",
+        undo::UndoAction::RestoreMeta {
+            previous,
+            sidecars: write_sidecars,
+        },
+    );
+    feed.0.emit(changefeed::ChangeKind::Updated, "task", unique);
+    publisher.0.notify();
+    Ok(report)
+}
 
-    let base = app
-        .path()
-        .app_data_dir()
-        .unwrap_or_else(|_| std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")));
-    let dir = base.join("ref_images");
-    fs::create_dir_all(&dir).map_err(|e| format!("create ref_images failed: {}", e))?;
+// 撤销最近一次原生破坏性操作（重命名、整理、批量改元数据、删除智能相册），可连续调用
+#[tauri::command(async)]
+fn undo_last_native_action(
+    webview: tauri::Webview,
+    storage: State<'_, StorageState>,
+    library: State<'_, LibraryState>,
+    undo: State<'_, undo::UndoState>,
+    feed: State<'_, changefeed::ChangefeedState>,
+) -> Result<undo::UndoReport, String> {
+    ipc_guard::require_trusted(&webview, "undo_last_native_action")?;
+    let report = undo.0.undo_last(storage.0.as_ref(), &library.0)?;
+    if report.kind == "delete_collection" {
+        feed.0.emit(
+            changefeed::ChangeKind::Created,
+            "collection",
+            report.ids.clone(),
+        );
+    } else {
+        feed.0
+            .emit(changefeed::ChangeKind::Updated, "task", report.ids.clone());
+    }
+    Ok(report)
+}
+
+// 可撤销的操作列表，最近的在前
+#[tauri::command(async)]
+fn list_undoable_actions(undo: State<'_, undo::UndoState>) -> Vec<undo::UndoSummary> {
+    undo.0.list()
+}
+
+// 画廊分页列表（keyset 游标），filters 与智能相册的查询条件一致
+#[tauri::command(async)]
+fn list_images(
+    library: State<'_, LibraryState>,
+    cursor: Option<String>,
+    limit: Option<u32>,
+    sort: Option<String>,
+    filters: Option<collections::CollectionQuery>,
+) -> Result<gallery::ImagePage, String> {
+    gallery::list_images(
+        &library.0,
+        cursor.as_deref(),
+        limit,
+        sort.as_deref(),
+        &filters.unwrap_or_default(),
+    )
+}
+
+// 图库统计（按模型/日期计数、平均分辨率、常用标签、占用空间增长），供统计面板使用
+#[tauri::command(async)]
+fn get_library_stats(
+    storage: State<'_, StorageState>,
+    library: State<'_, LibraryState>,
+) -> Result<gallery::LibraryStats, String> {
+    gallery::library_stats(&library.0, storage.0.as_ref())
+}
+
+// 导入其他用户导出的项目包（zip：图片 + manifest + 提示词），去重后合并进历史库并返回冲突报告
+#[tauri::command(async)]
+fn import_bundle(
+    webview: tauri::Webview,
+    app: tauri::AppHandle,
+    storage: State<'_, StorageState>,
+    library: State<'_, LibraryState>,
+    settings: State<'_, SettingsState>,
+    feed: State<'_, changefeed::ChangefeedState>,
+    path: String,
+) -> Result<bundle::ImportReport, String> {
+    ipc_guard::require_trusted(&webview, "import_bundle")?;
+    let trimmed = path.trim();
+    if trimmed.is_empty() {
+        return Err("path is empty".to_string());
+    }
+    let bundle_path = file_url::to_path(trimmed);
+    let name = bundle_path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let mut job = progress::Job::begin(&app, &format!("导入项目包 {}", name), 0);
+    let report = bundle::import_bundle(
+        &bundle_path,
+        storage.0.as_ref(),
+        &library.0,
+        &settings.0.get(),
+        now_ms(),
+        &mut |done, total| {
+            job.set_total(total);
+            job.advance(done, "")
+        },
+    )?;
+    feed.0.emit(
+        changefeed::ChangeKind::Created,
+        "task",
+        report.imported.iter().map(|i| i.task_id.clone()).collect(),
+    );
+    Ok(report)
+}
+
+// 立即扫描一次发布目录规则（含之前导出失败的），返回本轮发布结果
+#[tauri::command(async)]
+fn publish_now(app: tauri::AppHandle) -> Result<publish::PublishReport, String> {
+    publish::run(&app, true)
+}
+
+// 直播叠加层的当前状态：最新结果、固定文件路径与 OBS 可用的本机地址
+#[tauri::command]
+fn get_overlay_status(
+    settings: State<'_, SettingsState>,
+    overlay: State<'_, overlay::OverlayState>,
+) -> overlay::OverlayStatus {
+    overlay.0.status(&settings.0.get().overlay)
+}
+
+// 导出单个结果为 .nbp（图片 + 生成参数 + 溯源链）
+#[tauri::command(async)]
+fn export_nbp(
+    webview: tauri::Webview,
+    app: tauri::AppHandle,
+    storage: State<'_, StorageState>,
+    library: State<'_, LibraryState>,
+    id: String,
+    dest: String,
+) -> Result<String, String> {
+    ipc_guard::require_trusted(&webview, "export_nbp")?;
+    let task_id = id.trim();
+    if task_id.is_empty() {
+        return Err("id is empty".to_string());
+    }
+    let trimmed = dest.trim();
+    if trimmed.is_empty() {
+        return Err("dest is empty".to_string());
+    }
+
+    let conn = library.0.open()?;
+    let task = library
+        .0
+        .get_task(&conn, task_id)?
+        .ok_or_else(|| format!("task not found: {}", task_id))?;
+
+    let mut dest_path = file_url::to_path(trimmed);
+    if dest_path.extension().and_then(|e| e.to_str()) != Some(nbp::NBP_EXTENSION) {
+        dest_path.set_extension(nbp::NBP_EXTENSION);
+    }
+    let info = app.package_info();
+    nbp::export_nbp(
+        storage.0.as_ref(),
+        &task,
+        &dest_path,
+        &info.name,
+        &info.version.to_string(),
+    )?;
+    Ok(dest_path.to_string_lossy().to_string())
+}
+
+// 在局域网临时分享一张图片：返回带一次性 token 的地址和对应二维码，手机扫码下载一次或到期后自动关闭
+#[tauri::command(async)]
+fn share_over_lan(
+    webview: tauri::Webview,
+    app: tauri::AppHandle,
+    storage: State<'_, StorageState>,
+    library: State<'_, LibraryState>,
+    id: String,
+    ttl_secs: Option<u64>,
+) -> Result<lan_share::LanShare, String> {
+    ipc_guard::require_trusted(&webview, "share_over_lan")?;
+    let task_id = id.trim();
+    if task_id.is_empty() {
+        return Err("id is empty".to_string());
+    }
+    let conn = library.0.open()?;
+    let task = library
+        .0
+        .get_task(&conn, task_id)?
+        .ok_or_else(|| format!("task not found: {}", task_id))?;
+    let image_id = normalize_id(&task.local_path)
+        .map_err(|_| format!("task {} has no local image", task_id))?;
+    let bytes = storage.0.get(&image_id)?;
+    let share = lan_share::start(&app, task_id, bytes, ttl_secs)?;
+    app.state::<LogState>()
+        .log_app("INFO", &format!("lan share started for {}", task_id));
+    Ok(share)
+}
+
+// 提前关闭当前的局域网分享；没有进行中的分享时返回 false
+#[tauri::command]
+fn stop_lan_share(app: tauri::AppHandle) -> Result<bool, String> {
+    lan_share::stop(&app)
+}
+
+// 多设备接力：开启局域网端点并通过 mDNS 广播，其他设备配对后可拉取最近的生成结果
+#[tauri::command]
+fn start_handoff(
+    webview: tauri::Webview,
+    app: tauri::AppHandle,
+    handoff: State<'_, handoff::HandoffState>,
+) -> Result<handoff::HandoffInfo, String> {
+    ipc_guard::require_trusted(&webview, "start_handoff")?;
+    handoff.start(&app)
+}
+
+// 关闭端点并作废所有配对
+#[tauri::command]
+fn stop_handoff(handoff: State<'_, handoff::HandoffState>) -> Result<bool, String> {
+    handoff.stop()
+}
+
+#[tauri::command]
+fn get_handoff_status(
+    handoff: State<'_, handoff::HandoffState>,
+) -> Result<Option<handoff::HandoffInfo>, String> {
+    Ok(handoff.info())
+}
+
+// 拒绝一个待确认的配对请求（对方随后输入配对码也会失败）
+#[tauri::command]
+fn reject_handoff_pairing(
+    handoff: State<'_, handoff::HandoffState>,
+    request_id: String,
+) -> Result<bool, String> {
+    handoff.reject(request_id.trim())
+}
+
+#[tauri::command]
+fn list_handoff_devices(
+    handoff: State<'_, handoff::HandoffState>,
+) -> Result<Vec<handoff::PairedDevice>, String> {
+    handoff.devices()
+}
+
+#[tauri::command]
+fn revoke_handoff_device(
+    handoff: State<'_, handoff::HandoffState>,
+    id: String,
+) -> Result<bool, String> {
+    handoff.revoke(id.trim())
+}
+
+// 导出单页 PDF：原图 + 提示词/参数信息块，可选附带一个指回应用入口的二维码（link 由前端给出）
+#[tauri::command(async)]
+#[allow(clippy::too_many_arguments)]
+fn export_annotated_pdf(
+    webview: tauri::Webview,
+    app: tauri::AppHandle,
+    storage: State<'_, StorageState>,
+    library: State<'_, LibraryState>,
+    fonts: State<'_, fonts::FontState>,
+    text_state: State<'_, text::TextState>,
+    id: String,
+    dest: String,
+    link: Option<String>,
+) -> Result<String, String> {
+    ipc_guard::require_trusted(&webview, "export_annotated_pdf")?;
+    let task_id = id.trim();
+    if task_id.is_empty() {
+        return Err("id is empty".to_string());
+    }
+    let trimmed = dest.trim();
+    if trimmed.is_empty() {
+        return Err("dest is empty".to_string());
+    }
+    let link = link.map(|l| l.trim().to_string()).filter(|l| !l.is_empty());
+    if let Some(link) = &link {
+        tauri::Url::parse(link).map_err(|e| format!("invalid link: {}", e))?;
+    }
 
-    let dest_path = dir.join(dest);
-    if !dest_path.exists() {
-        fs::copy(&file_path, &dest_path)
-            .map_err(|e| format!("copy file failed: {} ({})", e, file_path.display()))?;
+    let conn = library.0.open()?;
+    let task = library
+        .0
+        .get_task(&conn, task_id)?
+        .ok_or_else(|| format!("task not found: {}", task_id))?;
+    let meta = library.0.get_meta(&conn, task_id)?;
+    let image_id = normalize_id(&task.local_path)
+        .map_err(|_| format!("task {} has no local image", task_id))?;
+    let bytes = storage.0.get(&image_id)?;
+    let info = generation_info::GenerationInfo::new(&task, &meta);
+
+    let mut dest_path = file_url::to_path(trimmed);
+    if dest_path.extension().and_then(|e| e.to_str()) != Some("pdf") {
+        dest_path.set_extension("pdf");
     }
+    let package = app.package_info();
+    let creator = format!("{} {}", package.name, package.version);
+    let mut guard = text_state
+        .0
+        .lock()
+        .map_err(|_| "text renderer lock poisoned".to_string())?;
+    let renderer = guard.get_or_insert_with(|| text::TextRenderer::new(fonts.database()));
+    pdf::export_annotated(
+        renderer,
+        &bytes,
+        &info,
+        link.as_deref(),
+        &creator,
+        &dest_path,
+    )?;
+    Ok(dest_path.to_string_lossy().to_string())
+}
 
-    Ok(format!("ref_images/{}", dest))
+// 按设置里的导出预设批量导出（格式、尺寸、水印、元数据策略、目标目录），单张失败记在 failed 里
+#[tauri::command(async)]
+#[allow(clippy::too_many_arguments)]
+fn export_with_preset(
+    webview: tauri::Webview,
+    app: tauri::AppHandle,
+    storage: State<'_, StorageState>,
+    library: State<'_, LibraryState>,
+    settings: State<'_, SettingsState>,
+    fonts: State<'_, fonts::FontState>,
+    text_state: State<'_, text::TextState>,
+    ids: Vec<String>,
+    preset: String,
+) -> Result<export_preset::ExportReport, String> {
+    ipc_guard::require_trusted(&webview, "export_with_preset")?;
+    let export = settings.0.get().export;
+    let preset = export_preset::find(&export, &preset)?;
+    let mut job = progress::Job::begin(&app, &format!("导出「{}」", preset.name.trim()), ids.len());
+    export_preset::export(
+        storage.0.as_ref(),
+        &library.0,
+        &text_state,
+        &fonts,
+        preset,
+        &ids,
+        &mut |done, _| job.advance(done, ""),
+    )
+}
+
+// 取消正在进行的原生长任务（id 见 job-progress 事件）；任务已结束时返回 false
+#[tauri::command]
+fn cancel_native_job(jobs: State<'_, progress::JobsState>, id: u64) -> bool {
+    jobs.0.cancel(id)
+}
+
+// 导出单张图片并嵌入 C2PA Content Credentials（需要用户提供签名证书与私钥）
+#[tauri::command(async)]
+fn export_with_credentials(
+    webview: tauri::Webview,
+    app: tauri::AppHandle,
+    storage: State<'_, StorageState>,
+    library: State<'_, LibraryState>,
+    id: String,
+    dest: String,
+    options: credentials::CredentialOptions,
+) -> Result<String, String> {
+    ipc_guard::require_trusted(&webview, "export_with_credentials")?;
+    let task_id = id.trim();
+    if task_id.is_empty() {
+        return Err("id is empty".to_string());
+    }
+    let trimmed = dest.trim();
+    if trimmed.is_empty() {
+        return Err("dest is empty".to_string());
+    }
+
+    let conn = library.0.open()?;
+    let task = library
+        .0
+        .get_task(&conn, task_id)?
+        .ok_or_else(|| format!("task not found: {}", task_id))?;
+    let image_id = normalize_id(&task.local_path)
+        .map_err(|_| format!("task {} has no local image", task_id))?;
+    let bytes = storage.0.get(&image_id)?;
+
+    // 目标扩展名与原图保持一致，C2PA 按原格式嵌入
+    let mut dest_path = file_url::to_path(trimmed);
+    if let Some(ext) = Path::new(&image_id).extension() {
+        dest_path.set_extension(ext);
+    }
+    let mime = match image::ImageFormat::from_path(&image_id) {
+        Ok(image::ImageFormat::Png) => "image/png",
+        Ok(image::ImageFormat::WebP) => "image/webp",
+        _ => "image/jpeg",
+    };
+
+    let info = app.package_info();
+    credentials::sign_export(
+        &bytes,
+        mime,
+        &task,
+        &options,
+        &info.name,
+        &info.version.to_string(),
+        &dest_path,
+    )?;
+    Ok(dest_path.to_string_lossy().to_string())
+}
+
+// 打开 .nbp：返回元数据与溯源链，并把图片解到存储中供预览
+#[tauri::command(async)]
+fn open_nbp(
+    webview: tauri::Webview,
+    storage: State<'_, StorageState>,
+    path: String,
+) -> Result<nbp::OpenedNbp, String> {
+    ipc_guard::require_trusted(&webview, "open_nbp")?;
+    let trimmed = path.trim();
+    if trimmed.is_empty() {
+        return Err("path is empty".to_string());
+    }
+    nbp::open_nbp(storage.0.as_ref(), &file_url::to_path(trimmed))
+}
+
+// 前端就绪后取走待打开的 .nbp 文件（冷启动时事件可能早于页面监听）
+#[tauri::command]
+fn take_pending_nbp_files(state: State<'_, PendingOpenState>) -> Vec<String> {
+    state
+        .0
+        .lock()
+        .map(|mut files| std::mem::take(&mut *files))
+        .unwrap_or_default()
+}
+
+fn is_nbp_path(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.eq_ignore_ascii_case(nbp::NBP_EXTENSION))
+        .unwrap_or(false)
 }
 
 #[tauri::command]
@@ -521,230 +2295,868 @@ fn greet(name: &str) -> String {
     format!("Hello, {}! You've been greeted from Rust!", name)
 }
 
-fn kill_sidecar(app_handle: &tauri::AppHandle) {
-    let sidecar_state = app_handle.state::<SidecarState>();
-    let log_state = app_handle.state::<LogState>();
-    let mut guard = sidecar_state.0.lock().unwrap();
-    if let Some(child) = guard.take() {
-        log_state.log_app("INFO", "Killing sidecar process on app exit.");
-        if let Err(err) = child.kill() {
-            log_state.log_app("ERROR", &format!("Failed to kill sidecar: {}", err));
+// 通过系统无障碍接口播报（VoiceOver / 讲述人 / NVDA），priority: low | medium | high
+#[tauri::command]
+fn announce(
+    window: tauri::WebviewWindow,
+    message: String,
+    priority: Option<String>,
+) -> Result<bool, String> {
+    let priority = accessibility::Priority::parse(priority.as_deref())?;
+    accessibility::announce(&window, &message, priority)
+}
+
+// 系统无障碍偏好（减弱动态效果、增强对比度等），变化时另有 accessibility-prefs-changed 事件
+#[tauri::command]
+fn get_accessibility_prefs() -> accessibility::AccessibilityPrefs {
+    accessibility::accessibility_prefs()
+}
+
+// 蒙版画布获得焦点时开始转发原生数位板压感/倾斜（stylus-input 事件），返回 false 表示本平台不需要
+#[tauri::command]
+fn start_stylus_input(window: tauri::WebviewWindow) -> Result<bool, String> {
+    stylus::start(&window)
+}
+
+#[tauri::command]
+fn stop_stylus_input(app: tauri::AppHandle) -> Result<(), String> {
+    stylus::stop(&app)
+}
+
+// 已安装字体（族名/字重/样式/文件），供水印、联系表等选择字体
+#[tauri::command(async)]
+fn list_system_fonts(fonts: State<'_, fonts::FontState>) -> Vec<fonts::SystemFont> {
+    fonts::list_system_fonts(fonts.database())
+}
+
+fn updater_pubkey(app: &tauri::AppHandle) -> Result<String, String> {
+    app.config()
+        .plugins
+        .0
+        .get("updater")
+        .and_then(|v| v.get("pubkey"))
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string())
+        .ok_or_else(|| "updater pubkey not configured".to_string())
+}
+
+// 检查是否有单独发布的后端更新（只替换 sidecar，不需要整包更新）
+#[tauri::command]
+async fn check_sidecar_update(
+    app: tauri::AppHandle,
+    store: State<'_, sidecar_update::SidecarUpdateState>,
+) -> Result<Option<sidecar_update::SidecarUpdateInfo>, String> {
+    if config::check_updater(&app).is_err() {
+        return Ok(None);
+    }
+    let app_version = app.package_info().version.to_string();
+    let current_version = store.0.current_version(&app_version);
+    let manifest = sidecar_update::fetch_manifest().await?;
+    Ok(
+        sidecar_update::applicable(&manifest, &current_version, &app_version).map(|entry| {
+            sidecar_update::SidecarUpdateInfo {
+                version: manifest.version.clone(),
+                current_version: current_version.clone(),
+                notes: manifest.notes.clone(),
+                size: entry.size,
+            }
+        }),
+    )
+}
+
+// 下载并安装后端更新（校验和 + 签名），下次启动后端时生效，也可调用 apply_sidecar_update 立即换上；进度通过 sidecar-update-progress 事件推送
+#[tauri::command]
+async fn install_sidecar_update(
+    webview: tauri::Webview,
+    app: tauri::AppHandle,
+    store: State<'_, sidecar_update::SidecarUpdateState>,
+) -> Result<sidecar_update::SidecarInstallResult, String> {
+    ipc_guard::require_trusted(&webview, "install_sidecar_update")?;
+    config::check_updater(&app)?;
+    let app_version = app.package_info().version.to_string();
+    let current_version = store.0.current_version(&app_version);
+    let manifest = sidecar_update::fetch_manifest().await?;
+    let entry = sidecar_update::applicable(&manifest, &current_version, &app_version)
+        .ok_or_else(|| "no applicable backend update".to_string())?;
+    let pubkey = updater_pubkey(&app)?;
+    let installed = store
+        .0
+        .install(&app, &manifest.version, entry, &pubkey)
+        .await?;
+    app.state::<LogState>().log_app(
+        "INFO",
+        &format!(
+            "backend update {} installed (delta: {})",
+            installed.installed.version, installed.delta
+        ),
+    );
+    Ok(installed)
+}
+
+#[derive(Clone, serde::Serialize)]
+struct AppUpdateResult {
+    version: String,
+    delta: bool,
+}
+
+// 整包更新：清单里有针对当前版本的补丁且本地留有上次的更新包时走增量，否则完整下载。
+// 安装完成后需要前端调用 relaunch 重启（Windows 安装程序会自行退出应用）
+#[tauri::command]
+async fn install_app_update(
+    webview: tauri::Webview,
+    app: tauri::AppHandle,
+    cache: State<'_, delta::AppUpdateCacheState>,
+    log: State<'_, LogState>,
+) -> Result<Option<AppUpdateResult>, String> {
+    ipc_guard::require_trusted(&webview, "install_app_update")?;
+    use tauri_plugin_updater::UpdaterExt;
+
+    config::check_updater(&app)?;
+    let update = app
+        .updater()
+        .map_err(|e| format!("updater init failed: {}", e))?
+        .check()
+        .await
+        .map_err(|e| format!("check update failed: {}", e))?;
+    let Some(update) = update else {
+        return Ok(None);
+    };
+    let pubkey = updater_pubkey(&app)?;
+
+    let patched = match (
+        delta::app_patch(
+            &update.raw_json,
+            update.download_url.as_str(),
+            &update.current_version,
+        ),
+        cache.0.base(&update.current_version),
+    ) {
+        (Some(patch), Some(base)) => {
+            let result = delta::download_and_apply(&app, &base, &patch, delta::APP_PROGRESS_EVENT)
+                .await
+                .and_then(|bytes| {
+                    sidecar_update::verify_signature(&bytes, &update.signature, &pubkey)
+                        .map(|_| bytes)
+                });
+            match result {
+                Ok(bytes) => Some(bytes),
+                Err(e) => {
+                    log.log_app("WARN", &format!("delta update failed, falling back: {}", e));
+                    None
+                }
+            }
+        }
+        _ => None,
+    };
+    let delta = patched.is_some();
+    let bytes = match patched {
+        Some(bytes) => bytes,
+        None => {
+            let handle = app.clone();
+            let mut downloaded = 0u64;
+            update
+                .download(
+                    move |chunk, total| {
+                        downloaded += chunk as u64;
+                        let _ = handle.emit(
+                            delta::APP_PROGRESS_EVENT,
+                            serde_json::json!({ "downloaded": downloaded, "total": total }),
+                        );
+                    },
+                    || {},
+                )
+                .await
+                .map_err(|e| format!("download update failed: {}", e))?
         }
+    };
+
+    // 留作下次增量更新的基准，失败不影响本次安装
+    if let Err(e) = cache.0.store(&update.version, &bytes) {
+        log.log_app("WARN", &format!("cache update bundle failed: {}", e));
     }
+    log.log_app(
+        "INFO",
+        &format!(
+            "installing app update {} (delta: {})",
+            update.version, delta
+        ),
+    );
+    update
+        .install(&bytes)
+        .map_err(|e| format!("install update failed: {}", e))?;
+    Ok(Some(AppUpdateResult {
+        version: update.version.clone(),
+        delta,
+    }))
+}
+
+// 不重启应用，立即换上当前生效的后端版本（安装或回滚之后调用）：结束旧 sidecar、启动新文件，
+// 新进程就绪后返回其端口，同时照常发出 backend-port / backend-restarted 供前端重连
+#[tauri::command]
+async fn apply_sidecar_update(
+    webview: tauri::Webview,
+    app: tauri::AppHandle,
+    store: State<'_, sidecar_update::SidecarUpdateState>,
+    safe_mode: State<'_, safe_mode::SafeModeState>,
+    timeout_ms: Option<u64>,
+) -> Result<supervisor::RestartedNotice, String> {
+    ipc_guard::require_trusted(&webview, "apply_sidecar_update")?;
+    if safe_mode.0.active {
+        return Err("backend updates are not used in safe mode".to_string());
+    }
+    // 与启动时一样，新文件先过自检
+    let (program, notes) = match store.0.active_binary()? {
+        Some((path, _)) => {
+            let notes = sidecar::preflight_path(&path)?;
+            (Some(path), notes)
+        }
+        None => {
+            let mut notes = sidecar::preflight("server")?;
+            notes.extend(sidecar::verify_integrity("server").map_err(|f| f.message)?);
+            (None, notes)
+        }
+    };
+    let log = app.state::<LogState>();
+    for note in notes {
+        log.log_app("INFO", &format!("sidecar preflight: {}", note));
+    }
+    let timeout =
+        std::time::Duration::from_millis(timeout_ms.unwrap_or(backend_ready::DEFAULT_TIMEOUT_MS));
+    tauri::async_runtime::spawn_blocking(move || supervisor::hot_swap(&app, program, timeout))
+        .await
+        .map_err(|e| format!("hot swap failed: {}", e))?
+}
+
+// 回滚到上一个后端版本（没有则回到包内自带版本），返回回滚后的版本号，null 表示包内版本
+#[tauri::command]
+fn rollback_sidecar_update(
+    webview: tauri::Webview,
+    store: State<'_, sidecar_update::SidecarUpdateState>,
+    log: State<'_, LogState>,
+) -> Result<Option<String>, String> {
+    ipc_guard::require_trusted(&webview, "rollback_sidecar_update")?;
+    let version = store.0.rollback()?;
+    log.log_app(
+        "INFO",
+        &format!(
+            "backend rolled back to {}",
+            version.as_deref().unwrap_or("bundled")
+        ),
+    );
+    Ok(version)
+}
+
+// 启动 API 后端与 sidecar.extra 里的其他 sidecar，在 setup 返回后的后台线程里执行
+fn start_backend(app: &tauri::AppHandle, updated_sidecar: Option<(PathBuf, String)>, safe: bool) {
+    let log = app.state::<LogState>();
+    let _ = app.emit(supervisor::STARTING_EVENT, ());
+
+    // 自检失败（签名无效、可执行位被去掉等）时不再启动，直接告诉用户原因，而不是留下一个没有后端的空壳
+    let preflight = match &updated_sidecar {
+        // 替换版本在 setup 里已经检查过
+        Some(_) => Ok(Vec::new()),
+        None => sidecar::preflight("server"),
+    };
+    match preflight {
+        Ok(notes) => {
+            for note in notes {
+                log.log_app("INFO", &format!("sidecar preflight: {}", note));
+            }
+        }
+        Err(e) => {
+            log.log_app("ERROR", &format!("sidecar preflight failed: {}", e));
+            report_sidecar_failure(app, &e);
+            return;
+        }
+    }
+    if updated_sidecar.is_none() {
+        match sidecar::verify_integrity("server") {
+            Ok(Some(note)) => log.log_app("INFO", &format!("sidecar preflight: {}", note)),
+            Ok(None) => log.log_app(
+                "WARN",
+                &format!(
+                    "sidecar integrity check skipped: {}",
+                    sidecar::integrity_skip_reason()
+                ),
+            ),
+            Err(failure) => {
+                log.log_app(
+                    "ERROR",
+                    &format!(
+                        "sidecar integrity check failed: {} (expected {}, actual {})",
+                        failure.message, failure.expected, failure.actual
+                    ),
+                );
+                let _ = app.emit(sidecar::INTEGRITY_FAILED_EVENT, &failure);
+                report_sidecar_failure(app, &failure.message);
+                return;
+            }
+        }
+    }
+
+    // 上次崩溃遗留的 sidecar 会占着端口与存储目录，先清掉再启动
+    let mut programs: Vec<PathBuf> = sidecar::sidecar_path("server").into_iter().collect();
+    programs.extend(updated_sidecar.as_ref().map(|(path, _)| path.clone()));
+    for note in stale_sidecar::cleanup(&programs) {
+        log.log_app("WARN", &format!("stale sidecar cleanup: {}", note));
+    }
+
+    // 自检期间用户已经退出应用
+    if app.state::<supervisor::SupervisorState>().0.is_stopping() {
+        return;
+    }
+    if let Err(e) = supervisor::spawn(app) {
+        report_sidecar_failure(app, &e);
+    }
+    if !safe && feature_flags::enabled(app, "extra_sidecars") {
+        let extra = app.state::<SettingsState>().0.get().sidecar.extra;
+        sidecar_manager::start(app, &extra);
+    }
+    health::start(app.clone());
+}
+
+// 后端无法启动时的原生错误提示（不依赖前端页面是否已加载）
+fn report_sidecar_failure(app: &tauri::AppHandle, reason: &str) {
+    if let Some(health) = app.try_state::<health::HealthState>() {
+        health
+            .0
+            .set(app, health::Status::Down, Some(reason.to_string()));
+    }
+    app.dialog()
+        .message(format!(
+            "后端服务无法启动，应用暂时不可用。\n\n原因：{}\n\n请将应用移到「应用程序」文件夹后重新打开，或重新下载安装。",
+            reason
+        ))
+        .title("启动失败")
+        .kind(MessageDialogKind::Error)
+        .show(|_| {});
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     let port_state = Arc::new(Mutex::new(0u16)); // 初始为 0
-    let port_state_for_setup = port_state.clone();
     let port_state_for_state = port_state.clone();
     let generation_state = Arc::new(Mutex::new(false));
     let quit_guard_state = Arc::new(Mutex::new(QuitGuard::default()));
-
-    tauri::Builder::default()
+    // Windows/Linux 通过命令行参数传入双击打开的文件
+    let pending_open: Vec<String> = std::env::args()
+        .skip(1)
+        .filter(|arg| is_nbp_path(Path::new(arg)))
+        .collect();
+    // 管理员策略不依赖 AppHandle，提前加载：策略禁用更新时连 updater 插件都不注册
+    let policy = config::load_policy();
+    let safe_mode = safe_mode::detect();
+    let safe = safe_mode.active;
+
+    let mut builder = tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
-        .plugin(tauri_plugin_opener::init())
+        .plugin(opener_policy::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_process::init())
-        .plugin(tauri_plugin_os::init())
-        .plugin(tauri_plugin_updater::Builder::new().build())
+        .plugin(tauri_plugin_os::init());
+    if policy.policy.updater != Some(false) {
+        builder = builder.plugin(tauri_plugin_updater::Builder::new().build());
+    }
+    if safe {
+        builder = builder.plugin(
+            tauri::plugin::Builder::<tauri::Wry>::new("safe-mode")
+                .js_init_script(safe_mode::init_script())
+                .build(),
+        );
+    }
+
+    builder
         .manage(BackendPort(port_state_for_state))
+        .manage(port_events::PortAnnouncer::default())
         .manage(GenerationState(generation_state))
         .manage(QuitGuardState(quit_guard_state))
+        .manage(fonts::FontState::default())
+        .manage(text::TextState::default())
+        .manage(progress::JobsState(progress::Jobs::default()))
+        .manage(app_nap::AppNapState(app_nap::AppNap::default()))
+        .manage(priority::PriorityState(priority::Priority::default()))
+        .manage(PendingOpenState(Arc::new(Mutex::new(pending_open))))
+        .manage(safe_mode::SafeModeState(safe_mode))
+        .manage(backend_bridge::BackendSocket::default())
+        .manage(lan_share::LanShareState::default())
+        .manage(handoff::HandoffState::default())
+        .manage(rate_limit::RateLimitState(
+            rate_limit::RateLimiter::default(),
+        ))
+        .register_asynchronous_uri_scheme_protocol(protocol::SCHEME, protocol::handle)
+        .register_asynchronous_uri_scheme_protocol(backend_bridge::SCHEME, backend_bridge::handle)
         .setup(move |app| {
-            let log_state = LogState::init(&app.handle());
+            // settings.json 固定在默认 AppData 下，data_dir 覆盖只影响图片、历史库与缓存
+            let default_base = app_data_base(app.handle());
+            let settings_path = default_base.join("settings.json");
+            let (settings_store, runtime, settings_warnings) = if safe {
+                (
+                    SettingsStore::defaults(settings_path),
+                    config::RuntimeConfig::load_safe(&policy.policy.runtime),
+                    Vec::new(),
+                )
+            } else {
+                let (store, warnings) = SettingsStore::load(settings_path);
+                let runtime =
+                    config::RuntimeConfig::load(&policy.policy.runtime, &store.get().runtime);
+                (store, runtime, warnings)
+            };
+            let log_state = LogState::init(app.handle(), &runtime.log_level.value);
             app.manage(log_state.clone());
-
-            let shell = app.shell();
-            let sidecar_command = shell
-                .sidecar("server")
-                .unwrap()
-                .env("TAURI_PLATFORM", "macos")
-                .env("TAURI_FAMILY", "unix")
-                .env("GODEBUG", "http2debug=2")
-                .env("GIN_MODE", "release");
-
-            println!("Attempting to spawn sidecar...");
-            log_state.log_app("INFO", "Attempting to spawn sidecar...");
-
-            let (mut rx, child) = sidecar_command.spawn().expect("Failed to spawn sidecar");
-
-            println!("Sidecar spawned with PID: {:?}", child.pid());
-            log_state.log_app(
-                "INFO",
-                &format!("Sidecar spawned with PID: {:?}", child.pid()),
+            for warning in &settings_warnings {
+                log_state.log_app("WARN", warning);
+            }
+            if safe {
+                let trigger = app.state::<safe_mode::SafeModeState>().0.trigger.clone();
+                log_state.log_app(
+                    "WARN",
+                    &format!(
+                        "safe mode active (trigger: {})",
+                        trigger.as_deref().unwrap_or("unknown")
+                    ),
+                );
+            }
+            if let Some(path) = &policy.path {
+                log_state.log_app("INFO", &format!("managed policy loaded from {}", path));
+            }
+            if let Some(error) = &policy.error {
+                log_state.log_app("ERROR", &format!("managed policy ignored: {}", error));
+            }
+            for warning in &runtime.warnings {
+                log_state.log_app("WARN", &format!("config: {}", warning));
+            }
+            let data_base = runtime.data_base(default_base.clone());
+            if let Err(e) = fs::create_dir_all(&data_base) {
+                log_state.log_app(
+                    "ERROR",
+                    &format!("create data dir failed: {} ({})", e, data_base.display()),
+                );
+            }
+            if !safe {
+                legacy_migration::run(&log_state, &data_base, &default_base);
+            }
+            let feed = Arc::new(changefeed::Changefeed::new(app.handle().clone()));
+            app.manage(StorageState(Arc::new(changefeed::NotifyingStorage::new(
+                Arc::new(LocalStorage::new(data_base.clone())),
+                feed.clone(),
+            ))));
+            app.manage(changefeed::ChangefeedState(feed));
+            app.manage(LibraryState(Library::new(data_base.join("data.db"))));
+            app.manage(publish::PublishState(publish::Publisher::default()));
+            publish::start(app.handle().clone());
+            app.manage(overlay::OverlayState(overlay::Overlay::default()));
+            overlay::start(app.handle().clone());
+            app.manage(clipboard_history::ClipboardHistoryState(
+                clipboard_history::ClipboardHistory::new(
+                    default_base.join("clipboard-history.json"),
+                ),
+            ));
+            if !safe {
+                clipboard_history::start(app.handle().clone());
+            }
+            app.manage(feedback::FeedbackState(feedback::FeedbackQueue::new(
+                default_base.join("feedback"),
+            )));
+            feedback::start(app.handle().clone());
+            app.manage(feature_flags::FeatureFlagState(
+                feature_flags::FeatureFlagStore::new(default_base.join("feature-flags.json"), safe),
+            ));
+            feature_flags::start(app.handle().clone());
+            app.manage(config::KillSwitchState(config::KillSwitchStore::new(
+                default_base.join("kill-switch.json"),
+            )));
+            config::start_kill_switch(app.handle().clone());
+            app.manage(license::LicenseState(license::Licensing::new(
+                default_base.join("license.json"),
+            )));
+            license::start(app.handle().clone());
+            let key_slots = key_slots::KeySlots::new(
+                default_base.join("key-slots.json"),
+                format!("{}.key-slots", app.config().identifier),
             );
-
-            let sidecar_state = Arc::new(Mutex::new(Some(child)));
-            app.manage(SidecarState(sidecar_state.clone()));
-            let child_clone = sidecar_state.clone();
-
-            let app_handle = app.handle().clone();
-            let port_state_inner = port_state_for_setup.clone();
-            let log_state_for_task = log_state.clone();
-
-            tauri::async_runtime::spawn(async move {
-                while let Some(event) = rx.recv().await {
-                    match event {
-                        CommandEvent::Stdout(line) => {
-                            let out = String::from_utf8_lossy(&line);
-                            println!("Sidecar STDOUT: {}", out);
-                            log_state_for_task.log_server("STDOUT", out.trim_end());
-
-                            if out.contains("SERVER_PORT=") {
-                                if let Some(port_str) = out.split('=').last() {
-                                    if let Ok(port) = port_str.trim().parse::<u16>() {
-                                        println!("Detected backend port: {}", port);
-                                        log_state_for_task.log_app(
-                                            "INFO",
-                                            &format!("Detected backend port: {}", port),
-                                        );
-                                        if let Ok(mut p) = port_state_inner.lock() {
-                                            *p = port;
-                                        }
-                                        // 依然发送事件，以便正在运行的页面能立即感知
-                                        let _ =
-                                            app_handle.emit("backend-port", PortPayload { port });
-                                    }
-                                }
-                            }
-                        }
-                        CommandEvent::Stderr(line) => {
-                            let err = String::from_utf8_lossy(&line);
-                            eprintln!("Sidecar STDERR: {}", err);
-                            log_state_for_task.log_server("STDERR", err.trim_end());
-                        }
-                        CommandEvent::Error(err) => {
-                            eprintln!("Sidecar Error: {}", err);
-                            log_state_for_task.log_app("ERROR", &format!("Sidecar Error: {}", err));
-                        }
-                        CommandEvent::Terminated(status) => {
-                            println!("Sidecar Terminated with status: {:?}", status);
-                            log_state_for_task.log_app(
-                                "WARN",
-                                &format!("Sidecar Terminated with status: {:?}", status),
-                            );
-                            // 进程退出了，清空 handle
-                            if let Ok(mut c) = child_clone.lock() {
-                                *c = None;
-                            }
-                        }
-                        _ => {}
-                    }
+            match key_slots.migrate() {
+                Ok(0) => {}
+                Ok(count) => log_state.log_app(
+                    "INFO",
+                    &format!("moved {} API keys into the system keychain", count),
+                ),
+                Err(e) => log_state.log_app("WARN", &format!("migrate API keys failed: {}", e)),
+            }
+            app.manage(key_slots::KeySlotsState(key_slots));
+            app.manage(undo::UndoState(undo::UndoJournal::new(
+                data_base.join("undo-journal.json"),
+            )));
+            if !safe {
+                collections::spawn_watcher(
+                    app.handle().clone(),
+                    Library::new(data_base.join("data.db")),
+                );
+            }
+            app.manage(SettingsState(settings_store));
+            app.manage(StatsState(stats::StatsStore::load(
+                default_base.join("stats.json"),
+            )));
+            app.manage(session_state::SessionStateState(
+                session_state::SessionStateStore::new(default_base.join("session-state.json")),
+            ));
+            if !safe {
+                accessibility::spawn_prefs_watcher(app.handle().clone());
+            }
+            app.manage(protocol::ProtocolState::new(resize::ResizeCache::new(
+                data_base.join("cache").join("nbimage"),
+            )));
+
+            // 单独更新过后端时优先使用 AppData/sidecar 下校验通过的版本，校验失败回退到包内版本
+            let sidecar_store = sidecar_update::SidecarStore::new(default_base.join("sidecar"));
+            let updated_sidecar = match sidecar_store.active_binary() {
+                // 安全模式固定使用包内版本，后端更新本身可能就是起不来的原因
+                Ok(_) if safe => None,
+                Ok(found) => found,
+                Err(e) => {
+                    log_state.log_app("WARN", &format!("updated sidecar ignored: {}", e));
+                    None
+                }
+            }
+            .filter(|(path, _)| match sidecar::preflight_path(path) {
+                Ok(_) => true,
+                Err(e) => {
+                    log_state.log_app("WARN", &format!("updated sidecar ignored: {}", e));
+                    false
                 }
             });
+            app.manage(sidecar_update::SidecarUpdateState(sidecar_store));
+            app.manage(delta::AppUpdateCacheState(delta::AppUpdateCache::new(
+                default_base.join("updates"),
+            )));
+
+            let mut sidecar_env: Vec<(String, String)> = runtime
+                .sidecar_env()
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect();
+            let hardware = hardware::detect(safe);
+            log_state.log_app("INFO", &hardware::describe(&hardware));
+            sidecar_env.extend(hardware::sidecar_env(&hardware));
+            app.manage(hardware::HardwareState(hardware));
+            if runtime.transport.value == "socket" {
+                let socket = backend_bridge::socket_path(&default_base);
+                log_state.log_app(
+                    "INFO",
+                    &format!("backend transport: socket ({})", socket.display()),
+                );
+                sidecar_env.push((
+                    "NB_LISTEN_SOCKET".to_string(),
+                    socket.to_string_lossy().to_string(),
+                ));
+            }
+            // socket 模式不监听端口，固定端口只在 tcp 模式下生效
+            let fixed_port = runtime
+                .fixed_port()
+                .filter(|_| runtime.transport.value != "socket");
+            if let Some(port) = fixed_port {
+                log_state.log_app("INFO", &format!("backend port fixed at {}", port));
+            }
+            if let Some((path, version)) = &updated_sidecar {
+                log_state.log_app(
+                    "INFO",
+                    &format!("using updated sidecar {} ({})", version, path.display()),
+                );
+            }
+            let external_port = runtime.external_port();
+            app.manage(supervisor::SupervisorState(supervisor::Supervisor::new(
+                supervisor::LaunchSpec {
+                    program: updated_sidecar.as_ref().map(|(path, _)| path.clone()),
+                    clear_env: safe,
+                    env: sidecar_env,
+                    data_dir: data_base.clone(),
+                    fixed_port,
+                    writable: vec![default_base.clone(), data_base.clone()],
+                    settings: app.state::<SettingsState>().0.get().sidecar,
+                    external_port,
+                },
+            )));
+            let runtime_transport_socket = runtime.transport.value == "socket";
+            app.manage(ConfigState(config::EffectiveConfig::new(&policy, runtime)));
+
+            app.manage(SidecarState(Arc::new(Mutex::new(None))));
+            app.manage(sidecar_manager::SidecarManagerState(
+                sidecar_manager::SidecarManager::new(data_base.clone(), safe),
+            ));
+            app.manage(backend_control::BackendControlState(
+                backend_control::BackendControl::default(),
+            ));
+            app.manage(health::HealthState(health::HealthMonitor::default()));
+            app.manage(handshake::HandshakeState::default());
+
+            // 外部后端模式：不启动 sidecar，直接采用配置的端口；后端稍后才起来也没关系，健康检查会跟上
+            if let Some(port) = external_port {
+                log_state.log_app(
+                    "INFO",
+                    &format!(
+                        "using external backend at 127.0.0.1:{}, sidecar disabled",
+                        port
+                    ),
+                );
+                if runtime_transport_socket {
+                    log_state.log_app(
+                        "WARN",
+                        "transport=socket is ignored with an external backend",
+                    );
+                }
+                let reachable = std::net::TcpStream::connect_timeout(
+                    &(std::net::Ipv4Addr::LOCALHOST, port).into(),
+                    std::time::Duration::from_millis(500),
+                )
+                .is_ok();
+                if !reachable {
+                    log_state.log_app(
+                        "WARN",
+                        &format!(
+                            "external backend is not listening on 127.0.0.1:{} yet",
+                            port
+                        ),
+                    );
+                }
+                if let Ok(mut current) = app.state::<BackendPort>().0.lock() {
+                    *current = port;
+                }
+                app.state::<port_events::PortAnnouncer>()
+                    .announce(app.handle(), port);
+                health::start(app.handle().clone());
+                return Ok(());
+            }
+
+            // 自检、清理残留进程与拉起 sidecar 放到后台线程，setup 先返回让窗口立即绘制，
+            // 前端收到 backend-starting 后显示「正在连接引擎」，端口就绪后照常收到 backend-port
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn_blocking(move || {
+                start_backend(&handle, updated_sidecar, safe);
+            });
 
             Ok(())
         })
-        .invoke_handler(tauri::generate_handler![
+        .invoke_handler(ipc_guard::wrap(rate_limit::wrap(tauri::generate_handler![
             greet,
             get_backend_port,
+            get_backend_port_info,
+            wait_for_backend_ready,
+            restart_backend,
+            get_backend_status,
+            get_sidecar_stats,
+            get_backend_info,
+            send_backend_command,
+            set_backend_log_level,
+            list_sidecars,
+            get_feature_flags,
+            get_hardware_info,
+            activate_license,
+            get_license_status,
+            has_license_feature,
+            deactivate_license,
+            restart_sidecar,
+            list_key_slots,
+            add_key_slot,
+            remove_key_slot,
+            set_active_key,
+            get_backend_base_url,
             get_app_data_dir,
+            get_runtime_config,
+            get_effective_config,
+            announce,
+            get_accessibility_prefs,
+            start_stylus_input,
+            stop_stylus_input,
+            list_system_fonts,
+            check_sidecar_update,
+            install_sidecar_update,
+            rollback_sidecar_update,
+            apply_sidecar_update,
+            install_app_update,
             get_log_dir,
+            submit_diagnostics,
+            send_feedback,
             open_log_dir,
             write_frontend_logs,
+            get_settings,
+            update_settings,
+            list_settings_versions,
+            revert_settings,
+            export_settings,
+            import_settings,
             copy_image_to_clipboard,
             copy_text_to_clipboard,
+            copy_metadata_to_clipboard,
+            get_clipboard_history,
+            clear_clipboard_history,
             read_image_from_clipboard,
+            prepare_reference,
+            capture_window_of_app,
+            upload_reference,
+            save_generated_image,
+            persist_data_uri,
             persist_ref_image,
-            set_generation_active
-        ])
+            strip_location,
+            read_embedded_prompt,
+            translate_text,
+            embed_invisible_watermark,
+            detect_invisible_watermark,
+            blur_regions,
+            apply_lut,
+            apply_filter,
+            draw_text,
+            rename_images,
+            reorganize_existing,
+            create_smart_collection,
+            list_smart_collections,
+            delete_smart_collection,
+            evaluate_smart_collection,
+            get_image_meta,
+            update_metadata_bulk,
+            undo_last_native_action,
+            list_undoable_actions,
+            list_images,
+            get_library_stats,
+            import_bundle,
+            publish_now,
+            get_overlay_status,
+            export_nbp,
+            export_annotated_pdf,
+            share_over_lan,
+            stop_lan_share,
+            start_handoff,
+            stop_handoff,
+            get_handoff_status,
+            reject_handoff_pairing,
+            list_handoff_devices,
+            revoke_handoff_device,
+            export_with_credentials,
+            export_with_preset,
+            cancel_native_job,
+            open_nbp,
+            take_pending_nbp_files,
+            set_generation_active,
+            get_session_stats,
+            save_session_state,
+            restore_session_state,
+            clear_session_state,
+            get_safe_mode,
+            exit_safe_mode
+        ])))
         .build(tauri::generate_context!())
         .expect("error while running tauri application")
         .run(|app_handle, event| match event {
-            tauri::RunEvent::WindowEvent { label, event, .. } => {
-                #[cfg(target_os = "macos")]
-                {
-                    if label == "main" {
-                        if let tauri::WindowEvent::CloseRequested { api, .. } = event {
-                            let allow_close = app_handle
-                                .state::<QuitGuardState>()
-                                .0
-                                .lock()
-                                .map(|s| s.confirmed_exit)
-                                .unwrap_or(false);
-
-                            if !allow_close {
-                                api.prevent_close();
-                                if let Some(window) = app_handle.get_webview_window("main") {
-                                    let _ = window.hide();
-                                }
-                            }
-                        }
+            tauri::RunEvent::WindowEvent {
+                label,
+                event: tauri::WindowEvent::Focused(focused),
+                ..
+            } if label == "main" => priority::on_focus(app_handle, focused),
+            #[cfg(target_os = "macos")]
+            tauri::RunEvent::WindowEvent {
+                label,
+                event: tauri::WindowEvent::CloseRequested { api, .. },
+                ..
+            } if label == "main" => {
+                let allow_close = app_handle
+                    .state::<QuitGuardState>()
+                    .0
+                    .lock()
+                    .map(|s| s.confirmed_exit)
+                    .unwrap_or(false);
+
+                if !allow_close {
+                    api.prevent_close();
+                    if let Some(window) = app_handle.get_webview_window("main") {
+                        let _ = window.hide();
                     }
                 }
             }
-            tauri::RunEvent::ExitRequested { code, api, .. } => {
-                #[cfg(target_os = "macos")]
-                {
-                    if code.is_none() {
-                        let quit_guard_state = app_handle.state::<QuitGuardState>();
-                        let mut guard = quit_guard_state.0.lock().unwrap();
-                        if guard.confirmed_exit {
-                            return;
-                        }
-                        if guard.confirming {
-                            api.prevent_exit();
-                            return;
-                        }
-
-                        let is_generating = app_handle
-                            .state::<GenerationState>()
-                            .0
-                            .lock()
-                            .map(|s| *s)
-                            .unwrap_or(false);
-
-                        if is_generating {
-                            api.prevent_exit();
-                            guard.confirming = true;
-                            let app_handle = app_handle.clone();
-
-                            app_handle
-                                .dialog()
-                                .message("当前有图片仍在生成，确定要退出吗？未完成任务会被中断。")
-                                .title("确认退出")
-                                .kind(MessageDialogKind::Warning)
-                                .buttons(MessageDialogButtons::OkCancelCustom(
-                                    "退出".to_string(),
-                                    "取消".to_string(),
-                                ))
-                                .show(move |should_exit| {
-                                    if let Ok(mut state) =
-                                        app_handle.state::<QuitGuardState>().0.lock()
-                                    {
-                                        state.confirming = false;
-                                        if should_exit {
-                                            state.confirmed_exit = true;
-                                        }
-                                    }
-
-                                    if should_exit {
-                                        app_handle.exit(0);
-                                    }
-                                });
-                            return;
-                        }
-
-                        guard.confirmed_exit = true;
-                    }
+            #[cfg(target_os = "macos")]
+            tauri::RunEvent::ExitRequested {
+                code: None, api, ..
+            } => {
+                let quit_guard_state = app_handle.state::<QuitGuardState>();
+                let mut guard = quit_guard_state.0.lock().unwrap();
+                if guard.confirmed_exit {
+                    return;
+                }
+                if guard.confirming {
+                    api.prevent_exit();
+                    return;
                 }
+
+                let is_generating = app_handle
+                    .state::<GenerationState>()
+                    .0
+                    .lock()
+                    .map(|s| *s)
+                    .unwrap_or(false);
+
+                if is_generating {
+                    api.prevent_exit();
+                    guard.confirming = true;
+                    let app_handle = app_handle.clone();
+
+                    app_handle
+                        .dialog()
+                        .message("当前有图片仍在生成，确定要退出吗？未完成任务会被中断。")
+                        .title("确认退出")
+                        .kind(MessageDialogKind::Warning)
+                        .buttons(MessageDialogButtons::OkCancelCustom(
+                            "退出".to_string(),
+                            "取消".to_string(),
+                        ))
+                        .show(move |should_exit| {
+                            if let Ok(mut state) = app_handle.state::<QuitGuardState>().0.lock() {
+                                state.confirming = false;
+                                if should_exit {
+                                    state.confirmed_exit = true;
+                                }
+                            }
+
+                            if should_exit {
+                                app_handle.exit(0);
+                            }
+                        });
+                    return;
+                }
+
+                guard.confirmed_exit = true;
             }
             #[cfg(target_os = "macos")]
             tauri::RunEvent::Reopen {
-                has_visible_windows,
+                has_visible_windows: false,
                 ..
             } => {
-                if !has_visible_windows {
-                    if let Some(window) = app_handle.get_webview_window("main") {
-                        let _ = window.show();
-                        let _ = window.set_focus();
-                    }
+                if let Some(window) = app_handle.get_webview_window("main") {
+                    let _ = window.show();
+                    let _ = window.set_focus();
+                }
+            }
+            // macOS 通过系统事件传入双击打开的文件
+            #[cfg(target_os = "macos")]
+            tauri::RunEvent::Opened { urls } => {
+                let files: Vec<String> = urls
+                    .iter()
+                    .filter_map(|url| url.to_file_path().ok())
+                    .filter(|path| is_nbp_path(path))
+                    .map(|path| path.to_string_lossy().to_string())
+                    .collect();
+                if files.is_empty() {
+                    return;
+                }
+                if let Ok(mut pending) = app_handle.state::<PendingOpenState>().0.lock() {
+                    pending.extend(files.iter().cloned());
                 }
+                let _ = app_handle.emit("nbp-open-requested", files);
             }
             tauri::RunEvent::Exit => {
-                kill_sidecar(app_handle);
+                app_handle.state::<StatsState>().0.mark_exit();
+                // 发出 mDNS 下线通告，其他设备不必等缓存过期
+                let _ = app_handle.state::<handoff::HandoffState>().stop();
+                // 优雅关闭后端，最多等 SHUTDOWN_TIMEOUT
+                supervisor::shutdown(app_handle, supervisor::SHUTDOWN_TIMEOUT);
+                sidecar_manager::shutdown(app_handle, supervisor::SHUTDOWN_TIMEOUT);
             }
             _ => {}
         });
@@ -0,0 +1,442 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+
+// 后端（Go 边车）的历史库：tasks 表由后端 gorm 迁移维护，这里按同一份 SQLite 文件直接读写，
+// 供需要批量/事务处理的原生命令使用，避免经过 HTTP 一条条往返
+pub struct Library {
+    path: PathBuf,
+}
+
+pub struct LibraryState(pub Library);
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct TaskRecord {
+    pub task_id: String,
+    pub prompt: String,
+    pub provider_name: String,
+    pub model_id: String,
+    pub status: String,
+    pub image_url: String,
+    pub local_path: String,
+    pub thumbnail_path: String,
+    pub width: i64,
+    pub height: i64,
+    pub config_snapshot: String,
+    pub created_at: String,
+}
+
+// 原生层维护的扩展元数据（标题/项目/标签/收藏），后端 tasks 表没有这些字段，
+// 单独放在同库的 image_meta 表中，按 task_id 关联。
+// revision 每次写入加一，多窗口同时编辑时据此发现冲突；从未写过的记录为 0
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ImageMeta {
+    pub title: String,
+    pub project: String,
+    pub tags: Vec<String>,
+    pub favorite: bool,
+    pub revision: u64,
+}
+
+// 后端 provider_configs 表中可迁移的字段；api_key 属于密钥，只有显式要求时才导出
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct ProviderConfigRecord {
+    pub provider_name: String,
+    #[serde(default)]
+    pub display_name: String,
+    #[serde(default)]
+    pub api_base: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+    #[serde(default)]
+    pub models: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub timeout_seconds: i64,
+    #[serde(default)]
+    pub max_retries: i64,
+    #[serde(default)]
+    pub extra_config: String,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+pub struct NewTask<'a> {
+    pub task_id: &'a str,
+    pub prompt: &'a str,
+    pub provider_name: &'a str,
+    pub model_id: &'a str,
+    pub local_path: &'a str,
+    pub width: i64,
+    pub height: i64,
+    pub config_snapshot: &'a str,
+}
+
+pub const TASK_COLUMNS: &str = "task_id, COALESCE(prompt, ''), COALESCE(provider_name, ''), \
+     COALESCE(model_id, ''), COALESCE(status, ''), COALESCE(image_url, ''), \
+     COALESCE(local_path, ''), COALESCE(thumbnail_path, ''), COALESCE(width, 0), \
+     COALESCE(height, 0), COALESCE(config_snapshot, ''), COALESCE(created_at, '')";
+
+impl Library {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn open(&self) -> Result<Connection, String> {
+        if !self.path.exists() {
+            return Err(format!("history db not found: {}", self.path.display()));
+        }
+        let conn = Connection::open(&self.path)
+            .map_err(|e| format!("open history db failed: {} ({})", e, self.path.display()))?;
+        // 与后端的 _busy_timeout=5000 保持一致，后端写入时等待而不是直接报 locked
+        conn.busy_timeout(Duration::from_secs(5))
+            .map_err(|e| format!("set busy timeout failed: {}", e))?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS image_meta (\
+                 task_id TEXT PRIMARY KEY NOT NULL, \
+                 title TEXT NOT NULL DEFAULT '', \
+                 project TEXT NOT NULL DEFAULT '', \
+                 tags TEXT NOT NULL DEFAULT '[]', \
+                 favorite INTEGER NOT NULL DEFAULT 0, \
+                 updated_at TEXT NOT NULL DEFAULT '', \
+                 revision INTEGER NOT NULL DEFAULT 0)",
+        )
+        .map_err(|e| format!("init image_meta failed: {}", e))?;
+        // 旧版本建的表没有 revision 列
+        let has_revision: bool = conn
+            .query_row(
+                "SELECT COUNT(*) > 0 FROM pragma_table_info('image_meta') WHERE name = 'revision'",
+                [],
+                |row| row.get(0),
+            )
+            .map_err(|e| format!("inspect image_meta failed: {}", e))?;
+        if !has_revision {
+            conn.execute_batch(
+                "ALTER TABLE image_meta ADD COLUMN revision INTEGER NOT NULL DEFAULT 0",
+            )
+            .map_err(|e| format!("migrate image_meta failed: {}", e))?;
+        }
+        Ok(conn)
+    }
+
+    pub fn get_meta(&self, conn: &Connection, task_id: &str) -> Result<ImageMeta, String> {
+        conn.query_row(
+            "SELECT title, project, tags, favorite, revision FROM image_meta WHERE task_id = ?1",
+            params![task_id],
+            meta_from_row,
+        )
+        .optional()
+        .map(|m| m.unwrap_or_default())
+        .map_err(|e| format!("query image meta failed: {}", e))
+    }
+
+    pub fn all_meta(&self, conn: &Connection) -> Result<HashMap<String, ImageMeta>, String> {
+        let mut stmt = conn
+            .prepare("SELECT title, project, tags, favorite, revision, task_id FROM image_meta")
+            .map_err(|e| format!("query image meta failed: {}", e))?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(5)?, meta_from_row(row)?))
+            })
+            .map_err(|e| format!("query image meta failed: {}", e))?;
+        rows.collect::<Result<HashMap<_, _>, _>>()
+            .map_err(|e| format!("query image meta failed: {}", e))
+    }
+
+    // 写入后 revision 加一，传入的 meta.revision 不参与写入
+    pub fn put_meta(
+        &self,
+        conn: &Connection,
+        task_id: &str,
+        meta: &ImageMeta,
+    ) -> Result<(), String> {
+        let tags = serde_json::to_string(&meta.tags)
+            .map_err(|e| format!("serialize tags failed: {}", e))?;
+        conn.execute(
+            "INSERT INTO image_meta (task_id, title, project, tags, favorite, updated_at, revision) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, 1) \
+             ON CONFLICT(task_id) DO UPDATE SET title = excluded.title, \
+             project = excluded.project, tags = excluded.tags, favorite = excluded.favorite, \
+             updated_at = excluded.updated_at, revision = image_meta.revision + 1",
+            params![
+                task_id,
+                meta.title,
+                meta.project,
+                tags,
+                meta.favorite,
+                db_timestamp()
+            ],
+        )
+        .map(|_| ())
+        .map_err(|e| format!("save image meta failed: {}", e))
+    }
+
+    pub fn get_task(&self, conn: &Connection, task_id: &str) -> Result<Option<TaskRecord>, String> {
+        conn.query_row(
+            &format!(
+                "SELECT {} FROM tasks WHERE task_id = ?1 AND deleted_at IS NULL",
+                TASK_COLUMNS
+            ),
+            params![task_id],
+            task_from_row,
+        )
+        .optional()
+        .map_err(|e| format!("query task failed: {}", e))
+    }
+
+    pub fn task_exists(&self, conn: &Connection, task_id: &str) -> Result<bool, String> {
+        conn.query_row(
+            "SELECT 1 FROM tasks WHERE task_id = ?1",
+            params![task_id],
+            |_| Ok(()),
+        )
+        .optional()
+        .map(|v| v.is_some())
+        .map_err(|e| format!("query task failed: {}", e))
+    }
+
+    // 已完成且有本地文件的记录，用于按内容去重等场景
+    pub fn completed_tasks(&self, conn: &Connection) -> Result<Vec<TaskRecord>, String> {
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {} FROM tasks WHERE deleted_at IS NULL AND status = 'completed' \
+                 AND local_path IS NOT NULL AND local_path != ''",
+                TASK_COLUMNS
+            ))
+            .map_err(|e| format!("query tasks failed: {}", e))?;
+        let rows = stmt
+            .query_map([], task_from_row)
+            .map_err(|e| format!("query tasks failed: {}", e))?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("query tasks failed: {}", e))
+    }
+
+    // 最近完成的若干条记录，按创建时间倒序
+    pub fn recent_tasks(&self, conn: &Connection, limit: usize) -> Result<Vec<TaskRecord>, String> {
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {} FROM tasks WHERE deleted_at IS NULL AND status = 'completed' \
+                 AND local_path IS NOT NULL AND local_path != '' \
+                 ORDER BY created_at DESC LIMIT ?1",
+                TASK_COLUMNS
+            ))
+            .map_err(|e| format!("query tasks failed: {}", e))?;
+        let rows = stmt
+            .query_map(params![limit as i64], task_from_row)
+            .map_err(|e| format!("query tasks failed: {}", e))?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("query tasks failed: {}", e))
+    }
+
+    pub fn get_tasks(&self, conn: &Connection, ids: &[String]) -> Result<Vec<TaskRecord>, String> {
+        let mut tasks = Vec::with_capacity(ids.len());
+        for id in ids {
+            let task = self
+                .get_task(conn, id)?
+                .ok_or_else(|| format!("task not found: {}", id))?;
+            tasks.push(task);
+        }
+        Ok(tasks)
+    }
+
+    pub fn update_paths(
+        &self,
+        conn: &Connection,
+        task_id: &str,
+        local_path: &str,
+        thumbnail_path: &str,
+    ) -> Result<(), String> {
+        conn.execute(
+            "UPDATE tasks SET local_path = ?2, thumbnail_path = ?3 WHERE task_id = ?1",
+            params![task_id, local_path, thumbnail_path],
+        )
+        .map(|_| ())
+        .map_err(|e| format!("update task failed: {}", e))
+    }
+
+    // 把以 from 开头的图片路径改成以 to 开头（数据迁移后绝对路径指向了旧位置），返回改动的行数
+    pub fn rebase_paths(&self, conn: &Connection, from: &str, to: &str) -> Result<usize, String> {
+        let mut changed = 0;
+        for column in ["local_path", "thumbnail_path"] {
+            changed += conn
+                .execute(
+                    &format!(
+                        "UPDATE tasks SET {column} = ?2 || substr({column}, length(?1) + 1) \
+                         WHERE substr({column}, 1, length(?1)) = ?1"
+                    ),
+                    params![from, to],
+                )
+                .map_err(|e| format!("rebase task paths failed: {}", e))?;
+        }
+        Ok(changed)
+    }
+
+    pub fn provider_configs(
+        &self,
+        conn: &Connection,
+        include_secrets: bool,
+    ) -> Result<Vec<ProviderConfigRecord>, String> {
+        let mut stmt = conn
+            .prepare(
+                "SELECT provider_name, COALESCE(display_name, ''), COALESCE(api_base, ''), \
+                 COALESCE(api_key, ''), COALESCE(models, ''), COALESCE(enabled, 1), \
+                 COALESCE(timeout_seconds, 0), COALESCE(max_retries, 0), \
+                 COALESCE(extra_config, '') FROM provider_configs \
+                 WHERE deleted_at IS NULL ORDER BY provider_name",
+            )
+            .map_err(|e| format!("query provider configs failed: {}", e))?;
+        let rows = stmt
+            .query_map([], |row| {
+                let api_key: String = row.get(3)?;
+                Ok(ProviderConfigRecord {
+                    provider_name: row.get(0)?,
+                    display_name: row.get(1)?,
+                    api_base: row.get(2)?,
+                    api_key: include_secrets.then_some(api_key),
+                    models: row.get(4)?,
+                    enabled: row.get(5)?,
+                    timeout_seconds: row.get(6)?,
+                    max_retries: row.get(7)?,
+                    extra_config: row.get(8)?,
+                })
+            })
+            .map_err(|e| format!("query provider configs failed: {}", e))?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("query provider configs failed: {}", e))
+    }
+
+    // 按 provider_name 更新或新增；api_key 为 None 时保留本机已有的密钥
+    pub fn upsert_provider_config(
+        &self,
+        conn: &Connection,
+        config: &ProviderConfigRecord,
+    ) -> Result<(), String> {
+        let now = db_timestamp();
+        let updated = conn
+            .execute(
+                "UPDATE provider_configs SET display_name = ?2, api_base = ?3, \
+                 api_key = COALESCE(?4, api_key), models = ?5, enabled = ?6, \
+                 timeout_seconds = ?7, max_retries = ?8, extra_config = ?9, updated_at = ?10, \
+                 deleted_at = NULL WHERE provider_name = ?1",
+                params![
+                    config.provider_name,
+                    config.display_name,
+                    config.api_base,
+                    config.api_key,
+                    config.models,
+                    config.enabled,
+                    config.timeout_seconds,
+                    config.max_retries,
+                    config.extra_config,
+                    now,
+                ],
+            )
+            .map_err(|e| format!("update provider config failed: {}", e))?;
+        if updated > 0 {
+            return Ok(());
+        }
+        conn.execute(
+            "INSERT INTO provider_configs (provider_name, display_name, api_base, api_key, \
+             models, enabled, timeout_seconds, max_retries, extra_config, created_at, updated_at) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?10)",
+            params![
+                config.provider_name,
+                config.display_name,
+                config.api_base,
+                config.api_key.clone().unwrap_or_default(),
+                config.models,
+                config.enabled,
+                config.timeout_seconds,
+                config.max_retries,
+                config.extra_config,
+                now,
+            ],
+        )
+        .map(|_| ())
+        .map_err(|e| format!("insert provider config failed: {}", e))
+    }
+
+    // 只替换密钥，其余配置不动；provider 尚未配置时报错
+    pub fn set_provider_api_key(
+        &self,
+        conn: &Connection,
+        provider_name: &str,
+        api_key: &str,
+    ) -> Result<(), String> {
+        let updated = conn
+            .execute(
+                "UPDATE provider_configs SET api_key = ?2, updated_at = ?3 \
+                 WHERE provider_name = ?1 AND deleted_at IS NULL",
+                params![provider_name, api_key, db_timestamp()],
+            )
+            .map_err(|e| format!("update provider api key failed: {}", e))?;
+        if updated == 0 {
+            return Err(format!("provider {} is not configured", provider_name));
+        }
+        Ok(())
+    }
+
+    pub fn insert_task(&self, conn: &Connection, task: &NewTask<'_>) -> Result<(), String> {
+        let now = db_timestamp();
+        conn.execute(
+            "INSERT INTO tasks (task_id, prompt, provider_name, model_id, status, error_message, \
+             image_url, local_path, thumbnail_url, thumbnail_path, width, height, total_count, \
+             config_snapshot, created_at, completed_at) \
+             VALUES (?1, ?2, ?3, ?4, 'completed', '', '', ?5, '', '', ?6, ?7, 1, ?8, ?9, ?9)",
+            params![
+                task.task_id,
+                task.prompt,
+                task.provider_name,
+                task.model_id,
+                task.local_path,
+                task.width,
+                task.height,
+                task.config_snapshot,
+                now,
+            ],
+        )
+        .map(|_| ())
+        .map_err(|e| format!("insert task failed: {}", e))
+    }
+}
+
+pub fn task_from_row(row: &Row<'_>) -> rusqlite::Result<TaskRecord> {
+    Ok(TaskRecord {
+        task_id: row.get(0)?,
+        prompt: row.get(1)?,
+        provider_name: row.get(2)?,
+        model_id: row.get(3)?,
+        status: row.get(4)?,
+        image_url: row.get(5)?,
+        local_path: row.get(6)?,
+        thumbnail_path: row.get(7)?,
+        width: row.get(8)?,
+        height: row.get(9)?,
+        config_snapshot: row.get(10)?,
+        created_at: row.get(11)?,
+    })
+}
+
+fn meta_from_row(row: &Row<'_>) -> rusqlite::Result<ImageMeta> {
+    let tags: String = row.get(2)?;
+    Ok(ImageMeta {
+        title: row.get(0)?,
+        project: row.get(1)?,
+        tags: serde_json::from_str(&tags).unwrap_or_default(),
+        favorite: row.get(3)?,
+        revision: row.get::<_, i64>(4)?.max(0) as u64,
+    })
+}
+
+// 与 go-sqlite3 写入 time.Time 的格式一致，后端读回时才能正确解析
+pub fn db_timestamp() -> String {
+    chrono::Local::now()
+        .format("%Y-%m-%d %H:%M:%S%.9f%:z")
+        .to_string()
+}
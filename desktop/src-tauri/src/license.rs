@@ -0,0 +1,487 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use base64::Engine;
+use sha2::{Digest, Sha256};
+use tauri::Manager;
+
+// 团队授权：许可证由发行方用 minisign 私钥签名，公钥（NB_LICENSE_PUBKEY）与激活服务地址（NB_LICENSE_SERVER）
+// 在构建时写入，校验全部在 Rust 里完成，前端只拿结果。配置了激活服务时，许可证还需按本机指纹在线激活，
+// 服务端返回同一把密钥签名的激活凭据；凭据离线也可用 OFFLINE_GRACE_DAYS 天，过期后需联网重新校验。
+// 记录存在 AppData/license.json，篡改或拷到别的机器上都过不了签名与指纹校验，因此无需另行加密
+const PUBKEY: Option<&str> = option_env!("NB_LICENSE_PUBKEY");
+const SERVER: Option<&str> = option_env!("NB_LICENSE_SERVER");
+const KEY_PREFIX: &str = "NBP1.";
+const MAX_KEY_LEN: usize = 8 * 1024;
+const MAX_RESPONSE_BYTES: usize = 64 * 1024;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+// 上次在线校验超过这个天数就在启动时后台再校验一次
+const REFRESH_AFTER_DAYS: i64 = 1;
+const OFFLINE_GRACE_DAYS: i64 = 14;
+// 允许的时钟回拨，超过视为改了系统时间
+const CLOCK_SKEW_HOURS: i64 = 24;
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+struct LicensePayload {
+    id: String,
+    licensee: String,
+    #[serde(default)]
+    plan: String,
+    #[serde(default)]
+    seats: Option<u32>,
+    #[serde(default)]
+    features: Vec<String>,
+    // RFC 3339，不写表示永久
+    #[serde(default)]
+    expires_at: Option<String>,
+}
+
+// 激活服务签发的凭据；token 为 base64 的 ActivationClaims，signature 为 base64 的 minisign 签名
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+struct SignedActivation {
+    token: String,
+    signature: String,
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+struct ActivationClaims {
+    license_id: String,
+    fingerprint: String,
+    issued_at: String,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+struct StoredLicense {
+    key: String,
+    #[serde(default)]
+    activation: Option<SignedActivation>,
+    activated_at: String,
+    // 见过的最晚时间，用来发现时钟回拨
+    #[serde(default)]
+    last_seen_at: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize)]
+pub struct LicenseStatus {
+    // none / active / grace / expired / revalidate / invalid
+    pub state: String,
+    pub licensee: Option<String>,
+    pub plan: Option<String>,
+    pub seats: Option<u32>,
+    pub features: Vec<String>,
+    pub expires_at: Option<String>,
+    pub fingerprint: String,
+    // 上次在线校验的时间，未配置激活服务时为空
+    pub checked_at: Option<String>,
+    // 离线宽限期截止时间
+    pub grace_until: Option<String>,
+    pub error: Option<String>,
+}
+
+impl LicenseStatus {
+    fn unlocked(&self) -> bool {
+        matches!(self.state.as_str(), "active" | "grace")
+    }
+}
+
+pub struct Licensing {
+    path: PathBuf,
+    lock: Mutex<()>,
+    // 首次用到时计算，macOS 上要跑一次 ioreg，不放在启动路径上
+    fingerprint: OnceLock<String>,
+}
+
+pub struct LicenseState(pub Licensing);
+
+fn pubkey() -> Result<&'static str, String> {
+    PUBKEY
+        .map(str::trim)
+        .filter(|k| !k.is_empty())
+        .ok_or_else(|| "licensing is not available in this build".to_string())
+}
+
+fn server() -> Option<&'static str> {
+    SERVER.map(str::trim).filter(|s| !s.is_empty())
+}
+
+fn decode_b64(value: &str) -> Result<Vec<u8>, String> {
+    let value = value.trim();
+    base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(value.trim_end_matches('='))
+        .or_else(|_| base64::engine::general_purpose::STANDARD.decode(value))
+        .map_err(|e| format!("decode license failed: {}", e))
+}
+
+fn parse_time(value: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    chrono::DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|t| t.with_timezone(&chrono::Utc))
+}
+
+// 许可证格式：NBP1.<base64url(payload JSON)>.<base64(minisign 签名)>，签名覆盖 payload 原始字节
+fn verify_key(key: &str) -> Result<LicensePayload, String> {
+    let key = key.trim();
+    if key.len() > MAX_KEY_LEN {
+        return Err("license key is too long".to_string());
+    }
+    let rest = key
+        .strip_prefix(KEY_PREFIX)
+        .ok_or_else(|| "license key is not recognized".to_string())?;
+    let (payload, signature) = rest
+        .split_once('.')
+        .ok_or_else(|| "license key is malformed".to_string())?;
+    let payload = decode_b64(payload)?;
+    // verify_signature 只认标准 base64
+    let signature = base64::engine::general_purpose::STANDARD.encode(decode_b64(signature)?);
+    crate::sidecar_update::verify_signature(&payload, &signature, pubkey()?)
+        .map_err(|_| "license key signature is invalid".to_string())?;
+    serde_json::from_slice(&payload).map_err(|e| format!("parse license failed: {}", e))
+}
+
+fn verify_activation(
+    activation: &SignedActivation,
+    license_id: &str,
+    fingerprint: &str,
+) -> Result<chrono::DateTime<chrono::Utc>, String> {
+    let token = decode_b64(&activation.token)?;
+    crate::sidecar_update::verify_signature(&token, &activation.signature, pubkey()?)
+        .map_err(|_| "activation signature is invalid".to_string())?;
+    let claims: ActivationClaims =
+        serde_json::from_slice(&token).map_err(|e| format!("parse activation failed: {}", e))?;
+    if claims.license_id != license_id {
+        return Err("activation belongs to another license".to_string());
+    }
+    if claims.fingerprint != fingerprint {
+        return Err("activation belongs to another machine".to_string());
+    }
+    parse_time(&claims.issued_at).ok_or_else(|| "activation time is invalid".to_string())
+}
+
+#[cfg(target_os = "macos")]
+fn machine_id() -> Option<String> {
+    let output = std::process::Command::new("ioreg")
+        .args(["-rd1", "-c", "IOPlatformExpertDevice"])
+        .output()
+        .ok()?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find(|line| line.contains("\"IOPlatformUUID\""))
+        .and_then(|line| line.split('"').nth(3))
+        .map(str::to_string)
+}
+
+#[cfg(windows)]
+fn machine_id() -> Option<String> {
+    use windows::core::PCWSTR;
+    use windows::Win32::System::Registry::{RegGetValueW, HKEY_LOCAL_MACHINE, RRF_RT_REG_SZ};
+
+    let wide = |value: &str| -> Vec<u16> { value.encode_utf16().chain(Some(0)).collect() };
+    let subkey = wide("SOFTWARE\\Microsoft\\Cryptography");
+    let value = wide("MachineGuid");
+    let mut buffer = [0u16; 128];
+    let mut size = std::mem::size_of_val(&buffer) as u32;
+    let status = unsafe {
+        RegGetValueW(
+            HKEY_LOCAL_MACHINE,
+            PCWSTR(subkey.as_ptr()),
+            PCWSTR(value.as_ptr()),
+            RRF_RT_REG_SZ,
+            None,
+            Some(buffer.as_mut_ptr() as *mut _),
+            Some(&mut size),
+        )
+    };
+    if status.is_err() {
+        return None;
+    }
+    let len = buffer.iter().position(|c| *c == 0).unwrap_or(buffer.len());
+    Some(String::from_utf16_lossy(&buffer[..len]))
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn machine_id() -> Option<String> {
+    ["/etc/machine-id", "/var/lib/dbus/machine-id"]
+        .iter()
+        .find_map(|path| fs::read_to_string(path).ok())
+        .map(|id| id.trim().to_string())
+}
+
+// 本机指纹：系统的机器标识加盐后取哈希，不直接外传原值；拿不到时退回主机名
+fn fingerprint() -> String {
+    let id = machine_id()
+        .filter(|id| !id.trim().is_empty())
+        .or_else(|| std::env::var("COMPUTERNAME").ok())
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .unwrap_or_default();
+    let digest = Sha256::digest(format!("nbp-license:{}", id.trim()).as_bytes());
+    hex::encode(&digest[..16])
+}
+
+impl Licensing {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            lock: Mutex::new(()),
+            fingerprint: OnceLock::new(),
+        }
+    }
+
+    fn fingerprint(&self) -> &str {
+        self.fingerprint.get_or_init(fingerprint)
+    }
+
+    fn read(&self) -> Option<StoredLicense> {
+        fs::read(&self.path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+    }
+
+    fn write(&self, stored: &StoredLicense) -> Result<(), String> {
+        let bytes = serde_json::to_vec_pretty(stored)
+            .map_err(|e| format!("serialize license failed: {}", e))?;
+        crate::storage::write_atomic(&self.path, &bytes)
+    }
+
+    fn evaluate(&self, stored: Option<&StoredLicense>) -> LicenseStatus {
+        let mut status = LicenseStatus {
+            state: "none".to_string(),
+            fingerprint: self.fingerprint().to_string(),
+            ..Default::default()
+        };
+        let Some(stored) = stored else {
+            return status;
+        };
+        let payload = match verify_key(&stored.key) {
+            Ok(payload) => payload,
+            Err(e) => {
+                status.state = "invalid".to_string();
+                status.error = Some(e);
+                return status;
+            }
+        };
+        status.licensee = Some(payload.licensee.clone());
+        status.plan = Some(payload.plan.clone()).filter(|p| !p.is_empty());
+        status.seats = payload.seats;
+        status.features = payload.features.clone();
+        status.expires_at = payload.expires_at.clone();
+        let now = chrono::Utc::now();
+        let last_seen = stored.last_seen_at.as_deref().and_then(parse_time);
+        if last_seen.is_some_and(|seen| now + chrono::Duration::hours(CLOCK_SKEW_HOURS) < seen) {
+            status.state = "invalid".to_string();
+            status.error = Some("system clock is behind the last license check".to_string());
+            return status;
+        }
+        // 有效期按见过的最晚时间算，调回系统时间不能续期
+        let now = last_seen.map_or(now, |seen| seen.max(now));
+        if let Some(expires_at) = payload.expires_at.as_deref() {
+            match parse_time(expires_at) {
+                Some(expires) if expires > now => {}
+                Some(_) => {
+                    status.state = "expired".to_string();
+                    return status;
+                }
+                None => {
+                    status.state = "invalid".to_string();
+                    status.error = Some("license expiry is invalid".to_string());
+                    return status;
+                }
+            }
+        }
+        if server().is_none() {
+            status.state = "active".to_string();
+            return status;
+        }
+        let checked = match stored
+            .activation
+            .as_ref()
+            .ok_or_else(|| "license is not activated on this machine".to_string())
+            .and_then(|a| verify_activation(a, &payload.id, self.fingerprint()))
+        {
+            Ok(checked) => checked,
+            Err(e) => {
+                status.state = "revalidate".to_string();
+                status.error = Some(e);
+                return status;
+            }
+        };
+        let grace_until = checked + chrono::Duration::days(OFFLINE_GRACE_DAYS);
+        status.checked_at = Some(checked.to_rfc3339());
+        status.grace_until = Some(grace_until.to_rfc3339());
+        status.state = if now - checked < chrono::Duration::days(REFRESH_AFTER_DAYS) {
+            "active"
+        } else if now < grace_until {
+            "grace"
+        } else {
+            "revalidate"
+        }
+        .to_string();
+        status
+    }
+
+    pub fn status(&self) -> LicenseStatus {
+        let _guard = self.lock.lock();
+        let stored = self.read();
+        let status = self.evaluate(stored.as_ref());
+        // 记下见过的最晚时间
+        if let Some(mut stored) = stored.filter(|_| status.unlocked()) {
+            let now = chrono::Utc::now();
+            let later = stored
+                .last_seen_at
+                .as_deref()
+                .and_then(parse_time)
+                .is_none_or(|seen| now > seen);
+            if later {
+                stored.last_seen_at = Some(now.to_rfc3339());
+                let _ = self.write(&stored);
+            }
+        }
+        status
+    }
+
+    // 需要授权的功能在 Rust 命令里按此把关，不依赖前端判断
+    pub fn has_feature(&self, feature: &str) -> bool {
+        let status = self.status();
+        status.unlocked() && status.features.iter().any(|f| f == feature || f == "*")
+    }
+
+    pub fn deactivate(&self) -> Result<(), String> {
+        let _guard = self
+            .lock
+            .lock()
+            .map_err(|_| "license lock poisoned".to_string())?;
+        match fs::remove_file(&self.path) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(format!("remove license failed: {}", e)),
+        }
+    }
+}
+
+// 向激活服务登记本机，返回签名的激活凭据；席位已满、许可证吊销等由服务端以非 2xx 与 error 字段说明
+async fn request_activation(
+    key: &str,
+    license_id: &str,
+    fingerprint: &str,
+    app_version: &str,
+) -> Result<SignedActivation, String> {
+    let Some(url) = server() else {
+        return Err("license server is not configured".to_string());
+    };
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .https_only(true)
+        .build()
+        .map_err(|e| format!("create http client failed: {}", e))?;
+    let body = serde_json::to_vec(&serde_json::json!({
+        "license_key": key,
+        "license_id": license_id,
+        "fingerprint": fingerprint,
+        "app_version": app_version,
+        "os": std::env::consts::OS,
+    }))
+    .map_err(|e| format!("serialize activation failed: {}", e))?;
+    let response = client
+        .post(url)
+        .header("Content-Type", "application/json")
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| format!("activate license failed: {}", e))?;
+    let status = response.status();
+    let bytes = response
+        .bytes()
+        .await
+        .map_err(|e| format!("read activation failed: {}", e))?;
+    if bytes.len() > MAX_RESPONSE_BYTES {
+        return Err("activation response too large".to_string());
+    }
+    if !status.is_success() {
+        let reason = serde_json::from_slice::<serde_json::Value>(&bytes)
+            .ok()
+            .and_then(|v| v["error"].as_str().map(|s| s.chars().take(200).collect()))
+            .unwrap_or_else(|| format!("HTTP {}", status.as_u16()));
+        return Err(format!("activate license failed: {}", reason));
+    }
+    serde_json::from_slice(&bytes).map_err(|e| format!("parse activation failed: {}", e))
+}
+
+pub async fn activate(app: &tauri::AppHandle, key: &str) -> Result<LicenseStatus, String> {
+    let key = key.trim().to_string();
+    let payload = verify_key(&key)?;
+    let state = app.state::<LicenseState>();
+    let licensing = &state.0;
+    let activation = match server() {
+        Some(_) => {
+            let version = app.package_info().version.to_string();
+            let activation =
+                request_activation(&key, &payload.id, licensing.fingerprint(), &version).await?;
+            verify_activation(&activation, &payload.id, licensing.fingerprint())?;
+            Some(activation)
+        }
+        None => None,
+    };
+    let stored = StoredLicense {
+        key,
+        activation,
+        activated_at: chrono::Utc::now().to_rfc3339(),
+        last_seen_at: Some(chrono::Utc::now().to_rfc3339()),
+    };
+    let status = licensing.evaluate(Some(&stored));
+    if !status.unlocked() {
+        return Err(status
+            .error
+            .unwrap_or_else(|| format!("license is {}", status.state)));
+    }
+    {
+        let _guard = licensing
+            .lock
+            .lock()
+            .map_err(|_| "license lock poisoned".to_string())?;
+        licensing.write(&stored)?;
+    }
+    app.state::<crate::LogState>().log_app(
+        "INFO",
+        &format!("license {} activated ({})", payload.id, status.state),
+    );
+    Ok(status)
+}
+
+// 启动时在线续期：上次校验超过 REFRESH_AFTER_DAYS 才请求；离线或服务不可用时沿用宽限期
+pub fn start(app: tauri::AppHandle) {
+    if server().is_none() {
+        return;
+    }
+    tauri::async_runtime::spawn(async move {
+        let state = app.state::<LicenseState>();
+        let licensing = &state.0;
+        let Some(mut stored) = licensing.read() else {
+            return;
+        };
+        if licensing.status().state == "active" {
+            return;
+        }
+        let Ok(payload) = verify_key(&stored.key) else {
+            return;
+        };
+        let version = app.package_info().version.to_string();
+        let log = app.state::<crate::LogState>();
+        match request_activation(&stored.key, &payload.id, licensing.fingerprint(), &version).await
+        {
+            Ok(activation) => {
+                if let Err(e) = verify_activation(&activation, &payload.id, licensing.fingerprint())
+                {
+                    log.log_app("WARN", &format!("license refresh rejected: {}", e));
+                    return;
+                }
+                stored.activation = Some(activation);
+                let _guard = licensing.lock.lock();
+                if let Err(e) = licensing.write(&stored) {
+                    log.log_app("WARN", &format!("save license failed: {}", e));
+                }
+            }
+            Err(e) => log.log_app("WARN", &format!("license refresh failed: {}", e)),
+        }
+    });
+}
@@ -0,0 +1,56 @@
+use std::path::{Path, PathBuf};
+
+// Windows 上普通 Win32 路径不能超过 MAX_PATH（260 个字符，建目录时为 248），OneDrive 同步目录下层级一深就会超，
+// 表现为 “找不到文件”。在真正读写文件的地方把超长的绝对路径换成 \\?\C:\… / \\?\UNC\server\share\… 形式，
+// 这种写法不再做 / 与 . / .. 的规范化，所以先按组件规范化一遍。其他平台原样返回
+#[cfg(windows)]
+const MAX_SHORT_PATH: usize = 248;
+
+#[cfg(windows)]
+pub fn extend(path: &Path) -> PathBuf {
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::{Component, Prefix};
+
+    let Some(Component::Prefix(prefix)) = path.components().next() else {
+        return path.to_path_buf();
+    };
+    // MAX_PATH 按 UTF-16 码元计
+    if !path.is_absolute() || path.as_os_str().encode_wide().count() < MAX_SHORT_PATH {
+        return path.to_path_buf();
+    }
+    let mut extended = std::ffi::OsString::new();
+    match prefix.kind() {
+        Prefix::Disk(_) => {
+            extended.push(r"\\?\");
+            extended.push(prefix.as_os_str());
+        }
+        Prefix::UNC(server, share) => {
+            extended.push(r"\\?\UNC\");
+            extended.push(server);
+            extended.push(r"\");
+            extended.push(share);
+        }
+        // 已经是 \\?\ 或 \\.\ 形式
+        _ => return path.to_path_buf(),
+    }
+    let mut parts: Vec<&std::ffi::OsStr> = Vec::new();
+    for component in path.components().skip(1) {
+        match component {
+            Component::Normal(part) => parts.push(part),
+            Component::ParentDir => {
+                parts.pop();
+            }
+            _ => {}
+        }
+    }
+    for part in parts {
+        extended.push(r"\");
+        extended.push(part);
+    }
+    PathBuf::from(extended)
+}
+
+#[cfg(not(windows))]
+pub fn extend(path: &Path) -> PathBuf {
+    path.to_path_buf()
+}
@@ -0,0 +1,140 @@
+use image::RgbaImage;
+
+// 防御：256^3 已远超常见的 33/65 点 LUT
+const MAX_LUT_SIZE: usize = 256;
+
+// Adobe/Resolve .cube 格式的 3D LUT：数据行按 R 变化最快、B 最慢的顺序排列
+pub struct Lut3d {
+    size: usize,
+    domain_min: [f32; 3],
+    domain_max: [f32; 3],
+    table: Vec<[f32; 3]>,
+}
+
+fn parse_triplet(parts: &[&str], line_no: usize) -> Result<[f32; 3], String> {
+    if parts.len() != 3 {
+        return Err(format!("line {}: expected 3 values", line_no));
+    }
+    let mut out = [0.0f32; 3];
+    for (slot, part) in out.iter_mut().zip(parts) {
+        *slot = part
+            .parse::<f32>()
+            .ok()
+            .filter(|v| v.is_finite())
+            .ok_or_else(|| format!("line {}: invalid number {}", line_no, part))?;
+    }
+    Ok(out)
+}
+
+impl Lut3d {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut size = 0usize;
+        let mut domain_min = [0.0f32; 3];
+        let mut domain_max = [1.0f32; 3];
+        let mut table: Vec<[f32; 3]> = Vec::new();
+
+        for (i, raw) in text.lines().enumerate() {
+            let line_no = i + 1;
+            let line = raw.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            match parts[0] {
+                "TITLE" => {}
+                "LUT_3D_SIZE" => {
+                    size = parts
+                        .get(1)
+                        .and_then(|v| v.parse::<usize>().ok())
+                        .filter(|v| (2..=MAX_LUT_SIZE).contains(v))
+                        .ok_or_else(|| format!("line {}: invalid LUT_3D_SIZE", line_no))?;
+                    table.reserve(size * size * size);
+                }
+                "LUT_1D_SIZE" => return Err("1D LUT is not supported".to_string()),
+                "DOMAIN_MIN" => domain_min = parse_triplet(&parts[1..], line_no)?,
+                "DOMAIN_MAX" => domain_max = parse_triplet(&parts[1..], line_no)?,
+                key if key.chars().all(|c| c.is_ascii_uppercase() || c == '_') => {
+                    // 其他厂商扩展关键字（如 LUT_3D_INPUT_RANGE）忽略
+                }
+                _ => {
+                    if size == 0 {
+                        return Err(format!("line {}: data before LUT_3D_SIZE", line_no));
+                    }
+                    table.push(parse_triplet(&parts, line_no)?);
+                }
+            }
+        }
+
+        if size == 0 {
+            return Err("missing LUT_3D_SIZE".to_string());
+        }
+        if table.len() != size * size * size {
+            return Err(format!(
+                "expected {} entries, got {}",
+                size * size * size,
+                table.len()
+            ));
+        }
+        if (0..3).any(|c| domain_max[c] <= domain_min[c]) {
+            return Err("invalid DOMAIN_MIN/DOMAIN_MAX".to_string());
+        }
+
+        Ok(Self {
+            size,
+            domain_min,
+            domain_max,
+            table,
+        })
+    }
+
+    fn entry(&self, r: usize, g: usize, b: usize) -> [f32; 3] {
+        self.table[r + self.size * (g + self.size * b)]
+    }
+
+    // 三线性插值
+    fn sample(&self, rgb: [f32; 3]) -> [f32; 3] {
+        let max = (self.size - 1) as f32;
+        let mut base = [0usize; 3];
+        let mut frac = [0.0f32; 3];
+        for c in 0..3 {
+            let t = ((rgb[c] - self.domain_min[c]) / (self.domain_max[c] - self.domain_min[c]))
+                .clamp(0.0, 1.0)
+                * max;
+            let i = (t.floor() as usize).min(self.size - 2);
+            base[c] = i;
+            frac[c] = t - i as f32;
+        }
+
+        let mut out = [0.0f32; 3];
+        for (dr, wr) in [(0, 1.0 - frac[0]), (1, frac[0])] {
+            for (dg, wg) in [(0, 1.0 - frac[1]), (1, frac[1])] {
+                for (db, wb) in [(0, 1.0 - frac[2]), (1, frac[2])] {
+                    let w = wr * wg * wb;
+                    if w == 0.0 {
+                        continue;
+                    }
+                    let v = self.entry(base[0] + dr, base[1] + dg, base[2] + db);
+                    for c in 0..3 {
+                        out[c] += v[c] * w;
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    // 逐像素查表，alpha 保持不变
+    pub fn apply(&self, img: &mut RgbaImage) {
+        for p in img.pixels_mut() {
+            let rgb = [
+                p[0] as f32 / 255.0,
+                p[1] as f32 / 255.0,
+                p[2] as f32 / 255.0,
+            ];
+            let out = self.sample(rgb);
+            for c in 0..3 {
+                p[c] = (out[c] * 255.0).round().clamp(0.0, 255.0) as u8;
+            }
+        }
+    }
+}
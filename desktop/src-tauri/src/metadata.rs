@@ -0,0 +1,237 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::library::{ImageMeta, Library};
+use crate::storage::{normalize_id, Storage};
+
+const MAX_BATCH: usize = 5000;
+
+// 批量元数据补丁：未设置的字段保持不变；tags 整体替换，add_tags/remove_tags 增量修改
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default)]
+pub struct MetadataPatch {
+    pub title: Option<String>,
+    pub project: Option<String>,
+    pub favorite: Option<bool>,
+    pub tags: Option<Vec<String>>,
+    pub add_tags: Vec<String>,
+    pub remove_tags: Vec<String>,
+}
+
+#[derive(Default, serde::Serialize)]
+pub struct BulkUpdateReport {
+    pub updated: usize,
+    pub sidecars_written: usize,
+    pub sidecar_errors: Vec<String>,
+}
+
+// 前端读到的 revision 与库里不一致：另一个窗口已经改过，带上最新记录让前端合并后重试
+#[derive(Debug, serde::Serialize)]
+pub struct MetadataConflict {
+    pub task_id: String,
+    pub expected: u64,
+    pub latest: ImageMeta,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct MetadataUpdateError {
+    // conflict / error
+    pub code: String,
+    pub message: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub conflicts: Vec<MetadataConflict>,
+}
+
+impl From<String> for MetadataUpdateError {
+    fn from(message: String) -> Self {
+        Self {
+            code: "error".to_string(),
+            message,
+            conflicts: Vec::new(),
+        }
+    }
+}
+
+fn clean_tags(tags: &[String]) -> Vec<String> {
+    let mut seen = HashSet::new();
+    tags.iter()
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty() && seen.insert(t.to_lowercase()))
+        .collect()
+}
+
+impl MetadataPatch {
+    fn is_empty(&self) -> bool {
+        self.title.is_none()
+            && self.project.is_none()
+            && self.favorite.is_none()
+            && self.tags.is_none()
+            && self.add_tags.is_empty()
+            && self.remove_tags.is_empty()
+    }
+
+    fn apply(&self, meta: &mut ImageMeta) {
+        if let Some(title) = &self.title {
+            meta.title = title.trim().to_string();
+        }
+        if let Some(project) = &self.project {
+            meta.project = project.trim().to_string();
+        }
+        if let Some(favorite) = self.favorite {
+            meta.favorite = favorite;
+        }
+        if let Some(tags) = &self.tags {
+            meta.tags = tags.clone();
+        }
+        meta.tags.extend(self.add_tags.iter().cloned());
+        let removed: HashSet<String> = self
+            .remove_tags
+            .iter()
+            .map(|t| t.trim().to_lowercase())
+            .collect();
+        meta.tags
+            .retain(|t| !removed.contains(&t.trim().to_lowercase()));
+        meta.tags = clean_tags(&meta.tags);
+    }
+}
+
+fn xml_escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            c if c.is_control() && c != '\n' && c != '\t' => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+// XMP sidecar（<图片名>.xmp）：Lightroom/Bridge 等 DAM 工具可以直接读取标题与关键词
+fn render_xmp(meta: &ImageMeta) -> String {
+    let subjects: String = meta
+        .tags
+        .iter()
+        .map(|t| format!("      <rdf:li>{}</rdf:li>\n", xml_escape(t)))
+        .collect();
+    format!(
+        "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n\
+         <x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n\
+         <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n\
+         <rdf:Description rdf:about=\"\"\n\
+         \x20   xmlns:dc=\"http://purl.org/dc/elements/1.1/\"\n\
+         \x20   xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\"\n\
+         \x20   xmlns:nb=\"https://ns.dztool.com/banana/1.0/\"\n\
+         \x20   xmp:Rating=\"{rating}\"\n\
+         \x20   nb:project=\"{project}\">\n\
+         \x20 <dc:title><rdf:Alt><rdf:li xml:lang=\"x-default\">{title}</rdf:li></rdf:Alt></dc:title>\n\
+         \x20 <dc:subject>\n    <rdf:Bag>\n{subjects}    </rdf:Bag>\n  </dc:subject>\n\
+         </rdf:Description>\n\
+         </rdf:RDF>\n\
+         </x:xmpmeta>\n\
+         <?xpacket end=\"w\"?>\n",
+        rating = if meta.favorite { 5 } else { 0 },
+        project = xml_escape(&meta.project),
+        title = xml_escape(&meta.title),
+        subjects = subjects,
+    )
+}
+
+fn sidecar_id(local_path: &str) -> Option<String> {
+    let id = normalize_id(local_path).ok()?;
+    let stem = match id.rsplit_once('.') {
+        Some((stem, _)) if !stem.ends_with('/') => stem.to_string(),
+        _ => id,
+    };
+    Some(format!("{}.xmp", stem))
+}
+
+pub fn write_sidecar(
+    storage: &dyn Storage,
+    local_path: &str,
+    meta: &ImageMeta,
+) -> Result<bool, String> {
+    let Some(sidecar) = sidecar_id(local_path) else {
+        return Ok(false);
+    };
+    storage.put(&sidecar, render_xmp(meta).as_bytes())?;
+    Ok(true)
+}
+
+// 在一个事务里更新多条记录的元数据，任意一条失败全部回滚；
+// sidecar 在提交后尽力写入，失败只记录不回滚（数据库是权威数据）。
+// expected 中给出的记录先比对 revision，有任何一条冲突时整批不写，未给出的记录不做检查
+pub fn update_metadata_bulk(
+    storage: &dyn Storage,
+    library: &Library,
+    ids: &[String],
+    patch: &MetadataPatch,
+    expected: &HashMap<String, u64>,
+    write_sidecars: bool,
+) -> Result<BulkUpdateReport, MetadataUpdateError> {
+    if ids.is_empty() {
+        return Err("ids is empty".to_string().into());
+    }
+    if ids.len() > MAX_BATCH {
+        return Err(format!("too many ids: {} (max {})", ids.len(), MAX_BATCH).into());
+    }
+    if patch.is_empty() {
+        return Err("patch is empty".to_string().into());
+    }
+
+    let mut conn = library.open()?;
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("begin transaction failed: {}", e))?;
+
+    let mut written: Vec<(String, ImageMeta)> = Vec::with_capacity(ids.len());
+    let mut conflicts = Vec::new();
+    let unique: HashSet<&str> = ids.iter().map(|id| id.as_str()).collect();
+    for id in unique {
+        let task = library
+            .get_task(&tx, id)?
+            .ok_or_else(|| format!("task not found: {}", id))?;
+        let mut meta = library.get_meta(&tx, id)?;
+        if let Some(&revision) = expected.get(id) {
+            if revision != meta.revision {
+                conflicts.push(MetadataConflict {
+                    task_id: id.to_string(),
+                    expected: revision,
+                    latest: meta,
+                });
+                continue;
+            }
+        }
+        patch.apply(&mut meta);
+        library.put_meta(&tx, id, &meta)?;
+        meta.revision += 1;
+        written.push((task.local_path, meta));
+    }
+    if !conflicts.is_empty() {
+        conflicts.sort_by(|a, b| a.task_id.cmp(&b.task_id));
+        return Err(MetadataUpdateError {
+            code: "conflict".to_string(),
+            message: format!("{} record(s) modified elsewhere", conflicts.len()),
+            conflicts,
+        });
+    }
+    tx.commit()
+        .map_err(|e| format!("commit metadata failed: {}", e))?;
+
+    let mut report = BulkUpdateReport {
+        updated: written.len(),
+        ..Default::default()
+    };
+    if write_sidecars {
+        for (local_path, meta) in &written {
+            match write_sidecar(storage, local_path, meta) {
+                Ok(true) => report.sidecars_written += 1,
+                Ok(false) => {}
+                Err(e) => report.sidecar_errors.push(e),
+            }
+        }
+    }
+    Ok(report)
+}
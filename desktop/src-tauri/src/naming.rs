@@ -0,0 +1,423 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::library::{Library, TaskRecord};
+use crate::settings::NamingSettings;
+use crate::storage::{normalize_id, Storage};
+
+// 文件名主体（不含扩展名）的最大字符数，留出冲突后缀与扩展名的余量
+const MAX_STEM_CHARS: usize = 120;
+const MAX_BATCH: usize = 5000;
+
+// 模板可用的字段：{date} {time} {prompt:30} {seq} {seq:4} {id} {model} {provider} {width} {height}
+pub struct TemplateContext<'a> {
+    pub id: &'a str,
+    pub prompt: &'a str,
+    pub model: &'a str,
+    pub provider: &'a str,
+    pub created_at: &'a str,
+    pub width: i64,
+    pub height: i64,
+    pub seq: usize,
+}
+
+impl<'a> TemplateContext<'a> {
+    pub fn for_task(task: &'a TaskRecord, seq: usize) -> Self {
+        Self {
+            id: &task.task_id,
+            prompt: &task.prompt,
+            model: &task.model_id,
+            provider: &task.provider_name,
+            created_at: &task.created_at,
+            width: task.width,
+            height: task.height,
+            seq,
+        }
+    }
+}
+
+// 提示词转文件名片段：保留各语言的字母数字（中文提示词也可读），其余字符折叠成 `-`
+pub fn slugify(text: &str, max_chars: usize) -> String {
+    let mut out = String::new();
+    let mut pending_dash = false;
+    for c in text.chars() {
+        if c.is_alphanumeric() {
+            if pending_dash && !out.is_empty() {
+                out.push('-');
+            }
+            pending_dash = false;
+            out.push(c);
+        } else {
+            pending_dash = true;
+        }
+        if out.chars().count() >= max_chars {
+            break;
+        }
+    }
+    out.chars().take(max_chars).collect()
+}
+
+// 去掉各平台文件名中的非法字符与首尾的点/空格（Windows 不允许以点或空格结尾）
+pub fn sanitize_stem(stem: &str) -> String {
+    let cleaned: String = stem
+        .chars()
+        .map(|c| {
+            if c.is_control() || matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') {
+                '-'
+            } else {
+                c
+            }
+        })
+        .collect();
+    cleaned
+        .trim_matches(|c: char| c == '.' || c.is_whitespace())
+        .chars()
+        .take(MAX_STEM_CHARS)
+        .collect()
+}
+
+// created_at 形如 2024-05-01 12:34:56.789+08:00；格式异常时退回当前时间
+fn date_parts(created_at: &str) -> (String, String) {
+    let valid = created_at.len() >= 19
+        && created_at.is_char_boundary(19)
+        && created_at[..10]
+            .chars()
+            .all(|c| c.is_ascii_digit() || c == '-');
+    if valid {
+        let time: String = created_at[11..19].chars().filter(|c| *c != ':').collect();
+        return (created_at[..10].to_string(), time);
+    }
+    let now = chrono::Local::now();
+    (
+        now.format("%Y-%m-%d").to_string(),
+        now.format("%H%M%S").to_string(),
+    )
+}
+
+pub fn render_template(template: &str, ctx: &TemplateContext<'_>) -> Result<String, String> {
+    let (date, time) = date_parts(ctx.created_at);
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let end = after
+            .find('}')
+            .ok_or_else(|| format!("unclosed token in template: {}", template))?;
+        let token = &after[..end];
+        let (name, arg) = match token.split_once(':') {
+            Some((n, a)) => (n.trim(), Some(a.trim())),
+            None => (token.trim(), None),
+        };
+        let arg_num = |default: usize, max: usize| -> Result<usize, String> {
+            match arg {
+                None => Ok(default),
+                Some(a) => a
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|v| *v > 0 && *v <= max)
+                    .ok_or_else(|| format!("invalid argument for {{{}}}: {}", name, a)),
+            }
+        };
+        let value = match name {
+            "date" => date.clone(),
+            "time" => time.clone(),
+            "prompt" => {
+                let slug = slugify(ctx.prompt, arg_num(30, MAX_STEM_CHARS)?);
+                if slug.is_empty() {
+                    "untitled".to_string()
+                } else {
+                    slug
+                }
+            }
+            "seq" => format!("{:0width$}", ctx.seq, width = arg_num(1, 10)?),
+            "id" => ctx.id.to_string(),
+            "model" => slugify(ctx.model, arg_num(40, MAX_STEM_CHARS)?),
+            "provider" => slugify(ctx.provider, arg_num(40, MAX_STEM_CHARS)?),
+            "width" => ctx.width.to_string(),
+            "height" => ctx.height.to_string(),
+            other => return Err(format!("unknown template token: {{{}}}", other)),
+        };
+        out.push_str(&value);
+        rest = &after[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+// 保存设置前校验模板，避免到写文件时才发现 token 写错
+pub fn validate_template(template: &str) -> Result<(), String> {
+    let ctx = TemplateContext {
+        id: "id",
+        prompt: "",
+        model: "",
+        provider: "",
+        created_at: "",
+        width: 0,
+        height: 0,
+        seq: 1,
+    };
+    render_template(template, &ctx).map(|_| ())
+}
+
+// 按命名方案为新文件分配逻辑 ID：<dir>[/<日期>]/<模板结果>.<ext>。
+// 重名时模板含 {seq} 则递增序号，否则追加 -2、-3 ...
+pub fn new_file_id(
+    storage: &dyn Storage,
+    naming: &NamingSettings,
+    dir: &str,
+    ctx: &TemplateContext<'_>,
+    ext: &str,
+) -> Result<String, String> {
+    let mut dir = dir.trim_matches('/').to_string();
+    if naming.subfolder_per_day {
+        dir = join_id(&dir, &chrono::Local::now().format("%Y-%m-%d").to_string());
+    }
+    let has_seq = naming.template.contains("{seq");
+
+    let render = |seq: usize| -> Result<String, String> {
+        let rendered = render_template(&naming.template, &TemplateContext { seq, ..*ctx })?;
+        let stem = sanitize_stem(&rendered);
+        Ok(if stem.is_empty() {
+            sanitize_stem(ctx.id)
+        } else {
+            stem
+        })
+    };
+
+    let base = render(ctx.seq.max(1))?;
+    if base.is_empty() {
+        return Err("file name is empty".to_string());
+    }
+    for n in 1..=MAX_BATCH {
+        let stem = match (n, has_seq) {
+            (1, _) => base.clone(),
+            (_, true) => render(ctx.seq.max(1) + n - 1)?,
+            (_, false) => format!("{}-{}", base, n),
+        };
+        let id = normalize_id(&join_id(&dir, &format!("{}.{}", stem, ext)))?;
+        if !storage.exists(&id) {
+            return Ok(id);
+        }
+    }
+    Err(format!("no free file name for {}", base))
+}
+
+// 后端按主图文件名推导缩略图（thumb_<主图名>），重命名时缩略图跟着改，删除记录时才能一并清理
+fn thumbnail_id(dir: &str, stem: &str, old_thumb: &str) -> String {
+    let ext = Path::new(old_thumb)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("jpg");
+    join_id(dir, &format!("thumb_{}.{}", stem, ext))
+}
+
+fn join_id(dir: &str, name: &str) -> String {
+    if dir.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", dir, name)
+    }
+}
+
+fn split_id(id: &str) -> (&str, &str) {
+    match id.rsplit_once('/') {
+        Some((dir, name)) => (dir, name),
+        None => ("", id),
+    }
+}
+
+#[derive(serde::Serialize)]
+pub struct RenamedImage {
+    pub id: String,
+    pub old_path: String,
+    pub new_path: String,
+}
+
+struct PlannedRename {
+    task_id: String,
+    from: String,
+    to: String,
+    thumb_from: Option<String>,
+    thumb_to: Option<String>,
+}
+
+fn plan_renames(
+    storage: &dyn Storage,
+    tasks: &[TaskRecord],
+    template: &str,
+) -> Result<Vec<PlannedRename>, String> {
+    let sources: HashSet<String> = tasks
+        .iter()
+        .filter_map(|t| normalize_id(&t.local_path).ok())
+        .map(|id| id.to_lowercase())
+        .collect();
+    let thumb_sources: HashSet<String> = tasks
+        .iter()
+        .filter_map(|t| normalize_id(&t.thumbnail_path).ok())
+        .map(|id| id.to_lowercase())
+        .collect();
+    let mut claimed: HashSet<String> = HashSet::new();
+    let mut plans = Vec::with_capacity(tasks.len());
+
+    for (i, task) in tasks.iter().enumerate() {
+        let from = normalize_id(&task.local_path)
+            .map_err(|_| format!("task {} has no local image", task.task_id))?;
+        if !storage.exists(&from) {
+            return Err(format!("image file missing for task {}", task.task_id));
+        }
+        let (dir, name) = split_id(&from);
+        let ext = Path::new(name)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| format!(".{}", e))
+            .unwrap_or_default();
+
+        let rendered = render_template(template, &TemplateContext::for_task(task, i + 1))?;
+        let base = sanitize_stem(&rendered);
+        if base.is_empty() {
+            return Err(format!(
+                "template produced an empty name for task {}",
+                task.task_id
+            ));
+        }
+
+        // 冲突处理：与批次内其他结果或存储中不属于本批次的文件重名时追加 -2、-3 ...
+        // 比较时忽略大小写，兼容 macOS/Windows 默认的大小写不敏感文件系统
+        let mut stem = base.clone();
+        let mut n = 1;
+        loop {
+            let candidate = join_id(dir, &format!("{}{}", stem, ext));
+            let key = candidate.to_lowercase();
+            let occupied = claimed.contains(&key)
+                || (key != from.to_lowercase()
+                    && storage.exists(&candidate)
+                    && !sources.contains(&key));
+            if !occupied {
+                claimed.insert(key);
+                break;
+            }
+            n += 1;
+            stem = format!("{}-{}", base, n);
+        }
+
+        let to = join_id(dir, &format!("{}{}", stem, ext));
+        let (thumb_from, thumb_to) = match normalize_id(&task.thumbnail_path) {
+            Ok(thumb) if storage.exists(&thumb) => {
+                let (thumb_dir, _) = split_id(&thumb);
+                let target = thumbnail_id(thumb_dir, &stem, &thumb);
+                // 目标缩略图名已被批次外的文件占用时保留原缩略图，不覆盖别人的文件
+                let key = target.to_lowercase();
+                if key != thumb.to_lowercase()
+                    && storage.exists(&target)
+                    && !thumb_sources.contains(&key)
+                {
+                    (None, None)
+                } else {
+                    (Some(thumb), Some(target))
+                }
+            }
+            _ => (None, None),
+        };
+        plans.push(PlannedRename {
+            task_id: task.task_id.clone(),
+            from,
+            to,
+            thumb_from,
+            thumb_to,
+        });
+    }
+    Ok(plans)
+}
+
+// 批重命名：按模板计算新文件名，文件改名与数据库更新要么全部成功，要么全部回滚。
+// 批次内可能出现 A->B、B->C 这类链式改名，先统一挪到临时名再落到目标名，避免互相覆盖
+pub fn rename_images(
+    storage: &dyn Storage,
+    library: &Library,
+    ids: &[String],
+    template: &str,
+) -> Result<Vec<RenamedImage>, String> {
+    if ids.is_empty() {
+        return Err("ids is empty".to_string());
+    }
+    if ids.len() > MAX_BATCH {
+        return Err(format!("too many ids: {} (max {})", ids.len(), MAX_BATCH));
+    }
+    if template.trim().is_empty() {
+        return Err("template is empty".to_string());
+    }
+    let unique: HashSet<&String> = ids.iter().collect();
+    if unique.len() != ids.len() {
+        return Err("ids contains duplicates".to_string());
+    }
+
+    let mut conn = library.open()?;
+    let tasks = library.get_tasks(&conn, ids)?;
+    let plans: Vec<PlannedRename> = plan_renames(storage, &tasks, template)?
+        .into_iter()
+        .filter(|p| p.from != p.to)
+        .collect();
+
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("begin transaction failed: {}", e))?;
+
+    // 已完成的文件移动（from, to），失败时逆序撤销
+    let mut moved: Vec<(String, String)> = Vec::new();
+    let undo = |moved: &[(String, String)]| {
+        for (from, to) in moved.iter().rev() {
+            let _ = storage.rename(to, from);
+        }
+    };
+
+    let result = (|| {
+        let seed = crate::now_ms();
+        let mut staged: Vec<(String, String)> = Vec::new();
+        for (i, plan) in plans.iter().enumerate() {
+            let pairs = std::iter::once((&plan.from, &plan.to))
+                .chain(plan.thumb_from.iter().zip(plan.thumb_to.iter()));
+            for (k, (from, to)) in pairs.enumerate() {
+                let (dir, _) = split_id(from);
+                let tmp = join_id(dir, &format!(".rename-{}-{}-{}.tmp", seed, i, k));
+                storage.rename(from, &tmp)?;
+                moved.push((from.clone(), tmp.clone()));
+                staged.push((tmp, to.clone()));
+            }
+        }
+        for (tmp, to) in &staged {
+            storage.rename(tmp, to)?;
+            moved.push((tmp.clone(), to.clone()));
+        }
+
+        for plan in &plans {
+            let thumb = plan.thumb_to.clone().unwrap_or_else(|| {
+                tasks
+                    .iter()
+                    .find(|t| t.task_id == plan.task_id)
+                    .map(|t| t.thumbnail_path.clone())
+                    .unwrap_or_default()
+            });
+            library.update_paths(&tx, &plan.task_id, &plan.to, &thumb)?;
+        }
+        Ok::<(), String>(())
+    })();
+
+    if let Err(e) = result {
+        undo(&moved);
+        return Err(e);
+    }
+    if let Err(e) = tx.commit() {
+        undo(&moved);
+        return Err(format!("commit rename failed: {}", e));
+    }
+
+    Ok(plans
+        .into_iter()
+        .map(|p| RenamedImage {
+            id: p.task_id,
+            old_path: p.from,
+            new_path: p.to,
+        })
+        .collect())
+}
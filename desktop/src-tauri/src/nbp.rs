@@ -0,0 +1,198 @@
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+
+use sha2::{Digest, Sha256};
+use zip::write::SimpleFileOptions;
+
+use crate::archive::read_entry;
+use crate::library::TaskRecord;
+use crate::storage::{normalize_id, Storage};
+
+pub const NBP_EXTENSION: &str = "nbp";
+const NBP_FORMAT: &str = "nano-banana-nbp";
+const NBP_VERSION: u32 = 1;
+const DOCUMENT_NAME: &str = "nbp.json";
+const MAX_DOCUMENT_BYTES: u64 = 4 * 1024 * 1024;
+const MAX_IMAGE_BYTES: u64 = 100 * 1024 * 1024;
+
+// .nbp 单文件结果：本质是一个 zip，内含 nbp.json（生成参数 + 溯源链）与原图，
+// 便于带着完整上下文在用户之间交换
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct NbpDocument {
+    pub format: String,
+    pub version: u32,
+    pub image: String,
+    pub sha256: String,
+    pub metadata: NbpMetadata,
+    #[serde(default)]
+    pub provenance: Vec<ProvenanceEntry>,
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct NbpMetadata {
+    pub task_id: String,
+    #[serde(default)]
+    pub prompt: String,
+    #[serde(default)]
+    pub provider_name: String,
+    #[serde(default)]
+    pub model_id: String,
+    #[serde(default)]
+    pub width: i64,
+    #[serde(default)]
+    pub height: i64,
+    #[serde(default)]
+    pub config_snapshot: String,
+    #[serde(default)]
+    pub created_at: String,
+}
+
+// 溯源链按时间顺序追加：generated -> exported -> (被其他人打开后再次导出) ...
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct ProvenanceEntry {
+    pub action: String,
+    pub tool: String,
+    pub version: String,
+    pub at: String,
+}
+
+#[derive(serde::Serialize)]
+pub struct OpenedNbp {
+    pub document: NbpDocument,
+    // 解出的图片在存储中的逻辑 ID 与本地路径（可直接用于 nbimage:// 或 asset://）
+    pub image_id: String,
+    pub image_path: Option<String>,
+}
+
+fn image_ext(local_path: &str) -> &str {
+    Path::new(local_path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .filter(|e| !e.is_empty())
+        .unwrap_or("png")
+}
+
+pub fn export_nbp(
+    storage: &dyn Storage,
+    task: &TaskRecord,
+    dest: &Path,
+    tool: &str,
+    version: &str,
+) -> Result<(), String> {
+    let id = normalize_id(&task.local_path)
+        .map_err(|_| format!("task {} has no local image", task.task_id))?;
+    let bytes = storage.get(&id)?;
+    let image_name = format!("image.{}", image_ext(&id).to_ascii_lowercase());
+
+    let now = chrono::Local::now().to_rfc3339();
+    let document = NbpDocument {
+        format: NBP_FORMAT.to_string(),
+        version: NBP_VERSION,
+        image: image_name.clone(),
+        sha256: hex::encode(Sha256::digest(&bytes)),
+        metadata: NbpMetadata {
+            task_id: task.task_id.clone(),
+            prompt: task.prompt.clone(),
+            provider_name: task.provider_name.clone(),
+            model_id: task.model_id.clone(),
+            width: task.width,
+            height: task.height,
+            config_snapshot: task.config_snapshot.clone(),
+            created_at: task.created_at.clone(),
+        },
+        provenance: vec![
+            ProvenanceEntry {
+                action: "generated".to_string(),
+                tool: task.provider_name.clone(),
+                version: task.model_id.clone(),
+                at: task.created_at.clone(),
+            },
+            ProvenanceEntry {
+                action: "exported".to_string(),
+                tool: tool.to_string(),
+                version: version.to_string(),
+                at: now,
+            },
+        ],
+    };
+    let json =
+        serde_json::to_vec_pretty(&document).map_err(|e| format!("serialize nbp failed: {}", e))?;
+
+    let dest = &crate::long_path::extend(dest);
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("create dir failed: {}", e))?;
+    }
+    // 先写临时文件再 rename，导出中断不会留下半个 .nbp
+    let tmp = dest.with_extension("nbp.tmp");
+    let result = (|| {
+        let file = File::create(&tmp).map_err(|e| format!("create nbp failed: {}", e))?;
+        let mut writer = zip::ZipWriter::new(file);
+        writer
+            .start_file(DOCUMENT_NAME, SimpleFileOptions::default())
+            .map_err(|e| format!("write nbp failed: {}", e))?;
+        writer
+            .write_all(&json)
+            .map_err(|e| format!("write nbp failed: {}", e))?;
+        // 图片本身已压缩，直接存储即可
+        writer
+            .start_file(
+                image_name.as_str(),
+                SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored),
+            )
+            .map_err(|e| format!("write nbp failed: {}", e))?;
+        writer
+            .write_all(&bytes)
+            .map_err(|e| format!("write nbp failed: {}", e))?;
+        writer
+            .finish()
+            .map_err(|e| format!("write nbp failed: {}", e))?;
+        fs::rename(&tmp, dest).map_err(|e| format!("save nbp failed: {}", e))
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+// 打开 .nbp：校验格式与图片哈希，把图片解到存储的 nbp/ 目录（按内容哈希命名，重复打开不重复写）
+pub fn open_nbp(storage: &dyn Storage, path: &Path) -> Result<OpenedNbp, String> {
+    let file = File::open(crate::long_path::extend(path))
+        .map_err(|e| format!("open nbp failed: {} ({})", e, path.display()))?;
+    let mut archive = zip::ZipArchive::new(file).map_err(|e| format!("read nbp failed: {}", e))?;
+
+    let json = read_entry(&mut archive, DOCUMENT_NAME, MAX_DOCUMENT_BYTES)?;
+    let document: NbpDocument =
+        serde_json::from_slice(&json).map_err(|e| format!("parse nbp failed: {}", e))?;
+    if document.format != NBP_FORMAT {
+        return Err(format!("unsupported nbp format: {}", document.format));
+    }
+    if document.version == 0 || document.version > NBP_VERSION {
+        return Err(format!("unsupported nbp version: {}", document.version));
+    }
+    let image_name = normalize_id(&document.image)?;
+
+    let bytes = read_entry(&mut archive, &image_name, MAX_IMAGE_BYTES)?;
+    let hash = hex::encode(Sha256::digest(&bytes));
+    if !hash.eq_ignore_ascii_case(&document.sha256) {
+        return Err("nbp image sha256 mismatch".to_string());
+    }
+
+    let image_id = format!(
+        "nbp/{}.{}",
+        &hash[..16],
+        image_ext(&image_name).to_ascii_lowercase()
+    );
+    if !storage.exists(&image_id) {
+        storage.put(&image_id, &bytes)?;
+    }
+    let image_path = storage
+        .local_path(&image_id)
+        .map(|p| p.to_string_lossy().to_string());
+
+    Ok(OpenedNbp {
+        document,
+        image_id,
+        image_path,
+    })
+}
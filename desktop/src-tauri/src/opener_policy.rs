@@ -0,0 +1,213 @@
+use std::path::{Path, PathBuf};
+
+use tauri::plugin::{Builder, TauriPlugin};
+use tauri::{AppHandle, Manager, Runtime};
+
+// 代替 tauri_plugin_opener::init() 注册同名 "opener" 插件：前端的 @tauri-apps/plugin-opener 调用不变，
+// 但每次打开都先过这里的白名单。原插件默认允许 WebView 打开任意 http(s) 链接，
+// 前端一旦被注入脚本就等于拿到了任意 open 能力。拒绝的请求写入应用日志。
+// 域名白名单来自分层配置的 runtime.open_domains（策略 / 命令行 / 环境变量 / 设置），按后缀匹配，同时放行子域名
+const PLUGIN_NAME: &str = "opener";
+const ALLOWED_SCHEMES: &[&str] = &["https", "mailto"];
+// AppData 下存放可执行文件的目录：单独更新的 sidecar 与下载的安装包
+const EXECUTABLE_DIRS: [&str; 2] = ["sidecar", "updates"];
+// 系统“打开”会直接运行的文件类型
+const EXECUTABLE_EXTENSIONS: &[&str] = &[
+    "exe", "com", "bat", "cmd", "msi", "msix", "ps1", "vbs", "vbe", "js", "jse", "wsf", "wsh",
+    "scr", "pif", "cpl", "lnk", "url", "reg", "hta", "jar", "app", "command", "tool", "sh", "pkg",
+    "dmg", "appimage", "desktop", "run", "bin", "py", "pl", "rb",
+];
+
+// 与原插件注入的脚本行为一致：target=_blank 或按住 Ctrl/Shift 点击的链接交给系统浏览器
+const LINK_SCRIPT: &str = r#"
+window.addEventListener('click', function (e) {
+  if (e.defaultPrevented || e.button !== 0 || e.metaKey || e.altKey) return;
+  var a = e.composedPath().find(function (n) { return n instanceof Node && n.nodeName.toUpperCase() === 'A'; });
+  if (!a || !a.href || (a.target !== '_blank' && !e.ctrlKey && !e.shiftKey)) return;
+  var url = new URL(a.href);
+  if (['http:', 'https:', 'mailto:', 'tel:'].indexOf(url.protocol) < 0) return;
+  e.preventDefault();
+  window.__TAURI_INTERNALS__.invoke('plugin:opener|open_url', { url: url.href });
+});
+"#;
+
+pub fn url_allowed(url: &str, domains: &[String]) -> Result<(), String> {
+    let parsed = tauri::Url::parse(url.trim()).map_err(|e| format!("invalid url: {}", e))?;
+    let scheme = parsed.scheme();
+    if !ALLOWED_SCHEMES.contains(&scheme) {
+        return Err(format!("scheme not allowed: {}", scheme));
+    }
+    if scheme == "mailto" {
+        return Ok(());
+    }
+    let host = parsed
+        .host_str()
+        .ok_or_else(|| "url has no host".to_string())?
+        .to_ascii_lowercase();
+    let host = host.trim_end_matches('.');
+    let allowed = domains
+        .iter()
+        .any(|domain| host == *domain || host.ends_with(&format!(".{}", domain)));
+    if !allowed {
+        return Err(format!("domain not allowed: {}", host));
+    }
+    Ok(())
+}
+
+fn allowed_domains<R: Runtime>(app: &AppHandle<R>) -> Vec<String> {
+    match app.try_state::<crate::ConfigState>() {
+        Some(config) => config.0.runtime.open_domains(),
+        None => Vec::new(),
+    }
+}
+
+// WebView 只能打开/定位应用自己的目录（日志、数据目录）下的路径
+fn allowed_roots<R: Runtime>(app: &AppHandle<R>) -> Vec<PathBuf> {
+    let mut roots = Vec::new();
+    if let Some(log) = app.try_state::<crate::LogState>() {
+        roots.push(log.dir.clone());
+    }
+    if let Ok(dir) = app.path().app_data_dir() {
+        roots.push(dir);
+    }
+    if let Some(config) = app.try_state::<crate::ConfigState>() {
+        if let Some(dir) = &config.0.runtime.data_dir.value {
+            roots.push(PathBuf::from(dir));
+        }
+    }
+    roots
+        .into_iter()
+        .filter_map(|root| root.canonicalize().ok())
+        .collect()
+}
+
+fn excluded_dirs<R: Runtime>(app: &AppHandle<R>) -> Vec<PathBuf> {
+    let Ok(base) = app.path().app_data_dir() else {
+        return Vec::new();
+    };
+    EXECUTABLE_DIRS
+        .iter()
+        .filter_map(|dir| base.join(dir).canonicalize().ok())
+        .collect()
+}
+
+fn is_executable(path: &Path) -> bool {
+    let by_extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+        .is_some_and(|ext| EXECUTABLE_EXTENSIONS.contains(&ext.as_str()));
+    if by_extension {
+        return true;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if let Ok(meta) = std::fs::metadata(path) {
+            return meta.is_file() && meta.permissions().mode() & 0o111 != 0;
+        }
+    }
+    false
+}
+
+// 只能定位（reveal）的路径：应用目录下、不在可执行文件目录里
+pub fn path_allowed<R: Runtime>(app: &AppHandle<R>, path: &Path) -> Result<(), String> {
+    let resolved = path
+        .canonicalize()
+        .map_err(|e| format!("resolve path failed: {} ({})", e, path.display()))?;
+    if !allowed_roots(app)
+        .iter()
+        .any(|root| resolved.starts_with(root))
+    {
+        return Err(format!("path outside app directories: {}", path.display()));
+    }
+    if excluded_dirs(app)
+        .iter()
+        .any(|dir| resolved.starts_with(dir))
+    {
+        return Err(format!("path in executable directory: {}", path.display()));
+    }
+    Ok(())
+}
+
+// 打开路径相当于让系统运行它，额外拒绝可执行文件
+fn open_allowed<R: Runtime>(app: &AppHandle<R>, path: &Path) -> Result<(), String> {
+    path_allowed(app, path)?;
+    let resolved = path
+        .canonicalize()
+        .map_err(|e| format!("resolve path failed: {} ({})", e, path.display()))?;
+    if is_executable(&resolved) {
+        return Err(format!(
+            "executable files cannot be opened: {}",
+            path.display()
+        ));
+    }
+    Ok(())
+}
+
+fn check<R: Runtime>(
+    app: &AppHandle<R>,
+    what: &str,
+    target: &str,
+    result: Result<(), String>,
+) -> Result<(), String> {
+    if let Err(reason) = &result {
+        if let Some(log) = app.try_state::<crate::LogState>() {
+            log.log_app(
+                "WARN",
+                &format!(
+                    "blocked opener {} from webview: {} ({})",
+                    what, target, reason
+                ),
+            );
+        }
+    }
+    result
+}
+
+#[tauri::command]
+async fn open_url<R: Runtime>(
+    app: AppHandle<R>,
+    url: String,
+    with: Option<String>,
+) -> Result<(), String> {
+    check(&app, "url", &url, url_allowed(&url, &allowed_domains(&app)))?;
+    tauri_plugin_opener::open_url(url.trim(), with).map_err(|e| format!("open url failed: {}", e))
+}
+
+#[tauri::command]
+async fn open_path<R: Runtime>(
+    app: AppHandle<R>,
+    path: String,
+    with: Option<String>,
+) -> Result<(), String> {
+    check(&app, "path", &path, open_allowed(&app, Path::new(&path)))?;
+    tauri_plugin_opener::open_path(&path, with).map_err(|e| format!("open path failed: {}", e))
+}
+
+#[tauri::command]
+async fn reveal_item_in_dir<R: Runtime>(
+    app: AppHandle<R>,
+    paths: Vec<PathBuf>,
+) -> Result<(), String> {
+    for path in &paths {
+        check(
+            &app,
+            "reveal",
+            &path.to_string_lossy(),
+            path_allowed(&app, path),
+        )?;
+    }
+    tauri_plugin_opener::reveal_items_in_dir(&paths)
+        .map_err(|e| format!("reveal item failed: {}", e))
+}
+
+pub fn init<R: Runtime>() -> TauriPlugin<R> {
+    Builder::new(PLUGIN_NAME)
+        .js_init_script(LINK_SCRIPT.to_string())
+        .invoke_handler(tauri::generate_handler![
+            open_url,
+            open_path,
+            reveal_item_in_dir
+        ])
+        .build()
+}
@@ -0,0 +1,286 @@
+use std::path::Path;
+
+use crate::library::{ImageMeta, Library, TaskRecord};
+use crate::naming::{sanitize_stem, slugify};
+use crate::settings::{FolderRule, RuleField};
+use crate::storage::{normalize_id, Storage};
+
+// 所有生成图都在 storage/ 下，规则只决定其中的子目录
+pub const STORAGE_ROOT: &str = "storage";
+const MAX_FOLDER_DEPTH: usize = 4;
+
+// 简单通配：`*` 匹配任意长度字符，`?` 匹配单个字符，不区分大小写
+fn glob_match(pattern: &str, value: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let value: Vec<char> = value.to_lowercase().chars().collect();
+    let (mut p, mut v) = (0, 0);
+    let (mut star, mut mark) = (None, 0);
+    while v < value.len() {
+        if p < pattern.len() && (pattern[p] == value[v] || pattern[p] == '?') {
+            p += 1;
+            v += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some(p);
+            mark = v;
+            p += 1;
+        } else if let Some(s) = star {
+            p = s + 1;
+            mark += 1;
+            v = mark;
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+fn task_date(task: &TaskRecord) -> String {
+    let date: String = task.created_at.chars().take(10).collect();
+    if date.len() == 10 && date.chars().all(|c| c.is_ascii_digit() || c == '-') {
+        date
+    } else {
+        chrono::Local::now().format("%Y-%m-%d").to_string()
+    }
+}
+
+// 返回命中的值（tag 规则需要知道具体命中了哪个标签，供目录模板使用）
+fn rule_hit(rule: &FolderRule, task: &TaskRecord, meta: &ImageMeta) -> Option<String> {
+    let pattern = rule.pattern.trim();
+    let check =
+        |value: &str| (!value.is_empty() && glob_match(pattern, value)).then(|| value.to_string());
+    match rule.field {
+        RuleField::Tag => meta.tags.iter().find_map(|t| check(t.trim())),
+        RuleField::Project => check(meta.project.trim()),
+        RuleField::Model => check(task.model_id.trim()),
+        RuleField::Provider => check(task.provider_name.trim()),
+        RuleField::Date => check(&task_date(task)),
+    }
+}
+
+// 目录模板：{project} {model} {provider} {tag} {date} {year} {month}，每一级单独清洗
+fn render_folder(
+    rule: &FolderRule,
+    hit: &str,
+    task: &TaskRecord,
+    meta: &ImageMeta,
+) -> Option<String> {
+    let date = task_date(task);
+    let mut segments = Vec::new();
+    for raw in rule.folder.split(['/', '\\']) {
+        let rendered = raw
+            .replace("{project}", &slugify(&meta.project, 60))
+            .replace("{model}", &slugify(&task.model_id, 60))
+            .replace("{provider}", &slugify(&task.provider_name, 60))
+            .replace("{tag}", &slugify(hit, 60))
+            .replace("{date}", &date)
+            .replace("{year}", date.get(..4).unwrap_or(""))
+            .replace("{month}", date.get(..7).unwrap_or(""));
+        let segment = sanitize_stem(&rendered);
+        if segment.is_empty() || segment == ".." {
+            continue;
+        }
+        segments.push(segment);
+    }
+    if segments.is_empty() || segments.len() > MAX_FOLDER_DEPTH {
+        return None;
+    }
+    Some(segments.join("/"))
+}
+
+// 按顺序匹配规则，第一条命中的规则决定子目录；都不命中返回 None（保持默认位置）
+pub fn resolve_folder(rules: &[FolderRule], task: &TaskRecord, meta: &ImageMeta) -> Option<String> {
+    rules.iter().find_map(|rule| {
+        let hit = rule_hit(rule, task, meta)?;
+        render_folder(rule, &hit, task, meta)
+    })
+}
+
+pub fn validate_rules(rules: &[FolderRule]) -> Result<(), String> {
+    for (i, rule) in rules.iter().enumerate() {
+        if rule.pattern.trim().is_empty() {
+            return Err(format!("organize.rules[{}].pattern is empty", i));
+        }
+        if rule.folder.trim().is_empty() {
+            return Err(format!("organize.rules[{}].folder is empty", i));
+        }
+        if rule.folder.split(['/', '\\']).count() > MAX_FOLDER_DEPTH {
+            return Err(format!("organize.rules[{}].folder is too deep", i));
+        }
+    }
+    Ok(())
+}
+
+// 新图保存时使用的目录：storage[/<规则目录>]
+pub fn target_dir(rules: &[FolderRule], task: &TaskRecord, meta: &ImageMeta) -> String {
+    match resolve_folder(rules, task, meta) {
+        Some(folder) => format!("{}/{}", STORAGE_ROOT, folder),
+        None => STORAGE_ROOT.to_string(),
+    }
+}
+
+#[derive(serde::Serialize)]
+pub struct PlannedMove {
+    pub id: String,
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(serde::Serialize)]
+pub struct FailedMove {
+    pub id: String,
+    pub error: String,
+}
+
+#[derive(Default, serde::Serialize)]
+pub struct ReorganizeReport {
+    pub dry_run: bool,
+    pub moved: Vec<PlannedMove>,
+    pub failed: Vec<FailedMove>,
+    pub unchanged: usize,
+}
+
+fn parent_dir(id: &str) -> &str {
+    id.rsplit_once('/').map(|(dir, _)| dir).unwrap_or("")
+}
+
+fn file_name(id: &str) -> &str {
+    id.rsplit_once('/').map(|(_, name)| name).unwrap_or(id)
+}
+
+// 目标目录出现同名文件时追加 -2、-3 ...
+fn free_target(storage: &dyn Storage, dir: &str, name: &str) -> String {
+    let path = Path::new(name);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or(name);
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| format!(".{}", e))
+        .unwrap_or_default();
+    let mut candidate = format!("{}/{}", dir, name);
+    let mut n = 1;
+    while storage.exists(&candidate) {
+        n += 1;
+        candidate = format!("{}/{}-{}{}", dir, stem, n, ext);
+    }
+    candidate
+}
+
+// 按当前规则整理已有图片：只移动命中规则且不在目标目录中的文件，未命中的保持原位。
+// dry_run 时只返回计划，不动文件与数据库；逐条提交，单条失败会回滚该条并继续
+pub fn reorganize_existing(
+    storage: &dyn Storage,
+    library: &Library,
+    rules: &[FolderRule],
+    dry_run: bool,
+) -> Result<ReorganizeReport, String> {
+    let conn = library.open()?;
+    let tasks = library.completed_tasks(&conn)?;
+    let metas = library.all_meta(&conn)?;
+    let mut report = ReorganizeReport {
+        dry_run,
+        ..Default::default()
+    };
+
+    for task in &tasks {
+        let Ok(from) = normalize_id(&task.local_path) else {
+            continue;
+        };
+        if !from.starts_with(&format!("{}/", STORAGE_ROOT)) {
+            report.unchanged += 1;
+            continue;
+        }
+        let default_meta = ImageMeta::default();
+        let meta = metas.get(&task.task_id).unwrap_or(&default_meta);
+        let Some(folder) = resolve_folder(rules, task, meta) else {
+            report.unchanged += 1;
+            continue;
+        };
+        let dir = format!("{}/{}", STORAGE_ROOT, folder);
+        // 已在目标目录（含按日期分出的下级目录）中的不再移动
+        let parent = parent_dir(&from);
+        if parent == dir || parent.starts_with(&format!("{}/", dir)) {
+            report.unchanged += 1;
+            continue;
+        }
+        if !storage.exists(&from) {
+            report.failed.push(FailedMove {
+                id: task.task_id.clone(),
+                error: "image file missing".to_string(),
+            });
+            continue;
+        }
+
+        let to = free_target(storage, &dir, file_name(&from));
+        if dry_run {
+            report.moved.push(PlannedMove {
+                id: task.task_id.clone(),
+                from,
+                to,
+            });
+            continue;
+        }
+
+        match move_task(storage, library, &conn, task, &from, &to, &dir) {
+            Ok(()) => report.moved.push(PlannedMove {
+                id: task.task_id.clone(),
+                from,
+                to,
+            }),
+            Err(error) => report.failed.push(FailedMove {
+                id: task.task_id.clone(),
+                error,
+            }),
+        }
+    }
+    Ok(report)
+}
+
+fn move_task(
+    storage: &dyn Storage,
+    library: &Library,
+    conn: &rusqlite::Connection,
+    task: &TaskRecord,
+    from: &str,
+    to: &str,
+    dir: &str,
+) -> Result<(), String> {
+    storage.rename(from, to)?;
+
+    // 缩略图（thumb_<主图名>）跟随主图移动；改名冲突时沿用主图新文件名推导
+    let mut thumb_move: Option<(String, String)> = None;
+    if let Ok(thumb) = normalize_id(&task.thumbnail_path) {
+        if storage.exists(&thumb) {
+            let ext = Path::new(&thumb)
+                .extension()
+                .and_then(|e| e.to_str())
+                .unwrap_or("jpg")
+                .to_string();
+            let stem = Path::new(to)
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or_default()
+                .to_string();
+            let thumb_to = format!("{}/thumb_{}.{}", dir, stem, ext);
+            if !storage.exists(&thumb_to) {
+                if let Err(e) = storage.rename(&thumb, &thumb_to) {
+                    let _ = storage.rename(to, from);
+                    return Err(e);
+                }
+                thumb_move = Some((thumb, thumb_to));
+            }
+        }
+    }
+
+    let thumb_path = thumb_move
+        .as_ref()
+        .map(|(_, t)| t.clone())
+        .unwrap_or_else(|| task.thumbnail_path.clone());
+    if let Err(e) = library.update_paths(conn, &task.task_id, to, &thumb_path) {
+        if let Some((thumb, thumb_to)) = &thumb_move {
+            let _ = storage.rename(thumb_to, thumb);
+        }
+        let _ = storage.rename(to, from);
+        return Err(e);
+    }
+    Ok(())
+}
@@ -0,0 +1,316 @@
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tauri::{Emitter, Manager};
+
+use crate::lan_share::{content_type, prepare_client, read_request, respond};
+use crate::resize::{OutputFormat, ResizeParams};
+use crate::settings::OverlaySettings;
+use crate::storage::{normalize_id, write_atomic};
+
+// 直播叠加层：后台线程盯着历史库里最新完成的结果，出新图就覆盖写到固定路径（OBS 图像源），
+// 并在 127.0.0.1 的固定端口上提供一个透明背景的页面（OBS 浏览器源）。页面轮询 /version，
+// 版本变化后用 /latest?v=<版本> 换图，既绕开缓存又不会在新图加载完之前闪白
+pub const EVENT: &str = "overlay-updated";
+pub const DEFAULT_PORT: u16 = 17880;
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const ACCEPT_POLL: Duration = Duration::from_millis(200);
+// 端口被占用时隔一段时间再试，不每轮都去绑定
+const BIND_RETRY: Duration = Duration::from_secs(30);
+
+const PAGE: &str = r#"<!doctype html>
+<html><head><meta charset="utf-8"><title>Nano Banana overlay</title>
+<style>html,body{margin:0;height:100%;background:transparent;overflow:hidden}
+img{width:100%;height:100%;object-fit:contain;display:none}</style></head>
+<body><img id="latest" alt=""><script>
+let version = 0;
+const img = document.getElementById("latest");
+async function poll() {
+  try {
+    const res = await fetch("/version", { cache: "no-store" });
+    const data = await res.json();
+    if (data.version !== version && data.version > 0) {
+      const next = new Image();
+      next.onload = () => { img.src = next.src; img.style.display = "block"; };
+      next.src = "/latest?v=" + data.version;
+      version = data.version;
+    }
+  } catch (e) {}
+  setTimeout(poll, 1000);
+}
+poll();
+</script></body></html>
+"#;
+
+#[derive(Clone, Debug, Default, serde::Serialize)]
+pub struct OverlayStatus {
+    pub enabled: bool,
+    pub task_id: Option<String>,
+    // 每换一张图 +1，0 表示还没有图
+    pub version: u64,
+    pub file: Option<String>,
+    // 浏览器源用的页面地址
+    pub url: Option<String>,
+    // 图片本身的地址，需要自行追加 ?v= 防缓存
+    pub image_url: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Clone)]
+struct Latest {
+    task_id: String,
+    bytes: Arc<Vec<u8>>,
+    content_type: &'static str,
+    version: u64,
+}
+
+struct Server {
+    port: u16,
+    stop: Arc<AtomicBool>,
+}
+
+#[derive(Default)]
+pub struct Overlay {
+    latest: Arc<Mutex<Option<Latest>>>,
+    server: Mutex<Option<Server>>,
+    // 最近一次写入固定路径的 (路径, task_id)，设置改了路径时重写
+    written: Mutex<Option<(String, String)>>,
+    error: Mutex<Option<String>>,
+    // 最近一次绑定失败的端口与时间
+    failed_port: Mutex<Option<(u16, Instant)>>,
+}
+
+pub struct OverlayState(pub Overlay);
+
+pub fn validate(settings: &OverlaySettings) -> Result<(), String> {
+    if let Some(file) = &settings.file {
+        if !std::path::Path::new(file.trim()).is_absolute() {
+            return Err("overlay.file must be an absolute path".to_string());
+        }
+    }
+    if let Some(port) = settings.port {
+        if port < 1024 {
+            return Err("overlay.port must be between 1024 and 65535".to_string());
+        }
+    }
+    Ok(())
+}
+
+fn port_of(settings: &OverlaySettings) -> u16 {
+    settings.port.unwrap_or(DEFAULT_PORT)
+}
+
+impl Overlay {
+    pub fn status(&self, settings: &OverlaySettings) -> OverlayStatus {
+        let latest = self.latest.lock().ok().and_then(|l| l.clone());
+        let port = self
+            .server
+            .lock()
+            .ok()
+            .and_then(|s| s.as_ref().map(|s| s.port));
+        OverlayStatus {
+            enabled: settings.enabled,
+            task_id: latest.as_ref().map(|l| l.task_id.clone()),
+            version: latest.map(|l| l.version).unwrap_or(0),
+            file: settings.file.clone().filter(|_| settings.enabled),
+            url: port.map(|p| format!("http://127.0.0.1:{}/", p)),
+            image_url: port.map(|p| format!("http://127.0.0.1:{}/latest", p)),
+            error: self.error.lock().ok().and_then(|e| e.clone()),
+        }
+    }
+
+    fn stop_server(&self) {
+        if let Ok(mut server) = self.server.lock() {
+            if let Some(server) = server.take() {
+                server.stop.store(true, Ordering::SeqCst);
+            }
+        }
+    }
+
+    // 按设置启停页面服务；端口变了就换新的监听
+    fn sync_server(&self, settings: &OverlaySettings) -> Result<(), String> {
+        if !settings.enabled || !settings.serve {
+            self.stop_server();
+            return Ok(());
+        }
+        let port = port_of(settings);
+        let running = self
+            .server
+            .lock()
+            .ok()
+            .and_then(|s| s.as_ref().map(|s| s.port));
+        if running == Some(port) {
+            return Ok(());
+        }
+        self.stop_server();
+        let recently_failed = self
+            .failed_port
+            .lock()
+            .ok()
+            .and_then(|p| *p)
+            .is_some_and(|(failed, at)| failed == port && at.elapsed() < BIND_RETRY);
+        if recently_failed {
+            return Err(format!("overlay port {} is not available", port));
+        }
+        let listener = match TcpListener::bind((Ipv4Addr::LOCALHOST, port)) {
+            Ok(listener) => listener,
+            Err(e) => {
+                if let Ok(mut failed) = self.failed_port.lock() {
+                    *failed = Some((port, Instant::now()));
+                }
+                return Err(format!("overlay port {} is not available: {}", port, e));
+            }
+        };
+        listener
+            .set_nonblocking(true)
+            .map_err(|e| format!("start overlay server failed: {}", e))?;
+        if let Ok(mut failed) = self.failed_port.lock() {
+            *failed = None;
+        }
+        let stop = Arc::new(AtomicBool::new(false));
+        let latest = self.latest.clone();
+        let thread_stop = stop.clone();
+        thread::spawn(move || {
+            while !thread_stop.load(Ordering::SeqCst) {
+                match listener.accept() {
+                    Ok((stream, _)) => serve_client(stream, port, &latest),
+                    Err(_) => thread::sleep(ACCEPT_POLL),
+                }
+            }
+        });
+        if let Ok(mut server) = self.server.lock() {
+            *server = Some(Server { port, stop });
+        }
+        Ok(())
+    }
+}
+
+// 只认本机地址的 Host，挡住借 DNS rebinding 从网页里读图
+fn host_allowed(host: Option<&str>, port: u16) -> bool {
+    host.is_some_and(|host| {
+        host == format!("127.0.0.1:{}", port) || host == format!("localhost:{}", port)
+    })
+}
+
+fn serve_client(mut stream: TcpStream, port: u16, latest: &Mutex<Option<Latest>>) {
+    prepare_client(&stream);
+    let Some(request) = read_request(&mut stream) else {
+        return;
+    };
+    if !host_allowed(request.header("host"), port) {
+        respond(&mut stream, "403 Forbidden", &[], 0, b"");
+        return;
+    }
+    if request.method != "GET" && request.method != "HEAD" {
+        respond(&mut stream, "405 Method Not Allowed", &[], 0, b"");
+        return;
+    }
+    let head = request.method == "HEAD";
+    let current = latest.lock().ok().and_then(|l| l.clone());
+    let (status, content, body): (&str, &str, Vec<u8>) = match request.path.as_str() {
+        "/" | "/index.html" => (
+            "200 OK",
+            "text/html; charset=utf-8",
+            PAGE.as_bytes().to_vec(),
+        ),
+        "/version" => {
+            let body = serde_json::json!({
+                "version": current.as_ref().map(|l| l.version).unwrap_or(0),
+                "task_id": current.as_ref().map(|l| l.task_id.clone()),
+            })
+            .to_string();
+            ("200 OK", "application/json", body.into_bytes())
+        }
+        "/latest" => match &current {
+            Some(latest) => ("200 OK", latest.content_type, latest.bytes.to_vec()),
+            None => ("404 Not Found", "text/plain", b"no result yet".to_vec()),
+        },
+        _ => ("404 Not Found", "text/plain", b"not found".to_vec()),
+    };
+    let headers = [("Content-Type", content.to_string())];
+    let payload: &[u8] = if head { b"" } else { &body };
+    respond(&mut stream, status, &headers, body.len(), payload);
+}
+
+// 固定路径的扩展名和图片实际格式不一致时转换一次，避免 OBS 按扩展名解码失败
+fn write_file(path: &str, bytes: &[u8]) -> Result<(), String> {
+    let wanted = OutputFormat::from_id(path);
+    let actual = OutputFormat::parse(content_type(bytes).1).unwrap_or(OutputFormat::Png);
+    if wanted == actual {
+        return write_atomic(&PathBuf::from(path), bytes);
+    }
+    let params = ResizeParams {
+        format: Some(wanted),
+        ..Default::default()
+    };
+    let converted = crate::resize::transform(bytes, &params, wanted)?;
+    write_atomic(&PathBuf::from(path), &converted)
+}
+
+fn tick(app: &tauri::AppHandle) -> Result<(), String> {
+    let settings = app.state::<crate::SettingsState>().0.get().overlay;
+    let state = app.state::<OverlayState>();
+    let overlay = &state.0;
+    overlay.sync_server(&settings)?;
+    if !settings.enabled {
+        return Ok(());
+    }
+
+    let library = &app.state::<crate::LibraryState>().0;
+    let conn = library.open()?;
+    let Some(task) = library.recent_tasks(&conn, 1)?.into_iter().next() else {
+        return Ok(());
+    };
+    let current = overlay.latest.lock().ok().and_then(|l| l.clone());
+    let latest = match current {
+        Some(latest) if latest.task_id == task.task_id => latest,
+        previous => {
+            let id = normalize_id(&task.local_path)?;
+            let bytes = app.state::<crate::StorageState>().0.get(&id)?;
+            let latest = Latest {
+                task_id: task.task_id.clone(),
+                content_type: content_type(&bytes).0,
+                bytes: Arc::new(bytes),
+                version: previous.map(|p| p.version).unwrap_or(0) + 1,
+            };
+            if let Ok(mut slot) = overlay.latest.lock() {
+                *slot = Some(latest.clone());
+            }
+            let _ = app.emit(EVENT, overlay.status(&settings));
+            latest
+        }
+    };
+
+    if let Some(file) = settings.file.as_deref().map(str::trim) {
+        let key = (file.to_string(), latest.task_id.clone());
+        let written = overlay.written.lock().ok().and_then(|w| w.clone());
+        if written.as_ref() != Some(&key) {
+            write_file(file, &latest.bytes)?;
+            if let Ok(mut slot) = overlay.written.lock() {
+                *slot = Some(key);
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn start(app: tauri::AppHandle) {
+    thread::spawn(move || loop {
+        let result = tick(&app);
+        let state = app.state::<OverlayState>();
+        let error = result.err();
+        if let Ok(mut last) = state.0.error.lock() {
+            // 同样的错误只记一次
+            if let Some(e) = error.as_ref().filter(|e| last.as_ref() != Some(*e)) {
+                app.state::<crate::LogState>()
+                    .log_app("WARN", &format!("overlay update failed: {}", e));
+            }
+            *last = error;
+        }
+        thread::sleep(POLL_INTERVAL);
+    });
+}
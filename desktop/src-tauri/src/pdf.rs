@@ -0,0 +1,335 @@
+use std::io::Write;
+use std::path::Path;
+
+use chrono::{Datelike, Timelike};
+use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+use pdf_writer::{Content, Date, Filter, Finish, Name, Pdf, Rect, Ref, TextStr};
+
+use crate::generation_info::GenerationInfo;
+use crate::text::{self, Anchor, TextOptions, TextPos, TextRenderer};
+
+// 单页 A4 PDF：上方原图（原分辨率嵌入），下方信息块（标题、提示词、参数，可选二维码），
+// 用于存档与给客户确认。信息块用原生文字渲染成位图（中日韩与 emoji 都不依赖 PDF 字体），
+// 完整提示词同时写进文档属性，PDF 阅读器里可以检索
+const PAGE_W: f32 = 595.28;
+const PAGE_H: f32 = 841.89;
+const MARGIN: f32 = 40.0;
+const GAP: f32 = 16.0;
+// 信息块渲染精度：150 DPI
+const PX_PER_PT: f32 = 150.0 / 72.0;
+const QR_PT: f32 = 86.0;
+// 信息块最多占内容区的一半，超出部分截断
+const MAX_PANEL_RATIO: f32 = 0.5;
+const MAX_PROMPT_CHARS: usize = 1500;
+
+struct Block {
+    text: String,
+    size: f32,
+    weight: u16,
+    color: [u8; 4],
+    gap_after: f32,
+}
+
+fn truncate(text: &str, max: usize) -> String {
+    let text = text.trim();
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let mut out: String = text.chars().take(max).collect();
+    out.push('…');
+    out
+}
+
+fn blocks(info: &GenerationInfo) -> Vec<Block> {
+    let title = if info.title.trim().is_empty() {
+        "生成信息".to_string()
+    } else {
+        info.title.trim().to_string()
+    };
+    let label = |text: &str| Block {
+        text: text.to_string(),
+        size: 9.0,
+        weight: 600,
+        color: [110, 110, 110, 255],
+        gap_after: 3.0,
+    };
+    let body = |text: String| Block {
+        text,
+        size: 10.5,
+        weight: 400,
+        color: [20, 20, 20, 255],
+        gap_after: 10.0,
+    };
+    let mut out = vec![Block {
+        text: title,
+        size: 16.0,
+        weight: 600,
+        color: [0, 0, 0, 255],
+        gap_after: 10.0,
+    }];
+    if !info.prompt.trim().is_empty() {
+        out.push(label("提示词"));
+        out.push(body(truncate(&info.prompt, MAX_PROMPT_CHARS)));
+    }
+    if let Some(negative) = &info.negative_prompt {
+        out.push(label("反向提示词"));
+        out.push(body(truncate(negative, MAX_PROMPT_CHARS / 2)));
+    }
+    let fields: Vec<String> = info
+        .fields()
+        .into_iter()
+        .map(|(key, value)| format!("{}：{}", key, truncate(&value, 200)))
+        .collect();
+    if !fields.is_empty() {
+        out.push(Block {
+            text: fields.join("\n"),
+            size: 8.5,
+            weight: 400,
+            color: [90, 90, 90, 255],
+            gap_after: 0.0,
+        });
+    }
+    out
+}
+
+fn options(block: &Block, max_width: f32) -> TextOptions {
+    TextOptions {
+        weight: Some(block.weight),
+        max_width: Some(max_width),
+        line_height: Some(1.35),
+        ..TextOptions::default()
+    }
+}
+
+// 信息块位图；宽度固定为内容区宽度，高度按内容计算并封顶
+fn render_panel(
+    renderer: &mut TextRenderer,
+    info: &GenerationInfo,
+    link: Option<&str>,
+    max_height_pt: f32,
+) -> Result<RgbaImage, String> {
+    let width_px = ((PAGE_W - MARGIN * 2.0) * PX_PER_PT).round();
+    let qr = match link {
+        Some(link) => Some(crate::qr::render(link, 4)?),
+        None => None,
+    };
+    let qr_px = (QR_PT * PX_PER_PT).round();
+    let text_width = if qr.is_some() {
+        width_px - qr_px - GAP * PX_PER_PT
+    } else {
+        width_px
+    };
+
+    let blocks = blocks(info);
+    let mut placed = Vec::with_capacity(blocks.len());
+    let mut y = 0.0f32;
+    for block in &blocks {
+        let size = block.size * PX_PER_PT;
+        let (_, h) = text::measure_text(
+            renderer,
+            &block.text,
+            None,
+            size,
+            &options(block, text_width),
+        )?;
+        placed.push((y, size));
+        y += h + block.gap_after * PX_PER_PT;
+    }
+    let caption_px = 8.0 * PX_PER_PT;
+    let qr_block = if qr.is_some() {
+        qr_px + caption_px * 1.6
+    } else {
+        0.0
+    };
+    let height_px = y
+        .max(qr_block)
+        .min(max_height_pt * PX_PER_PT)
+        .ceil()
+        .max(1.0);
+
+    let mut panel = RgbaImage::from_pixel(width_px as u32, height_px as u32, Rgba([255; 4]));
+    for (block, (top, size)) in blocks.iter().zip(placed) {
+        if top >= height_px {
+            break;
+        }
+        text::draw_text(
+            renderer,
+            &mut panel,
+            &block.text,
+            None,
+            size,
+            TextPos {
+                x: 0.0,
+                y: top,
+                anchor: Anchor::TopLeft,
+            },
+            block.color,
+            &options(block, text_width),
+        )?;
+    }
+    if let Some(qr) = qr {
+        let scaled = image::imageops::resize(
+            &DynamicImage::ImageLuma8(qr).to_rgba8(),
+            qr_px as u32,
+            qr_px as u32,
+            image::imageops::FilterType::Nearest,
+        );
+        let x = (width_px - qr_px) as i64;
+        image::imageops::overlay(&mut panel, &scaled, x, 0);
+        let caption = Block {
+            text: "扫码打开".to_string(),
+            size: 8.0,
+            weight: 400,
+            color: [110, 110, 110, 255],
+            gap_after: 0.0,
+        };
+        text::draw_text(
+            renderer,
+            &mut panel,
+            &caption.text,
+            None,
+            caption_px,
+            TextPos {
+                x: width_px - qr_px / 2.0,
+                y: qr_px + caption_px * 0.2,
+                anchor: Anchor::Top,
+            },
+            caption.color,
+            &options(&caption, qr_px),
+        )?;
+    }
+    Ok(panel)
+}
+
+fn zlib(data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder
+        .write_all(data)
+        .map_err(|e| format!("compress image failed: {}", e))?;
+    encoder
+        .finish()
+        .map_err(|e| format!("compress image failed: {}", e))
+}
+
+// 透明区域铺白底
+fn flatten_rgb(img: &DynamicImage) -> Vec<u8> {
+    img.to_rgba8()
+        .pixels()
+        .flat_map(|p| {
+            let a = p[3] as u32;
+            [0, 1, 2].map(|c| ((p[c] as u32 * a + 255 * (255 - a)) / 255) as u8)
+        })
+        .collect()
+}
+
+// JPEG 原样嵌入（不重新压缩），其他格式转 RGB 后无损压缩
+fn encode_image(bytes: &[u8]) -> Result<(Filter, Vec<u8>, u32, u32), String> {
+    let img = image::load_from_memory(bytes).map_err(|e| format!("decode image failed: {}", e))?;
+    let (w, h) = img.dimensions();
+    let is_jpeg = matches!(image::guess_format(bytes), Ok(image::ImageFormat::Jpeg));
+    if is_jpeg && img.color() == image::ColorType::Rgb8 {
+        return Ok((Filter::DctDecode, bytes.to_vec(), w, h));
+    }
+    Ok((Filter::FlateDecode, zlib(&flatten_rgb(&img))?, w, h))
+}
+
+pub fn export_annotated(
+    renderer: &mut TextRenderer,
+    image_bytes: &[u8],
+    info: &GenerationInfo,
+    link: Option<&str>,
+    creator: &str,
+    dest: &Path,
+) -> Result<(), String> {
+    let (filter, encoded, img_w, img_h) = encode_image(image_bytes)?;
+    let content_w = PAGE_W - MARGIN * 2.0;
+    let content_h = PAGE_H - MARGIN * 2.0;
+    let panel = render_panel(renderer, info, link, content_h * MAX_PANEL_RATIO)?;
+    let (panel_w_px, panel_h_px) = panel.dimensions();
+    let panel_h = panel_h_px as f32 / PX_PER_PT;
+    let panel_rgb = zlib(&DynamicImage::ImageRgba8(panel).to_rgb8())?;
+
+    // 原图等比缩放放进信息块上方的区域，水平居中
+    let avail_h = (content_h - panel_h - GAP).max(1.0);
+    let scale = (content_w / img_w as f32).min(avail_h / img_h as f32);
+    let (draw_w, draw_h) = (img_w as f32 * scale, img_h as f32 * scale);
+    let image_x = MARGIN + (content_w - draw_w) / 2.0;
+    let image_y = PAGE_H - MARGIN - draw_h;
+    let panel_y = image_y - GAP - panel_h;
+
+    let catalog_id = Ref::new(1);
+    let page_tree_id = Ref::new(2);
+    let page_id = Ref::new(3);
+    let image_id = Ref::new(4);
+    let panel_id = Ref::new(5);
+    let content_id = Ref::new(6);
+    let info_id = Ref::new(7);
+    let image_name = Name(b"Im1");
+    let panel_name = Name(b"Im2");
+
+    let mut pdf = Pdf::new();
+    pdf.catalog(catalog_id).pages(page_tree_id);
+    pdf.pages(page_tree_id).kids([page_id]).count(1);
+    let mut page = pdf.page(page_id);
+    page.media_box(Rect::new(0.0, 0.0, PAGE_W, PAGE_H));
+    page.parent(page_tree_id);
+    page.contents(content_id);
+    page.resources()
+        .x_objects()
+        .pair(image_name, image_id)
+        .pair(panel_name, panel_id);
+    page.finish();
+
+    let mut image = pdf.image_xobject(image_id, &encoded);
+    image.filter(filter);
+    image.width(img_w as i32);
+    image.height(img_h as i32);
+    image.color_space().device_rgb();
+    image.bits_per_component(8);
+    image.finish();
+
+    let mut panel_image = pdf.image_xobject(panel_id, &panel_rgb);
+    panel_image.filter(Filter::FlateDecode);
+    panel_image.width(panel_w_px as i32);
+    panel_image.height(panel_h_px as i32);
+    panel_image.color_space().device_rgb();
+    panel_image.bits_per_component(8);
+    panel_image.finish();
+
+    let mut content = Content::new();
+    content.save_state();
+    content.transform([draw_w, 0.0, 0.0, draw_h, image_x, image_y]);
+    content.x_object(image_name);
+    content.restore_state();
+    content.save_state();
+    content.transform([content_w, 0.0, 0.0, panel_h, MARGIN, panel_y]);
+    content.x_object(panel_name);
+    content.restore_state();
+    pdf.stream(content_id, &content.finish());
+
+    let now = chrono::Local::now();
+    let title = if info.title.trim().is_empty() {
+        info.id.as_str()
+    } else {
+        info.title.trim()
+    };
+    let keywords = info.tags.join(", ");
+    let mut doc = pdf.document_info(info_id);
+    doc.title(TextStr(title));
+    doc.subject(TextStr(info.prompt.trim()));
+    if !keywords.is_empty() {
+        doc.keywords(TextStr(&keywords));
+    }
+    doc.creator(TextStr(creator));
+    doc.creation_date(
+        Date::new(now.year().clamp(0, 9999) as u16)
+            .month(now.month() as u8)
+            .day(now.day() as u8)
+            .hour(now.hour() as u8)
+            .minute(now.minute() as u8)
+            .second(now.second() as u8),
+    );
+    doc.finish();
+
+    crate::storage::write_atomic(dest, &pdf.finish())
+}
@@ -0,0 +1,63 @@
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use tauri::Emitter;
+
+// backend-port 事件合并：边车短时间内连续重启会报出多个端口，前端若逐个处理会互相竞争。
+// 窗口期内的多次宣布只发最后一次；每次宣布带单调递增的 epoch，前端只接受比已知更大的 epoch
+pub const EVENT: &str = "backend-port";
+const COALESCE_WINDOW: Duration = Duration::from_millis(300);
+
+#[derive(Clone, Copy, Debug, Default, serde::Serialize)]
+pub struct PortAnnouncement {
+    pub port: u16,
+    pub epoch: u64,
+}
+
+#[derive(Default)]
+struct Inner {
+    latest: PortAnnouncement,
+    // 已有一个等待中的发送线程，新宣布只需更新 latest
+    scheduled: bool,
+}
+
+#[derive(Clone, Default)]
+pub struct PortAnnouncer(Arc<Mutex<Inner>>);
+
+impl PortAnnouncer {
+    pub fn announce(&self, app: &tauri::AppHandle, port: u16) -> u64 {
+        let Ok(mut inner) = self.0.lock() else {
+            return 0;
+        };
+        inner.latest = PortAnnouncement {
+            port,
+            epoch: inner.latest.epoch + 1,
+        };
+        let epoch = inner.latest.epoch;
+        if inner.scheduled {
+            return epoch;
+        }
+        inner.scheduled = true;
+        drop(inner);
+
+        let state = self.0.clone();
+        let app = app.clone();
+        thread::spawn(move || {
+            thread::sleep(COALESCE_WINDOW);
+            let latest = match state.lock() {
+                Ok(mut inner) => {
+                    inner.scheduled = false;
+                    inner.latest
+                }
+                Err(_) => return,
+            };
+            let _ = app.emit(EVENT, latest);
+        });
+        epoch
+    }
+
+    pub fn current(&self) -> PortAnnouncement {
+        self.0.lock().map(|inner| inner.latest).unwrap_or_default()
+    }
+}
@@ -0,0 +1,251 @@
+use image::imageops;
+use image::RgbaImage;
+
+// 打码区域，坐标为原图像素
+#[derive(Clone, Copy, Debug, serde::Deserialize)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+// 一次最多处理的区域数，防止前端误传超大列表
+pub const MAX_REGIONS: usize = 256;
+
+// 对指定区域做高斯模糊（区域外扩一圈一起模糊，避免边缘采样到未打码的原像素而露出轮廓）。
+// sigma 未指定时按区域大小取值，保证人脸/文字无法辨认
+pub fn blur_regions(img: &mut RgbaImage, rects: &[Rect], sigma: Option<f32>) -> Result<(), String> {
+    if rects.is_empty() {
+        return Err("rects is empty".to_string());
+    }
+    if rects.len() > MAX_REGIONS {
+        return Err(format!(
+            "too many rects: {} (max {})",
+            rects.len(),
+            MAX_REGIONS
+        ));
+    }
+    if let Some(s) = sigma {
+        if !(s.is_finite() && s > 0.0 && s <= 200.0) {
+            return Err(format!("invalid sigma: {}", s));
+        }
+    }
+
+    let (img_w, img_h) = img.dimensions();
+    for rect in rects {
+        // 裁到图像范围内，完全落在图外的区域直接报错，避免用户以为已经打码
+        let x0 = rect.x.min(img_w);
+        let y0 = rect.y.min(img_h);
+        let x1 = rect.x.saturating_add(rect.width).min(img_w);
+        let y1 = rect.y.saturating_add(rect.height).min(img_h);
+        if x1 <= x0 || y1 <= y0 {
+            return Err(format!(
+                "rect out of bounds: {}x{}+{}+{}",
+                rect.width, rect.height, rect.x, rect.y
+            ));
+        }
+        let (w, h) = (x1 - x0, y1 - y0);
+        let sigma = sigma.unwrap_or_else(|| (w.max(h) as f32 / 6.0).clamp(4.0, 64.0));
+
+        let pad = (sigma * 3.0).ceil() as u32;
+        let px0 = x0.saturating_sub(pad);
+        let py0 = y0.saturating_sub(pad);
+        let px1 = (x1 + pad).min(img_w);
+        let py1 = (y1 + pad).min(img_h);
+        let padded = imageops::crop_imm(img, px0, py0, px1 - px0, py1 - py0).to_image();
+        let blurred = imageops::blur(&padded, sigma);
+
+        let region = imageops::crop_imm(&blurred, x0 - px0, y0 - py0, w, h).to_image();
+        imageops::replace(img, &region, x0 as i64, y0 as i64);
+    }
+    Ok(())
+}
+
+// 原生后期滤镜：在 Rust 中计算，保证各平台结果一致（canvas 在不同 WebView 上的插值/色彩处理有差异）
+pub enum Filter {
+    Unsharp(UnsharpParams),
+    Grain(GrainParams),
+    Vignette(VignetteParams),
+}
+
+#[derive(serde::Deserialize)]
+#[serde(default)]
+pub struct UnsharpParams {
+    pub sigma: f32,
+    pub amount: f32,
+    // 亮度差低于阈值的像素不锐化，避免放大平坦区域的噪点
+    pub threshold: u8,
+}
+
+impl Default for UnsharpParams {
+    fn default() -> Self {
+        Self {
+            sigma: 1.5,
+            amount: 0.8,
+            threshold: 2,
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+#[serde(default)]
+pub struct GrainParams {
+    pub amount: f32,
+    pub monochrome: bool,
+    // 固定种子：同一张图同样参数多次导出结果一致
+    pub seed: u64,
+}
+
+impl Default for GrainParams {
+    fn default() -> Self {
+        Self {
+            amount: 0.08,
+            monochrome: true,
+            seed: 0,
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+#[serde(default)]
+pub struct VignetteParams {
+    pub amount: f32,
+    // 以半对角线为 1：从 radius 处开始变暗，经过 softness 过渡到最暗
+    pub radius: f32,
+    pub softness: f32,
+}
+
+impl Default for VignetteParams {
+    fn default() -> Self {
+        Self {
+            amount: 0.4,
+            radius: 0.6,
+            softness: 0.5,
+        }
+    }
+}
+
+fn check_range(name: &str, value: f32, min: f32, max: f32) -> Result<(), String> {
+    if value.is_finite() && value >= min && value <= max {
+        Ok(())
+    } else {
+        Err(format!(
+            "invalid {}: {} (expected {}..={})",
+            name, value, min, max
+        ))
+    }
+}
+
+impl Filter {
+    pub fn parse(name: &str, params: serde_json::Value) -> Result<Self, String> {
+        // 前端不传参数时走默认值
+        let params = if params.is_null() {
+            serde_json::json!({})
+        } else {
+            params
+        };
+        let invalid = |e: serde_json::Error| format!("invalid params for {}: {}", name, e);
+        let filter = match name.trim().to_ascii_lowercase().as_str() {
+            "unsharp" | "unsharp_mask" | "sharpen" => {
+                let p: UnsharpParams = serde_json::from_value(params).map_err(invalid)?;
+                check_range("sigma", p.sigma, 0.1, 50.0)?;
+                check_range("amount", p.amount, 0.0, 5.0)?;
+                Self::Unsharp(p)
+            }
+            "grain" | "noise" => {
+                let p: GrainParams = serde_json::from_value(params).map_err(invalid)?;
+                check_range("amount", p.amount, 0.0, 1.0)?;
+                Self::Grain(p)
+            }
+            "vignette" => {
+                let p: VignetteParams = serde_json::from_value(params).map_err(invalid)?;
+                check_range("amount", p.amount, 0.0, 1.0)?;
+                check_range("radius", p.radius, 0.0, 2.0)?;
+                check_range("softness", p.softness, 0.01, 2.0)?;
+                Self::Vignette(p)
+            }
+            other => return Err(format!("unsupported filter: {}", other)),
+        };
+        Ok(filter)
+    }
+
+    pub fn apply(&self, img: &mut RgbaImage) {
+        match self {
+            Self::Unsharp(p) => unsharp(img, p),
+            Self::Grain(p) => grain(img, p),
+            Self::Vignette(p) => vignette(img, p),
+        }
+    }
+}
+
+fn unsharp(img: &mut RgbaImage, p: &UnsharpParams) {
+    let blurred = imageops::blur(img, p.sigma);
+    for (px, b) in img.pixels_mut().zip(blurred.pixels()) {
+        for c in 0..3 {
+            let diff = px[c] as f32 - b[c] as f32;
+            if diff.abs() < p.threshold as f32 {
+                continue;
+            }
+            px[c] = (px[c] as f32 + diff * p.amount).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+}
+
+// splitmix64：不依赖随机数库，且各平台输出完全一致
+struct SplitMix(u64);
+
+impl SplitMix {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // 三个均匀分布求和近似高斯，范围约 [-1, 1]
+    fn noise(&mut self) -> f32 {
+        let mut sum = 0.0;
+        for _ in 0..3 {
+            sum += (self.next() >> 40) as f32 / (1u64 << 24) as f32;
+        }
+        (sum - 1.5) / 1.5
+    }
+}
+
+fn grain(img: &mut RgbaImage, p: &GrainParams) {
+    let mut rng = SplitMix(p.seed);
+    let scale = p.amount * 255.0;
+    for px in img.pixels_mut() {
+        if p.monochrome {
+            let n = rng.noise() * scale;
+            for c in 0..3 {
+                px[c] = (px[c] as f32 + n).round().clamp(0.0, 255.0) as u8;
+            }
+        } else {
+            for c in 0..3 {
+                let n = rng.noise() * scale;
+                px[c] = (px[c] as f32 + n).round().clamp(0.0, 255.0) as u8;
+            }
+        }
+    }
+}
+
+fn vignette(img: &mut RgbaImage, p: &VignetteParams) {
+    let (w, h) = img.dimensions();
+    let cx = w as f32 / 2.0;
+    let cy = h as f32 / 2.0;
+    let half_diag = (cx * cx + cy * cy).sqrt().max(1.0);
+    for (x, y, px) in img.enumerate_pixels_mut() {
+        let dx = x as f32 + 0.5 - cx;
+        let dy = y as f32 + 0.5 - cy;
+        let d = (dx * dx + dy * dy).sqrt() / half_diag;
+        let t = ((d - p.radius) / p.softness).clamp(0.0, 1.0);
+        // smoothstep 过渡，避免出现明显的圆环边界
+        let factor = 1.0 - p.amount * t * t * (3.0 - 2.0 * t);
+        for c in 0..3 {
+            px[c] = (px[c] as f32 * factor).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use tauri::Manager;
+
+// 前台优先：主窗口获得焦点（用户正在画布上操作）时把 sidecar 降到后台优先级，批量生成、缩放等
+// 重活让出 CPU 和 IO 给界面；窗口失焦、最小化或隐藏到托盘后恢复正常优先级全速跑。worker pool
+// 在 sidecar 进程里，按进程调整即覆盖其所有线程。macOS 用 PRIO_DARWIN_BG，Windows 用
+// BELOW_NORMAL_PRIORITY_CLASS；Linux 上普通用户调低 nice 后无法再调回，不做处理
+#[derive(Default)]
+pub struct Priority {
+    focused: AtomicBool,
+    // 上次设置的 (pid, 是否已降级)，避免重复调用；sidecar 重启后 pid 变化会重新设置
+    applied: Mutex<Option<(u32, bool)>>,
+    // 同一个错误只记一次
+    last_error: Mutex<Option<String>>,
+}
+
+pub struct PriorityState(pub Priority);
+
+#[cfg(target_os = "macos")]
+fn set_background(pid: u32, background: bool) -> Result<bool, String> {
+    const PRIO_DARWIN_PROCESS: i32 = 4;
+    const PRIO_DARWIN_BG: i32 = 0x1000;
+    extern "C" {
+        fn setpriority(which: i32, who: u32, prio: i32) -> i32;
+    }
+
+    let prio = if background { PRIO_DARWIN_BG } else { 0 };
+    if unsafe { setpriority(PRIO_DARWIN_PROCESS, pid, prio) } != 0 {
+        return Err(format!(
+            "setpriority failed: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok(true)
+}
+
+#[cfg(windows)]
+fn set_background(pid: u32, background: bool) -> Result<bool, String> {
+    use windows::Win32::Foundation::CloseHandle;
+    use windows::Win32::System::Threading::{
+        OpenProcess, SetPriorityClass, BELOW_NORMAL_PRIORITY_CLASS, NORMAL_PRIORITY_CLASS,
+        PROCESS_SET_INFORMATION,
+    };
+
+    let class = if background {
+        BELOW_NORMAL_PRIORITY_CLASS
+    } else {
+        NORMAL_PRIORITY_CLASS
+    };
+    unsafe {
+        let process = OpenProcess(PROCESS_SET_INFORMATION, false, pid)
+            .map_err(|e| format!("open sidecar process failed: {}", e))?;
+        let result = SetPriorityClass(process, class);
+        let _ = CloseHandle(process);
+        result.map_err(|e| format!("SetPriorityClass failed: {}", e))?;
+    }
+    Ok(true)
+}
+
+// 返回 false 表示平台不支持
+#[cfg(not(any(target_os = "macos", windows)))]
+fn set_background(_pid: u32, _background: bool) -> Result<bool, String> {
+    Ok(false)
+}
+
+fn enabled(app: &tauri::AppHandle) -> bool {
+    app.state::<crate::SettingsState>()
+        .0
+        .get()
+        .sidecar
+        .lower_priority_when_focused
+        .unwrap_or(true)
+}
+
+fn sidecar_pid(app: &tauri::AppHandle) -> Option<u32> {
+    let state = app.state::<crate::SidecarState>();
+    let guard = state.0.lock().ok()?;
+    guard.as_ref().map(|child| child.pid())
+}
+
+// 按当前焦点与设置调整 sidecar 优先级；焦点变化、sidecar 启动、设置修改后调用
+pub fn apply(app: &tauri::AppHandle) {
+    let state = app.state::<PriorityState>();
+    let Some(pid) = sidecar_pid(app) else {
+        return;
+    };
+    let background = enabled(app) && state.0.focused.load(Ordering::SeqCst);
+    let Ok(mut applied) = state.0.applied.lock() else {
+        return;
+    };
+    // 新启动的进程本来就是正常优先级
+    let current = match *applied {
+        Some((applied_pid, lowered)) if applied_pid == pid => lowered,
+        _ => false,
+    };
+    if current == background {
+        *applied = Some((pid, background));
+        return;
+    }
+    match set_background(pid, background) {
+        Ok(true) => {
+            *applied = Some((pid, background));
+            app.state::<crate::LogState>().log_app(
+                "INFO",
+                &format!(
+                    "sidecar priority: {}",
+                    if background { "background" } else { "normal" }
+                ),
+            );
+        }
+        Ok(false) => *applied = Some((pid, background)),
+        Err(e) => {
+            let mut last_error = state.0.last_error.lock().unwrap_or_else(|p| p.into_inner());
+            if last_error.as_deref() != Some(e.as_str()) {
+                app.state::<crate::LogState>()
+                    .log_app("WARN", &format!("sidecar priority: {}", e));
+                *last_error = Some(e);
+            }
+        }
+    }
+}
+
+pub fn on_focus(app: &tauri::AppHandle, focused: bool) {
+    app.state::<PriorityState>()
+        .0
+        .focused
+        .store(focused, Ordering::SeqCst);
+    apply(app);
+}
@@ -0,0 +1,234 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use base64::Engine;
+use tauri::{Emitter, Manager};
+
+// 原生长任务（导入项目包、按预设批量导出等）的进度：主窗口可见时只发 job-progress 事件给前端；
+// 任务开始时主窗口已隐藏（关窗缩到托盘）或最小化，就由 Rust 建一个置顶的小窗口显示进度，
+// 后台任务不再"悄无声息"。小窗口不在任何 capability 里、拿不到 IPC，取消按钮靠导航到
+// CANCEL_URL 触发，由 on_navigation 拦下；关掉小窗口不影响任务继续
+pub const EVENT: &str = "job-progress";
+const CANCEL_URL: &str = "https://progress.invalid/cancel";
+const UPDATE_INTERVAL: Duration = Duration::from_millis(200);
+const WINDOW_WIDTH: f64 = 380.0;
+const WINDOW_HEIGHT: f64 = 150.0;
+
+const PAGE: &str = r#"<!doctype html>
+<html><head><meta charset="utf-8">
+<style>body{margin:0;padding:16px 18px;font:13px -apple-system,"Segoe UI","PingFang SC","Microsoft YaHei",sans-serif;
+background:#1f1f1f;color:#eee;user-select:none;cursor:default}
+#title{font-weight:600;margin-bottom:10px;white-space:nowrap;overflow:hidden;text-overflow:ellipsis}
+#bar{height:6px;background:#3a3a3a;border-radius:3px;overflow:hidden}
+#fill{height:100%;width:0;background:#f5c518;transition:width .2s}
+#row{display:flex;justify-content:space-between;align-items:center;margin-top:12px}
+#detail{color:#aaa;white-space:nowrap;overflow:hidden;text-overflow:ellipsis;margin-right:12px}
+button{background:#3a3a3a;color:#eee;border:0;border-radius:4px;padding:4px 14px;font:inherit}
+button:disabled{opacity:.5}</style></head>
+<body><div id="title"></div><div id="bar"><div id="fill"></div></div>
+<div id="row"><span id="detail"></span><button id="cancel">取消</button></div><script>
+const $ = (id) => document.getElementById(id);
+window.nbProgress = (p) => {
+  $("title").textContent = p.title;
+  $("fill").style.width = (p.total > 0 ? Math.min(100, p.done * 100 / p.total) : 0) + "%";
+  $("detail").textContent = p.cancelled ? "正在取消…" : (p.total > 0 ? p.done + " / " + p.total : "") + (p.detail ? "  " + p.detail : "");
+  $("cancel").disabled = p.cancelled;
+};
+$("cancel").onclick = () => { location.href = "__CANCEL_URL__"; };
+</script></body></html>
+"#;
+
+#[derive(Clone, Debug, serde::Serialize)]
+struct JobProgress<'a> {
+    id: u64,
+    title: &'a str,
+    done: usize,
+    total: usize,
+    detail: &'a str,
+    cancelled: bool,
+    finished: bool,
+}
+
+// 正在进行的任务的取消标记，前端也可以经 cancel_native_job 取消
+#[derive(Default)]
+pub struct Jobs {
+    next_id: AtomicU64,
+    cancels: Mutex<HashMap<u64, Arc<AtomicBool>>>,
+}
+
+pub struct JobsState(pub Jobs);
+
+impl Jobs {
+    pub fn active(&self) -> usize {
+        self.cancels.lock().map(|c| c.len()).unwrap_or(0)
+    }
+
+    // 任务不存在（已结束）时返回 false
+    pub fn cancel(&self, id: u64) -> bool {
+        let flag = self
+            .cancels
+            .lock()
+            .ok()
+            .and_then(|cancels| cancels.get(&id).cloned());
+        match flag {
+            Some(flag) => {
+                flag.store(true, Ordering::SeqCst);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+fn main_window_hidden(app: &tauri::AppHandle) -> bool {
+    match app.get_webview_window("main") {
+        Some(window) => {
+            !window.is_visible().unwrap_or(true) || window.is_minimized().unwrap_or(false)
+        }
+        None => true,
+    }
+}
+
+fn open_window(
+    app: &tauri::AppHandle,
+    id: u64,
+    title: &str,
+    cancel: Arc<AtomicBool>,
+) -> Result<tauri::WebviewWindow, String> {
+    let page = PAGE.replace("__CANCEL_URL__", CANCEL_URL);
+    let url = format!(
+        "data:text/html;base64,{}",
+        base64::engine::general_purpose::STANDARD.encode(page)
+    );
+    let url = url
+        .parse()
+        .map_err(|e| format!("build progress page url failed: {}", e))?;
+    tauri::WebviewWindowBuilder::new(
+        app,
+        format!("progress-{}", id),
+        tauri::WebviewUrl::External(url),
+    )
+    .title(title)
+    .inner_size(WINDOW_WIDTH, WINDOW_HEIGHT)
+    .resizable(false)
+    .maximizable(false)
+    .minimizable(false)
+    .always_on_top(true)
+    .focused(false)
+    .on_navigation(move |url| {
+        if url.as_str() == CANCEL_URL {
+            cancel.store(true, Ordering::SeqCst);
+            return false;
+        }
+        url.scheme() == "data"
+    })
+    .build()
+    .map_err(|e| format!("create progress window failed: {}", e))
+}
+
+pub struct Job {
+    app: tauri::AppHandle,
+    id: u64,
+    title: String,
+    total: usize,
+    done: usize,
+    cancel: Arc<AtomicBool>,
+    window: Option<tauri::WebviewWindow>,
+    last_update: Option<Instant>,
+}
+
+impl Job {
+    // total 为 0 表示总数未知
+    pub fn begin(app: &tauri::AppHandle, title: &str, total: usize) -> Self {
+        let state = app.state::<JobsState>();
+        let id = state.0.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+        let cancel = Arc::new(AtomicBool::new(false));
+        if let Ok(mut cancels) = state.0.cancels.lock() {
+            cancels.insert(id, cancel.clone());
+        }
+        crate::app_nap::refresh(app);
+        let wanted =
+            app.state::<crate::SettingsState>().0.get().progress.window && main_window_hidden(app);
+        let window = if wanted {
+            match open_window(app, id, title, cancel.clone()) {
+                Ok(window) => Some(window),
+                Err(e) => {
+                    app.state::<crate::LogState>()
+                        .log_app("WARN", &format!("progress window: {}", e));
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        let mut job = Self {
+            app: app.clone(),
+            id,
+            title: title.to_string(),
+            total,
+            done: 0,
+            cancel,
+            window,
+            last_update: None,
+        };
+        job.report(0, "", false);
+        job
+    }
+
+    // 总数要处理到一半才知道时（如先读项目包的 manifest）再补上
+    pub fn set_total(&mut self, total: usize) {
+        self.total = total;
+    }
+
+    pub fn cancelled(&self) -> bool {
+        self.cancel.load(Ordering::SeqCst)
+    }
+
+    fn report(&mut self, done: usize, detail: &str, finished: bool) {
+        self.done = done;
+        let progress = JobProgress {
+            id: self.id,
+            title: &self.title,
+            done,
+            total: self.total,
+            detail,
+            cancelled: self.cancelled(),
+            finished,
+        };
+        let _ = self.app.emit(EVENT, &progress);
+        if let Some(window) = &self.window {
+            if let Ok(json) = serde_json::to_string(&progress) {
+                // 页面还没加载完时脚本会失败，下一次更新再补上
+                let _ = window.eval(format!("window.nbProgress && window.nbProgress({})", json));
+            }
+        }
+        self.last_update = Some(Instant::now());
+    }
+
+    // 逐项调用，内部限频；返回 false 表示用户已取消，调用方应停止处理剩余项
+    pub fn advance(&mut self, done: usize, detail: &str) -> bool {
+        self.done = done;
+        let due = self
+            .last_update
+            .is_none_or(|at| at.elapsed() >= UPDATE_INTERVAL);
+        if due {
+            self.report(done, detail, false);
+        }
+        !self.cancelled()
+    }
+}
+
+impl Drop for Job {
+    fn drop(&mut self) {
+        self.report(self.done, "", true);
+        if let Some(window) = self.window.take() {
+            let _ = window.destroy();
+        }
+        if let Ok(mut cancels) = self.app.state::<JobsState>().0.cancels.lock() {
+            cancels.remove(&self.id);
+        }
+        crate::app_nap::refresh(&self.app);
+    }
+}
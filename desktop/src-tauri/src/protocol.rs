@@ -0,0 +1,600 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use sha2::{Digest, Sha256};
+use tauri::http::{header, HeaderMap, Request, Response, StatusCode};
+use tauri::{AppHandle, Manager, Runtime, UriSchemeContext, UriSchemeResponder};
+
+use crate::resize::{ResizeCache, ResizeParams};
+use crate::storage::{normalize_id, Storage, StorageMeta, StorageState};
+
+pub const SCHEME: &str = "nbimage";
+
+// 允许 webview 短期直接复用缓存，过期后带 ETag 回来校验，未变化时只返回 304
+const CACHE_CONTROL: &str = "public, max-age=3600, must-revalidate";
+const MAX_ETAG_ENTRIES: usize = 4096;
+// 单次 Range 响应最多返回的字节数；<video> 会按需继续请求后续区间
+const MAX_RANGE_CHUNK: u64 = 4 * 1024 * 1024;
+// 协议只开放图片与音视频所在的目录；数据目录下的设置、密钥、数据库、日志等一律 404
+const SERVED_ROOTS: [&str; 11] = [
+    crate::organize::STORAGE_ROOT,
+    "ref_images",
+    "cache/nbimage",
+    "downloads",
+    "clipboard",
+    "watermarked",
+    "redacted",
+    "graded",
+    "filtered",
+    "captioned",
+    "nbp",
+];
+
+pub struct ProtocolState {
+    resize: ResizeCache,
+    etags: Mutex<HashMap<String, (StorageMeta, String)>>,
+}
+
+impl ProtocolState {
+    pub fn new(resize: ResizeCache) -> Self {
+        Self {
+            resize,
+            etags: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // ETag 取内容 SHA-256，按（大小, 修改时间）记忆，文件不变时不用再读盘计算。
+    // 首次计算时顺便把读到的内容带回去，避免同一请求读两次
+    fn etag(
+        &self,
+        storage: &dyn Storage,
+        id: &str,
+        meta: StorageMeta,
+    ) -> Result<(String, Option<Vec<u8>>), String> {
+        if let Ok(etags) = self.etags.lock() {
+            if let Some((cached_meta, etag)) = etags.get(id) {
+                if *cached_meta == meta {
+                    return Ok((etag.clone(), None));
+                }
+            }
+        }
+
+        let bytes = storage.get(id)?;
+        let digest = Sha256::digest(&bytes);
+        let etag = hex::encode(&digest[..16]);
+        if let Ok(mut etags) = self.etags.lock() {
+            if etags.len() >= MAX_ETAG_ENTRIES {
+                etags.clear();
+            }
+            etags.insert(id.to_string(), (meta, etag.clone()));
+        }
+        Ok((etag, Some(bytes)))
+    }
+}
+
+// 逻辑 ID 每一段单独编码，保留 '/' 分隔
+const SEGMENT: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'_').remove(b'.');
+
+// 逻辑 ID 对应的协议地址，前端可直接用作 <img src>
+pub fn url_for(id: &str) -> String {
+    let path = id
+        .split('/')
+        .map(|segment| utf8_percent_encode(segment, SEGMENT).to_string())
+        .collect::<Vec<_>>()
+        .join("/");
+    if cfg!(windows) {
+        format!("http://{}.localhost/{}", SCHEME, path)
+    } else {
+        format!("{}://localhost/{}", SCHEME, path)
+    }
+}
+
+// 自定义图片协议：nbimage://localhost/<逻辑ID>（Windows 上为 http://nbimage.localhost/<逻辑ID>），
+// 由当前存储后端解析逻辑 ID，前端不再需要关心文件实际存放在哪里。
+// 支持 ?w=&h=&fmt=&q= 按需缩放/转码；解码与编码较重，放到阻塞线程池里执行，避免卡住 webview
+// 音视频同样走该协议：支持单区间 Range / If-Range，<video> 可以拖动进度而不必整文件读入内存
+pub fn handle<R: Runtime>(
+    ctx: UriSchemeContext<'_, R>,
+    request: Request<Vec<u8>>,
+    responder: UriSchemeResponder,
+) {
+    let app = ctx.app_handle().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut response = serve(&app, &request);
+        with_cors(app.config(), &request, response.headers_mut());
+        responder.respond(response);
+    });
+}
+
+// 只对应用自身页面回 ACAO（canvas / fetch 读取像素需要），其他来源不放行
+fn with_cors(config: &tauri::Config, request: &Request<Vec<u8>>, headers: &mut HeaderMap) {
+    headers.insert(header::VARY, header::HeaderValue::from_static("Origin"));
+    let Some(origin) = request.headers().get(header::ORIGIN) else {
+        return;
+    };
+    let allowed = origin
+        .to_str()
+        .ok()
+        .and_then(|v| tauri::Url::parse(v).ok())
+        .is_some_and(|url| crate::ipc_guard::is_app_origin(config, &url));
+    if allowed {
+        headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin.clone());
+    }
+}
+
+// 逻辑 ID 需位于 SERVED_ROOTS 之下且扩展名是可识别的图片或音视频格式
+fn is_served(id: &str) -> bool {
+    let under_root = SERVED_ROOTS.iter().any(|root| {
+        id.strip_prefix(root)
+            .is_some_and(|rest| rest.starts_with('/'))
+    });
+    under_root && mime_for(id) != "application/octet-stream"
+}
+
+fn serve<R: Runtime>(app: &AppHandle<R>, request: &Request<Vec<u8>>) -> Response<Vec<u8>> {
+    let Some(storage) = app.try_state::<StorageState>() else {
+        return error_response(StatusCode::SERVICE_UNAVAILABLE, "storage not ready");
+    };
+
+    let decoded = percent_decode_str(request.uri().path()).decode_utf8_lossy();
+    let id = match normalize_id(&decoded) {
+        Ok(id) => id,
+        Err(err) => return error_response(StatusCode::BAD_REQUEST, &err),
+    };
+    if !is_served(&id) {
+        return error_response(StatusCode::NOT_FOUND, "not found");
+    }
+    let params = match ResizeParams::parse(request.uri().query()) {
+        Ok(params) => params,
+        Err(err) => return error_response(StatusCode::BAD_REQUEST, &err),
+    };
+
+    let Some(meta) = storage.0.stat(&id) else {
+        return error_response(StatusCode::NOT_FOUND, "not found");
+    };
+
+    let Some(state) = app.try_state::<ProtocolState>() else {
+        return error_response(StatusCode::SERVICE_UNAVAILABLE, "protocol not ready");
+    };
+
+    // 音视频文件动辄几百 MB，ETag 改用（大小, 修改时间）推导，不读全文件做摘要
+    let media = params.is_none() && is_media(&id);
+    let (source_etag, source_bytes) = if media {
+        (meta_etag(meta), None)
+    } else {
+        match state.etag(storage.0.as_ref(), &id, meta) {
+            Ok(v) => v,
+            Err(err) => return error_response(StatusCode::INTERNAL_SERVER_ERROR, &err),
+        }
+    };
+    // 缩放变体的 ETag = 原图 ETag + 变体键（SHA-256），原图或参数变化都会失效，且不随工具链升级改变
+    let etag = match &params {
+        Some(params) => format!(
+            "\"{}-{}\"",
+            source_etag,
+            crate::resize::variant_key(&id, meta, params)
+        ),
+        None => format!("\"{}\"", source_etag),
+    };
+    let last_modified = meta.modified.map(truncate_to_secs);
+
+    if is_not_modified(request.headers(), &etag, last_modified) {
+        let mut builder = Response::builder()
+            .status(StatusCode::NOT_MODIFIED)
+            .header(header::ETAG, &etag)
+            .header(header::CACHE_CONTROL, CACHE_CONTROL);
+        if let Some(t) = last_modified {
+            builder = builder.header(header::LAST_MODIFIED, httpdate::fmt_http_date(t));
+        }
+        return builder.body(Vec::new()).unwrap_or_else(|_| {
+            error_response(StatusCode::INTERNAL_SERVER_ERROR, "build response failed")
+        });
+    }
+
+    if params.is_none() {
+        let range = request
+            .headers()
+            .get(header::RANGE)
+            .and_then(|v| v.to_str().ok())
+            .filter(|_| if_range_matches(request.headers(), &etag, last_modified));
+        if let Some(range) = range {
+            match parse_range(range, meta.len) {
+                ByteRange::Full => {}
+                ByteRange::Unsatisfiable => return unsatisfiable_response(meta.len),
+                ByteRange::Partial(start, end) => {
+                    let count = chunk_len(start, end);
+                    let bytes = match &source_bytes {
+                        Some(bytes) => Ok(bytes
+                            .get(start as usize..)
+                            .map(|rest| rest[..rest.len().min(count as usize)].to_vec())
+                            .unwrap_or_default()),
+                        None => storage.0.get_range(&id, start, count),
+                    };
+                    return match bytes {
+                        // stat 之后文件被截短时读到空内容，按区间不可满足处理
+                        Ok(bytes) if bytes.is_empty() => error_response(
+                            StatusCode::RANGE_NOT_SATISFIABLE,
+                            "range not satisfiable",
+                        ),
+                        Ok(bytes) => {
+                            partial_response(&id, start, bytes, meta.len, &etag, last_modified)
+                        }
+                        Err(err) => error_response(StatusCode::INTERNAL_SERVER_ERROR, &err),
+                    };
+                }
+            }
+        }
+    }
+
+    let result = match &params {
+        Some(params) => state
+            .resize
+            .render(storage.0.as_ref(), &id, meta, params)
+            .map(|(bytes, format)| (bytes, format.mime())),
+        None => match source_bytes {
+            Some(bytes) => Ok((bytes, mime_for(&id))),
+            None => storage.0.get(&id).map(|bytes| (bytes, mime_for(&id))),
+        },
+    };
+
+    match result {
+        Ok((bytes, mime)) => {
+            let mut builder = Response::builder()
+                .status(StatusCode::OK)
+                .header(header::CONTENT_TYPE, mime)
+                .header(header::ETAG, &etag)
+                .header(
+                    header::ACCEPT_RANGES,
+                    if params.is_none() { "bytes" } else { "none" },
+                )
+                .header(header::CACHE_CONTROL, CACHE_CONTROL);
+            if let Some(t) = last_modified {
+                builder = builder.header(header::LAST_MODIFIED, httpdate::fmt_http_date(t));
+            }
+            builder.body(bytes).unwrap_or_else(|_| {
+                error_response(StatusCode::INTERNAL_SERVER_ERROR, "build response failed")
+            })
+        }
+        Err(err) => error_response(StatusCode::INTERNAL_SERVER_ERROR, &err),
+    }
+}
+
+// If-None-Match 优先；只有没带 If-None-Match 时才看 If-Modified-Since（RFC 9110）
+fn is_not_modified(headers: &HeaderMap, etag: &str, last_modified: Option<SystemTime>) -> bool {
+    if let Some(value) = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+    {
+        return value
+            .split(',')
+            .map(str::trim)
+            .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag);
+    }
+
+    let Some(modified) = last_modified else {
+        return false;
+    };
+    headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| httpdate::parse_http_date(v).ok())
+        .map(|since| modified <= since)
+        .unwrap_or(false)
+}
+
+fn partial_response(
+    id: &str,
+    start: u64,
+    bytes: Vec<u8>,
+    total: u64,
+    etag: &str,
+    last_modified: Option<SystemTime>,
+) -> Response<Vec<u8>> {
+    let end = start + bytes.len() as u64 - 1;
+    let mut builder = Response::builder()
+        .status(StatusCode::PARTIAL_CONTENT)
+        .header(header::CONTENT_TYPE, mime_for(id))
+        .header(
+            header::CONTENT_RANGE,
+            format!("bytes {}-{}/{}", start, end, total),
+        )
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::ETAG, etag)
+        .header(header::CACHE_CONTROL, CACHE_CONTROL);
+    if let Some(t) = last_modified {
+        builder = builder.header(header::LAST_MODIFIED, httpdate::fmt_http_date(t));
+    }
+    builder.body(bytes).unwrap_or_else(|_| {
+        error_response(StatusCode::INTERNAL_SERVER_ERROR, "build response failed")
+    })
+}
+
+fn unsatisfiable_response(total: u64) -> Response<Vec<u8>> {
+    Response::builder()
+        .status(StatusCode::RANGE_NOT_SATISFIABLE)
+        .header(header::CONTENT_RANGE, format!("bytes */{}", total))
+        .header(header::ACCEPT_RANGES, "bytes")
+        .body(Vec::new())
+        .unwrap_or_else(|_| {
+            error_response(StatusCode::INTERNAL_SERVER_ERROR, "build response failed")
+        })
+}
+
+// 闭区间 [start, end] 本次实际读取的字节数，不超过 MAX_RANGE_CHUNK
+fn chunk_len(start: u64, end: u64) -> u64 {
+    (end - start + 1).min(MAX_RANGE_CHUNK)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum ByteRange {
+    // 无法解析或多区间：忽略 Range 返回整个文件（RFC 9110 允许）
+    Full,
+    // 闭区间 [start, end]
+    Partial(u64, u64),
+    Unsatisfiable,
+}
+
+// 只支持单区间：bytes=a-b / bytes=a- / bytes=-n
+fn parse_range(value: &str, len: u64) -> ByteRange {
+    let Some(spec) = value.trim().strip_prefix("bytes=") else {
+        return ByteRange::Full;
+    };
+    if spec.contains(',') {
+        return ByteRange::Full;
+    }
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return ByteRange::Full;
+    };
+    let (start, end) = (start.trim(), end.trim());
+    if start.is_empty() {
+        // 后缀区间：最后 n 个字节
+        return match end.parse::<u64>() {
+            Ok(0) => ByteRange::Unsatisfiable,
+            Ok(n) if len > 0 => ByteRange::Partial(len.saturating_sub(n), len - 1),
+            Ok(_) => ByteRange::Unsatisfiable,
+            Err(_) => ByteRange::Full,
+        };
+    }
+    let Ok(start) = start.parse::<u64>() else {
+        return ByteRange::Full;
+    };
+    if start >= len {
+        return ByteRange::Unsatisfiable;
+    }
+    if end.is_empty() {
+        return ByteRange::Partial(start, len - 1);
+    }
+    match end.parse::<u64>() {
+        Ok(end) if end >= start => ByteRange::Partial(start, end.min(len - 1)),
+        _ => ByteRange::Full,
+    }
+}
+
+// If-Range 与当前版本不一致时按整文件响应，避免把新旧两个版本的片段拼在一起
+fn if_range_matches(headers: &HeaderMap, etag: &str, last_modified: Option<SystemTime>) -> bool {
+    let Some(value) = headers.get(header::IF_RANGE).and_then(|v| v.to_str().ok()) else {
+        return true;
+    };
+    let value = value.trim();
+    if value.starts_with('"') || value.starts_with("W/") {
+        // 弱 ETag 不能用于 If-Range（RFC 9110 13.1.5）
+        return value == etag;
+    }
+    match (httpdate::parse_http_date(value), last_modified) {
+        (Ok(date), Some(modified)) => date == modified,
+        _ => false,
+    }
+}
+
+fn meta_etag(meta: StorageMeta) -> String {
+    let secs = meta
+        .modified
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    format!("{:x}-{:x}", meta.len, secs)
+}
+
+fn is_media(id: &str) -> bool {
+    let mime = mime_for(id);
+    mime.starts_with("video/") || mime.starts_with("audio/")
+}
+
+// HTTP 日期只精确到秒，比较前先截断，否则 If-Modified-Since 永远比文件时间“旧”
+fn truncate_to_secs(t: SystemTime) -> SystemTime {
+    t.duration_since(UNIX_EPOCH)
+        .map(|d| UNIX_EPOCH + Duration::from_secs(d.as_secs()))
+        .unwrap_or(t)
+}
+
+fn mime_for(id: &str) -> &'static str {
+    let ext = id
+        .rsplit('.')
+        .next()
+        .map(|s| s.to_ascii_lowercase())
+        .unwrap_or_default();
+    match ext.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "webp" => "image/webp",
+        "gif" => "image/gif",
+        "bmp" => "image/bmp",
+        "mp4" | "m4v" => "video/mp4",
+        "webm" => "video/webm",
+        "mov" => "video/quicktime",
+        "ogv" => "video/ogg",
+        "mp3" => "audio/mpeg",
+        "m4a" => "audio/mp4",
+        "wav" => "audio/wav",
+        "ogg" | "oga" => "audio/ogg",
+        _ => "application/octet-stream",
+    }
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response<Vec<u8>> {
+    let mut response = Response::new(message.as_bytes().to_vec());
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static("text/plain; charset=utf-8"),
+    );
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn if_range(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_RANGE, value.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn parse_range_suffix() {
+        assert_eq!(
+            parse_range("bytes=-100", 1000),
+            ByteRange::Partial(900, 999)
+        );
+        // 后缀比文件还长时返回整个文件
+        assert_eq!(parse_range("bytes=-5000", 1000), ByteRange::Partial(0, 999));
+        assert_eq!(parse_range("bytes=-0", 1000), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range("bytes=-10", 0), ByteRange::Unsatisfiable);
+    }
+
+    #[test]
+    fn parse_range_start_past_end() {
+        assert_eq!(parse_range("bytes=1000-", 1000), ByteRange::Unsatisfiable);
+        assert_eq!(
+            parse_range("bytes=2000-3000", 1000),
+            ByteRange::Unsatisfiable
+        );
+        assert_eq!(parse_range("bytes=0-", 0), ByteRange::Unsatisfiable);
+    }
+
+    #[test]
+    fn parse_range_end_before_start() {
+        assert_eq!(parse_range("bytes=500-100", 1000), ByteRange::Full);
+    }
+
+    #[test]
+    fn parse_range_open_ended() {
+        assert_eq!(
+            parse_range("bytes=100-", 1000),
+            ByteRange::Partial(100, 999)
+        );
+        assert_eq!(parse_range("bytes=0-", 1), ByteRange::Partial(0, 0));
+    }
+
+    #[test]
+    fn parse_range_bounded() {
+        assert_eq!(parse_range("bytes=0-99", 1000), ByteRange::Partial(0, 99));
+        // 结束位置超出文件时截到最后一个字节
+        assert_eq!(
+            parse_range("bytes=900-5000", 1000),
+            ByteRange::Partial(900, 999)
+        );
+    }
+
+    #[test]
+    fn parse_range_falls_back_to_full() {
+        assert_eq!(parse_range("bytes=0-1,5-9", 1000), ByteRange::Full);
+        assert_eq!(parse_range("items=0-1", 1000), ByteRange::Full);
+        assert_eq!(parse_range("bytes=abc", 1000), ByteRange::Full);
+        assert_eq!(parse_range("bytes=x-10", 1000), ByteRange::Full);
+    }
+
+    #[test]
+    fn chunk_len_clamps_to_max_chunk() {
+        assert_eq!(chunk_len(0, 99), 100);
+        assert_eq!(chunk_len(0, MAX_RANGE_CHUNK - 1), MAX_RANGE_CHUNK);
+        assert_eq!(chunk_len(0, MAX_RANGE_CHUNK * 10), MAX_RANGE_CHUNK);
+        assert_eq!(chunk_len(10, 10), 1);
+    }
+
+    #[test]
+    fn if_range_without_header_matches() {
+        assert!(if_range_matches(&HeaderMap::new(), "\"abc\"", None));
+    }
+
+    #[test]
+    fn if_range_etag() {
+        assert!(if_range_matches(&if_range("\"abc\""), "\"abc\"", None));
+        assert!(!if_range_matches(&if_range("\"old\""), "\"abc\"", None));
+        assert!(!if_range_matches(&if_range("W/\"abc\""), "\"abc\"", None));
+    }
+
+    #[test]
+    fn if_range_last_modified() {
+        let modified = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let date = httpdate::fmt_http_date(modified);
+        assert!(if_range_matches(
+            &if_range(&date),
+            "\"abc\"",
+            Some(modified)
+        ));
+
+        let stale = httpdate::fmt_http_date(modified - Duration::from_secs(60));
+        assert!(!if_range_matches(
+            &if_range(&stale),
+            "\"abc\"",
+            Some(modified)
+        ));
+        assert!(!if_range_matches(&if_range(&date), "\"abc\"", None));
+        assert!(!if_range_matches(
+            &if_range("not a date"),
+            "\"abc\"",
+            Some(modified)
+        ));
+    }
+
+    #[test]
+    fn partial_response_headers() {
+        let modified = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let response = partial_response(
+            "videos/a.mp4",
+            100,
+            vec![0; 50],
+            1000,
+            "\"abc\"",
+            Some(modified),
+        );
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        let headers = response.headers();
+        assert_eq!(headers[header::CONTENT_RANGE], "bytes 100-149/1000");
+        assert_eq!(headers[header::CONTENT_TYPE], "video/mp4");
+        assert_eq!(headers[header::ETAG], "\"abc\"");
+        assert_eq!(
+            headers[header::LAST_MODIFIED],
+            httpdate::fmt_http_date(modified).as_str()
+        );
+        assert_eq!(response.body().len(), 50);
+    }
+
+    #[test]
+    fn served_only_media_under_roots() {
+        assert!(is_served("storage/2024/a.png"));
+        assert!(is_served("ref_images/b.JPG"));
+        assert!(is_served("cache/nbimage/c.webp"));
+        assert!(is_served("storage/clip.mp4"));
+        assert!(!is_served("key-slots.json"));
+        assert!(!is_served("settings.json"));
+        assert!(!is_served("data.db"));
+        assert!(!is_served("storage/notes.json"));
+        assert!(!is_served("storage/logo.svg"));
+        assert!(!is_served("storage"));
+        assert!(!is_served("storage2/a.png"));
+        assert!(!is_served("downloads/task.part"));
+    }
+
+    #[test]
+    fn unsatisfiable_response_headers() {
+        let response = unsatisfiable_response(1000);
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes */1000");
+        assert!(response.body().is_empty());
+    }
+}
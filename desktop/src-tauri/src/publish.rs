@@ -0,0 +1,326 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use rusqlite::{params, Connection};
+use tauri::{Emitter, Manager};
+
+use crate::library::{db_timestamp, Library, TaskRecord};
+use crate::resize::{OutputFormat, ResizeParams};
+use crate::settings::PublishSettings;
+use crate::storage::{normalize_id, write_atomic, Storage};
+
+// 发布目录：收藏或带指定标签的结果按设置的格式/尺寸导出到用户选的目录（如 Dropbox 同步目录）。
+// 由原生层的后台线程定时扫描，窗口关到托盘后照样工作；改元数据、改设置后立即扫一次。
+// 已发布的记录按 (task_id, 目录) 存在 publish_log 表，换目录后会重新发布；刚启用时已有的收藏也会补发
+pub const EVENT: &str = "image-published";
+const SCAN_INTERVAL: Duration = Duration::from_secs(30);
+const MAX_DIMENSION: u32 = 8192;
+// 同名文件追加 -2、-3 ... 的上限
+const MAX_NAME_TRIES: usize = 100;
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct PublishedItem {
+    pub task_id: String,
+    pub path: String,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct PublishFailure {
+    pub task_id: String,
+    pub error: String,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize)]
+pub struct PublishReport {
+    pub published: Vec<PublishedItem>,
+    pub failed: Vec<PublishFailure>,
+}
+
+#[derive(Default)]
+pub struct Publisher {
+    wake: (Mutex<bool>, Condvar),
+    // 同一时间只跑一轮
+    running: Mutex<()>,
+    // 本次运行中导出失败的 (task_id, 目录)，不再每轮重试刷日志；重启应用或 publish_now 会再试
+    failed: Mutex<HashSet<(String, String)>>,
+}
+
+pub struct PublishState(pub Publisher);
+
+impl Publisher {
+    // 让后台线程尽快扫描一次
+    pub fn notify(&self) {
+        let (lock, cvar) = &self.wake;
+        if let Ok(mut pending) = lock.lock() {
+            *pending = true;
+            cvar.notify_all();
+        }
+    }
+
+    fn wait(&self, timeout: Duration) {
+        let (lock, cvar) = &self.wake;
+        let Ok(guard) = lock.lock() else {
+            thread::sleep(timeout);
+            return;
+        };
+        if let Ok((mut pending, _)) = cvar.wait_timeout_while(guard, timeout, |pending| !*pending) {
+            *pending = false;
+        }
+    }
+}
+
+pub fn validate(settings: &PublishSettings) -> Result<(), String> {
+    if settings.enabled {
+        let folder = settings.folder.trim();
+        if folder.is_empty() {
+            return Err("publish.folder is empty".to_string());
+        }
+        if !Path::new(folder).is_absolute() {
+            return Err("publish.folder must be an absolute path".to_string());
+        }
+    }
+    if let Some(format) = &settings.format {
+        OutputFormat::parse(format)
+            .ok_or_else(|| format!("publish.format is not supported: {}", format))?;
+    }
+    if let Some(max) = settings.max_dimension {
+        if max == 0 || max > MAX_DIMENSION {
+            return Err(format!(
+                "publish.max_dimension must be between 1 and {}",
+                MAX_DIMENSION
+            ));
+        }
+    }
+    if let Some(quality) = settings.quality {
+        if !(1..=100).contains(&quality) {
+            return Err("publish.quality must be between 1 and 100".to_string());
+        }
+    }
+    Ok(())
+}
+
+fn ensure_log(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS publish_log (\
+             task_id TEXT NOT NULL, \
+             folder TEXT NOT NULL, \
+             path TEXT NOT NULL DEFAULT '', \
+             published_at TEXT NOT NULL DEFAULT '', \
+             PRIMARY KEY (task_id, folder))",
+    )
+    .map_err(|e| format!("init publish_log failed: {}", e))
+}
+
+fn published_ids(conn: &Connection, folder: &str) -> Result<HashSet<String>, String> {
+    let mut stmt = conn
+        .prepare("SELECT task_id FROM publish_log WHERE folder = ?1")
+        .map_err(|e| format!("prepare publish_log failed: {}", e))?;
+    let rows = stmt
+        .query_map(params![folder], |row| row.get::<_, String>(0))
+        .map_err(|e| format!("query publish_log failed: {}", e))?;
+    rows.collect::<Result<HashSet<_>, _>>()
+        .map_err(|e| format!("read publish_log failed: {}", e))
+}
+
+// 收藏或带有发布标签、已完成且有本地文件、还没发布到该目录的任务
+fn candidates(
+    library: &Library,
+    conn: &Connection,
+    settings: &PublishSettings,
+    folder: &str,
+) -> Result<Vec<TaskRecord>, String> {
+    let tags: HashSet<String> = settings
+        .tags
+        .iter()
+        .map(|t| t.trim().to_lowercase())
+        .filter(|t| !t.is_empty())
+        .collect();
+    let published = published_ids(conn, folder)?;
+    let mut ids: Vec<String> = library
+        .all_meta(conn)?
+        .into_iter()
+        .filter(|(id, meta)| {
+            !published.contains(id)
+                && (meta.favorite || meta.tags.iter().any(|t| tags.contains(&t.to_lowercase())))
+        })
+        .map(|(id, _)| id)
+        .collect();
+    if ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    ids.sort();
+    // image_meta 里可能还留着已删除任务的元数据，查不到的直接跳过
+    let mut tasks = Vec::new();
+    for id in &ids {
+        if let Some(task) = library.get_task(conn, id)? {
+            if task.status == "completed" && !task.local_path.is_empty() {
+                tasks.push(task);
+            }
+        }
+    }
+    Ok(tasks)
+}
+
+// 目标文件名沿用主图文件名；重名时追加序号，不覆盖目录里已有的文件
+pub fn target_path(folder: &Path, stem: &str, ext: &str) -> Result<PathBuf, String> {
+    for n in 1..=MAX_NAME_TRIES {
+        let name = match n {
+            1 => format!("{}.{}", stem, ext),
+            _ => format!("{}-{}.{}", stem, n, ext),
+        };
+        let path = folder.join(name);
+        if !path.exists() {
+            return Ok(path);
+        }
+    }
+    Err(format!("no free file name for {} in publish folder", stem))
+}
+
+fn export_one(
+    storage: &dyn Storage,
+    settings: &PublishSettings,
+    folder: &Path,
+    task: &TaskRecord,
+) -> Result<PathBuf, String> {
+    let id = normalize_id(&task.local_path)?;
+    let source = storage.get(&id)?;
+    let original = OutputFormat::from_id(&id);
+    let format = settings
+        .format
+        .as_deref()
+        .and_then(OutputFormat::parse)
+        .unwrap_or(original);
+    // 不换格式也不缩放时原样复制，保留原图的元数据
+    let (bytes, ext) = if format == original && settings.max_dimension.is_none() {
+        let ext = Path::new(&id)
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or(original.ext())
+            .to_ascii_lowercase();
+        (source, ext)
+    } else {
+        let params = ResizeParams {
+            width: settings.max_dimension,
+            height: settings.max_dimension,
+            format: Some(format),
+            quality: settings.quality,
+        };
+        (
+            crate::resize::transform(&source, &params, format)?,
+            format.ext().to_string(),
+        )
+    };
+    let stem = Path::new(&id)
+        .file_stem()
+        .map(|s| crate::naming::sanitize_stem(&s.to_string_lossy()))
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| crate::naming::sanitize_stem(&task.task_id));
+    let path = target_path(folder, &stem, &ext)?;
+    write_atomic(&path, &bytes)?;
+    Ok(path)
+}
+
+// 扫描一轮；retry_failed 为 true 时连之前失败的也重试
+pub fn run(app: &tauri::AppHandle, retry_failed: bool) -> Result<PublishReport, String> {
+    let state = app.state::<PublishState>();
+    let publisher = &state.0;
+    let Ok(_guard) = publisher.running.lock() else {
+        return Err("publish failed: lock poisoned".to_string());
+    };
+    let settings = app.state::<crate::SettingsState>().0.get().publish;
+    let mut report = PublishReport::default();
+    if !settings.enabled {
+        return Ok(report);
+    }
+    let folder_key = settings.folder.trim().to_string();
+    let folder = PathBuf::from(&folder_key);
+    std::fs::create_dir_all(&folder)
+        .map_err(|e| format!("create publish folder failed: {} ({})", e, folder.display()))?;
+
+    let library = &app.state::<crate::LibraryState>().0;
+    let conn = library.open()?;
+    ensure_log(&conn)?;
+    if retry_failed {
+        if let Ok(mut failed) = publisher.failed.lock() {
+            failed.clear();
+        }
+    }
+    let storage = app.state::<crate::StorageState>().0.clone();
+    let log_state = app.state::<crate::LogState>();
+    for task in candidates(library, &conn, &settings, &folder_key)? {
+        let key = (task.task_id.clone(), folder_key.clone());
+        if publisher
+            .failed
+            .lock()
+            .map(|failed| failed.contains(&key))
+            .unwrap_or(false)
+        {
+            continue;
+        }
+        let exported = export_one(storage.as_ref(), &settings, &folder, &task).and_then(|path| {
+            conn.execute(
+                "INSERT OR REPLACE INTO publish_log (task_id, folder, path, published_at) \
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    task.task_id,
+                    folder_key,
+                    path.to_string_lossy(),
+                    db_timestamp()
+                ],
+            )
+            .map_err(|e| format!("record publish failed: {}", e))?;
+            Ok(path)
+        });
+        match exported {
+            Ok(path) => {
+                let item = PublishedItem {
+                    task_id: task.task_id.clone(),
+                    path: path.to_string_lossy().to_string(),
+                };
+                log_state.log_app(
+                    "INFO",
+                    &format!("published {} to {}", item.task_id, item.path),
+                );
+                let _ = app.emit(EVENT, item.clone());
+                report.published.push(item);
+            }
+            Err(error) => {
+                log_state.log_app(
+                    "WARN",
+                    &format!("publish {} failed: {}", task.task_id, error),
+                );
+                if let Ok(mut failed) = publisher.failed.lock() {
+                    failed.insert(key);
+                }
+                report.failed.push(PublishFailure {
+                    task_id: task.task_id,
+                    error,
+                });
+            }
+        }
+    }
+    Ok(report)
+}
+
+pub fn start(app: tauri::AppHandle) {
+    thread::spawn(move || {
+        // 同样的错误（如历史库还没建好）只记一次
+        let mut last_error: Option<String> = None;
+        loop {
+            app.state::<PublishState>().0.wait(SCAN_INTERVAL);
+            match run(&app, false) {
+                Ok(_) => last_error = None,
+                Err(e) => {
+                    if last_error.as_deref() != Some(e.as_str()) {
+                        app.state::<crate::LogState>()
+                            .log_app("WARN", &format!("publish scan failed: {}", e));
+                    }
+                    last_error = Some(e);
+                }
+            }
+        }
+    });
+}
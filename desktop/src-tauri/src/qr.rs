@@ -0,0 +1,31 @@
+use image::{GrayImage, Luma};
+use qrcode::{Color, EcLevel, QrCode};
+
+const QUIET_ZONE: u32 = 4;
+const MAX_DATA_CHARS: usize = 1024;
+
+// 黑白二维码，四周留标准的 4 模块静区；module_px 为每个模块的像素边长
+pub fn render(data: &str, module_px: u32) -> Result<GrayImage, String> {
+    let data = data.trim();
+    if data.is_empty() {
+        return Err("qr data is empty".to_string());
+    }
+    if data.chars().count() > MAX_DATA_CHARS {
+        return Err("qr data too long".to_string());
+    }
+    let code = QrCode::with_error_correction_level(data.as_bytes(), EcLevel::M)
+        .map_err(|e| format!("encode qr failed: {}", e))?;
+    let width = code.width() as u32;
+    let module_px = module_px.clamp(1, 64);
+    let side = (width + QUIET_ZONE * 2) * module_px;
+    let colors = code.to_colors();
+    Ok(GrayImage::from_fn(side, side, |x, y| {
+        let (mx, my) = (x / module_px, y / module_px);
+        let dark = mx >= QUIET_ZONE
+            && my >= QUIET_ZONE
+            && mx < width + QUIET_ZONE
+            && my < width + QUIET_ZONE
+            && colors[((my - QUIET_ZONE) * width + (mx - QUIET_ZONE)) as usize] == Color::Dark;
+        Luma([if dark { 0 } else { 255 }])
+    }))
+}
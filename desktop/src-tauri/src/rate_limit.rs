@@ -0,0 +1,124 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use tauri::ipc::Invoke;
+use tauri::{Manager, Runtime};
+
+// 会触达系统 UI 的命令按滑动窗口限流：剪贴板读写、读屏播报、原生确认对话框。
+// 在 invoke_handler 外层拦截，命令本身不用改；前端异常循环或被注入脚本时，
+// 不能靠高频调用刷屏、连环弹窗或借剪贴板快速倒腾数据。同一限流组共用一个窗口
+struct Limit {
+    group: &'static str,
+    max: usize,
+    window: Duration,
+}
+
+const fn limit(group: &'static str, max: usize, secs: u64) -> Limit {
+    Limit {
+        group,
+        max,
+        window: Duration::from_secs(secs),
+    }
+}
+
+const CLIPBOARD_WRITE: Limit = limit("clipboard_write", 20, 10);
+const CLIPBOARD_READ: Limit = limit("clipboard_read", 10, 10);
+const ANNOUNCE: Limit = limit("announce", 30, 10);
+// 这些命令在带密钥时会弹原生确认框
+const SECRET_DIALOG: Limit = limit("secret_dialog", 3, 30);
+
+fn limit_for(command: &str) -> Option<&'static Limit> {
+    match command {
+        "copy_image_to_clipboard" | "copy_text_to_clipboard" | "copy_metadata_to_clipboard" => {
+            Some(&CLIPBOARD_WRITE)
+        }
+        "read_image_from_clipboard" | "get_clipboard_history" => Some(&CLIPBOARD_READ),
+        "announce" => Some(&ANNOUNCE),
+        "export_settings" | "import_settings" | "export_with_credentials" => Some(&SECRET_DIALOG),
+        _ => None,
+    }
+}
+
+#[derive(Default)]
+struct Bucket {
+    calls: VecDeque<Instant>,
+    // 同一窗口内只记一次日志，避免被刷爆的是日志文件
+    logged: bool,
+}
+
+#[derive(Default)]
+pub struct RateLimiter {
+    buckets: Mutex<HashMap<&'static str, Bucket>>,
+}
+
+pub struct RateLimitState(pub RateLimiter);
+
+impl RateLimiter {
+    // Ok(true) 放行；Err 为拒绝且需要写日志；Ok(false) 为拒绝但本轮已记过日志
+    fn check(&self, command: &str) -> Result<bool, String> {
+        let Some(limit) = limit_for(command) else {
+            return Ok(true);
+        };
+        let now = Instant::now();
+        let mut buckets = match self.buckets.lock() {
+            Ok(b) => b,
+            Err(_) => return Ok(true),
+        };
+        let bucket = buckets.entry(limit.group).or_default();
+        while bucket
+            .calls
+            .front()
+            .is_some_and(|at| now.duration_since(*at) >= limit.window)
+        {
+            bucket.calls.pop_front();
+        }
+        if bucket.calls.len() < limit.max {
+            bucket.calls.push_back(now);
+            bucket.logged = false;
+            return Ok(true);
+        }
+        if bucket.logged {
+            return Ok(false);
+        }
+        bucket.logged = true;
+        Err(format!(
+            "rate limit exceeded for {} ({}): max {} calls per {}s",
+            command,
+            limit.group,
+            limit.max,
+            limit.window.as_secs()
+        ))
+    }
+}
+
+pub fn wrap<R, F>(handler: F) -> impl Fn(Invoke<R>) -> bool + Send + Sync + 'static
+where
+    R: Runtime,
+    F: Fn(Invoke<R>) -> bool + Send + Sync + 'static,
+{
+    move |invoke: Invoke<R>| {
+        let command = invoke.message.command().to_string();
+        let webview = invoke.message.webview();
+        let allowed = match webview.try_state::<RateLimitState>() {
+            Some(state) => match state.0.check(&command) {
+                Ok(allowed) => allowed,
+                Err(message) => {
+                    if let Some(log) = webview.try_state::<crate::LogState>() {
+                        log.log_app("WARN", &message);
+                    }
+                    false
+                }
+            },
+            None => true,
+        };
+        if allowed {
+            handler(invoke)
+        } else {
+            invoke
+                .resolver
+                .reject(format!("{} rejected: too many calls", command));
+            true
+        }
+    }
+}
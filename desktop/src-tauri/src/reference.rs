@@ -0,0 +1,400 @@
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, ImageDecoder, ImageFormat, ImageReader, RgbImage};
+
+use crate::resize::OutputFormat;
+
+// 参考图上传前的校验与规范化：格式、体积、尺寸、宽高比不符合模型 API 限制时，
+// 能修的（超大、格式不支持、EXIF 旋转）在本地缩放/转码，修不了的（过小、比例过于极端）直接给出明确错误，
+// 不再等到上传后才收到一个看不出原因的 400。默认值为 Gemini 内联图片的限制，前端可按模型覆盖
+const DEFAULT_MAX_BYTES: u64 = 7 * 1024 * 1024;
+const DEFAULT_MAX_DIMENSION: u32 = 4096;
+const DEFAULT_MIN_DIMENSION: u32 = 32;
+// 读入的原图上限，防止误选超大文件把内存撑爆
+const MAX_INPUT_BYTES: usize = 200 * 1024 * 1024;
+const JPEG_QUALITIES: [u8; 4] = [90, 82, 74, 66];
+const MAX_SHRINK_STEPS: u32 = 6;
+const TEMP_DIR: &str = "nb-upload";
+// 临时文件只用于紧接着的一次上传，每次调用顺带清理一天前的
+const TEMP_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Clone, Debug, Default, serde::Deserialize)]
+#[serde(default)]
+pub struct UploadConstraints {
+    // 接受的格式：png / jpeg / webp，为空表示三者都可以
+    pub formats: Vec<String>,
+    pub max_bytes: Option<u64>,
+    // 最长边
+    pub max_dimension: Option<u32>,
+    // 最短边
+    pub min_dimension: Option<u32>,
+    // 宽 / 高
+    pub min_aspect: Option<f64>,
+    pub max_aspect: Option<f64>,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct ImageSummary {
+    pub format: String,
+    pub width: u32,
+    pub height: u32,
+    pub bytes: u64,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct PreparedReference {
+    // 可直接上传的临时文件
+    pub path: String,
+    pub mime: String,
+    pub original: ImageSummary,
+    pub result: ImageSummary,
+    // 做过的修改，前端可原样展示；为空表示原样上传
+    pub changes: Vec<String>,
+}
+
+struct Limits {
+    formats: Vec<OutputFormat>,
+    max_bytes: u64,
+    max_dimension: u32,
+    min_dimension: u32,
+    min_aspect: Option<f64>,
+    max_aspect: Option<f64>,
+}
+
+impl UploadConstraints {
+    fn resolve(&self) -> Result<Limits, String> {
+        let mut formats = Vec::new();
+        for name in &self.formats {
+            let format = OutputFormat::parse(name)
+                .ok_or_else(|| format!("unsupported format in constraints: {}", name))?;
+            if !formats.contains(&format) {
+                formats.push(format);
+            }
+        }
+        if formats.is_empty() {
+            formats = vec![OutputFormat::Png, OutputFormat::Jpeg, OutputFormat::Webp];
+        }
+        let max_bytes = self.max_bytes.unwrap_or(DEFAULT_MAX_BYTES);
+        if max_bytes < 16 * 1024 {
+            return Err("max_bytes must be at least 16KB".to_string());
+        }
+        let max_dimension = self.max_dimension.unwrap_or(DEFAULT_MAX_DIMENSION);
+        let min_dimension = self.min_dimension.unwrap_or(DEFAULT_MIN_DIMENSION);
+        if max_dimension == 0 || min_dimension > max_dimension {
+            return Err("invalid dimension constraints".to_string());
+        }
+        for aspect in [self.min_aspect, self.max_aspect].into_iter().flatten() {
+            if !aspect.is_finite() || aspect <= 0.0 {
+                return Err(format!("invalid aspect constraint: {}", aspect));
+            }
+        }
+        if let (Some(min), Some(max)) = (self.min_aspect, self.max_aspect) {
+            if min > max {
+                return Err("min_aspect is greater than max_aspect".to_string());
+            }
+        }
+        Ok(Limits {
+            formats,
+            max_bytes,
+            max_dimension,
+            min_dimension,
+            min_aspect: self.min_aspect,
+            max_aspect: self.max_aspect,
+        })
+    }
+}
+
+fn format_name(format: OutputFormat) -> &'static str {
+    match format {
+        OutputFormat::Png => "png",
+        OutputFormat::Jpeg => "jpeg",
+        OutputFormat::Webp => "webp",
+    }
+}
+
+fn detect(bytes: &[u8]) -> Result<OutputFormat, String> {
+    match image::guess_format(bytes) {
+        Ok(ImageFormat::Png) => Ok(OutputFormat::Png),
+        Ok(ImageFormat::Jpeg) => Ok(OutputFormat::Jpeg),
+        Ok(ImageFormat::WebP) => Ok(OutputFormat::Webp),
+        Ok(other) => Err(format!(
+            "unsupported image format: {}",
+            other.extensions_str().first().copied().unwrap_or("unknown")
+        )),
+        Err(_) => Err("file is not a recognized image".to_string()),
+    }
+}
+
+// 解码并按 EXIF 方向摆正；API 端不一定理会 EXIF，转码后方向信息也会丢失
+fn decode(bytes: &[u8]) -> Result<(DynamicImage, bool), String> {
+    let mut decoder = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .map_err(|e| format!("read image failed: {}", e))?
+        .into_decoder()
+        .map_err(|e| format!("decode image failed: {}", e))?;
+    let orientation = decoder
+        .orientation()
+        .unwrap_or(image::metadata::Orientation::NoTransforms);
+    let mut img =
+        DynamicImage::from_decoder(decoder).map_err(|e| format!("decode image failed: {}", e))?;
+    let rotated = orientation != image::metadata::Orientation::NoTransforms;
+    if rotated {
+        img.apply_orientation(orientation);
+    }
+    Ok((img, rotated))
+}
+
+// JPEG 不支持透明，透明区域铺白底，避免直接丢 alpha 变成黑底
+fn flatten(img: &DynamicImage) -> RgbImage {
+    let rgba = img.to_rgba8();
+    let mut out = RgbImage::new(rgba.width(), rgba.height());
+    for (src, dst) in rgba.pixels().zip(out.pixels_mut()) {
+        let alpha = src[3] as u32;
+        for c in 0..3 {
+            dst[c] = ((src[c] as u32 * alpha + 255 * (255 - alpha)) / 255) as u8;
+        }
+    }
+    out
+}
+
+fn encode(img: &DynamicImage, format: OutputFormat, quality: u8) -> Result<Vec<u8>, String> {
+    let mut out = Cursor::new(Vec::new());
+    match format {
+        OutputFormat::Jpeg => {
+            let encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut out, quality);
+            flatten(img)
+                .write_with_encoder(encoder)
+                .map_err(|e| format!("encode jpeg failed: {}", e))?;
+        }
+        OutputFormat::Png => {
+            img.write_to(&mut out, ImageFormat::Png)
+                .map_err(|e| format!("encode png failed: {}", e))?;
+        }
+        OutputFormat::Webp => {
+            img.to_rgba8()
+                .write_with_encoder(image::codecs::webp::WebPEncoder::new_lossless(&mut out))
+                .map_err(|e| format!("encode webp failed: {}", e))?;
+        }
+    }
+    Ok(out.into_inner())
+}
+
+fn check_shape(width: u32, height: u32, limits: &Limits) -> Result<(), String> {
+    if width.min(height) < limits.min_dimension {
+        return Err(format!(
+            "image too small: {}x{} (shortest side must be at least {}px)",
+            width, height, limits.min_dimension
+        ));
+    }
+    let aspect = width as f64 / height as f64;
+    if limits.min_aspect.is_some_and(|min| aspect < min)
+        || limits.max_aspect.is_some_and(|max| aspect > max)
+    {
+        return Err(format!(
+            "aspect ratio {:.2} ({}x{}) outside allowed range {}–{}",
+            aspect,
+            width,
+            height,
+            limits
+                .min_aspect
+                .map(|a| format!("{:.2}", a))
+                .unwrap_or_else(|| "0".to_string()),
+            limits
+                .max_aspect
+                .map(|a| format!("{:.2}", a))
+                .unwrap_or_else(|| "∞".to_string()),
+        ));
+    }
+    Ok(())
+}
+
+fn fit_longest(img: DynamicImage, longest: u32) -> DynamicImage {
+    if img.width().max(img.height()) <= longest {
+        return img;
+    }
+    img.resize(longest, longest, FilterType::Lanczos3)
+}
+
+fn kb(bytes: u64) -> String {
+    format!("{}KB", bytes.div_ceil(1024))
+}
+
+// 体积超限时的兜底：能用 JPEG 就逐级降低质量，仍超限再每次缩小 20%
+fn shrink_to_fit(
+    img: DynamicImage,
+    mut format: OutputFormat,
+    limits: &Limits,
+    changes: &mut Vec<String>,
+) -> Result<(DynamicImage, OutputFormat, Vec<u8>), String> {
+    if format != OutputFormat::Jpeg && limits.formats.contains(&OutputFormat::Jpeg) {
+        changes.push(format!(
+            "re-encoded {} as jpeg to fit {}",
+            format_name(format),
+            kb(limits.max_bytes)
+        ));
+        format = OutputFormat::Jpeg;
+    }
+    let qualities: &[u8] = if format == OutputFormat::Jpeg {
+        &JPEG_QUALITIES
+    } else {
+        &JPEG_QUALITIES[..1]
+    };
+    let mut img = img;
+    for step in 0..=MAX_SHRINK_STEPS {
+        if step > 0 {
+            let longest = (img.width().max(img.height()) as f64 * 0.8) as u32;
+            if longest < limits.min_dimension.max(1) {
+                break;
+            }
+            img = fit_longest(img, longest);
+        }
+        for &quality in qualities {
+            let bytes = encode(&img, format, quality)?;
+            if bytes.len() as u64 <= limits.max_bytes {
+                if format == OutputFormat::Jpeg && quality != JPEG_QUALITIES[0] {
+                    changes.push(format!("jpeg quality lowered to {}", quality));
+                }
+                if step > 0 {
+                    changes.push(format!(
+                        "downscaled to {}x{} to fit {}",
+                        img.width(),
+                        img.height(),
+                        kb(limits.max_bytes)
+                    ));
+                }
+                return Ok((img, format, bytes));
+            }
+        }
+    }
+    Err(format!(
+        "image cannot be reduced below {} within the allowed formats",
+        kb(limits.max_bytes)
+    ))
+}
+
+fn temp_dir() -> PathBuf {
+    std::env::temp_dir().join(TEMP_DIR)
+}
+
+fn cleanup_temp(dir: &Path) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let now = SystemTime::now();
+    for entry in entries.flatten() {
+        let expired = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| now.duration_since(t).ok())
+            .is_some_and(|age| age > TEMP_TTL);
+        if expired {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
+
+pub fn prepare(
+    bytes: &[u8],
+    stem: &str,
+    constraints: &UploadConstraints,
+) -> Result<PreparedReference, String> {
+    let limits = constraints.resolve()?;
+    if bytes.len() > MAX_INPUT_BYTES {
+        return Err(format!(
+            "image too large to process: {} (max {})",
+            kb(bytes.len() as u64),
+            kb(MAX_INPUT_BYTES as u64)
+        ));
+    }
+    let source_format = detect(bytes)?;
+    let (img, rotated) = decode(bytes)?;
+    let (width, height) = img.dimensions();
+    let original = ImageSummary {
+        format: format_name(source_format).to_string(),
+        width,
+        height,
+        bytes: bytes.len() as u64,
+    };
+    check_shape(width, height, &limits)?;
+
+    let mut changes = Vec::new();
+    if rotated {
+        changes.push("applied exif orientation".to_string());
+    }
+    let mut format = source_format;
+    if !limits.formats.contains(&format) {
+        // 有透明通道时优先保留透明
+        let prefer = if img.color().has_alpha() {
+            [OutputFormat::Png, OutputFormat::Webp, OutputFormat::Jpeg]
+        } else {
+            [OutputFormat::Jpeg, OutputFormat::Png, OutputFormat::Webp]
+        };
+        format = prefer
+            .into_iter()
+            .find(|f| limits.formats.contains(f))
+            .unwrap_or(limits.formats[0]);
+        changes.push(format!(
+            "converted {} to {}",
+            format_name(source_format),
+            format_name(format)
+        ));
+    }
+    let resized = width.max(height) > limits.max_dimension;
+    let img = fit_longest(img, limits.max_dimension);
+    if resized {
+        changes.push(format!(
+            "downscaled {}x{} to {}x{}",
+            width,
+            height,
+            img.width(),
+            img.height()
+        ));
+    }
+
+    let (img, format, output) = if changes.is_empty() && original.bytes <= limits.max_bytes {
+        (img, format, bytes.to_vec())
+    } else {
+        let encoded = encode(&img, format, JPEG_QUALITIES[0])?;
+        if encoded.len() as u64 <= limits.max_bytes {
+            (img, format, encoded)
+        } else {
+            if changes.is_empty() {
+                changes.push(format!(
+                    "file exceeds {} ({})",
+                    kb(limits.max_bytes),
+                    kb(original.bytes)
+                ));
+            }
+            shrink_to_fit(img, format, &limits, &mut changes)?
+        }
+    };
+
+    let dir = temp_dir();
+    fs::create_dir_all(&dir).map_err(|e| format!("create upload dir failed: {}", e))?;
+    cleanup_temp(&dir);
+    let path = dir.join(format!(
+        "{}-{}.{}",
+        if stem.is_empty() { "reference" } else { stem },
+        crate::now_ms(),
+        format.ext()
+    ));
+    fs::write(&path, &output).map_err(|e| format!("write upload file failed: {}", e))?;
+
+    Ok(PreparedReference {
+        path: path.to_string_lossy().to_string(),
+        mime: format.mime().to_string(),
+        original,
+        result: ImageSummary {
+            format: format_name(format).to_string(),
+            width: img.width(),
+            height: img.height(),
+            bytes: output.len() as u64,
+        },
+        changes,
+    })
+}
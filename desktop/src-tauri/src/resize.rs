@@ -0,0 +1,432 @@
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat};
+use sha2::{Digest, Sha256};
+
+use crate::storage::{Storage, StorageMeta};
+
+// 防御：避免前端请求超大尺寸导致内存暴涨
+const MAX_DIMENSION: u32 = 8192;
+const DEFAULT_QUALITY: u8 = 85;
+// 缓存目录上限，超出后按最近访问时间从旧到新删除
+const MAX_CACHE_BYTES: u64 = 512 * 1024 * 1024;
+// 每写入这么多字节扫描一次目录，不在每次写入时都遍历
+const TRIM_INTERVAL_BYTES: u64 = 32 * 1024 * 1024;
+
+// 临时文件名序号，同一进程内的并发请求各用各的临时文件
+static TMP_SEQ: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum OutputFormat {
+    Png,
+    Jpeg,
+    Webp,
+}
+
+impl OutputFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "png" => Some(Self::Png),
+            "jpg" | "jpeg" => Some(Self::Jpeg),
+            "webp" => Some(Self::Webp),
+            _ => None,
+        }
+    }
+
+    // 未指定 fmt 时沿用原图格式，无法识别的格式统一转 PNG
+    pub fn from_id(id: &str) -> Self {
+        match ImageFormat::from_path(id) {
+            Ok(ImageFormat::Jpeg) => Self::Jpeg,
+            Ok(ImageFormat::WebP) => Self::Webp,
+            _ => Self::Png,
+        }
+    }
+
+    pub fn ext(self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Jpeg => "jpg",
+            Self::Webp => "webp",
+        }
+    }
+
+    pub fn mime(self) -> &'static str {
+        match self {
+            Self::Png => "image/png",
+            Self::Jpeg => "image/jpeg",
+            Self::Webp => "image/webp",
+        }
+    }
+}
+
+// nbimage 协议的变换参数：?w=512&h=512&fmt=webp&q=80
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ResizeParams {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub format: Option<OutputFormat>,
+    pub quality: Option<u8>,
+}
+
+impl ResizeParams {
+    // 没有任何变换参数时返回 None，协议层直接返回原图
+    pub fn parse(query: Option<&str>) -> Result<Option<Self>, String> {
+        let Some(query) = query else {
+            return Ok(None);
+        };
+
+        let mut params = Self::default();
+        for pair in query.split('&').filter(|p| !p.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            match key {
+                "w" => params.width = Some(parse_dimension(key, value)?),
+                "h" => params.height = Some(parse_dimension(key, value)?),
+                "fmt" => {
+                    params.format = Some(
+                        OutputFormat::parse(value)
+                            .ok_or_else(|| format!("unsupported fmt: {}", value))?,
+                    )
+                }
+                "q" => {
+                    let quality = value
+                        .parse::<u8>()
+                        .ok()
+                        .filter(|q| (1..=100).contains(q))
+                        .ok_or_else(|| format!("invalid q: {}", value))?;
+                    params.quality = Some(quality);
+                }
+                // 其余参数（如前端的 cache-busting ?t=xxx）忽略
+                _ => {}
+            }
+        }
+
+        if params == Self::default() {
+            return Ok(None);
+        }
+        Ok(Some(params))
+    }
+}
+
+fn parse_dimension(key: &str, value: &str) -> Result<u32, String> {
+    value
+        .parse::<u32>()
+        .ok()
+        .filter(|v| *v > 0 && *v <= MAX_DIMENSION)
+        .ok_or_else(|| format!("invalid {}: {}", key, value))
+}
+
+// 变换结果的磁盘缓存：key 由逻辑 ID + 参数 + 原图大小/修改时间组成，原图变化后自然失效；
+// 总大小超过 MAX_CACHE_BYTES 时按 LRU 清理，命中时刷新修改时间作为访问时间
+pub struct ResizeCache {
+    dir: PathBuf,
+    // 距上次清理写入的字节数，初始即达到阈值，启动后第一次写入时清理一次
+    written: AtomicU64,
+}
+
+impl ResizeCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            written: AtomicU64::new(TRIM_INTERVAL_BYTES),
+        }
+    }
+
+    pub fn render(
+        &self,
+        storage: &dyn Storage,
+        id: &str,
+        meta: StorageMeta,
+        params: &ResizeParams,
+    ) -> Result<(Vec<u8>, OutputFormat), String> {
+        let format = output_format(id, params);
+        let cache_path = self.dir.join(format!(
+            "{}.{}",
+            variant_key(id, meta, params),
+            format.ext()
+        ));
+
+        if let Ok(bytes) = fs::read(&cache_path) {
+            touch(&cache_path);
+            return Ok((bytes, format));
+        }
+
+        let source = storage.get(id)?;
+        let bytes = transform(&source, params, format)?;
+
+        // 先写临时文件再 rename，避免并发请求读到写了一半的缓存；临时文件名带进程号与序号，互不覆盖
+        if fs::create_dir_all(&self.dir).is_ok() {
+            let tmp = cache_path.with_extension(format!(
+                "{}.{}.tmp",
+                std::process::id(),
+                TMP_SEQ.fetch_add(1, Ordering::Relaxed)
+            ));
+            if fs::write(&tmp, &bytes).is_ok() {
+                if fs::rename(&tmp, &cache_path).is_err() {
+                    let _ = fs::remove_file(&tmp);
+                }
+            } else {
+                let _ = fs::remove_file(&tmp);
+            }
+            let written = self
+                .written
+                .fetch_add(bytes.len() as u64, Ordering::Relaxed)
+                + bytes.len() as u64;
+            if written >= TRIM_INTERVAL_BYTES {
+                self.written.store(0, Ordering::Relaxed);
+                trim(&self.dir, MAX_CACHE_BYTES);
+            }
+        }
+
+        Ok((bytes, format))
+    }
+}
+
+fn output_format(id: &str, params: &ResizeParams) -> OutputFormat {
+    params.format.unwrap_or_else(|| OutputFormat::from_id(id))
+}
+
+// 缩放变体的标识，缓存文件名与协议层的变体 ETag 共用
+pub fn variant_key(id: &str, meta: StorageMeta, params: &ResizeParams) -> String {
+    cache_key(id, meta, params, output_format(id, params))
+}
+
+// 文件名用 SHA-256，与 ETag 一致；DefaultHasher 的结果不保证跨 Rust 版本稳定，升级后缓存会整体失效
+fn cache_key(id: &str, meta: StorageMeta, params: &ResizeParams, format: OutputFormat) -> String {
+    let modified = meta
+        .modified
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_nanos().to_string())
+        .unwrap_or_default();
+    let field = |value: Option<u32>| value.map(|v| v.to_string()).unwrap_or_default();
+    let key = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}\n{}",
+        id,
+        meta.len,
+        modified,
+        field(params.width),
+        field(params.height),
+        field(params.quality.map(u32::from)),
+        format.ext()
+    );
+    hex::encode(&Sha256::digest(key.as_bytes())[..16])
+}
+
+fn touch(path: &Path) {
+    if let Ok(file) = fs::File::options().write(true).open(path) {
+        let _ = file.set_modified(SystemTime::now());
+    }
+}
+
+// 删除最久未访问的缓存文件直到总大小不超过 max；残留的临时文件按普通文件一起清理
+fn trim(dir: &Path, max: u64) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut files: Vec<(SystemTime, u64, PathBuf)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let meta = entry.metadata().ok()?;
+            meta.is_file().then(|| {
+                (
+                    meta.modified().unwrap_or(UNIX_EPOCH),
+                    meta.len(),
+                    entry.path(),
+                )
+            })
+        })
+        .collect();
+    let mut total: u64 = files.iter().map(|(_, len, _)| len).sum();
+    if total <= max {
+        return;
+    }
+    files.sort_by_key(|(modified, _, _)| *modified);
+    for (_, len, path) in files {
+        if total <= max {
+            break;
+        }
+        if fs::remove_file(&path).is_ok() {
+            total = total.saturating_sub(len);
+        }
+    }
+}
+
+pub fn transform(
+    source: &[u8],
+    params: &ResizeParams,
+    format: OutputFormat,
+) -> Result<Vec<u8>, String> {
+    let img = image::load_from_memory(source).map_err(|e| format!("decode image failed: {}", e))?;
+    encode(&shrink(img, params), format, params.quality)
+}
+
+// 只缩小不放大，等比缩放到 w/h 限定的框内
+pub fn shrink(img: DynamicImage, params: &ResizeParams) -> DynamicImage {
+    let max_w = params.width.unwrap_or(u32::MAX);
+    let max_h = params.height.unwrap_or(u32::MAX);
+    if img.width() > max_w || img.height() > max_h {
+        img.resize(max_w, max_h, FilterType::Triangle)
+    } else {
+        img
+    }
+}
+
+pub fn encode(
+    img: &DynamicImage,
+    format: OutputFormat,
+    quality: Option<u8>,
+) -> Result<Vec<u8>, String> {
+    let mut out = Cursor::new(Vec::new());
+    match format {
+        OutputFormat::Jpeg => {
+            let encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(
+                &mut out,
+                quality.unwrap_or(DEFAULT_QUALITY),
+            );
+            img.to_rgb8()
+                .write_with_encoder(encoder)
+                .map_err(|e| format!("encode jpeg failed: {}", e))?;
+        }
+        OutputFormat::Png => {
+            img.write_to(&mut out, ImageFormat::Png)
+                .map_err(|e| format!("encode png failed: {}", e))?;
+        }
+        OutputFormat::Webp => {
+            // image 自带的 WebP 编码器只有无损模式，缩略图会比原图还大；改用 libwebp 有损编码，保留透明通道
+            let rgba = img.to_rgba8();
+            let encoded = webp::Encoder::from_rgba(&rgba, rgba.width(), rgba.height())
+                .encode_simple(false, f32::from(quality.unwrap_or(DEFAULT_QUALITY)))
+                .map_err(|e| format!("encode webp failed: {:?}", e))?;
+            return Ok(encoded.to_vec());
+        }
+    }
+    Ok(out.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meta(len: u64) -> StorageMeta {
+        StorageMeta {
+            len,
+            modified: Some(UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000)),
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("nb-resize-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn parse_without_params() {
+        assert_eq!(ResizeParams::parse(None), Ok(None));
+        assert_eq!(ResizeParams::parse(Some("")), Ok(None));
+        // 只有 cache-busting 参数时同样返回原图
+        assert_eq!(ResizeParams::parse(Some("t=123")), Ok(None));
+    }
+
+    #[test]
+    fn parse_full_query() {
+        let params = ResizeParams::parse(Some("w=512&fmt=webp&q=80"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            params,
+            ResizeParams {
+                width: Some(512),
+                height: None,
+                format: Some(OutputFormat::Webp),
+                quality: Some(80),
+            }
+        );
+        let params = ResizeParams::parse(Some("h=64&fmt=JPG")).unwrap().unwrap();
+        assert_eq!(params.height, Some(64));
+        assert_eq!(params.format, Some(OutputFormat::Jpeg));
+    }
+
+    #[test]
+    fn parse_rejects_invalid_values() {
+        for query in [
+            "w=0", "w=8193", "h=abc", "fmt=gif", "q=0", "q=101", "q=high",
+        ] {
+            assert!(ResizeParams::parse(Some(query)).is_err(), "{}", query);
+        }
+    }
+
+    #[test]
+    fn cache_key_is_stable_and_distinct() {
+        let params = ResizeParams {
+            width: Some(512),
+            quality: Some(80),
+            ..Default::default()
+        };
+        let key = cache_key("storage/a.png", meta(100), &params, OutputFormat::Webp);
+        assert_eq!(key.len(), 32);
+        assert_eq!(
+            key,
+            cache_key("storage/a.png", meta(100), &params, OutputFormat::Webp)
+        );
+        assert_ne!(
+            key,
+            cache_key("storage/b.png", meta(100), &params, OutputFormat::Webp)
+        );
+        assert_ne!(
+            key,
+            cache_key("storage/a.png", meta(101), &params, OutputFormat::Webp)
+        );
+        assert_ne!(
+            key,
+            cache_key("storage/a.png", meta(100), &params, OutputFormat::Jpeg)
+        );
+        let other = ResizeParams {
+            quality: Some(81),
+            ..params.clone()
+        };
+        assert_ne!(
+            key,
+            cache_key("storage/a.png", meta(100), &other, OutputFormat::Webp)
+        );
+    }
+
+    #[test]
+    fn trim_removes_least_recently_used() {
+        let dir = temp_dir("trim");
+        let base = SystemTime::now();
+        for (i, name) in ["old", "mid", "new"].iter().enumerate() {
+            let path = dir.join(name);
+            fs::write(&path, vec![0u8; 100]).unwrap();
+            let file = fs::File::options().write(true).open(&path).unwrap();
+            file.set_modified(base - std::time::Duration::from_secs(300 - i as u64 * 100))
+                .unwrap();
+        }
+        trim(&dir, 250);
+        assert!(!dir.join("old").exists());
+        assert!(dir.join("mid").exists());
+        assert!(dir.join("new").exists());
+
+        // 未超限时不删除
+        trim(&dir, 1000);
+        assert!(dir.join("mid").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn webp_is_lossy_and_keeps_alpha() {
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::from_fn(64, 64, |x, y| {
+            image::Rgba([(x * 4) as u8, (y * 4) as u8, ((x * y) % 256) as u8, 128])
+        }));
+        let low = encode(&img, OutputFormat::Webp, Some(10)).unwrap();
+        let high = encode(&img, OutputFormat::Webp, Some(95)).unwrap();
+        assert!(low.len() < high.len());
+        let decoded = image::load_from_memory(&low).unwrap();
+        assert!(decoded.color().has_alpha());
+    }
+}
@@ -0,0 +1,310 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tauri::{Emitter, Manager};
+
+use crate::settings::SidecarSettings;
+
+// 后端内存/CPU 上限：每 5 秒采样一次 sidecar 的常驻内存与 CPU 占用，超限时记一条事故日志、
+// 通知前端，并由 supervisor 结束后重新拉起。内存连续 2 次超限、CPU 持续 1 分钟超限才算，避免瞬时峰值误伤。
+// 不用 RLIMIT_AS：Go 运行时预留的虚拟地址空间远大于实际占用，按虚拟内存限制会让后端直接起不来；
+// 改为通过 GOMEMLIMIT 让 Go 自己尽量压在上限以内，Windows 另用 Job Object 做硬上限兜底
+pub const INCIDENT_EVENT: &str = "backend-resource-incident";
+pub const MIN_MEMORY_MB: u64 = 128;
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(5);
+const MEMORY_STRIKES: u32 = 2;
+const CPU_STRIKES: u32 = 12;
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct ResourceIncident {
+    // memory / cpu
+    pub kind: String,
+    pub value: u64,
+    pub limit: u64,
+    pub pid: u32,
+    pub at: String,
+}
+
+pub fn validate(settings: &SidecarSettings) -> Result<(), String> {
+    if let Some(mb) = settings.memory_limit_mb {
+        if mb < MIN_MEMORY_MB {
+            return Err(format!(
+                "sidecar.memory_limit_mb must be at least {}",
+                MIN_MEMORY_MB
+            ));
+        }
+    }
+    if let Some(percent) = settings.cpu_limit_percent {
+        if !(1..=100).contains(&percent) {
+            return Err("sidecar.cpu_limit_percent must be between 1 and 100".to_string());
+        }
+    }
+    Ok(())
+}
+
+// Go 的软内存上限，留 10% 余量给非堆内存
+pub fn go_mem_limit(settings: &SidecarSettings) -> Option<String> {
+    settings
+        .memory_limit_mb
+        .map(|mb| format!("{}MiB", mb * 9 / 10))
+}
+
+struct Sample {
+    rss_bytes: u64,
+    // 累计 CPU 时间
+    cpu: Duration,
+}
+
+#[cfg(target_os = "linux")]
+fn sample(pid: u32) -> Option<Sample> {
+    let status = std::fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+    let rss_kb: u64 = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // 进程名可能含空格，从最后一个 ')' 之后开始数：utime/stime 是第 14/15 个字段
+    let fields: Vec<&str> = stat.rsplit_once(')')?.1.split_whitespace().collect();
+    let ticks: u64 = fields.get(11)?.parse::<u64>().ok()? + fields.get(12)?.parse::<u64>().ok()?;
+    // /proc 中的时钟节拍固定按 USER_HZ=100 换算
+    Some(Sample {
+        rss_bytes: rss_kb * 1024,
+        cpu: Duration::from_millis(ticks * 10),
+    })
+}
+
+// ps 的 cputime 格式为 [[dd-]hh:]mm:ss.cc
+#[cfg(target_os = "macos")]
+fn parse_cputime(raw: &str) -> Option<Duration> {
+    let (days, rest) = match raw.split_once('-') {
+        Some((d, rest)) => (d.parse::<f64>().ok()?, rest),
+        None => (0.0, raw),
+    };
+    let mut seconds = days * 86400.0;
+    let mut unit = 1.0;
+    for part in rest.rsplit(':') {
+        seconds += part.parse::<f64>().ok()? * unit;
+        unit *= 60.0;
+    }
+    Some(Duration::from_secs_f64(seconds))
+}
+
+#[cfg(target_os = "macos")]
+fn sample(pid: u32) -> Option<Sample> {
+    let output = std::process::Command::new("/bin/ps")
+        .args(["-o", "rss=,cputime=", "-p", &pid.to_string()])
+        .output()
+        .ok()?;
+    let text = String::from_utf8_lossy(&output.stdout);
+    let mut parts = text.split_whitespace();
+    let rss_kb: u64 = parts.next()?.parse().ok()?;
+    let cpu = parse_cputime(parts.next()?)?;
+    Some(Sample {
+        rss_bytes: rss_kb * 1024,
+        cpu,
+    })
+}
+
+#[cfg(windows)]
+fn sample(pid: u32) -> Option<Sample> {
+    use windows::Win32::Foundation::{CloseHandle, FILETIME};
+    use windows::Win32::System::ProcessStatus::{GetProcessMemoryInfo, PROCESS_MEMORY_COUNTERS};
+    use windows::Win32::System::Threading::{
+        GetProcessTimes, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+
+    unsafe {
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid).ok()?;
+        let mut counters = PROCESS_MEMORY_COUNTERS::default();
+        let memory = GetProcessMemoryInfo(
+            process,
+            &mut counters,
+            std::mem::size_of::<PROCESS_MEMORY_COUNTERS>() as u32,
+        );
+        let (mut created, mut exited, mut kernel, mut user) = (
+            FILETIME::default(),
+            FILETIME::default(),
+            FILETIME::default(),
+            FILETIME::default(),
+        );
+        let times = GetProcessTimes(process, &mut created, &mut exited, &mut kernel, &mut user);
+        let _ = CloseHandle(process);
+        memory.ok()?;
+        times.ok()?;
+        // FILETIME 以 100ns 为单位
+        let hundred_ns = |t: FILETIME| ((t.dwHighDateTime as u64) << 32) | t.dwLowDateTime as u64;
+        Some(Sample {
+            rss_bytes: counters.WorkingSetSize as u64,
+            cpu: Duration::from_nanos((hundred_ns(kernel) + hundred_ns(user)) * 100),
+        })
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn sample(_pid: u32) -> Option<Sample> {
+    None
+}
+
+// 状态小部件用的即时读数；CPU 占用按两次相隔很短的采样换算
+const STATS_WINDOW: Duration = Duration::from_millis(250);
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct SidecarStats {
+    pub pid: u32,
+    // 占整机 CPU 的百分比，与 cpu_limit_percent 口径一致
+    pub cpu_percent: f64,
+    pub rss_bytes: u64,
+    // 打开的文件描述符数；Windows 上为句柄数，无法读取时为 null
+    pub open_files: Option<u64>,
+}
+
+#[cfg(target_os = "linux")]
+fn open_files(pid: u32) -> Option<u64> {
+    std::fs::read_dir(format!("/proc/{}/fd", pid))
+        .ok()
+        .map(|entries| entries.count() as u64)
+}
+
+// lsof -F f 每个描述符输出一行以 f 开头的记录
+#[cfg(target_os = "macos")]
+fn open_files(pid: u32) -> Option<u64> {
+    let output = std::process::Command::new("/usr/sbin/lsof")
+        .args(["-n", "-P", "-F", "f", "-p", &pid.to_string()])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let text = String::from_utf8_lossy(&output.stdout);
+    Some(text.lines().filter(|line| line.starts_with('f')).count() as u64)
+}
+
+#[cfg(windows)]
+fn open_files(pid: u32) -> Option<u64> {
+    use windows::Win32::Foundation::CloseHandle;
+    use windows::Win32::System::Threading::{
+        GetProcessHandleCount, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+
+    unsafe {
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid).ok()?;
+        let mut count = 0u32;
+        let result = GetProcessHandleCount(process, &mut count);
+        let _ = CloseHandle(process);
+        result.ok()?;
+        Some(count as u64)
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn open_files(_pid: u32) -> Option<u64> {
+    None
+}
+
+pub fn stats(pid: u32) -> Result<SidecarStats, String> {
+    let unavailable = || format!("read sidecar stats failed: pid {} is not readable", pid);
+    let first = sample(pid).ok_or_else(unavailable)?;
+    let at = Instant::now();
+    thread::sleep(STATS_WINDOW);
+    let second = sample(pid).ok_or_else(unavailable)?;
+    let wall = at.elapsed().as_secs_f64();
+    let cores = thread::available_parallelism()
+        .map(|n| n.get() as f64)
+        .unwrap_or(1.0);
+    let used = second.cpu.saturating_sub(first.cpu).as_secs_f64();
+    let cpu_percent = if wall > 0.0 {
+        (used / wall / cores * 1000.0).round() / 10.0
+    } else {
+        0.0
+    };
+    Ok(SidecarStats {
+        pid,
+        cpu_percent,
+        rss_bytes: second.rss_bytes,
+        open_files: open_files(pid),
+    })
+}
+
+// 每次启动 sidecar 时调用；generation 变化（进程被替换）或进程退出后线程自行结束
+pub fn spawn_watchdog(app: tauri::AppHandle, pid: u32, generation: u64, settings: SidecarSettings) {
+    if settings.memory_limit_mb.is_none() && settings.cpu_limit_percent.is_none() {
+        return;
+    }
+    let cores = thread::available_parallelism()
+        .map(|n| n.get() as f64)
+        .unwrap_or(1.0);
+    thread::spawn(move || {
+        let mut previous: Option<(Instant, Duration)> = None;
+        let mut memory_strikes = 0;
+        let mut cpu_strikes = 0;
+        loop {
+            thread::sleep(SAMPLE_INTERVAL);
+            let state = app.state::<crate::supervisor::SupervisorState>();
+            let supervisor = &state.0;
+            if supervisor.generation() != generation {
+                return;
+            }
+            let Some(sample) = sample(pid) else {
+                return;
+            };
+            let now = Instant::now();
+
+            let mut incident = None;
+            if let Some(limit_mb) = settings.memory_limit_mb {
+                let used_mb = sample.rss_bytes / (1024 * 1024);
+                memory_strikes = if used_mb > limit_mb {
+                    memory_strikes + 1
+                } else {
+                    0
+                };
+                if memory_strikes >= MEMORY_STRIKES {
+                    incident = Some(("memory", used_mb, limit_mb));
+                }
+            }
+            if let (Some(limit), Some((at, cpu))) = (settings.cpu_limit_percent, previous) {
+                let wall = now.duration_since(at).as_secs_f64();
+                let used = sample.cpu.saturating_sub(cpu).as_secs_f64();
+                let percent = if wall > 0.0 {
+                    (used / wall / cores * 100.0).round() as u64
+                } else {
+                    0
+                };
+                cpu_strikes = if percent > limit as u64 {
+                    cpu_strikes + 1
+                } else {
+                    0
+                };
+                if incident.is_none() && cpu_strikes >= CPU_STRIKES {
+                    incident = Some(("cpu", percent, limit as u64));
+                }
+            }
+            previous = Some((now, sample.cpu));
+
+            if let Some((kind, value, limit)) = incident {
+                let unit = if kind == "memory" { "MB" } else { "%" };
+                let reason = format!(
+                    "{} limit exceeded: {}{} > {}{} (pid {})",
+                    kind, value, unit, limit, unit, pid
+                );
+                app.state::<crate::LogState>()
+                    .log_app("ERROR", &format!("sidecar resource incident: {}", reason));
+                let _ = app.emit(
+                    INCIDENT_EVENT,
+                    ResourceIncident {
+                        kind: kind.to_string(),
+                        value,
+                        limit,
+                        pid,
+                        at: chrono::Local::now().to_rfc3339(),
+                    },
+                );
+                supervisor.restart(&app, generation, &reason);
+                return;
+            }
+        }
+    });
+}
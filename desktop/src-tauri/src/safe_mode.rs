@@ -0,0 +1,97 @@
+use tauri::Manager;
+
+// 安全模式：更新后应用起不来时的逃生通道。启动时带 --safe-mode、设置 NB_SAFE_MODE=1
+// 或按住 Shift 打开应用即进入：忽略 settings.json 与 NB_* 环境变量、不用单独更新过的后端、
+// 不启动后台监听任务、后端只继承最基本的环境变量；前端通过 window.__NB_SAFE_MODE__ 跳过本地缓存
+pub const FLAG: &str = "--safe-mode";
+pub const ENV: &str = "NB_SAFE_MODE";
+
+// 后端进程仍需要的系统变量（定位用户目录、临时目录、可执行文件搜索路径）
+const PASSTHROUGH_ENV: &[&str] = &[
+    "PATH",
+    "HOME",
+    "USER",
+    "TMPDIR",
+    "XDG_CONFIG_HOME",
+    "USERPROFILE",
+    "APPDATA",
+    "LOCALAPPDATA",
+    "TEMP",
+    "TMP",
+    "SystemRoot",
+];
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct SafeModeInfo {
+    pub active: bool,
+    // cli / env / modifier
+    pub trigger: Option<String>,
+}
+
+pub struct SafeModeState(pub SafeModeInfo);
+
+pub fn detect() -> SafeModeInfo {
+    let trigger = if std::env::args().skip(1).any(|arg| arg == FLAG) {
+        Some("cli")
+    } else if std::env::var(ENV)
+        .map(|v| matches!(v.trim(), "1" | "true" | "yes"))
+        .unwrap_or(false)
+    {
+        Some("env")
+    } else if platform::shift_held() {
+        Some("modifier")
+    } else {
+        None
+    };
+    SafeModeInfo {
+        active: trigger.is_some(),
+        trigger: trigger.map(|t| t.to_string()),
+    }
+}
+
+// 页面脚本执行前注入，前端据此跳过 localStorage 里的缓存状态
+pub fn init_script() -> &'static str {
+    "Object.defineProperty(window, '__NB_SAFE_MODE__', { value: true });"
+}
+
+pub fn passthrough_env() -> Vec<(String, String)> {
+    PASSTHROUGH_ENV
+        .iter()
+        .filter_map(|key| std::env::var(key).ok().map(|v| (key.to_string(), v)))
+        .collect()
+}
+
+// 以正常模式重新启动：去掉 --safe-mode 参数与环境变量
+pub fn restart_normal(app: &tauri::AppHandle) -> ! {
+    let mut env = app.env();
+    env.args_os.retain(|arg| arg != FLAG);
+    std::env::remove_var(ENV);
+    tauri::process::restart(&env)
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use objc2_app_kit::{NSEvent, NSEventModifierFlags};
+
+    pub fn shift_held() -> bool {
+        NSEvent::modifierFlags_class().contains(NSEventModifierFlags::Shift)
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use windows::Win32::UI::Input::KeyboardAndMouse::{GetAsyncKeyState, VK_SHIFT};
+
+    pub fn shift_held() -> bool {
+        // 最高位表示按键当前按下
+        unsafe { GetAsyncKeyState(VK_SHIFT.0 as i32) as u16 & 0x8000 != 0 }
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+mod platform {
+    // Linux 下没有不依赖窗口系统的按键查询，只支持参数与环境变量
+    pub fn shift_held() -> bool {
+        false
+    }
+}
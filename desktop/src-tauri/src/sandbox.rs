@@ -0,0 +1,243 @@
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::settings::{SandboxMode, SidecarSettings};
+
+// 后端 sidecar 降权启动，后端被攻破时把影响面限制在数据目录与网络访问内：
+// - standard：Unix 上经 /bin/sh 包一层再 exec，关闭 core dump（内存里有 API Key）；
+//   Windows 上放进 Job Object，禁止读写剪贴板、改系统设置、操作其他进程的窗口句柄，主程序退出时连带结束
+// - strict：在 standard 基础上，macOS 用 sandbox-exec 限制为只能写数据目录/临时目录、不能读用户主目录其他位置、
+//   不能再启动其他程序；Windows 上 Job 内限制只能有一个进程。Linux 没有免 root 的等价手段，与 standard 相同
+// 修改后重启生效
+
+#[cfg(target_os = "macos")]
+const SANDBOX_EXEC: &str = "/usr/bin/sandbox-exec";
+
+// 包装后的启动方式：program + args 代替直接启动 sidecar
+pub struct Launch {
+    pub program: PathBuf,
+    pub args: Vec<String>,
+    pub notes: Vec<String>,
+}
+
+#[cfg(unix)]
+fn shell_wrapper(limits: &[String], target: Vec<String>) -> Launch {
+    let mut script = limits.join(" && ");
+    if !script.is_empty() {
+        script.push_str(" && ");
+    }
+    script.push_str("exec \"$@\"");
+    let mut args = vec!["-c".to_string(), script, "sh".to_string()];
+    args.extend(target);
+    Launch {
+        program: PathBuf::from("/bin/sh"),
+        args,
+        notes: Vec::new(),
+    }
+}
+
+// sandbox 配置里的字符串字面量
+#[cfg(target_os = "macos")]
+fn sb_literal(path: &Path) -> String {
+    let raw = path.to_string_lossy();
+    format!("\"{}\"", raw.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(target_os = "macos")]
+fn macos_profile(program: &Path, writable: &[PathBuf]) -> String {
+    let mut profile = String::from("(version 1)\n(allow default)\n");
+    // 只允许启动 sidecar 自己
+    profile.push_str(&format!(
+        "(deny process-exec)\n(allow process-exec (literal {}))\n",
+        sb_literal(program)
+    ));
+    profile.push_str("(deny file-write*)\n");
+    profile.push_str("(allow file-write* (subpath \"/private/tmp\") (subpath \"/private/var/folders\") (subpath \"/dev\")");
+    for dir in writable {
+        profile.push_str(&format!(" (subpath {})", sb_literal(dir)));
+    }
+    profile.push_str(")\n");
+    if let Some(home) = std::env::var_os("HOME").filter(|h| !h.is_empty()) {
+        profile.push_str(&format!(
+            "(deny file-read* (subpath {}))\n",
+            sb_literal(Path::new(&home))
+        ));
+        let mut readable: Vec<PathBuf> = writable.to_vec();
+        if let Some(dir) = program.parent() {
+            readable.push(dir.to_path_buf());
+        }
+        profile.push_str("(allow file-read*");
+        for dir in &readable {
+            profile.push_str(&format!(" (subpath {})", sb_literal(dir)));
+        }
+        profile.push_str(")\n");
+    }
+    profile
+}
+
+// strict 模式下在 sidecar 前面再套一层 sandbox-exec
+#[cfg(target_os = "macos")]
+fn strict_prefix(program: &Path, writable: &[PathBuf], notes: &mut Vec<String>) -> Vec<String> {
+    if !Path::new(SANDBOX_EXEC).is_file() {
+        notes.push("sandbox-exec not available, strict profile skipped".to_string());
+        return Vec::new();
+    }
+    notes.push("sandbox-exec profile (writes limited to data dirs)".to_string());
+    vec![
+        SANDBOX_EXEC.to_string(),
+        "-p".to_string(),
+        macos_profile(program, writable),
+    ]
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn strict_prefix(_program: &Path, _writable: &[PathBuf], _notes: &mut Vec<String>) -> Vec<String> {
+    Vec::new()
+}
+
+// 返回需要改用的启动方式；None 表示按原方式直接启动 sidecar
+#[allow(unused_variables)]
+pub fn launch(mode: SandboxMode, program: &Path, writable: &[PathBuf]) -> Option<Launch> {
+    if mode == SandboxMode::Off {
+        return None;
+    }
+    #[cfg(unix)]
+    {
+        let limits = ["ulimit -c 0".to_string()];
+        let mut notes = vec!["core dumps disabled".to_string()];
+        let mut target = if mode == SandboxMode::Strict {
+            strict_prefix(program, writable, &mut notes)
+        } else {
+            Vec::new()
+        };
+        target.push(program.to_string_lossy().to_string());
+        let mut launch = shell_wrapper(&limits, target);
+        launch.notes = notes;
+        Some(launch)
+    }
+    #[cfg(not(unix))]
+    None
+}
+
+// 启动后才能施加的限制（Windows Job Object，含资源上限）；句柄随状态保存，释放即结束 sidecar
+pub struct Confinement {
+    #[cfg(windows)]
+    job: windows::Win32::Foundation::HANDLE,
+}
+
+// HANDLE 是裸指针，Job 句柄本身可以跨线程使用
+#[cfg(windows)]
+unsafe impl Send for Confinement {}
+#[cfg(windows)]
+unsafe impl Sync for Confinement {}
+
+#[cfg(windows)]
+impl Drop for Confinement {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = windows::Win32::Foundation::CloseHandle(self.job);
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct SandboxState(pub Mutex<Option<Confinement>>);
+
+#[cfg(windows)]
+pub fn confine(
+    pid: u32,
+    settings: &SidecarSettings,
+) -> Result<Option<(Confinement, Vec<String>)>, String> {
+    use windows::core::PCWSTR;
+    use windows::Win32::Foundation::CloseHandle;
+    use windows::Win32::System::JobObjects::*;
+    use windows::Win32::System::Threading::{OpenProcess, PROCESS_SET_QUOTA, PROCESS_TERMINATE};
+
+    let mode = settings.sandbox;
+    let limited = settings.memory_limit_mb.is_some() || settings.cpu_limit_percent.is_some();
+    if mode == SandboxMode::Off && !limited {
+        return Ok(None);
+    }
+    unsafe {
+        let job = CreateJobObjectW(None, PCWSTR::null())
+            .map_err(|e| format!("create job object failed: {}", e))?;
+        let confinement = Confinement { job };
+        let mut notes = Vec::new();
+
+        let mut limits = JOBOBJECT_EXTENDED_LIMIT_INFORMATION::default();
+        limits.BasicLimitInformation.LimitFlags =
+            JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE | JOB_OBJECT_LIMIT_DIE_ON_UNHANDLED_EXCEPTION;
+        if mode == SandboxMode::Strict {
+            limits.BasicLimitInformation.LimitFlags |= JOB_OBJECT_LIMIT_ACTIVE_PROCESS;
+            limits.BasicLimitInformation.ActiveProcessLimit = 1;
+            notes.push("child processes blocked".to_string());
+        }
+        // 监控线程在上限处就会重启后端，这里的硬上限高出 50%，只拦采样间隔内的暴涨
+        if let Some(mb) = settings.memory_limit_mb {
+            limits.BasicLimitInformation.LimitFlags |= JOB_OBJECT_LIMIT_PROCESS_MEMORY;
+            limits.ProcessMemoryLimit = (mb * 3 / 2 * 1024 * 1024) as usize;
+            notes.push(format!("hard memory cap {}MB", mb * 3 / 2));
+        }
+        SetInformationJobObject(
+            job,
+            JobObjectExtendedLimitInformation,
+            &limits as *const _ as *const std::ffi::c_void,
+            std::mem::size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32,
+        )
+        .map_err(|e| format!("set job limits failed: {}", e))?;
+
+        if let Some(percent) = settings.cpu_limit_percent {
+            let mut cpu = JOBOBJECT_CPU_RATE_CONTROL_INFORMATION {
+                ControlFlags: JOB_OBJECT_CPU_RATE_CONTROL_ENABLE
+                    | JOB_OBJECT_CPU_RATE_CONTROL_HARD_CAP,
+                ..Default::default()
+            };
+            // 单位是万分之一
+            cpu.Anonymous.CpuRate = percent * 100;
+            SetInformationJobObject(
+                job,
+                JobObjectCpuRateControlInformation,
+                &cpu as *const _ as *const std::ffi::c_void,
+                std::mem::size_of::<JOBOBJECT_CPU_RATE_CONTROL_INFORMATION>() as u32,
+            )
+            .map_err(|e| format!("set job cpu cap failed: {}", e))?;
+            notes.push(format!("cpu capped at {}%", percent));
+        }
+
+        if mode != SandboxMode::Off {
+            let ui = JOBOBJECT_BASIC_UI_RESTRICTIONS {
+                UIRestrictionsClass: JOB_OBJECT_UILIMIT_HANDLES
+                    | JOB_OBJECT_UILIMIT_READCLIPBOARD
+                    | JOB_OBJECT_UILIMIT_WRITECLIPBOARD
+                    | JOB_OBJECT_UILIMIT_SYSTEMPARAMETERS
+                    | JOB_OBJECT_UILIMIT_DISPLAYSETTINGS
+                    | JOB_OBJECT_UILIMIT_GLOBALATOMS
+                    | JOB_OBJECT_UILIMIT_DESKTOP
+                    | JOB_OBJECT_UILIMIT_EXITWINDOWS,
+            };
+            SetInformationJobObject(
+                job,
+                JobObjectBasicUIRestrictions,
+                &ui as *const _ as *const std::ffi::c_void,
+                std::mem::size_of::<JOBOBJECT_BASIC_UI_RESTRICTIONS>() as u32,
+            )
+            .map_err(|e| format!("set job ui restrictions failed: {}", e))?;
+            notes.push("job object (ui/clipboard restricted, killed with app)".to_string());
+        }
+
+        let process = OpenProcess(PROCESS_SET_QUOTA | PROCESS_TERMINATE, false, pid)
+            .map_err(|e| format!("open sidecar process failed: {}", e))?;
+        let assigned = AssignProcessToJobObject(job, process);
+        let _ = CloseHandle(process);
+        assigned.map_err(|e| format!("assign job object failed: {}", e))?;
+        Ok(Some((confinement, notes)))
+    }
+}
+
+#[cfg(not(windows))]
+pub fn confine(
+    _pid: u32,
+    _settings: &SidecarSettings,
+) -> Result<Option<(Confinement, Vec<String>)>, String> {
+    Ok(None)
+}
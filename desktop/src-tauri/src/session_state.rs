@@ -0,0 +1,90 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+// 崩溃恢复用的界面状态快照：前端定期把未提交的提示词、画布布局等序列化后交给这里原子写盘，
+// 下次启动时原样取回。内容对原生层不透明，只校验是合法 JSON 且不超过大小上限
+const MAX_STATE_BYTES: usize = 4 * 1024 * 1024;
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+struct SessionFile {
+    saved_at: String,
+    app_version: String,
+    state: serde_json::Value,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct RestoredSession {
+    pub saved_at: String,
+    pub app_version: String,
+    pub state: serde_json::Value,
+    // 上次是否异常退出，前端据此决定静默恢复还是先询问
+    pub previous_session_crashed: bool,
+}
+
+pub struct SessionStateStore {
+    path: PathBuf,
+    // 串行化写入，频繁保存时不会出现两个临时文件互相覆盖
+    lock: Mutex<()>,
+}
+
+pub struct SessionStateState(pub SessionStateStore);
+
+impl SessionStateStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            lock: Mutex::new(()),
+        }
+    }
+
+    pub fn save(&self, state: &str, app_version: &str) -> Result<String, String> {
+        if state.len() > MAX_STATE_BYTES {
+            return Err(format!(
+                "session state too large: {} bytes (max {})",
+                state.len(),
+                MAX_STATE_BYTES
+            ));
+        }
+        let state: serde_json::Value =
+            serde_json::from_str(state).map_err(|e| format!("invalid session state: {}", e))?;
+        let saved_at = chrono::Local::now().to_rfc3339();
+        let file = SessionFile {
+            saved_at: saved_at.clone(),
+            app_version: app_version.to_string(),
+            state,
+        };
+        let bytes = serde_json::to_vec(&file)
+            .map_err(|e| format!("serialize session state failed: {}", e))?;
+        let _guard = self
+            .lock
+            .lock()
+            .map_err(|_| "session state lock poisoned".to_string())?;
+        crate::storage::write_atomic(&self.path, &bytes)?;
+        Ok(saved_at)
+    }
+
+    // 文件损坏时当作没有快照，不影响启动
+    pub fn load(&self, previous_session_crashed: bool) -> Option<RestoredSession> {
+        let bytes = fs::read(&self.path).ok()?;
+        let file: SessionFile = serde_json::from_slice(&bytes).ok()?;
+        Some(RestoredSession {
+            saved_at: file.saved_at,
+            app_version: file.app_version,
+            state: file.state,
+            previous_session_crashed,
+        })
+    }
+
+    pub fn clear(&self) -> Result<(), String> {
+        let _guard = self
+            .lock
+            .lock()
+            .map_err(|_| "session state lock poisoned".to_string())?;
+        match fs::remove_file(&self.path) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(format!("remove session state failed: {}", e)),
+        }
+    }
+}
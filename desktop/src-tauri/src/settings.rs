@@ -0,0 +1,621 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+// 原生层设置（AppData/settings.json）：只放 Rust 侧需要用到的配置，前端业务设置仍由前端自行持久化
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub naming: NamingSettings,
+    pub organize: OrganizeSettings,
+    pub runtime: RuntimeSettings,
+    pub sidecar: SidecarSettings,
+    pub publish: PublishSettings,
+    pub overlay: OverlaySettings,
+    pub clipboard: ClipboardSettings,
+    pub export: ExportSettings,
+    pub progress: ProgressSettings,
+    pub support: SupportSettings,
+    pub translation: TranslationSettings,
+}
+
+// 新文件命名方案：模板语法同 rename_images（{date} {time} {prompt:30} {seq} {id} ...）
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct NamingSettings {
+    pub template: String,
+    // 按日期分子目录（<目录>/2024-05-01/xxx.png）
+    pub subfolder_per_day: bool,
+}
+
+impl Default for NamingSettings {
+    fn default() -> Self {
+        // 默认沿用原来的 ID 命名，用户在设置中主动切换
+        Self {
+            template: "{id}".to_string(),
+            subfolder_per_day: false,
+        }
+    }
+}
+
+// 子目录整理规则：按顺序匹配，第一条命中的规则决定图片落在 storage/ 下的哪个子目录
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct OrganizeSettings {
+    pub rules: Vec<FolderRule>,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct FolderRule {
+    pub field: RuleField,
+    // 支持 * / ? 通配，不区分大小写；date 字段按 YYYY-MM-DD 匹配（如 2024-05-*）
+    pub pattern: String,
+    // 目录模板，如 projects/{project}、{model}/{month}
+    pub folder: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleField {
+    Tag,
+    Model,
+    Provider,
+    Project,
+    Date,
+}
+
+// 启动期运行参数（文件层）：可被 NB_* 环境变量与命令行参数覆盖，修改后需重启生效
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct RuntimeSettings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_dir: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_level: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backend_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transport: Option<String>,
+    // 固定后端端口（防火墙白名单场景），不写时由后端从 8080 起自动寻找空闲端口
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backend_port: Option<String>,
+    // 连接自行启动的后端（开发时热重载用）：http://127.0.0.1:8080 或端口号，设置后不再启动 sidecar
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external_backend: Option<String>,
+    // WebView 可以交给系统浏览器打开的域名，逗号分隔、按后缀匹配；不写时用内置列表
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub open_domains: Option<String>,
+}
+
+// 后端 sidecar 的启动方式，修改后重启生效
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct SidecarSettings {
+    pub sandbox: SandboxMode,
+    // 资源上限，不写表示不限制，见 resource_limits.rs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory_limit_mb: Option<u64>,
+    // 占整机 CPU 的百分比
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu_limit_percent: Option<u32>,
+    // 等待后端宣布端口的秒数，超时发出 backend-start-failed；不写为 30s
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub startup_timeout_secs: Option<u64>,
+    // 追加或覆盖 sidecar 的环境变量（如 GODEBUG、GIN_MODE），见 sidecar_env.rs
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    // 追加在 API 后端命令行末尾的参数（如 --experimental-models）；不经过 shell，逐个原样传入
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub extra_args: Vec<String>,
+    // 主窗口有焦点时降低 sidecar 的进程优先级，失焦后恢复，见 priority.rs；不写为开启
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lower_priority_when_focused: Option<bool>,
+    // 除 API 后端外另行启动的 sidecar（如后台 worker），见 sidecar_manager.rs
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub extra: Vec<ExtraSidecarSettings>,
+}
+
+// 随包分发的其他 sidecar，可执行文件名即 name，日志写到 logs/<name>.log
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ExtraSidecarSettings {
+    pub name: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    pub restart: RestartPolicy,
+    // 分配一个空闲端口，以 --port 传入，重启后沿用
+    pub port: bool,
+    pub disabled: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RestartPolicy {
+    Never,
+    // 非 0 退出码或被信号结束时重启
+    #[default]
+    OnFailure,
+    Always,
+}
+
+// 发布目录：收藏或带指定标签的结果自动导出到用户选的目录（如 Dropbox 同步目录），见 publish.rs
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct PublishSettings {
+    pub enabled: bool,
+    pub folder: String,
+    // png / jpeg / webp，不写时保持原图格式
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    // 最长边上限，只缩小不放大
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_dimension: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quality: Option<u8>,
+    // 除收藏外，带有其中任一标签的结果也会发布
+    pub tags: Vec<String>,
+}
+
+// 直播叠加层：最新结果写到固定路径并/或在本机固定地址提供，供 OBS 图像源/浏览器源使用，见 overlay.rs
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct OverlaySettings {
+    pub enabled: bool,
+    // 每出一张新图就覆盖写入的文件（绝对路径），不写则不落盘
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    // 在 127.0.0.1 上提供页面与图片
+    pub serve: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+}
+
+// 各平台的具体限制见 sandbox.rs
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SandboxMode {
+    Off,
+    #[default]
+    Standard,
+    Strict,
+}
+
+// 剪贴板文本历史：开启后记录复制过的、像提示词的文本，只保存在本机，见 clipboard_history.rs
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ClipboardSettings {
+    pub text_history: bool,
+    // 最多保留条数，不写为 50
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_entries: Option<usize>,
+    // 单条文本的字符数范围，不写为 8 ~ 4000
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_chars: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_chars: Option<usize>,
+    // 包含其中任一关键词（不区分大小写）的文本不记录，如 password
+    pub exclude: Vec<String>,
+    // 不为空时只记录包含其中任一关键词的文本
+    pub include: Vec<String>,
+}
+
+// 导出预设：把固定的交付规格（格式、尺寸、水印、元数据、目标目录）存成一个名字，见 export_preset.rs
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ExportSettings {
+    pub presets: Vec<ExportPreset>,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ExportPreset {
+    pub name: String,
+    // png / jpeg / webp，不写时保持原图格式
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    // 最长边上限，只缩小不放大
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_dimension: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quality: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub watermark: Option<ExportWatermark>,
+    pub metadata: MetadataPolicy,
+    // 导出目录（绝对路径）
+    pub destination: String,
+}
+
+// 可见文字水印，尺寸与边距按图片短边的百分比计，导出不同尺寸时比例一致
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ExportWatermark {
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub font: Option<String>,
+    // 字号，不写为 3（%）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size_percent: Option<f32>,
+    // #rrggbb[aa]，不写为半透明白色
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    // top-left / bottom-right / center ...，不写为 bottom-right
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position: Option<String>,
+    // 不写为 2（%）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub margin_percent: Option<f32>,
+}
+
+// keep：不转码时原样保留；strip_location：去掉 GPS；strip_all：一律重新编码，不带任何 EXIF/XMP
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataPolicy {
+    #[default]
+    Keep,
+    StripLocation,
+    StripAll,
+}
+
+// 原生长任务开始时主窗口已隐藏或最小化，是否弹出独立的进度小窗口（见 progress.rs）
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ProgressSettings {
+    pub window: bool,
+}
+
+impl Default for ProgressSettings {
+    fn default() -> Self {
+        Self { window: true }
+    }
+}
+
+// 诊断包上传地址（https），不配置时 submit_diagnostics 不可用，见 diagnostics.rs
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct SupportSettings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+}
+
+// 提示词翻译，见 translate.rs：provider 为后端 provider_configs 里的名字，密钥只在 Rust 里读取；
+// 配置了 local_endpoint（本机 LibreTranslate 兼容服务）时优先使用本地翻译
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct TranslationSettings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    // 不写时按 provider 类型取默认模型
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub local_endpoint: Option<String>,
+}
+
+impl Settings {
+    fn validate(&self) -> Result<(), String> {
+        if self.naming.template.trim().is_empty() {
+            return Err("naming.template is empty".to_string());
+        }
+        crate::naming::validate_template(&self.naming.template)?;
+        crate::organize::validate_rules(&self.organize.rules)?;
+        crate::resource_limits::validate(&self.sidecar)?;
+        crate::supervisor::validate(&self.sidecar)?;
+        crate::sidecar_manager::validate(&self.sidecar.extra)?;
+        crate::publish::validate(&self.publish)?;
+        crate::overlay::validate(&self.overlay)?;
+        crate::clipboard_history::validate(&self.clipboard)?;
+        crate::export_preset::validate(&self.export)?;
+        crate::diagnostics::validate(&self.support)?;
+        crate::translate::validate(&self.translation)?;
+        crate::config::validate_layer(&self.runtime)
+    }
+}
+
+// 每次写入前把旧文件存一份到 settings-history/<版本号>.json，保留最近 HISTORY_KEEP 份，
+// 写坏了可以 revert_settings 回去，主文件损坏时启动也会自动用最近一份可用的历史版本
+const HISTORY_DIR: &str = "settings-history";
+const HISTORY_KEEP: usize = 10;
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct SettingsVersion {
+    pub version: u64,
+    pub saved_at: String,
+}
+
+pub struct SettingsStore {
+    path: PathBuf,
+    current: Mutex<Settings>,
+}
+
+pub struct SettingsState(pub SettingsStore);
+
+// 递归合并：patch 中的对象逐字段覆盖，其余类型整体替换；前端只需传要改的字段
+fn merge(target: &mut serde_json::Value, patch: serde_json::Value) {
+    match (target, patch) {
+        (serde_json::Value::Object(t), serde_json::Value::Object(p)) => {
+            for (key, value) in p {
+                merge(t.entry(key).or_insert(serde_json::Value::Null), value);
+            }
+        }
+        (t, p) => *t = p,
+    }
+}
+
+fn parse_valid(bytes: &[u8]) -> Result<Settings, String> {
+    let settings: Settings =
+        serde_json::from_slice(bytes).map_err(|e| format!("parse settings failed: {}", e))?;
+    settings.validate()?;
+    Ok(settings)
+}
+
+impl SettingsStore {
+    // 文件不存在或损坏时使用默认值，不阻塞启动。日志级别取决于设置，此时还没有日志，
+    // 过程中的警告随结果返回，由 setup 在日志初始化后写入
+    pub fn load(path: PathBuf) -> (Self, Vec<String>) {
+        let mut store = Self {
+            path,
+            current: Mutex::new(Settings::default()),
+        };
+        let mut warnings = Vec::new();
+        let settings = match fs::read(&store.path) {
+            Ok(bytes) => match parse_valid(&bytes) {
+                Ok(settings) => settings,
+                Err(e) => {
+                    warnings.push(format!("settings parse failed: {}", e));
+                    store.recover(&mut warnings)
+                }
+            },
+            Err(_) => Settings::default(),
+        };
+        store.current = Mutex::new(settings);
+        (store, warnings)
+    }
+
+    // 安全模式下不读磁盘上的设置；修改设置仍会写回（写前照常归档）
+    pub fn defaults(path: PathBuf) -> Self {
+        Self {
+            path,
+            current: Mutex::new(Settings::default()),
+        }
+    }
+
+    // 从最新的历史版本往前找第一份能用的
+    fn recover(&self, warnings: &mut Vec<String>) -> Settings {
+        for version in self.version_numbers().into_iter().rev() {
+            if let Ok(settings) = self.read_version(version) {
+                warnings.push(format!("settings restored from version {}", version));
+                return settings;
+            }
+        }
+        warnings.push("no usable settings version, using defaults".to_string());
+        Settings::default()
+    }
+
+    fn history_dir(&self) -> PathBuf {
+        self.path.with_file_name(HISTORY_DIR)
+    }
+
+    fn version_numbers(&self) -> Vec<u64> {
+        let mut versions: Vec<u64> = fs::read_dir(self.history_dir())
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .filter_map(|entry| {
+                        let name = entry.file_name().to_string_lossy().to_string();
+                        name.strip_suffix(".json")?.parse().ok()
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        versions.sort_unstable();
+        versions
+    }
+
+    pub fn versions(&self) -> Vec<SettingsVersion> {
+        let dir = self.history_dir();
+        self.version_numbers()
+            .into_iter()
+            .rev()
+            .map(|version| {
+                let saved_at = fs::metadata(dir.join(format!("{}.json", version)))
+                    .and_then(|m| m.modified())
+                    .map(|t| chrono::DateTime::<chrono::Local>::from(t).to_rfc3339())
+                    .unwrap_or_default();
+                SettingsVersion { version, saved_at }
+            })
+            .collect()
+    }
+
+    pub fn read_version(&self, version: u64) -> Result<Settings, String> {
+        let path = self.history_dir().join(format!("{}.json", version));
+        let bytes = fs::read(&path)
+            .map_err(|e| format!("read settings version {} failed: {}", version, e))?;
+        parse_valid(&bytes).map_err(|e| format!("settings version {} unusable: {}", version, e))
+    }
+
+    // 把当前磁盘上的文件归档为新版本；文件本身已损坏就不归档
+    fn journal(&self) -> Result<(), String> {
+        let Ok(bytes) = fs::read(&self.path) else {
+            return Ok(());
+        };
+        if parse_valid(&bytes).is_err() {
+            return Ok(());
+        }
+        let dir = self.history_dir();
+        fs::create_dir_all(&dir).map_err(|e| format!("create settings history failed: {}", e))?;
+        let versions = self.version_numbers();
+        let next = versions.last().map(|v| v + 1).unwrap_or(1);
+        crate::storage::write_atomic(&dir.join(format!("{}.json", next)), &bytes)?;
+        let excess = (versions.len() + 1).saturating_sub(HISTORY_KEEP);
+        for version in versions.iter().take(excess) {
+            let _ = fs::remove_file(dir.join(format!("{}.json", version)));
+        }
+        Ok(())
+    }
+
+    pub fn get(&self) -> Settings {
+        self.current.lock().map(|s| s.clone()).unwrap_or_default()
+    }
+
+    pub fn replace(&self, log: &crate::LogState, next: Settings) -> Result<Settings, String> {
+        next.validate()?;
+        let mut current = self
+            .current
+            .lock()
+            .map_err(|_| "settings lock poisoned".to_string())?;
+        self.persist(log, &next)?;
+        *current = next.clone();
+        Ok(next)
+    }
+
+    fn persist(&self, log: &crate::LogState, settings: &Settings) -> Result<(), String> {
+        let bytes = serde_json::to_vec_pretty(settings)
+            .map_err(|e| format!("serialize settings failed: {}", e))?;
+        // 归档失败不阻止本次写入
+        if let Err(e) = self.journal() {
+            log.log_app("WARN", &format!("settings journal failed: {}", e));
+        }
+        crate::storage::write_atomic(&self.path, &bytes)
+    }
+
+    pub fn update(
+        &self,
+        log: &crate::LogState,
+        patch: serde_json::Value,
+    ) -> Result<Settings, String> {
+        if !patch.is_object() {
+            return Err("settings patch must be an object".to_string());
+        }
+        let mut current = self
+            .current
+            .lock()
+            .map_err(|_| "settings lock poisoned".to_string())?;
+
+        let mut value = serde_json::to_value(&*current)
+            .map_err(|e| format!("serialize settings failed: {}", e))?;
+        merge(&mut value, patch);
+        let next: Settings =
+            serde_json::from_value(value).map_err(|e| format!("invalid settings: {}", e))?;
+        next.validate()?;
+
+        self.persist(log, &next)?;
+        *current = next.clone();
+        Ok(next)
+    }
+}
+
+pub const PORTABLE_FORMAT: &str = "nano-banana-settings";
+pub const PORTABLE_VERSION: u32 = 1;
+const MAX_PORTABLE_BYTES: u64 = 4 * 1024 * 1024;
+
+// 可移植设置文件：原生层设置 + 后端 Provider 配置，默认不含 API Key
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct PortableSettings {
+    pub format: String,
+    pub version: u32,
+    #[serde(default)]
+    pub exported_at: String,
+    #[serde(default)]
+    pub includes_secrets: bool,
+    #[serde(default)]
+    pub settings: Settings,
+    #[serde(default)]
+    pub providers: Vec<crate::library::ProviderConfigRecord>,
+}
+
+#[derive(serde::Serialize)]
+pub struct ImportSettingsReport {
+    pub settings: Settings,
+    pub providers_updated: Vec<String>,
+    // 文件里带了密钥但本次未导入
+    pub secrets_skipped: bool,
+}
+
+pub fn export_portable(
+    store: &SettingsStore,
+    library: &crate::library::Library,
+    dest: &Path,
+    include_secrets: bool,
+) -> Result<(), String> {
+    // 历史库还没建（首次启动后端未就绪）时只导出原生层设置
+    let providers = match library.open() {
+        Ok(conn) => library.provider_configs(&conn, include_secrets)?,
+        Err(_) => Vec::new(),
+    };
+    let file = PortableSettings {
+        format: PORTABLE_FORMAT.to_string(),
+        version: PORTABLE_VERSION,
+        exported_at: chrono::Local::now().to_rfc3339(),
+        includes_secrets: include_secrets,
+        settings: store.get(),
+        providers,
+    };
+    let bytes = serde_json::to_vec_pretty(&file)
+        .map_err(|e| format!("serialize settings failed: {}", e))?;
+    crate::storage::write_atomic(dest, &bytes)
+}
+
+// 先全部校验再写入：Provider 配置在一个事务里更新，成功后才替换原生层设置
+pub fn import_portable(
+    store: &SettingsStore,
+    log: &crate::LogState,
+    library: &crate::library::Library,
+    src: &Path,
+    include_secrets: bool,
+) -> Result<ImportSettingsReport, String> {
+    let src = &crate::long_path::extend(src);
+    let meta = fs::metadata(src)
+        .map_err(|e| format!("read settings failed: {} ({})", e, src.display()))?;
+    if meta.len() > MAX_PORTABLE_BYTES {
+        return Err("settings file too large".to_string());
+    }
+    let bytes =
+        fs::read(src).map_err(|e| format!("read settings failed: {} ({})", e, src.display()))?;
+    let mut file: PortableSettings =
+        serde_json::from_slice(&bytes).map_err(|e| format!("parse settings failed: {}", e))?;
+    if file.format != PORTABLE_FORMAT {
+        return Err(format!("unsupported settings format: {}", file.format));
+    }
+    if file.version == 0 || file.version > PORTABLE_VERSION {
+        return Err(format!("unsupported settings version: {}", file.version));
+    }
+    file.settings.validate()?;
+
+    let mut secrets_skipped = false;
+    for provider in &mut file.providers {
+        provider.provider_name = provider.provider_name.trim().to_string();
+        if provider.provider_name.is_empty() {
+            return Err("provider_name is empty".to_string());
+        }
+        if provider.api_key.is_some() && !include_secrets {
+            provider.api_key = None;
+            secrets_skipped = true;
+        }
+    }
+
+    let mut providers_updated = Vec::new();
+    if !file.providers.is_empty() {
+        let mut conn = library.open()?;
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("begin transaction failed: {}", e))?;
+        for provider in &file.providers {
+            library.upsert_provider_config(&tx, provider)?;
+            providers_updated.push(provider.provider_name.clone());
+        }
+        tx.commit()
+            .map_err(|e| format!("commit settings failed: {}", e))?;
+    }
+
+    let settings = store.replace(log, file.settings)?;
+    Ok(ImportSettingsReport {
+        settings,
+        providers_updated,
+        secrets_skipped,
+    })
+}
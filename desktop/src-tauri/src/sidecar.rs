@@ -0,0 +1,238 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+// 包内 sidecar 与构建时记录的 SHA-256 不一致，拒绝启动
+pub const INTEGRITY_FAILED_EVENT: &str = "sidecar-integrity-failed";
+// 由 build.rs 写入；为空表示构建时设置了 NB_SKIP_SIDECAR_HASH=1（release）或拿不到校验和（开发构建）
+const EXPECTED_SHA256: &str = env!("NB_SIDECAR_SHA256");
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct IntegrityFailure {
+    pub path: String,
+    pub expected: String,
+    // 读取失败时为空
+    pub actual: String,
+    pub message: String,
+}
+
+// 打包后 sidecar 与主程序同目录（externalBin 会去掉目标三元组后缀）
+pub fn sidecar_path(name: &str) -> Result<PathBuf, String> {
+    let exe = std::env::current_exe().map_err(|e| format!("locate app failed: {}", e))?;
+    let dir = exe
+        .parent()
+        .ok_or_else(|| "locate app failed: no parent dir".to_string())?;
+    let mut path = dir.join(name);
+    if cfg!(windows) {
+        path.set_extension("exe");
+    }
+    Ok(path)
+}
+
+// 启动前自检：文件存在、可执行，macOS 上清除隔离属性并校验签名。
+// 返回值是需要写入日志的修复记录；Err 是给用户看的具体原因，此时不应再尝试启动
+pub fn preflight(name: &str) -> Result<Vec<String>, String> {
+    preflight_path(&sidecar_path(name)?)
+}
+
+// 启动前比对包内 sidecar 的 SHA-256，防止被替换或篡改；单独更新的版本已按清单校验和与签名验证过，不走这里。
+// 开发构建与构建时没有校验和的包跳过，返回 Ok(None)，跳过原因见 integrity_skip_reason
+pub fn verify_integrity(name: &str) -> Result<Option<String>, IntegrityFailure> {
+    let expected = EXPECTED_SHA256.trim();
+    if cfg!(debug_assertions) || expected.is_empty() {
+        return Ok(None);
+    }
+    let path = sidecar_path(name).map_err(|message| IntegrityFailure {
+        path: String::new(),
+        expected: expected.to_string(),
+        actual: String::new(),
+        message,
+    })?;
+    let fail = |actual: String, message: String| IntegrityFailure {
+        path: path.display().to_string(),
+        expected: expected.to_string(),
+        actual,
+        message,
+    };
+    let actual = sha256_file(&path).map_err(|e| fail(String::new(), e))?;
+    if !actual.eq_ignore_ascii_case(expected) {
+        return Err(fail(
+            actual,
+            format!(
+                "sidecar checksum mismatch, the backend binary may have been tampered with ({})",
+                path.display()
+            ),
+        ));
+    }
+    Ok(Some(format!("verified sha256 of {}", path.display())))
+}
+
+pub fn integrity_skip_reason() -> &'static str {
+    if cfg!(debug_assertions) {
+        "development build"
+    } else {
+        "no checksum recorded at build time (NB_SKIP_SIDECAR_HASH)"
+    }
+}
+
+fn sha256_file(path: &Path) -> Result<String, String> {
+    let mut file = File::open(path).map_err(|e| format!("open sidecar failed: {}", e))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher).map_err(|e| format!("read sidecar failed: {}", e))?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+// 单独更新下载的 sidecar 同样要过一遍自检
+pub fn preflight_path(path: &std::path::Path) -> Result<Vec<String>, String> {
+    let path = path.to_path_buf();
+    if !path.is_file() {
+        return Err(format!("sidecar missing: {}", path.display()));
+    }
+    #[allow(unused_mut)]
+    let mut notes = Vec::new();
+    #[cfg(unix)]
+    if let Some(note) = ensure_executable(&path)? {
+        notes.push(note);
+    }
+    #[cfg(target_os = "macos")]
+    {
+        if let Some(note) = clear_quarantine(&path)? {
+            notes.push(note);
+        }
+        // 开发构建的 sidecar 没有签名
+        if !cfg!(debug_assertions) {
+            verify_signature(&path)?;
+        }
+    }
+    Ok(notes)
+}
+
+// 解压工具或同步盘可能丢掉可执行位，能修就顺手修掉
+#[cfg(unix)]
+fn ensure_executable(path: &Path) -> Result<Option<String>, String> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let meta = std::fs::metadata(path).map_err(|e| format!("stat sidecar failed: {}", e))?;
+    let mode = meta.permissions().mode();
+    if mode & 0o111 != 0 {
+        return Ok(None);
+    }
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode | 0o755)).map_err(|e| {
+        format!(
+            "sidecar is not executable and chmod failed: {} ({})",
+            e,
+            path.display()
+        )
+    })?;
+    Ok(Some(format!(
+        "restored executable bit on {}",
+        path.display()
+    )))
+}
+
+#[cfg(target_os = "macos")]
+fn run_tool(program: &str, args: &[&std::ffi::OsStr]) -> Result<std::process::Output, String> {
+    std::process::Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| format!("run {} failed: {}", program, e))
+}
+
+#[cfg(target_os = "macos")]
+const QUARANTINE_ATTR: &str = "com.apple.quarantine";
+
+#[cfg(target_os = "macos")]
+fn clear_quarantine(path: &Path) -> Result<Option<String>, String> {
+    use std::ffi::OsStr;
+
+    let probe = run_tool(
+        "/usr/bin/xattr",
+        &[
+            OsStr::new("-p"),
+            OsStr::new(QUARANTINE_ATTR),
+            path.as_os_str(),
+        ],
+    )?;
+    if !probe.status.success() {
+        return Ok(None);
+    }
+    // 从下载目录直接打开时系统会把应用随机挂载到只读的 AppTranslocation 目录，这里无法修改属性
+    if path.to_string_lossy().contains("/AppTranslocation/") {
+        return Err(
+            "app is running from a translocated read-only location; move it to /Applications and reopen"
+                .to_string(),
+        );
+    }
+    let removed = run_tool(
+        "/usr/bin/xattr",
+        &[
+            OsStr::new("-d"),
+            OsStr::new(QUARANTINE_ATTR),
+            path.as_os_str(),
+        ],
+    )?;
+    if !removed.status.success() {
+        return Err(format!(
+            "clear quarantine failed: {} ({})",
+            String::from_utf8_lossy(&removed.stderr).trim(),
+            path.display()
+        ));
+    }
+    Ok(Some(format!("cleared quarantine on {}", path.display())))
+}
+
+#[cfg(target_os = "macos")]
+fn team_identifier(path: &Path) -> Option<String> {
+    use std::ffi::OsStr;
+
+    let out = run_tool(
+        "/usr/bin/codesign",
+        &[
+            OsStr::new("-dv"),
+            OsStr::new("--verbose=2"),
+            path.as_os_str(),
+        ],
+    )
+    .ok()?;
+    // codesign -d 的信息输出在 stderr
+    String::from_utf8_lossy(&out.stderr)
+        .lines()
+        .find_map(|line| line.strip_prefix("TeamIdentifier="))
+        .map(|id| id.trim().to_string())
+        .filter(|id| !id.is_empty() && id != "not set")
+}
+
+// 签名必须完整有效，且与主程序属于同一开发者团队（防止 sidecar 被替换）
+#[cfg(target_os = "macos")]
+fn verify_signature(path: &Path) -> Result<(), String> {
+    use std::ffi::OsStr;
+
+    let out = run_tool(
+        "/usr/bin/codesign",
+        &[
+            OsStr::new("--verify"),
+            OsStr::new("--strict"),
+            path.as_os_str(),
+        ],
+    )?;
+    if !out.status.success() {
+        return Err(format!(
+            "sidecar code signature invalid: {}",
+            String::from_utf8_lossy(&out.stderr).trim()
+        ));
+    }
+    let app_team = std::env::current_exe()
+        .ok()
+        .and_then(|exe| team_identifier(&exe));
+    if let Some(app_team) = app_team {
+        let sidecar_team = team_identifier(path).unwrap_or_default();
+        if sidecar_team != app_team {
+            return Err(format!(
+                "sidecar signed by a different team: {} (expected {})",
+                sidecar_team, app_team
+            ));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,87 @@
+use std::collections::BTreeMap;
+
+// sidecar 的启动环境：内置默认值 < settings.json 的 sidecar.env < 启动器自己管理的变量。
+// 数据目录、监听 socket、内存上限等由其他设置决定，不允许在 sidecar.env 里改，避免两边对不上
+const DEFAULTS: [(&str, &str); 2] = [("GODEBUG", "http2debug=2"), ("GIN_MODE", "release")];
+const RESERVED: [&str; 8] = [
+    "NB_DATA_DIR",
+    "NB_LOG_LEVEL",
+    "NB_LISTEN_SOCKET",
+    "TAURI_PLATFORM",
+    "TAURI_FAMILY",
+    "GOMEMLIMIT",
+    "NB_ACCELERATION",
+    "NB_GPU",
+];
+const MAX_VARS: usize = 64;
+const MAX_KEY_LEN: usize = 128;
+const MAX_VALUE_LEN: usize = 4096;
+// 日志里隐藏这些变量的值
+const SECRET_HINTS: [&str; 5] = ["KEY", "TOKEN", "SECRET", "PASSWORD", "AUTH"];
+
+// Windows 的环境变量名不区分大小写，这里也不区分
+fn is_reserved(key: &str) -> bool {
+    RESERVED.iter().any(|r| r.eq_ignore_ascii_case(key))
+}
+
+pub fn validate(env: &BTreeMap<String, String>) -> Result<(), String> {
+    if env.len() > MAX_VARS {
+        return Err(format!("sidecar.env allows at most {} variables", MAX_VARS));
+    }
+    for (key, value) in env {
+        let valid = !key.is_empty()
+            && key.len() <= MAX_KEY_LEN
+            && !key.starts_with(|c: char| c.is_ascii_digit())
+            && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid {
+            return Err(format!(
+                "sidecar.env has an invalid variable name: {:?}",
+                key
+            ));
+        }
+        if is_reserved(key) {
+            return Err(format!(
+                "sidecar.env cannot override {}, it is managed by the app",
+                key
+            ));
+        }
+        if value.len() > MAX_VALUE_LEN || value.contains('\0') {
+            return Err(format!("sidecar.env value of {} is invalid", key));
+        }
+    }
+    Ok(())
+}
+
+// 内置默认值与用户覆盖合并后的结果，按变量名排序；启动器管理的变量随后再追加
+pub fn merged(overrides: &BTreeMap<String, String>) -> Vec<(String, String)> {
+    let mut env: Vec<(String, String)> = DEFAULTS
+        .iter()
+        .filter(|(key, _)| !overrides.keys().any(|k| k.eq_ignore_ascii_case(key)))
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+    env.extend(overrides.iter().map(|(k, v)| (k.clone(), v.clone())));
+    env.sort_by(|a, b| a.0.cmp(&b.0));
+    env
+}
+
+fn redact(key: &str, value: &str) -> String {
+    let upper = key.to_ascii_uppercase();
+    if SECRET_HINTS.iter().any(|hint| upper.contains(hint)) {
+        return "***".to_string();
+    }
+    // 代理地址可能带 user:pass@
+    match (value.find("://"), value.rfind('@')) {
+        (Some(scheme), Some(at)) if at > scheme + 3 => {
+            format!("{}***{}", &value[..scheme + 3], &value[at..])
+        }
+        _ => value.to_string(),
+    }
+}
+
+// 写入日志的一行：KEY=VALUE，敏感值隐藏
+pub fn describe(env: &[(String, String)]) -> String {
+    env.iter()
+        .map(|(key, value)| format!("{}={}", key, redact(key, value)))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
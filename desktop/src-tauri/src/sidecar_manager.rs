@@ -0,0 +1,515 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tauri::Manager;
+use tauri_plugin_shell::process::{CommandChild, CommandEvent, TerminatedPayload};
+use tauri_plugin_shell::ShellExt;
+
+use crate::settings::{ExtraSidecarSettings, RestartPolicy};
+use crate::supervisor::{Backoff, ExitStatus, LineBuffer};
+
+// 多个具名 sidecar 的看护。API 后端（server）仍由 supervisor.rs 负责（握手、端口事件、热替换），
+// sidecar.extra 里声明的其余 sidecar（如处理重活的后台 worker）由这里统一拉起：各自写 logs/<name>.log，
+// 按需分配空闲端口，退出后按各自的重启策略与退避重新拉起。只启动随包分发的同名可执行文件
+const MAX_EXTRA: usize = 8;
+const MAX_ARGS: usize = 32;
+
+struct Managed {
+    settings: ExtraSidecarSettings,
+    log: crate::LogWriter,
+    child: Mutex<Option<CommandChild>>,
+    // 每次启动 +1，旧进程的事件循环据此判断自己是否已过期
+    generation: AtomicU64,
+    // 首次启动时分配，之后的重启沿用
+    port: Mutex<Option<u16>>,
+    backoff: Mutex<Backoff>,
+    spawned: Mutex<Option<(u64, Instant)>>,
+    last_exit: Mutex<Option<ExitStatus>>,
+    last_error: Mutex<Option<String>>,
+    // 手动重启：退出后立即拉起，不算崩溃
+    restart_requested: AtomicBool,
+}
+
+pub struct SidecarManager {
+    data_dir: PathBuf,
+    // 安全模式下不继承用户环境变量
+    clear_env: bool,
+    sidecars: Mutex<BTreeMap<String, Arc<Managed>>>,
+    // 应用退出中，不再重启
+    stopping: AtomicBool,
+}
+
+pub struct SidecarManagerState(pub SidecarManager);
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct SidecarStatus {
+    pub name: String,
+    pub running: bool,
+    pub pid: Option<u32>,
+    pub port: Option<u16>,
+    pub restart: RestartPolicy,
+    // 本次会话内的重启次数（不含首次启动）
+    pub restart_count: u64,
+    pub uptime_ms: Option<u64>,
+    pub last_exit: Option<ExitStatus>,
+    // 最近一次启动失败的原因
+    pub last_error: Option<String>,
+    pub log_file: String,
+}
+
+pub fn validate(extra: &[ExtraSidecarSettings]) -> Result<(), String> {
+    if extra.len() > MAX_EXTRA {
+        return Err(format!(
+            "sidecar.extra allows at most {} sidecars",
+            MAX_EXTRA
+        ));
+    }
+    let mut seen = Vec::new();
+    for sidecar in extra {
+        let name = sidecar.name.as_str();
+        let valid = !name.is_empty()
+            && name.len() <= 32
+            && name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '-' | '_'));
+        if !valid {
+            return Err(format!(
+                "sidecar.extra name {:?} must be 1-32 lowercase letters, digits, - or _",
+                name
+            ));
+        }
+        // server 与 app 已被后端和 app.log 占用
+        if matches!(name, "server" | "app") {
+            return Err(format!("sidecar.extra name {:?} is reserved", name));
+        }
+        if seen.contains(&name) {
+            return Err(format!("sidecar.extra name {:?} is duplicated", name));
+        }
+        seen.push(name);
+        if sidecar.args.len() > MAX_ARGS {
+            return Err(format!(
+                "sidecar.extra {} allows at most {} args",
+                name, MAX_ARGS
+            ));
+        }
+        crate::sidecar_env::validate(&sidecar.env)?;
+    }
+    Ok(())
+}
+
+impl Managed {
+    fn new(settings: ExtraSidecarSettings, log_dir: &std::path::Path) -> Self {
+        let log = crate::LogWriter::new(log_dir.join(format!("{}.log", settings.name)));
+        Self {
+            settings,
+            log,
+            child: Mutex::new(None),
+            generation: AtomicU64::new(0),
+            port: Mutex::new(None),
+            backoff: Mutex::new(Backoff::default()),
+            spawned: Mutex::new(None),
+            last_exit: Mutex::new(None),
+            last_error: Mutex::new(None),
+            restart_requested: AtomicBool::new(false),
+        }
+    }
+
+    fn write(&self, log: &crate::LogState, stream: &str, message: &str) {
+        self.log
+            .write_line(&format!("{} [{}] {}", log.stamp(), stream, message));
+    }
+
+    fn pid(&self) -> Option<u32> {
+        self.child
+            .lock()
+            .ok()
+            .and_then(|child| child.as_ref().map(|c| c.pid()))
+    }
+
+    // 该代进程已退出（被替换或已清掉 spawned）
+    fn wait_exited(&self, generation: u64, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let exited = self.generation.load(Ordering::SeqCst) != generation
+                || self.spawned.lock().is_ok_and(|s| s.is_none());
+            if exited {
+                return true;
+            }
+            if Instant::now() >= deadline {
+                return false;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    fn set_error(&self, error: Option<String>) {
+        if let Ok(mut last) = self.last_error.lock() {
+            *last = error;
+        }
+    }
+
+    fn status(&self) -> SidecarStatus {
+        let pid = self.pid();
+        let spawned = self.spawned.lock().ok().and_then(|s| *s);
+        SidecarStatus {
+            name: self.settings.name.clone(),
+            running: pid.is_some(),
+            pid,
+            port: self.port.lock().ok().and_then(|p| *p),
+            restart: self.settings.restart,
+            restart_count: self.generation.load(Ordering::SeqCst).saturating_sub(1),
+            uptime_ms: spawned
+                .filter(|_| pid.is_some())
+                .map(|(_, at)| at.elapsed().as_millis() as u64),
+            last_exit: self.last_exit.lock().ok().and_then(|e| e.clone()),
+            last_error: self.last_error.lock().ok().and_then(|e| e.clone()),
+            log_file: self.log.path.to_string_lossy().to_string(),
+        }
+    }
+}
+
+impl SidecarManager {
+    pub fn new(data_dir: PathBuf, clear_env: bool) -> Self {
+        Self {
+            data_dir,
+            clear_env,
+            sidecars: Mutex::new(BTreeMap::new()),
+            stopping: AtomicBool::new(false),
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<Arc<Managed>> {
+        self.sidecars.lock().ok()?.get(name).cloned()
+    }
+
+    fn all(&self) -> Vec<Arc<Managed>> {
+        self.sidecars
+            .lock()
+            .map(|sidecars| sidecars.values().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn list(&self) -> Vec<SidecarStatus> {
+        self.all().iter().map(|m| m.status()).collect()
+    }
+}
+
+fn spawn_one(app: &tauri::AppHandle, managed: &Arc<Managed>) -> Result<(), String> {
+    let state = app.state::<SidecarManagerState>();
+    let manager = &state.0;
+    let log_state = app.state::<crate::LogState>();
+    let name = managed.settings.name.clone();
+
+    let mut command = app
+        .shell()
+        .sidecar(&name)
+        .map_err(|e| format!("resolve sidecar {} failed: {}", name, e))?;
+    if manager.clear_env {
+        command = command
+            .env_clear()
+            .envs(crate::safe_mode::passthrough_env());
+    }
+    // 用户的 env 在前，约定的几项不允许被覆盖
+    let mut env: Vec<(String, String)> = if manager.clear_env {
+        Vec::new()
+    } else {
+        managed.settings.env.clone().into_iter().collect()
+    };
+    env.extend([
+        ("TAURI_PLATFORM".to_string(), "macos".to_string()),
+        ("TAURI_FAMILY".to_string(), "unix".to_string()),
+        ("NB_SIDECAR_NAME".to_string(), name.clone()),
+        (
+            "NB_LOG_LEVEL".to_string(),
+            log_state.server_level().to_string(),
+        ),
+    ]);
+    for (key, value) in &env {
+        command = command.env(key, value);
+    }
+    // 数据目录按 OsStr 原样传，见 supervisor::spawn
+    command = command
+        .env("NB_DATA_DIR", &manager.data_dir)
+        .arg("--data-dir")
+        .arg(&manager.data_dir);
+    if managed.settings.port {
+        let mut port = managed
+            .port
+            .lock()
+            .map_err(|_| "sidecar port lock poisoned".to_string())?;
+        let port = match *port {
+            Some(port) => port,
+            None => *port.insert(crate::supervisor::pick_free_port()?),
+        };
+        command = command.args(["--port".to_string(), port.to_string()]);
+    }
+    command = command.args(&managed.settings.args);
+
+    let (mut rx, child) = command
+        .spawn()
+        .map_err(|e| format!("spawn sidecar {} failed: {}", name, e))?;
+    let pid = child.pid();
+    let generation = managed.generation.fetch_add(1, Ordering::SeqCst) + 1;
+    if let Ok(mut guard) = managed.child.lock() {
+        *guard = Some(child);
+    }
+    if let Ok(mut spawned) = managed.spawned.lock() {
+        *spawned = Some((crate::now_ms() as u64, Instant::now()));
+    }
+    if let Ok(mut backoff) = managed.backoff.lock() {
+        backoff.started();
+    }
+    managed.set_error(None);
+    log_state.log_app(
+        "INFO",
+        &format!("sidecar {} spawned with PID: {}", name, pid),
+    );
+
+    let app_handle = app.clone();
+    let managed = managed.clone();
+    let log_state = log_state.inner().clone();
+    tauri::async_runtime::spawn(async move {
+        let mut stdout = LineBuffer::default();
+        let mut stderr = LineBuffer::default();
+        while let Some(event) = rx.recv().await {
+            match event {
+                CommandEvent::Stdout(chunk) => {
+                    for line in stdout.push(&chunk) {
+                        managed.write(&log_state, "STDOUT", &line);
+                    }
+                }
+                CommandEvent::Stderr(chunk) => {
+                    for line in stderr.push(&chunk) {
+                        managed.write(&log_state, "STDERR", &line);
+                    }
+                }
+                CommandEvent::Error(err) => {
+                    log_state.log_app("ERROR", &format!("sidecar {} error: {}", name, err));
+                }
+                CommandEvent::Terminated(status) => {
+                    if let Some(line) = stdout.finish() {
+                        managed.write(&log_state, "STDOUT", &line);
+                    }
+                    if let Some(line) = stderr.finish() {
+                        managed.write(&log_state, "STDERR", &line);
+                    }
+                    on_terminated(&app_handle, &managed, generation, status);
+                }
+                _ => {}
+            }
+        }
+    });
+    Ok(())
+}
+
+fn on_terminated(
+    app: &tauri::AppHandle,
+    managed: &Arc<Managed>,
+    generation: u64,
+    status: TerminatedPayload,
+) {
+    // 已被手动重启替换的旧进程
+    if managed.generation.load(Ordering::SeqCst) != generation {
+        return;
+    }
+    let stopping = app
+        .state::<SidecarManagerState>()
+        .0
+        .stopping
+        .load(Ordering::SeqCst);
+    let requested = managed.restart_requested.swap(false, Ordering::SeqCst);
+    if let Ok(mut child) = managed.child.lock() {
+        *child = None;
+    }
+    if let Ok(mut spawned) = managed.spawned.lock() {
+        *spawned = None;
+    }
+    if let Ok(mut last_exit) = managed.last_exit.lock() {
+        *last_exit = Some(ExitStatus {
+            code: status.code,
+            signal: status.signal,
+            at_ms: crate::now_ms() as u64,
+            requested: requested || stopping,
+        });
+    }
+    let name = &managed.settings.name;
+    let log_state = app.state::<crate::LogState>();
+    log_state.log_app(
+        if requested || stopping {
+            "INFO"
+        } else {
+            "WARN"
+        },
+        &format!(
+            "sidecar {} terminated: code={:?} signal={:?}",
+            name, status.code, status.signal
+        ),
+    );
+    if stopping {
+        return;
+    }
+    let delay = if requested {
+        Duration::ZERO
+    } else {
+        let restart = match managed.settings.restart {
+            RestartPolicy::Never => false,
+            RestartPolicy::OnFailure => status.code != Some(0),
+            RestartPolicy::Always => true,
+        };
+        if !restart {
+            return;
+        }
+        let (failures, delay) = match managed.backoff.lock() {
+            Ok(mut backoff) => backoff.next(),
+            Err(_) => return,
+        };
+        log_state.log_app(
+            "INFO",
+            &format!(
+                "restarting sidecar {} in {} ms (attempt {})",
+                name,
+                delay.as_millis(),
+                failures
+            ),
+        );
+        delay
+    };
+    let app = app.clone();
+    let managed = managed.clone();
+    std::thread::spawn(move || {
+        std::thread::sleep(delay);
+        if app
+            .state::<SidecarManagerState>()
+            .0
+            .stopping
+            .load(Ordering::SeqCst)
+        {
+            return;
+        }
+        if let Err(e) = spawn_one(&app, &managed) {
+            app.state::<crate::LogState>().log_app("ERROR", &e);
+            managed.set_error(Some(e));
+        }
+    });
+}
+
+// setup 阶段按 sidecar.extra 逐个启动；单个启动失败只记日志，不影响其余
+pub fn start(app: &tauri::AppHandle, extra: &[ExtraSidecarSettings]) {
+    let state = app.state::<SidecarManagerState>();
+    let log_dir = app.state::<crate::LogState>().dir.clone();
+    for settings in extra.iter().filter(|s| !s.disabled) {
+        let managed = Arc::new(Managed::new(settings.clone(), &log_dir));
+        if let Ok(mut sidecars) = state.0.sidecars.lock() {
+            sidecars.insert(settings.name.clone(), managed.clone());
+        }
+        if let Err(e) = spawn_one(app, &managed) {
+            app.state::<crate::LogState>().log_app("ERROR", &e);
+            managed.set_error(Some(e));
+        }
+    }
+}
+
+// 关闭标准输入并发 SIGTERM，SHUTDOWN_TIMEOUT 内未退出再强制结束
+fn stop_gracefully(
+    app: &tauri::AppHandle,
+    managed: &Managed,
+    child: CommandChild,
+    generation: u64,
+) {
+    let pid = child.pid();
+    drop(child);
+    let log_state = app.state::<crate::LogState>();
+    if let Err(e) = crate::supervisor::terminate(pid) {
+        log_state.log_app(
+            "WARN",
+            &format!(
+                "send SIGTERM to sidecar {} failed: {}",
+                managed.settings.name, e
+            ),
+        );
+    }
+    if managed.wait_exited(generation, crate::supervisor::SHUTDOWN_TIMEOUT) {
+        return;
+    }
+    log_state.log_app(
+        "WARN",
+        &format!(
+            "sidecar {} did not exit in time, killing pid {}",
+            managed.settings.name, pid
+        ),
+    );
+    if let Err(e) = crate::supervisor::force_kill(pid) {
+        log_state.log_app(
+            "ERROR",
+            &format!("kill sidecar {} failed: {}", managed.settings.name, e),
+        );
+    }
+}
+
+// 结束并重新拉起；未在运行（如重启策略为 never 且已退出）时直接启动
+pub fn restart(app: &tauri::AppHandle, name: &str) -> Result<(), String> {
+    let managed = app
+        .state::<SidecarManagerState>()
+        .0
+        .get(name)
+        .ok_or_else(|| format!("sidecar {} is not configured", name))?;
+    let child = managed.child.lock().ok().and_then(|mut guard| guard.take());
+    match child {
+        Some(child) => {
+            managed.restart_requested.store(true, Ordering::SeqCst);
+            let generation = managed.generation.load(Ordering::SeqCst);
+            let app = app.clone();
+            std::thread::spawn(move || {
+                stop_gracefully(&app, &managed, child, generation);
+            });
+            Ok(())
+        }
+        None => spawn_one(app, &managed).inspect_err(|e| managed.set_error(Some(e.clone()))),
+    }
+}
+
+// 应用退出时关闭各 sidecar 的标准输入并发 SIGTERM 让它们自行退出，最多共等 timeout，之后仍未退出的强制结束
+pub fn shutdown(app: &tauri::AppHandle, timeout: Duration) {
+    let Some(state) = app.try_state::<SidecarManagerState>() else {
+        return;
+    };
+    state.0.stopping.store(true, Ordering::SeqCst);
+    let mut pending = Vec::new();
+    for managed in state.0.all() {
+        let child = managed.child.lock().ok().and_then(|mut guard| guard.take());
+        if let Some(child) = child {
+            let pid = child.pid();
+            pending.push((managed.clone(), pid));
+            // CommandChild 持有标准输入的写端，drop 即关闭
+            drop(child);
+            let _ = crate::supervisor::terminate(pid);
+        }
+    }
+    let deadline = Instant::now() + timeout;
+    // 退出后 on_terminated 会清掉 spawned
+    let alive = |managed: &Managed| managed.spawned.lock().is_ok_and(|s| s.is_some());
+    while Instant::now() < deadline && pending.iter().any(|(m, _)| alive(m)) {
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    let log_state = app.state::<crate::LogState>();
+    for (managed, pid) in pending.iter().filter(|(m, _)| alive(m)) {
+        log_state.log_app(
+            "WARN",
+            &format!(
+                "sidecar {} did not exit within {} ms, killing pid {}",
+                managed.settings.name,
+                timeout.as_millis(),
+                pid
+            ),
+        );
+        if let Err(e) = crate::supervisor::force_kill(*pid) {
+            log_state.log_app(
+                "ERROR",
+                &format!("kill sidecar {} failed: {}", managed.settings.name, e),
+            );
+        }
+    }
+}
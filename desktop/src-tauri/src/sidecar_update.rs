@@ -0,0 +1,388 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use base64::Engine;
+use sha2::{Digest, Sha256};
+
+// 后端单独更新：只替换 sidecar，不走整包更新。新版本放在 AppData/sidecar/<版本>/ 下，
+// 应用包内自带的 sidecar 不动（macOS 改动签名过的包会直接失效），上一个版本保留用于回滚
+pub const MANIFEST_URL: &str =
+    "https://github.com/ShellMonster/Nano_Banana_Pro_Web/releases/latest/download/sidecar.json";
+pub const PROGRESS_EVENT: &str = "sidecar-update-progress";
+const RECORD_FILE: &str = "active.json";
+const MAX_MANIFEST_BYTES: usize = 256 * 1024;
+const MAX_SIDECAR_BYTES: u64 = 512 * 1024 * 1024;
+
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct Manifest {
+    pub version: String,
+    #[serde(default)]
+    pub notes: String,
+    // 新后端依赖的最低前端版本，低于该版本的应用不会收到这次后端更新
+    #[serde(default)]
+    pub min_app_version: Option<String>,
+    pub platforms: HashMap<String, PlatformEntry>,
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct PlatformEntry {
+    pub url: String,
+    pub sha256: String,
+    // minisign 签名（base64），与应用更新使用同一把 updater 密钥
+    pub signature: String,
+    #[serde(default)]
+    pub size: Option<u64>,
+    // 针对旧版本的增量补丁，按旧版本号索引
+    #[serde(default)]
+    pub patches: HashMap<String, crate::delta::PatchEntry>,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct InstalledSidecar {
+    pub version: String,
+    pub sha256: String,
+    // 相对 sidecar 目录的路径
+    pub file: String,
+    pub installed_at: String,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct SidecarRecord {
+    // None 表示使用应用包内自带的 sidecar
+    pub current: Option<InstalledSidecar>,
+    pub previous: Option<InstalledSidecar>,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct SidecarInstallResult {
+    #[serde(flatten)]
+    pub installed: InstalledSidecar,
+    // 是否通过增量补丁安装
+    pub delta: bool,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct SidecarUpdateInfo {
+    pub version: String,
+    pub current_version: String,
+    pub notes: String,
+    pub size: Option<u64>,
+}
+
+#[derive(Clone, serde::Serialize)]
+struct ProgressPayload {
+    downloaded: u64,
+    total: Option<u64>,
+}
+
+pub struct SidecarStore {
+    dir: PathBuf,
+}
+
+pub struct SidecarUpdateState(pub SidecarStore);
+
+// 与 externalBin 的目标三元组命名一致
+pub fn platform_key() -> String {
+    let os = if cfg!(target_os = "macos") {
+        "apple-darwin"
+    } else if cfg!(target_os = "windows") {
+        "pc-windows-msvc"
+    } else {
+        "unknown-linux-gnu"
+    };
+    format!("{}-{}", std::env::consts::ARCH, os)
+}
+
+fn binary_name() -> &'static str {
+    if cfg!(windows) {
+        "server.exe"
+    } else {
+        "server"
+    }
+}
+
+fn sha256_file(path: &Path) -> Result<String, String> {
+    let mut file = fs::File::open(path)
+        .map_err(|e| format!("open sidecar failed: {} ({})", e, path.display()))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)
+        .map_err(|e| format!("read sidecar failed: {} ({})", e, path.display()))?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+fn is_newer(candidate: &str, current: &str) -> bool {
+    let parse = |v: &str| semver::Version::parse(v.trim().trim_start_matches('v')).ok();
+    match (parse(candidate), parse(current)) {
+        (Some(a), Some(b)) => a > b,
+        _ => false,
+    }
+}
+
+// 签名格式与 tauri updater 相同：pubkey / signature 都是 base64 包了一层的 minisign 文本
+pub fn verify_signature(data: &[u8], signature: &str, pubkey: &str) -> Result<(), String> {
+    let decode = |value: &str| -> Result<String, String> {
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(value.trim())
+            .map_err(|e| format!("decode signature failed: {}", e))?;
+        String::from_utf8(bytes).map_err(|e| format!("decode signature failed: {}", e))
+    };
+    let key = minisign_verify::PublicKey::decode(&decode(pubkey)?)
+        .map_err(|e| format!("invalid updater pubkey: {}", e))?;
+    let signature = minisign_verify::Signature::decode(&decode(signature)?)
+        .map_err(|e| format!("invalid signature: {}", e))?;
+    key.verify(data, &signature, true)
+        .map_err(|e| format!("signature verification failed: {}", e))
+}
+
+impl SidecarStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    pub fn record(&self) -> SidecarRecord {
+        fs::read(self.dir.join(RECORD_FILE))
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default()
+    }
+
+    fn save_record(&self, record: &SidecarRecord) -> Result<(), String> {
+        let bytes = serde_json::to_vec_pretty(record)
+            .map_err(|e| format!("serialize sidecar record failed: {}", e))?;
+        crate::storage::write_atomic(&self.dir.join(RECORD_FILE), &bytes)
+    }
+
+    // 当前应使用的替换版本；文件缺失或校验和不符时返回 Err，调用方回退到包内版本
+    pub fn active_binary(&self) -> Result<Option<(PathBuf, String)>, String> {
+        let Some(current) = self.record().current else {
+            return Ok(None);
+        };
+        let path = self.dir.join(&current.file);
+        let actual = sha256_file(&path)?;
+        if !actual.eq_ignore_ascii_case(&current.sha256) {
+            return Err(format!(
+                "sidecar {} checksum mismatch ({})",
+                current.version,
+                path.display()
+            ));
+        }
+        Ok(Some((path, current.version)))
+    }
+
+    pub fn current_version(&self, app_version: &str) -> String {
+        self.record()
+            .current
+            .map(|c| c.version)
+            .unwrap_or_else(|| app_version.to_string())
+    }
+
+    // 切回上一个版本；没有上一个版本时回到包内自带的 sidecar。返回回滚后的版本（None 表示包内版本）
+    pub fn rollback(&self) -> Result<Option<String>, String> {
+        let mut record = self.record();
+        if record.current.is_none() {
+            return Err("already using the bundled backend".to_string());
+        }
+        let dropped = record.current.take();
+        record.current = record.previous.take();
+        self.save_record(&record)?;
+        if let Some(dropped) = dropped {
+            self.remove_version_dir(&dropped);
+        }
+        Ok(record.current.map(|c| c.version))
+    }
+
+    fn remove_version_dir(&self, installed: &InstalledSidecar) {
+        if let Some(parent) = Path::new(&installed.file).parent() {
+            if !parent.as_os_str().is_empty() {
+                let _ = fs::remove_dir_all(self.dir.join(parent));
+            }
+        }
+    }
+
+    // 下载 → 大小/SHA-256/签名校验 → 落到 <版本>/ 目录 → 更新记录（旧 current 变为 previous）
+    pub async fn install(
+        &self,
+        app: &tauri::AppHandle,
+        version: &str,
+        entry: &PlatformEntry,
+        pubkey: &str,
+    ) -> Result<SidecarInstallResult, String> {
+        let version_dir = crate::naming::sanitize_stem(version);
+        if version_dir.is_empty() {
+            return Err(format!("invalid version: {}", version));
+        }
+        if entry.size.is_some_and(|size| size > MAX_SIDECAR_BYTES) {
+            return Err("sidecar too large".to_string());
+        }
+        fs::create_dir_all(&self.dir).map_err(|e| format!("create sidecar dir failed: {}", e))?;
+
+        let (bytes, delta) = match self.try_delta(app, entry).await {
+            Some(bytes) => (bytes, true),
+            None => (
+                download(
+                    app,
+                    &entry.url,
+                    entry.size,
+                    MAX_SIDECAR_BYTES,
+                    PROGRESS_EVENT,
+                )
+                .await?,
+                false,
+            ),
+        };
+
+        let digest = hex::encode(Sha256::digest(&bytes));
+        if !digest.eq_ignore_ascii_case(entry.sha256.trim()) {
+            return Err(format!(
+                "sidecar checksum mismatch: expected {}, got {}",
+                entry.sha256.trim(),
+                digest
+            ));
+        }
+        verify_signature(&bytes, &entry.signature, pubkey)?;
+
+        let relative = format!("{}/{}", version_dir, binary_name());
+        let target = self.dir.join(&relative);
+        let tmp = self.dir.join(format!("{}.download", version_dir));
+        {
+            let mut file =
+                fs::File::create(&tmp).map_err(|e| format!("write sidecar failed: {}", e))?;
+            file.write_all(&bytes)
+                .and_then(|_| file.sync_all())
+                .map_err(|e| format!("write sidecar failed: {}", e))?;
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&tmp, fs::Permissions::from_mode(0o755))
+                .map_err(|e| format!("chmod sidecar failed: {}", e))?;
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("create sidecar dir failed: {}", e))?;
+        }
+        fs::rename(&tmp, &target).map_err(|e| {
+            let _ = fs::remove_file(&tmp);
+            format!("install sidecar failed: {}", e)
+        })?;
+
+        let installed = InstalledSidecar {
+            version: version.to_string(),
+            sha256: digest,
+            file: relative,
+            installed_at: chrono::Local::now().to_rfc3339(),
+        };
+        let mut record = self.record();
+        // 只保留当前与上一个版本，更早的目录清掉
+        if let Some(stale) = record.previous.take() {
+            if stale.file != installed.file {
+                self.remove_version_dir(&stale);
+            }
+        }
+        record.previous = record.current.take().filter(|c| c.file != installed.file);
+        record.current = Some(installed.clone());
+        self.save_record(&record)?;
+        Ok(SidecarInstallResult { installed, delta })
+    }
+
+    // 以当前在用的后端（替换版本或包内版本）为基准打补丁；无补丁或失败返回 None，走完整下载
+    async fn try_delta(&self, app: &tauri::AppHandle, entry: &PlatformEntry) -> Option<Vec<u8>> {
+        let (base_path, base_version) = match self.record().current {
+            Some(current) => (self.dir.join(&current.file), current.version),
+            None => (
+                crate::sidecar::sidecar_path("server").ok()?,
+                app.package_info().version.to_string(),
+            ),
+        };
+        let patch = entry.patches.get(&base_version)?;
+        let base = fs::read(&base_path).ok()?;
+        match crate::delta::download_and_apply(app, &base, patch, PROGRESS_EVENT).await {
+            Ok(bytes) => Some(bytes),
+            Err(e) => {
+                use tauri::Manager;
+                app.state::<crate::LogState>().log_app(
+                    "WARN",
+                    &format!("sidecar delta update failed, falling back: {}", e),
+                );
+                None
+            }
+        }
+    }
+}
+
+// 带进度事件的下载，超过 max_bytes 直接中止
+pub async fn download(
+    app: &tauri::AppHandle,
+    url: &str,
+    size_hint: Option<u64>,
+    max_bytes: u64,
+    event: &str,
+) -> Result<Vec<u8>, String> {
+    use tauri::Emitter;
+
+    if size_hint.is_some_and(|size| size > max_bytes) {
+        return Err("download too large".to_string());
+    }
+    let mut response = reqwest::get(url)
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| format!("download failed: {}", e))?;
+    let total = response.content_length().or(size_hint);
+    let mut bytes = Vec::with_capacity(total.unwrap_or(0).min(max_bytes) as usize);
+    let mut last_emit = 0u64;
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| format!("download failed: {}", e))?
+    {
+        bytes.extend_from_slice(&chunk);
+        if bytes.len() as u64 > max_bytes {
+            return Err("download too large".to_string());
+        }
+        let downloaded = bytes.len() as u64;
+        if downloaded - last_emit >= 512 * 1024 {
+            last_emit = downloaded;
+            let _ = app.emit(event, ProgressPayload { downloaded, total });
+        }
+    }
+    let _ = app.emit(
+        event,
+        ProgressPayload {
+            downloaded: bytes.len() as u64,
+            total,
+        },
+    );
+    Ok(bytes)
+}
+
+pub async fn fetch_manifest() -> Result<Manifest, String> {
+    let bytes = reqwest::get(MANIFEST_URL)
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| format!("fetch sidecar manifest failed: {}", e))?
+        .bytes()
+        .await
+        .map_err(|e| format!("fetch sidecar manifest failed: {}", e))?;
+    if bytes.len() > MAX_MANIFEST_BYTES {
+        return Err("sidecar manifest too large".to_string());
+    }
+    serde_json::from_slice(&bytes).map_err(|e| format!("parse sidecar manifest failed: {}", e))
+}
+
+// 有适用于本机、比当前更新且兼容当前前端的版本时返回对应条目
+pub fn applicable<'a>(
+    manifest: &'a Manifest,
+    current_version: &str,
+    app_version: &str,
+) -> Option<&'a PlatformEntry> {
+    if !is_newer(&manifest.version, current_version) {
+        return None;
+    }
+    if let Some(min) = &manifest.min_app_version {
+        if is_newer(min, app_version) {
+            return None;
+        }
+    }
+    manifest.platforms.get(&platform_key())
+}
@@ -0,0 +1,204 @@
+use std::fs;
+use std::marker::PhantomData;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use base64::Engine;
+use serde::de::DeserializeOwned;
+use tauri::{Emitter, Manager};
+
+// 功能开关与远程紧急开关共用的签名清单：服务端下发 {manifest: base64 JSON, signature: minisign 签名}，
+// 用 updater 密钥验签。清单连同签名缓存在 AppData，离线时沿用上次的；每次读取都重新验签，
+// 版本号只增不减，旧清单不能回放。只走 https，响应体超过上限时立即停止读取
+const FETCH_TIMEOUT: Duration = Duration::from_secs(15);
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct SignedManifest {
+    // base64 的清单 JSON
+    pub manifest: String,
+    // base64 的 minisign 签名
+    pub signature: String,
+}
+
+// 缓存文件；其他字段（如功能开关的灰度分桶）原样保留
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct Cache {
+    #[serde(default)]
+    pub manifest: Option<SignedManifest>,
+    #[serde(default)]
+    pub fetched_at: Option<String>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+pub trait Manifest: DeserializeOwned + Send + 'static {
+    fn version(&self) -> u64;
+    // 条目数等内容检查
+    fn check(&self) -> Result<(), String>;
+}
+
+pub struct Remote<M> {
+    // 日志与错误信息里的名称，如 "feature flags"
+    name: &'static str,
+    url: Option<&'static str>,
+    path: PathBuf,
+    max_bytes: usize,
+    manifest: PhantomData<fn() -> M>,
+}
+
+impl<M> Clone for Remote<M> {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name,
+            url: self.url,
+            path: self.path.clone(),
+            max_bytes: self.max_bytes,
+            manifest: PhantomData,
+        }
+    }
+}
+
+impl<M: Manifest> Remote<M> {
+    pub fn new(
+        name: &'static str,
+        url: Option<&'static str>,
+        path: PathBuf,
+        max_bytes: usize,
+    ) -> Self {
+        Self {
+            name,
+            url: url.map(str::trim).filter(|u| !u.is_empty()),
+            path,
+            max_bytes,
+            manifest: PhantomData,
+        }
+    }
+
+    pub fn read(&self) -> Cache {
+        fs::read(&self.path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn write(&self, cache: &Cache) -> Result<(), String> {
+        let bytes = serde_json::to_vec_pretty(cache)
+            .map_err(|e| format!("serialize {} failed: {}", self.name, e))?;
+        crate::storage::write_atomic(&self.path, &bytes)
+    }
+
+    fn verify(&self, signed: &SignedManifest, pubkey: &str) -> Result<M, String> {
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(signed.manifest.trim())
+            .map_err(|e| format!("decode {} failed: {}", self.name, e))?;
+        crate::sidecar_update::verify_signature(&bytes, &signed.signature, pubkey)?;
+        let manifest: M = serde_json::from_slice(&bytes)
+            .map_err(|e| format!("parse {} failed: {}", self.name, e))?;
+        manifest.check()?;
+        Ok(manifest)
+    }
+
+    // 缓存里验签通过的清单及其拉取时间；验签失败记 WARN 后当作没有缓存
+    pub fn cached(&self, app: &tauri::AppHandle, pubkey: &str) -> Option<(M, Option<String>)> {
+        let cache = self.read();
+        match self.verify(cache.manifest.as_ref()?, pubkey) {
+            Ok(manifest) => Some((manifest, cache.fetched_at)),
+            Err(e) => {
+                app.state::<crate::LogState>()
+                    .log_app("WARN", &format!("cached {} ignored: {}", self.name, e));
+                None
+            }
+        }
+    }
+
+    async fn fetch(&self, url: &str) -> Result<SignedManifest, String> {
+        let client = reqwest::Client::builder()
+            .timeout(FETCH_TIMEOUT)
+            .https_only(true)
+            .build()
+            .map_err(|e| format!("create http client failed: {}", e))?;
+        let mut response = client
+            .get(url)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| format!("fetch {} failed: {}", self.name, e))?;
+        let too_large = || format!("{} too large", self.name);
+        if response
+            .content_length()
+            .is_some_and(|len| len > self.max_bytes as u64)
+        {
+            return Err(too_large());
+        }
+        let mut bytes = Vec::new();
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| format!("read {} failed: {}", self.name, e))?
+        {
+            if bytes.len() + chunk.len() > self.max_bytes {
+                return Err(too_large());
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+        serde_json::from_slice(&bytes).map_err(|e| format!("parse {} failed: {}", self.name, e))
+    }
+
+    // 未配置地址时什么都不做。后台拉取，验签通过且不比缓存旧时写缓存，再交给 apply 生效，
+    // 其返回值作为 event 的负载发给前端
+    pub fn refresh<T, F>(
+        &self,
+        app: &tauri::AppHandle,
+        pubkey: String,
+        cached_version: Option<u64>,
+        event: &'static str,
+        apply: F,
+    ) where
+        T: serde::Serialize + Clone,
+        F: FnOnce(&tauri::AppHandle, &M, String) -> T + Send + 'static,
+    {
+        let Some(url) = self.url else {
+            return;
+        };
+        let remote = self.clone();
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move {
+            let log = app.state::<crate::LogState>();
+            let signed = match remote.fetch(url).await {
+                Ok(signed) => signed,
+                Err(e) => {
+                    log.log_app("WARN", &e);
+                    return;
+                }
+            };
+            let manifest = match remote.verify(&signed, &pubkey) {
+                Ok(manifest) => manifest,
+                Err(e) => {
+                    log.log_app("WARN", &format!("remote {} rejected: {}", remote.name, e));
+                    return;
+                }
+            };
+            if let Some(cached) = cached_version.filter(|cached| manifest.version() < *cached) {
+                log.log_app(
+                    "WARN",
+                    &format!(
+                        "remote {} v{} is older than cached v{}, ignored",
+                        remote.name,
+                        manifest.version(),
+                        cached
+                    ),
+                );
+                return;
+            }
+            let fetched_at = chrono::Local::now().to_rfc3339();
+            let mut cache = remote.read();
+            cache.manifest = Some(signed);
+            cache.fetched_at = Some(fetched_at.clone());
+            if let Err(e) = remote.write(&cache) {
+                log.log_app("WARN", &format!("cache {} failed: {}", remote.name, e));
+            }
+            let payload = apply(&app, &manifest, fetched_at);
+            let _ = app.emit(event, payload);
+        });
+    }
+}
@@ -0,0 +1,261 @@
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// 启动前清理上次遗留的 sidecar：应用崩溃或被强杀后，旧的 server 进程可能还占着存储目录和端口。
+// 按可执行文件路径在进程表里找同一个 sidecar，再沿父进程链往上看：链上有正在运行的本应用，说明它属于
+// 另一个还开着的实例，不动；否则视为孤儿直接结束。不依赖 PID 文件，PID 被复用也不会误杀无关进程
+const EXIT_WAIT: Duration = Duration::from_secs(2);
+const EXIT_POLL: Duration = Duration::from_millis(100);
+// 沙箱启动时中间隔着 sandbox-exec/bwrap 等，往上多看几层
+const MAX_ANCESTORS: usize = 8;
+
+struct Process {
+    pid: u32,
+    ppid: u32,
+    // 读不到（权限不足等）时为 None
+    exe: Option<PathBuf>,
+}
+
+fn same_path(a: &Path, b: &Path) -> bool {
+    let a = a.canonicalize().unwrap_or_else(|_| a.to_path_buf());
+    let b = b.canonicalize().unwrap_or_else(|_| b.to_path_buf());
+    if cfg!(windows) {
+        a.to_string_lossy().to_lowercase() == b.to_string_lossy().to_lowercase()
+    } else {
+        a == b
+    }
+}
+
+fn is_one_of(exe: &Option<PathBuf>, paths: &[PathBuf]) -> bool {
+    exe.as_ref()
+        .is_some_and(|exe| paths.iter().any(|path| same_path(exe, path)))
+}
+
+// 父进程链上是否有正在运行的本应用
+fn owned_by_live_app(process: &Process, table: &[Process], app_exe: &Path) -> bool {
+    let mut ppid = process.ppid;
+    for _ in 0..MAX_ANCESTORS {
+        if ppid <= 1 {
+            return false;
+        }
+        let Some(parent) = table.iter().find(|p| p.pid == ppid) else {
+            return false;
+        };
+        if parent
+            .exe
+            .as_ref()
+            .is_some_and(|exe| same_path(exe, app_exe))
+        {
+            return true;
+        }
+        ppid = parent.ppid;
+    }
+    false
+}
+
+fn wait_gone(pid: u32) -> bool {
+    let deadline = Instant::now() + EXIT_WAIT;
+    while Instant::now() < deadline {
+        if !platform::processes(&[]).iter().any(|p| p.pid == pid) {
+            return true;
+        }
+        thread::sleep(EXIT_POLL);
+    }
+    false
+}
+
+// programs 为可能的 sidecar 路径（包内与单独更新的版本）；返回需要写入日志的清理记录
+pub fn cleanup(programs: &[PathBuf]) -> Vec<String> {
+    let mut notes = Vec::new();
+    let app_exe = match std::env::current_exe() {
+        Ok(exe) => exe,
+        Err(e) => {
+            notes.push(format!("skipped: locate app failed: {}", e));
+            return notes;
+        }
+    };
+    let names: Vec<String> = programs
+        .iter()
+        .filter_map(|p| p.file_name().map(|n| n.to_string_lossy().to_lowercase()))
+        .collect();
+    let table = platform::processes(&names);
+    let own_pid = std::process::id();
+    for process in &table {
+        if process.pid == own_pid
+            || !is_one_of(&process.exe, programs)
+            || owned_by_live_app(process, &table, &app_exe)
+        {
+            continue;
+        }
+        let exe = process
+            .exe
+            .as_ref()
+            .map(|p| p.display().to_string())
+            .unwrap_or_default();
+        match crate::supervisor::force_kill(process.pid) {
+            Ok(()) if wait_gone(process.pid) => notes.push(format!(
+                "terminated stale sidecar pid {} (parent {} gone, {})",
+                process.pid, process.ppid, exe
+            )),
+            Ok(()) => notes.push(format!(
+                "stale sidecar pid {} did not exit after kill ({})",
+                process.pid, exe
+            )),
+            Err(e) => notes.push(format!(
+                "kill stale sidecar pid {} failed: {} ({})",
+                process.pid, e, exe
+            )),
+        }
+    }
+    notes
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::Process;
+
+    // /proc/<pid>/stat 中 ')' 之后依次为 state、ppid
+    pub fn processes(_names: &[String]) -> Vec<Process> {
+        let Ok(entries) = std::fs::read_dir("/proc") else {
+            return Vec::new();
+        };
+        entries
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                let pid: u32 = entry.file_name().to_str()?.parse().ok()?;
+                let stat = std::fs::read_to_string(entry.path().join("stat")).ok()?;
+                let ppid = stat
+                    .rsplit_once(')')?
+                    .1
+                    .split_whitespace()
+                    .nth(1)?
+                    .parse()
+                    .ok()?;
+                Some(Process {
+                    pid,
+                    ppid,
+                    exe: std::fs::read_link(entry.path().join("exe")).ok(),
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::path::PathBuf;
+
+    use super::Process;
+
+    // macOS 的 ps comm 列是可执行文件的完整路径，路径可能含空格，放在最后一列
+    pub fn processes(_names: &[String]) -> Vec<Process> {
+        let Ok(output) = std::process::Command::new("/bin/ps")
+            .args(["-axo", "pid=,ppid=,comm="])
+            .output()
+        else {
+            return Vec::new();
+        };
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| {
+                let mut parts = line.trim_start().splitn(2, char::is_whitespace);
+                let pid = parts.next()?.parse().ok()?;
+                let rest = parts.next()?.trim_start();
+                let mut parts = rest.splitn(2, char::is_whitespace);
+                let ppid = parts.next()?.parse().ok()?;
+                let comm = parts.next().map(str::trim).unwrap_or_default();
+                Some(Process {
+                    pid,
+                    ppid,
+                    exe: (!comm.is_empty()).then(|| PathBuf::from(comm)),
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use std::path::PathBuf;
+
+    use windows::core::PWSTR;
+    use windows::Win32::Foundation::CloseHandle;
+    use windows::Win32::System::Diagnostics::ToolHelp::{
+        CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W,
+        TH32CS_SNAPPROCESS,
+    };
+    use windows::Win32::System::Threading::{
+        OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32,
+        PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+
+    use super::Process;
+
+    fn image_path(pid: u32) -> Option<PathBuf> {
+        unsafe {
+            let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid).ok()?;
+            let mut buf = [0u16; 1024];
+            let mut len = buf.len() as u32;
+            let result = QueryFullProcessImageNameW(
+                process,
+                PROCESS_NAME_WIN32,
+                PWSTR(buf.as_mut_ptr()),
+                &mut len,
+            );
+            let _ = CloseHandle(process);
+            result.ok()?;
+            Some(PathBuf::from(String::from_utf16_lossy(
+                &buf[..len as usize],
+            )))
+        }
+    }
+
+    // 只为 sidecar 与本应用同名的进程查完整路径，其余进程逐个 OpenProcess 没有必要
+    pub fn processes(names: &[String]) -> Vec<Process> {
+        let app_name = std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.file_name().map(|n| n.to_string_lossy().to_lowercase()));
+        let mut table = Vec::new();
+        unsafe {
+            let Ok(snapshot) = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0) else {
+                return table;
+            };
+            let mut entry = PROCESSENTRY32W {
+                dwSize: std::mem::size_of::<PROCESSENTRY32W>() as u32,
+                ..Default::default()
+            };
+            let mut more = Process32FirstW(snapshot, &mut entry).is_ok();
+            while more {
+                let len = entry
+                    .szExeFile
+                    .iter()
+                    .position(|&c| c == 0)
+                    .unwrap_or(entry.szExeFile.len());
+                let name = String::from_utf16_lossy(&entry.szExeFile[..len]).to_lowercase();
+                let wanted = names.contains(&name) || app_name.as_deref() == Some(name.as_str());
+                table.push(Process {
+                    pid: entry.th32ProcessID,
+                    ppid: entry.th32ParentProcessID,
+                    exe: if wanted {
+                        image_path(entry.th32ProcessID)
+                    } else {
+                        None
+                    },
+                });
+                more = Process32NextW(snapshot, &mut entry).is_ok();
+            }
+            let _ = CloseHandle(snapshot);
+        }
+        table
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+mod platform {
+    use super::Process;
+
+    pub fn processes(_names: &[String]) -> Vec<Process> {
+        Vec::new()
+    }
+}
@@ -0,0 +1,162 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Instant;
+
+// 本地运行统计（AppData/stats.json），只给“关于/诊断”页面和排查问题用，不上传
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct LifetimeStats {
+    pub sessions: u64,
+    // 已结束会话的累计运行时长
+    pub uptime_secs: u64,
+    pub generations: u64,
+    pub sidecar_restarts: u64,
+    pub sidecar_crashes: u64,
+    // 上次没有正常退出（崩溃、强杀、断电）的次数
+    pub app_crashes: u64,
+}
+
+// 落盘格式：累计统计 + 本次会话的运行标记与时长
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+struct StatsFile {
+    #[serde(flatten)]
+    lifetime: LifetimeStats,
+    running: bool,
+    open_session_secs: u64,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize)]
+pub struct SessionCounters {
+    pub generations: u64,
+    pub sidecar_starts: u64,
+    pub sidecar_restarts: u64,
+    pub sidecar_crashes: u64,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct SessionStats {
+    pub started_at: String,
+    pub uptime_secs: u64,
+    #[serde(flatten)]
+    pub counters: SessionCounters,
+    pub previous_session_crashed: bool,
+    pub lifetime: LifetimeStats,
+}
+
+struct Inner {
+    lifetime: LifetimeStats,
+    session: SessionCounters,
+    shutting_down: bool,
+}
+
+pub struct StatsStore {
+    path: PathBuf,
+    started: Instant,
+    started_at: String,
+    previous_session_crashed: bool,
+    inner: Mutex<Inner>,
+}
+
+pub struct StatsState(pub StatsStore);
+
+impl StatsStore {
+    // 上次会话的时长计入累计；上次仍标记为运行中说明没有正常退出
+    pub fn load(path: PathBuf) -> Self {
+        let file: StatsFile = fs::read(&path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+        let mut lifetime = file.lifetime;
+        lifetime.uptime_secs += file.open_session_secs;
+        if file.running {
+            lifetime.app_crashes += 1;
+        }
+        lifetime.sessions += 1;
+        let store = Self {
+            path,
+            started: Instant::now(),
+            started_at: chrono::Local::now().to_rfc3339(),
+            previous_session_crashed: file.running,
+            inner: Mutex::new(Inner {
+                lifetime,
+                session: SessionCounters::default(),
+                shutting_down: false,
+            }),
+        };
+        store.update(|_| {});
+        store
+    }
+
+    fn uptime_secs(&self) -> u64 {
+        self.started.elapsed().as_secs()
+    }
+
+    // 计数很少变，每次变化直接落盘
+    fn update(&self, f: impl FnOnce(&mut Inner)) {
+        let Ok(mut inner) = self.inner.lock() else {
+            return;
+        };
+        f(&mut inner);
+        let file = StatsFile {
+            lifetime: inner.lifetime.clone(),
+            running: !inner.shutting_down,
+            open_session_secs: self.uptime_secs(),
+        };
+        if let Ok(bytes) = serde_json::to_vec_pretty(&file) {
+            let _ = crate::storage::write_atomic(&self.path, &bytes);
+        }
+    }
+
+    pub fn generation_started(&self) {
+        self.update(|inner| {
+            inner.session.generations += 1;
+            inner.lifetime.generations += 1;
+        });
+    }
+
+    pub fn sidecar_started(&self) {
+        self.update(|inner| {
+            inner.session.sidecar_starts += 1;
+            if inner.session.sidecar_starts > 1 {
+                inner.session.sidecar_restarts += 1;
+                inner.lifetime.sidecar_restarts += 1;
+            }
+        });
+    }
+
+    // 应用退出过程中杀掉后端不算崩溃
+    pub fn sidecar_terminated(&self, code: Option<i32>) {
+        self.update(|inner| {
+            if !inner.shutting_down && code != Some(0) {
+                inner.session.sidecar_crashes += 1;
+                inner.lifetime.sidecar_crashes += 1;
+            }
+        });
+    }
+
+    // 正常退出：清掉运行中标记，本次时长留到下次启动时计入累计
+    pub fn mark_exit(&self) {
+        self.update(|inner| inner.shutting_down = true);
+    }
+
+    pub fn snapshot(&self) -> SessionStats {
+        let uptime_secs = self.uptime_secs();
+        let (counters, lifetime) = self
+            .inner
+            .lock()
+            .map(|inner| (inner.session.clone(), inner.lifetime.clone()))
+            .unwrap_or_default();
+        SessionStats {
+            started_at: self.started_at.clone(),
+            uptime_secs,
+            counters,
+            previous_session_crashed: self.previous_session_crashed,
+            lifetime: LifetimeStats {
+                uptime_secs: lifetime.uptime_secs + uptime_secs,
+                ..lifetime
+            },
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+// 存储后端抽象：命令与自定义协议只认逻辑 ID（如 ref_images/xxx.png、storage/local/xxx.jpg），
+// 具体落在哪里由后端决定。后续接入 S3/WebDAV 时新增实现即可，不需要改动各个命令。
+pub trait Storage: Send + Sync {
+    fn put(&self, id: &str, bytes: &[u8]) -> Result<(), String>;
+
+    // 原地改写已有文件：先写临时文件再替换，中途崩溃不会留下被截断的原件；不支持的后端退回 put
+    fn replace(&self, id: &str, bytes: &[u8]) -> Result<(), String> {
+        self.put(id, bytes)
+    }
+
+    fn get(&self, id: &str) -> Result<Vec<u8>, String>;
+
+    // 读取 [start, start + len) 区间（视频 Range 请求），超出文件末尾的部分截断
+    fn get_range(&self, id: &str, start: u64, len: u64) -> Result<Vec<u8>, String>;
+
+    fn exists(&self, id: &str) -> bool;
+
+    // 删除不存在的 ID 视为成功
+    fn delete(&self, id: &str) -> Result<(), String>;
+
+    // 目标已存在时覆盖，调用方负责先做冲突检查
+    fn rename(&self, from: &str, to: &str) -> Result<(), String>;
+
+    // 文件元信息（大小、修改时间），用于缓存失效判断；不存在时返回 None
+    fn stat(&self, id: &str) -> Option<StorageMeta>;
+
+    // 本地后端可以直接给出磁盘路径（剪贴板、系统打开等需要真实文件的场景），远端后端返回 None
+    fn local_path(&self, id: &str) -> Option<PathBuf>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StorageMeta {
+    pub len: u64,
+    pub modified: Option<SystemTime>,
+}
+
+pub struct StorageState(pub Arc<dyn Storage>);
+
+// 规范化逻辑 ID：统一使用 `/` 分隔，拒绝绝对路径与 `..`，避免越出存储根目录
+pub fn normalize_id(id: &str) -> Result<String, String> {
+    let unified = id.trim().replace('\\', "/");
+    let trimmed = unified.trim_start_matches('/');
+    if trimmed.is_empty() {
+        return Err("storage id is empty".to_string());
+    }
+
+    let mut parts: Vec<&str> = Vec::new();
+    for component in Path::new(trimmed).components() {
+        match component {
+            Component::Normal(part) => {
+                let Some(part) = part.to_str() else {
+                    return Err(format!("storage id invalid: {}", id));
+                };
+                parts.push(part);
+            }
+            Component::CurDir => {}
+            _ => return Err(format!("storage id invalid: {}", id)),
+        }
+    }
+    if parts.is_empty() {
+        return Err("storage id is empty".to_string());
+    }
+    Ok(parts.join("/"))
+}
+
+// 本地文件系统后端：逻辑 ID 即相对 AppData 的路径，与后端数据库中的 storage/local/xxx.jpg 保持一致
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    fn resolve(&self, id: &str) -> Result<PathBuf, String> {
+        let id = normalize_id(id)?;
+        Ok(self.root.join(id))
+    }
+
+    // 实际读写用的路径：数据目录很深时在 Windows 上加 \\?\ 前缀；local_path 仍返回普通路径
+    fn fs_path(&self, id: &str) -> Result<PathBuf, String> {
+        self.resolve(id).map(|path| crate::long_path::extend(&path))
+    }
+}
+
+impl Storage for LocalStorage {
+    fn put(&self, id: &str, bytes: &[u8]) -> Result<(), String> {
+        let path = self.fs_path(id)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("create dir failed: {} ({})", e, parent.display()))?;
+        }
+        fs::write(&path, bytes)
+            .map_err(|e| format!("write file failed: {} ({})", e, path.display()))
+    }
+
+    fn replace(&self, id: &str, bytes: &[u8]) -> Result<(), String> {
+        write_atomic(&self.resolve(id)?, bytes)
+    }
+
+    fn get(&self, id: &str) -> Result<Vec<u8>, String> {
+        let path = self.fs_path(id)?;
+        fs::read(&path).map_err(|e| format!("read file failed: {} ({})", e, path.display()))
+    }
+
+    fn get_range(&self, id: &str, start: u64, len: u64) -> Result<Vec<u8>, String> {
+        use std::io::{Read, Seek, SeekFrom};
+
+        let path = self.fs_path(id)?;
+        let mut file = fs::File::open(&path)
+            .map_err(|e| format!("read file failed: {} ({})", e, path.display()))?;
+        file.seek(SeekFrom::Start(start))
+            .map_err(|e| format!("seek file failed: {} ({})", e, path.display()))?;
+        let mut bytes = Vec::with_capacity(len.min(16 * 1024 * 1024) as usize);
+        file.take(len)
+            .read_to_end(&mut bytes)
+            .map_err(|e| format!("read file failed: {} ({})", e, path.display()))?;
+        Ok(bytes)
+    }
+
+    fn exists(&self, id: &str) -> bool {
+        self.fs_path(id).map(|p| p.is_file()).unwrap_or(false)
+    }
+
+    fn delete(&self, id: &str) -> Result<(), String> {
+        let path = self.fs_path(id)?;
+        match fs::remove_file(&path) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(format!("delete file failed: {} ({})", e, path.display())),
+        }
+    }
+
+    fn rename(&self, from: &str, to: &str) -> Result<(), String> {
+        let src = self.fs_path(from)?;
+        let dst = self.fs_path(to)?;
+        if let Some(parent) = dst.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("create dir failed: {} ({})", e, parent.display()))?;
+        }
+        fs::rename(&src, &dst).map_err(|e| {
+            format!(
+                "rename file failed: {} ({} -> {})",
+                e,
+                src.display(),
+                dst.display()
+            )
+        })
+    }
+
+    fn stat(&self, id: &str) -> Option<StorageMeta> {
+        let meta = fs::metadata(self.fs_path(id).ok()?).ok()?;
+        if !meta.is_file() {
+            return None;
+        }
+        Some(StorageMeta {
+            len: meta.len(),
+            modified: meta.modified().ok(),
+        })
+    }
+
+    fn local_path(&self, id: &str) -> Option<PathBuf> {
+        self.resolve(id).ok()
+    }
+}
+
+// 先写同目录临时文件再 rename，写到一半中断也不会留下损坏的目标文件
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    let path = &crate::long_path::extend(path);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("create dir failed: {} ({})", e, parent.display()))?;
+    }
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp = path.with_file_name(tmp_name);
+    if let Err(e) = fs::write(&tmp, bytes) {
+        let _ = fs::remove_file(&tmp);
+        return Err(format!("write file failed: {} ({})", e, tmp.display()));
+    }
+    fs::rename(&tmp, path).map_err(|e| {
+        let _ = fs::remove_file(&tmp);
+        format!("rename file failed: {} ({})", e, path.display())
+    })
+}
@@ -0,0 +1,547 @@
+// 数位板压感/倾斜桥接：WebView 在 Windows Ink 关闭或部分驱动下拿不到 PointerEvent 的 pressure/tilt，
+// macOS 的 WKWebView 也不转发倾斜。蒙版/局部重绘画布获得焦点时由前端调用 start，原生层直接监听系统的
+// 数位板输入并向该窗口发出 stylus-input 事件，失去焦点时调用 stop。坐标为窗口内容区的 CSS 像素
+#[cfg_attr(not(any(target_os = "macos", target_os = "windows")), allow(dead_code))]
+pub const EVENT: &str = "stylus-input";
+
+#[cfg_attr(not(any(target_os = "macos", target_os = "windows")), allow(dead_code))]
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct StylusInput {
+    pub x: f64,
+    pub y: f64,
+    // 0..1
+    pub pressure: f64,
+    // -1..1，设备没有倾斜传感器时为 0
+    pub tilt_x: f64,
+    pub tilt_y: f64,
+    // 笔杆旋转角度（度），设备不支持时为 0
+    pub rotation: f64,
+    // pen / eraser
+    pub pointer: &'static str,
+    // 笔尖是否接触板面；悬停时为 false
+    pub contact: bool,
+}
+
+// 返回 true 表示已开始原生监听；不支持的平台返回 false，前端继续用 PointerEvent
+pub fn start(window: &tauri::WebviewWindow) -> Result<bool, String> {
+    platform::start(window)
+}
+
+pub fn stop(app: &tauri::AppHandle) -> Result<(), String> {
+    platform::stop(app)
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::cell::{Cell, RefCell};
+    use std::ptr::NonNull;
+
+    use block2::RcBlock;
+    use objc2::rc::Retained;
+    use objc2::runtime::AnyObject;
+    use objc2::MainThreadMarker;
+    use objc2_app_kit::{NSEvent, NSEventMask, NSEventSubtype, NSEventType, NSPointingDeviceType};
+    use tauri::{Emitter, Manager};
+
+    use super::{StylusInput, EVENT};
+
+    // 监听器只在主线程上注册和移除
+    thread_local! {
+        static MONITOR: RefCell<Option<Retained<AnyObject>>> = const { RefCell::new(None) };
+        static ERASER: Cell<bool> = const { Cell::new(false) };
+    }
+
+    fn remove_monitor() {
+        MONITOR.with(|monitor| {
+            if let Some(monitor) = monitor.borrow_mut().take() {
+                unsafe { NSEvent::removeMonitor(&monitor) };
+            }
+        });
+    }
+
+    fn handle(app: &tauri::AppHandle, label: &str, ns_window: usize, event: &NSEvent) {
+        let kind = event.r#type();
+        // 笔靠近板面时报告设备类型，之后的点事件沿用
+        if kind == NSEventType::TabletProximity {
+            ERASER.set(event.pointingDeviceType() == NSPointingDeviceType::Eraser);
+            return;
+        }
+        let tablet =
+            kind == NSEventType::TabletPoint || event.subtype() == NSEventSubtype::TabletPoint;
+        if !tablet {
+            return;
+        }
+        let Some(mtm) = MainThreadMarker::new() else {
+            return;
+        };
+        let Some(window) = event.window(mtm) else {
+            return;
+        };
+        if Retained::as_ptr(&window) as usize != ns_window {
+            return;
+        }
+        // AppKit 的窗口坐标原点在左下角
+        let height = window
+            .contentView()
+            .map(|view| view.frame().size.height)
+            .unwrap_or(0.0);
+        let location = event.locationInWindow();
+        let tilt = event.tilt();
+        let pressure = event.pressure() as f64;
+        let contact = match kind {
+            NSEventType::LeftMouseDown | NSEventType::LeftMouseDragged => true,
+            NSEventType::LeftMouseUp => false,
+            _ => pressure > 0.0,
+        };
+        let input = StylusInput {
+            x: location.x,
+            y: height - location.y,
+            pressure: pressure.clamp(0.0, 1.0),
+            tilt_x: tilt.x.clamp(-1.0, 1.0),
+            tilt_y: tilt.y.clamp(-1.0, 1.0),
+            rotation: event.rotation() as f64,
+            pointer: if ERASER.get() { "eraser" } else { "pen" },
+            contact,
+        };
+        let _ = app.emit_to(label, EVENT, input);
+    }
+
+    pub fn start(window: &tauri::WebviewWindow) -> Result<bool, String> {
+        let ns_window = window
+            .ns_window()
+            .map_err(|e| format!("start stylus input failed: {}", e))?
+            as usize;
+        let app = window.app_handle().clone();
+        let label = window.label().to_string();
+        window
+            .run_on_main_thread(move || {
+                remove_monitor();
+                let mask = NSEventMask::LeftMouseDown
+                    | NSEventMask::LeftMouseUp
+                    | NSEventMask::LeftMouseDragged
+                    | NSEventMask::TabletPoint
+                    | NSEventMask::TabletProximity;
+                // 本地监听器只看不改，事件照常交给 WebView
+                let block = RcBlock::new(move |event: NonNull<NSEvent>| -> *mut NSEvent {
+                    handle(&app, &label, ns_window, unsafe { event.as_ref() });
+                    event.as_ptr()
+                });
+                let monitor =
+                    unsafe { NSEvent::addLocalMonitorForEventsMatchingMask_handler(mask, &block) };
+                MONITOR.with(|slot| *slot.borrow_mut() = monitor);
+            })
+            .map_err(|e| format!("start stylus input failed: {}", e))?;
+        Ok(true)
+    }
+
+    pub fn stop(app: &tauri::AppHandle) -> Result<(), String> {
+        app.run_on_main_thread(remove_monitor)
+            .map_err(|e| format!("stop stylus input failed: {}", e))
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::sync::{Mutex, OnceLock};
+    use std::thread;
+
+    use tauri::{Emitter, Manager};
+    use windows::core::w;
+    use windows::Win32::Devices::HumanInterfaceDevice::{
+        HidP_GetSpecificValueCaps, HidP_GetUsageValue, HidP_GetUsages, HidP_Input,
+        HIDP_STATUS_SUCCESS, HIDP_VALUE_CAPS, PHIDP_PREPARSED_DATA,
+    };
+    use windows::Win32::Foundation::{HANDLE, HINSTANCE, HWND, LPARAM, LRESULT, POINT, WPARAM};
+    use windows::Win32::Graphics::Gdi::ScreenToClient;
+    use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+    use windows::Win32::UI::Input::{
+        GetRawInputData, GetRawInputDeviceInfoW, RegisterRawInputDevices, HRAWINPUT, RAWINPUT,
+        RAWINPUTDEVICE, RAWINPUTHEADER, RIDEV_INPUTSINK, RIDI_PREPARSEDDATA, RID_INPUT,
+        RIM_TYPEHID,
+    };
+    use windows::Win32::UI::WindowsAndMessaging::{
+        CreateWindowExW, DefWindowProcW, DispatchMessageW, GetCursorPos, GetForegroundWindow,
+        GetMessageW, RegisterClassW, HWND_MESSAGE, MSG, WINDOW_EX_STYLE, WINDOW_STYLE, WM_INPUT,
+        WNDCLASSW,
+    };
+
+    use super::{StylusInput, EVENT};
+
+    // HID 数字化仪（Digitizer）用途页与笔相关的用途
+    const PAGE_DIGITIZER: u16 = 0x0D;
+    const USAGE_PEN: u16 = 0x02;
+    const USAGE_TIP_PRESSURE: u16 = 0x30;
+    const USAGE_INVERT: u16 = 0x3C;
+    const USAGE_X_TILT: u16 = 0x3D;
+    const USAGE_Y_TILT: u16 = 0x3E;
+    const USAGE_TWIST: u16 = 0x41;
+    const USAGE_TIP_SWITCH: u16 = 0x42;
+    const USAGE_ERASER: u16 = 0x45;
+    const MAX_USAGES: usize = 32;
+
+    // 当前接收事件的窗口；HWND 不是 Send，按地址保存
+    struct Target {
+        app: tauri::AppHandle,
+        label: String,
+        hwnd: isize,
+        scale: f64,
+    }
+
+    static TARGET: Mutex<Option<Target>> = Mutex::new(None);
+    // 后台消息窗口只建一次，之后靠 TARGET 开关
+    static LISTENER: OnceLock<Result<(), String>> = OnceLock::new();
+
+    #[derive(Clone, Copy)]
+    struct Range {
+        min: i32,
+        max: i32,
+        bits: u16,
+    }
+
+    struct Device {
+        // HidP_* 需要的预解析数据，按 8 字节对齐存放
+        preparsed: Vec<u64>,
+        pressure: Option<Range>,
+        tilt_x: Option<Range>,
+        tilt_y: Option<Range>,
+        twist: Option<Range>,
+    }
+
+    impl Device {
+        fn data(&self) -> PHIDP_PREPARSED_DATA {
+            PHIDP_PREPARSED_DATA(self.preparsed.as_ptr() as isize)
+        }
+
+        // 逻辑最小值为负时按位宽做符号扩展
+        fn value(&self, range: Option<Range>, usage: u16, report: &[u8]) -> Option<(f64, Range)> {
+            let range = range?;
+            let mut raw = 0u32;
+            let status = unsafe {
+                HidP_GetUsageValue(
+                    HidP_Input,
+                    PAGE_DIGITIZER,
+                    None,
+                    usage,
+                    &mut raw,
+                    self.data(),
+                    report,
+                )
+            };
+            if status != HIDP_STATUS_SUCCESS {
+                return None;
+            }
+            let value = if range.min < 0 && range.bits > 0 && range.bits < 32 {
+                let shift = 32 - range.bits as u32;
+                ((raw << shift) as i32) >> shift
+            } else {
+                raw as i32
+            };
+            Some((value as f64, range))
+        }
+    }
+
+    // 每个设备的预解析数据只取一次；取不到的设备记为 None，之后直接忽略
+    thread_local! {
+        static DEVICES: RefCell<HashMap<isize, Option<Device>>> = RefCell::new(HashMap::new());
+    }
+
+    fn value_range(data: PHIDP_PREPARSED_DATA, usage: u16) -> Option<Range> {
+        let mut caps = [HIDP_VALUE_CAPS::default(); 4];
+        let mut len = caps.len() as u16;
+        let status = unsafe {
+            HidP_GetSpecificValueCaps(
+                HidP_Input,
+                Some(PAGE_DIGITIZER),
+                None,
+                Some(usage),
+                caps.as_mut_ptr(),
+                &mut len,
+                data,
+            )
+        };
+        let cap = caps[0];
+        (status == HIDP_STATUS_SUCCESS && len > 0 && cap.LogicalMax > cap.LogicalMin).then_some(
+            Range {
+                min: cap.LogicalMin,
+                max: cap.LogicalMax,
+                bits: cap.BitSize,
+            },
+        )
+    }
+
+    fn load_device(handle: HANDLE) -> Option<Device> {
+        let mut size = 0u32;
+        unsafe { GetRawInputDeviceInfoW(Some(handle), RIDI_PREPARSEDDATA, None, &mut size) };
+        if size == 0 {
+            return None;
+        }
+        let mut preparsed = vec![0u64; (size as usize).div_ceil(8)];
+        let read = unsafe {
+            GetRawInputDeviceInfoW(
+                Some(handle),
+                RIDI_PREPARSEDDATA,
+                Some(preparsed.as_mut_ptr().cast()),
+                &mut size,
+            )
+        };
+        if read == 0 || read == u32::MAX {
+            return None;
+        }
+        let data = PHIDP_PREPARSED_DATA(preparsed.as_ptr() as isize);
+        Some(Device {
+            pressure: value_range(data, USAGE_TIP_PRESSURE),
+            tilt_x: value_range(data, USAGE_X_TILT),
+            tilt_y: value_range(data, USAGE_Y_TILT),
+            twist: value_range(data, USAGE_TWIST),
+            preparsed,
+        })
+    }
+
+    fn parse(device: &Device, report: &mut [u8], x: f64, y: f64) -> StylusInput {
+        let mut usages = [0u16; MAX_USAGES];
+        let mut len = MAX_USAGES as u32;
+        let status = unsafe {
+            HidP_GetUsages(
+                HidP_Input,
+                PAGE_DIGITIZER,
+                None,
+                usages.as_mut_ptr(),
+                &mut len,
+                device.data(),
+                report,
+            )
+        };
+        let pressed: &[u16] = if status == HIDP_STATUS_SUCCESS {
+            &usages[..(len as usize).min(MAX_USAGES)]
+        } else {
+            &[]
+        };
+        let pressure = device
+            .value(device.pressure, USAGE_TIP_PRESSURE, report)
+            .map(|(v, r)| ((v - r.min as f64) / (r.max - r.min) as f64).clamp(0.0, 1.0))
+            .unwrap_or(0.0);
+        let tilt = |range, usage| {
+            device
+                .value(range, usage, report)
+                .map(|(v, r): (f64, Range)| {
+                    let extent = r.min.unsigned_abs().max(r.max.unsigned_abs()) as f64;
+                    (v / extent).clamp(-1.0, 1.0)
+                })
+                .unwrap_or(0.0)
+        };
+        let rotation = device
+            .value(device.twist, USAGE_TWIST, report)
+            .map(|(v, r)| (v - r.min as f64) / (r.max - r.min) as f64 * 360.0)
+            .unwrap_or(0.0);
+        let eraser = pressed.contains(&USAGE_ERASER) || pressed.contains(&USAGE_INVERT);
+        StylusInput {
+            x,
+            y,
+            pressure,
+            tilt_x: tilt(device.tilt_x, USAGE_X_TILT),
+            tilt_y: tilt(device.tilt_y, USAGE_Y_TILT),
+            rotation,
+            pointer: if eraser { "eraser" } else { "pen" },
+            contact: if status == HIDP_STATUS_SUCCESS {
+                pressed.contains(&USAGE_TIP_SWITCH)
+            } else {
+                pressure > 0.0
+            },
+        }
+    }
+
+    fn handle_input(handle: HRAWINPUT) {
+        let target = TARGET.lock().ok().and_then(|target| {
+            target
+                .as_ref()
+                .map(|t| (t.app.clone(), t.label.clone(), t.hwnd, t.scale))
+        });
+        let Some((app, label, hwnd, scale)) = target else {
+            return;
+        };
+        // 只在画布所在窗口处于前台时转发
+        let hwnd = HWND(hwnd as *mut _);
+        if unsafe { GetForegroundWindow() } != hwnd {
+            return;
+        }
+        let header_size = std::mem::size_of::<RAWINPUTHEADER>() as u32;
+        let mut size = 0u32;
+        unsafe { GetRawInputData(handle, RID_INPUT, None, &mut size, header_size) };
+        if size == 0 {
+            return;
+        }
+        let mut buf = vec![0u64; (size as usize).div_ceil(8)];
+        let read = unsafe {
+            GetRawInputData(
+                handle,
+                RID_INPUT,
+                Some(buf.as_mut_ptr().cast()),
+                &mut size,
+                header_size,
+            )
+        };
+        if read == 0 || read == u32::MAX {
+            return;
+        }
+        let raw = unsafe { &*(buf.as_ptr() as *const RAWINPUT) };
+        if raw.header.dwType != RIM_TYPEHID.0 {
+            return;
+        }
+        let (report_size, count) = unsafe {
+            (
+                raw.data.hid.dwSizeHid as usize,
+                raw.data.hid.dwCount as usize,
+            )
+        };
+        let start = unsafe { std::ptr::addr_of!(raw.data.hid.bRawData) as *const u8 };
+        let offset = start as usize - buf.as_ptr() as usize;
+        if report_size == 0 || offset + report_size * count > read as usize {
+            return;
+        }
+        let reports = unsafe { std::slice::from_raw_parts(start, report_size * count) };
+
+        // 笔同时驱动系统光标，用光标位置换算成窗口坐标，省去按屏幕映射数字化仪的逻辑坐标
+        let mut cursor = POINT::default();
+        if unsafe { GetCursorPos(&mut cursor) }.is_err()
+            || !unsafe { ScreenToClient(hwnd, &mut cursor) }.as_bool()
+        {
+            return;
+        }
+        let (x, y) = (cursor.x as f64 / scale, cursor.y as f64 / scale);
+        let device_key = raw.header.hDevice.0 as isize;
+        DEVICES.with(|devices| {
+            let mut devices = devices.borrow_mut();
+            let device = devices
+                .entry(device_key)
+                .or_insert_with(|| load_device(raw.header.hDevice));
+            let Some(device) = device else {
+                return;
+            };
+            for report in reports.chunks(report_size) {
+                let mut report = report.to_vec();
+                let input = parse(device, &mut report, x, y);
+                let _ = app.emit_to(label.as_str(), EVENT, input);
+            }
+        });
+    }
+
+    unsafe extern "system" fn wndproc(
+        hwnd: HWND,
+        msg: u32,
+        wparam: WPARAM,
+        lparam: LPARAM,
+    ) -> LRESULT {
+        if msg == WM_INPUT {
+            handle_input(HRAWINPUT(lparam.0 as *mut _));
+        }
+        // WM_INPUT 也要交给默认处理，系统才会释放输入缓冲
+        unsafe { DefWindowProcW(hwnd, msg, wparam, lparam) }
+    }
+
+    // 建一个只收消息的隐藏窗口，以 INPUTSINK 方式注册笔的原始输入：不依赖 WebView2 转发，
+    // 也不用子类化 tao 的窗口过程
+    fn spawn_listener() -> Result<(), String> {
+        let (tx, rx) = std::sync::mpsc::channel();
+        thread::spawn(move || unsafe {
+            let instance = match GetModuleHandleW(None) {
+                Ok(module) => HINSTANCE(module.0),
+                Err(e) => {
+                    let _ = tx.send(Err(format!("get module handle failed: {}", e)));
+                    return;
+                }
+            };
+            let class_name = w!("NanoBananaStylusInput");
+            let class = WNDCLASSW {
+                lpfnWndProc: Some(wndproc),
+                hInstance: instance,
+                lpszClassName: class_name,
+                ..Default::default()
+            };
+            if RegisterClassW(&class) == 0 {
+                let _ = tx.send(Err("register stylus window class failed".to_string()));
+                return;
+            }
+            let hwnd = match CreateWindowExW(
+                WINDOW_EX_STYLE(0),
+                class_name,
+                w!(""),
+                WINDOW_STYLE(0),
+                0,
+                0,
+                0,
+                0,
+                Some(HWND_MESSAGE),
+                None,
+                Some(instance),
+                None,
+            ) {
+                Ok(hwnd) => hwnd,
+                Err(e) => {
+                    let _ = tx.send(Err(format!("create stylus window failed: {}", e)));
+                    return;
+                }
+            };
+            let device = RAWINPUTDEVICE {
+                usUsagePage: PAGE_DIGITIZER,
+                usUsage: USAGE_PEN,
+                dwFlags: RIDEV_INPUTSINK,
+                hwndTarget: hwnd,
+            };
+            if let Err(e) =
+                RegisterRawInputDevices(&[device], std::mem::size_of::<RAWINPUTDEVICE>() as u32)
+            {
+                let _ = tx.send(Err(format!("register raw input failed: {}", e)));
+                return;
+            }
+            let _ = tx.send(Ok(()));
+            let mut msg = MSG::default();
+            while GetMessageW(&mut msg, None, 0, 0).as_bool() {
+                DispatchMessageW(&msg);
+            }
+        });
+        rx.recv()
+            .unwrap_or_else(|_| Err("stylus listener exited".to_string()))
+    }
+
+    pub fn start(window: &tauri::WebviewWindow) -> Result<bool, String> {
+        LISTENER
+            .get_or_init(spawn_listener)
+            .clone()
+            .map_err(|e| format!("start stylus input failed: {}", e))?;
+        let hwnd = window
+            .hwnd()
+            .map_err(|e| format!("start stylus input failed: {}", e))?;
+        let scale = window.scale_factor().unwrap_or(1.0);
+        let mut target = TARGET
+            .lock()
+            .map_err(|_| "stylus input lock poisoned".to_string())?;
+        *target = Some(Target {
+            app: window.app_handle().clone(),
+            label: window.label().to_string(),
+            hwnd: hwnd.0 as isize,
+            scale,
+        });
+        Ok(true)
+    }
+
+    pub fn stop(_app: &tauri::AppHandle) -> Result<(), String> {
+        if let Ok(mut target) = TARGET.lock() {
+            *target = None;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+mod platform {
+    // WebKitGTK 会把 XInput 的压感交给 PointerEvent，原生层不再另行监听
+    pub fn start(_window: &tauri::WebviewWindow) -> Result<bool, String> {
+        Ok(false)
+    }
+
+    pub fn stop(_app: &tauri::AppHandle) -> Result<(), String> {
+        Ok(())
+    }
+}
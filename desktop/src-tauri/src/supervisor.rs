@@ -0,0 +1,1316 @@
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use tauri::{Emitter, Manager};
+use tauri_plugin_shell::process::{CommandChild, CommandEvent, TerminatedPayload};
+use tauri_plugin_shell::ShellExt;
+
+use crate::settings::SidecarSettings;
+
+// 后端 sidecar 的启动与事件处理。启动参数在 setup 阶段确定后存下来，
+// 窗口显示后在后台开始启动后端时发出，前端据此显示「正在连接引擎」，直到收到 backend-port
+pub const STARTING_EVENT: &str = "backend-starting";
+// 进程意外退出或资源超限时按同样的参数再拉起一次；新进程宣布端口后照常发出 backend-port
+pub const RESTARTING_EVENT: &str = "backend-restarting";
+// 手动重启完成、新进程健康检查通过后发出
+pub const RESTARTED_EVENT: &str = "backend-restarted";
+// 配置的固定端口被占用，本次不启动后端
+pub const PORT_UNAVAILABLE_EVENT: &str = "backend-port-unavailable";
+// 后端没能绑定端口就退出，换一个空闲端口重新拉起
+pub const PORT_CONFLICT_EVENT: &str = "backend-port-conflict";
+// 启动后迟迟不宣布端口（卡在初始化里），前端据此弹出重试对话框，重试走 restart_backend
+pub const START_FAILED_EVENT: &str = "backend-start-failed";
+// 短时间内反复崩溃，已停止自动重启，等用户处理
+pub const CRASH_LOOP_EVENT: &str = "backend-crash-loop";
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+// 崩溃重启按 1s、2s、4s… 退避，最长 30s；稳定运行满 1 分钟后退避清零
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+const STABLE_AFTER: Duration = Duration::from_secs(60);
+// 结束 sidecar（退出、重启、更新）时先关闭标准输入并发 SIGTERM，后端据此停止任务队列与 HTTP 服务
+// （自身最多等 5s），写完数据库与正在保存的图片；超时再强制结束
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(6);
+// 宣布端口之前、启动后这段时间内非零退出，按端口冲突处理
+const EARLY_EXIT: Duration = Duration::from_secs(5);
+// 连续换端口的次数上限，超过后交给普通的崩溃退避
+const MAX_PORT_RETRIES: u32 = 3;
+const DEFAULT_STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_STARTUP_TIMEOUT_SECS: u64 = 600;
+// sidecar.extra_args 的上限；--data-dir / --port 由启动器传入，不允许覆盖
+const MAX_EXTRA_ARGS: usize = 32;
+const MAX_EXTRA_ARG_LEN: usize = 1024;
+const RESERVED_ARGS: [&str; 2] = ["--data-dir", "--port"];
+// backend-start-failed 附带的 stderr 行数
+const STDERR_TAIL: usize = 20;
+// 一直不换行的输出超过这个长度就按一行处理，避免缓冲无限增长
+const MAX_LINE_BYTES: usize = 64 * 1024;
+// CRASH_LOOP_WINDOW 内意外退出 CRASH_LOOP_LIMIT 次即判定为崩溃循环
+const CRASH_LOOP_LIMIT: usize = 5;
+const CRASH_LOOP_WINDOW: Duration = Duration::from_secs(120);
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct RestartNotice {
+    pub attempt: u32,
+    pub delay_ms: u64,
+    pub reason: String,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct RestartedNotice {
+    // socket 模式下为 0
+    pub port: u16,
+    pub base_url: String,
+    pub generation: u64,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct PortUnavailable {
+    pub port: u16,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct PortConflict {
+    // 后端自行探测端口时为 0
+    pub failed_port: u16,
+    pub port: u16,
+    pub attempt: u32,
+    pub reason: String,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct StartFailed {
+    pub reason: String,
+    pub timeout_ms: u64,
+    pub pid: u32,
+    // 最近的 stderr 输出，旧的在前
+    pub stderr: Vec<String>,
+    // 完整日志所在目录，供对话框里“打开日志”使用
+    pub log_dir: String,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct CrashLoop {
+    pub exits: usize,
+    pub window_ms: u64,
+    pub last_code: Option<i32>,
+    pub last_signal: Option<i32>,
+    // 最后一次运行的 stderr 输出，旧的在前
+    pub stderr: Vec<String>,
+    pub log_dir: String,
+    pub suggestion: String,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct ExitStatus {
+    pub code: Option<i32>,
+    // Unix 上被信号结束时的信号值
+    pub signal: Option<i32>,
+    pub at_ms: u64,
+    // 是否为主动结束（手动重启、热替换、看门狗等），而非崩溃
+    pub requested: bool,
+}
+
+// 诊断面板用的后端进程信息
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct BackendInfo {
+    pub running: bool,
+    pub pid: Option<u32>,
+    // 当前进程的启动时间（Unix 毫秒）与已运行时长，未运行时为 null
+    pub spawned_at_ms: Option<u64>,
+    pub uptime_ms: Option<u64>,
+    // 本次会话内的重启次数（不含首次启动）
+    pub restart_count: u64,
+    pub last_exit: Option<ExitStatus>,
+    pub program: Option<String>,
+    // 连接外部后端时为其端口，此时以上进程信息均为空
+    pub external_port: Option<u16>,
+    // 因崩溃循环已停止自动重启
+    pub crash_loop: bool,
+}
+
+// 把 sidecar 输出拼成整行：CommandEvent 可能只带半行，或一次带多行，只有完整的行才交给解析与日志
+#[derive(Default)]
+pub struct LineBuffer {
+    pending: Vec<u8>,
+}
+
+impl LineBuffer {
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.pending.extend_from_slice(chunk);
+        let mut lines = Vec::new();
+        while let Some(index) = self.pending.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=index).collect();
+            lines.push(Self::decode(&line));
+        }
+        if self.pending.len() > MAX_LINE_BYTES {
+            lines.push(Self::decode(&std::mem::take(&mut self.pending)));
+        }
+        lines
+    }
+
+    // 进程退出时剩下的最后半行
+    pub fn finish(&mut self) -> Option<String> {
+        let rest = std::mem::take(&mut self.pending);
+        let line = Self::decode(&rest);
+        (!line.is_empty()).then_some(line)
+    }
+
+    fn decode(bytes: &[u8]) -> String {
+        String::from_utf8_lossy(bytes)
+            .trim_end_matches(['\r', '\n'])
+            .to_string()
+    }
+}
+
+#[derive(Default)]
+struct ProcessRecord {
+    spawned: Option<(u64, Instant)>,
+    last_exit: Option<ExitStatus>,
+}
+
+// 连续崩溃的退避：每次翻倍，上限 MAX_BACKOFF；进程稳定运行 STABLE_AFTER 后清零
+#[derive(Default)]
+pub struct Backoff {
+    failures: u32,
+    started_at: Option<Instant>,
+}
+
+impl Backoff {
+    pub fn started(&mut self) {
+        self.started_at = Some(Instant::now());
+    }
+
+    pub fn next(&mut self) -> (u32, Duration) {
+        if self
+            .started_at
+            .is_some_and(|at| at.elapsed() >= STABLE_AFTER)
+        {
+            self.failures = 0;
+        }
+        self.failures += 1;
+        let delay = RESTART_DELAY
+            .saturating_mul(1 << (self.failures - 1).min(16))
+            .min(MAX_BACKOFF);
+        (self.failures, delay)
+    }
+
+    pub fn reset(&mut self) {
+        self.failures = 0;
+    }
+}
+
+pub struct LaunchSpec {
+    // 启动时选定的单独更新过的后端；None 表示包内 sidecar。之后可经 hot_swap 替换
+    pub program: Option<PathBuf>,
+    // 安全模式下不继承用户环境变量
+    pub clear_env: bool,
+    pub env: Vec<(String, String)>,
+    // 后端数据目录，与 get_app_data_dir 相同；以 --data-dir 传入并同时设置 NB_DATA_DIR（旧版后端只认环境变量）
+    pub data_dir: PathBuf,
+    // 以 --port 传给后端；启动前先确认端口空闲
+    pub fixed_port: Option<u16>,
+    // 沙箱内允许写入的目录
+    pub writable: Vec<PathBuf>,
+    pub settings: SidecarSettings,
+    // 连接外部后端时不启动 sidecar，重启也由开发者自己负责
+    pub external_port: Option<u16>,
+}
+
+pub struct Supervisor {
+    spec: LaunchSpec,
+    // 每次启动 +1；旧进程的事件循环与监控线程据此判断自己是否已过期
+    generation: AtomicU64,
+    // 主动结束进程时写入原因，进程退出后据此重新拉起
+    restart_reason: Mutex<Option<String>>,
+    backoff: Mutex<Backoff>,
+    // 应用退出中，不再重启
+    stopping: AtomicBool,
+    // 最近一次退出的进程代数，优雅退出时等待它追上当前代
+    exited: (Mutex<u64>, Condvar),
+    // 同一时间只允许一次手动重启
+    manual: Mutex<()>,
+    // 端口冲突后改用的端口，之后的重启沿用
+    port_override: Mutex<Option<u16>>,
+    port_retries: AtomicU32,
+    // 当前使用的后端可执行文件，初始为 spec.program
+    program: Mutex<Option<PathBuf>>,
+    process: Mutex<ProcessRecord>,
+    // 最近几次意外退出的时间
+    crashes: Mutex<VecDeque<Instant>>,
+    // 已判定为崩溃循环，手动重启前不再自动拉起
+    crash_loop: AtomicBool,
+}
+
+pub struct SupervisorState(pub Supervisor);
+
+impl Supervisor {
+    pub fn new(spec: LaunchSpec) -> Self {
+        let program = Mutex::new(spec.program.clone());
+        Self {
+            program,
+            spec,
+            generation: AtomicU64::new(0),
+            restart_reason: Mutex::new(None),
+            backoff: Mutex::new(Backoff::default()),
+            stopping: AtomicBool::new(false),
+            exited: (Mutex::new(0), Condvar::new()),
+            manual: Mutex::new(()),
+            port_override: Mutex::new(None),
+            port_retries: AtomicU32::new(0),
+            process: Mutex::new(ProcessRecord::default()),
+            crashes: Mutex::new(VecDeque::new()),
+            crash_loop: AtomicBool::new(false),
+        }
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    // 结束指定代的进程并在其退出后重新拉起；进程已被替换时忽略
+    pub fn restart(&self, app: &tauri::AppHandle, generation: u64, reason: &str) {
+        if self.generation() != generation {
+            return;
+        }
+        if let Ok(mut pending) = self.restart_reason.lock() {
+            *pending = Some(reason.to_string());
+        }
+        let child = app
+            .state::<crate::SidecarState>()
+            .0
+            .lock()
+            .ok()
+            .and_then(|mut guard| guard.take());
+        // 不阻塞调用方（看门狗、手动重启），进程退出后由事件循环照常重新拉起
+        if let Some(child) = child {
+            let app = app.clone();
+            std::thread::spawn(move || {
+                let state = app.state::<SupervisorState>();
+                state
+                    .0
+                    .stop_gracefully(&app, child, generation, SHUTDOWN_TIMEOUT);
+            });
+        }
+    }
+
+    // 请进程自行退出，超过 timeout 仍未退出再强制结束。返回是否在超时前正常退出
+    fn stop_gracefully(
+        &self,
+        app: &tauri::AppHandle,
+        child: CommandChild,
+        generation: u64,
+        timeout: Duration,
+    ) -> bool {
+        let log_state = app.state::<crate::LogState>();
+        let pid = child.pid();
+        // CommandChild 持有标准输入的写端，drop 即关闭
+        drop(child);
+        if let Err(e) = terminate(pid) {
+            log_state.log_app("WARN", &format!("send SIGTERM to sidecar failed: {}", e));
+        }
+        if self.wait_exited(generation, timeout) {
+            log_state.log_app("INFO", "Sidecar exited gracefully.");
+            return true;
+        }
+        log_state.log_app(
+            "WARN",
+            &format!(
+                "sidecar did not exit within {} ms, killing pid {}",
+                timeout.as_millis(),
+                pid
+            ),
+        );
+        if let Err(e) = force_kill(pid) {
+            log_state.log_app("ERROR", &format!("Failed to kill sidecar: {}", e));
+        }
+        false
+    }
+
+    // 应用退出时调用，避免退出过程中被重新拉起
+    pub fn stop(&self) {
+        self.stopping.store(true, Ordering::SeqCst);
+        if let Ok(mut pending) = self.restart_reason.lock() {
+            *pending = None;
+        }
+    }
+
+    pub fn is_stopping(&self) -> bool {
+        self.stopping.load(Ordering::SeqCst)
+    }
+
+    fn mark_exited(&self, generation: u64) {
+        let (lock, cvar) = &self.exited;
+        if let Ok(mut exited) = lock.lock() {
+            *exited = (*exited).max(generation);
+            cvar.notify_all();
+        }
+    }
+
+    fn wait_exited(&self, generation: u64, timeout: Duration) -> bool {
+        let (lock, cvar) = &self.exited;
+        let Ok(guard) = lock.lock() else {
+            return false;
+        };
+        cvar.wait_timeout_while(guard, timeout, |exited| *exited < generation)
+            .map(|(_, result)| !result.timed_out())
+            .unwrap_or(false)
+    }
+
+    fn mark_started(&self) {
+        if let Ok(mut backoff) = self.backoff.lock() {
+            backoff.started();
+        }
+        if let Ok(mut process) = self.process.lock() {
+            process.spawned = Some((crate::now_ms() as u64, Instant::now()));
+        }
+    }
+
+    // current 为 false 表示退出的是已被替换的旧进程，不动新进程的启动时间
+    fn mark_terminated(&self, status: &TerminatedPayload, current: bool) {
+        let requested = !current
+            || self.stopping.load(Ordering::SeqCst)
+            || self.restart_reason.lock().is_ok_and(|r| r.is_some());
+        if let Ok(mut process) = self.process.lock() {
+            if current {
+                process.spawned = None;
+            }
+            process.last_exit = Some(ExitStatus {
+                code: status.code,
+                signal: status.signal,
+                at_ms: crate::now_ms() as u64,
+                requested,
+            });
+        }
+    }
+
+    pub fn info(&self, pid: Option<u32>) -> BackendInfo {
+        let (spawned, last_exit) = match self.process.lock() {
+            Ok(process) => (process.spawned, process.last_exit.clone()),
+            Err(_) => (None, None),
+        };
+        let running = pid.is_some() && spawned.is_some();
+        BackendInfo {
+            running,
+            pid: pid.filter(|_| running),
+            spawned_at_ms: spawned.map(|(at, _)| at).filter(|_| running),
+            uptime_ms: spawned
+                .map(|(_, at)| at.elapsed().as_millis() as u64)
+                .filter(|_| running),
+            restart_count: self.generation().saturating_sub(1),
+            last_exit,
+            program: self.program().map(|p| p.to_string_lossy().to_string()),
+            external_port: self.spec.external_port,
+            crash_loop: self.crash_loop.load(Ordering::SeqCst),
+        }
+    }
+
+    // 返回本次是第几次连续崩溃与应等待的时长
+    fn next_backoff(&self) -> (u32, Duration) {
+        match self.backoff.lock() {
+            Ok(mut backoff) => backoff.next(),
+            Err(_) => (1, RESTART_DELAY),
+        }
+    }
+
+    fn reset_backoff(&self) {
+        if let Ok(mut backoff) = self.backoff.lock() {
+            backoff.reset();
+        }
+        if let Ok(mut crashes) = self.crashes.lock() {
+            crashes.clear();
+        }
+        self.crash_loop.store(false, Ordering::SeqCst);
+    }
+
+    // 记一次意外退出，返回窗口内的次数
+    fn record_crash(&self) -> usize {
+        let Ok(mut crashes) = self.crashes.lock() else {
+            return 1;
+        };
+        let now = Instant::now();
+        while crashes
+            .front()
+            .is_some_and(|at| now.duration_since(*at) > CRASH_LOOP_WINDOW)
+        {
+            crashes.pop_front();
+        }
+        crashes.push_back(now);
+        crashes.len()
+    }
+
+    fn take_restart(&self) -> Option<String> {
+        self.restart_reason.lock().ok().and_then(|mut r| r.take())
+    }
+
+    // 本次以 --port 传给后端的端口
+    fn launch_port(&self) -> Option<u16> {
+        self.port_override
+            .lock()
+            .ok()
+            .and_then(|port| *port)
+            .or(self.spec.fixed_port)
+    }
+
+    pub fn program(&self) -> Option<PathBuf> {
+        self.program.lock().ok().and_then(|p| p.clone())
+    }
+
+    fn set_program(&self, program: Option<PathBuf>) {
+        if let Ok(mut current) = self.program.lock() {
+            *current = program;
+        }
+    }
+
+    fn uses_socket(&self) -> bool {
+        self.spec
+            .env
+            .iter()
+            .any(|(key, _)| key == "NB_LISTEN_SOCKET")
+    }
+}
+
+pub fn validate(settings: &SidecarSettings) -> Result<(), String> {
+    if let Some(secs) = settings.startup_timeout_secs {
+        if secs == 0 || secs > MAX_STARTUP_TIMEOUT_SECS {
+            return Err(format!(
+                "sidecar.startup_timeout_secs must be between 1 and {}",
+                MAX_STARTUP_TIMEOUT_SECS
+            ));
+        }
+    }
+    validate_extra_args(&settings.extra_args)?;
+    crate::sidecar_env::validate(&settings.env)
+}
+
+fn validate_extra_args(args: &[String]) -> Result<(), String> {
+    if args.len() > MAX_EXTRA_ARGS {
+        return Err(format!(
+            "sidecar.extra_args allows at most {} arguments",
+            MAX_EXTRA_ARGS
+        ));
+    }
+    for arg in args {
+        if arg.is_empty() || arg.len() > MAX_EXTRA_ARG_LEN || arg.chars().any(char::is_control) {
+            return Err(format!(
+                "sidecar.extra_args has an invalid argument: {:?}",
+                arg
+            ));
+        }
+        let name = arg.split('=').next().unwrap_or(arg);
+        if RESERVED_ARGS.contains(&name) {
+            return Err(format!(
+                "sidecar.extra_args cannot set {}, it is managed by the app",
+                name
+            ));
+        }
+    }
+    Ok(())
+}
+
+// 写入日志时按 POSIX shell 的写法加引号，便于复制出来手动复现
+fn shell_quote(arg: &str) -> String {
+    let plain = arg
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "-_=.,:/+@%".contains(c));
+    if plain {
+        return arg.to_string();
+    }
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+fn startup_timeout(settings: &SidecarSettings) -> Duration {
+    settings
+        .startup_timeout_secs
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_STARTUP_TIMEOUT)
+}
+
+// 后端绑定失败时的输出（Go 的 net.Listen 错误与 main.go 的 Fatal 信息）
+fn is_bind_failure(line: &str) -> bool {
+    let line = line.to_ascii_lowercase();
+    [
+        "address already in use",
+        "only one usage of each socket address",
+        "could not find any available port",
+        "is not available",
+    ]
+    .iter()
+    .any(|pattern| line.contains(pattern))
+}
+
+// 由系统分配一个当前空闲的端口
+pub fn pick_free_port() -> Result<u16, String> {
+    std::net::TcpListener::bind((std::net::Ipv4Addr::LOCALHOST, 0))
+        .and_then(|listener| listener.local_addr())
+        .map(|addr| addr.port())
+        .map_err(|e| format!("find free port failed: {}", e))
+}
+
+// 后端默认监听 127.0.0.1，在同一地址上试绑一次；绑定成功立即释放
+fn check_port_free(port: u16) -> Result<(), String> {
+    std::net::TcpListener::bind((std::net::Ipv4Addr::LOCALHOST, port))
+        .map(drop)
+        .map_err(|e| {
+            format!(
+                "backend port {} is not available ({}); free it or change runtime.backend_port",
+                port, e
+            )
+        })
+}
+
+pub fn spawn(app: &tauri::AppHandle) -> Result<(), String> {
+    let state = app.state::<SupervisorState>();
+    let supervisor = &state.0;
+    let spec = &supervisor.spec;
+    let log_state = app.state::<crate::LogState>();
+    let shell = app.shell();
+
+    if let Some(port) = spec.external_port {
+        return Err(format!(
+            "backend is external (127.0.0.1:{}), sidecar is not started",
+            port
+        ));
+    }
+    if let Some(port) = spec.fixed_port {
+        if let Err(message) = check_port_free(port) {
+            log_state.log_app("ERROR", &message);
+            let _ = app.emit(
+                PORT_UNAVAILABLE_EVENT,
+                PortUnavailable {
+                    port,
+                    message: message.clone(),
+                },
+            );
+            return Err(message);
+        }
+    }
+
+    let updated = supervisor.program();
+    let program = match &updated {
+        Some(path) => Ok(path.clone()),
+        None => crate::sidecar::sidecar_path("server"),
+    };
+    let launch = program
+        .as_ref()
+        .ok()
+        .and_then(|program| crate::sandbox::launch(spec.settings.sandbox, program, &spec.writable));
+    let mut command = match (&launch, &updated) {
+        (Some(launch), _) => {
+            for note in &launch.notes {
+                log_state.log_app("INFO", &format!("sidecar sandbox: {}", note));
+            }
+            shell.command(&launch.program).args(&launch.args)
+        }
+        (None, Some(path)) => shell.command(path),
+        (None, None) => shell
+            .sidecar("server")
+            .map_err(|e| format!("resolve sidecar failed: {}", e))?,
+    };
+    if spec.clear_env {
+        command = command
+            .env_clear()
+            .envs(crate::safe_mode::passthrough_env());
+    }
+    // 安全模式下不应用 sidecar.env，用户的覆盖本身可能就是起不来的原因
+    let overrides = if spec.clear_env {
+        Default::default()
+    } else {
+        spec.settings.env.clone()
+    };
+    let mut env = vec![
+        ("TAURI_PLATFORM".to_string(), "macos".to_string()),
+        ("TAURI_FAMILY".to_string(), "unix".to_string()),
+    ];
+    env.extend(crate::sidecar_env::merged(&overrides));
+    env.extend(spec.env.iter().cloned());
+    crate::config::apply_kill_switch_env(app, &mut env);
+    let data_dir = spec.data_dir.to_string_lossy().to_string();
+    env.push(("NB_DATA_DIR".to_string(), data_dir));
+    env.push((
+        "NB_LOG_LEVEL".to_string(),
+        log_state.server_level().to_string(),
+    ));
+    if let Some(limit) = crate::resource_limits::go_mem_limit(&spec.settings) {
+        env.push(("GOMEMLIMIT".to_string(), limit));
+    }
+    for (key, value) in &env {
+        command = command.env(key, value);
+    }
+    // 上面的字符串只用于日志；数据目录按 OsStr 原样传，非 UTF-8 的路径不会被替换成 U+FFFD
+    command = command.env("NB_DATA_DIR", &spec.data_dir);
+    log_state.log_app(
+        "INFO",
+        &format!("sidecar env: {}", crate::sidecar_env::describe(&env)),
+    );
+    command = command.arg("--data-dir").arg(&spec.data_dir);
+    let launch_port = supervisor.launch_port();
+    if let Some(port) = launch_port {
+        command = command.args(["--port".to_string(), port.to_string()]);
+    }
+    // 安全模式下同 sidecar.env 一样不追加
+    if !spec.clear_env && !spec.settings.extra_args.is_empty() {
+        log_state.log_app(
+            "INFO",
+            &format!(
+                "sidecar extra args: {}",
+                spec.settings
+                    .extra_args
+                    .iter()
+                    .map(|arg| shell_quote(arg))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+        );
+        command = command.args(&spec.settings.extra_args);
+    }
+
+    println!("Attempting to spawn sidecar...");
+    log_state.log_app("INFO", "Attempting to spawn sidecar...");
+
+    let (mut rx, child) = command.spawn().map_err(|e| {
+        log_state.log_app("ERROR", &format!("Failed to spawn sidecar: {}", e));
+        format!("spawn failed: {}", e)
+    })?;
+    let pid = child.pid();
+    let generation = supervisor.generation.fetch_add(1, Ordering::SeqCst) + 1;
+
+    println!("Sidecar spawned with PID: {:?}", pid);
+    log_state.log_app("INFO", &format!("Sidecar spawned with PID: {:?}", pid));
+
+    let sandbox_state = app.state::<crate::sandbox::SandboxState>();
+    match crate::sandbox::confine(pid, &spec.settings) {
+        Ok(Some((confinement, notes))) => {
+            for note in notes {
+                log_state.log_app("INFO", &format!("sidecar sandbox: {}", note));
+            }
+            if let Ok(mut guard) = sandbox_state.0.lock() {
+                *guard = Some(confinement);
+            }
+        }
+        Ok(None) => {}
+        Err(e) => log_state.log_app("WARN", &format!("sidecar sandbox skipped: {}", e)),
+    }
+    let sidecar_state = app.state::<crate::SidecarState>().0.clone();
+    if let Ok(mut guard) = sidecar_state.lock() {
+        *guard = Some(child);
+    }
+    crate::priority::apply(app);
+    app.state::<crate::StatsState>().0.sidecar_started();
+    app.state::<crate::health::HealthState>()
+        .0
+        .set(app, crate::health::Status::Starting, None);
+    supervisor.mark_started();
+    crate::resource_limits::spawn_watchdog(app.clone(), pid, generation, spec.settings.clone());
+
+    let app_handle = app.clone();
+    let log_state = log_state.inner().clone();
+    let started = Instant::now();
+    // 是否已经宣布过监听地址；之前出现绑定失败的输出即视为端口冲突
+    let announced = Arc::new(AtomicBool::new(false));
+    let stderr_tail = Arc::new(Mutex::new(VecDeque::with_capacity(STDERR_TAIL)));
+    watch_startup(
+        app.clone(),
+        generation,
+        pid,
+        startup_timeout(&spec.settings),
+        announced.clone(),
+        stderr_tail.clone(),
+    );
+
+    tauri::async_runtime::spawn(async move {
+        // 本进程是否已经完成结构化握手
+        let mut handshaken = false;
+        let mut bind_failed = false;
+        let (mut stdout, mut stderr) = (LineBuffer::default(), LineBuffer::default());
+        while let Some(event) = rx.recv().await {
+            match event {
+                CommandEvent::Stdout(chunk) => {
+                    for out in stdout.push(&chunk) {
+                        println!("Sidecar STDOUT: {}", out);
+                        log_state.log_server("STDOUT", &out);
+                        if crate::backend_control::handle_output(&app_handle, &out)
+                            || crate::key_slots::handle_output(&app_handle, &out)
+                        {
+                            continue;
+                        }
+
+                        match crate::handshake::parse(&out, pid) {
+                            Some(Ok(handshake)) => {
+                                log_state.log_app(
+                                    "INFO",
+                                    &format!(
+                                        "sidecar handshake: version {}, pid {}, data dir {}",
+                                        handshake.version, handshake.pid, handshake.data_dir
+                                    ),
+                                );
+                                handshaken = true;
+                                announced.store(true, Ordering::SeqCst);
+                                apply_endpoint(&app_handle, handshake.endpoint());
+                                if let Ok(mut current) = app_handle
+                                    .state::<crate::handshake::HandshakeState>()
+                                    .0
+                                    .lock()
+                                {
+                                    *current = Some(handshake);
+                                }
+                            }
+                            Some(Err(e)) => {
+                                log_state
+                                    .log_app("WARN", &format!("ignored sidecar handshake: {}", e));
+                            }
+                            None if !handshaken => {
+                                if let Some(endpoint) = crate::handshake::parse_legacy(&out) {
+                                    announced.store(true, Ordering::SeqCst);
+                                    apply_endpoint(&app_handle, endpoint);
+                                }
+                            }
+                            None => {}
+                        }
+                        bind_failed |= !announced.load(Ordering::SeqCst) && is_bind_failure(&out);
+                    }
+                }
+                CommandEvent::Stderr(chunk) => {
+                    for err in stderr.push(&chunk) {
+                        eprintln!("Sidecar STDERR: {}", err);
+                        log_state.log_server("STDERR", &err);
+                        bind_failed |= !announced.load(Ordering::SeqCst) && is_bind_failure(&err);
+                        if let Ok(mut tail) = stderr_tail.lock() {
+                            if tail.len() == STDERR_TAIL {
+                                tail.pop_front();
+                            }
+                            tail.push_back(err);
+                        }
+                    }
+                }
+                CommandEvent::Error(err) => {
+                    eprintln!("Sidecar Error: {}", err);
+                    log_state.log_app("ERROR", &format!("Sidecar Error: {}", err));
+                }
+                CommandEvent::Terminated(status) => {
+                    if let Some(out) = stdout.finish() {
+                        log_state.log_server("STDOUT", &out);
+                    }
+                    if let Some(err) = stderr.finish() {
+                        bind_failed |= !announced.load(Ordering::SeqCst) && is_bind_failure(&err);
+                        log_state.log_server("STDERR", &err);
+                        if let Ok(mut tail) = stderr_tail.lock() {
+                            if tail.len() == STDERR_TAIL {
+                                tail.pop_front();
+                            }
+                            tail.push_back(err);
+                        }
+                    }
+                    let early_exit = !announced.load(Ordering::SeqCst)
+                        && status.code.is_some_and(|code| code != 0)
+                        && started.elapsed() < EARLY_EXIT;
+                    let conflict = (bind_failed || early_exit).then_some(launch_port);
+                    let stderr: Vec<String> = stderr_tail
+                        .lock()
+                        .map(|tail| tail.iter().cloned().collect())
+                        .unwrap_or_default();
+                    on_terminated(&app_handle, generation, status, conflict, stderr);
+                }
+                _ => {}
+            }
+        }
+    });
+    Ok(())
+}
+
+// 超时仍未宣布监听地址时报告启动失败。进程不结束：它可能只是慢，之后照常宣布端口仍会被采用；
+// 用户选择重试时由 restart_backend 结束并重新拉起
+fn watch_startup(
+    app: tauri::AppHandle,
+    generation: u64,
+    pid: u32,
+    timeout: Duration,
+    announced: Arc<AtomicBool>,
+    stderr_tail: Arc<Mutex<VecDeque<String>>>,
+) {
+    std::thread::spawn(move || {
+        std::thread::sleep(timeout);
+        if announced.load(Ordering::SeqCst) {
+            return;
+        }
+        let state = app.state::<SupervisorState>();
+        // 已被替换或已退出的进程交给退出处理
+        let running = app
+            .state::<crate::SidecarState>()
+            .0
+            .lock()
+            .map(|child| child.is_some())
+            .unwrap_or(false);
+        if state.0.stopping.load(Ordering::SeqCst) || state.0.generation() != generation || !running
+        {
+            return;
+        }
+        let reason = format!(
+            "backend did not report its address within {}s",
+            timeout.as_secs()
+        );
+        let stderr: Vec<String> = stderr_tail
+            .lock()
+            .map(|tail| tail.iter().cloned().collect())
+            .unwrap_or_default();
+        let log_state = app.state::<crate::LogState>();
+        log_state.log_app("ERROR", &format!("{} (pid {})", reason, pid));
+        app.state::<crate::health::HealthState>().0.set(
+            &app,
+            crate::health::Status::Down,
+            Some(reason.clone()),
+        );
+        let _ = app.emit(
+            START_FAILED_EVENT,
+            StartFailed {
+                reason,
+                timeout_ms: timeout.as_millis() as u64,
+                pid,
+                stderr,
+                log_dir: log_state.dir.to_string_lossy().to_string(),
+            },
+        );
+    });
+}
+
+// 采用后端宣布的监听地址
+fn apply_endpoint(app: &tauri::AppHandle, endpoint: crate::handshake::Endpoint) {
+    let log_state = app.state::<crate::LogState>();
+    match endpoint {
+        crate::handshake::Endpoint::Port(port) => {
+            println!("Detected backend port: {}", port);
+            log_state.log_app("INFO", &format!("Detected backend port: {}", port));
+            if let Ok(mut p) = app.state::<crate::BackendPort>().0.lock() {
+                *p = port;
+            }
+            app.state::<SupervisorState>()
+                .0
+                .port_retries
+                .store(0, Ordering::SeqCst);
+            // 依然发送事件，以便正在运行的页面能立即感知；短时间内多次宣布会合并
+            app.state::<crate::port_events::PortAnnouncer>()
+                .announce(app, port);
+            crate::firewall::spawn_probe(app.clone(), port);
+        }
+        crate::handshake::Endpoint::Socket(path) => {
+            log_state.log_app(
+                "INFO",
+                &format!("Detected backend socket: {}", path.display()),
+            );
+            if let Ok(mut socket) = app.state::<crate::backend_bridge::BackendSocket>().0.lock() {
+                *socket = Some(path);
+            }
+            let _ = app.emit(
+                "backend-url",
+                format!("{}/api/v1", crate::backend_bridge::base_url()),
+            );
+        }
+    }
+    let generation = app.state::<SupervisorState>().0.generation();
+    crate::backend_ready::announce_when_ready(app.clone(), generation);
+}
+
+// 疑似端口冲突时换一个空闲端口重新拉起，返回是否已安排重启。
+// 配置了固定端口时尊重用户的选择，不擅自更换；socket 模式与端口无关
+fn retry_on_free_port(
+    app: &tauri::AppHandle,
+    generation: u64,
+    failed_port: Option<u16>,
+    code: Option<i32>,
+) -> bool {
+    let state = app.state::<SupervisorState>();
+    let supervisor = &state.0;
+    let log_state = app.state::<crate::LogState>();
+    if supervisor.uses_socket() || supervisor.spec.fixed_port.is_some() {
+        return false;
+    }
+    let attempt = supervisor.port_retries.fetch_add(1, Ordering::SeqCst) + 1;
+    if attempt > MAX_PORT_RETRIES {
+        log_state.log_app(
+            "WARN",
+            &format!(
+                "sidecar still failing after {} port changes, falling back to restart backoff",
+                MAX_PORT_RETRIES
+            ),
+        );
+        return false;
+    }
+    let port = match pick_free_port() {
+        Ok(port) => port,
+        Err(e) => {
+            log_state.log_app("ERROR", &e);
+            return false;
+        }
+    };
+    if let Ok(mut current) = supervisor.port_override.lock() {
+        *current = Some(port);
+    }
+    let reason = format!("sidecar could not bind its port (exit {:?})", code);
+    log_state.log_app(
+        "WARN",
+        &format!(
+            "{}; retrying on port {} (attempt {})",
+            reason, port, attempt
+        ),
+    );
+    if let Ok(mut current) = app.state::<crate::BackendPort>().0.lock() {
+        *current = 0;
+    }
+    let _ = app.emit(
+        PORT_CONFLICT_EVENT,
+        PortConflict {
+            failed_port: failed_port.unwrap_or(0),
+            port,
+            attempt,
+            reason,
+        },
+    );
+    schedule_respawn(app, generation, RESTART_DELAY);
+    true
+}
+
+fn schedule_respawn(app: &tauri::AppHandle, generation: u64, delay: Duration) {
+    let app = app.clone();
+    std::thread::spawn(move || {
+        std::thread::sleep(delay);
+        let state = app.state::<SupervisorState>();
+        // 等待期间可能已被手动重启拉起
+        if state.0.stopping.load(Ordering::SeqCst) || state.0.generation() != generation {
+            return;
+        }
+        if let Err(e) = spawn(&app) {
+            crate::report_sidecar_failure(&app, &e);
+        }
+    });
+}
+
+// conflict 为 Some 表示进程疑似因端口冲突退出，内含本次传入的 --port
+fn on_terminated(
+    app: &tauri::AppHandle,
+    generation: u64,
+    status: TerminatedPayload,
+    conflict: Option<Option<u16>>,
+    stderr: Vec<String>,
+) {
+    println!("Sidecar Terminated with status: {:?}", status);
+    let log_state = app.state::<crate::LogState>();
+    app.state::<crate::StatsState>()
+        .0
+        .sidecar_terminated(status.code);
+    log_state.log_app(
+        "WARN",
+        &format!("Sidecar Terminated with status: {:?}", status),
+    );
+    let state = app.state::<SupervisorState>();
+    let supervisor = &state.0;
+    supervisor.mark_exited(generation);
+    supervisor.mark_terminated(&status, supervisor.generation() == generation);
+    // 已被新进程替换时不动新进程的状态
+    if supervisor.generation() != generation {
+        return;
+    }
+    // 进程退出了，清空 handle
+    if let Ok(mut c) = app.state::<crate::SidecarState>().0.lock() {
+        *c = None;
+    }
+    if let Ok(mut confinement) = app.state::<crate::sandbox::SandboxState>().0.lock() {
+        *confinement = None;
+    }
+    app.state::<crate::health::HealthState>().0.set(
+        app,
+        crate::health::Status::Down,
+        Some(format!("process exited ({:?})", status.code)),
+    );
+    if supervisor.stopping.load(Ordering::SeqCst) {
+        return;
+    }
+    let pending = supervisor.take_restart();
+    if pending.is_none() {
+        if let Some(failed_port) = conflict {
+            if retry_on_free_port(app, generation, failed_port, status.code) {
+                return;
+            }
+        }
+    }
+    let (attempt, delay, reason) = match pending {
+        Some(reason) => (1, RESTART_DELAY, reason),
+        None => {
+            let exits = supervisor.record_crash();
+            if exits >= CRASH_LOOP_LIMIT {
+                report_crash_loop(app, exits, &status, stderr);
+                return;
+            }
+            let (attempt, delay) = supervisor.next_backoff();
+            (
+                attempt,
+                delay,
+                format!("exited unexpectedly ({:?})", status.code),
+            )
+        }
+    };
+    log_state.log_app(
+        "WARN",
+        &format!(
+            "restarting sidecar in {} ms (attempt {}): {}",
+            delay.as_millis(),
+            attempt,
+            reason
+        ),
+    );
+    // 旧端口已失效，等新进程宣布端口
+    if let Ok(mut port) = app.state::<crate::BackendPort>().0.lock() {
+        *port = 0;
+    }
+    let _ = app.emit(
+        RESTARTING_EVENT,
+        RestartNotice {
+            attempt,
+            delay_ms: delay.as_millis() as u64,
+            reason,
+        },
+    );
+    schedule_respawn(app, generation, delay);
+}
+
+// 不再重启，交给用户：发出 backend-crash-loop，前端据此弹出带 stderr 与“打开日志”的对话框，
+// 之后由 restart_backend 手动重启
+fn report_crash_loop(
+    app: &tauri::AppHandle,
+    exits: usize,
+    status: &TerminatedPayload,
+    stderr: Vec<String>,
+) {
+    let state = app.state::<SupervisorState>();
+    state.0.crash_loop.store(true, Ordering::SeqCst);
+    let log_state = app.state::<crate::LogState>();
+    let message = format!(
+        "backend exited {} times within {}s, automatic restart stopped",
+        exits,
+        CRASH_LOOP_WINDOW.as_secs()
+    );
+    log_state.log_app("ERROR", &message);
+    app.state::<crate::health::HealthState>().0.set(
+        app,
+        crate::health::Status::Down,
+        Some(message),
+    );
+    if let Ok(mut port) = app.state::<crate::BackendPort>().0.lock() {
+        *port = 0;
+    }
+    let _ = app.emit(
+        CRASH_LOOP_EVENT,
+        CrashLoop {
+            exits,
+            window_ms: CRASH_LOOP_WINDOW.as_millis() as u64,
+            last_code: status.code,
+            last_signal: status.signal,
+            stderr,
+            log_dir: log_state.dir.to_string_lossy().to_string(),
+            suggestion: "Open the log directory to see why the backend keeps exiting, \
+                         then restart it once the cause is fixed."
+                .to_string(),
+        },
+    );
+}
+
+// 手动重启：结束当前进程（已经退出、正在等待重启时直接拉起），等新进程端口就绪且健康检查通过后
+// 发出 backend-restarted。崩溃退避随之清零，用户主动重启不应继承之前的等待时长
+pub fn restart_now(app: &tauri::AppHandle, timeout: Duration) -> Result<RestartedNotice, String> {
+    let state = app.state::<SupervisorState>();
+    let supervisor = &state.0;
+    let Ok(_guard) = supervisor.manual.try_lock() else {
+        return Err("restart backend failed: a restart is already in progress".to_string());
+    };
+    if supervisor.stopping.load(Ordering::SeqCst) {
+        return Err("restart backend failed: app is shutting down".to_string());
+    }
+    if let Some(port) = supervisor.spec.external_port {
+        return Err(format!(
+            "restart backend failed: backend is external (127.0.0.1:{}), restart it yourself",
+            port
+        ));
+    }
+    let deadline = Instant::now() + timeout;
+    let before = supervisor.generation();
+    supervisor.reset_backoff();
+    app.state::<crate::LogState>()
+        .log_app("INFO", "Restarting sidecar on user request.");
+    let running = app
+        .state::<crate::SidecarState>()
+        .0
+        .lock()
+        .map(|child| child.is_some())
+        .unwrap_or(false);
+    if running {
+        supervisor.restart(app, before, "restart requested by user");
+    } else {
+        spawn(app).map_err(|e| format!("restart backend failed: {}", e))?;
+    }
+
+    // 等新一代进程启动并登记 handle，之后才能交给就绪检查
+    loop {
+        let replaced = supervisor.generation() > before
+            && app
+                .state::<crate::SidecarState>()
+                .0
+                .lock()
+                .map(|child| child.is_some())
+                .unwrap_or(false);
+        if replaced {
+            break;
+        }
+        if Instant::now() >= deadline {
+            return Err(format!(
+                "restart backend failed: new process not started after {} ms",
+                timeout.as_millis()
+            ));
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    let ready = crate::backend_ready::wait(app, Some(remaining.as_millis() as u64))
+        .map_err(|e| format!("restart backend failed: {} ({})", e.message, e.code))?;
+    let notice = RestartedNotice {
+        port: ready.port,
+        base_url: ready.base_url,
+        generation: supervisor.generation(),
+    };
+    app.state::<crate::LogState>().log_app(
+        "INFO",
+        &format!("Sidecar restarted on port {}", notice.port),
+    );
+    let _ = app.emit(RESTARTED_EVENT, notice.clone());
+    Ok(notice)
+}
+
+// 后端更新安装或回滚后不重启整个应用：换上新的可执行文件（None 为包内版本），按手动重启的流程结束旧进程、
+// 拉起新进程，新进程宣布端口后照常发出 backend-port 与 backend-restarted，前端据此重连。
+// 新版本起不来时换回原来的文件再启动一次，返回的错误里说明已回退
+pub fn hot_swap(
+    app: &tauri::AppHandle,
+    program: Option<PathBuf>,
+    timeout: Duration,
+) -> Result<RestartedNotice, String> {
+    let state = app.state::<SupervisorState>();
+    let supervisor = &state.0;
+    let previous = supervisor.program();
+    if previous == program {
+        return Err("hot swap failed: this backend is already running".to_string());
+    }
+    let log_state = app.state::<crate::LogState>();
+    let describe = |program: &Option<PathBuf>| {
+        program
+            .as_ref()
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|| "bundled sidecar".to_string())
+    };
+    log_state.log_app(
+        "INFO",
+        &format!(
+            "Hot-swapping sidecar: {} -> {}",
+            describe(&previous),
+            describe(&program)
+        ),
+    );
+    supervisor.set_program(program);
+    let error = match restart_now(app, timeout) {
+        Ok(notice) => return Ok(notice),
+        Err(e) => e,
+    };
+    log_state.log_app(
+        "ERROR",
+        &format!(
+            "hot swap failed, reverting to {}: {}",
+            describe(&previous),
+            error
+        ),
+    );
+    supervisor.set_program(previous);
+    match restart_now(app, timeout) {
+        Ok(_) => Err(format!(
+            "hot swap failed, reverted to the previous backend: {}",
+            error
+        )),
+        Err(e) => Err(format!(
+            "hot swap failed: {}; reverting also failed: {}",
+            error, e
+        )),
+    }
+}
+
+// 应用退出：先请后端自行退出，超时未退出再强制结束。返回是否在超时前正常退出
+pub fn shutdown(app: &tauri::AppHandle, timeout: Duration) -> bool {
+    let log_state = app.state::<crate::LogState>();
+    let Some(state) = app.try_state::<SupervisorState>() else {
+        return true;
+    };
+    let supervisor = &state.0;
+    supervisor.stop();
+    let child = app
+        .state::<crate::SidecarState>()
+        .0
+        .lock()
+        .ok()
+        .and_then(|mut guard| guard.take());
+    let Some(child) = child else {
+        return true;
+    };
+    let generation = supervisor.generation();
+    log_state.log_app("INFO", "Requesting sidecar shutdown on app exit.");
+    if supervisor.stop_gracefully(app, child, generation, timeout) {
+        return true;
+    }
+    // 沙箱的 Job 句柄关闭时也会结束进程
+    if let Ok(mut confinement) = app.state::<crate::sandbox::SandboxState>().0.lock() {
+        *confinement = None;
+    }
+    false
+}
+
+// Unix 上发 SIGTERM；Windows 没有对应的信号，关闭标准输入即是退出请求
+#[cfg(not(windows))]
+pub fn terminate(pid: u32) -> Result<(), String> {
+    let status = std::process::Command::new("kill")
+        .args(["-TERM", &pid.to_string()])
+        .status()
+        .map_err(|e| format!("run kill failed: {}", e))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("kill exited with {}", status))
+    }
+}
+
+#[cfg(windows)]
+pub fn terminate(_pid: u32) -> Result<(), String> {
+    Ok(())
+}
+
+pub fn force_kill(pid: u32) -> Result<(), String> {
+    #[cfg(windows)]
+    let mut command = {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+        let mut command = std::process::Command::new("taskkill");
+        command
+            .args(["/F", "/T", "/PID", &pid.to_string()])
+            .creation_flags(CREATE_NO_WINDOW);
+        command
+    };
+    #[cfg(not(windows))]
+    let mut command = {
+        let mut command = std::process::Command::new("kill");
+        command.args(["-KILL", &pid.to_string()]);
+        command
+    };
+    let status = command
+        .status()
+        .map_err(|e| format!("run kill failed: {}", e))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("kill exited with {}", status))
+    }
+}
@@ -0,0 +1,348 @@
+use std::sync::Mutex;
+
+use cosmic_text::{
+    Align, Attrs, Buffer, Color, Family, FontSystem, Metrics, Shaping, Style, SwashCache, Weight,
+    Wrap,
+};
+use image::{Rgba, RgbaImage};
+
+const MAX_TEXT_CHARS: usize = 2000;
+const MAX_STROKE: f32 = 64.0;
+
+// 字形排版（cosmic-text：双向文本、连字、字体回退）与光栅化（swash：含彩色 emoji）都比较重，
+// 首次调用时建好，之后复用
+pub struct TextRenderer {
+    font_system: FontSystem,
+    cache: SwashCache,
+}
+
+#[derive(Default)]
+pub struct TextState(pub Mutex<Option<TextRenderer>>);
+
+impl TextRenderer {
+    pub fn new(db: &fontdb::Database) -> Self {
+        let locale = sys_locale();
+        Self {
+            font_system: FontSystem::new_with_locale_and_db(locale, db.clone()),
+            cache: SwashCache::new(),
+        }
+    }
+}
+
+fn sys_locale() -> String {
+    std::env::var("LANG")
+        .ok()
+        .and_then(|v| v.split('.').next().map(|s| s.replace('_', "-")))
+        .filter(|v| !v.is_empty() && v != "C" && v != "POSIX")
+        .unwrap_or_else(|| "zh-CN".to_string())
+}
+
+// (x, y) 对齐到文字块的哪个点；水平方向同时决定多行文字的对齐方式
+#[derive(Clone, Copy, Debug, Default, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Anchor {
+    #[default]
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    pub fn factors(self) -> (f32, f32) {
+        match self {
+            Anchor::TopLeft => (0.0, 0.0),
+            Anchor::Top => (0.5, 0.0),
+            Anchor::TopRight => (1.0, 0.0),
+            Anchor::Left => (0.0, 0.5),
+            Anchor::Center => (0.5, 0.5),
+            Anchor::Right => (1.0, 0.5),
+            Anchor::BottomLeft => (0.0, 1.0),
+            Anchor::Bottom => (0.5, 1.0),
+            Anchor::BottomRight => (1.0, 1.0),
+        }
+    }
+
+    fn align(self) -> Align {
+        match self.factors().0 {
+            h if h < 0.25 => Align::Left,
+            h if h > 0.75 => Align::Right,
+            _ => Align::Center,
+        }
+    }
+}
+
+// 坐标为原图像素
+#[derive(Clone, Copy, Debug, serde::Deserialize)]
+pub struct TextPos {
+    pub x: f32,
+    pub y: f32,
+    #[serde(default)]
+    pub anchor: Anchor,
+}
+
+#[derive(Clone, Debug, Default, serde::Deserialize)]
+#[serde(default)]
+pub struct TextOptions {
+    // 100~900，默认 400
+    pub weight: Option<u16>,
+    pub italic: bool,
+    // 超过该宽度自动换行
+    pub max_width: Option<f32>,
+    // 行高倍数，默认 1.2
+    pub line_height: Option<f32>,
+    // 描边（表情包常用的黑边白字）
+    pub stroke_color: Option<String>,
+    pub stroke_width: Option<f32>,
+}
+
+// #rgb / #rrggbb / #rrggbbaa
+pub fn parse_color(value: &str) -> Result<[u8; 4], String> {
+    let hex = value.trim().trim_start_matches('#');
+    let expanded: String = if hex.len() == 3 {
+        hex.chars().flat_map(|c| [c, c]).collect()
+    } else {
+        hex.to_string()
+    };
+    if !(expanded.len() == 6 || expanded.len() == 8) || !expanded.is_ascii() {
+        return Err(format!("invalid color: {}", value));
+    }
+    let channel = |i: usize| {
+        u8::from_str_radix(&expanded[i..i + 2], 16).map_err(|_| format!("invalid color: {}", value))
+    };
+    let alpha = if expanded.len() == 8 {
+        channel(6)?
+    } else {
+        255
+    };
+    Ok([channel(0)?, channel(2)?, channel(4)?, alpha])
+}
+
+// 前端传的是族名，大小写不敏感匹配已安装字体，找不到直接报错而不是悄悄回退
+fn resolve_family(db: &fontdb::Database, font: &str) -> Result<String, String> {
+    let wanted = font.trim();
+    db.faces()
+        .flat_map(|face| face.families.iter())
+        .find(|(name, _)| name.eq_ignore_ascii_case(wanted))
+        .map(|(name, _)| name.clone())
+        .ok_or_else(|| format!("font not found: {}", wanted))
+}
+
+// 源在上的 alpha 合成（目标可以是半透明）
+fn blend(dst: &mut Rgba<u8>, src: [u8; 4], coverage: f32) {
+    let sa = (src[3] as f32 / 255.0) * coverage.clamp(0.0, 1.0);
+    if sa <= 0.0 {
+        return;
+    }
+    let da = dst[3] as f32 / 255.0;
+    let out_a = sa + da * (1.0 - sa);
+    for c in 0..3 {
+        let value = (src[c] as f32 * sa + dst[c] as f32 * da * (1.0 - sa)) / out_a;
+        dst[c] = value.round().clamp(0.0, 255.0) as u8;
+    }
+    dst[3] = (out_a * 255.0).round().clamp(0.0, 255.0) as u8;
+}
+
+// 描边：对文字 alpha 做圆形膨胀
+fn dilate(alpha: &[f32], width: usize, height: usize, radius: f32) -> Vec<f32> {
+    let r = radius.ceil() as i64;
+    let offsets: Vec<(i64, i64, f32)> = (-r..=r)
+        .flat_map(|dy| (-r..=r).map(move |dx| (dx, dy)))
+        .filter_map(|(dx, dy)| {
+            let dist = ((dx * dx + dy * dy) as f32).sqrt();
+            // 边缘一像素做线性过渡，描边外沿不起锯齿
+            let weight = (radius + 0.5 - dist).clamp(0.0, 1.0);
+            (weight > 0.0).then_some((dx, dy, weight))
+        })
+        .collect();
+    let mut out = vec![0.0f32; alpha.len()];
+    for y in 0..height as i64 {
+        for x in 0..width as i64 {
+            let mut best = 0.0f32;
+            for (dx, dy, weight) in &offsets {
+                let (sx, sy) = (x + dx, y + dy);
+                if sx < 0 || sy < 0 || sx >= width as i64 || sy >= height as i64 {
+                    continue;
+                }
+                let a = alpha[sy as usize * width + sx as usize] * weight;
+                if a > best {
+                    best = a;
+                    if best >= 1.0 {
+                        break;
+                    }
+                }
+            }
+            out[y as usize * width + x as usize] = best;
+        }
+    }
+    out
+}
+
+// 排版并收紧到最长一行，返回 (buffer, 宽, 高)
+fn layout(
+    renderer: &mut TextRenderer,
+    text: &str,
+    font: Option<&str>,
+    size: f32,
+    align: Align,
+    options: &TextOptions,
+) -> Result<(Buffer, f32, f32), String> {
+    let text = text.trim_end();
+    if text.trim().is_empty() {
+        return Err("text is empty".to_string());
+    }
+    if text.chars().count() > MAX_TEXT_CHARS {
+        return Err(format!("text too long (max {} chars)", MAX_TEXT_CHARS));
+    }
+    if !(size.is_finite() && (4.0..=2000.0).contains(&size)) {
+        return Err(format!("invalid size: {}", size));
+    }
+    let line_height = options.line_height.unwrap_or(1.2);
+    if !(line_height.is_finite() && (0.5..=4.0).contains(&line_height)) {
+        return Err(format!("invalid line_height: {}", line_height));
+    }
+
+    let family = match font {
+        Some(f) if !f.trim().is_empty() => Some(resolve_family(renderer.font_system.db(), f)?),
+        _ => None,
+    };
+    let mut attrs = Attrs::new()
+        .family(
+            family
+                .as_deref()
+                .map(Family::Name)
+                .unwrap_or(Family::SansSerif),
+        )
+        .weight(Weight(options.weight.unwrap_or(400).clamp(100, 900)));
+    if options.italic {
+        attrs = attrs.style(Style::Italic);
+    }
+
+    let fs = &mut renderer.font_system;
+    let mut buffer = Buffer::new(fs, Metrics::new(size, size * line_height));
+    match options.max_width {
+        Some(w) if w.is_finite() && w > 0.0 => {
+            buffer.set_wrap(Wrap::WordOrGlyph);
+            buffer.set_size(Some(w), None);
+        }
+        _ => {
+            buffer.set_wrap(Wrap::None);
+            buffer.set_size(None, None);
+        }
+    }
+    buffer.set_text(text, &attrs, Shaping::Advanced, Some(align));
+    buffer.shape_until_scroll(fs, false);
+
+    let measure = |buffer: &Buffer| {
+        buffer.layout_runs().fold((0.0f32, 0.0f32), |(w, h), run| {
+            (w.max(run.line_w), h.max(run.line_top + run.line_height))
+        })
+    };
+    let (text_w, _) = measure(&buffer);
+    if text_w <= 0.0 {
+        return Err("nothing to draw".to_string());
+    }
+    // 居中/右对齐是相对排版宽度的，收紧到最长一行后各行才会彼此对齐
+    buffer.set_size(Some(text_w.ceil()), None);
+    buffer.shape_until_scroll(fs, false);
+    let (text_w, text_h) = measure(&buffer);
+    Ok((buffer, text_w, text_h))
+}
+
+// 只排版不绘制，返回文字块的宽高（像素）
+pub fn measure_text(
+    renderer: &mut TextRenderer,
+    text: &str,
+    font: Option<&str>,
+    size: f32,
+    options: &TextOptions,
+) -> Result<(f32, f32), String> {
+    let (_, w, h) = layout(renderer, text, font, size, Align::Left, options)?;
+    Ok((w, h))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn draw_text(
+    renderer: &mut TextRenderer,
+    img: &mut RgbaImage,
+    text: &str,
+    font: Option<&str>,
+    size: f32,
+    pos: TextPos,
+    color: [u8; 4],
+    options: &TextOptions,
+) -> Result<(), String> {
+    if !(pos.x.is_finite() && pos.y.is_finite()) {
+        return Err("invalid position".to_string());
+    }
+    let stroke = match (&options.stroke_color, options.stroke_width) {
+        (Some(c), Some(w)) if w > 0.0 => {
+            if !(w.is_finite() && w <= MAX_STROKE.min(size / 2.0)) {
+                return Err(format!("invalid stroke_width: {}", w));
+            }
+            Some((parse_color(c)?, w))
+        }
+        _ => None,
+    };
+    let (mut buffer, text_w, text_h) =
+        layout(renderer, text, font, size, pos.anchor.align(), options)?;
+    let fs = &mut renderer.font_system;
+
+    // 文字层：字形可能超出排版框（斜体、描边、emoji），四周留出余量
+    let pad = (stroke.map(|(_, w)| w).unwrap_or(0.0) + size * 0.3).ceil() as i64;
+    let layer_w = text_w.ceil() as i64 + pad * 2;
+    let layer_h = text_h.ceil() as i64 + pad * 2;
+    let mut layer = RgbaImage::new(layer_w as u32, layer_h as u32);
+    let (ax, ay) = pos.anchor.factors();
+    let origin_x = (pos.x - text_w * ax).round() as i64 - pad;
+    let origin_y = (pos.y - text_h * ay).round() as i64 - pad;
+
+    let base = Color::rgba(color[0], color[1], color[2], color[3]);
+    buffer.draw(fs, &mut renderer.cache, base, |x, y, w, h, c| {
+        for py in y..y + h as i32 {
+            for px in x..x + w as i32 {
+                let (lx, ly) = (px as i64 + pad, py as i64 + pad);
+                if lx < 0 || ly < 0 || lx >= layer_w || ly >= layer_h {
+                    continue;
+                }
+                let pixel = layer.get_pixel_mut(lx as u32, ly as u32);
+                blend(pixel, c.as_rgba(), 1.0);
+            }
+        }
+    });
+
+    let stroke_mask = stroke.map(|(_, width)| {
+        let alpha: Vec<f32> = layer.pixels().map(|p| p[3] as f32 / 255.0).collect();
+        dilate(&alpha, layer_w as usize, layer_h as usize, width)
+    });
+
+    let (img_w, img_h) = (img.width() as i64, img.height() as i64);
+    for ly in 0..layer_h {
+        let iy = origin_y + ly;
+        if iy < 0 || iy >= img_h {
+            continue;
+        }
+        for lx in 0..layer_w {
+            let ix = origin_x + lx;
+            if ix < 0 || ix >= img_w {
+                continue;
+            }
+            let dst = img.get_pixel_mut(ix as u32, iy as u32);
+            if let (Some((stroke_color, _)), Some(mask)) = (stroke, &stroke_mask) {
+                blend(
+                    dst,
+                    stroke_color,
+                    mask[ly as usize * layer_w as usize + lx as usize],
+                );
+            }
+            let src = layer.get_pixel(lx as u32, ly as u32).0;
+            blend(dst, src, 1.0);
+        }
+    }
+    Ok(())
+}
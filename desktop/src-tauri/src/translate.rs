@@ -0,0 +1,296 @@
+use std::time::Duration;
+
+use crate::library::{Library, ProviderConfigRecord};
+use crate::settings::TranslationSettings;
+
+// 提示词翻译（中英互译等）：前端只传文本与目标语言，请求由 Rust 发出。远程翻译复用后端 provider_configs
+// 里配置的 gemini / openai 密钥，密钥不经过 webview；配置了本机 LibreTranslate 兼容服务时走本地，不出网
+const PROVIDERS: [&str; 2] = ["gemini", "openai"];
+const DEFAULT_GEMINI_BASE: &str = "https://generativelanguage.googleapis.com";
+const DEFAULT_GEMINI_MODEL: &str = "gemini-2.5-flash";
+const DEFAULT_OPENAI_MODEL: &str = "gpt-4o-mini";
+const MAX_TEXT_CHARS: usize = 8000;
+const MAX_RESPONSE_BYTES: usize = 256 * 1024;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct Translation {
+    pub text: String,
+    pub target_lang: String,
+    // gemini / openai / local
+    pub provider: String,
+}
+
+fn local_url(settings: &TranslationSettings) -> Result<Option<tauri::Url>, String> {
+    let Some(endpoint) = settings
+        .local_endpoint
+        .as_deref()
+        .map(str::trim)
+        .filter(|e| !e.is_empty())
+    else {
+        return Ok(None);
+    };
+    let url = tauri::Url::parse(endpoint)
+        .map_err(|e| format!("translation.local_endpoint is invalid: {}", e))?;
+    let loopback = matches!(
+        url.host_str(),
+        Some("127.0.0.1") | Some("localhost") | Some("[::1]")
+    );
+    if !matches!(url.scheme(), "http" | "https") || !loopback {
+        return Err("translation.local_endpoint must be an http URL on this machine".to_string());
+    }
+    Ok(Some(url))
+}
+
+pub fn validate(settings: &TranslationSettings) -> Result<(), String> {
+    if let Some(provider) = settings.provider.as_deref().map(str::trim) {
+        if !PROVIDERS.contains(&provider) {
+            return Err(format!(
+                "translation.provider must be one of {}",
+                PROVIDERS.join("/")
+            ));
+        }
+    }
+    if settings
+        .model
+        .as_deref()
+        .is_some_and(|m| m.trim().is_empty())
+    {
+        return Err("translation.model is empty".to_string());
+    }
+    local_url(settings).map(drop)
+}
+
+// zh / en / zh-CN / pt-BR 这样的语言标签
+fn validate_lang(lang: &str) -> Result<String, String> {
+    let lang = lang.trim();
+    let valid = (2..=16).contains(&lang.len())
+        && lang.split('-').all(|part| {
+            !part.is_empty() && part.len() <= 8 && part.chars().all(|c| c.is_ascii_alphanumeric())
+        });
+    if !valid {
+        return Err(format!("invalid target language: {:?}", lang));
+    }
+    Ok(lang.to_string())
+}
+
+fn language_name(lang: &str) -> &str {
+    match lang.to_ascii_lowercase().as_str() {
+        "zh" | "zh-cn" | "zh-hans" => "Simplified Chinese",
+        "zh-tw" | "zh-hk" | "zh-hant" => "Traditional Chinese",
+        "en" | "en-us" | "en-gb" => "English",
+        "ja" => "Japanese",
+        "ko" => "Korean",
+        _ => lang,
+    }
+}
+
+fn instruction(text: &str, lang: &str) -> String {
+    format!(
+        "Translate the following image generation prompt into {}. Keep weights, brackets, \
+         LoRA tags, parameter syntax and proper nouns unchanged. Reply with the translation only.\n\n{}",
+        language_name(lang),
+        text
+    )
+}
+
+// 指定了 provider 时只用它；否则取第一个启用且填了密钥的
+fn pick_provider(
+    library: &Library,
+    settings: &TranslationSettings,
+) -> Result<ProviderConfigRecord, String> {
+    let conn = library.open()?;
+    let configs = library.provider_configs(&conn, true)?;
+    let wanted: Vec<&str> = match settings.provider.as_deref().map(str::trim) {
+        Some(provider) => vec![provider],
+        None => PROVIDERS.to_vec(),
+    };
+    wanted
+        .iter()
+        .find_map(|name| {
+            configs.iter().find(|c| {
+                c.provider_name == *name
+                    && c.enabled
+                    && c.api_key.as_deref().is_some_and(|k| !k.trim().is_empty())
+            })
+        })
+        .cloned()
+        .ok_or_else(|| {
+            format!(
+                "no {} provider with an API key is configured for translation",
+                wanted.join("/")
+            )
+        })
+}
+
+fn client() -> Result<reqwest::Client, String> {
+    reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .map_err(|e| format!("create http client failed: {}", e))
+}
+
+async fn post_json(
+    request: reqwest::RequestBuilder,
+    body: serde_json::Value,
+) -> Result<serde_json::Value, String> {
+    let body = serde_json::to_vec(&body).map_err(|e| format!("serialize request failed: {}", e))?;
+    let response = request
+        .header("Content-Type", "application/json")
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| format!("translate failed: {}", e))?;
+    let status = response.status();
+    let bytes = response
+        .bytes()
+        .await
+        .map_err(|e| format!("read translation failed: {}", e))?;
+    if bytes.len() > MAX_RESPONSE_BYTES {
+        return Err("translation response too large".to_string());
+    }
+    if !status.is_success() {
+        // 不把响应原文带回去，里面可能回显请求头
+        return Err(format!("translate failed: HTTP {}", status.as_u16()));
+    }
+    serde_json::from_slice(&bytes).map_err(|e| format!("parse translation failed: {}", e))
+}
+
+async fn translate_gemini(
+    config: &ProviderConfigRecord,
+    model: &str,
+    prompt: String,
+) -> Result<String, String> {
+    let base = match config.api_base.trim().trim_end_matches('/') {
+        "" => DEFAULT_GEMINI_BASE,
+        base => base,
+    };
+    let url = format!("{}/v1beta/models/{}:generateContent", base, model);
+    let request = client()?.post(url).header(
+        "x-goog-api-key",
+        config.api_key.as_deref().unwrap_or_default(),
+    );
+    let response = post_json(
+        request,
+        serde_json::json!({
+            "contents": [{ "role": "user", "parts": [{ "text": prompt }] }],
+            "generationConfig": { "temperature": 0.2 },
+        }),
+    )
+    .await?;
+    let parts = response["candidates"][0]["content"]["parts"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+    Ok(parts
+        .iter()
+        .filter_map(|part| part["text"].as_str())
+        .collect::<Vec<_>>()
+        .join(""))
+}
+
+// 与后端 NormalizeOpenAIBaseURL 一致：补齐或截断到 /v1
+fn openai_base(api_base: &str) -> String {
+    let mut base = api_base.trim().trim_end_matches('/').to_string();
+    if base.is_empty() {
+        return "https://api.openai.com/v1".to_string();
+    }
+    if let Some(index) = base.find("/chat/completions") {
+        base = base[..index].trim_end_matches('/').to_string();
+    }
+    if let Some(index) = base.find("/v1/") {
+        return format!("{}/v1", &base[..index]);
+    }
+    if base.ends_with("/v1") {
+        return base;
+    }
+    format!("{}/v1", base)
+}
+
+async fn translate_openai(
+    config: &ProviderConfigRecord,
+    model: &str,
+    prompt: String,
+) -> Result<String, String> {
+    let url = format!("{}/chat/completions", openai_base(&config.api_base));
+    let request = client()?.post(url).header(
+        "Authorization",
+        format!("Bearer {}", config.api_key.as_deref().unwrap_or_default()),
+    );
+    let response = post_json(
+        request,
+        serde_json::json!({
+            "model": model,
+            "temperature": 0.2,
+            "messages": [{ "role": "user", "content": prompt }],
+        }),
+    )
+    .await?;
+    Ok(response["choices"][0]["message"]["content"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string())
+}
+
+// LibreTranslate 的 /translate 接口
+async fn translate_local(url: tauri::Url, text: &str, lang: &str) -> Result<String, String> {
+    let response = post_json(
+        client()?.post(url),
+        serde_json::json!({
+            "q": text,
+            "source": "auto",
+            "target": lang.split('-').next().unwrap_or(lang),
+            "format": "text",
+        }),
+    )
+    .await?;
+    Ok(response["translatedText"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string())
+}
+
+pub async fn translate(
+    library: &Library,
+    settings: &TranslationSettings,
+    text: &str,
+    target_lang: &str,
+) -> Result<Translation, String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err("text is empty".to_string());
+    }
+    if text.chars().count() > MAX_TEXT_CHARS {
+        return Err(format!(
+            "text is too long (max {} characters)",
+            MAX_TEXT_CHARS
+        ));
+    }
+    let lang = validate_lang(target_lang)?;
+    let (translated, provider) = match local_url(settings)? {
+        Some(url) => (
+            translate_local(url, text, &lang).await?,
+            "local".to_string(),
+        ),
+        None => {
+            let config = pick_provider(library, settings)?;
+            let model = settings.model.as_deref().map(str::trim);
+            let prompt = instruction(text, &lang);
+            let translated = if config.provider_name == "gemini" {
+                translate_gemini(&config, model.unwrap_or(DEFAULT_GEMINI_MODEL), prompt).await?
+            } else {
+                translate_openai(&config, model.unwrap_or(DEFAULT_OPENAI_MODEL), prompt).await?
+            };
+            (translated, config.provider_name)
+        }
+    };
+    let translated = translated.trim().to_string();
+    if translated.is_empty() {
+        return Err("translation is empty".to_string());
+    }
+    Ok(Translation {
+        text: translated,
+        target_lang: lang,
+        provider,
+    })
+}
@@ -0,0 +1,391 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use sha2::{Digest, Sha256};
+
+use crate::collections::{self, SmartCollection};
+use crate::library::{ImageMeta, Library, TaskRecord};
+use crate::storage::{normalize_id, Storage};
+
+// 原生破坏性操作的撤销日志：批量重命名、按规则整理、批量改元数据、删除智能相册、原地去除定位。
+// 每次操作成功后记一条足以逆转它的记录，undo_last_native_action 从最近一条开始依次撤销。
+// 与回收站互补：回收站管图片本身，这里管文件位置与元数据这一层；原地改写文件内容时原件存进
+// 日志旁的 undo-backups/，记录被挤出或撤销后删除
+const MAX_ENTRIES: usize = 20;
+const BACKUP_DIR: &str = "undo-backups";
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct PathChange {
+    pub task_id: String,
+    pub old_local: String,
+    pub old_thumb: String,
+    pub new_local: String,
+    pub new_thumb: String,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum UndoAction {
+    MovePaths {
+        changes: Vec<PathChange>,
+    },
+    RestoreMeta {
+        previous: Vec<(String, ImageMeta)>,
+        sidecars: bool,
+    },
+    RestoreCollection {
+        collection: Box<SmartCollection>,
+    },
+    RestoreFile {
+        id: String,
+        // undo-backups/ 下的原件文件名
+        backup: String,
+        // 改写后内容的 SHA-256，撤销时内容已变就不覆盖
+        written: String,
+    },
+}
+
+impl UndoAction {
+    fn items(&self) -> usize {
+        match self {
+            UndoAction::MovePaths { changes } => changes.len(),
+            UndoAction::RestoreMeta { previous, .. } => previous.len(),
+            UndoAction::RestoreCollection { .. } | UndoAction::RestoreFile { .. } => 1,
+        }
+    }
+
+    fn backup(&self) -> Option<&str> {
+        match self {
+            UndoAction::RestoreFile { backup, .. } => Some(backup),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+struct UndoEntry {
+    id: String,
+    // rename / reorganize / metadata / delete_collection / strip_location
+    kind: String,
+    at: String,
+    action: UndoAction,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct UndoSummary {
+    pub id: String,
+    pub kind: String,
+    pub at: String,
+    pub items: usize,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct UndoReport {
+    pub kind: String,
+    pub at: String,
+    pub restored: usize,
+    // 实际恢复的任务 ID（删除智能相册时为相册 ID）
+    pub ids: Vec<String>,
+    // 操作之后又被改动过的条目不强行覆盖，列在这里
+    pub skipped: Vec<String>,
+    pub remaining: usize,
+}
+
+pub struct UndoJournal {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+pub struct UndoState(pub UndoJournal);
+
+// 对比操作前后的记录，得出路径发生变化的条目
+pub fn path_changes(before: &[TaskRecord], after: &[TaskRecord]) -> Vec<PathChange> {
+    let after: HashMap<&str, &TaskRecord> = after.iter().map(|t| (t.task_id.as_str(), t)).collect();
+    before
+        .iter()
+        .filter_map(|old| {
+            let new = after.get(old.task_id.as_str())?;
+            if new.local_path == old.local_path && new.thumbnail_path == old.thumbnail_path {
+                return None;
+            }
+            Some(PathChange {
+                task_id: old.task_id.clone(),
+                old_local: old.local_path.clone(),
+                old_thumb: old.thumbnail_path.clone(),
+                new_local: new.local_path.clone(),
+                new_thumb: new.thumbnail_path.clone(),
+            })
+        })
+        .collect()
+}
+
+impl UndoJournal {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn read(&self) -> Vec<UndoEntry> {
+        fs::read(&self.path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default()
+    }
+
+    fn backup_dir(&self) -> PathBuf {
+        self.path.with_file_name(BACKUP_DIR)
+    }
+
+    fn remove_backup(&self, action: &UndoAction) {
+        if let Some(name) = action.backup() {
+            let _ = fs::remove_file(self.backup_dir().join(name));
+        }
+    }
+
+    // 原地改写文件前调用：保存原件，返回可直接 record 的撤销记录
+    pub fn backup_file(
+        &self,
+        id: &str,
+        original: &[u8],
+        written: &[u8],
+    ) -> Result<UndoAction, String> {
+        let dir = self.backup_dir();
+        fs::create_dir_all(&dir).map_err(|e| format!("create undo backup dir failed: {}", e))?;
+        let backup = format!("{}.bin", crate::now_ms());
+        crate::storage::write_atomic(&dir.join(&backup), original)?;
+        Ok(UndoAction::RestoreFile {
+            id: id.to_string(),
+            backup,
+            written: hex::encode(Sha256::digest(written)),
+        })
+    }
+
+    fn write(&self, entries: &[UndoEntry]) -> Result<(), String> {
+        let bytes = serde_json::to_vec(entries)
+            .map_err(|e| format!("serialize undo journal failed: {}", e))?;
+        crate::storage::write_atomic(&self.path, &bytes)
+    }
+
+    pub fn record(&self, kind: &str, action: UndoAction) -> Result<(), String> {
+        if action.items() == 0 {
+            return Ok(());
+        }
+        let _guard = self
+            .lock
+            .lock()
+            .map_err(|_| "undo journal lock poisoned".to_string())?;
+        let mut entries = self.read();
+        entries.push(UndoEntry {
+            id: format!("undo-{}", crate::now_ms()),
+            kind: kind.to_string(),
+            at: chrono::Local::now().to_rfc3339(),
+            action,
+        });
+        let overflow = entries.len().saturating_sub(MAX_ENTRIES);
+        for entry in entries.drain(..overflow) {
+            self.remove_backup(&entry.action);
+        }
+        self.write(&entries)
+    }
+
+    // 最近的在前
+    pub fn list(&self) -> Vec<UndoSummary> {
+        self.read()
+            .into_iter()
+            .rev()
+            .map(|entry| UndoSummary {
+                items: entry.action.items(),
+                id: entry.id,
+                kind: entry.kind,
+                at: entry.at,
+            })
+            .collect()
+    }
+
+    // 撤销失败时记录保留在日志里，可以处理完冲突后再试
+    pub fn undo_last(
+        &self,
+        storage: &dyn Storage,
+        library: &Library,
+    ) -> Result<UndoReport, String> {
+        let _guard = self
+            .lock
+            .lock()
+            .map_err(|_| "undo journal lock poisoned".to_string())?;
+        let mut entries = self.read();
+        let entry = entries
+            .last()
+            .cloned()
+            .ok_or_else(|| "nothing to undo".to_string())?;
+        let (ids, skipped) = match &entry.action {
+            UndoAction::MovePaths { changes } => undo_moves(storage, library, changes)?,
+            UndoAction::RestoreMeta { previous, sidecars } => {
+                undo_meta(storage, library, previous, *sidecars)?
+            }
+            UndoAction::RestoreCollection { collection } => {
+                collections::restore(library, collection)?;
+                (vec![collection.id.clone()], Vec::new())
+            }
+            UndoAction::RestoreFile {
+                id,
+                backup,
+                written,
+            } => undo_file(storage, &self.backup_dir().join(backup), id, written)?,
+        };
+        entries.pop();
+        self.write(&entries)?;
+        self.remove_backup(&entry.action);
+        Ok(UndoReport {
+            kind: entry.kind,
+            at: entry.at,
+            restored: ids.len(),
+            ids,
+            skipped,
+            remaining: entries.len(),
+        })
+    }
+}
+
+// 同一批里可能有 A->B、B->C 的链式移动，先全部挪到临时名再落回原位，避免互相覆盖；
+// 任一步失败则把已挪动的文件恢复到撤销前的状态
+fn undo_moves(
+    storage: &dyn Storage,
+    library: &Library,
+    changes: &[PathChange],
+) -> Result<(Vec<String>, Vec<String>), String> {
+    let mut conn = library.open()?;
+    let ids: Vec<String> = changes.iter().map(|c| c.task_id.clone()).collect();
+    let current: HashMap<String, TaskRecord> = library
+        .get_tasks(&conn, &ids)?
+        .into_iter()
+        .map(|t| (t.task_id.clone(), t))
+        .collect();
+
+    let mut skipped = Vec::new();
+    let mut valid = Vec::new();
+    for change in changes {
+        match current.get(&change.task_id) {
+            Some(task)
+                if task.local_path == change.new_local
+                    && task.thumbnail_path == change.new_thumb =>
+            {
+                valid.push(change)
+            }
+            Some(_) => skipped.push(format!("{}: moved again since", change.task_id)),
+            None => skipped.push(format!("{}: record not found", change.task_id)),
+        }
+    }
+
+    let mut pairs: Vec<(String, String)> = Vec::new();
+    for change in &valid {
+        for (new, old) in [
+            (&change.new_local, &change.old_local),
+            (&change.new_thumb, &change.old_thumb),
+        ] {
+            if new == old {
+                continue;
+            }
+            if let (Ok(new), Ok(old)) = (normalize_id(new), normalize_id(old)) {
+                if storage.exists(&new) {
+                    pairs.push((new, old));
+                }
+            }
+        }
+    }
+
+    let mut moved: Vec<(String, String)> = Vec::new();
+    let rollback = |moved: &[(String, String)]| {
+        for (from, to) in moved.iter().rev() {
+            let _ = storage.rename(to, from);
+        }
+    };
+    let seed = crate::now_ms();
+    let result = (|| {
+        let mut staged = Vec::with_capacity(pairs.len());
+        for (i, (from, to)) in pairs.iter().enumerate() {
+            let dir = from.rsplit_once('/').map(|(dir, _)| dir).unwrap_or("");
+            let name = format!(".undo-{}-{}.tmp", seed, i);
+            let tmp = if dir.is_empty() {
+                name
+            } else {
+                format!("{}/{}", dir, name)
+            };
+            storage.rename(from, &tmp)?;
+            moved.push((from.clone(), tmp.clone()));
+            staged.push((tmp, to.clone()));
+        }
+        for (tmp, to) in &staged {
+            if storage.exists(to) {
+                return Err(format!("cannot restore {}: file already exists", to));
+            }
+            storage.rename(tmp, to)?;
+            moved.push((tmp.clone(), to.clone()));
+        }
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("begin transaction failed: {}", e))?;
+        for change in &valid {
+            library.update_paths(&tx, &change.task_id, &change.old_local, &change.old_thumb)?;
+        }
+        tx.commit()
+            .map_err(|e| format!("commit undo failed: {}", e))
+    })();
+    if let Err(e) = result {
+        rollback(&moved);
+        return Err(e);
+    }
+    Ok((valid.iter().map(|c| c.task_id.clone()).collect(), skipped))
+}
+
+// 改写之后文件又被改过（内容摘要不一致）时跳过，不覆盖用户后来的修改
+fn undo_file(
+    storage: &dyn Storage,
+    backup: &std::path::Path,
+    id: &str,
+    written: &str,
+) -> Result<(Vec<String>, Vec<String>), String> {
+    let current = storage.get(id)?;
+    if hex::encode(Sha256::digest(&current)) != written {
+        return Ok((Vec::new(), vec![format!("{}: modified since", id)]));
+    }
+    let original = fs::read(backup).map_err(|e| format!("read undo backup failed: {}", e))?;
+    storage.replace(id, &original)?;
+    Ok((vec![id.to_string()], Vec::new()))
+}
+
+fn undo_meta(
+    storage: &dyn Storage,
+    library: &Library,
+    previous: &[(String, ImageMeta)],
+    sidecars: bool,
+) -> Result<(Vec<String>, Vec<String>), String> {
+    let mut conn = library.open()?;
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("begin transaction failed: {}", e))?;
+    let mut skipped = Vec::new();
+    let mut restored = Vec::new();
+    for (task_id, meta) in previous {
+        let Some(task) = library.get_task(&tx, task_id)? else {
+            skipped.push(format!("{}: record not found", task_id));
+            continue;
+        };
+        library.put_meta(&tx, task_id, meta)?;
+        restored.push((task_id.clone(), task.local_path, meta));
+    }
+    tx.commit()
+        .map_err(|e| format!("commit undo failed: {}", e))?;
+    // 与批量修改一致：sidecar 尽力重写，失败只记录
+    if sidecars {
+        for (_, local_path, meta) in &restored {
+            if let Err(e) = crate::metadata::write_sidecar(storage, local_path, meta) {
+                skipped.push(e);
+            }
+        }
+    }
+    Ok((restored.into_iter().map(|(id, _, _)| id).collect(), skipped))
+}
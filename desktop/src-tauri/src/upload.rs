@@ -0,0 +1,262 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use tauri::http::Request;
+use tauri::Emitter;
+
+// 大体积参考图的分片上传：由原生层按块读文件、逐块 PUT 给后端 /api/v1/uploads，
+// WebView 不用把整个文件读进内存再一次性 fetch。网络抖动或后端短暂无响应时按退避重试，
+// 重试前先向后端确认已接收的字节数再续传；传入上次的 upload_id 可以跨调用续传。
+// 完成后返回后端本地路径，前端放进 generate-with-images 的 refPaths 即可
+pub const PROGRESS_EVENT: &str = "upload-progress";
+const DEFAULT_CHUNK_SIZE: u64 = 4 * 1024 * 1024;
+const MAX_CHUNK_SIZE: u64 = 16 * 1024 * 1024;
+// 连续失败次数上限，成功一个分片后清零
+const MAX_RETRIES: u32 = 6;
+const RETRY_BASE: Duration = Duration::from_millis(500);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(8);
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct UploadProgress {
+    pub upload_id: String,
+    pub source: String,
+    pub sent: u64,
+    pub total: u64,
+    pub retries: u32,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct UploadResult {
+    pub upload_id: String,
+    // 后端可直接读取的本地路径
+    pub path: String,
+    pub size: u64,
+    pub retries: u32,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct Envelope {
+    #[serde(default)]
+    message: String,
+    data: Option<SessionStatus>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct SessionStatus {
+    upload_id: String,
+    size: u64,
+    received: u64,
+    path: String,
+    #[serde(default)]
+    chunk_size: u64,
+}
+
+// 网络层失败可以重试；后端明确拒绝（4xx）时直接报错
+enum Failure {
+    Retry(String),
+    Fatal(String),
+}
+
+fn call(
+    app: &tauri::AppHandle,
+    method: &str,
+    target: &str,
+    content_type: &str,
+    body: Vec<u8>,
+) -> Result<(u16, Option<SessionStatus>), Failure> {
+    let request = Request::builder()
+        .method(method)
+        .uri(target)
+        .header("Content-Type", content_type)
+        .body(body)
+        .map_err(|e| Failure::Fatal(format!("build request failed: {}", e)))?;
+    let response = crate::backend_bridge::call(app, &request).map_err(Failure::Retry)?;
+    let status = response.status().as_u16();
+    if status >= 500 {
+        return Err(Failure::Retry(format!("backend returned {}", status)));
+    }
+    let envelope: Envelope = serde_json::from_slice(response.body())
+        .map_err(|e| Failure::Fatal(format!("parse upload response failed: {}", e)))?;
+    // 409 时 data 里是当前进度
+    if !(200..300).contains(&status) && status != 409 {
+        return Err(Failure::Fatal(format!(
+            "upload rejected ({}): {}",
+            status, envelope.message
+        )));
+    }
+    Ok((status, envelope.data))
+}
+
+fn session(
+    app: &tauri::AppHandle,
+    method: &str,
+    target: &str,
+    content_type: &str,
+    body: Vec<u8>,
+) -> Result<SessionStatus, Failure> {
+    let (_, data) = call(app, method, target, content_type, body)?;
+    data.ok_or_else(|| Failure::Fatal("upload response has no data".to_string()))
+}
+
+fn start(app: &tauri::AppHandle, filename: &str, size: u64) -> Result<SessionStatus, Failure> {
+    let body = serde_json::json!({ "filename": filename, "size": size }).to_string();
+    session(
+        app,
+        "POST",
+        "/api/v1/uploads",
+        "application/json",
+        body.into_bytes(),
+    )
+}
+
+fn status(app: &tauri::AppHandle, upload_id: &str) -> Result<SessionStatus, Failure> {
+    session(
+        app,
+        "GET",
+        &format!("/api/v1/uploads/{}", upload_id),
+        "application/json",
+        Vec::new(),
+    )
+}
+
+fn read_chunk(file: &mut File, offset: u64, len: u64) -> Result<Vec<u8>, String> {
+    file.seek(SeekFrom::Start(offset))
+        .map_err(|e| format!("seek upload source failed: {}", e))?;
+    let mut chunk = Vec::with_capacity(len as usize);
+    file.by_ref()
+        .take(len)
+        .read_to_end(&mut chunk)
+        .map_err(|e| format!("read upload source failed: {}", e))?;
+    if (chunk.len() as u64) < len {
+        return Err("upload source changed while uploading".to_string());
+    }
+    Ok(chunk)
+}
+
+fn retry_delay(failures: u32) -> Duration {
+    RETRY_BASE
+        .saturating_mul(1 << failures.saturating_sub(1).min(8))
+        .min(MAX_RETRY_DELAY)
+}
+
+fn valid_upload_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= 64 && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+pub fn upload(
+    app: &tauri::AppHandle,
+    source: &Path,
+    resume: Option<&str>,
+) -> Result<UploadResult, String> {
+    let mut file = File::open(crate::long_path::extend(source))
+        .map_err(|e| format!("open upload source failed: {}", e))?;
+    let size = file
+        .metadata()
+        .map_err(|e| format!("stat upload source failed: {}", e))?
+        .len();
+    if size == 0 {
+        return Err("upload source is empty".to_string());
+    }
+    let filename = source
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let mut retries = 0;
+    let mut failures = 0;
+    // 续传时先确认会话还在、大小一致；后端重启过会话就没了，重新开始
+    let resumed = match resume.filter(|id| valid_upload_id(id)) {
+        Some(id) => status(app, id).ok().filter(|s| s.size == size),
+        None => None,
+    };
+    let mut current = match resumed {
+        Some(current) => current,
+        None => loop {
+            match start(app, &filename, size) {
+                Ok(current) => break current,
+                Err(Failure::Fatal(e)) => return Err(e),
+                Err(Failure::Retry(e)) => {
+                    failures += 1;
+                    if failures > MAX_RETRIES {
+                        return Err(format!("start upload failed: {}", e));
+                    }
+                    retries += 1;
+                    thread::sleep(retry_delay(failures));
+                }
+            }
+        },
+    };
+    let upload_id = current.upload_id.clone();
+    let chunk_size = match current.chunk_size {
+        0 => DEFAULT_CHUNK_SIZE,
+        n => n.min(MAX_CHUNK_SIZE),
+    };
+    let source_label = source.to_string_lossy().to_string();
+    let progress = |sent: u64, retries: u32| {
+        let _ = app.emit(
+            PROGRESS_EVENT,
+            UploadProgress {
+                upload_id: upload_id.clone(),
+                source: source_label.clone(),
+                sent,
+                total: size,
+                retries,
+            },
+        );
+    };
+    progress(current.received, retries);
+
+    failures = 0;
+    while current.received < size {
+        let offset = current.received;
+        let chunk = read_chunk(&mut file, offset, chunk_size.min(size - offset))?;
+        let target = format!("/api/v1/uploads/{}?offset={}", upload_id, offset);
+        match call(app, "PUT", &target, "application/octet-stream", chunk) {
+            Ok((_, Some(next))) => {
+                // 409 说明两边进度不一致，以后端为准；进度没有前进按失败计，避免空转
+                if next.received == offset {
+                    failures += 1;
+                } else {
+                    failures = 0;
+                }
+                current = next;
+                progress(current.received, retries);
+            }
+            Ok((_, None)) => return Err("upload response has no data".to_string()),
+            Err(Failure::Fatal(e)) => return Err(e),
+            Err(Failure::Retry(e)) => {
+                failures += 1;
+                if failures > MAX_RETRIES {
+                    return Err(format!(
+                        "upload interrupted at {}/{} bytes: {} (retry with upload_id {} to resume)",
+                        current.received, size, e, upload_id
+                    ));
+                }
+                retries += 1;
+                thread::sleep(retry_delay(failures));
+                // 分片可能部分写入，续传前重新对齐进度
+                match status(app, &upload_id) {
+                    Ok(next) => current = next,
+                    Err(Failure::Fatal(e)) => return Err(e),
+                    Err(Failure::Retry(_)) => {}
+                }
+            }
+        }
+        if failures > MAX_RETRIES {
+            return Err(format!(
+                "upload stalled at {}/{} bytes",
+                current.received, size
+            ));
+        }
+    }
+
+    Ok(UploadResult {
+        upload_id,
+        path: current.path,
+        size,
+        retries,
+    })
+}
@@ -0,0 +1,205 @@
+use std::f32::consts::PI;
+
+use image::RgbaImage;
+use sha2::{Digest, Sha256};
+
+// 不可见水印：在亮度的 8x8 块 DCT 中频系数上做 QIM（量化索引调制）嵌入，
+// 固定长度的帧在全图块上循环重复，检测时对每一位做软判决投票。
+// 对无损保存、轻度调色/压缩有一定鲁棒性，但不抗裁剪与缩放
+pub const MAX_PAYLOAD_BYTES: usize = 32;
+
+const BLOCK: usize = 8;
+// 中频系数位置：避开直流和低频（肉眼敏感）与高频（易被压缩抹掉）
+const COEF_U: usize = 2;
+const COEF_V: usize = 3;
+const STEP: f32 = 24.0;
+const MAGIC: u16 = 0x4e42;
+const FRAME_BITS: usize = 16 + 8 + MAX_PAYLOAD_BYTES * 8 + 16;
+// 每一位至少重复这么多次才允许嵌入，否则小图上检测不可靠
+const MIN_REPEATS: usize = 3;
+// 平均软判决置信度下限；主要靠帧内 magic + 校验和判定，纹理规整的图片置信度本身也可能偏高
+const MIN_CONFIDENCE: f32 = 0.35;
+
+#[derive(serde::Serialize)]
+pub struct WatermarkDetection {
+    pub found: bool,
+    pub payload: Option<String>,
+    pub confidence: f32,
+}
+
+fn basis(u: usize, v: usize, x: usize, y: usize) -> f32 {
+    let alpha = |k: usize| {
+        if k == 0 {
+            (1.0 / BLOCK as f32).sqrt()
+        } else {
+            (2.0 / BLOCK as f32).sqrt()
+        }
+    };
+    alpha(u)
+        * alpha(v)
+        * (((2 * x + 1) as f32 * u as f32 * PI) / (2 * BLOCK) as f32).cos()
+        * (((2 * y + 1) as f32 * v as f32 * PI) / (2 * BLOCK) as f32).cos()
+}
+
+fn basis_table() -> [[f32; BLOCK]; BLOCK] {
+    let mut table = [[0.0; BLOCK]; BLOCK];
+    for (y, row) in table.iter_mut().enumerate() {
+        for (x, cell) in row.iter_mut().enumerate() {
+            *cell = basis(COEF_U, COEF_V, x, y);
+        }
+    }
+    table
+}
+
+fn luma(p: &image::Rgba<u8>) -> f32 {
+    0.299 * p[0] as f32 + 0.587 * p[1] as f32 + 0.114 * p[2] as f32
+}
+
+fn block_coefficient(img: &RgbaImage, table: &[[f32; BLOCK]; BLOCK], bx: usize, by: usize) -> f32 {
+    let mut sum = 0.0;
+    for (y, row) in table.iter().enumerate() {
+        for (x, weight) in row.iter().enumerate() {
+            let p = img.get_pixel((bx * BLOCK + x) as u32, (by * BLOCK + y) as u32);
+            sum += luma(p) * weight;
+        }
+    }
+    sum
+}
+
+fn checksum(data: &[u8]) -> u16 {
+    let digest = Sha256::digest(data);
+    u16::from_be_bytes([digest[0], digest[1]])
+}
+
+// 帧格式：magic(16) | len(8) | payload(32 字节，不足补 0) | checksum(16)
+fn encode_frame(payload: &[u8]) -> Vec<bool> {
+    let mut bytes = Vec::with_capacity(FRAME_BITS / 8);
+    bytes.extend_from_slice(&MAGIC.to_be_bytes());
+    bytes.push(payload.len() as u8);
+    let mut padded = [0u8; MAX_PAYLOAD_BYTES];
+    padded[..payload.len()].copy_from_slice(payload);
+    bytes.extend_from_slice(&padded);
+    let sum = checksum(&bytes);
+    bytes.extend_from_slice(&sum.to_be_bytes());
+
+    bytes
+        .iter()
+        .flat_map(|b| (0..8).rev().map(move |i| (b >> i) & 1 == 1))
+        .collect()
+}
+
+fn decode_frame(bits: &[bool]) -> Option<String> {
+    let bytes: Vec<u8> = bits
+        .chunks(8)
+        .map(|chunk| chunk.iter().fold(0u8, |acc, b| (acc << 1) | *b as u8))
+        .collect();
+    if u16::from_be_bytes([bytes[0], bytes[1]]) != MAGIC {
+        return None;
+    }
+    let len = bytes[2] as usize;
+    if len > MAX_PAYLOAD_BYTES {
+        return None;
+    }
+    let body_end = 3 + MAX_PAYLOAD_BYTES;
+    let expected = u16::from_be_bytes([bytes[body_end], bytes[body_end + 1]]);
+    if checksum(&bytes[..body_end]) != expected {
+        return None;
+    }
+    String::from_utf8(bytes[3..3 + len].to_vec()).ok()
+}
+
+fn block_grid(img: &RgbaImage) -> (usize, usize) {
+    (img.width() as usize / BLOCK, img.height() as usize / BLOCK)
+}
+
+pub fn embed(img: &mut RgbaImage, payload: &str) -> Result<(), String> {
+    let payload = payload.as_bytes();
+    if payload.is_empty() {
+        return Err("payload is empty".to_string());
+    }
+    if payload.len() > MAX_PAYLOAD_BYTES {
+        return Err(format!(
+            "payload too long: {} bytes (max {})",
+            payload.len(),
+            MAX_PAYLOAD_BYTES
+        ));
+    }
+
+    let (cols, rows) = block_grid(img);
+    if cols * rows < FRAME_BITS * MIN_REPEATS {
+        return Err("image too small for watermark".to_string());
+    }
+
+    let bits = encode_frame(payload);
+    let table = basis_table();
+    for by in 0..rows {
+        for bx in 0..cols {
+            let bit = bits[(by * cols + bx) % FRAME_BITS];
+            let coef = block_coefficient(img, &table, bx, by);
+
+            // 量化到最近的、奇偶性等于目标位的格点
+            let mut q = (coef / STEP).round();
+            if (q.rem_euclid(2.0) == 1.0) != bit {
+                q += if coef / STEP > q { 1.0 } else { -1.0 };
+            }
+            let delta = q * STEP - coef;
+
+            // 只改单个系数：逆变换就是 delta 乘以该基函数；RGB 同加即可让亮度变化 delta
+            for (y, row) in table.iter().enumerate() {
+                for (x, weight) in row.iter().enumerate() {
+                    let p = img.get_pixel_mut((bx * BLOCK + x) as u32, (by * BLOCK + y) as u32);
+                    let d = delta * weight;
+                    for c in 0..3 {
+                        p[c] = (p[c] as f32 + d).round().clamp(0.0, 255.0) as u8;
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn detect(img: &RgbaImage) -> WatermarkDetection {
+    let not_found = WatermarkDetection {
+        found: false,
+        payload: None,
+        confidence: 0.0,
+    };
+    let (cols, rows) = block_grid(img);
+    if cols * rows < FRAME_BITS {
+        return not_found;
+    }
+
+    // 软判决：系数离奇/偶格点的远近映射到 [-1, 1]，同一位的所有块累加投票
+    let table = basis_table();
+    let mut votes = vec![0.0f32; FRAME_BITS];
+    let mut counts = vec![0u32; FRAME_BITS];
+    for by in 0..rows {
+        for bx in 0..cols {
+            let index = (by * cols + bx) % FRAME_BITS;
+            let coef = block_coefficient(img, &table, bx, by);
+            votes[index] -= (coef / STEP * PI).cos();
+            counts[index] += 1;
+        }
+    }
+
+    let bits: Vec<bool> = votes.iter().map(|v| *v > 0.0).collect();
+    let confidence = votes
+        .iter()
+        .zip(&counts)
+        .map(|(v, n)| v.abs() / *n as f32)
+        .sum::<f32>()
+        / FRAME_BITS as f32;
+
+    match decode_frame(&bits) {
+        Some(payload) if confidence >= MIN_CONFIDENCE => WatermarkDetection {
+            found: true,
+            payload: Some(payload),
+            confidence,
+        },
+        _ => WatermarkDetection {
+            confidence,
+            ..not_found
+        },
+    }
+}
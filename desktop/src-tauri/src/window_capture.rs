@@ -0,0 +1,345 @@
+use crate::reference::{PreparedReference, UploadConstraints};
+
+// 截取其他应用的窗口作为参考图（如“对着 Figma 里的画板迭代”）：按 bundle id、进程名或窗口标题找到
+// 最前面的一个窗口，截成 PNG 后走参考图的校验与规范化，返回可直接上传的临时文件。
+// macOS 需要“屏幕录制”权限，未授权时先触发系统授权提示并打开对应的设置页
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct CapturedWindow {
+    // 窗口所属应用的名称
+    pub app: String,
+    pub title: String,
+    pub reference: PreparedReference,
+}
+
+#[cfg_attr(not(any(target_os = "macos", windows)), allow(dead_code))]
+struct Captured {
+    app: String,
+    title: String,
+    png: Vec<u8>,
+}
+
+// 标题按包含匹配，应用名/进程名按整词匹配，都不区分大小写
+#[cfg_attr(not(any(target_os = "macos", windows)), allow(dead_code))]
+fn title_matches(title: &str, query: &str) -> bool {
+    !title.is_empty() && title.to_lowercase().contains(query)
+}
+
+pub fn capture(target: &str, constraints: &UploadConstraints) -> Result<CapturedWindow, String> {
+    let query = target.trim();
+    if query.is_empty() {
+        return Err("capture window failed: target is empty".to_string());
+    }
+    let captured = platform::capture(query)?;
+    let stem = crate::naming::sanitize_stem(&format!("capture-{}", captured.app));
+    let reference = crate::reference::prepare(&captured.png, &stem, constraints)?;
+    Ok(CapturedWindow {
+        app: captured.app,
+        title: captured.title,
+        reference,
+    })
+}
+
+// 只要截图本身（如反馈附带的主窗口截图），不走参考图的规范化
+pub fn capture_png(target: &str) -> Result<Vec<u8>, String> {
+    let query = target.trim();
+    if query.is_empty() {
+        return Err("capture window failed: target is empty".to_string());
+    }
+    platform::capture(query).map(|captured| captured.png)
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::ffi::c_void;
+
+    use objc2::rc::Retained;
+    use objc2::runtime::AnyObject;
+    use objc2_app_kit::NSRunningApplication;
+    use objc2_foundation::{NSArray, NSDictionary, NSNumber, NSString};
+
+    use super::{title_matches, Captured};
+
+    const LIST_ON_SCREEN_ONLY: u32 = 1 << 0;
+    const LIST_EXCLUDE_DESKTOP: u32 = 1 << 4;
+    const SCREEN_RECORDING_PANE: &str =
+        "x-apple.systempreferences:com.apple.preference.security?Privacy_ScreenCapture";
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGWindowListCopyWindowInfo(option: u32, relative_to_window: u32) -> *mut c_void;
+        fn CGPreflightScreenCaptureAccess() -> bool;
+        fn CGRequestScreenCaptureAccess() -> bool;
+    }
+
+    type WindowInfo = NSDictionary<NSString, AnyObject>;
+
+    fn string(info: &WindowInfo, key: &str) -> String {
+        info.objectForKey(&NSString::from_str(key))
+            .and_then(|value| value.downcast_ref::<NSString>().map(|s| s.to_string()))
+            .unwrap_or_default()
+    }
+
+    fn number(info: &WindowInfo, key: &str) -> Option<i64> {
+        info.objectForKey(&NSString::from_str(key))
+            .and_then(|value| value.downcast_ref::<NSNumber>().map(|n| n.as_i64()))
+    }
+
+    // 首次请求会弹出系统授权框；之前拒绝过则只能去系统设置里打开，授权后需重启应用才生效
+    fn ensure_permission() -> Result<(), String> {
+        if unsafe { CGPreflightScreenCaptureAccess() } {
+            return Ok(());
+        }
+        if unsafe { CGRequestScreenCaptureAccess() } {
+            return Ok(());
+        }
+        let _ = std::process::Command::new("/usr/bin/open")
+            .arg(SCREEN_RECORDING_PANE)
+            .status();
+        Err("capture window failed: screen recording permission is required; enable it in System Settings > Privacy & Security > Screen Recording, then restart the app".to_string())
+    }
+
+    fn bundle_pids(query: &str) -> Vec<i64> {
+        let apps = NSRunningApplication::runningApplicationsWithBundleIdentifier(
+            &NSString::from_str(query),
+        );
+        (0..apps.count())
+            .map(|i| apps.objectAtIndex(i).processIdentifier() as i64)
+            .collect()
+    }
+
+    // 窗口列表按从前到后排列，取第一个匹配的普通窗口（layer 0）
+    fn find_window(query: &str) -> Result<(u32, String, String), String> {
+        let raw =
+            unsafe { CGWindowListCopyWindowInfo(LIST_ON_SCREEN_ONLY | LIST_EXCLUDE_DESKTOP, 0) };
+        let windows: Retained<NSArray<WindowInfo>> = unsafe { Retained::from_raw(raw.cast()) }
+            .ok_or_else(|| "capture window failed: window list unavailable".to_string())?;
+        let pids = bundle_pids(query);
+        let lower = query.to_lowercase();
+        let own_pid = std::process::id() as i64;
+        let mut by_title = None;
+        for i in 0..windows.count() {
+            let info = windows.objectAtIndex(i);
+            let pid = number(&info, "kCGWindowOwnerPID").unwrap_or(-1);
+            if pid == own_pid || number(&info, "kCGWindowLayer") != Some(0) {
+                continue;
+            }
+            let Some(id) = number(&info, "kCGWindowNumber") else {
+                continue;
+            };
+            let app = string(&info, "kCGWindowOwnerName");
+            let title = string(&info, "kCGWindowName");
+            if pids.contains(&pid) || app.to_lowercase() == lower {
+                return Ok((id as u32, app, title));
+            }
+            if by_title.is_none() && title_matches(&title, &lower) {
+                by_title = Some((id as u32, app, title));
+            }
+        }
+        by_title
+            .ok_or_else(|| format!("capture window failed: no visible window matches {}", query))
+    }
+
+    pub fn capture(query: &str) -> Result<Captured, String> {
+        ensure_permission()?;
+        let (id, app, title) = find_window(query)?;
+        let path = std::env::temp_dir().join(format!(
+            "nb-capture-{}-{}.png",
+            std::process::id(),
+            crate::now_ms()
+        ));
+        // -l 按窗口号截取，-o 去掉阴影，-x 不播放快门声
+        let status = std::process::Command::new("/usr/sbin/screencapture")
+            .args(["-x", "-o", "-t", "png", &format!("-l{}", id)])
+            .arg(&path)
+            .status()
+            .map_err(|e| format!("capture window failed: {}", e))?;
+        let png = std::fs::read(&path);
+        let _ = std::fs::remove_file(&path);
+        if !status.success() {
+            return Err(format!(
+                "capture window failed: screencapture exited ({:?})",
+                status.code()
+            ));
+        }
+        let png = png.map_err(|e| format!("capture window failed: {}", e))?;
+        Ok(Captured { app, title, png })
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use std::io::Cursor;
+    use std::path::Path;
+
+    use windows::core::{BOOL, PWSTR};
+    use windows::Win32::Foundation::{CloseHandle, HWND, LPARAM, RECT};
+    use windows::Win32::Graphics::Gdi::{
+        CreateCompatibleBitmap, CreateCompatibleDC, DeleteDC, DeleteObject, GetDC, GetDIBits,
+        ReleaseDC, SelectObject, BITMAPINFO, BITMAPINFOHEADER, BI_RGB, DIB_RGB_COLORS,
+    };
+    use windows::Win32::Storage::Xps::{PrintWindow, PRINT_WINDOW_FLAGS};
+    use windows::Win32::System::Threading::{
+        OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32,
+        PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+    use windows::Win32::UI::WindowsAndMessaging::{
+        EnumWindows, GetWindowRect, GetWindowTextW, GetWindowThreadProcessId, IsIconic,
+        IsWindowVisible,
+    };
+
+    use super::{title_matches, Captured};
+
+    // PrintWindow 的 PW_RENDERFULLCONTENT：连同 DirectComposition 内容（Chromium/Electron 应用）一起绘制
+    const RENDER_FULL_CONTENT: PRINT_WINDOW_FLAGS = PRINT_WINDOW_FLAGS(2);
+
+    struct Candidate {
+        hwnd: HWND,
+        pid: u32,
+        title: String,
+    }
+
+    unsafe extern "system" fn collect(hwnd: HWND, lparam: LPARAM) -> BOOL {
+        let windows = unsafe { &mut *(lparam.0 as *mut Vec<Candidate>) };
+        if unsafe { IsWindowVisible(hwnd) }.as_bool() {
+            let mut buf = [0u16; 512];
+            let len = unsafe { GetWindowTextW(hwnd, &mut buf) }.max(0) as usize;
+            let mut pid = 0u32;
+            unsafe { GetWindowThreadProcessId(hwnd, Some(&mut pid)) };
+            windows.push(Candidate {
+                hwnd,
+                pid,
+                title: String::from_utf16_lossy(&buf[..len]),
+            });
+        }
+        BOOL(1)
+    }
+
+    // 进程可执行文件名（不含扩展名），如 Figma
+    fn process_name(pid: u32) -> Option<String> {
+        unsafe {
+            let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid).ok()?;
+            let mut buf = [0u16; 1024];
+            let mut len = buf.len() as u32;
+            let result = QueryFullProcessImageNameW(
+                process,
+                PROCESS_NAME_WIN32,
+                PWSTR(buf.as_mut_ptr()),
+                &mut len,
+            );
+            let _ = CloseHandle(process);
+            result.ok()?;
+            let path = String::from_utf16_lossy(&buf[..len as usize]);
+            Path::new(&path)
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+        }
+    }
+
+    // EnumWindows 按 Z 序从前到后枚举，进程名匹配优先于标题匹配
+    fn find_window(query: &str) -> Result<(HWND, String, String), String> {
+        let mut windows: Vec<Candidate> = Vec::new();
+        unsafe {
+            EnumWindows(
+                Some(collect),
+                LPARAM(&mut windows as *mut Vec<Candidate> as isize),
+            )
+        }
+        .map_err(|e| format!("capture window failed: {}", e))?;
+        let lower = query.to_lowercase();
+        let exe = lower.strip_suffix(".exe").unwrap_or(&lower);
+        let own_pid = std::process::id();
+        let mut by_title = None;
+        for window in windows {
+            if window.pid == own_pid || window.title.is_empty() {
+                continue;
+            }
+            let app = process_name(window.pid).unwrap_or_default();
+            if app.to_lowercase() == exe {
+                return Ok((window.hwnd, app, window.title));
+            }
+            if by_title.is_none() && title_matches(&window.title, &lower) {
+                by_title = Some((window.hwnd, app, window.title));
+            }
+        }
+        by_title
+            .ok_or_else(|| format!("capture window failed: no visible window matches {}", query))
+    }
+
+    // 让窗口自己绘制到内存位图里，被遮挡的窗口也能截到；Windows 上截其他窗口不需要额外授权
+    fn print_window(hwnd: HWND) -> Result<Vec<u8>, String> {
+        if unsafe { IsIconic(hwnd) }.as_bool() {
+            return Err("capture window failed: window is minimized".to_string());
+        }
+        let mut rect = RECT::default();
+        unsafe { GetWindowRect(hwnd, &mut rect) }
+            .map_err(|e| format!("capture window failed: {}", e))?;
+        let (width, height) = (rect.right - rect.left, rect.bottom - rect.top);
+        if width <= 0 || height <= 0 {
+            return Err("capture window failed: window has no size".to_string());
+        }
+        let mut pixels = vec![0u8; width as usize * height as usize * 4];
+        let copied = unsafe {
+            let screen = GetDC(Some(hwnd));
+            let memory = CreateCompatibleDC(Some(screen));
+            let bitmap = CreateCompatibleBitmap(screen, width, height);
+            let previous = SelectObject(memory, bitmap.into());
+            let printed = PrintWindow(hwnd, memory, RENDER_FULL_CONTENT).as_bool();
+            let mut info = BITMAPINFO {
+                bmiHeader: BITMAPINFOHEADER {
+                    biSize: std::mem::size_of::<BITMAPINFOHEADER>() as u32,
+                    biWidth: width,
+                    // 负高度表示自上而下的行序
+                    biHeight: -height,
+                    biPlanes: 1,
+                    biBitCount: 32,
+                    biCompression: BI_RGB.0,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            let lines = GetDIBits(
+                memory,
+                bitmap,
+                0,
+                height as u32,
+                Some(pixels.as_mut_ptr().cast()),
+                &mut info,
+                DIB_RGB_COLORS,
+            );
+            SelectObject(memory, previous);
+            let _ = DeleteObject(bitmap.into());
+            let _ = DeleteDC(memory);
+            ReleaseDC(Some(hwnd), screen);
+            printed && lines == height
+        };
+        if !copied {
+            return Err("capture window failed: window could not be drawn".to_string());
+        }
+        // BGRA -> RGB，PrintWindow 不保证 alpha 通道有效，直接丢弃
+        let rgb: Vec<u8> = pixels
+            .chunks_exact(4)
+            .flat_map(|px| [px[2], px[1], px[0]])
+            .collect();
+        let img = image::RgbImage::from_raw(width as u32, height as u32, rgb)
+            .ok_or_else(|| "capture window failed: invalid bitmap".to_string())?;
+        let mut png = Vec::new();
+        img.write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+            .map_err(|e| format!("encode capture failed: {}", e))?;
+        Ok(png)
+    }
+
+    pub fn capture(query: &str) -> Result<Captured, String> {
+        let (hwnd, app, title) = find_window(query)?;
+        let png = print_window(hwnd)?;
+        Ok(Captured { app, title, png })
+    }
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+mod platform {
+    use super::Captured;
+
+    pub fn capture(_query: &str) -> Result<Captured, String> {
+        Err("capture window failed: not supported on this platform".to_string())
+    }
+}